@@ -0,0 +1,285 @@
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+const LIBRARY_EXTENSION: &str = "photoslibrary";
+/// Where Photos keeps imported files: `originals` since Photos 5 (macOS
+/// 10.15), `Masters` before.
+const ORIGINALS_FOLDERS: [&str; 2] = ["originals", "Masters"];
+const DATABASE: &str = "database/Photos.sqlite";
+/// Core Data counts seconds from 2001-01-01 UTC.
+const CORE_DATA_EPOCH: i64 = 978_307_200;
+
+/// One asset row of the library database.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AssetRow {
+    /// The folder below the originals folder, such as `A`.
+    pub directory: String,
+    pub filename: String,
+    /// The name the file had when it was imported.
+    pub original_name: Option<String>,
+    /// Core Data seconds.
+    pub date_created: Option<f64>,
+    pub favorite: bool,
+    pub hidden: bool,
+    pub trashed: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LibraryOriginal {
+    /// The file inside the library, for `read_exif` and the other readers.
+    pub path: String,
+    pub original_name: Option<String>,
+    /// When Photos says it was taken, in UTC.
+    pub created: Option<String>,
+    pub favorite: bool,
+    pub hidden: bool,
+    /// In Recently Deleted.
+    pub trashed: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PhotosLibrary {
+    pub originals: Vec<LibraryOriginal>,
+    /// Why the database could not be read, when it could not; the originals
+    /// are then listed from the folders alone.
+    pub database_error: Option<String>,
+}
+
+pub fn is_library(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case(LIBRARY_EXTENSION))
+        && path.is_dir()
+}
+
+/// The Photos library `path` lies in, if any.
+pub fn library_of(path: &Path) -> Option<&Path> {
+    path.ancestors().find(|ancestor| is_library(ancestor))
+}
+
+/// The folder of `library` that holds the originals, when `library` is a
+/// Photos library. Scans walk it instead of the bundle, whose thumbnails,
+/// previews and edit renders would otherwise show up as photos.
+pub fn originals_folder(library: &Path) -> Option<PathBuf> {
+    if !is_library(library) {
+        return None;
+    }
+    ORIGINALS_FOLDERS
+        .iter()
+        .map(|folder| library.join(folder))
+        .find(|folder| folder.is_dir())
+}
+
+/// `files`, found below `originals`, with what `rows` say about them.
+fn join(originals: &Path, files: Vec<PathBuf>, rows: Vec<AssetRow>) -> Vec<LibraryOriginal> {
+    let mut rows: HashMap<String, AssetRow> = rows
+        .into_iter()
+        .map(|row| {
+            let key = match row.directory.is_empty() {
+                true => row.filename.clone(),
+                false => format!("{}/{}", row.directory, row.filename),
+            };
+            (key.to_lowercase(), row)
+        })
+        .collect();
+    files
+        .into_iter()
+        .map(|file| {
+            let key = file
+                .strip_prefix(originals)
+                .unwrap_or(&file)
+                .to_string_lossy()
+                .replace('\\', "/")
+                .to_lowercase();
+            let row = rows.remove(&key).unwrap_or_default();
+            LibraryOriginal {
+                path: file.to_string_lossy().into_owned(),
+                original_name: row.original_name,
+                created: row
+                    .date_created
+                    .and_then(|seconds| {
+                        chrono::DateTime::from_timestamp(seconds as i64 + CORE_DATA_EPOCH, 0)
+                    })
+                    .map(|time| time.format("%Y-%m-%d %H:%M:%S UTC").to_string()),
+                favorite: row.favorite,
+                hidden: row.hidden,
+                trashed: row.trashed,
+            }
+        })
+        .collect()
+}
+
+/// Lists the originals of `library` with their import names, dates and
+/// flags from its database. Neither the files nor the database are written.
+pub fn list(library: &Path) -> Result<PhotosLibrary, String> {
+    let originals = originals_folder(library)
+        .ok_or_else(|| "The selected folder is not an Apple Photos library.".to_string())?;
+    let mut files = Vec::new();
+    crate::walk_files(&originals, &mut |path| {
+        if crate::is_supported_image(path) {
+            files.push(path.to_path_buf());
+        }
+    });
+    files.sort();
+    let (rows, database_error) = match library_db::assets(&library.join(DATABASE)) {
+        Ok(rows) => (rows, None),
+        Err(error) => (Vec::new(), Some(error)),
+    };
+    Ok(PhotosLibrary {
+        originals: join(&originals, files, rows),
+        database_error,
+    })
+}
+
+/// The SQLite side, compiled only with the `apple-photos` feature.
+#[cfg(feature = "apple-photos")]
+mod library_db {
+    use super::AssetRow;
+    use rusqlite::{Connection, OpenFlags, OptionalExtension};
+    use std::path::Path;
+
+    /// `ZASSET` since Photos 6, `ZGENERICASSET` in Photos 5.
+    const ASSET_TABLES: [&str; 2] = ["ZASSET", "ZGENERICASSET"];
+
+    fn describe(error: rusqlite::Error) -> String {
+        format!("Unable to read the Photos library database: {}", error)
+    }
+
+    pub fn assets(database: &Path) -> Result<Vec<AssetRow>, String> {
+        if !database.is_file() {
+            return Err("The library has no Photos.sqlite database.".to_string());
+        }
+        // `immutable` keeps SQLite from taking locks or touching the WAL
+        // while Photos has the library open; its newest changes may be missed.
+        let uri = format!(
+            "file:{}?immutable=1",
+            database
+                .to_string_lossy()
+                .replace('?', "%3f")
+                .replace('#', "%23")
+        );
+        let connection = Connection::open_with_flags(
+            uri,
+            OpenFlags::SQLITE_OPEN_READ_ONLY
+                | OpenFlags::SQLITE_OPEN_URI
+                | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )
+        .map_err(describe)?;
+
+        let mut table = None;
+        for candidate in ASSET_TABLES {
+            let found = connection
+                .query_row(
+                    "SELECT name FROM sqlite_master WHERE type = 'table' AND name = ?1",
+                    [candidate],
+                    |row| row.get::<_, String>(0),
+                )
+                .optional()
+                .map_err(describe)?;
+            if found.is_some() {
+                table = Some(candidate);
+                break;
+            }
+        }
+        let Some(table) = table else {
+            return Err(
+                "The library database is from a Photos version this app does not read.".to_string(),
+            );
+        };
+
+        let query = format!(
+            "SELECT a.ZDIRECTORY, a.ZFILENAME, attributes.ZORIGINALFILENAME, a.ZDATECREATED,
+                    a.ZFAVORITE, a.ZHIDDEN, a.ZTRASHEDSTATE
+             FROM {} a
+             LEFT JOIN ZADDITIONALASSETATTRIBUTES attributes ON attributes.ZASSET = a.Z_PK
+             WHERE a.ZFILENAME IS NOT NULL",
+            table
+        );
+        let mut statement = connection.prepare(&query).map_err(describe)?;
+        let rows = statement
+            .query_map((), |row| {
+                Ok(AssetRow {
+                    directory: row.get::<_, Option<String>>(0)?.unwrap_or_default(),
+                    filename: row.get(1)?,
+                    original_name: row.get(2)?,
+                    date_created: row.get(3)?,
+                    favorite: row.get::<_, Option<i64>>(4)?.unwrap_or(0) != 0,
+                    hidden: row.get::<_, Option<i64>>(5)?.unwrap_or(0) != 0,
+                    trashed: row.get::<_, Option<i64>>(6)?.unwrap_or(0) != 0,
+                })
+            })
+            .map_err(describe)?
+            .collect::<Result<_, _>>()
+            .map_err(describe)?;
+        Ok(rows)
+    }
+}
+
+#[cfg(not(feature = "apple-photos"))]
+mod library_db {
+    use super::AssetRow;
+    use std::path::Path;
+
+    pub fn assets(_database: &Path) -> Result<Vec<AssetRow>, String> {
+        Err(
+            "This build was made without Apple Photos database support (the `apple-photos` feature)."
+                .to_string(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn originals_are_listed_with_their_database_rows() {
+        let library = crate::fixtures::temp_path("photos.photoslibrary");
+        let originals = library.join("originals");
+        fs::create_dir_all(originals.join("A")).unwrap();
+        fs::create_dir_all(library.join("resources/derivatives")).unwrap();
+        fs::write(originals.join("A/ABCD-1234.jpeg"), b"").unwrap();
+        fs::write(originals.join("A/EFGH-5678.heic"), b"").unwrap();
+        fs::write(
+            library.join("resources/derivatives/ABCD-1234_1_105_c.jpeg"),
+            b"",
+        )
+        .unwrap();
+
+        let mut walked = Vec::new();
+        crate::walk_files(&library, &mut |path| walked.push(path.to_path_buf()));
+        let listed = list(&library).unwrap();
+        let inside = library_of(&originals.join("A/ABCD-1234.jpeg")).map(Path::to_path_buf);
+        fs::remove_dir_all(&library).ok();
+
+        assert_eq!(walked.len(), 2);
+        assert_eq!(inside, Some(library.clone()));
+        assert_eq!(listed.originals.len(), 2);
+
+        let files = vec![
+            originals.join("A/ABCD-1234.jpeg"),
+            originals.join("A/EFGH-5678.heic"),
+        ];
+        let rows = vec![AssetRow {
+            directory: "A".to_string(),
+            filename: "ABCD-1234.jpeg".to_string(),
+            original_name: Some("IMG_0042.JPG".to_string()),
+            date_created: Some(738_959_400.0),
+            favorite: true,
+            ..AssetRow::default()
+        }];
+        let joined = join(&originals, files, rows);
+        assert_eq!(joined[0].original_name.as_deref(), Some("IMG_0042.JPG"));
+        assert_eq!(
+            joined[0].created.as_deref(),
+            Some("2024-06-01 18:30:00 UTC")
+        );
+        assert!(joined[0].favorite);
+        assert_eq!(joined[1].original_name, None);
+    }
+}
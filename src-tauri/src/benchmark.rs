@@ -0,0 +1,163 @@
+use serde::Serialize;
+use std::{
+    path::Path,
+    time::{Duration, Instant},
+};
+use tracing::info_span;
+
+/// Parse cost for one file format.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FormatTiming {
+    pub format: String,
+    pub files: usize,
+    pub bytes: u64,
+    pub parse_ms: f64,
+}
+
+/// Where the time went while reading every supported image below a folder.
+/// Stage times are summed over all files; `total_ms` is wall-clock.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BenchmarkReport {
+    pub folder: String,
+    pub files: usize,
+    /// Files that could not be read or parsed; they still count towards `read_ms`.
+    pub failed: usize,
+    pub bytes_read: u64,
+    pub fields: usize,
+    pub read_ms: f64,
+    pub parse_ms: f64,
+    pub serialize_ms: f64,
+    pub total_ms: f64,
+    pub files_per_sec: f64,
+    pub megabytes_per_sec: f64,
+    pub formats: Vec<FormatTiming>,
+}
+
+/// Reads, parses and serializes every image below `folder` the way
+/// `read_exif` does, timing each stage. Each stage is also a `tracing` span
+/// (`benchmark.read`, `benchmark.parse`, `benchmark.serialize`) for a
+/// subscriber to record.
+pub fn run(folder: &Path) -> BenchmarkReport {
+    let mut report = BenchmarkReport {
+        folder: folder.to_string_lossy().into_owned(),
+        ..BenchmarkReport::default()
+    };
+    let (mut read, mut parse, mut serialize) = (Duration::ZERO, Duration::ZERO, Duration::ZERO);
+    let started = Instant::now();
+
+    crate::walk_files(folder, &mut |path| {
+        if !crate::is_supported_image(path) {
+            return;
+        }
+        report.files += 1;
+        let format = format_name(path);
+
+        let stage = Instant::now();
+        let data = info_span!("benchmark.read", path = %path.display())
+            .in_scope(|| crate::load_file_data(path));
+        read += stage.elapsed();
+        let Ok(data) = data else {
+            report.failed += 1;
+            return;
+        };
+        report.bytes_read += data.len() as u64;
+
+        let stage = Instant::now();
+        let fields = info_span!("benchmark.parse", format = %format)
+            .in_scope(|| crate::collect_fields_from_bytes(&data));
+        let parse_time = stage.elapsed();
+        parse += parse_time;
+        let entry = match report
+            .formats
+            .iter_mut()
+            .position(|entry| entry.format == format)
+        {
+            Some(index) => &mut report.formats[index],
+            None => {
+                report.formats.push(FormatTiming {
+                    format: format.clone(),
+                    ..FormatTiming::default()
+                });
+                report.formats.last_mut().expect("just pushed")
+            }
+        };
+        entry.files += 1;
+        entry.bytes += data.len() as u64;
+        entry.parse_ms += millis(parse_time);
+        let Ok(fields) = fields else {
+            report.failed += 1;
+            return;
+        };
+        report.fields += fields.len();
+
+        let stage = Instant::now();
+        info_span!("benchmark.serialize").in_scope(|| serde_json::to_vec(&fields).ok());
+        serialize += stage.elapsed();
+    });
+
+    let total = started.elapsed();
+    report.read_ms = millis(read);
+    report.parse_ms = millis(parse);
+    report.serialize_ms = millis(serialize);
+    report.total_ms = millis(total);
+    let seconds = total.as_secs_f64();
+    if seconds > 0.0 {
+        report.files_per_sec = report.files as f64 / seconds;
+        report.megabytes_per_sec = report.bytes_read as f64 / (1024.0 * 1024.0) / seconds;
+    }
+    report.formats.sort_by(|a, b| a.format.cmp(&b.format));
+    report
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+/// Lowercase format name for a file, folding extension aliases together.
+pub fn format_name(path: &Path) -> String {
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "jpg" => "jpeg".to_string(),
+        "tif" => "tiff".to_string(),
+        "heif" => "heic".to_string(),
+        _ => extension,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{ascii_field, jpeg_from_fields};
+    use exif::Tag;
+    use std::fs;
+
+    #[test]
+    fn counts_files_bytes_and_formats() {
+        let folder = crate::fixtures::temp_path("benchmark");
+        fs::create_dir_all(&folder).unwrap();
+        let jpeg = jpeg_from_fields(&[ascii_field(Tag::Model, "EOS R5")]);
+        fs::write(folder.join("a.jpg"), &jpeg).unwrap();
+        fs::write(folder.join("b.JPEG"), &jpeg).unwrap();
+        fs::write(folder.join("broken.png"), b"not a png").unwrap();
+        fs::write(folder.join("notes.txt"), b"skipped").unwrap();
+
+        let report = run(&folder);
+        fs::remove_dir_all(&folder).ok();
+
+        assert_eq!(report.files, 3);
+        assert_eq!(report.failed, 1);
+        assert_eq!(report.bytes_read, 2 * jpeg.len() as u64 + 9);
+        let formats: Vec<(&str, usize)> = report
+            .formats
+            .iter()
+            .map(|entry| (entry.format.as_str(), entry.files))
+            .collect();
+        assert_eq!(formats, [("jpeg", 2), ("png", 1)]);
+        assert!(report.fields >= 2);
+    }
+}
@@ -0,0 +1,329 @@
+use crate::{
+    capture::{self, CaptureTime},
+    jpeg_codec::{self, Raster},
+    merge::MergePolicy,
+    FieldSelection, FormatStyle,
+};
+use chrono::Duration;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Frames further apart than this start a new burst.
+const BURST_GAP_MS: i64 = 2000;
+/// Luma at or beyond these counts as clipped shadows and highlights.
+const SHADOW_CLIP: f64 = 5.0;
+const HIGHLIGHT_CLIP: f64 = 250.0;
+
+/// How much each metric counts towards a frame's score; 0 leaves it out.
+/// The aesthetic score only counts when asked for, since few files carry one.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PickCriteria {
+    pub sharpness: f64,
+    pub exposure: f64,
+    pub aesthetic: f64,
+}
+
+impl Default for PickCriteria {
+    fn default() -> Self {
+        PickCriteria {
+            sharpness: 1.0,
+            exposure: 1.0,
+            aesthetic: 0.0,
+        }
+    }
+}
+
+impl PickCriteria {
+    pub fn validate(&self) -> Result<(), String> {
+        let weights = [self.sharpness, self.exposure, self.aesthetic];
+        if weights
+            .iter()
+            .any(|weight| !weight.is_finite() || *weight < 0.0)
+            || weights.iter().all(|weight| *weight == 0.0)
+        {
+            return Err("Give at least one criterion a positive weight.".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FrameScore {
+    path: String,
+    /// 1 for the nominated keeper.
+    rank: usize,
+    /// Weighted mean of the metrics, each scaled to 0–1 within the burst.
+    score: f64,
+    /// DCT detail of a JPEG; higher is sharper.
+    sharpness: Option<f64>,
+    /// 1 for a mid-grey mean with nothing clipped, lower the further off.
+    exposure: Option<f64>,
+    aesthetic: Option<f64>,
+    captured: Option<String>,
+    error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BurstPick {
+    keeper: Option<String>,
+    /// Best first.
+    candidates: Vec<FrameScore>,
+}
+
+type Metric = fn(&FrameScore) -> Option<f64>;
+
+struct Frame {
+    score: FrameScore,
+    capture: Option<CaptureTime>,
+}
+
+/// How well `image` is exposed: its mean luma near mid-grey and few pixels
+/// clipped at either end.
+fn exposure(image: &Raster) -> f64 {
+    let luma: Vec<f64> = image
+        .pixels
+        .chunks_exact(3)
+        .map(|rgb| 0.299 * rgb[0] as f64 + 0.587 * rgb[1] as f64 + 0.114 * rgb[2] as f64)
+        .collect();
+    if luma.is_empty() {
+        return 0.0;
+    }
+    let mean = luma.iter().sum::<f64>() / luma.len() as f64;
+    let clipped = luma
+        .iter()
+        .filter(|&&value| value <= SHADOW_CLIP || value >= HIGHLIGHT_CLIP)
+        .count() as f64
+        / luma.len() as f64;
+    (1.0 - (mean / 255.0 - 0.5).abs() * 2.0).max(0.0) * (1.0 - clipped)
+}
+
+fn measure(path: &Path, criteria: &PickCriteria) -> Frame {
+    let mut score = FrameScore {
+        path: path.to_string_lossy().into_owned(),
+        rank: 0,
+        score: 0.0,
+        sharpness: None,
+        exposure: None,
+        aesthetic: None,
+        captured: None,
+        error: None,
+    };
+    let data = match crate::load_file_data(path) {
+        Ok(data) => data,
+        Err(error) => {
+            score.error = Some(error);
+            return Frame {
+                score,
+                capture: None,
+            };
+        }
+    };
+    let capture = crate::read_exif_container(&data)
+        .as_ref()
+        .and_then(capture::capture_time);
+    score.captured = capture.map(CaptureTime::to_iso8601);
+    if criteria.sharpness > 0.0 || criteria.exposure > 0.0 {
+        // Other formats simply go without these metrics.
+        if let Ok(preview) = jpeg_codec::preview(&data) {
+            score.sharpness = Some(preview.detail);
+            score.exposure = Some(exposure(&preview.image));
+        }
+    }
+    if criteria.aesthetic > 0.0 {
+        score.aesthetic = crate::read_file_fields(
+            path,
+            FormatStyle::default(),
+            MergePolicy::default(),
+            &FieldSelection::default(),
+        )
+        .ok()
+        .and_then(|fields| crate::extract_aesthetic_score(&fields));
+    }
+    Frame { score, capture }
+}
+
+/// Splits frames into bursts where consecutive capture times are more than
+/// [`BURST_GAP_MS`] apart. Frames without a capture time form one burst.
+fn group(frames: Vec<Frame>) -> Vec<Vec<Frame>> {
+    let (mut frames, undated): (Vec<Frame>, Vec<Frame>) = frames
+        .into_iter()
+        .partition(|frame| frame.capture.is_some());
+    frames.sort_by_key(|frame| frame.capture.map(CaptureTime::sort_key));
+    let mut bursts: Vec<Vec<Frame>> = Vec::new();
+    for frame in frames {
+        let joins = bursts
+            .last()
+            .and_then(|burst| burst.last())
+            .is_some_and(|last| {
+                let (Some(previous), Some(current)) = (last.capture, frame.capture) else {
+                    return false;
+                };
+                current.sort_key() - previous.sort_key() <= Duration::milliseconds(BURST_GAP_MS)
+            });
+        match bursts.last_mut() {
+            Some(burst) if joins => burst.push(frame),
+            _ => bursts.push(vec![frame]),
+        }
+    }
+    if !undated.is_empty() {
+        bursts.push(undated);
+    }
+    bursts
+}
+
+/// Scores `frames` of one burst and sorts them best first.
+fn rank(mut frames: Vec<Frame>, criteria: &PickCriteria) -> BurstPick {
+    let metrics: [(f64, Metric); 3] = [
+        (criteria.sharpness, |score| score.sharpness),
+        (criteria.exposure, |score| score.exposure),
+        (criteria.aesthetic, |score| score.aesthetic),
+    ];
+    let total: f64 = metrics.iter().map(|(weight, _)| weight).sum();
+    let mut scores = vec![0.0; frames.len()];
+    for (weight, metric) in metrics {
+        if weight == 0.0 {
+            continue;
+        }
+        let values: Vec<Option<f64>> = frames.iter().map(|frame| metric(&frame.score)).collect();
+        let present = values.iter().flatten();
+        let (low, high) = present
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(low, high), &value| {
+                (low.min(value), high.max(value))
+            });
+        for (score, value) in scores.iter_mut().zip(&values) {
+            // A frame missing the metric gets nothing for it.
+            let scaled = value.map_or(0.0, |value| match high - low {
+                range if range > 0.0 => (value - low) / range,
+                _ => 1.0,
+            });
+            *score += weight * scaled;
+        }
+    }
+    for (frame, score) in frames.iter_mut().zip(scores) {
+        frame.score.score = score / total;
+    }
+    frames.sort_by(|a, b| {
+        (a.score.error.is_some())
+            .cmp(&b.score.error.is_some())
+            .then(b.score.score.total_cmp(&a.score.score))
+            .then_with(|| a.score.path.cmp(&b.score.path))
+    });
+    let candidates: Vec<FrameScore> = frames
+        .into_iter()
+        .enumerate()
+        .map(|(index, frame)| FrameScore {
+            rank: index + 1,
+            ..frame.score
+        })
+        .collect();
+    BurstPick {
+        keeper: candidates
+            .first()
+            .filter(|best| best.error.is_none())
+            .map(|best| best.path.clone()),
+        candidates,
+    }
+}
+
+/// Groups `paths` into bursts by capture time and nominates a keeper for
+/// each from sharpness, exposure and, when weighted, the aesthetic score.
+/// Sharpness and exposure are measured on baseline JPEGs only.
+pub fn pick_best(paths: &[PathBuf], criteria: &PickCriteria) -> Vec<BurstPick> {
+    let frames = paths.iter().map(|path| measure(path, criteria)).collect();
+    group(frames)
+        .into_iter()
+        .map(|burst| rank(burst, criteria))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        exif_write::{apply_jpeg_edits, FieldEdit},
+        fixtures::Seeded,
+    };
+    use exif::Tag;
+    use std::fs;
+
+    /// A noisy frame; `blur` averages each pixel with its neighbours that many times.
+    fn frame(brightness: u8, blur: usize, time: &str) -> Vec<u8> {
+        let (width, height) = (64usize, 48usize);
+        let mut random = Seeded::new(7);
+        let mut grey: Vec<f64> = (0..width * height)
+            .map(|_| (brightness as f64 + random.below(80) as f64 - 40.0).clamp(0.0, 255.0))
+            .collect();
+        for _ in 0..blur {
+            grey = (0..width * height)
+                .map(|at| {
+                    let (x, y) = (at % width, at / width);
+                    let left = grey[y * width + x.saturating_sub(1)];
+                    let right = grey[y * width + (x + 1).min(width - 1)];
+                    let up = grey[y.saturating_sub(1) * width + x];
+                    let down = grey[(y + 1).min(height - 1) * width + x];
+                    (grey[at] + left + right + up + down) / 5.0
+                })
+                .collect();
+        }
+        let image = Raster {
+            width,
+            height,
+            pixels: grey.iter().flat_map(|&value| [value as u8; 3]).collect(),
+        };
+        apply_jpeg_edits(
+            &jpeg_codec::encode(&image, 90),
+            &[FieldEdit::ascii(Tag::DateTimeOriginal, time)],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn the_sharpest_well_exposed_frame_of_each_burst_is_kept() {
+        let dir = crate::fixtures::temp_path("burst");
+        fs::create_dir_all(&dir).unwrap();
+        let shots = [
+            ("a.jpg", frame(120, 3, "2024:05:01 10:00:00")),
+            ("b.jpg", frame(125, 0, "2024:05:01 10:00:01")),
+            ("c.jpg", frame(245, 0, "2024:05:01 10:00:02")),
+            ("d.jpg", frame(120, 0, "2024:05:01 10:05:00")),
+        ];
+        let paths: Vec<PathBuf> = shots
+            .iter()
+            .map(|(name, data)| {
+                let path = dir.join(name);
+                fs::write(&path, data).unwrap();
+                path
+            })
+            .collect();
+
+        let picks = pick_best(&paths, &PickCriteria::default());
+        fs::remove_dir_all(&dir).ok();
+
+        assert_eq!(picks.len(), 2);
+        let name = |path: &Option<String>| {
+            path.as_deref()
+                .and_then(|path| Path::new(path).file_name())
+                .map(|name| name.to_string_lossy().into_owned())
+        };
+        assert_eq!(name(&picks[0].keeper).as_deref(), Some("b.jpg"));
+        assert_eq!(picks[0].candidates.len(), 3);
+        let blurred = picks[0]
+            .candidates
+            .iter()
+            .find(|frame| frame.path.ends_with("a.jpg"))
+            .unwrap();
+        let sharp = &picks[0].candidates[0];
+        assert!(sharp.sharpness > blurred.sharpness);
+        assert_eq!(name(&picks[1].keeper).as_deref(), Some("d.jpg"));
+        assert!(PickCriteria {
+            sharpness: 0.0,
+            exposure: 0.0,
+            aesthetic: 0.0
+        }
+        .validate()
+        .is_err());
+    }
+}
@@ -0,0 +1,816 @@
+use crate::{
+    cbor::{self, Cbor},
+    heif::{self, boxes, u16_at, u32_at},
+    jpeg, png_chunks,
+};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+const APP11: u8 = 0xEB;
+/// The common identifier of JPEG APP11 segments carrying JUMBF.
+const JPEG_JUMBF: &[u8] = b"JP";
+/// The `uuid` box C2PA uses in ISO BMFF files (HEIF, AVIF, MP4).
+const BMFF_UUID: [u8; 16] = [
+    0xD8, 0xFE, 0xC3, 0xD6, 0x1B, 0x0E, 0x48, 0x3C, 0x92, 0x97, 0x58, 0x28, 0x87, 0x7E, 0xC4, 0x81,
+];
+const PNG_CHUNK: &[u8; 4] = b"caBX";
+const STORE_LABEL: &str = "c2pa";
+const ASSERTIONS_LABEL: &str = "c2pa.assertions";
+const SIGNATURE_LABEL: &str = "c2pa.signature";
+/// JUMBF content types are a four-character code followed by this suffix.
+const TYPE_SUFFIX: [u8; 12] = [
+    0x00, 0x11, 0x00, 0x10, 0x80, 0x00, 0x00, 0xAA, 0x00, 0x38, 0x9B, 0x71,
+];
+/// COSE header labels (RFC 9052) and the tag of a COSE_Sign1 message.
+const COSE_ALG: i128 = 1;
+const COSE_X5CHAIN: i128 = 33;
+const COSE_SIGN1_TAG: u64 = 18;
+/// Certificate name attributes: common name and organization.
+const OID_COMMON_NAME: &[u8] = &[0x55, 0x04, 0x03];
+const OID_ORGANIZATION: &[u8] = &[0x55, 0x04, 0x0A];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SignatureStatus {
+    /// The claim has no signature box.
+    Missing,
+    /// The signature box is not a COSE_Sign1 structure.
+    Malformed,
+    /// A well-formed COSE signature with a certificate. The cryptography
+    /// and trust chain are not checked.
+    Unverified,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CredentialSignature {
+    status: SignatureStatus,
+    /// COSE algorithm name, e.g. `ES256`.
+    algorithm: Option<String>,
+    /// Subject of the signing certificate.
+    signer: Option<String>,
+    issuer: Option<String>,
+    /// End of the signing certificate's validity, `YYYY-MM-DD`.
+    valid_until: Option<String>,
+    /// An RFC 3161 time-stamp is attached.
+    timestamped: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CredentialAction {
+    action: String,
+    software_agent: Option<String>,
+    when: Option<String>,
+    digital_source_type: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContentCredentials {
+    /// Manifests in the store; the last one describes the file as it is.
+    manifests: usize,
+    active_manifest: Option<String>,
+    generator: Option<String>,
+    title: Option<String>,
+    format: Option<String>,
+    actions: Vec<CredentialAction>,
+    /// Titles of the ingredients (source files) the active manifest lists.
+    ingredients: Vec<String>,
+    signature: CredentialSignature,
+    /// Structural problems: missing boxes, unresolved references...
+    problems: Vec<String>,
+}
+
+/// A JUMBF superbox: its description and content boxes.
+struct Superbox<'a> {
+    /// Four-character code of the content type, e.g. `c2ma` for a manifest.
+    kind: [u8; 4],
+    label: Option<String>,
+    children: Vec<(&'a [u8], &'a [u8])>,
+}
+
+impl<'a> Superbox<'a> {
+    /// Parses the body of a `jumb` box.
+    fn parse(body: &'a [u8]) -> Option<Self> {
+        let mut children = boxes(body);
+        let (kind, description) = children.next()?;
+        if kind != b"jumd" || description.get(4..16)? != TYPE_SUFFIX {
+            return None;
+        }
+        let toggles = *description.get(16)?;
+        let label = (toggles & 0x02 != 0).then(|| {
+            let text = &description[17..];
+            let end = text
+                .iter()
+                .position(|&byte| byte == 0)
+                .unwrap_or(text.len());
+            String::from_utf8_lossy(&text[..end]).into_owned()
+        });
+        Some(Superbox {
+            kind: description[..4].try_into().ok()?,
+            label,
+            children: children.collect(),
+        })
+    }
+
+    fn superboxes(&self) -> impl Iterator<Item = Superbox<'a>> + '_ {
+        self.children
+            .iter()
+            .filter(|(kind, _)| *kind == b"jumb")
+            .filter_map(|(_, body)| Superbox::parse(body))
+    }
+
+    fn find(&self, matches: impl Fn(&str) -> bool) -> Option<Superbox<'a>> {
+        self.superboxes()
+            .find(|superbox| superbox.label.as_deref().is_some_and(&matches))
+    }
+
+    /// The CBOR content box, decoded.
+    fn cbor(&self) -> Option<Cbor> {
+        self.children
+            .iter()
+            .find(|(kind, _)| *kind == b"cbor")
+            .and_then(|(_, body)| cbor::decode(body).ok())
+    }
+}
+
+/// The JUMBF of a JPEG, reassembled from its APP11 packets. Each packet
+/// repeats the box header, which only the first keeps.
+fn jpeg_jumbf(data: &[u8]) -> Option<Vec<u8>> {
+    let (segments, _) = jpeg::segments(data).ok()?;
+    let mut instances: BTreeMap<u32, Vec<(u32, &[u8])>> = BTreeMap::new();
+    for segment in segments.iter().filter(|segment| segment.marker == APP11) {
+        let payload = segment.payload(data);
+        if !payload.starts_with(JPEG_JUMBF) || payload.len() < 16 {
+            continue;
+        }
+        let (instance, sequence) = (u16_at(payload, 2)?, u32_at(payload, 4)?);
+        instances
+            .entry(instance)
+            .or_default()
+            .push((sequence, &payload[8..]));
+    }
+    instances.into_values().find_map(|mut packets| {
+        packets.sort_by_key(|(sequence, _)| *sequence);
+        let mut jumbf = packets.first()?.1.to_vec();
+        for (_, packet) in &packets[1..] {
+            let header = if u32_at(packet, 0) == Some(1) { 16 } else { 8 };
+            jumbf.extend_from_slice(packet.get(header..)?);
+        }
+        (jumbf.get(4..8) == Some(b"jumb")).then_some(jumbf)
+    })
+}
+
+/// The JUMBF of an ISO BMFF file: the body of a top-level C2PA `uuid` box
+/// after its purpose and offset, or of a `c2pa` box.
+fn bmff_jumbf(data: &[u8]) -> Option<Vec<u8>> {
+    heif::boxes(data).find_map(|(kind, body)| match kind {
+        b"uuid" if body.get(..16) == Some(&BMFF_UUID[..]) => {
+            // Version and flags, then a NUL-terminated purpose.
+            let purpose = body.get(20..)?;
+            let end = purpose.iter().position(|&byte| byte == 0)?;
+            if &purpose[..end] != b"manifest" {
+                return None;
+            }
+            // A 64-bit offset to the Merkle data follows.
+            Some(purpose.get(end + 9..)?.to_vec())
+        }
+        b"c2pa" => Some(body.to_vec()),
+        _ => None,
+    })
+}
+
+fn png_jumbf(data: &[u8]) -> Option<Vec<u8>> {
+    let (chunks, _) = png_chunks::chunks(data).ok()?;
+    chunks
+        .iter()
+        .find(|chunk| &chunk.kind == PNG_CHUNK)
+        .map(|chunk| chunk.payload(data).to_vec())
+}
+
+/// The C2PA manifest store embedded in `data`, as JUMBF.
+fn manifest_store(data: &[u8]) -> Option<Vec<u8>> {
+    if jpeg::is_jpeg(data) {
+        return jpeg_jumbf(data);
+    }
+    if data.starts_with(&crate::PNG_SIGNATURE) {
+        return png_jumbf(data);
+    }
+    // A standalone `.c2pa` manifest file is the store itself.
+    if data.get(4..8) == Some(b"jumb") {
+        return Some(data.to_vec());
+    }
+    bmff_jumbf(data)
+}
+
+fn text(item: Option<&Cbor>) -> Option<String> {
+    item.and_then(Cbor::as_text).map(str::to_string)
+}
+
+/// `claim_generator`, or the name and version of the first
+/// `claim_generator_info` entry (an array in v1 claims, a map in v2).
+fn generator(claim: &Cbor) -> Option<String> {
+    if let Some(generator) = text(claim.get("claim_generator")) {
+        return Some(generator);
+    }
+    let info = claim.get("claim_generator_info")?;
+    let info = info.as_array().and_then(<[Cbor]>::first).unwrap_or(info);
+    let name = text(info.get("name"))?;
+    Some(match text(info.get("version")) {
+        Some(version) => format!("{} {}", name, version),
+        None => name,
+    })
+}
+
+/// The assertion labels a claim references, taken from the last component
+/// of each hashed URI such as `self#jumbf=c2pa.assertions/c2pa.actions`.
+fn referenced_assertions(claim: &Cbor) -> Vec<String> {
+    ["assertions", "created_assertions", "gathered_assertions"]
+        .iter()
+        .filter_map(|key| claim.get(key)?.as_array())
+        .flatten()
+        .filter_map(|reference| text(reference.get("url")))
+        .map(|url| url.rsplit('/').next().unwrap_or(&url).to_string())
+        .collect()
+}
+
+fn actions(assertion: &Cbor) -> Vec<CredentialAction> {
+    let Some(actions) = assertion.get("actions").and_then(Cbor::as_array) else {
+        return Vec::new();
+    };
+    actions
+        .iter()
+        .filter_map(|action| {
+            let agent = action.get("softwareAgent");
+            Some(CredentialAction {
+                action: text(action.get("action"))?,
+                software_agent: text(agent).or_else(|| text(agent?.get("name"))),
+                when: text(action.get("when")),
+                digital_source_type: text(action.get("digitalSourceType")),
+            })
+        })
+        .collect()
+}
+
+/// Splits one DER element off `data`: its tag, contents and what follows.
+fn der(data: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let tag = *data.first()?;
+    let first = *data.get(1)? as usize;
+    let (length, header) = match first {
+        0..=0x7F => (first, 2),
+        0x81..=0x84 => {
+            let width = first - 0x80;
+            let length = data
+                .get(2..2 + width)?
+                .iter()
+                .fold(0usize, |length, &byte| (length << 8) | byte as usize);
+            (length, 2 + width)
+        }
+        _ => return None,
+    };
+    let end = header.checked_add(length)?;
+    Some((tag, data.get(header..end)?, &data[end..]))
+}
+
+fn der_items(mut data: &[u8]) -> Vec<(u8, &[u8])> {
+    let mut items = Vec::new();
+    while let Some((tag, contents, rest)) = der(data) {
+        items.push((tag, contents));
+        data = rest;
+    }
+    items
+}
+
+/// A distinguished name as `common name (organization)`.
+fn name(name: &[u8]) -> Option<String> {
+    let mut common_name = None;
+    let mut organization = None;
+    for (_, set) in der_items(name) {
+        for (_, attribute) in der_items(set) {
+            let (_, oid, rest) = der(attribute)?;
+            let (_, value, _) = der(rest)?;
+            let value = String::from_utf8_lossy(value).into_owned();
+            match oid {
+                OID_COMMON_NAME => common_name = Some(value),
+                OID_ORGANIZATION => organization = Some(value),
+                _ => {}
+            }
+        }
+    }
+    match (common_name, organization) {
+        (Some(common_name), Some(organization)) if common_name != organization => {
+            Some(format!("{} ({})", common_name, organization))
+        }
+        (common_name, organization) => common_name.or(organization),
+    }
+}
+
+/// A UTCTime or GeneralizedTime as `YYYY-MM-DD`.
+fn der_date(tag: u8, value: &[u8]) -> Option<String> {
+    let text = std::str::from_utf8(value).ok()?;
+    let (year, rest) = match tag {
+        0x17 => {
+            let year: u32 = text.get(..2)?.parse().ok()?;
+            (
+                if year < 50 { 2000 + year } else { 1900 + year },
+                text.get(2..)?,
+            )
+        }
+        0x18 => (text.get(..4)?.parse().ok()?, text.get(4..)?),
+        _ => return None,
+    };
+    Some(format!(
+        "{:04}-{}-{}",
+        year,
+        rest.get(..2)?,
+        rest.get(2..4)?
+    ))
+}
+
+/// Subject, issuer and expiry date of an X.509 certificate.
+fn certificate(der_bytes: &[u8]) -> Option<(Option<String>, Option<String>, Option<String>)> {
+    let (_, certificate, _) = der(der_bytes)?;
+    let (_, tbs, _) = der(certificate)?;
+    let mut fields = der_items(tbs).into_iter();
+    // The explicit version tag is optional.
+    let mut next = fields.next()?;
+    if next.0 == 0xA0 {
+        next = fields.next()?;
+    }
+    let _serial = next;
+    let _algorithm = fields.next()?;
+    let (_, issuer) = fields.next()?;
+    let (_, validity) = fields.next()?;
+    let (_, subject) = fields.next()?;
+    let valid_until = der_items(validity)
+        .get(1)
+        .and_then(|(tag, value)| der_date(*tag, value));
+    Some((name(subject), name(issuer), valid_until))
+}
+
+fn algorithm_name(algorithm: i128) -> String {
+    match algorithm {
+        -7 => "ES256".to_string(),
+        -35 => "ES384".to_string(),
+        -36 => "ES512".to_string(),
+        -37 => "PS256".to_string(),
+        -38 => "PS384".to_string(),
+        -39 => "PS512".to_string(),
+        -8 => "Ed25519".to_string(),
+        other => format!("COSE algorithm {}", other),
+    }
+}
+
+fn signature(superbox: Option<Superbox>, problems: &mut Vec<String>) -> CredentialSignature {
+    let mut signature = CredentialSignature {
+        status: SignatureStatus::Missing,
+        algorithm: None,
+        signer: None,
+        issuer: None,
+        valid_until: None,
+        timestamped: false,
+    };
+    let Some(superbox) = superbox else {
+        problems.push("The claim has no signature.".to_string());
+        return signature;
+    };
+    signature.status = SignatureStatus::Malformed;
+    let Some(cose) = superbox.cbor() else {
+        problems.push("The signature is not valid CBOR.".to_string());
+        return signature;
+    };
+    let tagged = matches!(cose, Cbor::Tag(COSE_SIGN1_TAG, _)) || !matches!(cose, Cbor::Tag(..));
+    let parts = cose.as_array().filter(|parts| parts.len() == 4 && tagged);
+    let Some([protected, unprotected, _, _]) = parts else {
+        problems.push("The signature is not a COSE_Sign1 structure.".to_string());
+        return signature;
+    };
+    let protected = protected
+        .as_bytes()
+        .and_then(|bytes| cbor::decode(bytes).ok());
+    let header = |label: i128| {
+        protected
+            .as_ref()
+            .and_then(|protected| protected.get_label(label))
+            .or_else(|| unprotected.get_label(label))
+    };
+    signature.algorithm = header(COSE_ALG)
+        .and_then(Cbor::as_integer)
+        .map(algorithm_name);
+    signature.timestamped =
+        unprotected.get("sigTst").is_some() || unprotected.get("sigTst2").is_some();
+    let chain = header(COSE_X5CHAIN);
+    let leaf = chain.and_then(|chain| {
+        chain
+            .as_bytes()
+            .or_else(|| chain.as_array()?.first()?.as_bytes())
+    });
+    match leaf.and_then(certificate) {
+        Some((signer, issuer, valid_until)) => {
+            signature.status = SignatureStatus::Unverified;
+            signature.signer = signer;
+            signature.issuer = issuer;
+            signature.valid_until = valid_until;
+        }
+        None => problems.push("The signature carries no readable certificate.".to_string()),
+    }
+    signature
+}
+
+/// Locates the C2PA manifest store in a JPEG (APP11), PNG (`caBX`), HEIF,
+/// AVIF or MP4 (`uuid` box) or standalone `.c2pa` file and summarizes its
+/// active manifest, noting structural problems as it goes.
+pub fn read(data: &[u8]) -> Result<ContentCredentials, String> {
+    let jumbf = manifest_store(data)
+        .ok_or_else(|| "The selected file has no Content Credentials.".to_string())?;
+    let store = boxes(&jumbf)
+        .next()
+        .filter(|(kind, _)| *kind == b"jumb")
+        .and_then(|(_, body)| Superbox::parse(body))
+        .ok_or_else(|| "The Content Credentials are unreadable.".to_string())?;
+
+    let mut problems = Vec::new();
+    if &store.kind != b"c2pa" || store.label.as_deref() != Some(STORE_LABEL) {
+        problems.push("The manifest store is not labelled as C2PA.".to_string());
+    }
+    let manifests: Vec<Superbox> = store
+        .superboxes()
+        .filter(|manifest| matches!(&manifest.kind, b"c2ma" | b"c2um"))
+        .collect();
+    let mut credentials = ContentCredentials {
+        manifests: manifests.len(),
+        active_manifest: None,
+        generator: None,
+        title: None,
+        format: None,
+        actions: Vec::new(),
+        ingredients: Vec::new(),
+        signature: signature(None, &mut Vec::new()),
+        problems: Vec::new(),
+    };
+    let Some(manifest) = manifests.last() else {
+        problems.push("The manifest store holds no manifests.".to_string());
+        credentials.problems = problems;
+        return Ok(credentials);
+    };
+    credentials.active_manifest = manifest.label.clone();
+
+    let assertions: Vec<Superbox> = manifest
+        .find(|label| label == ASSERTIONS_LABEL)
+        .map(|store| store.superboxes().collect())
+        .unwrap_or_default();
+    for assertion in &assertions {
+        let label = assertion.label.as_deref().unwrap_or_default();
+        let Some(content) = assertion.cbor() else {
+            continue;
+        };
+        if label.starts_with("c2pa.actions") {
+            credentials.actions.extend(actions(&content));
+        } else if label.starts_with("c2pa.ingredient") {
+            credentials
+                .ingredients
+                .extend(text(content.get("dc:title")).or_else(|| text(content.get("title"))));
+        }
+    }
+
+    let claim = manifest.find(|label| label.starts_with("c2pa.claim"));
+    match claim.as_ref().map(Superbox::cbor) {
+        None => problems.push("The manifest has no claim.".to_string()),
+        Some(None) => problems.push("The claim is not valid CBOR.".to_string()),
+        Some(Some(claim)) => {
+            credentials.generator = generator(&claim);
+            credentials.title = text(claim.get("dc:title")).or_else(|| text(claim.get("title")));
+            credentials.format = text(claim.get("dc:format"));
+            if credentials.generator.is_none() {
+                problems.push("The claim does not name its generator.".to_string());
+            }
+            let signature_reference = text(claim.get("signature"));
+            if !signature_reference.is_some_and(|reference| reference.ends_with(SIGNATURE_LABEL)) {
+                problems.push("The claim does not reference its signature.".to_string());
+            }
+            for label in referenced_assertions(&claim) {
+                if !assertions
+                    .iter()
+                    .any(|assertion| assertion.label.as_deref() == Some(&label))
+                {
+                    problems.push(format!(
+                        "The claim references a missing assertion, {}.",
+                        label
+                    ));
+                }
+            }
+        }
+    }
+    credentials.signature = signature(
+        manifest.find(|label| label == SIGNATURE_LABEL),
+        &mut problems,
+    );
+    credentials.problems = problems;
+    Ok(credentials)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{bmff_box, JpegBuilder};
+
+    /// Encodes `item` as CBOR with definite lengths and the shortest heads.
+    pub fn cbor_bytes(item: &Cbor) -> Vec<u8> {
+        fn head(out: &mut Vec<u8>, major: u8, value: u64) {
+            let major = major << 5;
+            match value {
+                0..=23 => out.push(major | value as u8),
+                24..=0xFF => out.extend([major | 24, value as u8]),
+                0x100..=0xFFFF => {
+                    out.push(major | 25);
+                    out.extend((value as u16).to_be_bytes());
+                }
+                0x1_0000..=0xFFFF_FFFF => {
+                    out.push(major | 26);
+                    out.extend((value as u32).to_be_bytes());
+                }
+                _ => {
+                    out.push(major | 27);
+                    out.extend(value.to_be_bytes());
+                }
+            }
+        }
+        fn encode(out: &mut Vec<u8>, item: &Cbor) {
+            match item {
+                Cbor::Integer(value) if *value >= 0 => head(out, 0, *value as u64),
+                Cbor::Integer(value) => head(out, 1, (-1 - *value) as u64),
+                Cbor::Bytes(bytes) => {
+                    head(out, 2, bytes.len() as u64);
+                    out.extend_from_slice(bytes);
+                }
+                Cbor::Text(text) => {
+                    head(out, 3, text.len() as u64);
+                    out.extend_from_slice(text.as_bytes());
+                }
+                Cbor::Array(items) => {
+                    head(out, 4, items.len() as u64);
+                    items.iter().for_each(|item| encode(out, item));
+                }
+                Cbor::Map(entries) => {
+                    head(out, 5, entries.len() as u64);
+                    for (key, value) in entries {
+                        encode(out, key);
+                        encode(out, value);
+                    }
+                }
+                Cbor::Tag(tag, inner) => {
+                    head(out, 6, *tag);
+                    encode(out, inner);
+                }
+                Cbor::Bool(flag) => out.push(0xF4 | *flag as u8),
+                Cbor::Null => out.push(0xF6),
+                Cbor::Undefined => out.push(0xF7),
+                Cbor::Float(value) => {
+                    out.push(0xFB);
+                    out.extend(value.to_be_bytes());
+                }
+            }
+        }
+        let mut out = Vec::new();
+        encode(&mut out, item);
+        out
+    }
+
+    fn superbox(kind: &[u8; 4], label: &str, contents: &[Vec<u8>]) -> Vec<u8> {
+        let mut description = kind.to_vec();
+        description.extend_from_slice(&TYPE_SUFFIX);
+        description.push(0x03);
+        description.extend_from_slice(label.as_bytes());
+        description.push(0);
+        let mut body = bmff_box(b"jumd", &description);
+        contents
+            .iter()
+            .for_each(|content| body.extend_from_slice(content));
+        bmff_box(b"jumb", &body)
+    }
+
+    fn text(value: &str) -> Cbor {
+        Cbor::Text(value.to_string())
+    }
+
+    fn map(entries: Vec<(&str, Cbor)>) -> Cbor {
+        Cbor::Map(
+            entries
+                .into_iter()
+                .map(|(key, value)| (text(key), value))
+                .collect(),
+        )
+    }
+
+    /// A DER element; lengths here stay under 128 bytes... or use long form.
+    fn der_element(tag: u8, contents: &[u8]) -> Vec<u8> {
+        let mut element = vec![tag];
+        if contents.len() < 0x80 {
+            element.push(contents.len() as u8);
+        } else {
+            element.push(0x82);
+            element.extend_from_slice(&(contents.len() as u16).to_be_bytes());
+        }
+        element.extend_from_slice(contents);
+        element
+    }
+
+    fn der_name(common_name: &str, organization: &str) -> Vec<u8> {
+        let attribute = |oid: &[u8], value: &str| {
+            let mut pair = der_element(0x06, oid);
+            pair.extend(der_element(0x0C, value.as_bytes()));
+            der_element(0x31, &der_element(0x30, &pair))
+        };
+        let mut name = attribute(OID_COMMON_NAME, common_name);
+        name.extend(attribute(OID_ORGANIZATION, organization));
+        der_element(0x30, &name)
+    }
+
+    fn test_certificate() -> Vec<u8> {
+        let mut tbs = der_element(0xA0, &der_element(0x02, &[2]));
+        tbs.extend(der_element(0x02, &[0x01, 0x23]));
+        tbs.extend(der_element(
+            0x30,
+            &der_element(0x06, &[0x2A, 0x86, 0x48, 0xCE, 0x3D, 0x04, 0x03, 0x02]),
+        ));
+        tbs.extend(der_name("Test CA", "Provenance Labs"));
+        let mut validity = der_element(0x17, b"240101000000Z");
+        validity.extend(der_element(0x17, b"270630235959Z"));
+        tbs.extend(der_element(0x30, &validity));
+        tbs.extend(der_name("Camera Signer", "Provenance Labs"));
+        let mut certificate = der_element(0x30, &tbs);
+        certificate.extend(der_element(0x30, &[]));
+        certificate.extend(der_element(0x03, &[0, 0xAB]));
+        der_element(0x30, &certificate)
+    }
+
+    fn store(with_signature: bool) -> Vec<u8> {
+        let actions = map(vec![(
+            "actions",
+            Cbor::Array(vec![map(vec![
+                ("action", text("c2pa.created")),
+                ("softwareAgent", map(vec![("name", text("Lens App 2.1"))])),
+                (
+                    "digitalSourceType",
+                    text("http://cv.iptc.org/newscodes/digitalsourcetype/digitalCapture"),
+                ),
+            ])]),
+        )]);
+        let reference = |label: &str| {
+            map(vec![
+                (
+                    "url",
+                    text(&format!("self#jumbf=c2pa.assertions/{}", label)),
+                ),
+                ("hash", Cbor::Bytes(vec![0; 32])),
+            ])
+        };
+        let claim = map(vec![
+            (
+                "claim_generator_info",
+                Cbor::Array(vec![map(vec![
+                    ("name", text("Lens App")),
+                    ("version", text("2.1")),
+                ])]),
+            ),
+            ("dc:title", text("IMG_0042.jpg")),
+            ("dc:format", text("image/jpeg")),
+            ("signature", text("self#jumbf=c2pa.signature")),
+            (
+                "assertions",
+                Cbor::Array(vec![reference("c2pa.actions"), reference("c2pa.hash.data")]),
+            ),
+        ]);
+        let protected = cbor_bytes(&Cbor::Map(vec![(
+            Cbor::Integer(COSE_ALG),
+            Cbor::Integer(-7),
+        )]));
+        let cose = Cbor::Tag(
+            COSE_SIGN1_TAG,
+            Box::new(Cbor::Array(vec![
+                Cbor::Bytes(protected),
+                Cbor::Map(vec![
+                    (
+                        Cbor::Integer(COSE_X5CHAIN),
+                        Cbor::Array(vec![Cbor::Bytes(test_certificate())]),
+                    ),
+                    (text("sigTst"), map(vec![])),
+                ]),
+                Cbor::Null,
+                Cbor::Bytes(vec![0; 64]),
+            ])),
+        );
+        let mut manifest = vec![
+            superbox(
+                b"c2as",
+                ASSERTIONS_LABEL,
+                &[superbox(
+                    b"cbor",
+                    "c2pa.actions",
+                    &[bmff_box(b"cbor", &cbor_bytes(&actions))],
+                )],
+            ),
+            superbox(
+                b"c2cl",
+                "c2pa.claim",
+                &[bmff_box(b"cbor", &cbor_bytes(&claim))],
+            ),
+        ];
+        if with_signature {
+            manifest.push(superbox(
+                b"c2cs",
+                SIGNATURE_LABEL,
+                &[bmff_box(b"cbor", &cbor_bytes(&cose))],
+            ));
+        }
+        superbox(
+            b"c2pa",
+            STORE_LABEL,
+            &[superbox(
+                b"c2ma",
+                "urn:uuid:0b7c5e4e-6f30-4e8a-9a67-3c2d5b1e0f11",
+                &manifest,
+            )],
+        )
+    }
+
+    /// Splits `jumbf` over APP11 packets of at most `size` bytes of box data,
+    /// repeating the box header in each, as writers do for large manifests.
+    fn app11_packets(jumbf: &[u8], size: usize) -> Vec<Vec<u8>> {
+        let (header, body) = jumbf.split_at(8);
+        body.chunks(size)
+            .enumerate()
+            .map(|(index, chunk)| {
+                let mut payload = JPEG_JUMBF.to_vec();
+                payload.extend_from_slice(&1u16.to_be_bytes());
+                payload.extend_from_slice(&(index as u32 + 1).to_be_bytes());
+                payload.extend_from_slice(header);
+                payload.extend_from_slice(chunk);
+                payload
+            })
+            .collect()
+    }
+
+    #[test]
+    fn manifests_split_over_app11_segments_are_summarized() {
+        let mut builder = JpegBuilder::new();
+        // Out of order on purpose: packets are joined by sequence number.
+        let mut packets = app11_packets(&store(true), 200);
+        packets.swap(0, 1);
+        for packet in &packets {
+            builder = builder.segment(APP11, packet);
+        }
+        let credentials = read(&builder.finish()).unwrap();
+
+        assert_eq!(credentials.manifests, 1);
+        assert_eq!(credentials.generator.as_deref(), Some("Lens App 2.1"));
+        assert_eq!(credentials.title.as_deref(), Some("IMG_0042.jpg"));
+        assert_eq!(
+            credentials.actions,
+            [CredentialAction {
+                action: "c2pa.created".to_string(),
+                software_agent: Some("Lens App 2.1".to_string()),
+                when: None,
+                digital_source_type: Some(
+                    "http://cv.iptc.org/newscodes/digitalsourcetype/digitalCapture".to_string()
+                ),
+            }]
+        );
+        let signature = &credentials.signature;
+        assert_eq!(signature.status, SignatureStatus::Unverified);
+        assert_eq!(signature.algorithm.as_deref(), Some("ES256"));
+        assert_eq!(
+            signature.signer.as_deref(),
+            Some("Camera Signer (Provenance Labs)")
+        );
+        assert_eq!(
+            signature.issuer.as_deref(),
+            Some("Test CA (Provenance Labs)")
+        );
+        assert_eq!(signature.valid_until.as_deref(), Some("2027-06-30"));
+        assert!(signature.timestamped);
+        assert_eq!(
+            credentials.problems,
+            ["The claim references a missing assertion, c2pa.hash.data."]
+        );
+    }
+
+    #[test]
+    fn bmff_uuid_boxes_are_found_and_missing_signatures_reported() {
+        let mut body = BMFF_UUID.to_vec();
+        body.extend_from_slice(&[0, 0, 0, 0]);
+        body.extend_from_slice(b"manifest\0");
+        body.extend_from_slice(&0u64.to_be_bytes());
+        body.extend(store(false));
+        let mut data = bmff_box(b"ftyp", b"avif\0\0\0\0mif1");
+        data.extend(bmff_box(b"uuid", &body));
+
+        let credentials = read(&data).unwrap();
+        assert_eq!(credentials.signature.status, SignatureStatus::Missing);
+        assert!(credentials
+            .problems
+            .contains(&"The claim has no signature.".to_string()));
+        assert!(read(&bmff_box(b"ftyp", b"heic")).is_err());
+    }
+}
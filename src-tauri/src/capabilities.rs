@@ -0,0 +1,78 @@
+use crate::heif::HeicSupport;
+use serde::Serialize;
+
+/// Optional cargo features and whether this build has them. Subsystems that
+/// pull in heavy dependencies go behind a feature and get a line here.
+const FEATURES: &[(&str, bool)] = &[
+    ("lightroom", cfg!(feature = "lightroom")),
+    ("ml-captions", cfg!(feature = "ml-captions")),
+    ("sqlite-index", cfg!(feature = "sqlite-index")),
+    ("index-encryption", cfg!(feature = "index-encryption")),
+    ("remote-storage", cfg!(feature = "remote-storage")),
+    ("sftp", cfg!(feature = "sftp")),
+    ("apple-photos", cfg!(feature = "apple-photos")),
+    ("phone-backup", cfg!(feature = "phone-backup")),
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Capabilities {
+    pub version: String,
+    /// Optional cargo features compiled into this build.
+    pub features: Vec<String>,
+    /// Every optional feature this version knows about, compiled in or not.
+    pub known_features: Vec<String>,
+    /// File extensions the viewer reads.
+    pub image_formats: Vec<String>,
+    /// Tray icon, background GPS scans and single-instance handling.
+    pub tray: bool,
+    pub context_menu: bool,
+    pub inspect_selection: bool,
+    /// HEIC metadata is always readable; previews depend on the platform.
+    pub heic: HeicSupport,
+}
+
+/// What this build and platform can do, so the frontend can hide the rest.
+pub fn discover() -> Capabilities {
+    #[cfg(desktop)]
+    let inspect_selection = crate::quick_inspect::SELECTION_SUPPORTED;
+    #[cfg(not(desktop))]
+    let inspect_selection = false;
+    Capabilities {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        features: FEATURES
+            .iter()
+            .filter(|(_, enabled)| *enabled)
+            .map(|(name, _)| name.to_string())
+            .collect(),
+        known_features: FEATURES.iter().map(|(name, _)| name.to_string()).collect(),
+        image_formats: crate::SUPPORTED_IMAGE_EXTENSIONS
+            .iter()
+            .map(|extension| extension.to_string())
+            .collect(),
+        tray: cfg!(desktop),
+        context_menu: crate::shell_integration::status().supported,
+        inspect_selection,
+        heic: crate::heif::support(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_compiled_features_among_the_known_ones() {
+        let capabilities = discover();
+        assert_eq!(
+            capabilities.features.contains(&"lightroom".to_string()),
+            cfg!(feature = "lightroom")
+        );
+        assert!(capabilities
+            .features
+            .iter()
+            .all(|feature| capabilities.known_features.contains(feature)));
+        assert!(capabilities.image_formats.contains(&"heic".to_string()));
+        assert!(capabilities.heic.metadata);
+    }
+}
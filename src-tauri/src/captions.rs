@@ -0,0 +1,332 @@
+use crate::{
+    capture::{capture_time, first_ascii},
+    commit::{commit_edits, Committed, EditTargets},
+    exif_write::FieldEdit,
+    iptc,
+    mwg::{self, LogicalField},
+    verify::WriteVerification,
+    write_lock::{FileStamp, WriteError, WriteProblemReport},
+    xmp,
+};
+use exif::{Exif, Tag};
+use serde::Serialize;
+use std::path::Path;
+
+/// IPTC-IIM location datasets (2:90, 2:95 and 2:101).
+const IPTC_CITY: u8 = 90;
+const IPTC_PROVINCE: u8 = 95;
+const IPTC_COUNTRY: u8 = 101;
+
+/// Placeholders a caption template can use.
+const PLACEHOLDERS: [&str; 9] = [
+    "name", "date", "year", "place", "event", "camera", "creator", "keywords", "scene",
+];
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CaptionResult {
+    path: String,
+    caption: Option<String>,
+    /// The file already had a description and `overwrite` was off.
+    skipped: bool,
+    written: bool,
+    /// The caption was stored in the app because originals are protected.
+    stored_externally: bool,
+    error: Option<String>,
+    problem: Option<WriteProblemReport>,
+    verification: Option<WriteVerification>,
+}
+
+/// Checks that every `{placeholder}` in `template` is known and that the
+/// `[optional]` segments are closed, before any file is touched.
+pub fn validate(template: &str) -> Result<(), String> {
+    if template.trim().is_empty() {
+        return Err("The caption template is empty.".to_string());
+    }
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        let Some(close) = rest[open..].find('}') else {
+            return Err("The caption template has an unclosed placeholder.".to_string());
+        };
+        let name = &rest[open + 1..open + close];
+        if !PLACEHOLDERS.contains(&name) {
+            return Err(format!(
+                "The caption template uses an unknown placeholder: {{{}}}.",
+                name
+            ));
+        }
+        if name == "scene" && !cfg!(feature = "ml-captions") {
+            return Err("{scene} needs a build with the local captioner.".to_string());
+        }
+        rest = &rest[open + close + 1..];
+    }
+    if template.matches('[').count() != template.matches(']').count() {
+        return Err("The caption template has an unclosed [optional] segment.".to_string());
+    }
+    Ok(())
+}
+
+/// `text` with its placeholders filled. A placeholder without a value is
+/// left blank, or makes the whole result `None` unless `blank_missing`.
+fn fill(text: &str, value: &dyn Fn(&str) -> Option<String>, blank_missing: bool) -> Option<String> {
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(open) = rest.find('{') {
+        let Some(close) = rest[open..].find('}').map(|close| open + close) else {
+            break;
+        };
+        out.push_str(&rest[..open]);
+        match value(&rest[open + 1..close]) {
+            Some(filled) => out.push_str(&filled),
+            None if blank_missing => {}
+            None => return None,
+        }
+        rest = &rest[close + 1..];
+    }
+    out.push_str(rest);
+    Some(out)
+}
+
+/// Expands `{placeholders}` with `value`. A `[segment]` is left out when any
+/// placeholder inside it has no value; a placeholder outside one becomes
+/// empty.
+pub fn expand(template: &str, value: &dyn Fn(&str) -> Option<String>) -> String {
+    let mut caption = String::new();
+    let mut rest = template;
+    while let Some(open) = rest.find('[') {
+        let Some(close) = rest[open..].find(']').map(|close| open + close) else {
+            break;
+        };
+        caption.extend(fill(&rest[..open], value, true));
+        caption.extend(fill(&rest[open + 1..close], value, false));
+        rest = &rest[close + 1..];
+    }
+    caption.extend(fill(rest, value, true));
+    caption.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn nonempty(value: impl Into<String>) -> Option<String> {
+    let value = value.into().trim().to_string();
+    (!value.is_empty()).then_some(value)
+}
+
+/// City, region and country from XMP, then IPTC-IIM, then the first IPTC
+/// Extension LocationCreated.
+fn place(packet: Option<&str>, datasets: &[iptc::Dataset]) -> Option<String> {
+    let from_xmp = ["photoshop:City", "photoshop:State", "photoshop:Country"]
+        .map(|name| packet.and_then(|packet| xmp::property(packet, name)));
+    let from_iptc = [IPTC_CITY, IPTC_PROVINCE, IPTC_COUNTRY]
+        .map(|number| iptc::values(datasets, number).first().copied());
+    let created = packet
+        .map(|packet| xmp::structs(packet, "Iptc4xmpExt:LocationCreated"))
+        .unwrap_or_default();
+    let from_extension = [
+        "Iptc4xmpExt:City",
+        "Iptc4xmpExt:ProvinceState",
+        "Iptc4xmpExt:CountryName",
+    ]
+    .map(|name| {
+        created
+            .first()
+            .and_then(|fields| fields.iter().find(|(field, _)| *field == name))
+            .map(|(_, value)| *value)
+    });
+    [from_xmp, from_iptc, from_extension]
+        .into_iter()
+        .map(|parts| {
+            parts
+                .into_iter()
+                .flatten()
+                .map(str::trim)
+                .filter(|part| !part.is_empty() && !part.contains('<'))
+                .collect::<Vec<_>>()
+                .join(", ")
+        })
+        .find_map(nonempty)
+}
+
+/// Make and model as one name, without repeating the make.
+pub fn camera(exif: &Exif) -> Option<String> {
+    let text =
+        |tag| first_ascii(exif, tag).map(|text| String::from_utf8_lossy(text).trim().to_string());
+    let make = text(Tag::Make).unwrap_or_default();
+    let model = text(Tag::Model).unwrap_or_default();
+    // Most models already start with the make ("Canon EOS R5").
+    if model.to_lowercase().starts_with(&make.to_lowercase()) {
+        nonempty(model)
+    } else {
+        nonempty(format!("{} {}", make, model))
+    }
+}
+
+/// The value of each placeholder for one file.
+fn placeholder_values<'a>(
+    path: &'a Path,
+    data: &'a [u8],
+    exif: Option<&'a Exif>,
+    captioner: Option<&'a str>,
+) -> impl Fn(&str) -> Option<String> + 'a {
+    let packet = xmp::packet(data);
+    let datasets = iptc::read(data);
+    let captured = exif.and_then(capture_time);
+    move |name: &str| match name {
+        "name" => nonempty(path.file_stem()?.to_string_lossy()),
+        "date" => captured.map(|time| time.local.format("%Y-%m-%d").to_string()),
+        "year" => captured.map(|time| time.local.format("%Y").to_string()),
+        "place" => place(packet, &datasets),
+        // The IPTC Extension event, or the folder the shoot was filed in.
+        "event" => packet
+            .and_then(|packet| {
+                xmp::items(packet, "Iptc4xmpExt:Event")
+                    .first()
+                    .copied()
+                    .or_else(|| xmp::property(packet, "Iptc4xmpExt:Event"))
+            })
+            .and_then(nonempty)
+            .or_else(|| nonempty(path.parent()?.file_name()?.to_string_lossy())),
+        "camera" => exif.and_then(camera),
+        "creator" => {
+            mwg::reconcile(data, &Default::default())
+                .into_iter()
+                .find(|field| field.field == LogicalField::Creator)?
+                .value
+        }
+        "keywords" => {
+            let keywords = packet
+                .map(|packet| xmp::items(packet, "dc:subject"))
+                .filter(|keywords| !keywords.is_empty())
+                .unwrap_or_else(|| iptc::values(&datasets, iptc::KEYWORDS));
+            nonempty(keywords.join(", "))
+        }
+        "scene" => scene(path, captioner?),
+        _ => None,
+    }
+}
+
+/// What the image shows, from the local captioning program, which is run as
+/// `<program> <image>` and prints one caption on its standard output.
+#[cfg(feature = "ml-captions")]
+fn scene(path: &Path, program: &str) -> Option<String> {
+    let output = std::process::Command::new(program)
+        .arg(path)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        tracing::warn!(
+            file = %crate::logging::file_name(path),
+            program = %crate::logging::file_name(std::path::Path::new(program)),
+            "captioner failed"
+        );
+        return None;
+    }
+    nonempty(String::from_utf8_lossy(&output.stdout).lines().next()?)
+}
+
+#[cfg(not(feature = "ml-captions"))]
+fn scene(_path: &Path, _program: &str) -> Option<String> {
+    None
+}
+
+/// Fills the EXIF description of `path` from `template`. Files that already
+/// have one are skipped unless `overwrite` is set.
+pub fn caption_file(
+    path: &Path,
+    template: &str,
+    overwrite: bool,
+    dry_run: bool,
+    captioner: Option<&str>,
+    targets: &EditTargets,
+) -> CaptionResult {
+    let mut result = CaptionResult {
+        path: path.to_string_lossy().into_owned(),
+        caption: None,
+        skipped: false,
+        written: false,
+        stored_externally: false,
+        error: None,
+        problem: None,
+        verification: None,
+    };
+
+    let outcome = (|| -> Result<(), WriteError> {
+        let stamp = FileStamp::of(path)?;
+        let data = crate::load_file_data(path)?;
+        let exif = crate::read_exif_container(&data);
+        let existing = exif
+            .as_ref()
+            .and_then(|exif| first_ascii(exif, Tag::ImageDescription))
+            .is_some_and(|text| !String::from_utf8_lossy(text).trim().is_empty());
+        if existing && !overwrite {
+            result.skipped = true;
+            return Ok(());
+        }
+        let caption = expand(
+            template,
+            &placeholder_values(path, &data, exif.as_ref(), captioner),
+        );
+        if caption.is_empty() {
+            return Err("The template produced an empty caption for this file."
+                .to_string()
+                .into());
+        }
+        result.caption = Some(caption.clone());
+        if dry_run {
+            return Ok(());
+        }
+        match commit_edits(
+            targets,
+            "generate_captions",
+            path,
+            stamp,
+            &data,
+            exif.as_ref(),
+            &[FieldEdit::ascii(Tag::ImageDescription, &caption)],
+        )? {
+            Committed::InPlace(verification) => {
+                result.written = true;
+                result.verification = Some(verification);
+            }
+            Committed::External => result.stored_externally = true,
+        }
+        Ok(())
+    })();
+
+    if let Err(error) = outcome {
+        result.error = Some(error.message());
+        result.problem = error.report();
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{ascii_field, jpeg_from_fields};
+
+    #[test]
+    fn templates_fill_from_metadata_and_drop_empty_segments() {
+        let data = jpeg_from_fields(&[
+            ascii_field(Tag::Make, "Canon"),
+            ascii_field(Tag::Model, "Canon EOS R5"),
+            ascii_field(Tag::DateTimeOriginal, "2024:06:01 10:00:00"),
+        ]);
+        let exif = crate::read_exif_container(&data);
+        let path = Path::new("/shoots/Porto Marathon/IMG_0001.jpg");
+        let values = placeholder_values(path, &data, exif.as_ref(), None);
+        let caption = expand(
+            "{event}[ in {place}], {date}[ by {creator}]. Shot on {camera}.",
+            &values,
+        );
+        assert_eq!(caption, "Porto Marathon, 2024-06-01. Shot on Canon EOS R5.");
+    }
+
+    #[test]
+    fn templates_are_checked_before_use() {
+        assert!(validate("{event} on {date}").is_ok());
+        assert!(validate("{event} at {venue}")
+            .unwrap_err()
+            .contains("{venue}"));
+        assert!(validate("[ in {place}").is_err());
+        assert_eq!(validate("{scene}").is_ok(), cfg!(feature = "ml-captions"));
+    }
+}
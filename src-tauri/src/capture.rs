@@ -0,0 +1,127 @@
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use exif::{DateTime, Exif, In, Tag, Value};
+
+/// When a photo was taken, as recorded by the camera or GPS receiver.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CaptureTime {
+    /// Wall-clock time as stored in the file.
+    pub local: NaiveDateTime,
+    /// Offset from UTC in minutes when the file records one.
+    pub offset_minutes: Option<i32>,
+}
+
+impl CaptureTime {
+    pub fn utc(self) -> Option<NaiveDateTime> {
+        self.offset_minutes
+            .map(|offset| self.local - Duration::minutes(offset as i64))
+    }
+
+    /// UTC when known, otherwise the camera's wall-clock time.
+    pub fn sort_key(self) -> NaiveDateTime {
+        self.utc().unwrap_or(self.local)
+    }
+
+    /// ISO 8601 rendering; times without an offset are left unqualified.
+    pub fn to_iso8601(self) -> String {
+        match self.utc() {
+            Some(utc) => format!("{}Z", utc.format("%Y-%m-%dT%H:%M:%S")),
+            None => self.local.format("%Y-%m-%dT%H:%M:%S").to_string(),
+        }
+    }
+}
+
+/// Picks the most trustworthy capture time: the GPS fix (always UTC), then
+/// DateTimeOriginal, CreateDate and DateTime with their offset tags.
+pub fn capture_time(exif: &Exif) -> Option<CaptureTime> {
+    gps_time(exif)
+        .or_else(|| camera_time(exif, Tag::DateTimeOriginal, Tag::OffsetTimeOriginal))
+        .or_else(|| camera_time(exif, Tag::DateTimeDigitized, Tag::OffsetTimeDigitized))
+        .or_else(|| camera_time(exif, Tag::DateTime, Tag::OffsetTime))
+}
+
+/// Camera wall-clock time from DateTimeOriginal and OffsetTimeOriginal.
+pub fn original_time(exif: &Exif) -> Option<CaptureTime> {
+    camera_time(exif, Tag::DateTimeOriginal, Tag::OffsetTimeOriginal)
+}
+
+fn camera_time(exif: &Exif, tag: Tag, offset_tag: Tag) -> Option<CaptureTime> {
+    let bytes = first_ascii(exif, tag)?;
+    let mut datetime = DateTime::from_ascii(bytes).ok()?;
+    if let Some(offset) = first_ascii(exif, offset_tag) {
+        datetime.parse_offset(offset).ok();
+    }
+    let local = NaiveDate::from_ymd_opt(
+        datetime.year as i32,
+        datetime.month as u32,
+        datetime.day as u32,
+    )?
+    .and_hms_opt(
+        datetime.hour as u32,
+        datetime.minute as u32,
+        datetime.second as u32,
+    )?;
+    Some(CaptureTime {
+        local,
+        offset_minutes: datetime.offset.map(i32::from),
+    })
+}
+
+/// UTC time of the GPS fix from GPSDateStamp and GPSTimeStamp.
+pub fn gps_time(exif: &Exif) -> Option<CaptureTime> {
+    let date = String::from_utf8_lossy(first_ascii(exif, Tag::GPSDateStamp)?).into_owned();
+    let date = NaiveDate::parse_from_str(date.trim(), "%Y:%m:%d").ok()?;
+    let parts = match &exif.get_field(Tag::GPSTimeStamp, In::PRIMARY)?.value {
+        Value::Rational(values) if values.len() == 3 => values
+            .iter()
+            .map(|value| value.to_f64())
+            .collect::<Vec<_>>(),
+        _ => return None,
+    };
+    if parts.iter().any(|part| !part.is_finite() || *part < 0.0) {
+        return None;
+    }
+    let local = date.and_hms_opt(parts[0] as u32, parts[1] as u32, parts[2] as u32)?;
+    Some(CaptureTime {
+        local,
+        offset_minutes: Some(0),
+    })
+}
+
+pub fn first_ascii(exif: &Exif, tag: Tag) -> Option<&[u8]> {
+    match &exif.get_field(tag, In::PRIMARY)?.value {
+        Value::Ascii(values) => values.first().map(Vec::as_slice),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{ascii_field, exif_from_fields, rational_field};
+
+    #[test]
+    fn offset_tags_convert_to_utc() {
+        let exif = exif_from_fields(&[
+            ascii_field(Tag::DateTimeOriginal, "2024:06:01 18:30:00"),
+            ascii_field(Tag::OffsetTimeOriginal, "+02:00"),
+        ]);
+        let time = capture_time(&exif).expect("capture time should parse");
+        assert_eq!(time.offset_minutes, Some(120));
+        assert_eq!(time.to_iso8601(), "2024-06-01T16:30:00Z");
+    }
+
+    #[test]
+    fn gps_fix_wins_over_camera_clock() {
+        let exif = exif_from_fields(&[
+            ascii_field(Tag::DateTimeOriginal, "2024:06:01 18:30:00"),
+            ascii_field(Tag::GPSDateStamp, "2024:06:01"),
+            rational_field(Tag::GPSTimeStamp, &[(16, 1), (29, 1), (58, 1)]),
+        ]);
+        let time = capture_time(&exif).expect("capture time should parse");
+        assert_eq!(time.to_iso8601(), "2024-06-01T16:29:58Z");
+        assert_eq!(
+            original_time(&exif).map(|time| time.offset_minutes),
+            Some(None)
+        );
+    }
+}
@@ -0,0 +1,283 @@
+use crate::{capture::CaptureTime, AestheticMatch};
+use chrono::DateTime;
+use serde::{Deserialize, Serialize};
+use std::{cmp::Ordering, path::Path, time::UNIX_EPOCH};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SortKey {
+    /// Best aesthetic score first.
+    #[default]
+    Score,
+    /// Oldest capture first, or newest with `newest_first`.
+    CaptureDate,
+    /// Grouped by film roll in roll order, frames in order within each roll;
+    /// files without a roll come last.
+    Roll,
+    /// Grouped by the `City, Country` of the GPS position, best score first
+    /// within each place; files without one come last. Needs reverse
+    /// geocoding turned on.
+    Place,
+}
+
+/// What a date sort does with files that record no capture time.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MissingDates {
+    /// Use the file's modification time in their place.
+    #[default]
+    FileModified,
+    /// Leave them undated and list them after every dated file.
+    Separate,
+}
+
+/// Where a match's `captured` time came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DateSource {
+    Metadata,
+    FileModified,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ScanSort {
+    pub by: SortKey,
+    pub missing_dates: MissingDates,
+    pub newest_first: bool,
+}
+
+fn modified_time(path: &Path) -> Option<CaptureTime> {
+    let modified = std::fs::metadata(path).ok()?.modified().ok()?;
+    let seconds = modified.duration_since(UNIX_EPOCH).ok()?.as_secs() as i64;
+    Some(CaptureTime {
+        local: DateTime::from_timestamp(seconds, 0)?.naive_utc(),
+        offset_minutes: Some(0),
+    })
+}
+
+/// Numbers in numeric order (`9` before `10`), anything else by its text.
+fn numbered_order(a: &str, b: &str) -> Ordering {
+    match (a.parse::<u64>(), b.parse::<u64>()) {
+        (Ok(a_number), Ok(b_number)) => a_number.cmp(&b_number),
+        _ => a.cmp(b),
+    }
+}
+
+/// Orders `matches` as `sort` asks. A date sort also fills in the fallback
+/// dates and the `day` each match is grouped under; undated matches keep the
+/// score order among themselves.
+pub fn sort_matches(matches: &mut [AestheticMatch], sort: ScanSort) {
+    let by_score = |a: &AestheticMatch, b: &AestheticMatch| {
+        b.score.partial_cmp(&a.score).unwrap_or(Ordering::Equal)
+    };
+    match sort.by {
+        SortKey::Score => {
+            matches.sort_by(by_score);
+            return;
+        }
+        SortKey::Roll => {
+            matches.sort_by(|a, b| match (&a.roll, &b.roll) {
+                (Some(a_roll), Some(b_roll)) => numbered_order(a_roll, b_roll)
+                    .then_with(|| match (&a.frame, &b.frame) {
+                        (Some(a_frame), Some(b_frame)) => numbered_order(a_frame, b_frame),
+                        (a_frame, b_frame) => b_frame.is_some().cmp(&a_frame.is_some()),
+                    })
+                    .then_with(|| by_score(a, b)),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => by_score(a, b),
+            });
+            return;
+        }
+        SortKey::Place => {
+            matches.sort_by(|a, b| match (&a.place, &b.place) {
+                (Some(a_place), Some(b_place)) => a_place.cmp(b_place).then_with(|| by_score(a, b)),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => by_score(a, b),
+            });
+            return;
+        }
+        SortKey::CaptureDate => {}
+    }
+
+    for found in matches.iter_mut() {
+        if found.capture.is_none() && sort.missing_dates == MissingDates::FileModified {
+            found.capture = modified_time(Path::new(&found.path));
+            if found.capture.is_some() {
+                found.captured = found.capture.map(CaptureTime::to_iso8601);
+                found.captured_from = Some(DateSource::FileModified);
+            }
+        }
+        found.day = found
+            .capture
+            .map(|capture| capture.local.format("%Y-%m-%d").to_string());
+    }
+    let key = |found: &AestheticMatch| found.capture.map(CaptureTime::sort_key);
+    matches.sort_by(|a, b| match (key(a), key(b)) {
+        (Some(a_key), Some(b_key)) => {
+            let chronological: Ordering = a_key.cmp(&b_key);
+            if sort.newest_first {
+                chronological.reverse()
+            } else {
+                chronological
+            }
+        }
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => by_score(a, b),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MatchStatus;
+    use chrono::NaiveDate;
+
+    fn found(path: &str, score: f64, taken: Option<(u32, u32)>) -> AestheticMatch {
+        let capture = taken.map(|(day, hour)| CaptureTime {
+            local: NaiveDate::from_ymd_opt(2024, 6, day)
+                .unwrap()
+                .and_hms_opt(hour, 0, 0)
+                .unwrap(),
+            offset_minutes: None,
+        });
+        AestheticMatch {
+            path: path.to_string(),
+            score,
+            sidecar: None,
+            fields: None,
+            captured: capture.map(CaptureTime::to_iso8601),
+            captured_from: capture.map(|_| DateSource::Metadata),
+            day: None,
+            roll: None,
+            frame: None,
+            place: None,
+            companions: Vec::new(),
+            status: MatchStatus::Read,
+            unavailable: None,
+            capture,
+        }
+    }
+
+    fn paths(matches: &[AestheticMatch]) -> Vec<&str> {
+        matches.iter().map(|found| found.path.as_str()).collect()
+    }
+
+    #[test]
+    fn date_sorts_follow_capture_time_and_list_undated_files_last() {
+        let mut matches = vec![
+            found("missing-undated.jpg", 0.9, None),
+            found("late.jpg", 0.5, Some((2, 9))),
+            found("early.jpg", 0.7, Some((1, 18))),
+        ];
+        let separate = ScanSort {
+            by: SortKey::CaptureDate,
+            missing_dates: MissingDates::Separate,
+            newest_first: false,
+        };
+        sort_matches(&mut matches, separate);
+        assert_eq!(
+            paths(&matches),
+            ["early.jpg", "late.jpg", "missing-undated.jpg"]
+        );
+        assert_eq!(matches[0].day.as_deref(), Some("2024-06-01"));
+        assert_eq!(matches[2].day, None);
+
+        sort_matches(
+            &mut matches,
+            ScanSort {
+                newest_first: true,
+                ..separate
+            },
+        );
+        assert_eq!(
+            paths(&matches),
+            ["late.jpg", "early.jpg", "missing-undated.jpg"]
+        );
+
+        sort_matches(&mut matches, ScanSort::default());
+        assert_eq!(
+            paths(&matches),
+            ["missing-undated.jpg", "early.jpg", "late.jpg"]
+        );
+    }
+
+    #[test]
+    fn the_modification_time_stands_in_for_a_missing_date() {
+        let path = crate::fixtures::temp_path("capture_sort.jpg");
+        std::fs::write(&path, b"").unwrap();
+        let mut matches = vec![
+            found(&path.to_string_lossy(), 0.9, None),
+            found("old.jpg", 0.5, Some((1, 9))),
+        ];
+        sort_matches(
+            &mut matches,
+            ScanSort {
+                by: SortKey::CaptureDate,
+                ..ScanSort::default()
+            },
+        );
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(matches[0].path, "old.jpg");
+        assert_eq!(matches[1].captured_from, Some(DateSource::FileModified));
+        assert!(matches[1]
+            .captured
+            .as_deref()
+            .is_some_and(|time| time.ends_with('Z')));
+    }
+
+    #[test]
+    fn roll_sorts_group_frames_in_order() {
+        let on_roll = |path: &str, roll: Option<&str>, frame: &str| AestheticMatch {
+            roll: roll.map(str::to_string),
+            frame: Some(frame.to_string()),
+            ..found(path, 0.5, None)
+        };
+        let mut matches = vec![
+            found("digital.jpg", 0.9, None),
+            on_roll("r10-1.tif", Some("10"), "1"),
+            on_roll("r9-12.tif", Some("9"), "12"),
+            on_roll("r9-2.tif", Some("9"), "2"),
+        ];
+        sort_matches(
+            &mut matches,
+            ScanSort {
+                by: SortKey::Roll,
+                ..ScanSort::default()
+            },
+        );
+        assert_eq!(
+            paths(&matches),
+            ["r9-2.tif", "r9-12.tif", "r10-1.tif", "digital.jpg"]
+        );
+    }
+
+    #[test]
+    fn place_sorts_group_by_city_then_score() {
+        let at = |path: &str, score: f64, place: &str| AestheticMatch {
+            place: Some(place.to_string()),
+            ..found(path, score, None)
+        };
+        let mut matches = vec![
+            found("indoors.jpg", 0.9, None),
+            at("tokyo-1.jpg", 0.4, "Tokyo, Japan"),
+            at("paris.jpg", 0.2, "Paris, France"),
+            at("tokyo-2.jpg", 0.8, "Tokyo, Japan"),
+        ];
+        sort_matches(
+            &mut matches,
+            ScanSort {
+                by: SortKey::Place,
+                ..ScanSort::default()
+            },
+        );
+        assert_eq!(
+            paths(&matches),
+            ["paris.jpg", "tokyo-2.jpg", "tokyo-1.jpg", "indoors.jpg"]
+        );
+    }
+}
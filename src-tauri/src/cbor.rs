@@ -0,0 +1,282 @@
+/// Nesting beyond this is treated as malformed rather than risking the stack.
+const MAX_DEPTH: usize = 64;
+
+/// A decoded CBOR data item (RFC 8949).
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cbor {
+    Integer(i128),
+    Bytes(Vec<u8>),
+    Text(String),
+    Array(Vec<Cbor>),
+    Map(Vec<(Cbor, Cbor)>),
+    Tag(u64, Box<Cbor>),
+    Bool(bool),
+    Null,
+    Undefined,
+    Float(f64),
+}
+
+impl Cbor {
+    /// The value of a map entry with a text key.
+    pub fn get(&self, key: &str) -> Option<&Cbor> {
+        self.entries()?
+            .iter()
+            .find(|(found, _)| found.as_text() == Some(key))
+            .map(|(_, value)| value)
+    }
+
+    /// The value of a map entry with an integer key, as COSE headers use.
+    pub fn get_label(&self, key: i128) -> Option<&Cbor> {
+        self.entries()?
+            .iter()
+            .find(|(found, _)| *found == Cbor::Integer(key))
+            .map(|(_, value)| value)
+    }
+
+    pub fn entries(&self) -> Option<&[(Cbor, Cbor)]> {
+        match self.untagged() {
+            Cbor::Map(entries) => Some(entries),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Cbor]> {
+        match self.untagged() {
+            Cbor::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    pub fn as_text(&self) -> Option<&str> {
+        match self.untagged() {
+            Cbor::Text(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self.untagged() {
+            Cbor::Bytes(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    pub fn as_integer(&self) -> Option<i128> {
+        match self.untagged() {
+            Cbor::Integer(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// The item inside any tags.
+    pub fn untagged(&self) -> &Cbor {
+        match self {
+            Cbor::Tag(_, inner) => inner.untagged(),
+            item => item,
+        }
+    }
+}
+
+fn malformed() -> String {
+    "The CBOR data is malformed.".to_string()
+}
+
+struct Decoder<'a> {
+    data: &'a [u8],
+    at: usize,
+}
+
+impl Decoder<'_> {
+    fn take(&mut self, count: usize) -> Result<&[u8], String> {
+        let bytes = self
+            .data
+            .get(self.at..self.at.checked_add(count).ok_or_else(malformed)?)
+            .ok_or_else(malformed)?;
+        self.at += count;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    /// The argument of an item head: its value, length or count. `None`
+    /// marks an indefinite length.
+    fn argument(&mut self, info: u8) -> Result<Option<u64>, String> {
+        let width = match info {
+            0..=23 => return Ok(Some(info as u64)),
+            24 => 1,
+            25 => 2,
+            26 => 4,
+            27 => 8,
+            31 => return Ok(None),
+            _ => return Err(malformed()),
+        };
+        let bytes = self.take(width)?;
+        Ok(Some(
+            bytes
+                .iter()
+                .fold(0u64, |value, &byte| (value << 8) | byte as u64),
+        ))
+    }
+
+    fn length(&mut self, info: u8) -> Result<usize, String> {
+        let length = self.argument(info)?.ok_or_else(malformed)?;
+        // Every item takes at least one byte, so longer counts cannot be real.
+        usize::try_from(length)
+            .ok()
+            .filter(|length| *length <= self.data.len())
+            .ok_or_else(malformed)
+    }
+
+    fn at_break(&mut self) -> bool {
+        let found = self.data.get(self.at) == Some(&0xFF);
+        if found {
+            self.at += 1;
+        }
+        found
+    }
+
+    /// The chunks of an indefinite-length byte or text string.
+    fn chunks(&mut self, major: u8) -> Result<Vec<u8>, String> {
+        let mut bytes = Vec::new();
+        while !self.at_break() {
+            let head = self.byte()?;
+            if head >> 5 != major {
+                return Err(malformed());
+            }
+            let length = self.length(head & 0x1F)?;
+            bytes.extend_from_slice(self.take(length)?);
+        }
+        Ok(bytes)
+    }
+
+    fn item(&mut self, depth: usize) -> Result<Cbor, String> {
+        if depth > MAX_DEPTH {
+            return Err(malformed());
+        }
+        let head = self.byte()?;
+        let (major, info) = (head >> 5, head & 0x1F);
+        Ok(match major {
+            0 => Cbor::Integer(self.argument(info)?.ok_or_else(malformed)? as i128),
+            1 => Cbor::Integer(-1 - self.argument(info)?.ok_or_else(malformed)? as i128),
+            2 | 3 => {
+                let bytes = match info {
+                    31 => self.chunks(major)?,
+                    _ => {
+                        let length = self.length(info)?;
+                        self.take(length)?.to_vec()
+                    }
+                };
+                match major {
+                    2 => Cbor::Bytes(bytes),
+                    _ => Cbor::Text(String::from_utf8(bytes).map_err(|_| malformed())?),
+                }
+            }
+            4 => {
+                let mut items = Vec::new();
+                match self.argument(info)? {
+                    Some(count) => {
+                        for _ in 0..count.min(self.data.len() as u64) {
+                            items.push(self.item(depth + 1)?);
+                        }
+                    }
+                    None => {
+                        while !self.at_break() {
+                            items.push(self.item(depth + 1)?);
+                        }
+                    }
+                }
+                Cbor::Array(items)
+            }
+            5 => {
+                let mut entries = Vec::new();
+                match self.argument(info)? {
+                    Some(count) => {
+                        for _ in 0..count.min(self.data.len() as u64) {
+                            entries.push((self.item(depth + 1)?, self.item(depth + 1)?));
+                        }
+                    }
+                    None => {
+                        while !self.at_break() {
+                            entries.push((self.item(depth + 1)?, self.item(depth + 1)?));
+                        }
+                    }
+                }
+                Cbor::Map(entries)
+            }
+            6 => {
+                let tag = self.argument(info)?.ok_or_else(malformed)?;
+                Cbor::Tag(tag, Box::new(self.item(depth + 1)?))
+            }
+            _ => match info {
+                20 => Cbor::Bool(false),
+                21 => Cbor::Bool(true),
+                22 => Cbor::Null,
+                23 => Cbor::Undefined,
+                25 => Cbor::Float(half_float(u16::from_be_bytes([self.byte()?, self.byte()?]))),
+                26 => Cbor::Float(
+                    f32::from_be_bytes(self.take(4)?.try_into().expect("4 bytes")) as f64,
+                ),
+                27 => Cbor::Float(f64::from_be_bytes(
+                    self.take(8)?.try_into().expect("8 bytes"),
+                )),
+                0..=19 | 24 => {
+                    self.argument(info)?;
+                    Cbor::Undefined
+                }
+                _ => return Err(malformed()),
+            },
+        })
+    }
+}
+
+fn half_float(bits: u16) -> f64 {
+    let exponent = ((bits >> 10) & 0x1F) as i32;
+    let mantissa = (bits & 0x3FF) as f64;
+    let magnitude = match exponent {
+        0 => mantissa * 2f64.powi(-24),
+        31 if mantissa == 0.0 => f64::INFINITY,
+        31 => f64::NAN,
+        _ => (1.0 + mantissa / 1024.0) * 2f64.powi(exponent - 15),
+    };
+    if bits & 0x8000 != 0 {
+        -magnitude
+    } else {
+        magnitude
+    }
+}
+
+/// Decodes the first data item of `data`. Trailing bytes are ignored.
+pub fn decode(data: &[u8]) -> Result<Cbor, String> {
+    Decoder { data, at: 0 }.item(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rfc_examples_decode() {
+        // From RFC 8949 appendix A.
+        assert_eq!(decode(&[0x19, 0x03, 0xE8]), Ok(Cbor::Integer(1000)));
+        assert_eq!(decode(&[0x38, 0x63]), Ok(Cbor::Integer(-100)));
+        assert_eq!(decode(&[0xF9, 0x3C, 0x00]), Ok(Cbor::Float(1.0)));
+        assert_eq!(
+            decode(&[0x64, 0x49, 0x45, 0x54, 0x46]),
+            Ok(Cbor::Text("IETF".to_string()))
+        );
+        let map = decode(&[0xA2, 0x61, 0x61, 0x01, 0x61, 0x62, 0x82, 0x02, 0x03]).unwrap();
+        assert_eq!(map.get("a"), Some(&Cbor::Integer(1)));
+        assert_eq!(
+            map.get("b").and_then(Cbor::as_array).map(<[Cbor]>::len),
+            Some(2)
+        );
+        let indefinite = decode(&[0x5F, 0x42, 0x01, 0x02, 0x43, 0x03, 0x04, 0x05, 0xFF]).unwrap();
+        assert_eq!(indefinite, Cbor::Bytes(vec![1, 2, 3, 4, 5]));
+        let tagged = decode(&[0xD2, 0x80]).unwrap();
+        assert_eq!(tagged.as_array(), Some(&[][..]));
+        assert!(decode(&[0x9B, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]).is_err());
+        assert!(decode(&[0x62, 0x61]).is_err());
+    }
+}
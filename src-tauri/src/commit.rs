@@ -0,0 +1,254 @@
+use crate::{
+    exif_write::{apply_edits, FieldEdit},
+    oplog::{field_changes, FieldChange, OperationLog, OperationRecord},
+    settings::EditMode,
+    verify::{verify_write, verify_xmp_write, WriteVerification},
+    virtual_fields::{VirtualField, VirtualFieldStore},
+    write_lock::{write_checked, FileStamp, WriteError},
+};
+use exif::{Exif, Field, In};
+use std::path::Path;
+
+/// Source name for edits kept outside the originals.
+pub const EXTERNAL_EDITS_SOURCE: &str = "Edits";
+const REMOVED_VALUE: &str = "(removed)";
+
+/// Stores every metadata write needs: the operation log, the external edit
+/// store, and the mode deciding which of the two destinations is used.
+pub struct EditTargets<'a> {
+    pub log: &'a OperationLog,
+    pub external: &'a VirtualFieldStore,
+    pub mode: EditMode,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Committed {
+    /// The file itself was rewritten and re-read.
+    InPlace(WriteVerification),
+    /// The edits were stored in the app; the original was not opened for writing.
+    External,
+}
+
+/// The single path through which metadata edits reach disk.
+///
+/// `data` and `stamp` must come from the same read of `path`. In
+/// [`EditMode::ExternalOnly`] the original is never written, whatever the caller.
+pub fn commit_edits(
+    targets: &EditTargets,
+    operation: &str,
+    path: &Path,
+    stamp: FileStamp,
+    data: &[u8],
+    exif: Option<&Exif>,
+    edits: &[FieldEdit],
+) -> Result<Committed, WriteError> {
+    let changes = field_changes(exif, edits);
+    let committed = match targets.mode {
+        EditMode::InPlace => {
+            let updated = apply_edits(data, edits)?;
+            write_checked(path, stamp, &updated)?;
+            let reread = crate::load_file_data(path)?;
+            Committed::InPlace(verify_write(data, &reread, edits))
+        }
+        EditMode::ExternalOnly => {
+            store_externally(targets.external, path, edits)?;
+            Committed::External
+        }
+    };
+
+    let operation = match committed {
+        Committed::InPlace(_) => operation.to_string(),
+        Committed::External => format!("{} (external)", operation),
+    };
+    targets
+        .log
+        .record(&OperationRecord::new(&operation, path, changes))?;
+    Ok(committed)
+}
+
+/// Replaces `path` with `updated`, a structural rewrite such as removed
+/// segments or reordered chunks, and logs `changes`. Such rewrites have no
+/// field edits to keep in the app, so [`EditMode::ExternalOnly`] refuses them.
+pub fn commit_rewrite(
+    log: &OperationLog,
+    mode: EditMode,
+    operation: &str,
+    path: &Path,
+    stamp: FileStamp,
+    updated: &[u8],
+    changes: Vec<FieldChange>,
+) -> Result<(), WriteError> {
+    refuse_external(mode)?;
+    write_checked(path, stamp, updated)?;
+    log.record(&OperationRecord::new(operation, path, changes))?;
+    Ok(())
+}
+
+/// Replaces the XMP packet of the JPEG `data`, read from `path` with
+/// `stamp`, and logs `changes`. Like [`commit_edits`] the file is re-read
+/// afterwards, to check that `packet` landed and nothing else moved.
+pub fn commit_xmp(
+    targets: &EditTargets,
+    operation: &str,
+    path: &Path,
+    stamp: FileStamp,
+    data: &[u8],
+    packet: &str,
+    changes: Vec<FieldChange>,
+) -> Result<WriteVerification, WriteError> {
+    refuse_external(targets.mode)?;
+    let updated = crate::xmp::embed_in_jpeg(data, packet)?;
+    write_checked(path, stamp, &updated)?;
+    let reread = crate::load_file_data(path)?;
+    let properties: Vec<String> = changes.iter().map(|change| change.tag.clone()).collect();
+    let verification = verify_xmp_write(data, &reread, packet, &properties);
+    targets
+        .log
+        .record(&OperationRecord::new(operation, path, changes))?;
+    Ok(verification)
+}
+
+fn refuse_external(mode: EditMode) -> Result<(), WriteError> {
+    if mode == EditMode::ExternalOnly {
+        return Err(WriteError::Failed(
+            "This change rewrites the file itself, so it is unavailable while edits are kept outside the originals.".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Merges `edits` into the external edits already recorded for `path`.
+fn store_externally(
+    store: &VirtualFieldStore,
+    path: &Path,
+    edits: &[FieldEdit],
+) -> Result<(), String> {
+    let mut fields: Vec<VirtualField> = store
+        .fields_for(path)
+        .into_iter()
+        .filter(|field| field.source == EXTERNAL_EDITS_SOURCE)
+        .collect();
+    for edit in edits {
+        let tag = edit.tag.to_string();
+        let value = match &edit.value {
+            Some(value) => Field {
+                tag: edit.tag,
+                ifd_num: In::PRIMARY,
+                value: value.clone(),
+            }
+            .display_value()
+            .to_string(),
+            None => REMOVED_VALUE.to_string(),
+        };
+        fields.retain(|field| field.tag != tag);
+        fields.push(VirtualField {
+            source: EXTERNAL_EDITS_SOURCE.to_string(),
+            tag,
+            value,
+        });
+    }
+    store.replace_source(path, EXTERNAL_EDITS_SOURCE, fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use exif::Tag;
+    use std::fs;
+
+    #[test]
+    fn external_mode_leaves_the_original_byte_for_byte() {
+        let photo = crate::fixtures::temp_path("archive.jpg");
+        // Not even a valid JPEG: external mode must not try to parse or rewrite it.
+        fs::write(&photo, b"\xFF\xD8original").unwrap();
+        let log_path = crate::fixtures::temp_path("operations.jsonl");
+        let store_path = crate::fixtures::temp_path("virtual-fields.json");
+        let log = OperationLog::open(log_path.clone());
+        let external = VirtualFieldStore::open(store_path.clone());
+        let targets = EditTargets {
+            log: &log,
+            external: &external,
+            mode: EditMode::ExternalOnly,
+        };
+
+        let stamp = FileStamp::of(&photo).unwrap();
+        let committed = commit_edits(
+            &targets,
+            "edit",
+            &photo,
+            stamp,
+            b"\xFF\xD8original",
+            None,
+            &[FieldEdit::ascii(Tag::Artist, "Ada")],
+        )
+        .unwrap();
+        commit_edits(
+            &targets,
+            "edit",
+            &photo,
+            stamp,
+            b"\xFF\xD8original",
+            None,
+            &[FieldEdit::ascii(Tag::Copyright, "CC-BY")],
+        )
+        .unwrap();
+
+        let contents = fs::read(&photo).unwrap();
+        let fields = external.fields_for(&photo);
+        let history = log.history(&photo).unwrap();
+        for path in [&photo, &log_path, &store_path] {
+            fs::remove_file(path).ok();
+        }
+
+        assert_eq!(committed, Committed::External);
+        assert_eq!(contents, b"\xFF\xD8original");
+        assert_eq!(fields.len(), 2);
+        assert_eq!(history[0].operation, "edit (external)");
+    }
+
+    #[test]
+    fn xmp_rewrites_are_read_back() {
+        let photo = crate::fixtures::temp_path("event.jpg");
+        let original = crate::fixtures::JpegBuilder::new()
+            .exif(&[crate::fixtures::ascii_field(Tag::Artist, "Ada")])
+            .xmp("<x:xmpmeta><rdf:RDF></rdf:RDF></x:xmpmeta>")
+            .finish();
+        fs::write(&photo, &original).unwrap();
+        let log_path = crate::fixtures::temp_path("operations.jsonl");
+        let store_path = crate::fixtures::temp_path("virtual-fields.json");
+        let log = OperationLog::open(log_path.clone());
+        let external = VirtualFieldStore::open(store_path.clone());
+        let targets = EditTargets {
+            log: &log,
+            external: &external,
+            mode: EditMode::InPlace,
+        };
+
+        let packet = crate::xmp::with_properties(
+            crate::xmp::jpeg_packet(&original),
+            &[("xmpDM:album", "Lisbon")],
+        )
+        .unwrap();
+        let change = FieldChange {
+            tag: "xmpDM:album".to_string(),
+            before: None,
+            after: Some("Lisbon".to_string()),
+        };
+        let stamp = FileStamp::of(&photo).unwrap();
+        let verification = commit_xmp(
+            &targets,
+            "infer_events",
+            &photo,
+            stamp,
+            &original,
+            &packet,
+            vec![change],
+        );
+        for path in [&photo, &log_path, &store_path] {
+            fs::remove_file(path).ok();
+        }
+
+        let verification = verification.unwrap();
+        assert!(verification.verified, "{:?}", verification);
+    }
+}
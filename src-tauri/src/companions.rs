@@ -0,0 +1,200 @@
+use crate::capture::CaptureTime;
+use chrono::{DateTime, NaiveDateTime};
+use serde::Serialize;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// How a non-image file belongs to a photo.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Relationship {
+    /// Edit settings from darktable, Lightroom, RawTherapee, DxO or Apple Photos.
+    Sidecar,
+    /// The small preview some cameras write beside videos and RAW files.
+    Thumbnail,
+    /// A voice note recorded on the camera.
+    VoiceMemo,
+    /// A GPS track named after the photo or recorded while it was taken.
+    Track,
+    /// A copy kept by an editor before writing, like exiftool's `_original`.
+    Backup,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Companion {
+    pub path: String,
+    pub relationship: Relationship,
+}
+
+/// Extensions of companion files, compared case-insensitively.
+const COMPANION_EXTENSIONS: [(&str, Relationship); 9] = [
+    ("xmp", Relationship::Sidecar),
+    ("pp3", Relationship::Sidecar),
+    ("dop", Relationship::Sidecar),
+    ("aae", Relationship::Sidecar),
+    ("thm", Relationship::Thumbnail),
+    ("wav", Relationship::VoiceMemo),
+    ("m4a", Relationship::VoiceMemo),
+    ("mp3", Relationship::VoiceMemo),
+    ("gpx", Relationship::Track),
+];
+
+/// How the file at `path` belongs to a photo, going by its extension.
+pub fn relationship(path: &Path) -> Option<Relationship> {
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    if extension == "bak" || extension.ends_with("_original") {
+        return Some(Relationship::Backup);
+    }
+    COMPANION_EXTENSIONS
+        .iter()
+        .find(|(known, _)| *known == extension)
+        .map(|(_, relationship)| *relationship)
+}
+
+/// The first and last `<time>` of a GPX file, in UTC.
+fn track_span(text: &str) -> Option<(NaiveDateTime, NaiveDateTime)> {
+    let times: Vec<NaiveDateTime> = text
+        .split("<time>")
+        .skip(1)
+        .filter_map(|rest| rest.split_once("</time>"))
+        .filter_map(|(time, _)| DateTime::parse_from_rfc3339(time.trim()).ok())
+        .map(|time| time.naive_utc())
+        .collect();
+    Some((*times.iter().min()?, *times.iter().max()?))
+}
+
+/// Finds the companion files of scan matches. The listing of the last
+/// folder, and the time span of its GPX tracks, are kept because matches
+/// arrive folder by folder.
+#[derive(Default)]
+pub struct CompanionFinder {
+    folder: Option<PathBuf>,
+    files: Vec<(PathBuf, Relationship)>,
+    tracks: Vec<(PathBuf, NaiveDateTime, NaiveDateTime)>,
+}
+
+impl CompanionFinder {
+    fn load(&mut self, folder: &Path) {
+        if self.folder.as_deref() == Some(folder) {
+            return;
+        }
+        self.folder = Some(folder.to_path_buf());
+        self.files = fs::read_dir(folder)
+            .map(|entries| {
+                entries
+                    .filter_map(Result::ok)
+                    .map(|entry| entry.path())
+                    .filter_map(|path| {
+                        let relationship = relationship(&path)?;
+                        Some((path, relationship))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        self.files.sort_by(|a, b| a.0.cmp(&b.0));
+        self.tracks = self
+            .files
+            .iter()
+            .filter(|(_, relationship)| *relationship == Relationship::Track)
+            .filter_map(|(path, _)| {
+                let (start, end) = track_span(&fs::read_to_string(path).ok()?)?;
+                Some((path.clone(), start, end))
+            })
+            .collect();
+    }
+
+    /// Files sharing `image`'s name up to its extension (`IMG_0001.WAV`,
+    /// `IMG_0001.CR2.xmp`, `IMG_0001.JPG_original`), plus GPX tracks
+    /// recorded while it was taken.
+    pub fn find(&mut self, image: &Path, capture: Option<CaptureTime>) -> Vec<Companion> {
+        let (Some(folder), Some(stem)) = (image.parent(), image.file_stem()) else {
+            return Vec::new();
+        };
+        self.load(folder);
+        let prefix = format!("{}.", stem.to_string_lossy().to_lowercase());
+        let mut companions: Vec<Companion> = self
+            .files
+            .iter()
+            .filter(|(path, _)| {
+                path.file_name()
+                    .is_some_and(|name| name.to_string_lossy().to_lowercase().starts_with(&prefix))
+            })
+            .map(|(path, relationship)| Companion {
+                path: path.to_string_lossy().into_owned(),
+                relationship: *relationship,
+            })
+            .collect();
+        if let Some(taken) = capture.and_then(CaptureTime::utc) {
+            for (path, start, end) in &self.tracks {
+                let path = path.to_string_lossy().into_owned();
+                if (*start..=*end).contains(&taken)
+                    && !companions.iter().any(|seen| seen.path == path)
+                {
+                    companions.push(Companion {
+                        path,
+                        relationship: Relationship::Track,
+                    });
+                }
+            }
+        }
+        companions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn same_name_files_and_covering_tracks_are_companions() {
+        let folder = crate::fixtures::temp_path("companions");
+        fs::create_dir_all(&folder).unwrap();
+        let gpx = "<gpx><trk><trkseg><trkpt><time>2024-05-04T09:00:00Z</time></trkpt>\
+            <trkpt><time>2024-05-04T11:00:00Z</time></trkpt></trkseg></trk></gpx>";
+        for (name, contents) in [
+            ("IMG_0001.JPG", ""),
+            ("IMG_0001.WAV", ""),
+            ("IMG_0001.CR2.xmp", ""),
+            ("IMG_0001.JPG_original", ""),
+            ("IMG_00010.xmp", ""),
+            ("morning.gpx", gpx),
+        ] {
+            fs::write(folder.join(name), contents).unwrap();
+        }
+        let taken = CaptureTime {
+            local: NaiveDate::from_ymd_opt(2024, 5, 4)
+                .unwrap()
+                .and_hms_opt(12, 30, 0)
+                .unwrap(),
+            offset_minutes: Some(120),
+        };
+
+        let companions: Vec<(String, Relationship)> = CompanionFinder::default()
+            .find(&folder.join("IMG_0001.JPG"), Some(taken))
+            .into_iter()
+            .map(|companion| {
+                let name = Path::new(&companion.path)
+                    .file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .into_owned();
+                (name, companion.relationship)
+            })
+            .collect();
+        fs::remove_dir_all(&folder).ok();
+
+        assert_eq!(
+            companions,
+            [
+                ("IMG_0001.CR2.xmp".to_string(), Relationship::Sidecar),
+                ("IMG_0001.JPG_original".to_string(), Relationship::Backup),
+                ("IMG_0001.WAV".to_string(), Relationship::VoiceMemo),
+                ("morning.gpx".to_string(), Relationship::Track),
+            ]
+        );
+    }
+}
@@ -0,0 +1,111 @@
+use crate::AestheticMatch;
+use serde::Serialize;
+use std::path::PathBuf;
+use url::Url;
+
+pub const SCHEME: &str = "exifviewer";
+/// Same threshold the scan form starts with.
+const DEFAULT_MIN_SCORE: f64 = 0.75;
+
+/// An action requested through an `exifviewer://` link.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DeepLink {
+    /// `exifviewer://open?path=...`
+    Open { path: PathBuf },
+    /// `exifviewer://scan?folder=...&min=0.7`
+    Scan { folder: PathBuf, min_score: f64 },
+}
+
+/// Outcome of a scan started from a link, sent to the frontend as an event.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LinkedScan {
+    pub folder: String,
+    pub min_score: f64,
+    pub matches: Vec<AestheticMatch>,
+    pub error: Option<String>,
+}
+
+pub fn parse(link: &str) -> Result<DeepLink, String> {
+    let url = Url::parse(link).map_err(|_| format!("\"{}\" is not a valid link.", link))?;
+    if url.scheme() != SCHEME {
+        return Err(format!("Only {}:// links are supported.", SCHEME));
+    }
+    // `exifviewer://open?...` puts the action in the host, `exifviewer:open?...`
+    // in the path; accept both spellings.
+    let action = url
+        .host_str()
+        .filter(|host| !host.is_empty())
+        .unwrap_or_else(|| url.path().trim_matches('/'))
+        .to_ascii_lowercase();
+    let query = |name: &str| {
+        url.query_pairs()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.into_owned())
+            .filter(|value| !value.is_empty())
+    };
+
+    match action.as_str() {
+        "open" => {
+            let path = query("path").ok_or("Open links need a path parameter.")?;
+            Ok(DeepLink::Open {
+                path: PathBuf::from(path),
+            })
+        }
+        "scan" => {
+            let folder = query("folder").ok_or("Scan links need a folder parameter.")?;
+            let min_score = match query("min") {
+                Some(min) => min
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|score| score.is_finite())
+                    .ok_or_else(|| format!("\"{}\" is not a valid minimum score.", min))?,
+                None => DEFAULT_MIN_SCORE,
+            };
+            Ok(DeepLink::Scan {
+                folder: PathBuf::from(folder),
+                min_score,
+            })
+        }
+        other => Err(format!("Unknown {}:// action \"{}\".", SCHEME, other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn open_and_scan_links_decode_their_parameters() {
+        assert_eq!(
+            parse("exifviewer://open?path=%2FUsers%2Fada%2FIMG%201.jpg").unwrap(),
+            DeepLink::Open {
+                path: PathBuf::from("/Users/ada/IMG 1.jpg")
+            }
+        );
+        assert_eq!(
+            parse("exifviewer://scan?folder=/photos&min=0.7").unwrap(),
+            DeepLink::Scan {
+                folder: PathBuf::from("/photos"),
+                min_score: 0.7
+            }
+        );
+        assert_eq!(
+            parse("exifviewer:scan?folder=/photos").unwrap(),
+            DeepLink::Scan {
+                folder: PathBuf::from("/photos"),
+                min_score: DEFAULT_MIN_SCORE
+            }
+        );
+    }
+
+    #[test]
+    fn malformed_links_are_rejected_with_a_reason() {
+        assert!(parse("https://example.com/open?path=/a.jpg").is_err());
+        assert!(parse("exifviewer://open").is_err());
+        assert!(parse("exifviewer://scan?folder=/photos&min=high").is_err());
+        assert!(parse("exifviewer://delete?path=/a.jpg")
+            .unwrap_err()
+            .contains("delete"));
+    }
+}
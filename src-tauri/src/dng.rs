@@ -0,0 +1,255 @@
+use crate::{
+    ifd::{self, Tiff},
+    makernote, ExifField,
+};
+use exif::Exif;
+
+pub const COMPUTED_DNG_IFD: &str = "DNG";
+pub const APPLE_IFD: &str = "Apple";
+
+const SUB_IFDS: u16 = 0x014A;
+const IMAGE_WIDTH: u16 = 0x0100;
+const IMAGE_LENGTH: u16 = 0x0101;
+const DNG_VERSION: u16 = 0xC612;
+const UNIQUE_CAMERA_MODEL: u16 = 0xC614;
+const PROFILE_NAME: u16 = 0xC6F8;
+/// DNG 1.6 additions used by Apple ProRAW.
+const PROFILE_GAIN_TABLE_MAP: u16 = 0xCD2D;
+const SEMANTIC_NAME: u16 = 0xCD2E;
+const SEMANTIC_INSTANCE_ID: u16 = 0xCD30;
+
+/// The fixed part of a ProfileGainTableMap before its float gains.
+const GAIN_TABLE_HEADER: usize = 4 + 4 + 8 * 4 + 4 + 4 * 5;
+
+/// A sub-image of a DNG, such as the raw data or a semantic mask.
+#[derive(Debug, Clone, PartialEq)]
+struct SubImage {
+    width: u32,
+    height: u32,
+    semantic_name: Option<String>,
+    instance: Option<String>,
+}
+
+/// Points of a gain table map in each dimension, when the blob is well formed.
+fn gain_table_points(bytes: &[u8], little_endian: bool) -> Option<(u32, u32, u32)> {
+    // The spec stores the table big-endian; some writers use the file order.
+    [false, little_endian]
+        .into_iter()
+        .find_map(|little_endian| {
+            let table = Tiff::new(bytes, little_endian);
+            let (rows, columns, layers) = (table.u32(0)?, table.u32(4)?, table.u32(40)?);
+            let gains = (rows as usize)
+                .checked_mul(columns as usize)?
+                .checked_mul(layers as usize)?
+                .checked_mul(4)?;
+            (GAIN_TABLE_HEADER.checked_add(gains)? == bytes.len())
+                .then_some((rows, columns, layers))
+        })
+}
+
+fn sub_ifds(tiff: &Tiff, ifd0: &[ifd::Entry]) -> Vec<Vec<ifd::Entry>> {
+    ifd::find(ifd0, SUB_IFDS)
+        .map(|pointers| tiff.uints(pointers))
+        .unwrap_or_default()
+        .into_iter()
+        .map(|offset| tiff.entries(offset as usize))
+        .collect()
+}
+
+fn sub_image(tiff: &Tiff, entries: &[ifd::Entry]) -> SubImage {
+    let first = |tag| ifd::find(entries, tag).and_then(|entry| tiff.uints(entry).first().copied());
+    let text = |tag| ifd::find(entries, tag).and_then(|entry| tiff.text(entry));
+    SubImage {
+        width: first(IMAGE_WIDTH).unwrap_or_default(),
+        height: first(IMAGE_LENGTH).unwrap_or_default(),
+        semantic_name: text(SEMANTIC_NAME),
+        instance: text(SEMANTIC_INSTANCE_ID),
+    }
+}
+
+fn field(tag: &str, value: String) -> ExifField {
+    ExifField {
+        tag: tag.to_string(),
+        ifd: COMPUTED_DNG_IFD.to_string(),
+        value,
+    }
+}
+
+/// DNG structure kamadak-exif leaves as numbered tags or does not reach:
+/// the version, camera profile, ProRAW's ProfileGainTableMap and the
+/// semantic masks (sky, skin, hair...) stored as SubIFDs.
+pub fn computed_fields(exif: &Exif) -> Vec<ExifField> {
+    let tiff = Tiff::new(exif.buf(), exif.little_endian());
+    let Some(ifd0) = tiff.first_ifd().map(|offset| tiff.entries(offset)) else {
+        return Vec::new();
+    };
+    let Some(version) = ifd::find(&ifd0, DNG_VERSION).map(|entry| tiff.uints(entry)) else {
+        return Vec::new();
+    };
+    let mut fields = vec![field(
+        "DNGVersion",
+        version
+            .iter()
+            .map(u32::to_string)
+            .collect::<Vec<_>>()
+            .join("."),
+    )];
+    for (tag, name) in [
+        (UNIQUE_CAMERA_MODEL, "UniqueCameraModel"),
+        (PROFILE_NAME, "ProfileName"),
+    ] {
+        if let Some(text) = ifd::find(&ifd0, tag).and_then(|entry| tiff.text(entry)) {
+            fields.push(field(name, text));
+        }
+    }
+
+    let sub_ifds = sub_ifds(&tiff, &ifd0);
+    let gain_table = std::iter::once(&ifd0)
+        .chain(&sub_ifds)
+        .find_map(|entries| ifd::find(entries, PROFILE_GAIN_TABLE_MAP));
+    if let Some(entry) = gain_table {
+        let bytes = tiff.bytes(entry).unwrap_or_default();
+        let value = match gain_table_points(bytes, exif.little_endian()) {
+            Some((rows, columns, layers)) => format!(
+                "{} × {} points, {} gain layer{}",
+                rows,
+                columns,
+                layers,
+                if layers == 1 { "" } else { "s" }
+            ),
+            None => format!("present ({} bytes)", bytes.len()),
+        };
+        fields.push(field("ProfileGainTableMap", value));
+    }
+
+    let masks: Vec<String> = sub_ifds
+        .iter()
+        .map(|entries| sub_image(&tiff, entries))
+        .filter_map(|image| {
+            let name = match (image.semantic_name?, image.instance) {
+                (name, Some(instance)) => format!("{} #{}", name, instance),
+                (name, None) => name,
+            };
+            Some(format!("{} ({}×{})", name, image.width, image.height))
+        })
+        .collect();
+    if !masks.is_empty() {
+        fields.push(field("SemanticMasks", masks.join(", ")));
+    }
+    fields
+}
+
+/// Named Apple MakerNote fields for iPhone files in any container.
+pub fn apple_fields(exif: &Exif) -> Vec<ExifField> {
+    makernote::apple_note(exif)
+        .map(makernote::apple_fields)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(tag, value)| ExifField {
+            tag: tag.to_string(),
+            ifd: APPLE_IFD.to_string(),
+            value,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Appends one IFD to `out` at its end, with out-of-line values placed
+    /// right after it. Entries are `(tag, type, count, value bytes)`.
+    fn push_ifd(out: &mut Vec<u8>, entries: &[(u16, u16, u32, Vec<u8>)]) -> u32 {
+        let start = out.len();
+        let mut extra = start + 2 + entries.len() * 12 + 4;
+        let mut values = Vec::new();
+        out.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+        for (tag, kind, count, value) in entries {
+            out.extend_from_slice(&tag.to_le_bytes());
+            out.extend_from_slice(&kind.to_le_bytes());
+            out.extend_from_slice(&count.to_le_bytes());
+            if value.len() <= 4 {
+                let mut inline = value.clone();
+                inline.resize(4, 0);
+                out.extend(inline);
+            } else {
+                out.extend_from_slice(&(extra as u32).to_le_bytes());
+                values.extend_from_slice(value);
+                extra += value.len();
+            }
+        }
+        out.extend_from_slice(&0u32.to_le_bytes());
+        out.extend(values);
+        start as u32
+    }
+
+    fn long(value: u32) -> Vec<u8> {
+        value.to_le_bytes().to_vec()
+    }
+
+    /// A little-endian ProRAW-like DNG: IFD0 with the DNG tags, a 2×3×1 gain
+    /// table and one SubIFD holding a sky mask.
+    fn proraw() -> Vec<u8> {
+        let mut gain_table = Vec::new();
+        for word in [2u32, 3] {
+            gain_table.extend_from_slice(&word.to_be_bytes());
+        }
+        gain_table.extend_from_slice(&[0; 32]);
+        gain_table.extend_from_slice(&1u32.to_be_bytes());
+        gain_table.extend_from_slice(&[0; 20 + 6 * 4]);
+
+        let mut tiff = b"II*\0\0\0\0\0".to_vec();
+        let mask = push_ifd(
+            &mut tiff,
+            &[
+                (IMAGE_WIDTH, 4, 1, long(1008)),
+                (IMAGE_LENGTH, 4, 1, long(756)),
+                (SEMANTIC_NAME, 2, 4, b"Sky\0".to_vec()),
+            ],
+        );
+        let ifd0 = push_ifd(
+            &mut tiff,
+            &[
+                (0x010F, 2, 6, b"Apple\0".to_vec()),
+                (SUB_IFDS, 4, 1, long(mask)),
+                (DNG_VERSION, 1, 4, vec![1, 6, 0, 0]),
+                (UNIQUE_CAMERA_MODEL, 2, 17, b"iPhone 15 Pro Max".to_vec()),
+                (
+                    PROFILE_GAIN_TABLE_MAP,
+                    7,
+                    gain_table.len() as u32,
+                    gain_table,
+                ),
+            ],
+        );
+        tiff[4..8].copy_from_slice(&ifd0.to_le_bytes());
+        tiff
+    }
+
+    #[test]
+    fn proraw_structure_gets_named_fields() {
+        let exif = exif::Reader::new().read_raw(proraw()).unwrap();
+        let fields: Vec<(String, String)> = computed_fields(&exif)
+            .into_iter()
+            .map(|field| (field.tag, field.value))
+            .collect();
+        let expected = [
+            ("DNGVersion", "1.6.0.0"),
+            ("UniqueCameraModel", "iPhone 15 Pro Max"),
+            ("ProfileGainTableMap", "2 × 3 points, 1 gain layer"),
+            ("SemanticMasks", "Sky (1008×756)"),
+        ]
+        .map(|(tag, value)| (tag.to_string(), value.to_string()));
+        assert_eq!(fields, expected);
+    }
+
+    #[test]
+    fn other_files_get_no_dng_fields() {
+        let exif = crate::fixtures::exif_from_fields(&[crate::fixtures::ascii_field(
+            exif::Tag::Model,
+            "EOS R5",
+        )]);
+        assert!(computed_fields(&exif).is_empty());
+        assert!(apple_fields(&exif).is_empty());
+    }
+}
@@ -0,0 +1,159 @@
+use crate::{gps::first_rational, makernote, ExifField};
+use exif::{Exif, In, Tag, Value};
+
+pub const COMPUTED_DOF_IFD: &str = "Depth of field";
+/// Circle of confusion for a full-frame (36×24 mm) sensor.
+const FULL_FRAME_COC_MM: f64 = 0.030;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DepthOfField {
+    /// Distances in metres; `far` is infinite beyond the hyperfocal distance.
+    pub near: f64,
+    pub far: f64,
+    pub hyperfocal: f64,
+}
+
+impl DepthOfField {
+    pub fn total(&self) -> f64 {
+        self.far - self.near
+    }
+}
+
+/// Thin-lens depth of field for a subject `distance` metres away.
+pub fn depth_of_field(focal_mm: f64, f_number: f64, coc_mm: f64, distance: f64) -> DepthOfField {
+    let hyperfocal_mm = focal_mm * focal_mm / (f_number * coc_mm) + focal_mm;
+    let hyperfocal = hyperfocal_mm / 1000.0;
+    if distance.is_infinite() {
+        return DepthOfField {
+            near: hyperfocal,
+            far: f64::INFINITY,
+            hyperfocal,
+        };
+    }
+
+    let distance_mm = distance * 1000.0;
+    let near =
+        distance_mm * (hyperfocal_mm - focal_mm) / (hyperfocal_mm + distance_mm - 2.0 * focal_mm);
+    let far = if distance_mm < hyperfocal_mm {
+        distance_mm * (hyperfocal_mm - focal_mm) / (hyperfocal_mm - distance_mm)
+    } else {
+        f64::INFINITY
+    };
+    DepthOfField {
+        near: near / 1000.0,
+        far: far / 1000.0,
+        hyperfocal,
+    }
+}
+
+/// Focus distance from the MakerNote, falling back to the standard
+/// SubjectDistance tag (where `0xFFFFFFFF` means infinity).
+fn focus_distance(exif: &Exif) -> Option<f64> {
+    makernote::focus_distance(exif).or_else(|| {
+        let field = exif.get_field(Tag::SubjectDistance, In::PRIMARY)?;
+        if let Value::Rational(values) = &field.value {
+            if values.first()?.num == u32::MAX {
+                return Some(f64::INFINITY);
+            }
+        }
+        first_rational(exif, Tag::SubjectDistance).filter(|distance| *distance > 0.0)
+    })
+}
+
+/// Scales the full-frame circle of confusion by the crop factor implied by
+/// the 35 mm equivalent focal length, assuming full frame when it is absent.
+fn circle_of_confusion(exif: &Exif, focal_mm: f64) -> f64 {
+    exif.get_field(Tag::FocalLengthIn35mmFilm, In::PRIMARY)
+        .and_then(|field| field.value.get_uint(0))
+        .filter(|equivalent| *equivalent > 0)
+        .map(|equivalent| FULL_FRAME_COC_MM * focal_mm / equivalent as f64)
+        .unwrap_or(FULL_FRAME_COC_MM)
+}
+
+fn format_distance(metres: f64) -> String {
+    if metres.is_infinite() {
+        "∞".to_string()
+    } else if metres < 1.0 {
+        format!("{:.0} cm", metres * 100.0)
+    } else {
+        format!("{:.2} m", metres)
+    }
+}
+
+pub fn computed_fields(exif: &Exif) -> Vec<ExifField> {
+    let (Some(distance), Some(focal_mm), Some(f_number)) = (
+        focus_distance(exif),
+        first_rational(exif, Tag::FocalLength).filter(|focal| *focal > 0.0),
+        first_rational(exif, Tag::FNumber).filter(|aperture| *aperture > 0.0),
+    ) else {
+        return Vec::new();
+    };
+    let coc = circle_of_confusion(exif, focal_mm);
+    let dof = depth_of_field(focal_mm, f_number, coc, distance);
+
+    [
+        ("Focus distance", format_distance(distance)),
+        ("Near limit", format_distance(dof.near)),
+        ("Far limit", format_distance(dof.far)),
+        ("Total", format_distance(dof.total())),
+        ("Hyperfocal distance", format_distance(dof.hyperfocal)),
+        ("Circle of confusion", format!("{:.3} mm", coc)),
+    ]
+    .into_iter()
+    .map(|(tag, value)| ExifField {
+        tag: tag.to_string(),
+        ifd: COMPUTED_DOF_IFD.to_string(),
+        value,
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{exif_from_fields, rational_field};
+    use exif::Field;
+
+    #[test]
+    fn fifty_millimetre_at_f8_matches_published_tables() {
+        let dof = depth_of_field(50.0, 8.0, 0.030, 5.0);
+        assert!((dof.hyperfocal - 10.47).abs() < 0.01, "{:?}", dof);
+        assert!((dof.near - 3.39).abs() < 0.01, "{:?}", dof);
+        assert!((dof.far - 9.53).abs() < 0.01, "{:?}", dof);
+
+        let beyond = depth_of_field(50.0, 8.0, 0.030, 20.0);
+        assert!(beyond.far.is_infinite());
+    }
+
+    #[test]
+    fn subject_distance_and_crop_factor_feed_the_computed_group() {
+        let exif = exif_from_fields(&[
+            rational_field(Tag::SubjectDistance, &[(3, 1)]),
+            rational_field(Tag::FocalLength, &[(35, 1)]),
+            rational_field(Tag::FNumber, &[(28, 10)]),
+            Field {
+                tag: Tag::FocalLengthIn35mmFilm,
+                ifd_num: In::PRIMARY,
+                value: Value::Short(vec![52]),
+            },
+        ]);
+
+        let fields = computed_fields(&exif);
+        let value = |tag: &str| {
+            fields
+                .iter()
+                .find(|field| field.tag == tag)
+                .map(|field| field.value.clone())
+        };
+        assert_eq!(value("Focus distance").as_deref(), Some("3.00 m"));
+        assert_eq!(value("Circle of confusion").as_deref(), Some("0.020 mm"));
+        assert!(fields.iter().all(|field| field.ifd == COMPUTED_DOF_IFD));
+
+        let infinity = exif_from_fields(&[
+            rational_field(Tag::SubjectDistance, &[(u32::MAX, 1)]),
+            rational_field(Tag::FocalLength, &[(35, 1)]),
+            rational_field(Tag::FNumber, &[(28, 10)]),
+        ]);
+        assert_eq!(focus_distance(&infinity), Some(f64::INFINITY));
+    }
+}
@@ -0,0 +1,363 @@
+use crate::{
+    capture::capture_time,
+    commit::{commit_xmp, EditTargets},
+    oplog::FieldChange,
+    settings::EditMode,
+    verify::WriteVerification,
+    virtual_fields::{VirtualField, VirtualFieldStore},
+    write_lock::{FileStamp, WriteError, WriteProblemReport},
+    xmp,
+};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::path::{Component, Path};
+
+/// Virtual field source for albums and events kept in the app's index.
+pub const EVENT_SOURCE: &str = "Album";
+const ALBUM_PROPERTY: &str = "xmpDM:album";
+const EVENT_PROPERTY: &str = "Iptc4xmpExt:Event";
+
+/// Where inferred albums and events are recorded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum EventTarget {
+    /// Virtual fields in the app's index; files are not touched.
+    #[default]
+    Index,
+    /// The XMP of JPEG files, as `xmpDM:album` and `Iptc4xmpExt:Event`.
+    Xmp,
+}
+
+/// Album, event and date read from the folders above a file.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InferredEvent {
+    /// The topmost named folder below the scanned root.
+    pub album: Option<String>,
+    /// The innermost named folder.
+    pub event: Option<String>,
+    /// `YYYY`, `YYYY-MM` or `YYYY-MM-DD` from the folder names, else the
+    /// capture day.
+    pub date: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EventTagging {
+    path: String,
+    #[serde(flatten)]
+    inferred: InferredEvent,
+    written: bool,
+    /// Recorded in the index, by request or because originals are protected.
+    indexed: bool,
+    error: Option<String>,
+    problem: Option<WriteProblemReport>,
+    verification: Option<WriteVerification>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EventHit {
+    path: String,
+    album: Option<String>,
+    event: Option<String>,
+    date: Option<String>,
+}
+
+fn is_separator(c: char) -> bool {
+    matches!(c, '-' | '_' | '.' | ' ' | ',')
+}
+
+/// Splits a folder name such as `2024-06-01 Porto Marathon`, `20240601_Trip`
+/// or `06 June` into its leading numbers and the name after them.
+fn split_name(component: &str) -> (Vec<&str>, &str) {
+    let mut numbers = Vec::new();
+    let mut rest = component.trim();
+    loop {
+        let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        if digits == 0 {
+            break;
+        }
+        let (number, after) = rest.split_at(digits);
+        if number.len() == 8 && numbers.is_empty() {
+            numbers.extend([&number[..4], &number[4..6], &number[6..]]);
+        } else {
+            numbers.push(number);
+        }
+        let trimmed = after.trim_start_matches(is_separator);
+        if trimmed.len() == after.len() && !after.is_empty() {
+            // `2024x`: the number is part of the name.
+            numbers.pop();
+            return (numbers, rest);
+        }
+        rest = trimmed;
+    }
+    (numbers, rest.trim_matches(is_separator))
+}
+
+/// Extends `date` (year, month, day) with the numbers of one folder name;
+/// a four-digit year starts a new date.
+fn extend_date(date: &mut Vec<u32>, numbers: &[&str]) -> bool {
+    let mut candidate = date.clone();
+    for number in numbers {
+        let Ok(value) = number.parse::<u32>() else {
+            return false;
+        };
+        match (number.len(), candidate.len()) {
+            (4, _) if (1800..=2200).contains(&value) => candidate = vec![value],
+            (1 | 2, 1) if (1..=12).contains(&value) => candidate.push(value),
+            (1 | 2, 2) if (1..=31).contains(&value) => candidate.push(value),
+            _ => return false,
+        }
+    }
+    if let [year, month, day] = candidate[..] {
+        if NaiveDate::from_ymd_opt(year as i32, month, day).is_none() {
+            return false;
+        }
+    }
+    *date = candidate;
+    true
+}
+
+/// Reads album, event and date from the folders between `root` and `path`.
+pub fn infer(root: &Path, path: &Path) -> InferredEvent {
+    let folders = path
+        .parent()
+        .and_then(|parent| parent.strip_prefix(root).ok())
+        .map(|relative| {
+            relative
+                .components()
+                .filter_map(|component| match component {
+                    Component::Normal(name) => Some(name.to_string_lossy().into_owned()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    let mut date = Vec::new();
+    let mut names = Vec::new();
+    for folder in &folders {
+        let (numbers, name) = split_name(folder);
+        let name = if extend_date(&mut date, &numbers) {
+            name
+        } else {
+            folder.as_str()
+        };
+        // A month folder such as `06 June` names nothing new.
+        let is_month = numbers.len() == 1 && date.len() == 2;
+        if !name.is_empty() && !is_month {
+            names.push(name.to_string());
+        }
+    }
+    InferredEvent {
+        album: names.first().cloned(),
+        event: names.last().cloned(),
+        date: match date[..] {
+            [] => None,
+            [year] => Some(format!("{:04}", year)),
+            [year, month] => Some(format!("{:04}-{:02}", year, month)),
+            [year, month, day, ..] => Some(format!("{:04}-{:02}-{:02}", year, month, day)),
+        },
+    }
+}
+
+fn virtual_fields(inferred: &InferredEvent) -> Vec<VirtualField> {
+    [
+        ("Album", &inferred.album),
+        ("Event", &inferred.event),
+        ("EventDate", &inferred.date),
+    ]
+    .into_iter()
+    .filter_map(|(tag, value)| {
+        Some(VirtualField {
+            source: EVENT_SOURCE.to_string(),
+            tag: tag.to_string(),
+            value: value.clone()?,
+        })
+    })
+    .collect()
+}
+
+fn write_xmp(
+    path: &Path,
+    inferred: &InferredEvent,
+    targets: &EditTargets,
+) -> Result<WriteVerification, WriteError> {
+    let stamp = FileStamp::of(path)?;
+    let data = crate::load_file_data(path)?;
+    let packet = xmp::jpeg_packet(&data);
+    let properties: Vec<(&str, &str)> = [
+        (ALBUM_PROPERTY, &inferred.album),
+        (EVENT_PROPERTY, &inferred.event),
+    ]
+    .into_iter()
+    .filter_map(|(name, value)| Some((name, value.as_deref()?)))
+    .collect();
+    let changes = properties
+        .iter()
+        .map(|(name, value)| FieldChange {
+            tag: name.to_string(),
+            before: packet.and_then(|packet| existing_value(packet, name)),
+            after: Some(value.to_string()),
+        })
+        .collect();
+    let packet = xmp::with_properties(packet, &properties)?;
+    commit_xmp(
+        targets,
+        "infer_events",
+        path,
+        stamp,
+        &data,
+        &packet,
+        changes,
+    )
+}
+
+fn existing_value(packet: &str, name: &str) -> Option<String> {
+    xmp::items(packet, name)
+        .first()
+        .copied()
+        .or_else(|| xmp::property(packet, name))
+        .map(str::to_string)
+}
+
+/// Infers the album and event of `path` from the folders below `root` and
+/// records them where `target` says. XMP is only written into JPEG files,
+/// and never in [`EditMode::ExternalOnly`], where the index is used instead.
+pub fn tag_file(
+    root: &Path,
+    path: &Path,
+    target: EventTarget,
+    dry_run: bool,
+    targets: &EditTargets,
+) -> EventTagging {
+    let mut inferred = infer(root, path);
+    let mut tagging = EventTagging {
+        path: path.to_string_lossy().into_owned(),
+        inferred: InferredEvent::default(),
+        written: false,
+        indexed: false,
+        error: None,
+        problem: None,
+        verification: None,
+    };
+
+    let result = (|| -> Result<(), WriteError> {
+        let data = crate::load_file_data(path)?;
+        if inferred.date.is_none() {
+            inferred.date = crate::read_exif_container(&data)
+                .and_then(|exif| capture_time(&exif))
+                .map(|time| time.local.format("%Y-%m-%d").to_string());
+        }
+        if dry_run || (inferred.album.is_none() && inferred.event.is_none()) {
+            return Ok(());
+        }
+        let in_file = target == EventTarget::Xmp && targets.mode == EditMode::InPlace;
+        if in_file && crate::jpeg::is_jpeg(&data) {
+            tagging.verification = Some(write_xmp(path, &inferred, targets)?);
+            tagging.written = true;
+        } else {
+            targets
+                .external
+                .replace_source(path, EVENT_SOURCE, virtual_fields(&inferred))?;
+            tagging.indexed = true;
+        }
+        Ok(())
+    })();
+
+    tagging.inferred = inferred;
+    if let Err(error) = result {
+        tagging.error = Some(error.message());
+        tagging.problem = error.report();
+    }
+    tagging
+}
+
+/// The album and event recorded for `path`, from the index or its XMP.
+fn recorded(path: &Path, store: &VirtualFieldStore) -> Option<EventHit> {
+    let indexed = store.fields_for(path);
+    let from_index = |tag: &str| {
+        indexed
+            .iter()
+            .find(|field| field.source == EVENT_SOURCE && field.tag == tag)
+            .map(|field| field.value.clone())
+    };
+    let data = crate::memory_budget::load_scan_data(path).ok();
+    let packet = data.as_deref().and_then(xmp::packet);
+    let from_xmp = |name: &str| packet.and_then(|packet| existing_value(packet, name));
+    let hit = EventHit {
+        path: path.to_string_lossy().into_owned(),
+        album: from_index("Album").or_else(|| from_xmp(ALBUM_PROPERTY)),
+        event: from_index("Event").or_else(|| from_xmp(EVENT_PROPERTY)),
+        date: from_index("EventDate"),
+    };
+    (hit.album.is_some() || hit.event.is_some()).then_some(hit)
+}
+
+/// Images below `root` whose album, event or date contains every word of
+/// `query`, ignoring case.
+pub fn search(root: &Path, store: &VirtualFieldStore, query: &str) -> Vec<EventHit> {
+    let words: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+    let mut hits = Vec::new();
+    crate::walk_files(root, &mut |path| {
+        if !crate::is_supported_image(path) {
+            return;
+        }
+        let Some(hit) = recorded(path, store) else {
+            return;
+        };
+        let haystack = [&hit.album, &hit.event, &hit.date]
+            .into_iter()
+            .flatten()
+            .cloned()
+            .collect::<Vec<_>>()
+            .join("\n")
+            .to_lowercase();
+        if words.iter().all(|word| haystack.contains(word.as_str())) {
+            hits.push(hit);
+        }
+    });
+    hits.sort_by(|a, b| a.path.cmp(&b.path));
+    hits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inferred(relative: &str) -> (Option<String>, Option<String>, Option<String>) {
+        let root = Path::new("/photos");
+        let event = infer(root, &root.join(relative));
+        (event.album, event.event, event.date)
+    }
+
+    fn some(
+        album: &str,
+        event: &str,
+        date: Option<&str>,
+    ) -> (Option<String>, Option<String>, Option<String>) {
+        (
+            Some(album.to_string()),
+            Some(event.to_string()),
+            date.map(str::to_string),
+        )
+    }
+
+    #[test]
+    fn folder_names_give_album_event_and_date() {
+        assert_eq!(
+            inferred("2024/06 June/2024-06-01 Porto Marathon/IMG_1.jpg"),
+            some("Porto Marathon", "Porto Marathon", Some("2024-06-01"))
+        );
+        assert_eq!(
+            inferred("Travel/20230814_Lisbon/Day 2/a.jpg"),
+            some("Travel", "Day 2", Some("2023-08-14"))
+        );
+        assert_eq!(
+            inferred("2019/05/Wedding/a.jpg"),
+            some("Wedding", "Wedding", Some("2019-05"))
+        );
+        assert_eq!(inferred("a.jpg"), (None, None, None));
+    }
+}
@@ -0,0 +1,340 @@
+use crate::{
+    jpeg::{self, APP0, APP1, EXIF_HEADER, SOI},
+    thumbnail::embedded_thumbnail,
+};
+use exif::{experimental::Writer, Context, Exif, Field, In, Reader, Tag, Value};
+use std::io::Cursor;
+
+/// Sets (or with `value: None`, removes) a tag in the primary IFD.
+#[derive(Debug, Clone)]
+pub struct FieldEdit {
+    pub tag: Tag,
+    pub value: Option<Value>,
+}
+
+impl FieldEdit {
+    pub fn ascii(tag: Tag, text: &str) -> Self {
+        FieldEdit {
+            tag,
+            value: Some(Value::Ascii(vec![text.as_bytes().to_vec()])),
+        }
+    }
+}
+
+/// Points at IFDs the encoder cannot rebuild, as in DNG and most raws.
+const SUB_IFDS: Tag = Tag(Context::Tiff, 0x014A);
+
+pub fn is_tiff(data: &[u8]) -> bool {
+    data.starts_with(b"II*\0") || data.starts_with(b"MM\0*")
+}
+
+/// [`apply_jpeg_edits`] or [`apply_tiff_edits`], whichever fits `data`.
+pub fn apply_edits(data: &[u8], edits: &[FieldEdit]) -> Result<Vec<u8>, String> {
+    if jpeg::is_jpeg(data) {
+        apply_jpeg_edits(data, edits)
+    } else if is_tiff(data) {
+        apply_tiff_edits(data, edits)
+    } else {
+        Err("Only JPEG and TIFF files can be written.".to_string())
+    }
+}
+
+/// Re-encodes the EXIF block of a JPEG with `edits` applied.
+///
+/// Only the APP1 segment is rebuilt; every other segment and the compressed
+/// image data are copied byte for byte. MakerNote blobs are carried over
+/// unchanged, so vendor notes that use absolute offsets may not survive the
+/// move, which is the same trade-off most metadata editors make.
+pub fn apply_jpeg_edits(data: &[u8], edits: &[FieldEdit]) -> Result<Vec<u8>, String> {
+    rebuild_exif(data, edits, None)
+}
+
+/// Re-encodes the EXIF block of a JPEG with `thumbnail` as its IFD1
+/// thumbnail, replacing any existing one and marking it JPEG-compressed.
+pub fn replace_jpeg_thumbnail(data: &[u8], thumbnail: &[u8]) -> Result<Vec<u8>, String> {
+    rebuild_exif(data, &[], Some(thumbnail))
+}
+
+/// Rebuilds APP1 with `edits` applied, keeping the existing thumbnail
+/// unless a `replacement` is given.
+fn rebuild_exif(
+    data: &[u8],
+    edits: &[FieldEdit],
+    replacement: Option<&[u8]>,
+) -> Result<Vec<u8>, String> {
+    let (segments, scan_start) = jpeg::segments(data)?;
+    let existing = segments.iter().find(|segment| segment.is_exif(data));
+
+    let (mut fields, little_endian, thumbnail) = match existing {
+        Some(segment) => {
+            let tiff = segment.payload(data)[EXIF_HEADER.len()..].to_vec();
+            let exif = Reader::new()
+                .read_raw(tiff)
+                .map_err(|error| format!("The existing EXIF block is unreadable: {}", error))?;
+            let fields: Vec<Field> = exif.fields().cloned().collect();
+            let thumbnail = embedded_thumbnail(&exif).map(<[u8]>::to_vec);
+            (fields, exif.little_endian(), thumbnail)
+        }
+        None => (Vec::new(), false, None),
+    };
+
+    apply_field_edits(&mut fields, edits);
+
+    if replacement.is_some() {
+        fields.retain(|field| !(field.tag == Tag::Compression && field.ifd_num == In::THUMBNAIL));
+        fields.push(Field {
+            tag: Tag::Compression,
+            ifd_num: In::THUMBNAIL,
+            value: Value::Short(vec![6]),
+        });
+    }
+    let thumbnail = replacement.or(thumbnail.as_deref());
+
+    let mut payload = EXIF_HEADER.to_vec();
+    payload.extend(encode_tiff(&fields, thumbnail, little_endian)?);
+    let app1 = jpeg::encode_segment(APP1, &payload)?;
+
+    let mut output = Vec::with_capacity(data.len() + app1.len());
+    output.extend_from_slice(&SOI);
+    let mut inserted = false;
+    for segment in &segments {
+        if Some(segment) == existing {
+            output.extend_from_slice(&app1);
+            inserted = true;
+            continue;
+        }
+        if !inserted && segment.marker != APP0 {
+            output.extend_from_slice(&app1);
+            inserted = true;
+        }
+        output.extend_from_slice(&data[segment.start..segment.end]);
+    }
+    if !inserted {
+        output.extend_from_slice(&app1);
+    }
+    output.extend_from_slice(&data[scan_start..]);
+    Ok(output)
+}
+
+fn apply_field_edits(fields: &mut Vec<Field>, edits: &[FieldEdit]) {
+    for edit in edits {
+        fields.retain(|field| !(field.tag == edit.tag && field.ifd_num == In::PRIMARY));
+        if let Some(value) = &edit.value {
+            fields.push(Field {
+                tag: edit.tag,
+                ifd_num: In::PRIMARY,
+                value: value.clone(),
+            });
+        }
+    }
+}
+
+/// Where an IFD of a TIFF keeps its pixels.
+#[derive(Debug, PartialEq)]
+pub enum ImageData<'a> {
+    Strips(Vec<&'a [u8]>),
+    Tiles(Vec<&'a [u8]>),
+    Jpeg(&'a [u8]),
+}
+
+/// The byte ranges named by an offsets tag and its byte-counts tag.
+fn chunks<'a>(
+    data: &'a [u8],
+    exif: &Exif,
+    ifd: In,
+    offsets: Tag,
+    counts: Tag,
+) -> Result<Option<Vec<&'a [u8]>>, String> {
+    let uints = |tag| {
+        exif.get_field(tag, ifd)
+            .and_then(|field| field.value.iter_uint())
+            .map(|values| values.map(|value| value as usize).collect::<Vec<_>>())
+    };
+    let (Some(offsets), Some(counts)) = (uints(offsets), uints(counts)) else {
+        return Ok(None);
+    };
+    offsets
+        .iter()
+        .zip(&counts)
+        .map(|(&offset, &count)| {
+            data.get(offset..offset.saturating_add(count))
+                .ok_or_else(|| "The TIFF image data is truncated.".to_string())
+        })
+        .collect::<Result<_, _>>()
+        .map(Some)
+}
+
+/// The pixels of each IFD of a TIFF, which a metadata rewrite must carry
+/// over unchanged.
+pub fn tiff_image_data<'a>(
+    data: &'a [u8],
+    exif: &Exif,
+) -> Result<Vec<(In, ImageData<'a>)>, String> {
+    let mut ifds: Vec<In> = Vec::new();
+    for field in exif.fields() {
+        if !ifds.contains(&field.ifd_num) {
+            ifds.push(field.ifd_num);
+        }
+    }
+    let mut images = Vec::new();
+    for ifd in ifds {
+        if let Some(strips) = chunks(data, exif, ifd, Tag::StripOffsets, Tag::StripByteCounts)? {
+            images.push((ifd, ImageData::Strips(strips)));
+        } else if let Some(tiles) = chunks(data, exif, ifd, Tag::TileOffsets, Tag::TileByteCounts)?
+        {
+            images.push((ifd, ImageData::Tiles(tiles)));
+        } else if let Some(mut jpeg) = chunks(
+            data,
+            exif,
+            ifd,
+            Tag::JPEGInterchangeFormat,
+            Tag::JPEGInterchangeFormatLength,
+        )? {
+            images.push((ifd, ImageData::Jpeg(jpeg.remove(0))));
+        }
+    }
+    Ok(images)
+}
+
+/// Rewrites a TIFF with `edits` applied to its first IFD. The file is laid
+/// out afresh, so only baseline TIFFs are accepted: SubIFDs, as in DNG, and
+/// the MakerNotes of raws, whose offsets count from the start of the file,
+/// would be left pointing at the old layout. The pixels of every IFD are
+/// copied byte for byte.
+pub fn apply_tiff_edits(data: &[u8], edits: &[FieldEdit]) -> Result<Vec<u8>, String> {
+    let exif = Reader::new()
+        .read_raw(data.to_vec())
+        .map_err(|error| format!("The TIFF structure is unreadable: {}", error))?;
+    if exif
+        .fields()
+        .any(|field| field.tag == SUB_IFDS || field.tag == Tag::MakerNote)
+    {
+        return Err(
+            "This looks like a raw or DNG file, whose layout cannot be rewritten safely; only plain TIFFs can be edited."
+                .to_string(),
+        );
+    }
+    let images = tiff_image_data(data, &exif)?;
+    let mut fields: Vec<Field> = exif.fields().cloned().collect();
+    apply_field_edits(&mut fields, edits);
+
+    let mut writer = Writer::new();
+    for field in &fields {
+        writer.push_field(field);
+    }
+    for (ifd, image) in &images {
+        match image {
+            ImageData::Strips(strips) => writer.set_strips(strips, *ifd),
+            ImageData::Tiles(tiles) => writer.set_tiles(tiles, *ifd),
+            ImageData::Jpeg(jpeg) => writer.set_jpeg(jpeg, *ifd),
+        }
+    }
+    let mut buffer = Cursor::new(Vec::new());
+    writer
+        .write(&mut buffer, exif.little_endian())
+        .map_err(|error| format!("Unable to encode the TIFF: {}", error))?;
+    Ok(buffer.into_inner())
+}
+
+fn encode_tiff(
+    fields: &[Field],
+    thumbnail: Option<&[u8]>,
+    little_endian: bool,
+) -> Result<Vec<u8>, String> {
+    let mut writer = Writer::new();
+    for field in fields {
+        writer.push_field(field);
+    }
+    if let Some(thumbnail) = thumbnail {
+        writer.set_jpeg(thumbnail, In::THUMBNAIL);
+    }
+    let mut buffer = Cursor::new(Vec::new());
+    writer
+        .write(&mut buffer, little_endian)
+        .map_err(|error| format!("Unable to encode the EXIF block: {}", error))?;
+    Ok(buffer.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::read_exif_container;
+
+    fn minimal_jpeg() -> Vec<u8> {
+        let mut data = SOI.to_vec();
+        data.extend(jpeg::encode_segment(APP0, b"JFIF\0\x01\x02\0\0\x01\0\x01\0\0").unwrap());
+        data.extend_from_slice(&[0xFF, jpeg::SOS, 0x00, 0x02, 0xAB, 0xCD, 0xFF, 0xD9]);
+        data
+    }
+
+    #[test]
+    fn edits_insert_exif_after_jfif_and_keep_scan_data() {
+        let original = minimal_jpeg();
+        let edited = apply_jpeg_edits(
+            &original,
+            &[FieldEdit::ascii(Tag::OffsetTimeOriginal, "+02:00")],
+        )
+        .expect("edit should succeed");
+
+        let (segments, _) = jpeg::segments(&edited).expect("edited JPEG should parse");
+        assert_eq!(segments[0].marker, APP0);
+        assert!(segments[1].is_exif(&edited));
+        assert!(edited.ends_with(&[0xAB, 0xCD, 0xFF, 0xD9]));
+
+        let exif = read_exif_container(&edited).expect("EXIF should be readable");
+        let field = exif
+            .get_field(Tag::OffsetTimeOriginal, In::PRIMARY)
+            .expect("offset should be written");
+        assert_eq!(field.display_value().to_string(), "\"+02:00\"");
+    }
+
+    #[test]
+    fn removing_a_tag_keeps_the_others() {
+        let with_tags = apply_jpeg_edits(
+            &minimal_jpeg(),
+            &[
+                FieldEdit::ascii(Tag::Artist, "Ada"),
+                FieldEdit::ascii(Tag::Copyright, "CC-BY"),
+            ],
+        )
+        .unwrap();
+        let edited = apply_jpeg_edits(
+            &with_tags,
+            &[FieldEdit {
+                tag: Tag::Artist,
+                value: None,
+            }],
+        )
+        .unwrap();
+
+        let exif = read_exif_container(&edited).expect("EXIF should be readable");
+        assert!(exif.get_field(Tag::Artist, In::PRIMARY).is_none());
+        assert!(exif.get_field(Tag::Copyright, In::PRIMARY).is_some());
+    }
+
+    #[test]
+    fn tiff_edits_keep_the_strips() {
+        let width = Field {
+            tag: Tag::ImageWidth,
+            ifd_num: In::PRIMARY,
+            value: Value::Long(vec![2]),
+        };
+        let strips: [&[u8]; 2] = [b"\x01\x02", b"\x03\x04"];
+        let mut writer = Writer::new();
+        writer.push_field(&width);
+        writer.set_strips(&strips, In::PRIMARY);
+        let mut buffer = Cursor::new(Vec::new());
+        writer.write(&mut buffer, true).unwrap();
+        let original = buffer.into_inner();
+
+        let edited = apply_edits(&original, &[FieldEdit::ascii(Tag::Artist, "Ada")]).unwrap();
+        let exif = Reader::new().read_raw(edited.clone()).unwrap();
+        assert!(exif.little_endian());
+        assert!(exif.get_field(Tag::Artist, In::PRIMARY).is_some());
+        assert_eq!(
+            tiff_image_data(&edited, &exif).unwrap(),
+            [(In::PRIMARY, ImageData::Strips(strips.to_vec()))]
+        );
+        assert!(apply_edits(b"GIF89a", &[]).is_err());
+    }
+}
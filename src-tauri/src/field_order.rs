@@ -0,0 +1,119 @@
+use crate::ExifField;
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::HashMap;
+
+/// Stable identifier of a field within one `read_exif` result:
+/// `{ifd}/{tag}`, with `#2`, `#3`, ... appended when a tag repeats in the
+/// same group (PNG text chunks may). Only repeats count, so fields a new
+/// parser adds to other groups never shift an existing ID.
+pub fn field_ids(fields: &[ExifField]) -> Vec<String> {
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    fields
+        .iter()
+        .map(|field| {
+            let base = format!("{}/{}", field.ifd, field.tag);
+            let count = seen.entry(base.clone()).or_default();
+            *count += 1;
+            match *count {
+                1 => base,
+                repeat => format!("{}#{}", base, repeat),
+            }
+        })
+        .collect()
+}
+
+/// The canonical field order: by group, then tag, both compared as bytes.
+/// The sort is stable, so a tag that repeats in a group keeps the order it
+/// has in the file and with it its `#n` ID.
+pub fn sort_canonical(fields: &mut [ExifField]) {
+    fields.sort_by(|a, b| a.ifd.cmp(&b.ifd).then_with(|| a.tag.cmp(&b.tag)));
+}
+
+/// Fields as the frontend receives them: each one carries its
+/// [`field_ids`] ID as `id`, beside `tag`, `ifd` and `value`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(transparent)]
+pub struct FieldList(pub Vec<ExifField>);
+
+impl std::ops::Deref for FieldList {
+    type Target = Vec<ExifField>;
+
+    fn deref(&self) -> &Vec<ExifField> {
+        &self.0
+    }
+}
+
+impl Serialize for FieldList {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        struct Identified<'a> {
+            id: String,
+            #[serde(flatten)]
+            field: &'a ExifField,
+        }
+        serializer.collect_seq(
+            field_ids(&self.0)
+                .into_iter()
+                .zip(&self.0)
+                .map(|(id, field)| Identified { id, field }),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(ifd: &str, tag: &str, value: &str) -> ExifField {
+        ExifField {
+            tag: tag.to_string(),
+            ifd: ifd.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    #[test]
+    fn new_groups_do_not_move_existing_ids() {
+        let mut before = vec![
+            field("PNG tEXt", "Comment", "first"),
+            field("In(0)", "Model", "\"X100V\""),
+            field("PNG tEXt", "Comment", "second"),
+        ];
+        let mut after = before.clone();
+        after.insert(1, field("ICC Profile", "Description", "sRGB"));
+        after.push(field("In(0)", "Make", "\"Fujifilm\""));
+        sort_canonical(&mut before);
+        sort_canonical(&mut after);
+
+        let ids = |fields: &[ExifField]| -> Vec<(String, String)> {
+            field_ids(fields)
+                .into_iter()
+                .zip(fields.iter().map(|field| field.value.clone()))
+                .collect()
+        };
+        let (before, after) = (ids(&before), ids(&after));
+        assert!(before.iter().all(|id| after.contains(id)));
+        assert_eq!(
+            after.iter().map(|(id, _)| id.as_str()).collect::<Vec<_>>(),
+            [
+                "ICC Profile/Description",
+                "In(0)/Make",
+                "In(0)/Model",
+                "PNG tEXt/Comment",
+                "PNG tEXt/Comment#2"
+            ]
+        );
+        assert_eq!(after[4].1, "second");
+    }
+
+    #[test]
+    fn serialized_fields_carry_their_id() {
+        let fields = FieldList(vec![field("XMP", "dc:subject", "pier")]);
+        let json = serde_json::to_value(&fields).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!([{"id": "XMP/dc:subject", "tag": "dc:subject", "ifd": "XMP", "value": "pier"}])
+        );
+        assert_eq!(serde_json::from_value::<FieldList>(json).unwrap(), fields);
+    }
+}
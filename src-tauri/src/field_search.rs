@@ -0,0 +1,87 @@
+use crate::{field_order::field_ids, field_table::plain_value, ExifField};
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+/// IDs of the fields whose tag or value contains every whitespace-separated
+/// word of `pattern`, ignoring case. An empty pattern matches everything.
+pub fn find(fields: &[ExifField], pattern: &str) -> Vec<String> {
+    let words: Vec<String> = pattern.split_whitespace().map(str::to_lowercase).collect();
+    field_ids(fields)
+        .into_iter()
+        .zip(fields)
+        .filter(|(_, field)| {
+            let haystack = format!("{}\n{}", field.tag, plain_value(&field.value)).to_lowercase();
+            words.iter().all(|word| haystack.contains(word.as_str()))
+        })
+        .map(|(id, _)| id)
+        .collect()
+}
+
+/// Keeps the fields of the file searched last, so each keystroke in the
+/// filter box doesn't re-read and re-format it. Entries are dropped once the
+/// file's modification time changes.
+#[derive(Default)]
+pub struct FieldSearchCache {
+    last: Mutex<Option<CachedFields>>,
+}
+
+struct CachedFields {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    fields: Arc<Vec<ExifField>>,
+}
+
+impl FieldSearchCache {
+    pub fn fields(
+        &self,
+        path: &Path,
+        load: impl FnOnce() -> Result<Vec<ExifField>, String>,
+    ) -> Result<Arc<Vec<ExifField>>, String> {
+        let modified = std::fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok();
+        let mut last = self.last.lock().expect("field search cache poisoned");
+        if let Some(cached) = last.as_ref() {
+            if cached.path == path && cached.modified == modified {
+                return Ok(Arc::clone(&cached.fields));
+            }
+        }
+        let fields = Arc::new(load()?);
+        *last = Some(CachedFields {
+            path: path.to_path_buf(),
+            modified,
+            fields: Arc::clone(&fields),
+        });
+        Ok(fields)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(ifd: &str, tag: &str, value: &str) -> ExifField {
+        ExifField {
+            tag: tag.to_string(),
+            ifd: ifd.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    #[test]
+    fn words_match_tags_or_values_and_repeats_get_numbered_ids() {
+        let fields = [
+            field("In(0)", "Model", "\"Canon EOS R5\""),
+            field("In(0)", "LensModel", "\"RF24-70mm F2.8 L IS USM\""),
+            field("PNG tEXt", "Comment", "canon raw"),
+            field("PNG tEXt", "Comment", "second"),
+        ];
+        assert_eq!(find(&fields, "canon"), ["In(0)/Model", "PNG tEXt/Comment"]);
+        assert_eq!(find(&fields, "MODEL f2.8"), ["In(0)/LensModel"]);
+        assert_eq!(find(&fields, "second"), ["PNG tEXt/Comment#2"]);
+        assert_eq!(find(&fields, " ").len(), 4);
+    }
+}
@@ -0,0 +1,307 @@
+use crate::{format::FormatStyle, ExifField};
+use exif::{Exif, Value};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::path::Path;
+
+/// Columns used when the caller does not pick any: the usual camera settings.
+pub const DEFAULT_MARKDOWN_COLUMNS: &[&str] = &[
+    "Model",
+    "LensModel",
+    "FocalLength",
+    "FNumber",
+    "ExposureTime",
+    "PhotographicSensitivity",
+];
+
+#[derive(Debug, Serialize)]
+pub struct MarkdownExport {
+    path: String,
+    rows: usize,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TableFormat {
+    /// Tab separated, pastes into spreadsheets as columns.
+    #[default]
+    Tsv,
+    /// An array of `{tag, group, value, display}` objects with numbers kept numeric.
+    Json,
+    Markdown,
+}
+
+/// One requested field with both its displayed text and its raw value.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SelectedField {
+    pub tag: String,
+    pub group: String,
+    pub display: String,
+    pub value: serde_json::Value,
+}
+
+/// Picks `tags` out of `fields` in the order asked for, case-insensitively.
+/// Tags the file does not carry are skipped. Values come from the EXIF record
+/// when there is one, so numbers stay numbers; computed and virtual fields
+/// only have their text.
+pub fn select_fields(
+    fields: &[ExifField],
+    exif: Option<&Exif>,
+    tags: &[String],
+) -> Vec<SelectedField> {
+    tags.iter()
+        .filter_map(|wanted| {
+            let field = fields
+                .iter()
+                .find(|field| field.tag.eq_ignore_ascii_case(wanted))?;
+            let raw = exif.and_then(|exif| {
+                exif.fields()
+                    .find(|raw| raw.tag.to_string() == field.tag)
+                    .and_then(|raw| typed_value(&raw.value))
+            });
+            Some(SelectedField {
+                tag: field.tag.clone(),
+                group: crate::report::group_title(&field.ifd).to_string(),
+                display: field.value.clone(),
+                value: raw.unwrap_or_else(|| json!(field.value)),
+            })
+        })
+        .collect()
+}
+
+/// The field's value as JSON: text, a number, or a list when the tag holds several.
+fn typed_value(value: &Value) -> Option<serde_json::Value> {
+    fn collapse(mut values: Vec<serde_json::Value>) -> Option<serde_json::Value> {
+        match values.len() {
+            0 => None,
+            1 => values.pop(),
+            _ => Some(serde_json::Value::Array(values)),
+        }
+    }
+    match value {
+        Value::Ascii(strings) => collapse(
+            strings
+                .iter()
+                .map(|bytes| json!(String::from_utf8_lossy(bytes).trim_end_matches('\0')))
+                .collect(),
+        ),
+        Value::Byte(values) => collapse(values.iter().map(|&value| json!(value)).collect()),
+        Value::Short(values) => collapse(values.iter().map(|&value| json!(value)).collect()),
+        Value::Long(values) => collapse(values.iter().map(|&value| json!(value)).collect()),
+        Value::SByte(values) => collapse(values.iter().map(|&value| json!(value)).collect()),
+        Value::SShort(values) => collapse(values.iter().map(|&value| json!(value)).collect()),
+        Value::SLong(values) => collapse(values.iter().map(|&value| json!(value)).collect()),
+        Value::Float(values) => collapse(values.iter().map(|&value| json!(value)).collect()),
+        Value::Double(values) => collapse(values.iter().map(|&value| json!(value)).collect()),
+        Value::Rational(values) => collapse(
+            values
+                .iter()
+                .filter(|value| value.denom != 0)
+                .map(|value| json!(value.to_f64()))
+                .collect(),
+        ),
+        Value::SRational(values) => collapse(
+            values
+                .iter()
+                .filter(|value| value.denom != 0)
+                .map(|value| json!(value.to_f64()))
+                .collect(),
+        ),
+        _ => None,
+    }
+}
+
+pub fn render(fields: &[SelectedField], format: TableFormat) -> Result<String, String> {
+    match format {
+        TableFormat::Tsv => {
+            let mut out = String::from("Field\tValue\n");
+            for field in fields {
+                out.push_str(&format!(
+                    "{}\t{}\n",
+                    tsv_cell(&field.tag),
+                    tsv_cell(&plain_value(&field.display))
+                ));
+            }
+            Ok(out)
+        }
+        TableFormat::Json => {
+            serde_json::to_string_pretty(fields).map_err(|error| error.to_string())
+        }
+        TableFormat::Markdown => Ok(markdown_table(
+            &["Field", "Value"],
+            fields
+                .iter()
+                .map(|field| vec![field.tag.clone(), plain_value(&field.display)])
+                .collect(),
+        )),
+    }
+}
+
+/// Drops the quotes the EXIF reader puts around text values.
+pub fn plain_value(value: &str) -> String {
+    let trimmed = value.trim();
+    match trimmed
+        .strip_prefix('"')
+        .and_then(|inner| inner.strip_suffix('"'))
+    {
+        Some(inner) => inner.to_string(),
+        None => trimmed.to_string(),
+    }
+}
+
+fn tsv_cell(text: &str) -> String {
+    text.replace(['\t', '\n', '\r'], " ")
+}
+
+/// A GitHub-flavoured Markdown table; pipes and line breaks in cells are escaped.
+pub fn markdown_table(header: &[&str], rows: Vec<Vec<String>>) -> String {
+    let cell = |text: &str| text.replace('|', "\\|").replace(['\n', '\r'], " ");
+    let mut out = format!(
+        "| {} |\n|{}|\n",
+        header
+            .iter()
+            .map(|title| cell(title))
+            .collect::<Vec<_>>()
+            .join(" | "),
+        header.iter().map(|_| " --- ").collect::<Vec<_>>().join("|")
+    );
+    for row in rows {
+        out.push_str(&format!(
+            "| {} |\n",
+            row.iter()
+                .map(|text| cell(text))
+                .collect::<Vec<_>>()
+                .join(" | ")
+        ));
+    }
+    out
+}
+
+/// Writes a Markdown table of `columns` for `root`. A single file becomes a
+/// Field/Value table; a folder becomes one row per image, sorted by path,
+/// with the file name first and blanks where a tag is missing.
+pub fn export_markdown(
+    root: &Path,
+    columns: &[String],
+    output: &Path,
+) -> Result<MarkdownExport, String> {
+    let row_for = |path: &Path| -> Option<Vec<SelectedField>> {
+        let data = crate::memory_budget::load_scan_data(path).ok()?;
+        let fields = crate::collect_fields_with_style(&data, FormatStyle::default()).ok()?;
+        Some(select_fields(
+            &fields,
+            crate::read_exif_container(&data).as_ref(),
+            columns,
+        ))
+    };
+
+    let (markdown, rows) = if root.is_file() {
+        let selected = row_for(root).unwrap_or_default();
+        (render(&selected, TableFormat::Markdown)?, selected.len())
+    } else {
+        let mut images = Vec::new();
+        crate::walk_files(root, &mut |path| {
+            if crate::is_supported_image(path) {
+                images.push(path.to_path_buf());
+            }
+        });
+        images.sort();
+        let rows: Vec<Vec<String>> = images
+            .iter()
+            .filter_map(|path| {
+                let selected = row_for(path)?;
+                let name = path
+                    .strip_prefix(root)
+                    .unwrap_or(path)
+                    .to_string_lossy()
+                    .into_owned();
+                Some(folder_row(name, columns, &selected))
+            })
+            .collect();
+        let mut header = vec!["File"];
+        header.extend(columns.iter().map(String::as_str));
+        let count = rows.len();
+        (markdown_table(&header, rows), count)
+    };
+
+    crate::write_file_atomically(output, markdown.as_bytes())?;
+    Ok(MarkdownExport {
+        path: output.to_string_lossy().into_owned(),
+        rows,
+    })
+}
+
+fn folder_row(name: String, columns: &[String], selected: &[SelectedField]) -> Vec<String> {
+    let mut row = vec![name];
+    row.extend(columns.iter().map(|column| {
+        selected
+            .iter()
+            .find(|field| field.tag.eq_ignore_ascii_case(column))
+            .map(|field| plain_value(&field.display))
+            .unwrap_or_default()
+    }));
+    row
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{ascii_field, exif_from_fields, rational_field};
+    use exif::Tag;
+
+    fn camera_settings() -> Vec<SelectedField> {
+        let exif = exif_from_fields(&[
+            rational_field(Tag::FNumber, &[(28, 10)]),
+            rational_field(Tag::ExposureTime, &[(1, 200)]),
+            ascii_field(Tag::Model, "EOS R5 | mk II"),
+        ]);
+        let fields: Vec<ExifField> = exif
+            .fields()
+            .map(|field| ExifField {
+                tag: field.tag.to_string(),
+                ifd: format!("{:?}", field.ifd_num),
+                value: field.display_value().with_unit(&exif).to_string(),
+            })
+            .collect();
+        let tags = ["exposuretime", "FNumber", "ISOSpeed", "Model"].map(String::from);
+        select_fields(&fields, Some(&exif), &tags)
+    }
+
+    #[test]
+    fn selected_fields_follow_the_requested_order_and_keep_numbers() {
+        let fields = camera_settings();
+        let tags: Vec<&str> = fields.iter().map(|field| field.tag.as_str()).collect();
+        assert_eq!(tags, ["ExposureTime", "FNumber", "Model"]);
+        assert_eq!(fields[0].value, json!(0.005));
+        assert_eq!(fields[1].value, json!(2.8));
+        assert_eq!(fields[2].value, json!("EOS R5 | mk II"));
+    }
+
+    #[test]
+    fn each_format_renders_the_same_fields() {
+        let fields = camera_settings();
+        let tsv = render(&fields, TableFormat::Tsv).unwrap();
+        assert!(tsv.starts_with("Field\tValue\nExposureTime\t1/200 s\n"));
+
+        let markdown = render(&fields, TableFormat::Markdown).unwrap();
+        assert!(markdown.starts_with("| Field | Value |\n| --- | --- |\n"));
+        assert!(markdown.contains("EOS R5 \\| mk II"));
+
+        let parsed: serde_json::Value =
+            serde_json::from_str(&render(&fields, TableFormat::Json).unwrap()).unwrap();
+        assert_eq!(parsed[1]["value"], json!(2.8));
+        assert_eq!(parsed[1]["group"], json!("Image"));
+    }
+
+    #[test]
+    fn folder_rows_leave_missing_columns_blank() {
+        let columns = ["FNumber", "LensModel"].map(String::from);
+        let row = folder_row("trip/a.jpg".to_string(), &columns, &camera_settings());
+        assert_eq!(row, ["trip/a.jpg", "f/2.8", ""]);
+        assert_eq!(
+            markdown_table(&["File", "FNumber", "LensModel"], vec![row]),
+            "| File | FNumber | LensModel |\n| --- | --- | --- |\n| trip/a.jpg | f/2.8 |  |\n"
+        );
+    }
+}
@@ -0,0 +1,272 @@
+use crate::{capture::first_ascii, xmp, ExifField};
+use exif::{Context, Exif, In, Tag, Value};
+use std::path::Path;
+
+pub const COMPUTED_FILM_IFD: &str = "Film";
+
+/// DNG's ReelName, which some scanning workflows fill with the roll.
+const REEL_NAME: Tag = Tag(Context::Tiff, 0xC789);
+
+/// XMP local names, in any namespace, that film-logging and scanning tools
+/// (AnalogExif, Exif Notes, VueScan and SilverFast presets) use.
+const ROLL_NAMES: &[&str] = &["roll", "rollid", "rollnumber", "filmroll"];
+const FRAME_NAMES: &[&str] = &["frame", "framenumber", "frameno", "exposurenumber"];
+const STOCK_NAMES: &[&str] = &["film", "filmstock", "filmname"];
+
+/// Words that precede a roll or frame number in file names and comments.
+const ROLL_WORDS: &[&str] = &["roll", "r", "film", "reel"];
+const FRAME_WORDS: &[&str] = &["frame", "fr", "f", "frm", "exp"];
+
+/// Where on a roll a scanned frame sits.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FilmFrame {
+    pub roll: Option<String>,
+    pub frame: Option<String>,
+    pub stock: Option<String>,
+    /// Where the roll and frame were found: `xmp`, `exif` or `filename`.
+    pub source: &'static str,
+}
+
+impl FilmFrame {
+    fn is_empty(&self) -> bool {
+        self.roll.is_none() && self.frame.is_none()
+    }
+}
+
+/// Every `prefix:Name` property of `packet` written as an attribute or a
+/// simple element, with the local name lowercased.
+fn xmp_properties(packet: &str) -> Vec<(String, &str)> {
+    let mut found = Vec::new();
+    let mut rest = packet;
+    while let Some(colon) = rest.find(':') {
+        let name_start = rest[..colon]
+            .trim_end_matches(|c: char| c.is_ascii_alphanumeric())
+            .len();
+        let is_element = rest[..name_start].ends_with('<');
+        let after = &rest[colon + 1..];
+        let local_end = after
+            .find(|c: char| !c.is_ascii_alphanumeric())
+            .unwrap_or(after.len());
+        let local = after[..local_end].to_ascii_lowercase();
+        let tail = &after[local_end..];
+        let value = if let Some(value) = tail.strip_prefix("=\"") {
+            value.split_once('"').map(|(value, _)| value)
+        } else if is_element {
+            tail.strip_prefix('>')
+                .and_then(|value| value.split_once('<'))
+                .map(|(value, _)| value.trim())
+                .filter(|value| !value.is_empty())
+        } else {
+            None
+        };
+        if let Some(value) = value {
+            found.push((local, value));
+        }
+        rest = tail;
+    }
+    found
+}
+
+fn from_xmp(packet: &str) -> FilmFrame {
+    let properties = xmp_properties(packet);
+    let lookup = |names: &[&str]| {
+        properties
+            .iter()
+            .find(|(local, _)| names.contains(&local.as_str()))
+            .map(|(_, value)| value.to_string())
+    };
+    FilmFrame {
+        roll: lookup(ROLL_NAMES),
+        frame: lookup(FRAME_NAMES),
+        stock: lookup(STOCK_NAMES),
+        source: "xmp",
+    }
+}
+
+/// Alternating runs of letters and digits, lowercased: `Roll12_F05` gives
+/// `roll`, `12`, `f`, `05`.
+fn tokens(text: &str) -> Vec<String> {
+    let mut tokens: Vec<String> = Vec::new();
+    for c in text.chars() {
+        let c = c.to_ascii_lowercase();
+        let same_kind = tokens
+            .last()
+            .and_then(|last| last.chars().last())
+            .is_some_and(|last| {
+                last.is_ascii_digit() == c.is_ascii_digit() && c.is_ascii_alphanumeric()
+            });
+        if !c.is_ascii_alphanumeric() {
+            tokens.push(String::new());
+        } else if same_kind {
+            tokens.last_mut().expect("checked above").push(c);
+        } else {
+            tokens.push(c.to_string());
+        }
+    }
+    tokens.retain(|token| !token.is_empty());
+    tokens
+}
+
+/// Roll and frame numbers written as `Roll 12 Frame 5`, `R12F05` or
+/// `roll12_05` (the last number after the roll is taken as the frame). A
+/// frame without a roll is ignored: `f 8` is more likely an aperture.
+fn from_text(text: &str, source: &'static str) -> FilmFrame {
+    let tokens = tokens(text);
+    let is_number = |token: &String| token.chars().all(|c| c.is_ascii_digit());
+    let number_after = |words: &[&str]| {
+        tokens
+            .windows(2)
+            .position(|pair| words.contains(&pair[0].as_str()) && is_number(&pair[1]))
+    };
+    let roll_at = number_after(ROLL_WORDS);
+    let frame = number_after(FRAME_WORDS)
+        .map(|at| tokens[at + 1].clone())
+        .or_else(|| {
+            let after_roll = roll_at? + 2;
+            tokens[after_roll..]
+                .iter()
+                .rev()
+                .find(|token| is_number(token))
+                .cloned()
+        });
+    FilmFrame {
+        roll: roll_at.map(|at| tokens[at + 1].clone()),
+        frame: frame.filter(|_| roll_at.is_some()),
+        stock: None,
+        source,
+    }
+}
+
+fn user_comment(exif: &Exif) -> Option<String> {
+    match &exif.get_field(Tag::UserComment, In::PRIMARY)?.value {
+        // Eight bytes name the character set.
+        Value::Undefined(bytes, _) => Some(
+            String::from_utf8_lossy(bytes.get(8..)?)
+                .trim_matches('\0')
+                .to_string(),
+        ),
+        _ => None,
+    }
+}
+
+/// Roll, frame and film stock from XMP first, then the EXIF description,
+/// comment and DNG reel name, then the file name.
+pub fn frame_info(path: &Path, exif: Option<&Exif>, packet: Option<&str>) -> Option<FilmFrame> {
+    let mut found = packet.map(from_xmp).unwrap_or_default();
+    let stock = found.stock.clone();
+    if found.is_empty() {
+        if let Some(exif) = exif {
+            let texts = [
+                first_ascii(exif, Tag::ImageDescription)
+                    .map(|text| String::from_utf8_lossy(text).into_owned()),
+                user_comment(exif),
+            ];
+            found = texts
+                .into_iter()
+                .flatten()
+                .map(|text| from_text(&text, "exif"))
+                .find(|frame| !frame.is_empty())
+                .unwrap_or_default();
+            if found.is_empty() {
+                if let Some(reel) = first_ascii(exif, REEL_NAME) {
+                    found.roll = Some(String::from_utf8_lossy(reel).trim_matches('\0').to_string());
+                    found.source = "exif";
+                }
+            }
+        }
+    }
+    if found.is_empty() {
+        let stem = path.file_stem()?.to_string_lossy();
+        found = from_text(&stem, "filename");
+    }
+    found.stock = found.stock.or(stock);
+    (!found.is_empty() || found.stock.is_some()).then_some(found)
+}
+
+/// [`frame_info`] for a file already loaded into `data`.
+pub fn read(path: &Path, data: &[u8]) -> Option<FilmFrame> {
+    frame_info(
+        path,
+        crate::read_exif_container(data).as_ref(),
+        xmp::packet(data),
+    )
+}
+
+pub fn computed_fields(frame: Option<&FilmFrame>) -> Vec<ExifField> {
+    let Some(frame) = frame else {
+        return Vec::new();
+    };
+    [
+        ("Roll", frame.roll.clone()),
+        ("Frame", frame.frame.clone()),
+        ("FilmStock", frame.stock.clone()),
+        (
+            "RollSource",
+            (!frame.is_empty()).then(|| frame.source.to_string()),
+        ),
+    ]
+    .into_iter()
+    .filter_map(|(tag, value)| {
+        Some(ExifField {
+            tag: tag.to_string(),
+            ifd: COMPUTED_FILM_IFD.to_string(),
+            value: value?,
+        })
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{ascii_field, exif_from_fields};
+
+    fn roll_and_frame(frame: Option<FilmFrame>) -> (Option<String>, Option<String>, &'static str) {
+        let frame = frame.unwrap_or_default();
+        (frame.roll, frame.frame, frame.source)
+    }
+
+    fn found(
+        roll: &str,
+        frame: &str,
+        source: &'static str,
+    ) -> (Option<String>, Option<String>, &'static str) {
+        (Some(roll.to_string()), Some(frame.to_string()), source)
+    }
+
+    #[test]
+    fn file_names_carry_roll_and_frame() {
+        for (name, roll, frame) in [
+            ("Roll12_Frame05.tif", "12", "05"),
+            ("R7F33.jpg", "7", "33"),
+            ("2024-03 roll 4 - 18.tif", "4", "18"),
+        ] {
+            assert_eq!(
+                roll_and_frame(frame_info(Path::new(name), None, None)),
+                found(roll, frame, "filename"),
+                "{}",
+                name
+            );
+        }
+        assert_eq!(frame_info(Path::new("IMG_0042.jpg"), None, None), None);
+    }
+
+    #[test]
+    fn metadata_wins_over_the_file_name() {
+        let packet = r#"<x:xmpmeta><rdf:Description AnalogExif:RollId="A-301"
+            AnalogExif:Film="Kodak Portra 400"><AnalogExif:Frame>14</AnalogExif:Frame>
+            </rdf:Description></x:xmpmeta>"#;
+        let from_xmp = frame_info(Path::new("Roll1_F1.tif"), None, Some(packet)).unwrap();
+        assert_eq!(
+            roll_and_frame(Some(from_xmp.clone())),
+            found("A-301", "14", "xmp")
+        );
+        assert_eq!(from_xmp.stock.as_deref(), Some("Kodak Portra 400"));
+
+        let exif = exif_from_fields(&[ascii_field(Tag::ImageDescription, "Roll 9, frame 21")]);
+        assert_eq!(
+            roll_and_frame(frame_info(Path::new("scan.tif"), Some(&exif), None)),
+            found("9", "21", "exif")
+        );
+    }
+}
@@ -0,0 +1,287 @@
+use crate::jpeg;
+use exif::{experimental::Writer, Exif, Field, In, Rational, Reader, Tag, Value};
+use flate2::{write::ZlibEncoder, Compression, Crc};
+use std::{
+    io::{Cursor, Write},
+    panic::{self, AssertUnwindSafe},
+    path::PathBuf,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Encodes `fields` into a big-endian TIFF/EXIF blob.
+pub fn tiff_from_fields(fields: &[Field]) -> Vec<u8> {
+    let mut writer = Writer::new();
+    for field in fields {
+        writer.push_field(field);
+    }
+    let mut buffer = Cursor::new(Vec::new());
+    writer
+        .write(&mut buffer, false)
+        .expect("should encode EXIF fixture");
+    buffer.into_inner()
+}
+
+/// Round-trips `fields` through the encoder so tests get a real [`Exif`].
+pub fn exif_from_fields(fields: &[Field]) -> Exif {
+    Reader::new()
+        .read_raw(tiff_from_fields(fields))
+        .expect("should decode EXIF fixture")
+}
+
+pub fn rational_field(tag: Tag, values: &[(u32, u32)]) -> Field {
+    Field {
+        tag,
+        ifd_num: In::PRIMARY,
+        value: Value::Rational(
+            values
+                .iter()
+                .map(|&(num, denom)| Rational { num, denom })
+                .collect(),
+        ),
+    }
+}
+
+pub fn ascii_field(tag: Tag, text: &str) -> Field {
+    Field {
+        tag,
+        ifd_num: In::PRIMARY,
+        value: Value::Ascii(vec![text.as_bytes().to_vec()]),
+    }
+}
+
+/// Wraps `fields` in the smallest JPEG the readers accept: SOI, an EXIF APP1
+/// segment and a stub scan.
+pub fn jpeg_from_fields(fields: &[Field]) -> Vec<u8> {
+    JpegBuilder::new().exif(fields).finish()
+}
+
+/// Builds a JPEG one marker segment at a time, in the order they are added,
+/// so layouts a camera would never write can be produced too.
+pub struct JpegBuilder {
+    data: Vec<u8>,
+}
+
+impl Default for JpegBuilder {
+    fn default() -> Self {
+        JpegBuilder {
+            data: jpeg::SOI.to_vec(),
+        }
+    }
+}
+
+impl JpegBuilder {
+    pub fn new() -> Self {
+        JpegBuilder::default()
+    }
+
+    /// Adds a segment with `marker` (`0xE0` for APP0 and so on).
+    pub fn segment(mut self, marker: u8, payload: &[u8]) -> Self {
+        self.data
+            .extend(jpeg::encode_segment(marker, payload).expect("fixture segment should fit"));
+        self
+    }
+
+    /// Adds an EXIF APP1 segment holding `fields`.
+    pub fn exif(self, fields: &[Field]) -> Self {
+        let mut payload = jpeg::EXIF_HEADER.to_vec();
+        payload.extend(tiff_from_fields(fields));
+        self.segment(jpeg::APP1, &payload)
+    }
+
+    /// Adds an XMP APP1 segment holding `packet`.
+    pub fn xmp(self, packet: &str) -> Self {
+        let mut payload = b"http://ns.adobe.com/xap/1.0/\0".to_vec();
+        payload.extend_from_slice(packet.as_bytes());
+        self.segment(jpeg::APP1, &payload)
+    }
+
+    /// The bytes so far, without a scan; for files that end early.
+    pub fn build(self) -> Vec<u8> {
+        self.data
+    }
+
+    /// The bytes so far followed by a stub scan and EOI.
+    pub fn finish(mut self) -> Vec<u8> {
+        self.data
+            .extend_from_slice(&[0xFF, jpeg::SOS, 0x00, 0x02, 0xAB, 0xCD, 0xFF, 0xD9]);
+        self.data
+    }
+}
+
+/// Builds a PNG one chunk at a time, in the order they are added and with
+/// correct CRCs. Nothing is checked, so chunks may repeat, come before
+/// `IHDR` or follow `IEND`.
+pub struct PngBuilder {
+    data: Vec<u8>,
+}
+
+impl Default for PngBuilder {
+    fn default() -> Self {
+        PngBuilder {
+            data: crate::PNG_SIGNATURE.to_vec(),
+        }
+    }
+}
+
+impl PngBuilder {
+    pub fn new() -> Self {
+        PngBuilder::default()
+    }
+
+    pub fn chunk(mut self, kind: &[u8; 4], payload: &[u8]) -> Self {
+        let mut crc = Crc::new();
+        crc.update(kind);
+        crc.update(payload);
+        self.data
+            .extend_from_slice(&(payload.len() as u32).to_be_bytes());
+        self.data.extend_from_slice(kind);
+        self.data.extend_from_slice(payload);
+        self.data.extend_from_slice(&crc.sum().to_be_bytes());
+        self
+    }
+
+    /// An `IHDR` for an 8-bit RGB image of the given size.
+    pub fn header(self, width: u32, height: u32) -> Self {
+        let mut payload = width.to_be_bytes().to_vec();
+        payload.extend_from_slice(&height.to_be_bytes());
+        payload.extend_from_slice(&[8, 2, 0, 0, 0]);
+        self.chunk(b"IHDR", &payload)
+    }
+
+    /// A `tEXt` chunk; `value` is stored as Latin-1.
+    pub fn text(self, keyword: &str, value: &str) -> Self {
+        let mut payload = latin1(keyword);
+        payload.push(0);
+        payload.extend(latin1(value));
+        self.chunk(b"tEXt", &payload)
+    }
+
+    /// A `zTXt` chunk; `value` is stored as Latin-1.
+    pub fn compressed_text(self, keyword: &str, value: &str) -> Self {
+        let mut payload = latin1(keyword);
+        payload.extend_from_slice(&[0, 0]);
+        payload.extend(deflate(&latin1(value)));
+        self.chunk(b"zTXt", &payload)
+    }
+
+    /// An `iTXt` chunk with UTF-8 `value`, compressed when `compress` is set.
+    pub fn international_text(
+        self,
+        keyword: &str,
+        language: &str,
+        translated: &str,
+        value: &str,
+        compress: bool,
+    ) -> Self {
+        let mut payload = latin1(keyword);
+        payload.extend_from_slice(&[0, compress as u8, 0]);
+        payload.extend_from_slice(language.as_bytes());
+        payload.push(0);
+        payload.extend_from_slice(translated.as_bytes());
+        payload.push(0);
+        if compress {
+            payload.extend(deflate(value.as_bytes()));
+        } else {
+            payload.extend_from_slice(value.as_bytes());
+        }
+        self.chunk(b"iTXt", &payload)
+    }
+
+    /// An `eXIf` chunk holding `fields`.
+    pub fn exif(self, fields: &[Field]) -> Self {
+        self.chunk(b"eXIf", &tiff_from_fields(fields))
+    }
+
+    /// The bytes so far, without `IEND`.
+    pub fn build(self) -> Vec<u8> {
+        self.data
+    }
+
+    /// The bytes so far followed by `IEND`.
+    pub fn finish(self) -> Vec<u8> {
+        self.chunk(b"IEND", &[]).data
+    }
+}
+
+fn latin1(text: &str) -> Vec<u8> {
+    text.chars().map(|c| c as u32 as u8).collect()
+}
+
+fn deflate(bytes: &[u8]) -> Vec<u8> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder
+        .write_all(bytes)
+        .expect("writing to a Vec cannot fail");
+    encoder.finish().expect("writing to a Vec cannot fail")
+}
+
+/// A path in the system temp folder no other test uses, ending in `name` so
+/// extensions survive. Nothing is created there.
+pub fn temp_path(name: &str) -> PathBuf {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    std::env::temp_dir().join(format!(
+        "exif_viewer_{}_{}_{}_{}",
+        std::process::id(),
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos(),
+        NEXT.fetch_add(1, Ordering::SeqCst),
+        name
+    ))
+}
+
+/// A small deterministic generator (xorshift64*) for property tests, so a
+/// failing case can be replayed from its seed.
+pub struct Seeded(u64);
+
+impl Seeded {
+    pub fn new(seed: u64) -> Self {
+        // Zero is a fixed point of xorshift.
+        Seeded(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// A number in `0..bound`; `bound` must not be zero.
+    pub fn below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+
+    pub fn bytes(&mut self, len: usize) -> Vec<u8> {
+        (0..len).map(|_| self.next_u64() as u8).collect()
+    }
+
+    /// Printable ASCII, never empty, at most `max_len` long.
+    pub fn text(&mut self, max_len: usize) -> String {
+        let len = 1 + self.below(max_len.max(1));
+        (0..len)
+            .map(|_| char::from(b' ' + self.below(95) as u8))
+            .collect()
+    }
+}
+
+/// Runs `property` for seeds `1..=cases`, naming the seed that failed.
+pub fn check_property(cases: u64, property: impl Fn(&mut Seeded)) {
+    for seed in 1..=cases {
+        let outcome = panic::catch_unwind(AssertUnwindSafe(|| property(&mut Seeded::new(seed))));
+        if let Err(payload) = outcome {
+            eprintln!("property failed for seed {}", seed);
+            panic::resume_unwind(payload);
+        }
+    }
+}
+
+/// An ISO BMFF box (also the framing JUMBF uses) holding `body`.
+pub fn bmff_box(kind: &[u8; 4], body: &[u8]) -> Vec<u8> {
+    let mut data = ((body.len() + 8) as u32).to_be_bytes().to_vec();
+    data.extend_from_slice(kind);
+    data.extend_from_slice(body);
+    data
+}
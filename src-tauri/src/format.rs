@@ -0,0 +1,136 @@
+use exif::{Exif, Field, Tag, Value};
+use serde::{Deserialize, Serialize};
+
+/// Converts a decoded EXIF field into the string shown to the user.
+///
+/// Frontends pick a formatter through [`FormatStyle`] so the typed value
+/// conversion stays in one place regardless of how terse the output must be.
+pub trait ValueFormatter {
+    fn format(&self, field: &Field, exif: &Exif) -> String;
+}
+
+/// Human-readable values with units, e.g. `1/250 s` or `f/2.8`.
+pub struct VerboseFormatter;
+
+/// Values without units, e.g. `1/250` or `2.8`.
+pub struct TerseFormatter;
+
+/// Mirrors the conventions of `exiftool` for the common exposure tags and
+/// prints other rationals as plain decimals.
+pub struct ExiftoolFormatter;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FormatStyle {
+    #[default]
+    Verbose,
+    Terse,
+    Exiftool,
+}
+
+impl FormatStyle {
+    pub fn formatter(self) -> &'static dyn ValueFormatter {
+        match self {
+            FormatStyle::Verbose => &VerboseFormatter,
+            FormatStyle::Terse => &TerseFormatter,
+            FormatStyle::Exiftool => &ExiftoolFormatter,
+        }
+    }
+}
+
+impl ValueFormatter for VerboseFormatter {
+    fn format(&self, field: &Field, exif: &Exif) -> String {
+        field.display_value().with_unit(exif).to_string()
+    }
+}
+
+impl ValueFormatter for TerseFormatter {
+    fn format(&self, field: &Field, _exif: &Exif) -> String {
+        field.display_value().to_string()
+    }
+}
+
+impl ValueFormatter for ExiftoolFormatter {
+    fn format(&self, field: &Field, exif: &Exif) -> String {
+        match field.tag {
+            Tag::ExposureTime => field.display_value().to_string(),
+            Tag::FNumber => first_rational(&field.value)
+                .map(|value| format!("{:.1}", value))
+                .unwrap_or_else(|| field.display_value().to_string()),
+            Tag::FocalLength => first_rational(&field.value)
+                .map(|value| format!("{:.1} mm", value))
+                .unwrap_or_else(|| field.display_value().with_unit(exif).to_string()),
+            _ => match &field.value {
+                Value::Rational(values) if !values.is_empty() => values
+                    .iter()
+                    .map(|value| trim_decimal(value.to_f64()))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                Value::SRational(values) if !values.is_empty() => values
+                    .iter()
+                    .map(|value| trim_decimal(value.to_f64()))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                _ => field.display_value().to_string(),
+            },
+        }
+    }
+}
+
+fn first_rational(value: &Value) -> Option<f64> {
+    match value {
+        Value::Rational(values) => values.first().map(|value| value.to_f64()),
+        Value::SRational(values) => values.first().map(|value| value.to_f64()),
+        _ => None,
+    }
+}
+
+fn trim_decimal(value: f64) -> String {
+    if !value.is_finite() {
+        return "undef".to_string();
+    }
+    let formatted = format!("{:.4}", value);
+    formatted
+        .trim_end_matches('0')
+        .trim_end_matches('.')
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{exif_from_fields, rational_field};
+    use exif::In;
+
+    #[test]
+    fn styles_format_the_same_field_differently() {
+        let exif = exif_from_fields(&[
+            rational_field(Tag::FNumber, &[(28, 10)]),
+            rational_field(Tag::FocalLength, &[(50, 1)]),
+            rational_field(Tag::ExposureTime, &[(1, 250)]),
+        ]);
+        let fnumber = exif
+            .get_field(Tag::FNumber, In::PRIMARY)
+            .expect("fixture should contain FNumber");
+        let focal = exif
+            .get_field(Tag::FocalLength, In::PRIMARY)
+            .expect("fixture should contain FocalLength");
+
+        assert_eq!(
+            FormatStyle::Verbose.formatter().format(fnumber, &exif),
+            "f/2.8"
+        );
+        assert_eq!(FormatStyle::Terse.formatter().format(fnumber, &exif), "2.8");
+        assert_eq!(
+            FormatStyle::Exiftool.formatter().format(focal, &exif),
+            "50.0 mm"
+        );
+    }
+
+    #[test]
+    fn exiftool_style_trims_plain_rationals() {
+        assert_eq!(trim_decimal(0.5), "0.5");
+        assert_eq!(trim_decimal(72.0), "72");
+        assert_eq!(trim_decimal(f64::NAN), "undef");
+    }
+}
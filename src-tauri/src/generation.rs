@@ -0,0 +1,421 @@
+use crate::ExifField;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::path::Path;
+
+pub const GENERATION_IFD: &str = "Generation";
+
+/// The PNG text keyword AUTOMATIC1111 and compatible UIs write.
+const PARAMETERS_KEYWORD: &str = "parameters";
+/// ComfyUI's keywords: the executed node graph in API form, and the editor
+/// graph that can be loaded back into ComfyUI.
+const COMFY_PROMPT_KEYWORD: &str = "prompt";
+const COMFY_WORKFLOW_KEYWORD: &str = "workflow";
+/// Node inputs a prompt's text may arrive through, in the order they are tried.
+const TEXT_INPUTS: [&str; 6] = [
+    "text",
+    "text_g",
+    "string",
+    "value",
+    "conditioning",
+    "conditioning_1",
+];
+/// Links followed before a graph is assumed to loop.
+const MAX_LINKS: usize = 32;
+const NEGATIVE_PREFIX: &str = "Negative prompt:";
+/// The settings line always starts with the step count.
+const SETTINGS_PREFIX: &str = "Steps: ";
+
+/// `Key name` as a tag: `CFG scale` becomes `CFGScale`, `Model hash`
+/// becomes `ModelHash`.
+fn tag_name(key: &str) -> String {
+    key.split_whitespace()
+        .map(|word| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect::<String>())
+                .unwrap_or_default()
+        })
+        .collect()
+}
+
+/// Splits the settings line into `key: value` pairs. Values may be quoted
+/// to hold commas, as `Lora hashes: "a: 1f2e, b: 3c4d"` does.
+fn settings(line: &str) -> Vec<(&str, String)> {
+    let mut items = Vec::new();
+    let (mut start, mut quoted) = (0, false);
+    for (index, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ',' if !quoted => {
+                items.push(&line[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(&line[start..]);
+    items
+        .into_iter()
+        .filter_map(|item| {
+            let (key, value) = item.split_once(':')?;
+            let value = value.trim();
+            let value = value
+                .strip_prefix('"')
+                .and_then(|value| value.strip_suffix('"'))
+                .unwrap_or(value);
+            Some((key.trim(), value.to_string()))
+        })
+        .filter(|(key, _)| !key.is_empty())
+        .collect()
+}
+
+/// `<lora:name:weight>` tags in a prompt as `name (weight)`.
+fn prompt_loras(prompt: &str) -> Vec<String> {
+    prompt
+        .split("<lora:")
+        .skip(1)
+        .filter_map(|rest| {
+            let tag = &rest[..rest.find('>')?];
+            Some(match tag.rsplit_once(':') {
+                Some((name, weight)) if weight.parse::<f64>().is_ok() => {
+                    format!("{} ({})", name, weight)
+                }
+                _ => tag.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Splits an AUTOMATIC1111 `parameters` blob into the prompt, negative
+/// prompt, one field per setting (`Steps`, `Sampler`, `CFGScale`, `Seed`,
+/// `ModelHash`...) and the LoRAs the prompt loads. Text without a settings
+/// line is not treated as parameters.
+pub fn parse_parameters(text: &str) -> Vec<ExifField> {
+    let lines: Vec<&str> = text.lines().collect();
+    let Some(settings_at) = lines
+        .iter()
+        .rposition(|line| line.starts_with(SETTINGS_PREFIX))
+    else {
+        return Vec::new();
+    };
+    let (mut prompt, mut negative) = (Vec::new(), Vec::new());
+    for line in &lines[..settings_at] {
+        match line.strip_prefix(NEGATIVE_PREFIX) {
+            Some(rest) => negative.push(rest.trim_start()),
+            None if negative.is_empty() => prompt.push(*line),
+            None => negative.push(*line),
+        }
+    }
+    let field = |tag: String, value: String| ExifField {
+        tag,
+        ifd: GENERATION_IFD.to_string(),
+        value,
+    };
+
+    let prompt = prompt.join("\n").trim().to_string();
+    let negative = negative.join("\n").trim().to_string();
+    let mut loras = prompt_loras(&prompt);
+    let mut fields = Vec::new();
+    if !prompt.is_empty() {
+        fields.push(field("Prompt".to_string(), prompt));
+    }
+    if !negative.is_empty() {
+        fields.push(field("NegativePrompt".to_string(), negative));
+    }
+    for (key, value) in settings(lines[settings_at]) {
+        // Only names from the hashes when the prompt did not list the LoRAs.
+        if key == "Lora hashes" && loras.is_empty() {
+            loras = settings(&value)
+                .into_iter()
+                .map(|(name, _)| name.to_string())
+                .collect();
+        }
+        fields.push(field(tag_name(key), value));
+    }
+    if !loras.is_empty() {
+        fields.push(field("LoRAs".to_string(), loras.join(", ")));
+    }
+    fields
+}
+
+type Graph = Map<String, Value>;
+
+/// The node an input links to; links are `[node id, output index]`.
+fn linked<'a>(graph: &'a Graph, input: &Value) -> Option<&'a Value> {
+    let id = input.as_array()?.first()?;
+    match id {
+        Value::String(id) => graph.get(id),
+        id => graph.get(&id.to_string()),
+    }
+}
+
+/// The literal an input ends in, following links through nodes that pass a
+/// value along under one of `keys` (primitives, text encoders...).
+fn resolve<'a>(
+    graph: &'a Graph,
+    input: &'a Value,
+    keys: &[&str],
+    depth: usize,
+) -> Option<&'a Value> {
+    if !input.is_array() {
+        return Some(input);
+    }
+    let inputs = linked(graph, input)?.get("inputs")?.as_object()?;
+    let next = keys.iter().find_map(|key| inputs.get(*key))?;
+    (depth < MAX_LINKS).then(|| resolve(graph, next, keys, depth + 1))?
+}
+
+fn display(value: &Value) -> Option<String> {
+    match value {
+        Value::String(text) => Some(text.trim().to_string()).filter(|text| !text.is_empty()),
+        Value::Number(number) => Some(number.to_string()),
+        Value::Bool(flag) => Some(flag.to_string()),
+        _ => None,
+    }
+}
+
+/// Walks the `model` links from a sampler back to the checkpoint, collecting
+/// the LoRA loaders on the way as `name (strength)`.
+fn model_chain(graph: &Graph, input: &Value) -> (Option<String>, Vec<String>) {
+    let (mut input, mut loras) = (input, Vec::new());
+    for _ in 0..MAX_LINKS {
+        let Some(inputs) = linked(graph, input)
+            .and_then(|node| node.get("inputs"))
+            .and_then(Value::as_object)
+        else {
+            break;
+        };
+        let name = ["ckpt_name", "unet_name"]
+            .iter()
+            .find_map(|key| inputs.get(*key).and_then(display));
+        if name.is_some() {
+            return (name, loras);
+        }
+        if let Some(lora) = inputs.get("lora_name").and_then(display) {
+            loras.push(match inputs.get("strength_model").and_then(display) {
+                Some(strength) => format!("{} ({})", lora, strength),
+                None => lora,
+            });
+        }
+        let Some(next) = inputs.get("model") else {
+            break;
+        };
+        input = next;
+    }
+    (None, loras)
+}
+
+/// Fields from a ComfyUI API-form graph: the prompts, checkpoint, LoRAs and
+/// sampler settings of its first sampler node (by node ID), in the tags
+/// AUTOMATIC1111 parameters use where the two overlap.
+pub fn parse_comfy_prompt(json: &str, workflow: Option<&str>) -> Vec<ExifField> {
+    let Ok(Value::Object(graph)) = serde_json::from_str::<Value>(json) else {
+        return Vec::new();
+    };
+    let mut samplers: Vec<(&String, &Map<String, Value>)> = graph
+        .iter()
+        .filter_map(|(id, node)| {
+            let class = node.get("class_type")?.as_str()?;
+            let inputs = node.get("inputs")?.as_object()?;
+            (class.contains("Sampler") && inputs.contains_key("positive")).then_some((id, inputs))
+        })
+        .collect();
+    samplers.sort_by_key(|(id, _)| (id.parse::<u64>().unwrap_or(u64::MAX), id.to_string()));
+    let Some((_, sampler)) = samplers.first() else {
+        return Vec::new();
+    };
+
+    let text = |key: &str| {
+        sampler
+            .get(key)
+            .and_then(|input| resolve(&graph, input, &TEXT_INPUTS, 0))
+            .and_then(display)
+    };
+    let setting = |keys: &[&str]| {
+        keys.iter()
+            .find_map(|key| sampler.get(*key))
+            .and_then(|input| resolve(&graph, input, &["value", "seed", "int"], 0))
+            .and_then(display)
+    };
+    let (checkpoint, loras) = sampler
+        .get("model")
+        .map(|input| model_chain(&graph, input))
+        .unwrap_or_default();
+    let nodes = workflow
+        .and_then(|workflow| serde_json::from_str::<Value>(workflow).ok())
+        .and_then(|workflow| Some(workflow.get("nodes")?.as_array()?.len()));
+
+    [
+        ("Prompt", text("positive")),
+        ("NegativePrompt", text("negative")),
+        ("Checkpoint", checkpoint),
+        ("Seed", setting(&["seed", "noise_seed"])),
+        ("Steps", setting(&["steps"])),
+        ("CFGScale", setting(&["cfg"])),
+        ("Sampler", setting(&["sampler_name"])),
+        ("Scheduler", setting(&["scheduler"])),
+        ("Denoise", setting(&["denoise"])),
+        (
+            "LoRAs",
+            Some(loras.join(", ")).filter(|loras| !loras.is_empty()),
+        ),
+        (
+            "Workflow",
+            nodes.map(|nodes| format!("ComfyUI, {} nodes", nodes)),
+        ),
+    ]
+    .into_iter()
+    .filter_map(|(tag, value)| {
+        Some(ExifField {
+            tag: tag.to_string(),
+            ifd: GENERATION_IFD.to_string(),
+            value: value?,
+        })
+    })
+    .collect()
+}
+
+/// The text of the PNG text chunk with `keyword`.
+fn text_chunk(text: &[ExifField], keyword: &str) -> Option<String> {
+    text.iter()
+        .find(|field| field.tag == keyword)
+        .map(|field| field.value.clone())
+}
+
+/// The `Generation` group of an image whose PNG text holds AUTOMATIC1111
+/// parameters or a ComfyUI graph.
+pub fn fields(data: &[u8]) -> Vec<ExifField> {
+    let text = crate::parse_png_text_chunks(data);
+    if let Some(parameters) = text_chunk(&text, PARAMETERS_KEYWORD) {
+        return parse_parameters(&parameters);
+    }
+    text_chunk(&text, COMFY_PROMPT_KEYWORD)
+        .map(|prompt| {
+            parse_comfy_prompt(
+                &prompt,
+                text_chunk(&text, COMFY_WORKFLOW_KEYWORD).as_deref(),
+            )
+        })
+        .unwrap_or_default()
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkflowExport {
+    path: String,
+    bytes: usize,
+}
+
+/// Writes the ComfyUI `workflow` of the PNG `data` to `output` exactly as
+/// stored, ready to be dropped back into ComfyUI.
+pub fn export_workflow(data: &[u8], output: &Path) -> Result<WorkflowExport, String> {
+    let workflow = text_chunk(&crate::parse_png_text_chunks(data), COMFY_WORKFLOW_KEYWORD)
+        .ok_or_else(|| "The selected image has no ComfyUI workflow.".to_string())?;
+    serde_json::from_str::<Value>(&workflow)
+        .map_err(|_| "The image's ComfyUI workflow is not valid JSON.".to_string())?;
+    crate::write_file_atomically(output, workflow.as_bytes())?;
+    Ok(WorkflowExport {
+        path: output.to_string_lossy().into_owned(),
+        bytes: workflow.len(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::PngBuilder;
+
+    #[test]
+    fn splits_an_automatic1111_blob_into_fields() {
+        let parameters = "a lighthouse at dusk, <lora:film_grain:0.6>, 35mm\nmoody sky\nNegative prompt: blurry, lowres\nwatermark\nSteps: 30, Sampler: DPM++ 2M Karras, CFG scale: 7.5, Seed: 1234567890, Size: 768x512, Model hash: 6ce0161689, Model: v1-5-pruned, Lora hashes: \"film_grain: 2a3b4c5d6e7f\", Version: v1.6.0";
+        let data = PngBuilder::new()
+            .header(768, 512)
+            .international_text(PARAMETERS_KEYWORD, "", "", parameters, false)
+            .finish();
+        let fields: Vec<(String, String)> = fields(&data)
+            .into_iter()
+            .map(|field| (field.tag, field.value))
+            .collect();
+        let expected = [
+            (
+                "Prompt",
+                "a lighthouse at dusk, <lora:film_grain:0.6>, 35mm\nmoody sky",
+            ),
+            ("NegativePrompt", "blurry, lowres\nwatermark"),
+            ("Steps", "30"),
+            ("Sampler", "DPM++ 2M Karras"),
+            ("CFGScale", "7.5"),
+            ("Seed", "1234567890"),
+            ("Size", "768x512"),
+            ("ModelHash", "6ce0161689"),
+            ("Model", "v1-5-pruned"),
+            ("LoraHashes", "film_grain: 2a3b4c5d6e7f"),
+            ("Version", "v1.6.0"),
+            ("LoRAs", "film_grain (0.6)"),
+        ];
+        assert_eq!(
+            fields,
+            expected.map(|(tag, value)| (tag.to_string(), value.to_string()))
+        );
+    }
+
+    #[test]
+    fn text_without_settings_is_not_parameters() {
+        assert!(parse_parameters("just a caption\nNegative prompt: none").is_empty());
+        let only_hashes = parse_parameters("Steps: 20, Lora hashes: \"detail: 1a, style: 2b\"");
+        assert_eq!(
+            only_hashes.last().map(|field| field.value.as_str()),
+            Some("detail, style")
+        );
+    }
+
+    const COMFY_PROMPT: &str = r#"{
+        "3": {"class_type": "KSampler", "inputs": {"seed": ["12", 0], "steps": 25, "cfg": 6.5, "sampler_name": "euler_ancestral", "scheduler": "karras", "denoise": 1, "model": ["10", 0], "positive": ["6", 0], "negative": ["7", 0], "latent_image": ["5", 0]}},
+        "4": {"class_type": "CheckpointLoaderSimple", "inputs": {"ckpt_name": "sd_xl_base_1.0.safetensors"}},
+        "6": {"class_type": "CLIPTextEncode", "inputs": {"text": "a red fox in snow", "clip": ["10", 1]}},
+        "7": {"class_type": "CLIPTextEncode", "inputs": {"text": ["13", 0], "clip": ["10", 1]}},
+        "10": {"class_type": "LoraLoader", "inputs": {"lora_name": "winter.safetensors", "strength_model": 0.8, "model": ["4", 0], "clip": ["4", 1]}},
+        "12": {"class_type": "PrimitiveNode", "inputs": {"value": 424242}},
+        "13": {"class_type": "PrimitiveNode", "inputs": {"value": "blurry, text"}}
+    }"#;
+
+    #[test]
+    fn comfyui_graphs_are_followed_to_prompts_and_checkpoint() {
+        let workflow = r#"{"nodes": [{"id": 3}, {"id": 4}, {"id": 6}], "links": []}"#;
+        let data = PngBuilder::new()
+            .header(1024, 1024)
+            .text(COMFY_PROMPT_KEYWORD, COMFY_PROMPT)
+            .text(COMFY_WORKFLOW_KEYWORD, workflow)
+            .finish();
+        let fields: Vec<(String, String)> = fields(&data)
+            .into_iter()
+            .map(|field| (field.tag, field.value))
+            .collect();
+        let expected = [
+            ("Prompt", "a red fox in snow"),
+            ("NegativePrompt", "blurry, text"),
+            ("Checkpoint", "sd_xl_base_1.0.safetensors"),
+            ("Seed", "424242"),
+            ("Steps", "25"),
+            ("CFGScale", "6.5"),
+            ("Sampler", "euler_ancestral"),
+            ("Scheduler", "karras"),
+            ("Denoise", "1"),
+            ("LoRAs", "winter.safetensors (0.8)"),
+            ("Workflow", "ComfyUI, 3 nodes"),
+        ];
+        assert_eq!(
+            fields,
+            expected.map(|(tag, value)| (tag.to_string(), value.to_string()))
+        );
+
+        let output = crate::fixtures::temp_path("workflow.json");
+        let export = export_workflow(&data, &output).unwrap();
+        let written = std::fs::read_to_string(&output).unwrap();
+        std::fs::remove_file(&output).ok();
+        assert_eq!((written.as_str(), export.bytes), (workflow, workflow.len()));
+        assert!(export_workflow(&PngBuilder::new().header(1, 1).finish(), &output).is_err());
+    }
+}
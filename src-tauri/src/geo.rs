@@ -0,0 +1,256 @@
+use crate::{
+    gps::{collect_gps_points, GpsPoint},
+    track::escape_xml,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::{
+    fmt::Write,
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GeoFormat {
+    GeoJson,
+    Kml,
+}
+
+impl GeoFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            GeoFormat::GeoJson => "geojson",
+            GeoFormat::Kml => "kml",
+        }
+    }
+}
+
+/// What goes in the `path` property of each photo. By default there is none,
+/// only the file name, since exports are shared and the folders of a library
+/// can say more about its owner than the photos do.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GeoPaths {
+    #[default]
+    Omitted,
+    /// Relative to the folder of the export file, for web maps served with
+    /// the photos.
+    Relative,
+    Absolute,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeoExport {
+    path: String,
+    features: usize,
+}
+
+/// Writes one feature per geotagged photo under `root` for use in GIS tools.
+pub fn export_geo(
+    root: &Path,
+    format: GeoFormat,
+    paths: GeoPaths,
+    output: &Path,
+) -> Result<GeoExport, String> {
+    let points = collect_gps_points(root, false);
+    if points.is_empty() {
+        return Err("No geotagged photos were found in the selected folder.".to_string());
+    }
+    write_geo(&points, format, paths, output)
+}
+
+pub fn write_geo(
+    points: &[GpsPoint],
+    format: GeoFormat,
+    paths: GeoPaths,
+    output: &Path,
+) -> Result<GeoExport, String> {
+    let base = output.parent().unwrap_or(Path::new(""));
+    let document = match format {
+        GeoFormat::GeoJson => render_geojson(points, paths, base),
+        GeoFormat::Kml => render_kml(points, paths, base),
+    };
+    fs::write(output, document)
+        .map_err(|error| format!("Unable to write the export file: {}", error))?;
+
+    Ok(GeoExport {
+        path: output.to_string_lossy().into_owned(),
+        features: points.len(),
+    })
+}
+
+/// `path` as seen from the folder `base`, with `..` where it lies outside.
+fn relative_path(path: &Path, base: &Path) -> PathBuf {
+    let path: Vec<Component> = path.components().collect();
+    let base: Vec<Component> = base.components().collect();
+    let shared = path.iter().zip(&base).take_while(|(a, b)| a == b).count();
+    let mut relative: PathBuf = base[shared..]
+        .iter()
+        .map(|_| Component::ParentDir)
+        .collect();
+    relative.extend(&path[shared..]);
+    relative
+}
+
+/// Per-photo attributes shared by every output format. `base` is the
+/// folder relative paths start from.
+fn properties(point: &GpsPoint, paths: GeoPaths, base: &Path) -> Map<String, Value> {
+    let mut properties = Map::new();
+    let name = point
+        .path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    properties.insert("name".to_string(), json!(name));
+    let path = match paths {
+        GeoPaths::Omitted => None,
+        // Forward slashes on every platform, as URLs use them.
+        GeoPaths::Relative => Some(
+            relative_path(&point.path, base)
+                .to_string_lossy()
+                .replace('\\', "/"),
+        ),
+        GeoPaths::Absolute => Some(point.path.to_string_lossy().into_owned()),
+    };
+    if let Some(path) = path {
+        properties.insert("path".to_string(), json!(path));
+    }
+    if let Some(time) = point.captured {
+        properties.insert("capturedAt".to_string(), json!(time.to_iso8601()));
+    }
+    let reading = &point.reading;
+    if let Some(altitude) = reading.altitude {
+        properties.insert("altitude".to_string(), json!(altitude));
+    }
+    if let Some(direction) = reading.image_direction {
+        properties.insert("imageDirection".to_string(), json!(direction.degrees));
+    }
+    if let Some(track) = reading.track {
+        properties.insert("track".to_string(), json!(track.degrees));
+    }
+    if let Some(speed) = reading.speed_kmh {
+        properties.insert("speedKmh".to_string(), json!(speed));
+    }
+    properties
+}
+
+fn render_geojson(points: &[GpsPoint], paths: GeoPaths, base: &Path) -> String {
+    let features: Vec<Value> = points
+        .iter()
+        .map(|point| {
+            let (latitude, longitude) = point.position();
+            let mut coordinates = vec![json!(longitude), json!(latitude)];
+            if let Some(altitude) = point.reading.altitude {
+                coordinates.push(json!(altitude));
+            }
+            json!({
+                "type": "Feature",
+                "geometry": { "type": "Point", "coordinates": coordinates },
+                "properties": properties(point, paths, base),
+            })
+        })
+        .collect();
+
+    let collection = json!({ "type": "FeatureCollection", "features": features });
+    serde_json::to_string_pretty(&collection).expect("GeoJSON values always serialize")
+}
+
+fn render_kml(points: &[GpsPoint], paths: GeoPaths, base: &Path) -> String {
+    let mut placemarks = String::new();
+    for point in points {
+        let (latitude, longitude) = point.position();
+        let properties = properties(point, paths, base);
+        let mut data = String::new();
+        for (key, value) in &properties {
+            let text = match value {
+                Value::String(text) => text.clone(),
+                other => other.to_string(),
+            };
+            let _ = write!(
+                data,
+                "<Data name=\"{}\"><value>{}</value></Data>",
+                escape_xml(key),
+                escape_xml(&text)
+            );
+        }
+        let name = properties
+            .get("name")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        let _ = writeln!(
+            placemarks,
+            "    <Placemark><name>{}</name><ExtendedData>{}</ExtendedData>\
+             <Point><coordinates>{:.7},{:.7},{:.1}</coordinates></Point></Placemark>",
+            escape_xml(name),
+            data,
+            longitude,
+            latitude,
+            point.reading.altitude.unwrap_or(0.0)
+        );
+    }
+
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <kml xmlns=\"http://www.opengis.net/kml/2.2\">\n  <Document>\n    <name>Geotagged photos</name>\n\
+         {}  </Document>\n</kml>\n",
+        placemarks
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gps::{Bearing, GpsReading};
+
+    fn point() -> GpsPoint {
+        GpsPoint {
+            path: "/photos/harbour.jpg".into(),
+            reading: GpsReading {
+                latitude: Some(59.91),
+                longitude: Some(10.75),
+                image_direction: Some(Bearing {
+                    degrees: 270.0,
+                    magnetic: false,
+                }),
+                ..GpsReading::default()
+            },
+            captured: None,
+            thumbnail: None,
+        }
+    }
+
+    #[test]
+    fn geojson_uses_lon_lat_order_and_properties() {
+        let render = |paths| {
+            let document: Value =
+                serde_json::from_str(&render_geojson(&[point()], paths, Path::new("/maps")))
+                    .expect("valid GeoJSON");
+            document
+        };
+        let document = render(GeoPaths::Omitted);
+        let feature = &document["features"][0];
+        assert_eq!(document["type"], "FeatureCollection");
+        assert_eq!(feature["geometry"]["coordinates"], json!([10.75, 59.91]));
+        assert_eq!(feature["properties"]["name"], "harbour.jpg");
+        assert_eq!(feature["properties"]["imageDirection"], 270.0);
+        assert!(feature["properties"].get("path").is_none());
+        assert_eq!(
+            render(GeoPaths::Relative)["features"][0]["properties"]["path"],
+            "../photos/harbour.jpg"
+        );
+        assert_eq!(
+            render(GeoPaths::Absolute)["features"][0]["properties"]["path"],
+            "/photos/harbour.jpg"
+        );
+    }
+
+    #[test]
+    fn kml_exposes_properties_as_extended_data() {
+        let kml = render_kml(&[point()], GeoPaths::Omitted, Path::new("/photos"));
+        assert!(kml.contains("<Data name=\"imageDirection\"><value>270.0</value></Data>"));
+        assert!(kml.contains("<coordinates>10.7500000,59.9100000,0.0</coordinates>"));
+        assert!(!kml.contains("/photos/harbour.jpg"));
+    }
+}
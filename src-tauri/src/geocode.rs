@@ -0,0 +1,123 @@
+use crate::{
+    gps::{distance_km, GpsReading},
+    ExifField,
+};
+use exif::Exif;
+use serde::Deserialize;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    OnceLock,
+};
+
+pub const COMPUTED_PLACE_IFD: &str = "Place";
+
+/// Cities and larger towns with their country, so places are named without
+/// a network lookup.
+const PLACE_DATA: &str = include_str!("../data/places.json");
+/// Positions farther than this from every bundled city stay unnamed rather
+/// than being credited to a distant one.
+const MAX_DISTANCE_KM: f64 = 100.0;
+
+#[derive(Debug, Deserialize)]
+struct City {
+    city: String,
+    country: String,
+    latitude: f64,
+    longitude: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Place {
+    pub city: String,
+    pub country: String,
+    /// From the position to the city's centre.
+    pub distance_km: f64,
+}
+
+impl Place {
+    /// `City, Country`, as shown and grouped by.
+    pub fn label(&self) -> String {
+        format!("{}, {}", self.city, self.country)
+    }
+}
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+fn cities() -> &'static [City] {
+    static CITIES: OnceLock<Vec<City>> = OnceLock::new();
+    CITIES
+        .get_or_init(|| serde_json::from_str(PLACE_DATA).expect("bundled place data is valid JSON"))
+}
+
+/// The bundled city nearest to a position, if one is close enough.
+pub fn reverse_geocode(latitude: f64, longitude: f64) -> Option<Place> {
+    cities()
+        .iter()
+        .map(|city| {
+            let distance = distance_km((latitude, longitude), (city.latitude, city.longitude));
+            (city, distance)
+        })
+        .filter(|(_, distance)| *distance <= MAX_DISTANCE_KM)
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(city, distance)| Place {
+            city: city.city.clone(),
+            country: city.country.clone(),
+            distance_km: distance,
+        })
+}
+
+/// Where the GPS position recorded in `exif` lies.
+pub fn place_of(exif: &Exif) -> Option<Place> {
+    let (latitude, longitude) = GpsReading::from_exif(exif).position()?;
+    reverse_geocode(latitude, longitude)
+}
+
+pub fn computed_fields(place: Option<&Place>) -> Vec<ExifField> {
+    let Some(place) = place else {
+        return Vec::new();
+    };
+    [
+        ("Location", place.label()),
+        ("City", place.city.clone()),
+        ("Country", place.country.clone()),
+        ("PlaceDistance", format!("{:.0} km", place.distance_km)),
+    ]
+    .into_iter()
+    .map(|(tag, value)| ExifField {
+        tag: tag.to_string(),
+        ifd: COMPUTED_PLACE_IFD.to_string(),
+        value,
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn positions_take_the_nearest_city_within_reach() {
+        let louvre = reverse_geocode(48.861, 2.336).unwrap();
+        assert_eq!(louvre.label(), "Paris, France");
+        assert!(louvre.distance_km < 2.0);
+
+        // Shibuya is nearer Tokyo than Yokohama.
+        let shibuya = reverse_geocode(35.659, 139.700).unwrap();
+        assert_eq!(shibuya.city, "Tokyo");
+
+        let fields = computed_fields(Some(&shibuya));
+        assert_eq!(fields[0].tag, "Location");
+        assert_eq!(fields[0].value, "Tokyo, Japan");
+
+        // Mid-Pacific, nowhere near a bundled city.
+        assert_eq!(reverse_geocode(0.0, -140.0), None);
+    }
+}
@@ -0,0 +1,373 @@
+use crate::{
+    capture::original_time,
+    commit::{commit_edits, Committed, EditTargets},
+    exif_write::{self, FieldEdit},
+    gps::GpsReading,
+    jpeg, sidecar,
+    verify::WriteVerification,
+    write_lock::{FileStamp, WriteError, WriteProblemReport},
+};
+use chrono::{DateTime, Duration, NaiveDateTime};
+use exif::{Rational, Tag, Value};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Photos taken further than this from a track point, or inside a longer
+/// gap in the recording, are not placed on the track.
+const MAX_GAP_SECONDS: i64 = 30 * 60;
+
+/// Where correlated positions are written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum GeotagTarget {
+    /// The EXIF GPS tags of JPEG files.
+    #[default]
+    File,
+    /// An XMP sidecar next to the image, for RAW and other formats.
+    Sidecar,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrackPoint {
+    pub time: NaiveDateTime,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub elevation: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Geotagging {
+    path: String,
+    /// The corrected capture time matched against the track, in UTC.
+    matched_time: Option<String>,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+    altitude: Option<f64>,
+    written: bool,
+    stored_externally: bool,
+    /// The XMP sidecar written, with `target: "sidecar"`.
+    sidecar: Option<String>,
+    error: Option<String>,
+    problem: Option<WriteProblemReport>,
+    verification: Option<WriteVerification>,
+}
+
+/// The value of `name="..."` in an XML start tag.
+fn attribute(tag: &str, name: &str) -> Option<f64> {
+    let needle = format!(" {}=", name);
+    let start = tag.find(&needle)? + needle.len();
+    let quote = tag[start..]
+        .chars()
+        .next()
+        .filter(|c| *c == '"' || *c == '\'')?;
+    let value = &tag[start + 1..];
+    value[..value.find(quote)?].trim().parse().ok()
+}
+
+fn element<'a>(body: &'a str, name: &str) -> Option<&'a str> {
+    let start = body.find(&format!("<{}>", name))? + name.len() + 2;
+    let end = body[start..].find(&format!("</{}>", name))?;
+    Some(body[start..start + end].trim())
+}
+
+/// The timed track points of a GPX document, oldest first.
+pub fn parse_gpx(text: &str) -> Result<Vec<TrackPoint>, String> {
+    let mut points = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("<trkpt") {
+        rest = &rest[start + "<trkpt".len()..];
+        let Some(head_end) = rest.find('>') else {
+            break;
+        };
+        let head = &rest[..head_end];
+        let body = if head.ends_with('/') {
+            ""
+        } else {
+            &rest[head_end..rest.find("</trkpt>").unwrap_or(rest.len())]
+        };
+        let time = element(body, "time")
+            .and_then(|time| DateTime::parse_from_rfc3339(time).ok())
+            .map(|time| time.naive_utc());
+        if let (Some(latitude), Some(longitude), Some(time)) =
+            (attribute(head, "lat"), attribute(head, "lon"), time)
+        {
+            points.push(TrackPoint {
+                time,
+                latitude,
+                longitude,
+                elevation: element(body, "ele").and_then(|ele| ele.parse().ok()),
+            });
+        }
+    }
+    if points.is_empty() {
+        return Err("The GPX file has no timed track points.".to_string());
+    }
+    points.sort_by_key(|point| point.time);
+    Ok(points)
+}
+
+/// The position at `time`, interpolated between the track points around it.
+pub fn position_at(track: &[TrackPoint], time: NaiveDateTime) -> Option<TrackPoint> {
+    let max_gap = Duration::seconds(MAX_GAP_SECONDS);
+    let after = track.partition_point(|point| point.time < time);
+    let (before, next) = match (
+        after.checked_sub(1).map(|at| track[at]),
+        track.get(after).copied(),
+    ) {
+        (_, Some(next)) if next.time == time => return Some(next),
+        (Some(before), Some(next)) => (before, next),
+        (Some(end), None) | (None, Some(end)) => {
+            return ((end.time - time).abs() <= max_gap).then_some(TrackPoint { time, ..end });
+        }
+        (None, None) => return None,
+    };
+    if next.time - before.time > max_gap {
+        return None;
+    }
+    let share = (time - before.time).num_milliseconds() as f64
+        / (next.time - before.time).num_milliseconds() as f64;
+    let between = |from: f64, to: f64| from + (to - from) * share;
+    Some(TrackPoint {
+        time,
+        latitude: between(before.latitude, next.latitude),
+        longitude: between(before.longitude, next.longitude),
+        elevation: match (before.elevation, next.elevation) {
+            (Some(from), Some(to)) => Some(between(from, to)),
+            (from, to) => from.or(to),
+        },
+    })
+}
+
+/// Degrees, minutes and hundredths of seconds, as EXIF stores coordinates.
+fn dms(value: f64) -> Value {
+    let hundredths = (value.abs() * 360_000.0).round() as u32;
+    Value::Rational(vec![
+        Rational::from((hundredths / 360_000, 1)),
+        Rational::from((hundredths / 6_000 % 60, 1)),
+        Rational::from((hundredths % 6_000, 100)),
+    ])
+}
+
+pub fn exif_edits(point: &TrackPoint) -> Vec<FieldEdit> {
+    let hemisphere = |value: f64, positive: &str, negative: &str| {
+        if value < 0.0 {
+            negative.to_string()
+        } else {
+            positive.to_string()
+        }
+    };
+    let mut edits = vec![
+        FieldEdit {
+            tag: Tag::GPSVersionID,
+            value: Some(Value::Byte(vec![2, 3, 0, 0])),
+        },
+        FieldEdit::ascii(Tag::GPSLatitudeRef, &hemisphere(point.latitude, "N", "S")),
+        FieldEdit {
+            tag: Tag::GPSLatitude,
+            value: Some(dms(point.latitude)),
+        },
+        FieldEdit::ascii(Tag::GPSLongitudeRef, &hemisphere(point.longitude, "E", "W")),
+        FieldEdit {
+            tag: Tag::GPSLongitude,
+            value: Some(dms(point.longitude)),
+        },
+    ];
+    if let Some(elevation) = point.elevation {
+        edits.push(FieldEdit {
+            tag: Tag::GPSAltitudeRef,
+            value: Some(Value::Byte(vec![u8::from(elevation < 0.0)])),
+        });
+        edits.push(FieldEdit {
+            tag: Tag::GPSAltitude,
+            value: Some(Value::Rational(vec![Rational::from((
+                (elevation.abs() * 100.0).round() as u32,
+                100,
+            ))])),
+        });
+    }
+    edits
+}
+
+/// XMP writes coordinates as `DDD,MM.mmmmmmK`.
+fn xmp_coordinate(value: f64, positive: char, negative: char) -> String {
+    let degrees = value.abs().trunc();
+    let minutes = (value.abs() - degrees) * 60.0;
+    format!(
+        "{},{:.6}{}",
+        degrees,
+        minutes,
+        if value < 0.0 { negative } else { positive }
+    )
+}
+
+pub fn xmp_properties(point: &TrackPoint) -> Vec<(&'static str, String)> {
+    let mut properties = vec![
+        ("exif:GPSVersionID", "2.3.0.0".to_string()),
+        ("exif:GPSLatitude", xmp_coordinate(point.latitude, 'N', 'S')),
+        (
+            "exif:GPSLongitude",
+            xmp_coordinate(point.longitude, 'E', 'W'),
+        ),
+    ];
+    if let Some(elevation) = point.elevation {
+        properties.push(("exif:GPSAltitudeRef", u8::from(elevation < 0.0).to_string()));
+        properties.push((
+            "exif:GPSAltitude",
+            format!("{}/100", (elevation.abs() * 100.0).round() as u32),
+        ));
+    }
+    properties
+}
+
+/// Places `path` on `track` by its DateTimeOriginal and writes the position
+/// where `target` says. `time_offset` seconds are added to the camera time
+/// first; camera times without a recorded UTC offset are taken as UTC, so
+/// the offset also covers a camera set to local time. Files that already
+/// have a position are left alone unless `overwrite` is set.
+pub fn geotag_file(
+    path: &Path,
+    track: &[TrackPoint],
+    time_offset: Duration,
+    target: GeotagTarget,
+    overwrite: bool,
+    dry_run: bool,
+    targets: &EditTargets,
+) -> Geotagging {
+    let mut tagging = Geotagging {
+        path: path.to_string_lossy().into_owned(),
+        matched_time: None,
+        latitude: None,
+        longitude: None,
+        altitude: None,
+        written: false,
+        stored_externally: false,
+        sidecar: None,
+        error: None,
+        problem: None,
+        verification: None,
+    };
+
+    let result = (|| -> Result<(), WriteError> {
+        let stamp = FileStamp::of(path)?;
+        let data = crate::load_file_data(path)?;
+        let exif = crate::read_exif_container(&data);
+        let capture = exif
+            .as_ref()
+            .and_then(original_time)
+            .ok_or_else(|| "The file records no DateTimeOriginal.".to_string())?;
+        let time = capture.sort_key() + time_offset;
+        tagging.matched_time = Some(format!("{}Z", time.format("%Y-%m-%dT%H:%M:%S")));
+        let point = position_at(track, time)
+            .ok_or_else(|| "The capture time is not covered by the track.".to_string())?;
+        tagging.latitude = Some(point.latitude);
+        tagging.longitude = Some(point.longitude);
+        tagging.altitude = point.elevation;
+
+        let has_position = exif
+            .as_ref()
+            .is_some_and(|exif| GpsReading::from_exif(exif).position().is_some());
+        if dry_run || (has_position && !overwrite) {
+            return Ok(());
+        }
+        match target {
+            GeotagTarget::Sidecar => {
+                let sidecar = sidecar::write_xmp(
+                    path,
+                    &xmp_properties(&point),
+                    "geotag_from_gpx",
+                    targets.log,
+                )?;
+                tagging.sidecar = Some(sidecar.to_string_lossy().into_owned());
+                tagging.written = true;
+            }
+            GeotagTarget::File => {
+                if !jpeg::is_jpeg(&data) && !exif_write::is_tiff(&data) {
+                    return Err("GPS positions can only be written into JPEG and TIFF files; use the sidecar target for other formats."
+                        .to_string()
+                        .into());
+                }
+                match commit_edits(
+                    targets,
+                    "geotag_from_gpx",
+                    path,
+                    stamp,
+                    &data,
+                    exif.as_ref(),
+                    &exif_edits(&point),
+                )? {
+                    Committed::InPlace(verification) => {
+                        tagging.written = true;
+                        tagging.verification = Some(verification);
+                    }
+                    Committed::External => tagging.stored_externally = true,
+                }
+            }
+        }
+        Ok(())
+    })();
+
+    if let Err(error) = result {
+        tagging.error = Some(error.message());
+        tagging.problem = error.report();
+    }
+    tagging
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    const TRACK: &str = r#"<?xml version="1.0"?>
+<gpx version="1.1"><trk><trkseg>
+  <trkpt lat="48.0" lon="2.0"><ele>100</ele><time>2024-06-01T10:00:00Z</time></trkpt>
+  <trkpt lat="48.1" lon="2.2"><ele>200</ele><time>2024-06-01T10:10:00Z</time></trkpt>
+  <trkpt lat="49.0" lon="3.0"><time>2024-06-01T12:00:00Z</time></trkpt>
+  <trkpt lat="1" lon="1"/>
+</trkseg></trk></gpx>"#;
+
+    fn at(hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 6, 1)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn positions_are_interpolated_between_nearby_points() {
+        let track = parse_gpx(TRACK).unwrap();
+        assert_eq!(track.len(), 3);
+
+        let halfway = position_at(&track, at(10, 5)).unwrap();
+        assert!((halfway.latitude - 48.05).abs() < 1e-9);
+        assert!((halfway.longitude - 2.1).abs() < 1e-9);
+        assert_eq!(halfway.elevation, Some(150.0));
+
+        // Shortly before the track starts, the first point is used.
+        assert_eq!(position_at(&track, at(9, 50)).unwrap().latitude, 48.0);
+        // An hour and fifty minutes without points is a gap, not a route.
+        assert_eq!(position_at(&track, at(11, 0)), None);
+        assert_eq!(position_at(&track, at(13, 0)), None);
+        assert!(parse_gpx("<gpx></gpx>").is_err());
+    }
+
+    #[test]
+    fn coordinates_are_written_in_exif_and_xmp_notation() {
+        let Value::Rational(parts) = dms(-33.8675) else {
+            panic!("coordinates are rationals");
+        };
+        let parts: Vec<(u32, u32)> = parts.iter().map(|part| (part.num, part.denom)).collect();
+        assert_eq!(parts, [(33, 1), (52, 1), (300, 100)]);
+        assert_eq!(xmp_coordinate(-33.8675, 'N', 'S'), "33,52.050000S");
+        let point = TrackPoint {
+            time: at(10, 0),
+            latitude: 1.0,
+            longitude: -2.5,
+            elevation: Some(-3.0),
+        };
+        let properties = xmp_properties(&point);
+        assert!(properties.contains(&("exif:GPSLongitude", "2,30.000000W".to_string())));
+        assert!(properties.contains(&("exif:GPSAltitudeRef", "1".to_string())));
+    }
+}
@@ -0,0 +1,343 @@
+use crate::{
+    capture::{self, CaptureTime},
+    ExifField,
+};
+use exif::{Exif, In, Tag, Value};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+pub const COMPUTED_GPS_IFD: &str = "GPS (computed)";
+const CARDINAL_POINTS: [&str; 16] = [
+    "N", "NNE", "NE", "ENE", "E", "ESE", "SE", "SSE", "S", "SSW", "SW", "WSW", "W", "WNW", "NW",
+    "NNW",
+];
+
+/// A bearing in degrees together with the north reference it was recorded against.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Bearing {
+    pub degrees: f64,
+    pub magnetic: bool,
+}
+
+/// Decimal GPS values decoded from the EXIF GPS IFD with the ref tags applied.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GpsReading {
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub altitude: Option<f64>,
+    pub image_direction: Option<Bearing>,
+    pub track: Option<Bearing>,
+    pub speed_kmh: Option<f64>,
+    pub dest_latitude: Option<f64>,
+    pub dest_longitude: Option<f64>,
+    pub dest_bearing: Option<Bearing>,
+}
+
+impl GpsReading {
+    pub fn from_exif(exif: &Exif) -> Self {
+        GpsReading {
+            latitude: coordinate(exif, Tag::GPSLatitude, Tag::GPSLatitudeRef),
+            longitude: coordinate(exif, Tag::GPSLongitude, Tag::GPSLongitudeRef),
+            altitude: altitude(exif),
+            image_direction: bearing(exif, Tag::GPSImgDirection, Tag::GPSImgDirectionRef),
+            track: bearing(exif, Tag::GPSTrack, Tag::GPSTrackRef),
+            speed_kmh: speed_kmh(exif),
+            dest_latitude: coordinate(exif, Tag::GPSDestLatitude, Tag::GPSDestLatitudeRef),
+            dest_longitude: coordinate(exif, Tag::GPSDestLongitude, Tag::GPSDestLongitudeRef),
+            dest_bearing: bearing(exif, Tag::GPSDestBearing, Tag::GPSDestBearingRef),
+        }
+    }
+
+    pub fn position(&self) -> Option<(f64, f64)> {
+        Some((self.latitude?, self.longitude?))
+    }
+}
+
+/// Where and when a photo was taken, as numbers a map can use directly.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GpsInfo {
+    /// Decimal degrees, negative south of the equator.
+    pub latitude: f64,
+    /// Decimal degrees, negative west of Greenwich.
+    pub longitude: f64,
+    /// Metres above sea level, negative below it.
+    pub altitude: Option<f64>,
+    /// UTC time of the fix from GPSDateStamp and GPSTimeStamp, in ISO 8601.
+    pub timestamp: Option<String>,
+    /// The way the camera was facing.
+    pub direction: Option<Bearing>,
+}
+
+impl GpsInfo {
+    /// `None` unless the EXIF holds both a latitude and a longitude.
+    pub fn from_exif(exif: &Exif) -> Option<Self> {
+        let reading = GpsReading::from_exif(exif);
+        let (latitude, longitude) = reading.position()?;
+        Some(GpsInfo {
+            latitude,
+            longitude,
+            altitude: reading.altitude,
+            timestamp: capture::gps_time(exif).map(CaptureTime::to_iso8601),
+            direction: reading.image_direction,
+        })
+    }
+}
+
+/// A geotagged photo found while walking a folder.
+#[derive(Debug, Clone)]
+pub struct GpsPoint {
+    pub path: PathBuf,
+    pub reading: GpsReading,
+    pub captured: Option<CaptureTime>,
+    pub thumbnail: Option<Vec<u8>>,
+}
+
+impl GpsPoint {
+    pub fn position(&self) -> (f64, f64) {
+        self.reading
+            .position()
+            .expect("collected points always carry a position")
+    }
+}
+
+/// Reads a supported image and returns it as a point when it carries a GPS position.
+pub fn gps_point(path: &Path, include_thumbnail: bool) -> Option<GpsPoint> {
+    if !crate::is_supported_image(path) {
+        return None;
+    }
+    let data = crate::memory_budget::load_scan_data(path).ok()?;
+    let exif = crate::read_exif_container(&data)?;
+    let reading = GpsReading::from_exif(&exif);
+    reading.position()?;
+    Some(GpsPoint {
+        path: path.to_path_buf(),
+        reading,
+        captured: capture::capture_time(&exif),
+        thumbnail: include_thumbnail
+            .then(|| crate::thumbnail::embedded_thumbnail(&exif).map(<[u8]>::to_vec))
+            .flatten(),
+    })
+}
+
+/// Walks `root` and returns every supported image that carries a GPS position.
+pub fn collect_gps_points(root: &Path, include_thumbnails: bool) -> Vec<GpsPoint> {
+    let mut points = Vec::new();
+    crate::walk_files(root, &mut |path| {
+        points.extend(gps_point(path, include_thumbnails));
+    });
+    points
+}
+
+/// Synthesizes combined GPS fields that are tedious to read from the raw tags.
+pub fn computed_fields(reading: &GpsReading) -> Vec<ExifField> {
+    let mut fields = Vec::new();
+    let mut push = |tag: &str, value: String| {
+        fields.push(ExifField {
+            tag: tag.to_string(),
+            ifd: COMPUTED_GPS_IFD.to_string(),
+            value,
+        });
+    };
+
+    if let Some((latitude, longitude)) = reading.position() {
+        push("Position", format!("{:.6}, {:.6}", latitude, longitude));
+    }
+    if let (Some(latitude), Some(longitude)) = (reading.dest_latitude, reading.dest_longitude) {
+        push("Destination", format!("{:.6}, {:.6}", latitude, longitude));
+    }
+    if let Some(direction) = reading.image_direction {
+        push("Image direction", describe_bearing(direction));
+    }
+    if let Some(track) = reading.track {
+        push("Track", describe_bearing(track));
+    }
+    if let Some(bearing) = reading.dest_bearing {
+        push("Destination bearing", describe_bearing(bearing));
+    }
+    if let Some(speed) = reading.speed_kmh {
+        push(
+            "Speed",
+            format!("{:.1} km/h ({:.1} mph)", speed, speed / 1.609_344),
+        );
+    }
+
+    fields
+}
+
+/// Great-circle distance between two positions in kilometres.
+pub fn distance_km(from: (f64, f64), to: (f64, f64)) -> f64 {
+    const EARTH_RADIUS_KM: f64 = 6_371.0;
+    let (lat1, lon1) = (from.0.to_radians(), from.1.to_radians());
+    let (lat2, lon2) = (to.0.to_radians(), to.1.to_radians());
+    let a = ((lat2 - lat1) / 2.0).sin().powi(2)
+        + lat1.cos() * lat2.cos() * ((lon2 - lon1) / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_KM * a.sqrt().asin()
+}
+
+pub fn cardinal_direction(degrees: f64) -> &'static str {
+    let normalized = degrees.rem_euclid(360.0);
+    let index = ((normalized / 22.5).round() as usize) % CARDINAL_POINTS.len();
+    CARDINAL_POINTS[index]
+}
+
+fn describe_bearing(bearing: Bearing) -> String {
+    format!(
+        "{:.1}° {} ({} north)",
+        bearing.degrees,
+        cardinal_direction(bearing.degrees),
+        if bearing.magnetic { "magnetic" } else { "true" }
+    )
+}
+
+fn coordinate(exif: &Exif, tag: Tag, ref_tag: Tag) -> Option<f64> {
+    let field = exif.get_field(tag, In::PRIMARY)?;
+    let magnitude = dms_to_decimal(&field.value)?;
+    let negative = matches!(ascii_ref(exif, ref_tag).as_deref(), Some("S" | "W"));
+    Some(if negative { -magnitude } else { magnitude })
+}
+
+/// Converts a degree/minute/second rational triple to decimal degrees.
+pub fn dms_to_decimal(value: &Value) -> Option<f64> {
+    let parts: Vec<f64> = match value {
+        Value::Rational(values) => values.iter().map(|value| value.to_f64()).collect(),
+        _ => return None,
+    };
+    let degrees = *parts.first()?;
+    let minutes = parts.get(1).copied().unwrap_or(0.0);
+    let seconds = parts.get(2).copied().unwrap_or(0.0);
+    let decimal = degrees + minutes / 60.0 + seconds / 3600.0;
+    decimal.is_finite().then_some(decimal)
+}
+
+fn altitude(exif: &Exif) -> Option<f64> {
+    let meters = first_rational(exif, Tag::GPSAltitude)?;
+    let below_sea_level = exif
+        .get_field(Tag::GPSAltitudeRef, In::PRIMARY)
+        .and_then(|field| field.value.get_uint(0))
+        == Some(1);
+    Some(if below_sea_level { -meters } else { meters })
+}
+
+fn bearing(exif: &Exif, tag: Tag, ref_tag: Tag) -> Option<Bearing> {
+    let degrees = first_rational(exif, tag)?;
+    let magnetic = ascii_ref(exif, ref_tag).as_deref() == Some("M");
+    Some(Bearing { degrees, magnetic })
+}
+
+fn speed_kmh(exif: &Exif) -> Option<f64> {
+    let speed = first_rational(exif, Tag::GPSSpeed)?;
+    let factor = match ascii_ref(exif, Tag::GPSSpeedRef).as_deref() {
+        Some("M") => 1.609_344,
+        Some("N") => 1.852,
+        _ => 1.0,
+    };
+    Some(speed * factor)
+}
+
+pub fn first_rational(exif: &Exif, tag: Tag) -> Option<f64> {
+    let value = match &exif.get_field(tag, In::PRIMARY)?.value {
+        Value::Rational(values) => values.first()?.to_f64(),
+        Value::SRational(values) => values.first()?.to_f64(),
+        _ => return None,
+    };
+    value.is_finite().then_some(value)
+}
+
+fn ascii_ref(exif: &Exif, tag: Tag) -> Option<String> {
+    match &exif.get_field(tag, In::PRIMARY)?.value {
+        Value::Ascii(values) => values
+            .first()
+            .map(|bytes| String::from_utf8_lossy(bytes).trim().to_ascii_uppercase()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{ascii_field, exif_from_fields, rational_field};
+
+    #[test]
+    fn reading_applies_refs_and_units() {
+        let exif = exif_from_fields(&[
+            rational_field(Tag::GPSLatitude, &[(48, 1), (51, 1), (2952, 100)]),
+            ascii_field(Tag::GPSLatitudeRef, "N"),
+            rational_field(Tag::GPSLongitude, &[(2, 1), (17, 1), (4020, 100)]),
+            ascii_field(Tag::GPSLongitudeRef, "W"),
+            rational_field(Tag::GPSImgDirection, &[(1355, 10)]),
+            ascii_field(Tag::GPSImgDirectionRef, "M"),
+            rational_field(Tag::GPSSpeed, &[(10, 1)]),
+            ascii_field(Tag::GPSSpeedRef, "N"),
+        ]);
+
+        let reading = GpsReading::from_exif(&exif);
+        let (latitude, longitude) = reading.position().expect("position should decode");
+        assert!((latitude - 48.858_2).abs() < 1e-4);
+        assert!((longitude + 2.294_5).abs() < 1e-4);
+        assert_eq!(
+            reading.image_direction,
+            Some(Bearing {
+                degrees: 135.5,
+                magnetic: true
+            })
+        );
+
+        let fields = computed_fields(&reading);
+        let direction = fields
+            .iter()
+            .find(|field| field.tag == "Image direction")
+            .expect("expected computed image direction");
+        assert_eq!(direction.value, "135.5° SE (magnetic north)");
+        let speed = fields
+            .iter()
+            .find(|field| field.tag == "Speed")
+            .expect("expected computed speed");
+        assert_eq!(speed.value, "18.5 km/h (11.5 mph)");
+    }
+
+    #[test]
+    fn gps_info_is_signed_decimal_degrees() {
+        let exif = exif_from_fields(&[
+            rational_field(Tag::GPSLatitude, &[(33, 1), (51, 1), (3168, 100)]),
+            ascii_field(Tag::GPSLatitudeRef, "S"),
+            rational_field(Tag::GPSLongitude, &[(151, 1), (12, 1), (3600, 100)]),
+            ascii_field(Tag::GPSLongitudeRef, "E"),
+            rational_field(Tag::GPSAltitude, &[(35, 10)]),
+            exif::Field {
+                tag: Tag::GPSAltitudeRef,
+                ifd_num: In::PRIMARY,
+                value: Value::Byte(vec![1]),
+            },
+            ascii_field(Tag::GPSDateStamp, "2024:03:09"),
+            rational_field(Tag::GPSTimeStamp, &[(22, 1), (5, 1), (7, 1)]),
+            rational_field(Tag::GPSImgDirection, &[(270, 1)]),
+            ascii_field(Tag::GPSImgDirectionRef, "T"),
+        ]);
+
+        let info = GpsInfo::from_exif(&exif).expect("position should decode");
+        assert!((info.latitude + 33.858_8).abs() < 1e-4);
+        assert!((info.longitude - 151.21).abs() < 1e-4);
+        assert_eq!(info.altitude, Some(-3.5));
+        assert_eq!(info.timestamp.as_deref(), Some("2024-03-09T22:05:07Z"));
+        assert_eq!(
+            info.direction,
+            Some(Bearing {
+                degrees: 270.0,
+                magnetic: false
+            })
+        );
+        let no_position = exif_from_fields(&[rational_field(Tag::GPSAltitude, &[(12, 1)])]);
+        assert_eq!(GpsInfo::from_exif(&no_position), None);
+    }
+
+    #[test]
+    fn cardinal_direction_wraps_around_north() {
+        assert_eq!(cardinal_direction(0.0), "N");
+        assert_eq!(cardinal_direction(355.0), "N");
+        assert_eq!(cardinal_direction(-90.0), "W");
+        assert_eq!(cardinal_direction(202.5), "SSW");
+    }
+}
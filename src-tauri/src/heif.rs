@@ -0,0 +1,754 @@
+use serde::Serialize;
+use std::sync::OnceLock;
+
+/// How the primary image of a HEIF file is coded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum HeifCodec {
+    Hevc,
+    Av1,
+    Jpeg,
+    Other,
+}
+
+impl HeifCodec {
+    fn from_item_type(item_type: &[u8]) -> Option<HeifCodec> {
+        match item_type {
+            b"hvc1" | b"hev1" => Some(HeifCodec::Hevc),
+            b"av01" => Some(HeifCodec::Av1),
+            b"jpeg" => Some(HeifCodec::Jpeg),
+            b"grid" | b"iden" | b"iovl" | b"Exif" | b"mime" | b"uri " => None,
+            _ => Some(HeifCodec::Other),
+        }
+    }
+}
+
+/// What the container says about the primary image, read from the `meta`
+/// box alone; no pixel data is decoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HeifImage {
+    pub codec: HeifCodec,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+}
+
+/// Whether the operating system can decode HEVC pixels for a preview.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(any(target_os = "macos", target_os = "ios"), allow(dead_code))]
+pub enum CodecStatus {
+    Available,
+    Missing,
+    Unknown,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HeicSupport {
+    /// HEIC metadata is read by the app's own parser, so this is always true.
+    pub metadata: bool,
+    pub hevc_codec: CodecStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hint: Option<String>,
+}
+
+/// What the viewer can show for one HEIF file.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HeifPreview {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image: Option<HeifImage>,
+    /// The pixels need a system decoder that is missing or unknown.
+    pub needs_codec: bool,
+    /// Base64 JPEG thumbnail embedded in the EXIF data, to show instead.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fallback_thumbnail: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+pub(crate) struct Boxes<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Iterator for Boxes<'a> {
+    type Item = (&'a [u8], &'a [u8]);
+
+    /// The next box's type and body.
+    fn next(&mut self) -> Option<Self::Item> {
+        let data = self.data;
+        let size = u32::from_be_bytes(data.get(..4)?.try_into().ok()?) as u64;
+        let kind = data.get(4..8)?;
+        let (header, size) = match size {
+            0 => (8, data.len() as u64),
+            1 => (16, u64::from_be_bytes(data.get(8..16)?.try_into().ok()?)),
+            size => (8, size),
+        };
+        let end = usize::try_from(size).ok().filter(|end| *end >= header)?;
+        let body = data.get(header..end)?;
+        self.data = &data[end..];
+        Some((kind, body))
+    }
+}
+
+pub(crate) fn boxes(data: &[u8]) -> Boxes<'_> {
+    Boxes { data }
+}
+
+pub(crate) fn child<'a>(data: &'a [u8], kind: &[u8]) -> Option<&'a [u8]> {
+    boxes(data)
+        .find(|(found, _)| *found == kind)
+        .map(|(_, body)| body)
+}
+
+pub(crate) fn u16_at(data: &[u8], at: usize) -> Option<u32> {
+    Some(u16::from_be_bytes(data.get(at..at + 2)?.try_into().ok()?) as u32)
+}
+
+pub(crate) fn u32_at(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+/// One `infe` entry of `iinf`.
+struct ItemInfo<'a> {
+    id: u32,
+    kind: &'a [u8],
+    /// The MIME type of a `mime` item, such as `application/rdf+xml` for XMP.
+    content_type: Option<&'a [u8]>,
+}
+
+fn item_infos(iinf: &[u8]) -> Vec<ItemInfo<'_>> {
+    let entries_at = if iinf.first() == Some(&0) { 6 } else { 8 };
+    boxes(iinf.get(entries_at..).unwrap_or_default())
+        .filter(|(kind, _)| *kind == b"infe")
+        .filter_map(|(_, infe)| {
+            let (id, kind_at) = match infe.first()? {
+                2 => (u16_at(infe, 4)?, 8),
+                3 => (u32_at(infe, 4)?, 10),
+                _ => return None,
+            };
+            let kind = infe.get(kind_at..kind_at + 4)?;
+            // The item name and content type follow as NUL-terminated strings.
+            let content_type = (kind == b"mime")
+                .then(|| infe.get(kind_at + 4..)?.split(|&byte| byte == 0).nth(1))
+                .flatten();
+            Some(ItemInfo {
+                id,
+                kind,
+                content_type,
+            })
+        })
+        .collect()
+}
+
+/// Reads a big-endian integer of 0, 4 or 8 bytes, as `iloc` sizes its fields.
+fn sized_at(data: &[u8], at: usize, size: usize) -> Option<u64> {
+    match size {
+        0 => Some(0),
+        4 => u32_at(data, at).map(u64::from),
+        8 => Some(u64::from_be_bytes(data.get(at..at + 8)?.try_into().ok()?)),
+        _ => None,
+    }
+}
+
+/// The bytes of `item`, joined from its `iloc` extents. Extents point into
+/// the file or, with construction method 1, into the `meta` box's `idat`.
+fn item_data(data: &[u8], meta: &[u8], item: u32) -> Option<Vec<u8>> {
+    let iloc = child(meta, b"iloc")?;
+    let version = *iloc.first()?;
+    let (offset_size, length_size) = ((iloc.get(4)? >> 4) as usize, (iloc.get(4)? & 0xF) as usize);
+    let base_offset_size = (iloc.get(5)? >> 4) as usize;
+    let index_size = if version > 0 {
+        (iloc.get(5)? & 0xF) as usize
+    } else {
+        0
+    };
+    let (count, mut at) = if version < 2 {
+        (u16_at(iloc, 6)?, 8)
+    } else {
+        (u32_at(iloc, 6)?, 10)
+    };
+    for _ in 0..count {
+        let id = if version < 2 {
+            at += 2;
+            u16_at(iloc, at - 2)?
+        } else {
+            at += 4;
+            u32_at(iloc, at - 4)?
+        };
+        let method = if version > 0 {
+            at += 2;
+            u16_at(iloc, at - 2)? & 0xF
+        } else {
+            0
+        };
+        // The data reference index, which is always 0 (this file) in practice.
+        at += 2;
+        let base = sized_at(iloc, at, base_offset_size)?;
+        at += base_offset_size;
+        let extents = u16_at(iloc, at)?;
+        at += 2;
+        let source = match method {
+            0 => data,
+            1 => child(meta, b"idat")?,
+            _ => &[],
+        };
+        let mut bytes = Vec::new();
+        for _ in 0..extents {
+            at += index_size;
+            let offset = base.checked_add(sized_at(iloc, at, offset_size)?)? as usize;
+            at += offset_size;
+            let length = sized_at(iloc, at, length_size)? as usize;
+            at += length_size;
+            if id == item {
+                // A length of 0 runs to the end of the source.
+                let end = match length {
+                    0 => source.len(),
+                    length => offset.checked_add(length)?,
+                };
+                bytes.extend_from_slice(source.get(offset..end)?);
+            }
+        }
+        if id == item {
+            return Some(bytes);
+        }
+    }
+    None
+}
+
+/// The body of a HEIF file's `meta` box after its version and flags.
+fn meta(data: &[u8]) -> Option<&[u8]> {
+    if child(data, b"ftyp")?.len() < 8 {
+        return None;
+    }
+    child(data, b"meta")?.get(4..)
+}
+
+/// The bytes of the first item that `matches`.
+fn find_item(data: &[u8], matches: impl Fn(&ItemInfo) -> bool) -> Option<Vec<u8>> {
+    let meta = meta(data)?;
+    let item = item_infos(child(meta, b"iinf")?)
+        .into_iter()
+        .find(|info| matches(info))?;
+    item_data(data, meta, item.id)
+}
+
+/// The TIFF-encoded EXIF of a HEIC or AVIF file, from its `Exif` item.
+pub fn exif(data: &[u8]) -> Option<Vec<u8>> {
+    let item = find_item(data, |info| info.kind == b"Exif")?;
+    // The item starts with the offset of the TIFF header past these 4 bytes,
+    // which skips the `Exif\0\0` some encoders write first.
+    let header = (u32_at(&item, 0)? as usize).checked_add(4)?;
+    Some(item.get(header..)?.to_vec())
+}
+
+/// The XMP packet of a HEIC or AVIF file, from its `application/rdf+xml` item.
+pub fn xmp(data: &[u8]) -> Option<Vec<u8>> {
+    find_item(data, |info| {
+        info.content_type == Some(b"application/rdf+xml".as_slice())
+    })
+}
+
+/// A property of an item: its box type and body, and whether the item
+/// marks it essential.
+type Property<'a> = (&'a [u8], &'a [u8], bool);
+
+/// The properties associated with `item` through `ipma`, in order.
+fn item_properties(iprp: &[u8], item: u32) -> Option<Vec<Property<'_>>> {
+    let properties: Vec<(&[u8], &[u8])> = boxes(child(iprp, b"ipco")?).collect();
+    let ipma = child(iprp, b"ipma")?;
+    let (version, wide_index) = (*ipma.first()?, ipma.get(3)? & 1 == 1);
+    let mut found = Vec::new();
+    let mut at = 8;
+    for _ in 0..u32_at(ipma, 4)? {
+        let id = if version < 1 {
+            at += 2;
+            u16_at(ipma, at - 2)?
+        } else {
+            at += 4;
+            u32_at(ipma, at - 4)?
+        };
+        let count = *ipma.get(at)? as usize;
+        at += 1;
+        for _ in 0..count {
+            let (index, essential) = if wide_index {
+                at += 2;
+                let entry = u16_at(ipma, at - 2)?;
+                (entry & 0x7FFF, entry & 0x8000 != 0)
+            } else {
+                at += 1;
+                let entry = *ipma.get(at - 1)?;
+                ((entry & 0x7F) as u32, entry & 0x80 != 0)
+            };
+            if id != item || index == 0 {
+                continue;
+            }
+            if let Some(&(kind, body)) = properties.get(index as usize - 1) {
+                found.push((kind, body, essential));
+            }
+        }
+    }
+    Some(found)
+}
+
+/// The `ispe` width and height among `properties`.
+fn spatial_extent(properties: &[Property]) -> Option<(u32, u32)> {
+    let (_, ispe, _) = properties.iter().find(|(kind, _, _)| *kind == b"ispe")?;
+    Some((u32_at(ispe, 4)?, u32_at(ispe, 8)?))
+}
+
+/// The `ispe` width and height associated with `item` through `ipma`.
+fn dimensions(iprp: &[u8], item: u32) -> Option<(u32, u32)> {
+    spatial_extent(&item_properties(iprp, item)?)
+}
+
+fn primary_item(meta: &[u8]) -> Option<u32> {
+    let pitm = child(meta, b"pitm")?;
+    match pitm.first()? {
+        0 => u16_at(pitm, 4),
+        _ => u32_at(pitm, 4),
+    }
+}
+
+/// Reads the primary image's coding and size from a HEIF/AVIF container.
+/// Grids report the coding of their tiles.
+pub fn primary_image(data: &[u8]) -> Option<HeifImage> {
+    let meta = meta(data)?;
+    let items = item_infos(child(meta, b"iinf")?);
+    let primary = primary_item(meta);
+    let primary_type = primary
+        .and_then(|primary| items.iter().find(|info| info.id == primary))
+        .map(|info| info.kind);
+    let codec = primary_type
+        .and_then(HeifCodec::from_item_type)
+        .or_else(|| {
+            items
+                .iter()
+                .find_map(|info| HeifCodec::from_item_type(info.kind))
+        })?;
+    let size = primary.and_then(|primary| dimensions(child(meta, b"iprp")?, primary));
+    Some(HeifImage {
+        codec,
+        width: size.map(|(width, _)| width),
+        height: size.map(|(_, height)| height),
+    })
+}
+
+/// A thumbnail item of a HEIF file, ready to be shown on its own.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeifThumbnail {
+    pub codec: HeifCodec,
+    pub width: u32,
+    pub height: u32,
+    /// A JPEG, or a HEIF file holding just the thumbnail.
+    pub file: Vec<u8>,
+}
+
+/// The items that point at `to` with a `kind` reference in `iref`.
+fn referencing_items(meta: &[u8], kind: &[u8], to: u32) -> Vec<u32> {
+    let Some(iref) = child(meta, b"iref") else {
+        return Vec::new();
+    };
+    // Version 1 widens item ids to 32 bits.
+    let width = if iref.first() == Some(&1) { 4 } else { 2 };
+    let id_at = |data: &[u8], at: usize| match width {
+        4 => u32_at(data, at),
+        _ => u16_at(data, at),
+    };
+    boxes(iref.get(4..).unwrap_or_default())
+        .filter(|(found, _)| *found == kind)
+        .filter_map(|(_, reference)| {
+            let from = id_at(reference, 0)?;
+            let count = u16_at(reference, width)? as usize;
+            (0..count)
+                .any(|index| id_at(reference, width + 2 + index * width) == Some(to))
+                .then_some(from)
+        })
+        .collect()
+}
+
+fn write_box(kind: &[u8], body: &[u8]) -> Vec<u8> {
+    let mut data = ((body.len() + 8) as u32).to_be_bytes().to_vec();
+    data.extend_from_slice(kind);
+    data.extend_from_slice(body);
+    data
+}
+
+/// The smallest HEIF file showing one coded image: a single item of type
+/// `kind` with the given properties, and `coded` as its data in `mdat`.
+fn standalone_file(brand: &[u8; 4], kind: &[u8], properties: &[Property], coded: &[u8]) -> Vec<u8> {
+    let ftyp = [brand.as_slice(), &[0; 4], b"mif1", brand].concat();
+    let mut ipco = Vec::new();
+    let mut ipma = vec![0, 0, 0, 0, 0, 0, 0, 1, 0, 1, properties.len() as u8];
+    for (index, (kind, body, essential)) in (1u8..).zip(properties) {
+        ipco.extend(write_box(kind, body));
+        ipma.push(if *essential { index | 0x80 } else { index });
+    }
+    let iprp = [write_box(b"ipco", &ipco), write_box(b"ipma", &ipma)].concat();
+    let infe = [&[2, 0, 0, 0, 0, 1, 0, 0], kind, &[0]].concat();
+    let iinf = [&[0, 0, 0, 0, 0, 1][..], &write_box(b"infe", &infe)].concat();
+    let meta = |offset: u32| {
+        // Version 0 with 4-byte offsets and lengths: item 1, one extent.
+        let mut iloc = vec![0, 0, 0, 0, 0x44, 0x00, 0, 1, 0, 1, 0, 0, 0, 1];
+        iloc.extend_from_slice(&offset.to_be_bytes());
+        iloc.extend_from_slice(&(coded.len() as u32).to_be_bytes());
+        let mut hdlr = vec![0; 8];
+        hdlr.extend_from_slice(b"pict");
+        hdlr.extend_from_slice(&[0; 13]);
+        let mut meta = vec![0; 4];
+        meta.extend(write_box(b"hdlr", &hdlr));
+        meta.extend(write_box(b"pitm", &[0, 0, 0, 0, 0, 1]));
+        meta.extend(write_box(b"iloc", &iloc));
+        meta.extend(write_box(b"iinf", &iinf));
+        meta.extend(write_box(b"iprp", &iprp));
+        write_box(b"meta", &meta)
+    };
+    let mut file = write_box(b"ftyp", &ftyp);
+    let offset = file.len() + meta(0).len() + 8;
+    file.extend(meta(offset as u32));
+    file.extend(write_box(b"mdat", coded));
+    file
+}
+
+/// The thumbnail item (`thmb` reference) of the primary image. HEVC and
+/// AV1 thumbnails come wrapped in a file of their own with the decoder
+/// configuration, so anything that shows HEIC or AVIF can show them.
+pub fn thumbnail(data: &[u8]) -> Option<HeifThumbnail> {
+    let meta = meta(data)?;
+    let items = item_infos(child(meta, b"iinf")?);
+    let iprp = child(meta, b"iprp")?;
+    referencing_items(meta, b"thmb", primary_item(meta)?)
+        .into_iter()
+        .find_map(|id| {
+            let info = items.iter().find(|info| info.id == id)?;
+            let codec = HeifCodec::from_item_type(info.kind)?;
+            let properties = item_properties(iprp, id)?;
+            let (width, height) = spatial_extent(&properties)?;
+            let coded = item_data(data, meta, id)?;
+            let file = match codec {
+                HeifCodec::Jpeg => coded,
+                HeifCodec::Hevc => standalone_file(b"heic", info.kind, &properties, &coded),
+                HeifCodec::Av1 => standalone_file(b"avif", info.kind, &properties, &coded),
+                HeifCodec::Other => return None,
+            };
+            Some(HeifThumbnail {
+                codec,
+                width,
+                height,
+                file,
+            })
+        })
+}
+
+#[cfg(target_os = "windows")]
+fn probe_hevc_codec() -> CodecStatus {
+    // Both the paid and the OEM builds of "HEVC Video Extensions" match.
+    let output = std::process::Command::new("powershell")
+        .args([
+            "-NoProfile",
+            "-NonInteractive",
+            "-Command",
+            "(Get-AppxPackage -Name Microsoft.HEVCVideoExtension*).Name",
+        ])
+        .output();
+    match output {
+        Ok(output) if output.status.success() => {
+            if String::from_utf8_lossy(&output.stdout).trim().is_empty() {
+                CodecStatus::Missing
+            } else {
+                CodecStatus::Available
+            }
+        }
+        _ => CodecStatus::Unknown,
+    }
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+fn probe_hevc_codec() -> CodecStatus {
+    CodecStatus::Available
+}
+
+/// WebKitGTK decodes through gdk-pixbuf, which handles HEIC only with the
+/// libheif loader installed.
+#[cfg(target_os = "linux")]
+fn probe_hevc_codec() -> CodecStatus {
+    const LOADERS: &str = "gdk-pixbuf-2.0/2.10.0/loaders";
+    let loader_dirs: Vec<std::path::PathBuf> = ["/usr/lib", "/usr/lib64", "/usr/local/lib"]
+        .iter()
+        .flat_map(|lib| {
+            let multiarch = std::fs::read_dir(lib)
+                .into_iter()
+                .flatten()
+                .flatten()
+                .map(|entry| entry.path().join(LOADERS));
+            std::iter::once(std::path::Path::new(lib).join(LOADERS)).chain(multiarch)
+        })
+        .filter(|dir| dir.is_dir())
+        .collect();
+    if loader_dirs.is_empty() {
+        return CodecStatus::Unknown;
+    }
+    let heif_loader = loader_dirs
+        .iter()
+        .filter_map(|dir| std::fs::read_dir(dir).ok())
+        .flatten()
+        .flatten()
+        .any(|entry| entry.file_name().to_string_lossy().contains("heif"));
+    if heif_loader {
+        CodecStatus::Available
+    } else {
+        CodecStatus::Missing
+    }
+}
+
+#[cfg(not(any(
+    target_os = "windows",
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "linux"
+)))]
+fn probe_hevc_codec() -> CodecStatus {
+    CodecStatus::Unknown
+}
+
+/// The platform's HEVC decoder status, probed once per run.
+pub fn hevc_codec() -> CodecStatus {
+    static STATUS: OnceLock<CodecStatus> = OnceLock::new();
+    *STATUS.get_or_init(probe_hevc_codec)
+}
+
+pub fn support() -> HeicSupport {
+    let hevc_codec = hevc_codec();
+    let hint = (hevc_codec == CodecStatus::Missing).then(|| {
+        if cfg!(target_os = "windows") {
+            "Install \"HEVC Video Extensions\" from the Microsoft Store to see HEIC previews. Metadata is read without it."
+        } else {
+            "Install the libheif loader for gdk-pixbuf (often packaged as heif-gdk-pixbuf) to see HEIC previews. Metadata is read without it."
+        }
+        .to_string()
+    });
+    HeicSupport {
+        metadata: true,
+        hevc_codec,
+        hint,
+    }
+}
+
+/// Explains what can be shown for the HEIF file in `data`, given the
+/// platform's codec status.
+pub fn preview(data: &[u8], codec: CodecStatus) -> HeifPreview {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+
+    let image = primary_image(data);
+    let needs_codec = image.is_some_and(|image| image.codec == HeifCodec::Hevc)
+        && codec != CodecStatus::Available;
+    let fallback_thumbnail = needs_codec
+        .then(|| crate::read_exif_container(data))
+        .flatten()
+        .and_then(|exif| {
+            crate::thumbnail::embedded_thumbnail(&exif).map(|jpeg| STANDARD.encode(jpeg))
+        });
+    let message = needs_codec.then(|| {
+        let missing = match codec {
+            CodecStatus::Missing => "this system has no HEVC decoder",
+            _ => "no HEVC decoder could be found on this system",
+        };
+        if fallback_thumbnail.is_some() {
+            format!("The image is HEVC-coded and {}, so the embedded thumbnail is shown instead. All metadata is still read.", missing)
+        } else {
+            format!("The image is HEVC-coded and {}, so it cannot be previewed. All metadata is still read.", missing)
+        }
+    });
+    HeifPreview {
+        image,
+        needs_codec,
+        fallback_thumbnail,
+        message,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn isobmff_box(kind: &[u8], body: &[u8]) -> Vec<u8> {
+        let mut out = ((body.len() + 8) as u32).to_be_bytes().to_vec();
+        out.extend_from_slice(kind);
+        out.extend_from_slice(body);
+        out
+    }
+
+    fn infe(id: u16, kind: &[u8]) -> Vec<u8> {
+        let mut body = vec![2, 0, 0, 0];
+        body.extend_from_slice(&id.to_be_bytes());
+        body.extend_from_slice(&[0, 0]);
+        body.extend_from_slice(kind);
+        body.push(0);
+        isobmff_box(b"infe", &body)
+    }
+
+    /// A HEIC with an HEVC primary item 1 of 4032x3024 and an Exif item 2.
+    fn heic() -> Vec<u8> {
+        let mut iinf = vec![0, 0, 0, 0, 0, 2];
+        iinf.extend(infe(1, b"hvc1"));
+        iinf.extend(infe(2, b"Exif"));
+        let mut ispe = vec![0; 4];
+        ispe.extend_from_slice(&4032u32.to_be_bytes());
+        ispe.extend_from_slice(&3024u32.to_be_bytes());
+        let ipco = isobmff_box(b"ipco", &isobmff_box(b"ispe", &ispe));
+        // One entry: item 1 has property 1, marked essential.
+        let ipma = isobmff_box(b"ipma", &[0, 0, 0, 0, 0, 0, 0, 1, 0, 1, 1, 0x81]);
+        let mut meta = vec![0; 4];
+        meta.extend(isobmff_box(b"pitm", &[0, 0, 0, 0, 0, 1]));
+        meta.extend(isobmff_box(b"iinf", &iinf));
+        meta.extend(isobmff_box(b"iprp", &[ipco, ipma].concat()));
+        let mut file = isobmff_box(b"ftyp", b"heic\0\0\0\0mif1heic");
+        file.extend(isobmff_box(b"meta", &meta));
+        file
+    }
+
+    #[test]
+    fn reads_the_primary_coding_and_size_without_decoding() {
+        assert_eq!(
+            primary_image(&heic()),
+            Some(HeifImage {
+                codec: HeifCodec::Hevc,
+                width: Some(4032),
+                height: Some(3024),
+            })
+        );
+        assert_eq!(primary_image(b"\xFF\xD8\xFF\xD9"), None);
+    }
+
+    #[test]
+    fn exif_and_xmp_items_are_resolved_through_iloc() {
+        use crate::fixtures::{ascii_field, tiff_from_fields};
+        use exif::Tag;
+
+        let mut exif_item = 6u32.to_be_bytes().to_vec();
+        exif_item.extend_from_slice(b"Exif\0\0");
+        exif_item.extend(tiff_from_fields(&[ascii_field(
+            Tag::Model,
+            "iPhone 15 Pro",
+        )]));
+        let packet = br#"<x:xmpmeta xmlns:x="adobe:ns:meta/"><rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#"><rdf:Description xmlns:xmp="http://ns.adobe.com/xap/1.0/" xmp:Rating="5"/></rdf:RDF></x:xmpmeta>"#;
+
+        let mut iinf = vec![0, 0, 0, 0, 0, 3];
+        iinf.extend(infe(1, b"hvc1"));
+        iinf.extend(infe(2, b"Exif"));
+        let mut mime = vec![2, 0, 0, 0, 0, 3, 0, 0];
+        mime.extend_from_slice(b"mime\0application/rdf+xml\0");
+        iinf.extend(isobmff_box(b"infe", &mime));
+        let meta = |exif_at: u32| {
+            // Version 1, 4-byte offsets and lengths, no base offset. Item 2
+            // is in the file in two extents, item 3 in `idat`.
+            let mut iloc = vec![1, 0, 0, 0, 0x44, 0x00, 0, 2];
+            iloc.extend_from_slice(&[0, 2, 0, 0, 0, 0, 0, 2]);
+            iloc.extend_from_slice(&exif_at.to_be_bytes());
+            iloc.extend_from_slice(&10u32.to_be_bytes());
+            iloc.extend_from_slice(&(exif_at + 10).to_be_bytes());
+            iloc.extend_from_slice(&(exif_item.len() as u32 - 10).to_be_bytes());
+            iloc.extend_from_slice(&[0, 3, 0, 1, 0, 0, 0, 1, 0, 0, 0, 0]);
+            iloc.extend_from_slice(&(packet.len() as u32).to_be_bytes());
+            let mut meta = vec![0; 4];
+            meta.extend(isobmff_box(b"pitm", &[0, 0, 0, 0, 0, 1]));
+            meta.extend(isobmff_box(b"iinf", &iinf));
+            meta.extend(isobmff_box(b"iloc", &iloc));
+            meta.extend(isobmff_box(b"idat", packet));
+            isobmff_box(b"meta", &meta)
+        };
+        let mut file = isobmff_box(b"ftyp", b"heic\0\0\0\0mif1heic");
+        let exif_at = (file.len() + meta(0).len() + 8) as u32;
+        file.extend(meta(exif_at));
+        file.extend(isobmff_box(b"mdat", &exif_item));
+
+        assert_eq!(exif(&file).as_deref(), Some(&exif_item[10..]));
+        assert_eq!(xmp(&file).as_deref(), Some(packet.as_slice()));
+        let fields = crate::collect_fields_from_bytes(&file).unwrap();
+        let value = |tag: &str| {
+            fields
+                .iter()
+                .find(|field| field.tag == tag)
+                .map(|field| field.value.clone())
+        };
+        assert_eq!(value("Model").as_deref(), Some("\"iPhone 15 Pro\""));
+        assert_eq!(value("xmp:Rating").as_deref(), Some("5"));
+        assert_eq!(exif(&heic()), None);
+    }
+
+    #[test]
+    fn hevc_thumbnail_items_are_wrapped_in_a_file_of_their_own() {
+        let ispe = |width: u32, height: u32| {
+            let body = [[0; 4], width.to_be_bytes(), height.to_be_bytes()].concat();
+            isobmff_box(b"ispe", &body)
+        };
+        let mut iinf = vec![0, 0, 0, 0, 0, 2];
+        iinf.extend(infe(1, b"hvc1"));
+        iinf.extend(infe(2, b"hvc1"));
+        let ipco = [
+            ispe(4032, 3024),
+            isobmff_box(b"hvcC", &[1; 23]),
+            ispe(320, 240),
+        ]
+        .concat();
+        // Item 1 has properties 1 and 2, item 2 (the thumbnail) 2 and 3.
+        let ipma = [
+            0, 0, 0, 0, 0, 0, 0, 2, 0, 1, 2, 0x81, 0x02, 0, 2, 2, 0x82, 0x03,
+        ];
+        let iref = [
+            &[0, 0, 0, 0][..],
+            &isobmff_box(b"thmb", &[0, 2, 0, 1, 0, 1]),
+        ]
+        .concat();
+        let coded = [0, 0, 0, 4, 0x26, 0x01, 0xAF, 0x09];
+        let meta = |offset: u32| {
+            let mut iloc = vec![0, 0, 0, 0, 0x44, 0x00, 0, 1, 0, 2, 0, 0, 0, 1];
+            iloc.extend_from_slice(&offset.to_be_bytes());
+            iloc.extend_from_slice(&(coded.len() as u32).to_be_bytes());
+            let mut meta = vec![0; 4];
+            meta.extend(isobmff_box(b"pitm", &[0, 0, 0, 0, 0, 1]));
+            meta.extend(isobmff_box(b"iinf", &iinf));
+            meta.extend(isobmff_box(b"iref", &iref));
+            meta.extend(isobmff_box(b"iloc", &iloc));
+            let iprp = [isobmff_box(b"ipco", &ipco), isobmff_box(b"ipma", &ipma)].concat();
+            meta.extend(isobmff_box(b"iprp", &iprp));
+            isobmff_box(b"meta", &meta)
+        };
+        let mut file = isobmff_box(b"ftyp", b"heic\0\0\0\0mif1heic");
+        let offset = (file.len() + meta(0).len() + 8) as u32;
+        file.extend(meta(offset));
+        file.extend(isobmff_box(b"mdat", &coded));
+
+        let found = thumbnail(&file).unwrap();
+        assert_eq!((found.width, found.height), (320, 240));
+        assert_eq!(
+            primary_image(&found.file),
+            Some(HeifImage {
+                codec: HeifCodec::Hevc,
+                width: Some(320),
+                height: Some(240),
+            })
+        );
+        assert_eq!(
+            find_item(&found.file, |info| info.id == 1).as_deref(),
+            Some(coded.as_slice())
+        );
+        assert_eq!(thumbnail(&heic()), None);
+    }
+
+    #[test]
+    fn a_missing_codec_is_explained() {
+        let missing = preview(&heic(), CodecStatus::Missing);
+        assert!(missing.needs_codec);
+        assert!(missing
+            .message
+            .as_deref()
+            .is_some_and(|message| message.contains("metadata is still read")));
+        let available = preview(&heic(), CodecStatus::Available);
+        assert!(!available.needs_codec);
+        assert_eq!(available.message, None);
+    }
+}
@@ -0,0 +1,377 @@
+use crate::{jpeg, ExifField};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use exif::{Context, Exif, In, Tag, Value};
+use flate2::read::ZlibDecoder;
+use serde::Serialize;
+use std::io::Read;
+
+pub const ICC_IFD: &str = "ICC Profile";
+
+pub(crate) const ICC_PROFILE: Tag = Tag(Context::Tiff, 34675);
+const ICC_MARKER: &[u8] = b"ICC_PROFILE\0";
+const APP2: u8 = 0xE2;
+/// Profiles are at most a few megabytes; anything larger is not one.
+const MAX_PROFILE_BYTES: u64 = 16 * 1024 * 1024;
+
+/// What an embedded profile says about itself.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IccSummary {
+    pub description: Option<String>,
+    pub color_space: Option<String>,
+    pub device_class: Option<String>,
+    pub version: Option<String>,
+    pub white_point: Option<String>,
+    pub rendering_intent: Option<String>,
+}
+
+/// The summary with the raw profile, base64-encoded, for saving it as a
+/// `.icc` file.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IccProfile {
+    #[serde(flatten)]
+    pub summary: IccSummary,
+    pub size: usize,
+    pub data: String,
+}
+
+/// The profile from JPEG APP2 chunks, a PNG `iCCP` chunk or TIFF tag 34675,
+/// reading the EXIF block for the last only when `exif` is not given.
+pub fn extract(data: &[u8], exif: Option<&Exif>) -> Option<Vec<u8>> {
+    app2_profile(data)
+        .or_else(|| png_profile(data))
+        .or_else(|| match exif {
+            Some(exif) => tagged_profile(exif),
+            None => crate::read_exif_container(data)
+                .as_ref()
+                .and_then(tagged_profile),
+        })
+}
+
+fn tagged_profile(exif: &Exif) -> Option<Vec<u8>> {
+    match &exif.get_field(ICC_PROFILE, In::PRIMARY)?.value {
+        Value::Undefined(bytes, _) | Value::Byte(bytes) => Some(bytes.clone()),
+        _ => None,
+    }
+}
+
+/// APP2 chunks carry a sequence number and count, and may arrive out of order.
+fn app2_profile(data: &[u8]) -> Option<Vec<u8>> {
+    let (segments, _) = jpeg::segments(data).ok()?;
+    let mut chunks: Vec<(u8, &[u8])> = segments
+        .iter()
+        .filter(|segment| segment.marker == APP2)
+        .filter_map(|segment| {
+            let payload = segment.payload(data).strip_prefix(ICC_MARKER)?;
+            Some((*payload.first()?, payload.get(2..)?))
+        })
+        .collect();
+    chunks.sort_by_key(|(sequence, _)| *sequence);
+    (!chunks.is_empty()).then(|| {
+        chunks
+            .into_iter()
+            .flat_map(|(_, chunk)| chunk.to_vec())
+            .collect()
+    })
+}
+
+/// `iCCP` holds a profile name, a NUL, the compression method (always
+/// zlib) and the compressed profile.
+fn png_profile(data: &[u8]) -> Option<Vec<u8>> {
+    let mut rest = data.strip_prefix(&crate::PNG_SIGNATURE)?;
+    while rest.len() >= 12 {
+        let length = u32::from_be_bytes(rest[..4].try_into().ok()?) as usize;
+        let chunk = rest.get(8..8 + length)?;
+        match &rest[4..8] {
+            b"iCCP" => {
+                let separator = chunk.iter().position(|&byte| byte == 0)?;
+                let mut profile = Vec::new();
+                ZlibDecoder::new(chunk.get(separator + 2..)?)
+                    .take(MAX_PROFILE_BYTES)
+                    .read_to_end(&mut profile)
+                    .ok()?;
+                return Some(profile);
+            }
+            // The profile must come before the image data.
+            b"IDAT" | b"IEND" => return None,
+            _ => rest = rest.get(12 + length..)?,
+        }
+    }
+    None
+}
+
+fn u32_at(profile: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(
+        profile.get(at..at + 4)?.try_into().ok()?,
+    ))
+}
+
+/// The data of tag `signature` from the profile's tag table.
+fn tag<'a>(profile: &'a [u8], signature: &[u8; 4]) -> Option<&'a [u8]> {
+    let count = u32_at(profile, 128)? as usize;
+    (0..count.min(256)).find_map(|index| {
+        let entry = 132 + index * 12;
+        if profile.get(entry..entry + 4)? != signature {
+            return None;
+        }
+        let offset = u32_at(profile, entry + 4)? as usize;
+        let size = u32_at(profile, entry + 8)? as usize;
+        profile.get(offset..offset.checked_add(size)?)
+    })
+}
+
+/// The profile's description from its `desc` tag, in the ICC v2 text form
+/// or the v4 multi-localized form.
+pub fn description(profile: &[u8]) -> Option<String> {
+    let tag = tag(profile, b"desc")?;
+    let description = match tag.get(..4)? {
+        b"desc" => {
+            let length = u32_at(tag, 8)? as usize;
+            String::from_utf8_lossy(tag.get(12..12 + length)?).into_owned()
+        }
+        b"mluc" => {
+            let length = u32_at(tag, 20)? as usize;
+            let start = u32_at(tag, 24)? as usize;
+            let units: Vec<u16> = tag
+                .get(start..start + length)?
+                .chunks_exact(2)
+                .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+                .collect();
+            String::from_utf16_lossy(&units)
+        }
+        _ => return None,
+    };
+    let description = description.trim_matches(['\0', ' ']).to_string();
+    (!description.is_empty()).then_some(description)
+}
+
+fn signature_at(profile: &[u8], at: usize) -> Option<&str> {
+    std::str::from_utf8(profile.get(at..at + 4)?)
+        .ok()
+        .map(str::trim_end)
+}
+
+fn color_space(profile: &[u8]) -> Option<String> {
+    let signature = signature_at(profile, 16)?;
+    Some(
+        match signature {
+            "RGB" => "RGB",
+            "GRAY" => "Grayscale",
+            "CMYK" => "CMYK",
+            "CMY" => "CMY",
+            "Lab" => "CIELAB",
+            "XYZ" => "CIEXYZ",
+            "YCbr" => "YCbCr",
+            "HSV" => "HSV",
+            other => other,
+        }
+        .to_string(),
+    )
+}
+
+fn device_class(profile: &[u8]) -> Option<String> {
+    let signature = signature_at(profile, 12)?;
+    Some(
+        match signature {
+            "scnr" => "Input device",
+            "mntr" => "Display",
+            "prtr" => "Output device",
+            "link" => "Device link",
+            "spac" => "Color space",
+            "abst" => "Abstract",
+            "nmcl" => "Named color",
+            other => other,
+        }
+        .to_string(),
+    )
+}
+
+/// Major, minor and bug-fix digits from the BCD version field.
+fn version(profile: &[u8]) -> Option<String> {
+    let (major, minor) = (*profile.get(8)?, *profile.get(9)?);
+    (major > 0).then(|| format!("{}.{}.{}", major, minor >> 4, minor & 0x0F))
+}
+
+fn rendering_intent(profile: &[u8]) -> Option<String> {
+    let intent = match u32_at(profile, 64)? {
+        0 => "Perceptual",
+        1 => "Media-relative colorimetric",
+        2 => "Saturation",
+        3 => "ICC-absolute colorimetric",
+        _ => return None,
+    };
+    Some(intent.to_string())
+}
+
+/// The `wtpt` tag as XYZ, named when it is D50 or D65.
+fn white_point(profile: &[u8]) -> Option<String> {
+    let tag = tag(profile, b"wtpt")?;
+    if tag.get(..4)? != b"XYZ " {
+        return None;
+    }
+    let fixed = |at: usize| Some(f64::from(u32_at(tag, at)? as i32) / 65536.0);
+    let (x, y, z) = (fixed(8)?, fixed(12)?, fixed(16)?);
+    let mut text = format!("X {:.4}, Y {:.4}, Z {:.4}", x, y, z);
+    let sum = x + y + z;
+    if sum > 0.0 {
+        let (cx, cy) = (x / sum, y / sum);
+        let named = [("D50", 0.3457, 0.3585), ("D65", 0.3127, 0.3290)]
+            .into_iter()
+            .find(|(_, nx, ny)| (cx - nx).abs() < 0.002 && (cy - ny).abs() < 0.002);
+        if let Some((name, ..)) = named {
+            text.push_str(&format!(" ({})", name));
+        }
+    }
+    Some(text)
+}
+
+/// Reads the header and the tags the summary needs; `None` when the bytes
+/// are not a profile.
+pub fn summarize(profile: &[u8]) -> Option<IccSummary> {
+    if profile.get(36..40)? != b"acsp" {
+        return None;
+    }
+    Some(IccSummary {
+        description: description(profile),
+        color_space: color_space(profile),
+        device_class: device_class(profile),
+        version: version(profile),
+        white_point: white_point(profile),
+        rendering_intent: rendering_intent(profile),
+    })
+}
+
+/// The summary of the embedded profile as fields of an `ICC Profile` group.
+pub fn fields(data: &[u8]) -> Vec<ExifField> {
+    let Some(summary) = extract(data, None).as_deref().and_then(summarize) else {
+        return Vec::new();
+    };
+    [
+        ("ProfileDescription", summary.description),
+        ("ColorSpace", summary.color_space),
+        ("DeviceClass", summary.device_class),
+        ("ProfileVersion", summary.version),
+        ("WhitePoint", summary.white_point),
+        ("RenderingIntent", summary.rendering_intent),
+    ]
+    .into_iter()
+    .filter_map(|(tag, value)| {
+        Some(ExifField {
+            tag: tag.to_string(),
+            ifd: ICC_IFD.to_string(),
+            value: value?,
+        })
+    })
+    .collect()
+}
+
+/// The embedded profile of `data` with its summary and bytes.
+pub fn profile(data: &[u8]) -> Result<IccProfile, String> {
+    let profile =
+        extract(data, None).ok_or_else(|| "This file has no embedded ICC profile.".to_string())?;
+    let summary =
+        summarize(&profile).ok_or_else(|| "The embedded ICC profile is damaged.".to_string())?;
+    Ok(IccProfile {
+        summary,
+        size: profile.len(),
+        data: STANDARD.encode(&profile),
+    })
+}
+
+#[cfg(test)]
+pub(crate) fn profile_with_description(description: &str) -> Vec<u8> {
+    let mut profile = vec![0; 128];
+    profile[8] = 2;
+    profile[9] = 0x10;
+    profile[12..16].copy_from_slice(b"mntr");
+    profile[16..20].copy_from_slice(b"RGB ");
+    profile[36..40].copy_from_slice(b"acsp");
+    profile[64..68].copy_from_slice(&1u32.to_be_bytes());
+    let desc_at = 128 + 4 + 2 * 12;
+    let mut desc = b"desc\0\0\0\0".to_vec();
+    desc.extend_from_slice(&(description.len() as u32 + 1).to_be_bytes());
+    desc.extend_from_slice(description.as_bytes());
+    desc.push(0);
+    let wtpt_at = desc_at + desc.len();
+    let mut wtpt = b"XYZ \0\0\0\0".to_vec();
+    // D65: 0.9505, 1.0, 1.0891.
+    for value in [62289u32, 65536, 71376] {
+        wtpt.extend_from_slice(&value.to_be_bytes());
+    }
+    profile.extend_from_slice(&2u32.to_be_bytes());
+    for (signature, at, size) in [(b"desc", desc_at, desc.len()), (b"wtpt", wtpt_at, 20)] {
+        profile.extend_from_slice(signature);
+        profile.extend_from_slice(&(at as u32).to_be_bytes());
+        profile.extend_from_slice(&(size as u32).to_be_bytes());
+    }
+    profile.extend(desc);
+    profile.extend(wtpt);
+    profile
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{write::ZlibEncoder, Compression};
+    use std::io::Write;
+
+    fn png_with_profile(profile: &[u8]) -> Vec<u8> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(profile).unwrap();
+        let mut chunk = b"sRGB IEC61966-2.1\0\0".to_vec();
+        chunk.extend(encoder.finish().unwrap());
+        let mut png = crate::PNG_SIGNATURE.to_vec();
+        png.extend_from_slice(&(chunk.len() as u32).to_be_bytes());
+        png.extend_from_slice(b"iCCP");
+        png.extend(chunk);
+        png.extend_from_slice(&[0; 4]);
+        png
+    }
+
+    #[test]
+    fn png_profiles_are_summarized() {
+        let profile = profile_with_description("Display P3");
+        let summary = summarize(&extract(&png_with_profile(&profile), None).unwrap()).unwrap();
+
+        assert_eq!(
+            summary,
+            IccSummary {
+                description: Some("Display P3".to_string()),
+                color_space: Some("RGB".to_string()),
+                device_class: Some("Display".to_string()),
+                version: Some("2.1.0".to_string()),
+                white_point: Some("X 0.9505, Y 1.0000, Z 1.0891 (D65)".to_string()),
+                rendering_intent: Some("Media-relative colorimetric".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn jpeg_chunks_are_joined_in_sequence_order() {
+        let profile = profile_with_description("Adobe RGB (1998)");
+        let (first, second) = profile.split_at(100);
+        let mut data = jpeg::SOI.to_vec();
+        for (sequence, part) in [(2u8, second), (1, first)] {
+            let mut payload = ICC_MARKER.to_vec();
+            payload.extend_from_slice(&[sequence, 2]);
+            payload.extend_from_slice(part);
+            data.extend(jpeg::encode_segment(APP2, &payload).unwrap());
+        }
+        data.extend_from_slice(&[0xFF, jpeg::SOS, 0x00, 0x02, 0xFF, 0xD9]);
+
+        let listed = profile_fields(&data);
+        assert_eq!(listed["ProfileDescription"], "Adobe RGB (1998)");
+        assert_eq!(
+            STANDARD.decode(self::profile(&data).unwrap().data).unwrap(),
+            profile
+        );
+    }
+
+    fn profile_fields(data: &[u8]) -> std::collections::HashMap<String, String> {
+        fields(data)
+            .into_iter()
+            .map(|field| (field.tag, field.value))
+            .collect()
+    }
+}
@@ -0,0 +1,131 @@
+/// One 12-byte IFD entry.
+#[derive(Debug, Clone, Copy)]
+pub struct Entry {
+    pub tag: u16,
+    kind: u16,
+    pub count: u32,
+    /// Where the entry's value-or-offset word sits.
+    at: usize,
+}
+
+/// Reads IFDs that kamadak-exif does not walk, such as DNG SubIFDs and
+/// vendor MakerNotes. Offsets are relative to the start of `data`.
+pub struct Tiff<'a> {
+    data: &'a [u8],
+    little_endian: bool,
+}
+
+fn unit_size(kind: u16) -> Option<usize> {
+    match kind {
+        1 | 2 | 6 | 7 => Some(1),
+        3 | 8 => Some(2),
+        4 | 9 | 11 | 13 => Some(4),
+        5 | 10 | 12 => Some(8),
+        _ => None,
+    }
+}
+
+impl<'a> Tiff<'a> {
+    pub fn new(data: &'a [u8], little_endian: bool) -> Self {
+        Tiff {
+            data,
+            little_endian,
+        }
+    }
+
+    pub fn u16(&self, at: usize) -> Option<u16> {
+        let bytes: [u8; 2] = self.data.get(at..at.checked_add(2)?)?.try_into().ok()?;
+        Some(if self.little_endian {
+            u16::from_le_bytes(bytes)
+        } else {
+            u16::from_be_bytes(bytes)
+        })
+    }
+
+    pub fn u32(&self, at: usize) -> Option<u32> {
+        let bytes: [u8; 4] = self.data.get(at..at.checked_add(4)?)?.try_into().ok()?;
+        Some(if self.little_endian {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    }
+
+    /// Offset of IFD0 from the TIFF header.
+    pub fn first_ifd(&self) -> Option<usize> {
+        self.u32(4).map(|offset| offset as usize)
+    }
+
+    /// The entries of the IFD at `offset`; empty when it is out of bounds.
+    pub fn entries(&self, offset: usize) -> Vec<Entry> {
+        let count = self.u16(offset).unwrap_or_default() as usize;
+        (0..count)
+            .map(|index| offset + 2 + index * 12)
+            .map_while(|at| {
+                Some(Entry {
+                    tag: self.u16(at)?,
+                    kind: self.u16(at + 2)?,
+                    count: self.u32(at + 4)?,
+                    at: at + 8,
+                })
+            })
+            .collect()
+    }
+
+    /// The raw bytes of an entry's value, inline or at its offset.
+    pub fn bytes(&self, entry: &Entry) -> Option<&'a [u8]> {
+        let length = unit_size(entry.kind)?.checked_mul(entry.count as usize)?;
+        let start = if length <= 4 {
+            entry.at
+        } else {
+            self.u32(entry.at)? as usize
+        };
+        self.data.get(start..start.checked_add(length)?)
+    }
+
+    /// Integer values of a BYTE, SHORT, LONG, SLONG or IFD entry; SLONG
+    /// values keep their bit pattern.
+    pub fn uints(&self, entry: &Entry) -> Vec<u32> {
+        let Some(bytes) = self.bytes(entry) else {
+            return Vec::new();
+        };
+        let reader = Tiff::new(bytes, self.little_endian);
+        match entry.kind {
+            1 => bytes.iter().map(|byte| *byte as u32).collect(),
+            3 => (0..bytes.len() / 2)
+                .filter_map(|index| reader.u16(index * 2).map(u32::from))
+                .collect(),
+            4 | 9 | 13 => (0..bytes.len() / 4)
+                .filter_map(|index| reader.u32(index * 4))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Signed rationals of an SRATIONAL entry, as floats.
+    pub fn srationals(&self, entry: &Entry) -> Vec<f64> {
+        let Some(bytes) = self.bytes(entry).filter(|_| entry.kind == 10) else {
+            return Vec::new();
+        };
+        let reader = Tiff::new(bytes, self.little_endian);
+        (0..bytes.len() / 8)
+            .filter_map(|index| {
+                let numerator = reader.u32(index * 8)? as i32;
+                let denominator = reader.u32(index * 8 + 4)? as i32;
+                (denominator != 0).then(|| numerator as f64 / denominator as f64)
+            })
+            .collect()
+    }
+
+    /// An ASCII entry without its trailing NULs.
+    pub fn text(&self, entry: &Entry) -> Option<String> {
+        let bytes = self.bytes(entry).filter(|_| entry.kind == 2)?;
+        let text = String::from_utf8_lossy(bytes);
+        let text = text.trim_end_matches('\0').trim();
+        (!text.is_empty()).then(|| text.to_string())
+    }
+}
+
+pub fn find(entries: &[Entry], tag: u16) -> Option<&Entry> {
+    entries.iter().find(|entry| entry.tag == tag)
+}
@@ -0,0 +1,174 @@
+/// Starts every sealed index file, followed by the format version.
+const MAGIC: &[u8] = b"EVIX\x01";
+
+/// What an encrypted index is opened with: a passphrase, stretched with
+/// the salt stored beside the data, or a random key from the OS keychain.
+#[derive(Clone)]
+#[cfg_attr(not(feature = "index-encryption"), allow(dead_code))]
+pub enum IndexSecret {
+    Passphrase(String),
+    Key([u8; 32]),
+}
+
+/// Whether `bytes` were written by [`seal`]. Sealing is only for the JSON
+/// store; SQLCipher encrypts an SQLite index page by page.
+#[cfg_attr(feature = "sqlite-index", allow(dead_code))]
+pub fn is_sealed(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+pub use sealing::{ensure_available, forget_keychain_secret, keychain_secret};
+#[cfg(not(feature = "sqlite-index"))]
+pub use sealing::{seal, unseal};
+
+#[cfg(feature = "index-encryption")]
+mod sealing {
+    use super::{IndexSecret, MAGIC};
+    use argon2::Argon2;
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use chacha20poly1305::{
+        aead::{rand_core::RngCore, Aead, AeadCore, KeyInit, OsRng},
+        ChaCha20Poly1305, Key, Nonce,
+    };
+
+    const SALT_BYTES: usize = 16;
+    const NONCE_BYTES: usize = 12;
+    const KEYCHAIN_SERVICE: &str = "exif-viewer";
+    const KEYCHAIN_ACCOUNT: &str = "metadata-index";
+
+    fn keychain_entry() -> Result<keyring::Entry, String> {
+        keyring::Entry::new(KEYCHAIN_SERVICE, KEYCHAIN_ACCOUNT)
+            .map_err(|error| format!("Unable to reach the OS keychain: {}", error))
+    }
+
+    /// The index key kept in the OS keychain, made and stored first when
+    /// `create` is set and there is none yet.
+    pub fn keychain_secret(create: bool) -> Result<IndexSecret, String> {
+        let entry = keychain_entry()?;
+        match entry.get_password() {
+            Ok(encoded) => {
+                let key = STANDARD
+                    .decode(encoded.trim())
+                    .ok()
+                    .and_then(|key| <[u8; 32]>::try_from(key).ok())
+                    .ok_or_else(|| "The index key in the OS keychain is damaged.".to_string())?;
+                Ok(IndexSecret::Key(key))
+            }
+            Err(keyring::Error::NoEntry) if create => {
+                let mut key = [0; 32];
+                OsRng.fill_bytes(&mut key);
+                entry
+                    .set_password(&STANDARD.encode(key))
+                    .map_err(|error| format!("Unable to store the index key: {}", error))?;
+                Ok(IndexSecret::Key(key))
+            }
+            Err(keyring::Error::NoEntry) => {
+                Err("The OS keychain has no key for the metadata index.".to_string())
+            }
+            Err(error) => Err(format!("Unable to read the OS keychain: {}", error)),
+        }
+    }
+
+    pub fn ensure_available() -> Result<(), String> {
+        Ok(())
+    }
+
+    pub fn forget_keychain_secret() -> Result<(), String> {
+        match keychain_entry()?.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(error) => Err(format!("Unable to remove the index key: {}", error)),
+        }
+    }
+
+    fn cipher(secret: &IndexSecret, salt: &[u8]) -> Result<ChaCha20Poly1305, String> {
+        let key = match secret {
+            IndexSecret::Key(key) => *key,
+            IndexSecret::Passphrase(passphrase) => {
+                let mut key = [0; 32];
+                Argon2::default()
+                    .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+                    .map_err(|error| format!("Unable to derive the index key: {}", error))?;
+                key
+            }
+        };
+        Ok(ChaCha20Poly1305::new(Key::from_slice(&key)))
+    }
+
+    /// Magic, salt, nonce, then the ChaCha20-Poly1305 ciphertext of `plain`.
+    #[cfg_attr(feature = "sqlite-index", allow(dead_code))]
+    pub fn seal(secret: &IndexSecret, plain: &[u8]) -> Result<Vec<u8>, String> {
+        let mut salt = [0; SALT_BYTES];
+        OsRng.fill_bytes(&mut salt);
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = cipher(secret, &salt)?
+            .encrypt(&nonce, plain)
+            .map_err(|_| "Unable to encrypt the metadata index.".to_string())?;
+        let mut sealed = MAGIC.to_vec();
+        sealed.extend_from_slice(&salt);
+        sealed.extend_from_slice(&nonce);
+        sealed.extend(ciphertext);
+        Ok(sealed)
+    }
+
+    #[cfg_attr(feature = "sqlite-index", allow(dead_code))]
+    pub fn unseal(secret: &IndexSecret, sealed: &[u8]) -> Result<Vec<u8>, String> {
+        let rest = sealed
+            .strip_prefix(MAGIC)
+            .filter(|rest| rest.len() > SALT_BYTES + NONCE_BYTES)
+            .ok_or_else(|| "The metadata index is not encrypted.".to_string())?;
+        let (salt, rest) = rest.split_at(SALT_BYTES);
+        let (nonce, ciphertext) = rest.split_at(NONCE_BYTES);
+        cipher(secret, salt)?
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| "The passphrase does not unlock the metadata index.".to_string())
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn sealed_data_opens_only_with_its_passphrase() {
+            let secret = IndexSecret::Passphrase("harbour".to_string());
+            let sealed = seal(&secret, b"{\"entries\":{}}").unwrap();
+
+            assert!(super::super::is_sealed(&sealed));
+            assert_eq!(unseal(&secret, &sealed).unwrap(), b"{\"entries\":{}}");
+            assert!(unseal(&IndexSecret::Passphrase("pier".to_string()), &sealed).is_err());
+        }
+    }
+}
+
+#[cfg(not(feature = "index-encryption"))]
+mod sealing {
+    use super::IndexSecret;
+
+    fn unavailable<T>() -> Result<T, String> {
+        Err(
+            "This build was made without index encryption (the `index-encryption` feature)."
+                .to_string(),
+        )
+    }
+
+    pub fn keychain_secret(_create: bool) -> Result<IndexSecret, String> {
+        unavailable()
+    }
+
+    pub fn forget_keychain_secret() -> Result<(), String> {
+        Ok(())
+    }
+
+    #[cfg_attr(feature = "sqlite-index", allow(dead_code))]
+    pub fn seal(_secret: &IndexSecret, _plain: &[u8]) -> Result<Vec<u8>, String> {
+        unavailable()
+    }
+
+    #[cfg_attr(feature = "sqlite-index", allow(dead_code))]
+    pub fn unseal(_secret: &IndexSecret, _sealed: &[u8]) -> Result<Vec<u8>, String> {
+        unavailable()
+    }
+
+    pub fn ensure_available() -> Result<(), String> {
+        unavailable()
+    }
+}
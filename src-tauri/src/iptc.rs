@@ -0,0 +1,219 @@
+use crate::jpeg;
+
+pub const APP13: u8 = 0xED;
+const PHOTOSHOP_HEADER: &[u8] = b"Photoshop 3.0\0";
+const IPTC_RESOURCE_ID: u16 = 0x0404;
+const TAG_MARKER: u8 = 0x1C;
+/// `1:90` holding `ESC % G` declares the text datasets as UTF-8.
+const UTF8_DECLARATION: &[u8] = b"\x1B%G";
+
+/// One IIM dataset, e.g. record 2 dataset 80 (By-line).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dataset {
+    pub record: u8,
+    pub number: u8,
+    pub value: String,
+}
+
+/// Application record (2:xx) datasets used for descriptive metadata.
+pub const KEYWORDS: u8 = 25;
+pub const DATE_CREATED: u8 = 55;
+pub const TIME_CREATED: u8 = 60;
+pub const BY_LINE: u8 = 80;
+pub const CREDIT: u8 = 110;
+pub const COPYRIGHT_NOTICE: u8 = 116;
+pub const CAPTION: u8 = 120;
+
+/// Names of the application record datasets shown in the viewer, as the
+/// IIM specification spells them.
+const DATASET_NAMES: &[(u8, &str)] = &[
+    (5, "ObjectName"),
+    (7, "EditStatus"),
+    (10, "Urgency"),
+    (15, "Category"),
+    (20, "SupplementalCategories"),
+    (KEYWORDS, "Keywords"),
+    (40, "SpecialInstructions"),
+    (DATE_CREATED, "DateCreated"),
+    (TIME_CREATED, "TimeCreated"),
+    (65, "OriginatingProgram"),
+    (BY_LINE, "By-line"),
+    (85, "By-lineTitle"),
+    (90, "City"),
+    (92, "Sub-location"),
+    (95, "Province-State"),
+    (100, "Country-PrimaryLocationCode"),
+    (101, "Country-PrimaryLocationName"),
+    (103, "OriginalTransmissionReference"),
+    (105, "Headline"),
+    (CREDIT, "Credit"),
+    (115, "Source"),
+    (COPYRIGHT_NOTICE, "CopyrightNotice"),
+    (118, "Contact"),
+    (CAPTION, "Caption-Abstract"),
+    (122, "Writer-Editor"),
+];
+
+/// Every named dataset present, repeated ones joined with `; `, in the
+/// order of the specification.
+pub fn named_values(datasets: &[Dataset]) -> Vec<(&'static str, String)> {
+    DATASET_NAMES
+        .iter()
+        .filter_map(|&(number, name)| {
+            let values = values(datasets, number);
+            (!values.is_empty()).then(|| (name, values.join("; ")))
+        })
+        .collect()
+}
+
+/// Reads the IPTC-IIM block Photoshop stores in a JPEG's APP13 segments.
+/// Resources too large for one segment continue in the next, so the
+/// segments are joined before the resources are walked. Files without
+/// one, and non-JPEG files, yield nothing.
+pub fn read(data: &[u8]) -> Vec<Dataset> {
+    let Ok((segments, _)) = jpeg::segments(data) else {
+        return Vec::new();
+    };
+    let resources: Vec<u8> = segments
+        .iter()
+        .filter(|segment| segment.marker == APP13)
+        .filter_map(|segment| segment.payload(data).strip_prefix(PHOTOSHOP_HEADER))
+        .flatten()
+        .copied()
+        .collect();
+    iptc_resource(&resources)
+        .map(parse_datasets)
+        .unwrap_or_default()
+}
+
+/// Walks Photoshop image resource blocks (`8BIM`, id, padded Pascal name,
+/// size, padded data) to the IPTC-NAA one.
+fn iptc_resource(mut blocks: &[u8]) -> Option<&[u8]> {
+    while blocks.len() >= 12 && blocks.starts_with(b"8BIM") {
+        let id = u16::from_be_bytes([blocks[4], blocks[5]]);
+        let name_len = blocks[6] as usize;
+        // The length byte plus the name is padded to an even size.
+        let name_field = (1 + name_len + 1) & !1;
+        let size_at = 6 + name_field;
+        let size_bytes = blocks.get(size_at..size_at + 4)?;
+        let size = u32::from_be_bytes(size_bytes.try_into().ok()?) as usize;
+        let data_at = size_at + 4;
+        let resource = blocks.get(data_at..data_at + size)?;
+        if id == IPTC_RESOURCE_ID {
+            return Some(resource);
+        }
+        blocks = blocks.get(data_at + ((size + 1) & !1)..)?;
+    }
+    None
+}
+
+fn parse_datasets(mut bytes: &[u8]) -> Vec<Dataset> {
+    let mut raw = Vec::new();
+    while bytes.len() >= 5 && bytes[0] == TAG_MARKER {
+        let size = u16::from_be_bytes([bytes[3], bytes[4]]) as usize;
+        // Extended datasets (high bit set) only carry binary previews.
+        if size & 0x8000 != 0 {
+            break;
+        }
+        let Some(value) = bytes.get(5..5 + size) else {
+            break;
+        };
+        raw.push((bytes[1], bytes[2], value));
+        bytes = &bytes[5 + size..];
+    }
+
+    let utf8 = raw
+        .iter()
+        .any(|&(record, number, value)| record == 1 && number == 90 && value == UTF8_DECLARATION);
+    raw.into_iter()
+        .filter(|&(record, _, _)| record == 2)
+        .map(|(record, number, value)| Dataset {
+            record,
+            number,
+            value: if utf8 {
+                String::from_utf8_lossy(value).into_owned()
+            } else {
+                crate::decode_latin1(value)
+            }
+            .trim_end_matches('\0')
+            .trim()
+            .to_string(),
+        })
+        .collect()
+}
+
+/// Every value of application record dataset `number`, in file order.
+pub fn values(datasets: &[Dataset], number: u8) -> Vec<&str> {
+    datasets
+        .iter()
+        .filter(|dataset| dataset.record == 2 && dataset.number == number)
+        .map(|dataset| dataset.value.as_str())
+        .filter(|value| !value.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+pub(crate) fn app13_segment(datasets: &[(u8, u8, &[u8])]) -> Vec<u8> {
+    let mut iim = Vec::new();
+    for (record, number, value) in datasets {
+        iim.extend_from_slice(&[TAG_MARKER, *record, *number]);
+        iim.extend_from_slice(&(value.len() as u16).to_be_bytes());
+        iim.extend_from_slice(value);
+    }
+    let mut payload = PHOTOSHOP_HEADER.to_vec();
+    // A thumbnail resource first, with an odd-length name, to exercise padding.
+    payload.extend_from_slice(b"8BIM\x04\x0C\x01a\x00\x00\x00\x03xyz\x00");
+    payload.extend_from_slice(b"8BIM\x04\x04\x00\x00");
+    payload.extend_from_slice(&(iim.len() as u32).to_be_bytes());
+    payload.extend_from_slice(&iim);
+    jpeg::encode_segment(APP13, &payload).expect("segment fits")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn datasets_are_found_behind_other_photoshop_resources() {
+        let mut data = jpeg::SOI.to_vec();
+        data.extend(app13_segment(&[
+            (1, 90, UTF8_DECLARATION),
+            (2, BY_LINE, "Zoë".as_bytes()),
+            (2, BY_LINE, b"Sam"),
+            (2, CAPTION, b"Harbour at dawn"),
+        ]));
+        data.extend_from_slice(&[0xFF, jpeg::SOS, 0x00, 0x02, 0xFF, 0xD9]);
+
+        let datasets = read(&data);
+        assert_eq!(values(&datasets, BY_LINE), ["Zoë", "Sam"]);
+        assert_eq!(values(&datasets, CAPTION), ["Harbour at dawn"]);
+        assert!(values(&datasets, KEYWORDS).is_empty());
+        assert_eq!(
+            named_values(&datasets),
+            [
+                ("By-line", "Zoë; Sam".to_string()),
+                ("Caption-Abstract", "Harbour at dawn".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn resources_continue_across_segments() {
+        let whole = app13_segment(&[(2, 105, b"Storm over the pier")]);
+        // Payload of the single segment, past the marker and length.
+        let payload = &whole[4 + PHOTOSHOP_HEADER.len()..];
+        let (first, second) = payload.split_at(payload.len() / 2);
+        let mut data = jpeg::SOI.to_vec();
+        for part in [first, second] {
+            let mut segment = PHOTOSHOP_HEADER.to_vec();
+            segment.extend_from_slice(part);
+            data.extend(jpeg::encode_segment(APP13, &segment).unwrap());
+        }
+        data.extend_from_slice(&[0xFF, jpeg::SOS, 0x00, 0x02, 0xFF, 0xD9]);
+
+        assert_eq!(
+            named_values(&read(&data)),
+            [("Headline", "Storm over the pier".to_string())]
+        );
+    }
+}
@@ -0,0 +1,185 @@
+use crate::{xmp, ExifField};
+
+pub const IPTC_EXT_IFD: &str = "IPTC Extension";
+
+/// Location structure fields, most specific first.
+const LOCATION_PARTS: [&str; 6] = [
+    "Iptc4xmpExt:LocationName",
+    "Iptc4xmpExt:Sublocation",
+    "Iptc4xmpExt:City",
+    "Iptc4xmpExt:ProvinceState",
+    "Iptc4xmpExt:CountryName",
+    "Iptc4xmpExt:WorldRegion",
+];
+
+/// PLUS release status codes, from the PLUS controlled vocabulary.
+const RELEASE_STATUS: [(&str, &str); 8] = [
+    ("MR-NON", "None"),
+    ("MR-NAP", "Not applicable"),
+    ("MR-UMR", "Unlimited model releases"),
+    ("MR-LMR", "Limited or incomplete model releases"),
+    ("PR-NON", "None"),
+    ("PR-NAP", "Not applicable"),
+    ("PR-UPR", "Unlimited property releases"),
+    ("PR-LPR", "Limited or incomplete property releases"),
+];
+
+fn value<'a>(fields: &[(&str, &'a str)], name: &str) -> Option<&'a str> {
+    fields
+        .iter()
+        .find(|(field, _)| *field == name)
+        .map(|(_, value)| *value)
+}
+
+/// `Ribeira, Porto, Portugal (PT)`.
+fn location(fields: &[(&str, &str)]) -> Option<String> {
+    let mut text = LOCATION_PARTS
+        .iter()
+        .filter_map(|part| value(fields, part))
+        .collect::<Vec<_>>()
+        .join(", ");
+    if let Some(code) = value(fields, "Iptc4xmpExt:CountryCode") {
+        text = if text.is_empty() {
+            code.to_string()
+        } else {
+            format!("{} ({})", text, code)
+        };
+    }
+    (!text.is_empty()).then_some(text)
+}
+
+/// `Agency Ltd (LIC-42) <https://agency.example>`.
+fn licensor(fields: &[(&str, &str)]) -> Option<String> {
+    let mut parts: Vec<String> = Vec::new();
+    parts.extend(value(fields, "plus:LicensorName").map(str::to_string));
+    parts.extend(value(fields, "plus:LicensorID").map(|id| format!("({})", id)));
+    for contact in ["plus:LicensorURL", "plus:LicensorEmail"] {
+        parts.extend(value(fields, contact).map(|contact| format!("<{}>", contact)));
+    }
+    (!parts.is_empty()).then(|| parts.join(" "))
+}
+
+/// `PLUS: 12345`, the registry's organisation then the item's ID there.
+fn registry_entry(fields: &[(&str, &str)]) -> Option<String> {
+    let item = value(fields, "Iptc4xmpExt:RegItemId")?;
+    Some(match value(fields, "Iptc4xmpExt:RegOrgId") {
+        Some(organisation) => format!("{}: {}", organisation, item),
+        None => item.to_string(),
+    })
+}
+
+fn release_status(uri: &str) -> String {
+    let code = uri.rsplit('/').next().unwrap_or(uri);
+    RELEASE_STATUS
+        .iter()
+        .find(|(known, _)| *known == code)
+        .map_or_else(|| uri.to_string(), |(_, label)| label.to_string())
+}
+
+fn field(tag: &str, value: String) -> ExifField {
+    ExifField {
+        tag: tag.to_string(),
+        ifd: IPTC_EXT_IFD.to_string(),
+        value,
+    }
+}
+
+/// IPTC Extension and PLUS structures from the XMP packet: where the photo
+/// was taken and what it shows, the people in it, model and property
+/// releases, registry IDs and licensors. Lists are joined with `"; "`.
+pub fn fields(data: &[u8]) -> Vec<ExifField> {
+    let Some(packet) = xmp::packet(data) else {
+        return Vec::new();
+    };
+    let mut fields = Vec::new();
+    let mut push = |tag: &str, values: Vec<String>| {
+        if !values.is_empty() {
+            fields.push(field(tag, values.join("; ")));
+        }
+    };
+    let each = |name: &str, describe: fn(&[(&str, &str)]) -> Option<String>| -> Vec<String> {
+        xmp::structs(packet, name)
+            .iter()
+            .filter_map(|fields| describe(fields))
+            .collect()
+    };
+    let texts = |name: &str| -> Vec<String> {
+        xmp::items(packet, name)
+            .into_iter()
+            .map(str::to_string)
+            .collect()
+    };
+
+    push(
+        "LocationCreated",
+        each("Iptc4xmpExt:LocationCreated", location),
+    );
+    push("LocationShown", each("Iptc4xmpExt:LocationShown", location));
+
+    let mut people = texts("Iptc4xmpExt:PersonInImage");
+    for person in each("Iptc4xmpExt:PersonInImageWDetails", |fields| {
+        value(fields, "Iptc4xmpExt:PersonName").map(str::to_string)
+    }) {
+        if !people.contains(&person) {
+            people.push(person);
+        }
+    }
+    push("PersonInImage", people);
+
+    for (tag, status, ids) in [
+        (
+            "ModelRelease",
+            "plus:ModelReleaseStatus",
+            "plus:ModelReleaseID",
+        ),
+        (
+            "PropertyRelease",
+            "plus:PropertyReleaseStatus",
+            "plus:PropertyReleaseID",
+        ),
+    ] {
+        if let Some(uri) = xmp::property(packet, status).filter(|uri| !uri.contains('<')) {
+            push(&format!("{}Status", tag), vec![release_status(uri)]);
+        }
+        push(&format!("{}ID", tag), texts(ids));
+    }
+
+    push("RegistryId", each("Iptc4xmpExt:RegistryId", registry_entry));
+    push("Licensor", each("plus:Licensor", licensor));
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stock_structures_become_readable_fields() {
+        let packet = br#"<x:xmpmeta><rdf:Description
+ plus:ModelReleaseStatus="http://ns.useplus.org/ldf/vocab/MR-UMR">
+<Iptc4xmpExt:LocationCreated><rdf:Bag><rdf:li Iptc4xmpExt:City="Porto"
+ Iptc4xmpExt:CountryName="Portugal" Iptc4xmpExt:CountryCode="PT"/></rdf:Bag></Iptc4xmpExt:LocationCreated>
+<Iptc4xmpExt:PersonInImage><rdf:Bag><rdf:li>Ana Ruiz</rdf:li></rdf:Bag></Iptc4xmpExt:PersonInImage>
+<plus:ModelReleaseID><rdf:Bag><rdf:li>MR-001</rdf:li><rdf:li>MR-002</rdf:li></rdf:Bag></plus:ModelReleaseID>
+<Iptc4xmpExt:RegistryId><rdf:Bag><rdf:li rdf:parseType="Resource">
+<Iptc4xmpExt:RegOrgId>PLUS</Iptc4xmpExt:RegOrgId><Iptc4xmpExt:RegItemId>12345</Iptc4xmpExt:RegItemId>
+</rdf:li></rdf:Bag></Iptc4xmpExt:RegistryId>
+<plus:Licensor><rdf:Seq><rdf:li rdf:parseType="Resource"><plus:LicensorName>Agency Ltd</plus:LicensorName>
+<plus:LicensorURL>https://agency.example</plus:LicensorURL></rdf:li></rdf:Seq></plus:Licensor>
+</rdf:Description></x:xmpmeta>"#;
+        let fields: Vec<(String, String)> = fields(packet)
+            .into_iter()
+            .map(|field| (field.tag, field.value))
+            .collect();
+        let expected = [
+            ("LocationCreated", "Porto, Portugal (PT)"),
+            ("PersonInImage", "Ana Ruiz"),
+            ("ModelReleaseStatus", "Unlimited model releases"),
+            ("ModelReleaseID", "MR-001; MR-002"),
+            ("RegistryId", "PLUS: 12345"),
+            ("Licensor", "Agency Ltd <https://agency.example>"),
+        ]
+        .map(|(tag, value)| (tag.to_string(), value.to_string()));
+        assert_eq!(fields, expected);
+    }
+}
@@ -0,0 +1,216 @@
+use crate::{format::FormatStyle, logging, projection::FieldSelection, ExifField};
+use serde::{Deserialize, Serialize};
+use std::{
+    any::Any,
+    io::{Read, Write},
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    process::{Command, ExitStatus, Stdio},
+    sync::atomic::{AtomicU8, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// First argument that turns the app binary into a one-shot parse worker.
+pub const WORKER_FLAG: &str = "--parse-worker";
+/// A worker still running after this long is assumed stuck and killed.
+const WORKER_TIMEOUT: Duration = Duration::from_secs(30);
+const WORKER_POLL: Duration = Duration::from_millis(10);
+
+/// How metadata parsing is shielded from malformed files.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ParseIsolation {
+    /// Parse in the app like any other work.
+    #[default]
+    Off,
+    /// Parse in the app, but turn a parser panic into an error for that file.
+    CatchPanic,
+    /// Parse in a short-lived child process, which also survives aborts,
+    /// stack overflows and hangs at the cost of a process start per file.
+    Subprocess,
+}
+
+static MODE: AtomicU8 = AtomicU8::new(0);
+
+pub fn set_mode(mode: ParseIsolation) {
+    MODE.store(mode as u8, Ordering::Relaxed);
+}
+
+pub fn mode() -> ParseIsolation {
+    match MODE.load(Ordering::Relaxed) {
+        1 => ParseIsolation::CatchPanic,
+        2 => ParseIsolation::Subprocess,
+        _ => ParseIsolation::Off,
+    }
+}
+
+/// What the app sends a worker on stdin.
+#[derive(Serialize, Deserialize)]
+struct WorkerRequest {
+    path: PathBuf,
+    style: FormatStyle,
+    selection: FieldSelection,
+}
+
+/// Parses `data`, read from `path`, under the current isolation mode. In
+/// subprocess mode the worker reads `path` itself, except for a remote
+/// object, which is parsed in the app with panics caught instead.
+pub fn collect_fields(
+    path: &Path,
+    data: &[u8],
+    style: FormatStyle,
+    selection: &FieldSelection,
+) -> Result<Vec<ExifField>, String> {
+    match mode() {
+        ParseIsolation::Off => crate::collect_selected_fields(data, style, selection),
+        ParseIsolation::Subprocess if !crate::remote::is_remote(&path.to_string_lossy()) => {
+            in_worker(path, style, selection)
+        }
+        ParseIsolation::CatchPanic | ParseIsolation::Subprocess => guarded(path, || {
+            crate::collect_selected_fields(data, style, selection)
+        }),
+    }
+}
+
+/// Runs `parse`, turning a panic into an error naming `path`.
+pub fn guarded(
+    path: &Path,
+    parse: impl FnOnce() -> Result<Vec<ExifField>, String>,
+) -> Result<Vec<ExifField>, String> {
+    panic::catch_unwind(AssertUnwindSafe(parse)).unwrap_or_else(|payload| {
+        let message = panic_message(payload.as_ref());
+        tracing::error!(file = %logging::file_name(path), panic = %message, "parser panicked");
+        Err(crashed(path))
+    })
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string())
+}
+
+fn crashed(path: &Path) -> String {
+    format!(
+        "The metadata parser crashed on {}; the file is probably malformed.",
+        path.display()
+    )
+}
+
+fn in_worker(
+    path: &Path,
+    style: FormatStyle,
+    selection: &FieldSelection,
+) -> Result<Vec<ExifField>, String> {
+    let executable = std::env::current_exe().map_err(|error| error.to_string())?;
+    let mut child = Command::new(executable)
+        .arg(WORKER_FLAG)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|error| format!("Unable to start the parse worker: {}", error))?;
+
+    let request = serde_json::to_vec(&WorkerRequest {
+        path: path.to_path_buf(),
+        style,
+        selection: selection.clone(),
+    })
+    .map_err(|error| error.to_string())?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(&request).ok();
+    }
+    // Drained on its own thread so a large reply cannot fill the pipe and
+    // stall the worker while we wait for it to exit.
+    let mut stdout = child.stdout.take().expect("stdout is piped");
+    let reply = thread::spawn(move || {
+        let mut reply = Vec::new();
+        stdout.read_to_end(&mut reply).ok();
+        reply
+    });
+
+    let started = Instant::now();
+    let status = loop {
+        match child.try_wait().map_err(|error| error.to_string())? {
+            Some(status) => break status,
+            None if started.elapsed() > WORKER_TIMEOUT => {
+                child.kill().ok();
+                child.wait().ok();
+                tracing::error!(file = %logging::file_name(path), "parse worker timed out");
+                return Err(format!(
+                    "Parsing {} took too long and was stopped.",
+                    path.display()
+                ));
+            }
+            None => thread::sleep(WORKER_POLL),
+        }
+    };
+    decode_reply(path, status, &reply.join().unwrap_or_default())
+}
+
+fn decode_reply(path: &Path, status: ExitStatus, reply: &[u8]) -> Result<Vec<ExifField>, String> {
+    if !status.success() {
+        tracing::error!(file = %logging::file_name(path), %status, "parse worker crashed");
+        return Err(crashed(path));
+    }
+    serde_json::from_slice::<Result<Vec<ExifField>, String>>(reply)
+        .map_err(|error| format!("The parse worker sent an unreadable reply: {}", error))?
+}
+
+/// Runs the worker side when the process was started with [`WORKER_FLAG`]:
+/// reads one request from stdin, writes the parse result to stdout and
+/// returns the exit code. Returns `None` for a normal app launch.
+pub fn worker_main() -> Option<i32> {
+    if std::env::args().nth(1).as_deref() != Some(WORKER_FLAG) {
+        return None;
+    }
+    let mut input = Vec::new();
+    if std::io::stdin().read_to_end(&mut input).is_err() {
+        return Some(2);
+    }
+    let Ok(request) = serde_json::from_slice::<WorkerRequest>(&input) else {
+        return Some(2);
+    };
+    let result = crate::load_file_data(&request.path)
+        .and_then(|data| crate::collect_selected_fields(&data, request.style, &request.selection));
+    let written = serde_json::to_writer(std::io::stdout().lock(), &result);
+    Some(if written.is_ok() { 0 } else { 2 })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn panics_become_an_error_for_that_file() {
+        let path = Path::new("broken.jpg");
+        let error = guarded(path, || panic!("index out of bounds")).unwrap_err();
+        assert!(error.contains("broken.jpg"));
+        assert!(guarded(path, || Ok(Vec::new())).unwrap().is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn worker_replies_are_decoded_and_crashes_reported() {
+        use std::os::unix::process::ExitStatusExt;
+        let path = Path::new("photo.jpg");
+        let ok = ExitStatus::from_raw(0);
+        let fields = decode_reply(
+            path,
+            ok,
+            br#"{"Ok":[{"tag":"Model","ifd":"In(0)","value":"EOS R5"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(fields[0].value, "EOS R5");
+        assert_eq!(
+            decode_reply(path, ok, br#"{"Err":"No metadata found."}"#).unwrap_err(),
+            "No metadata found."
+        );
+        // Killed by SIGSEGV.
+        let error = decode_reply(path, ExitStatus::from_raw(11), b"").unwrap_err();
+        assert!(error.contains("crashed"));
+    }
+}
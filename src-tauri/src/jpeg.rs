@@ -0,0 +1,377 @@
+use crate::ExifField;
+use serde::Serialize;
+
+pub const SOI: [u8; 2] = [0xFF, 0xD8];
+pub const APP0: u8 = 0xE0;
+pub const APP1: u8 = 0xE1;
+pub const APP15: u8 = 0xEF;
+pub const SOS: u8 = 0xDA;
+pub const COM: u8 = 0xFE;
+pub const EXIF_HEADER: &[u8] = b"Exif\0\0";
+pub const JPEG_IFD: &str = "JPEG";
+const JFIF_HEADER: &[u8] = b"JFIF\0";
+
+/// A marker segment preceding the entropy-coded image data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Segment {
+    pub marker: u8,
+    /// Offset of the `0xFF` byte that starts the marker.
+    pub start: usize,
+    /// Offset one past the end of the payload.
+    pub end: usize,
+}
+
+impl Segment {
+    /// Payload after the two-byte length field.
+    pub fn payload<'a>(&self, data: &'a [u8]) -> &'a [u8] {
+        &data[self.start + 4..self.end]
+    }
+
+    pub fn is_exif(&self, data: &[u8]) -> bool {
+        self.marker == APP1 && self.payload(data).starts_with(EXIF_HEADER)
+    }
+
+    /// `APP0` to `APP15`, `COM`, or the marker byte in hex for the rest.
+    pub fn name(&self) -> String {
+        match self.marker {
+            APP0..=APP15 => format!("APP{}", self.marker - APP0),
+            COM => "COM".to_string(),
+            marker => format!("0x{:02X}", marker),
+        }
+    }
+
+    /// The name with the identifier of an APP segment, e.g. `APP2 (MPF)`.
+    pub fn label(&self, data: &[u8]) -> String {
+        match app_identifier(self.payload(data)) {
+            Some(identifier) if (APP0..=APP15).contains(&self.marker) => {
+                format!("{} ({})", self.name(), identifier)
+            }
+            _ => self.name(),
+        }
+    }
+}
+
+/// Outcome of [`remove_segments`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SegmentRemoval {
+    /// Labels of the removed segments, in file order.
+    pub removed: Vec<String>,
+    pub bytes_saved: usize,
+}
+
+pub fn is_jpeg(data: &[u8]) -> bool {
+    data.starts_with(&SOI)
+}
+
+/// Lists the marker segments up to (but excluding) the start-of-scan marker.
+///
+/// Returns the segments and the offset where the SOS segment begins, which is
+/// where the remainder of the file can be copied verbatim.
+pub fn segments(data: &[u8]) -> Result<(Vec<Segment>, usize), String> {
+    if !is_jpeg(data) {
+        return Err("The selected file is not a JPEG image.".to_string());
+    }
+
+    let mut segments = Vec::new();
+    let mut offset = SOI.len();
+    loop {
+        // Markers may be preceded by any number of 0xFF fill bytes.
+        while data.get(offset) == Some(&0xFF) && data.get(offset + 1) == Some(&0xFF) {
+            offset += 1;
+        }
+        let (Some(&0xFF), Some(&marker)) = (data.get(offset), data.get(offset + 1)) else {
+            return Err("The selected file appears to be truncated or corrupted.".to_string());
+        };
+        if marker == SOS {
+            return Ok((segments, offset));
+        }
+        if (0xD0..=0xD7).contains(&marker) || marker == 0x01 {
+            offset += 2;
+            continue;
+        }
+        let Some(length_bytes) = data.get(offset + 2..offset + 4) else {
+            return Err("The selected file appears to be truncated or corrupted.".to_string());
+        };
+        let length = u16::from_be_bytes([length_bytes[0], length_bytes[1]]) as usize;
+        let end = offset + 2 + length;
+        if length < 2 || end > data.len() {
+            return Err("The selected file appears to be truncated or corrupted.".to_string());
+        }
+        segments.push(Segment {
+            marker,
+            start: offset,
+            end,
+        });
+        offset = end;
+    }
+}
+
+/// Width and height from the frame header (any SOFn marker).
+pub fn frame_size(data: &[u8]) -> Option<(u32, u32)> {
+    let (segments, _) = segments(data).ok()?;
+    let frame = segments.iter().find(|segment| {
+        matches!(segment.marker, 0xC0..=0xCF) && ![0xC4, 0xC8, 0xCC].contains(&segment.marker)
+    })?;
+    match frame.payload(data) {
+        [_, h1, h2, w1, w2, ..] => Some((
+            u16::from_be_bytes([*w1, *w2]) as u32,
+            u16::from_be_bytes([*h1, *h2]) as u32,
+        )),
+        _ => None,
+    }
+}
+
+/// Encodes a marker segment, failing when the payload exceeds the 64 KiB limit.
+pub fn encode_segment(marker: u8, payload: &[u8]) -> Result<Vec<u8>, String> {
+    let length = payload.len() + 2;
+    if length > u16::MAX as usize {
+        return Err("The metadata block is too large to fit in a JPEG segment.".to_string());
+    }
+    let mut segment = Vec::with_capacity(length + 2);
+    segment.extend_from_slice(&[0xFF, marker]);
+    segment.extend_from_slice(&(length as u16).to_be_bytes());
+    segment.extend_from_slice(payload);
+    Ok(segment)
+}
+
+/// The start of an APP segment up to its first NUL, which names what the
+/// segment holds (`JFIF`, `Exif`, `ICC_PROFILE`, an XMP namespace...).
+fn app_identifier(payload: &[u8]) -> Option<&str> {
+    let end = payload.iter().take(64).position(|&byte| byte == 0)?;
+    let identifier = std::str::from_utf8(&payload[..end]).ok()?;
+    (!identifier.is_empty()
+        && identifier
+            .bytes()
+            .all(|byte| byte.is_ascii_graphic() || byte == b' '))
+    .then_some(identifier)
+}
+
+/// A COM segment's text, which has no declared encoding: UTF-8 when it is
+/// valid UTF-8, Latin-1 otherwise.
+fn comment_text(payload: &[u8]) -> String {
+    let end = payload
+        .iter()
+        .rposition(|&byte| byte != 0)
+        .map_or(0, |last| last + 1);
+    let text = match std::str::from_utf8(&payload[..end]) {
+        Ok(text) => text.to_string(),
+        Err(_) => crate::decode_latin1(&payload[..end]),
+    };
+    text.trim().to_string()
+}
+
+/// The `JPEG` group: each COM comment, the JFIF version and density from
+/// APP0, and the APP markers present in file order.
+pub fn fields(data: &[u8]) -> Vec<ExifField> {
+    let Ok((segments, _)) = segments(data) else {
+        return Vec::new();
+    };
+    let field = |tag: &str, value: String| ExifField {
+        tag: tag.to_string(),
+        ifd: JPEG_IFD.to_string(),
+        value,
+    };
+    let mut fields: Vec<ExifField> = segments
+        .iter()
+        .filter(|segment| segment.marker == COM)
+        .map(|segment| comment_text(segment.payload(data)))
+        .filter(|text| !text.is_empty())
+        .map(|text| field("Comment", text))
+        .collect();
+
+    let jfif = segments
+        .iter()
+        .filter(|segment| segment.marker == APP0)
+        .find_map(|segment| segment.payload(data).strip_prefix(JFIF_HEADER));
+    if let Some(&[major, minor, units, x_high, x_low, y_high, y_low, ..]) = jfif {
+        let (x, y) = (
+            u16::from_be_bytes([x_high, x_low]),
+            u16::from_be_bytes([y_high, y_low]),
+        );
+        fields.push(field("JFIFVersion", format!("{}.{:02}", major, minor)));
+        fields.push(field(
+            "JFIFDensity",
+            match units {
+                1 => format!("{} x {} dpi", x, y),
+                2 => format!("{} x {} dots/cm", x, y),
+                _ => format!("{}:{} pixel aspect ratio", x, y),
+            },
+        ));
+    }
+
+    let markers: Vec<String> = segments
+        .iter()
+        .filter(|segment| (APP0..=APP15).contains(&segment.marker))
+        .map(|segment| segment.label(data))
+        .collect();
+    if !markers.is_empty() {
+        fields.push(field("AppMarkers", markers.join(", ")));
+    }
+    fields
+}
+
+/// The JPEG without the APP and COM segments `selectors` name. A selector is
+/// a marker name such as `APP13` or `COM`, or a label as `AppMarkers` lists
+/// it, such as `APP2 (MPF)`, to pick one kind of segment among those sharing
+/// a marker. With `duplicates_only` the first segment of each label is kept.
+///
+/// Nothing is recompressed: the remaining segments and the image data are
+/// copied byte for byte.
+pub fn remove_segments(
+    data: &[u8],
+    selectors: &[String],
+    duplicates_only: bool,
+) -> Result<(Vec<u8>, SegmentRemoval), String> {
+    for selector in selectors {
+        let name = selector.split(" (").next().unwrap_or_default();
+        let removable = name == "COM"
+            || name
+                .strip_prefix("APP")
+                .and_then(|number| number.parse::<u8>().ok())
+                .is_some_and(|number| number <= 15 && name == format!("APP{}", number));
+        if !removable {
+            return Err(format!(
+                "{} is not a segment that can be removed; only APP0 to APP15 and COM segments can.",
+                selector
+            ));
+        }
+    }
+
+    let (segments, scan_start) = segments(data)?;
+    let mut seen: Vec<String> = Vec::new();
+    let mut removal = SegmentRemoval::default();
+    let mut output = Vec::with_capacity(data.len());
+    output.extend_from_slice(&SOI);
+    for segment in &segments {
+        let label = segment.label(data);
+        let selected = selectors
+            .iter()
+            .any(|selector| *selector == label || *selector == segment.name());
+        let repeat = seen.contains(&label);
+        seen.push(label.clone());
+        if selected && (repeat || !duplicates_only) {
+            removal.bytes_saved += segment.end - segment.start;
+            removal.removed.push(label);
+        } else {
+            output.extend_from_slice(&data[segment.start..segment.end]);
+        }
+    }
+    output.extend_from_slice(&data[scan_start..]);
+    Ok((output, removal))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{ascii_field, check_property, JpegBuilder};
+    use exif::Tag;
+
+    #[test]
+    fn segments_stop_at_start_of_scan() {
+        let mut data = SOI.to_vec();
+        data.extend(encode_segment(APP0, b"JFIF\0\x01\x02").unwrap());
+        data.extend(encode_segment(APP1, b"Exif\0\0MM").unwrap());
+        let sos = data.len();
+        data.extend_from_slice(&[0xFF, SOS, 0x00, 0x02, 0x12, 0x34, 0xFF, 0xD9]);
+
+        let (segments, scan_start) = segments(&data).expect("segments should parse");
+        assert_eq!(scan_start, sos);
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments[0].payload(&data), b"JFIF\0\x01\x02");
+        assert!(segments[1].is_exif(&data));
+    }
+
+    #[test]
+    fn segments_are_listed_in_any_layout() {
+        check_property(64, |rng| {
+            let mut jpeg = JpegBuilder::new();
+            let mut markers = Vec::new();
+            for _ in 0..rng.below(10) {
+                let marker = APP0 + rng.below(16) as u8;
+                let len = rng.below(300);
+                jpeg = jpeg.segment(marker, &rng.bytes(len));
+                markers.push(marker);
+            }
+            let (found, _) = segments(&jpeg.finish()).expect("segments should parse");
+            let found: Vec<u8> = found.iter().map(|segment| segment.marker).collect();
+            assert_eq!(found, markers);
+        });
+    }
+
+    #[test]
+    fn comments_jfif_and_app_markers_are_listed() {
+        let data = JpegBuilder::new()
+            .segment(APP0, b"JFIF\0\x01\x02\x01\x01\x2C\x01\x2C\0\0")
+            .segment(COM, b"Scanned on an Epson V600\0")
+            .exif(&[ascii_field(Tag::Model, "Perfection V600")])
+            .segment(COM, b"caf\xE9")
+            .segment(APP0 + 14, b"Adobe\0\x64")
+            .finish();
+        let fields: Vec<(String, String)> = fields(&data)
+            .into_iter()
+            .map(|field| (field.tag, field.value))
+            .collect();
+        let expected = [
+            ("Comment", "Scanned on an Epson V600"),
+            ("Comment", "caf\u{e9}"),
+            ("JFIFVersion", "1.02"),
+            ("JFIFDensity", "300 x 300 dpi"),
+            ("AppMarkers", "APP0 (JFIF), APP1 (Exif), APP14 (Adobe)"),
+        ];
+        assert_eq!(
+            fields,
+            expected.map(|(tag, value)| (tag.to_string(), value.to_string()))
+        );
+    }
+
+    #[test]
+    fn selected_segments_are_removed_and_the_rest_kept() {
+        let data = JpegBuilder::new()
+            .segment(APP0, b"JFIF\0\x01\x02\0\0\x01\0\x01\0\0")
+            .segment(APP1, b"Exif\0\0first")
+            .segment(APP1, b"Exif\0\0second")
+            .segment(APP0 + 2, b"MPF\0index")
+            .segment(APP0 + 2, b"ICC_PROFILE\0\x01\x01")
+            .segment(APP0 + 13, b"Photoshop 3.0\0")
+            .finish();
+        let (slim, removal) = remove_segments(
+            &data,
+            &["APP2 (MPF)".to_string(), "APP13".to_string()],
+            false,
+        )
+        .unwrap();
+        assert_eq!(removal.removed, ["APP2 (MPF)", "APP13 (Photoshop 3.0)"]);
+        assert_eq!(removal.bytes_saved, data.len() - slim.len());
+        let labels = |data: &[u8]| -> Vec<String> {
+            let (segments, _) = segments(data).unwrap();
+            segments.iter().map(|segment| segment.label(data)).collect()
+        };
+        assert_eq!(
+            labels(&slim),
+            [
+                "APP0 (JFIF)",
+                "APP1 (Exif)",
+                "APP1 (Exif)",
+                "APP2 (ICC_PROFILE)"
+            ]
+        );
+        let scan = |data: &[u8]| data[segments(data).unwrap().1..].to_vec();
+        assert_eq!(scan(&slim), scan(&data));
+
+        let (deduplicated, removal) = remove_segments(&data, &["APP1".to_string()], true).unwrap();
+        assert_eq!(removal.removed, ["APP1 (Exif)"]);
+        assert!(deduplicated
+            .windows(11)
+            .any(|window| window == b"Exif\0\0first"));
+        assert!(remove_segments(&data, &["SOF0".to_string()], false).is_err());
+        assert!(remove_segments(&data, &["APP16".to_string()], false).is_err());
+    }
+
+    #[test]
+    fn truncated_segment_is_reported() {
+        let mut data = SOI.to_vec();
+        data.extend_from_slice(&[0xFF, APP1, 0x10, 0x00, b'E']);
+        assert!(segments(&data).is_err());
+    }
+}
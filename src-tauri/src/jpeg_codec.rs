@@ -0,0 +1,818 @@
+use crate::jpeg::{self, SOI, SOS};
+
+const SOF0: u8 = 0xC0;
+const SOF1: u8 = 0xC1;
+const SOF2: u8 = 0xC2;
+const DHT: u8 = 0xC4;
+const DQT: u8 = 0xDB;
+const DRI: u8 = 0xDD;
+const EOI: u8 = 0xD9;
+
+/// Natural (row-major) index of each coefficient in zigzag order.
+const ZIGZAG: [usize; 64] = [
+    0, 1, 8, 16, 9, 2, 3, 10, 17, 24, 32, 25, 18, 11, 4, 5, 12, 19, 26, 33, 40, 48, 41, 34, 27, 20,
+    13, 6, 7, 14, 21, 28, 35, 42, 49, 56, 57, 50, 43, 36, 29, 22, 15, 23, 30, 37, 44, 51, 58, 59,
+    52, 45, 38, 31, 39, 46, 53, 60, 61, 54, 47, 55, 62, 63,
+];
+
+/// The example quantization tables of the JPEG spec (Annex K), row-major.
+const LUMA_QUANT: [u16; 64] = [
+    16, 11, 10, 16, 24, 40, 51, 61, 12, 12, 14, 19, 26, 58, 60, 55, 14, 13, 16, 24, 40, 57, 69, 56,
+    14, 17, 22, 29, 51, 87, 80, 62, 18, 22, 37, 56, 68, 109, 103, 77, 24, 35, 55, 64, 81, 104, 113,
+    92, 49, 64, 78, 87, 103, 121, 120, 101, 72, 92, 95, 98, 112, 100, 103, 99,
+];
+const CHROMA_QUANT: [u16; 64] = [
+    17, 18, 24, 47, 99, 99, 99, 99, 18, 21, 26, 66, 99, 99, 99, 99, 24, 26, 56, 99, 99, 99, 99, 99,
+    47, 66, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99,
+    99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99,
+];
+
+fn truncated() -> String {
+    "The selected file appears to be truncated or corrupted.".to_string()
+}
+
+/// An 8-bit RGB image, row by row.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Raster {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+impl Raster {
+    fn pixel(&self, x: usize, y: usize) -> [f32; 3] {
+        let at = (y * self.width + x) * 3;
+        [
+            self.pixels[at] as f32,
+            self.pixels[at + 1] as f32,
+            self.pixels[at + 2] as f32,
+        ]
+    }
+
+    /// Scales the image to at most `size` pixels on its long side: box
+    /// filtering when shrinking, bilinear when enlarging.
+    pub fn fit(&self, size: usize) -> Raster {
+        let scale = size as f64 / self.width.max(self.height) as f64;
+        let width = ((self.width as f64 * scale).round() as usize).max(1);
+        let height = ((self.height as f64 * scale).round() as usize).max(1);
+        let mut pixels = Vec::with_capacity(width * height * 3);
+        for y in 0..height {
+            for x in 0..width {
+                let color = if scale < 1.0 {
+                    let (x0, x1) = (
+                        x * self.width / width,
+                        ((x + 1) * self.width / width).max(x * self.width / width + 1),
+                    );
+                    let (y0, y1) = (
+                        y * self.height / height,
+                        ((y + 1) * self.height / height).max(y * self.height / height + 1),
+                    );
+                    let mut sum = [0.0; 3];
+                    for sy in y0..y1 {
+                        for sx in x0..x1 {
+                            let pixel = self.pixel(sx, sy);
+                            (0..3).for_each(|channel| sum[channel] += pixel[channel]);
+                        }
+                    }
+                    let count = ((x1 - x0) * (y1 - y0)) as f32;
+                    sum.map(|channel| channel / count)
+                } else {
+                    let source_x =
+                        ((x as f64 + 0.5) / scale - 0.5).clamp(0.0, (self.width - 1) as f64);
+                    let source_y =
+                        ((y as f64 + 0.5) / scale - 0.5).clamp(0.0, (self.height - 1) as f64);
+                    let (x0, y0) = (source_x as usize, source_y as usize);
+                    let (x1, y1) = ((x0 + 1).min(self.width - 1), (y0 + 1).min(self.height - 1));
+                    let (fx, fy) = ((source_x - x0 as f64) as f32, (source_y - y0 as f64) as f32);
+                    let corners = [
+                        self.pixel(x0, y0),
+                        self.pixel(x1, y0),
+                        self.pixel(x0, y1),
+                        self.pixel(x1, y1),
+                    ];
+                    std::array::from_fn(|channel| {
+                        let top = corners[0][channel] * (1.0 - fx) + corners[1][channel] * fx;
+                        let bottom = corners[2][channel] * (1.0 - fx) + corners[3][channel] * fx;
+                        top * (1.0 - fy) + bottom * fy
+                    })
+                };
+                pixels.extend(color.map(|channel| channel.round().clamp(0.0, 255.0) as u8));
+            }
+        }
+        Raster {
+            width,
+            height,
+            pixels,
+        }
+    }
+}
+
+/// A decoding table built from DHT code counts and symbols (spec F.2.2.3).
+struct HuffmanTable {
+    max_code: [i32; 17],
+    min_code: [i32; 17],
+    first_value: [usize; 17],
+    values: Vec<u8>,
+}
+
+impl HuffmanTable {
+    fn new(counts: &[u8], values: &[u8]) -> Self {
+        let mut table = HuffmanTable {
+            max_code: [-1; 17],
+            min_code: [0; 17],
+            first_value: [0; 17],
+            values: values.to_vec(),
+        };
+        let (mut code, mut index) = (0i32, 0usize);
+        for length in 1..=16 {
+            let count = counts[length - 1] as i32;
+            table.first_value[length] = index;
+            table.min_code[length] = code;
+            if count > 0 {
+                table.max_code[length] = code + count - 1;
+            }
+            code = (code + count) << 1;
+            index += count as usize;
+        }
+        table
+    }
+
+    fn decode(&self, bits: &mut BitReader) -> Result<u8, String> {
+        let mut code = 0i32;
+        for length in 1..=16 {
+            code = (code << 1) | bits.bit()? as i32;
+            if code <= self.max_code[length] {
+                let index = self.first_value[length] + (code - self.min_code[length]) as usize;
+                return self.values.get(index).copied().ok_or_else(truncated);
+            }
+        }
+        Err(truncated())
+    }
+}
+
+/// Reads entropy-coded bits, dropping the stuffed zero after each 0xFF.
+/// At a marker it yields zero bits, as decoders conventionally do.
+struct BitReader<'a> {
+    data: &'a [u8],
+    at: usize,
+    byte: u8,
+    left: u32,
+}
+
+impl BitReader<'_> {
+    fn bit(&mut self) -> Result<u32, String> {
+        if self.left == 0 {
+            let byte = *self.data.get(self.at).ok_or_else(truncated)?;
+            self.byte = match (byte, self.data.get(self.at + 1)) {
+                (0xFF, Some(0)) => {
+                    self.at += 2;
+                    0xFF
+                }
+                (0xFF, _) => 0,
+                _ => {
+                    self.at += 1;
+                    byte
+                }
+            };
+            self.left = 8;
+        }
+        self.left -= 1;
+        Ok((self.byte as u32 >> self.left) & 1)
+    }
+
+    fn receive(&mut self, count: u8) -> Result<i32, String> {
+        let mut value = 0;
+        for _ in 0..count {
+            value = (value << 1) | self.bit()? as i32;
+        }
+        Ok(value)
+    }
+
+    /// Skips to the byte after the next RSTn marker.
+    fn restart(&mut self) {
+        self.left = 0;
+        if let Some(offset) = self.data.get(self.at..).and_then(|rest| {
+            rest.windows(2)
+                .position(|pair| pair[0] == 0xFF && (0xD0..=0xD7).contains(&pair[1]))
+        }) {
+            self.at += offset + 2;
+        }
+    }
+}
+
+/// A coefficient of `size` bits as sent: values below half the range are negative.
+fn extend(value: i32, size: u8) -> i32 {
+    if size == 0 {
+        0
+    } else if value < 1 << (size - 1) {
+        value - (1 << size) + 1
+    } else {
+        value
+    }
+}
+
+struct Component {
+    id: u8,
+    horizontal: usize,
+    vertical: usize,
+    /// Quantization table in zigzag order.
+    quant: [u16; 64],
+    dc_table: usize,
+    ac_table: usize,
+    blocks_wide: usize,
+    /// The mean of every block, from its DC coefficient.
+    plane: Vec<u8>,
+    predictor: i32,
+}
+
+/// A baseline JPEG decoded at 1/8 scale.
+pub struct Preview {
+    pub image: Raster,
+    /// Mean magnitude of the dequantized luma AC coefficients per block.
+    /// Blur and motion lower it, so it ranks frames of one scene by sharpness.
+    pub detail: f64,
+}
+
+/// Decodes a baseline JPEG at 1/8 scale from the DC coefficient of each
+/// block, which needs no inverse DCT and is plenty for a thumbnail.
+pub fn decode_dc(data: &[u8]) -> Result<Raster, String> {
+    preview(data).map(|preview| preview.image)
+}
+
+/// [`decode_dc`] plus how much detail the luma AC coefficients carry.
+/// Progressive, arithmetic-coded, lossless and CMYK files are refused.
+pub fn preview(data: &[u8]) -> Result<Preview, String> {
+    let (segments, sos) = jpeg::segments(data)?;
+    let mut quant_tables = [None; 4];
+    let mut dc_tables: [Option<HuffmanTable>; 4] = Default::default();
+    let mut ac_tables: [Option<HuffmanTable>; 4] = Default::default();
+    let mut restart_interval = 0usize;
+    let mut frame = None;
+    for segment in &segments {
+        let payload = segment.payload(data);
+        match segment.marker {
+            DQT => {
+                let mut at = 0;
+                while let Some(&spec) = payload.get(at) {
+                    let width = if spec >> 4 == 1 { 2 } else { 1 };
+                    let values = payload
+                        .get(at + 1..at + 1 + 64 * width)
+                        .ok_or_else(truncated)?;
+                    let table: [u16; 64] = std::array::from_fn(|index| match width {
+                        2 => u16::from_be_bytes([values[index * 2], values[index * 2 + 1]]),
+                        _ => values[index] as u16,
+                    });
+                    quant_tables[(spec & 3) as usize] = Some(table);
+                    at += 1 + 64 * width;
+                }
+            }
+            DHT => {
+                let mut at = 0;
+                while let Some(&spec) = payload.get(at) {
+                    let counts = payload.get(at + 1..at + 17).ok_or_else(truncated)?;
+                    let total: usize = counts.iter().map(|&count| count as usize).sum();
+                    let values = payload
+                        .get(at + 17..at + 17 + total)
+                        .ok_or_else(truncated)?;
+                    let table = Some(HuffmanTable::new(counts, values));
+                    match spec >> 4 {
+                        0 => dc_tables[(spec & 3) as usize] = table,
+                        _ => ac_tables[(spec & 3) as usize] = table,
+                    }
+                    at += 17 + total;
+                }
+            }
+            DRI => {
+                restart_interval = u16::from_be_bytes([
+                    *payload.first().ok_or_else(truncated)?,
+                    *payload.get(1).ok_or_else(truncated)?,
+                ]) as usize;
+            }
+            SOF0 | SOF1 => frame = Some(payload),
+            SOF2 => {
+                return Err("Progressive JPEGs cannot be decoded for a thumbnail yet.".to_string())
+            }
+            0xC3 | 0xC5..=0xC7 | 0xC9..=0xCB | 0xCD..=0xCF => {
+                return Err("This kind of JPEG cannot be decoded for a thumbnail.".to_string())
+            }
+            _ => {}
+        }
+    }
+    let frame = frame.ok_or_else(truncated)?;
+    let (height, width, count) = match frame {
+        [8, h1, h2, w1, w2, count, ..] => (
+            u16::from_be_bytes([*h1, *h2]) as usize,
+            u16::from_be_bytes([*w1, *w2]) as usize,
+            *count as usize,
+        ),
+        _ => return Err("Only 8-bit JPEGs can be decoded for a thumbnail.".to_string()),
+    };
+    if width == 0 || height == 0 || !(count == 1 || count == 3) {
+        return Err("Only greyscale and YCbCr JPEGs can be decoded for a thumbnail.".to_string());
+    }
+    let mut components = Vec::with_capacity(count);
+    for index in 0..count {
+        let spec = frame
+            .get(6 + index * 3..9 + index * 3)
+            .ok_or_else(truncated)?;
+        let quant = quant_tables[(spec[2] & 3) as usize].ok_or_else(truncated)?;
+        components.push(Component {
+            id: spec[0],
+            horizontal: (spec[1] >> 4).max(1) as usize,
+            vertical: (spec[1] & 0xF).max(1) as usize,
+            quant,
+            dc_table: 0,
+            ac_table: 0,
+            blocks_wide: 0,
+            plane: Vec::new(),
+            predictor: 0,
+        });
+    }
+
+    let header_len = u16::from_be_bytes([
+        *data.get(sos + 2).ok_or_else(truncated)?,
+        *data.get(sos + 3).ok_or_else(truncated)?,
+    ]) as usize;
+    let scan = data
+        .get(sos + 4..sos + 2 + header_len)
+        .ok_or_else(truncated)?;
+    if scan.first().map(|&count| count as usize) != Some(count) {
+        return Err(
+            "Only JPEGs with all components in one scan can be decoded for a thumbnail."
+                .to_string(),
+        );
+    }
+    for index in 0..count {
+        let spec = scan
+            .get(1 + index * 2..3 + index * 2)
+            .ok_or_else(truncated)?;
+        let component = components
+            .iter_mut()
+            .find(|component| component.id == spec[0])
+            .ok_or_else(truncated)?;
+        component.dc_table = (spec[1] >> 4 & 3) as usize;
+        component.ac_table = (spec[1] & 3) as usize;
+    }
+
+    let max_horizontal = components
+        .iter()
+        .map(|component| component.horizontal)
+        .max()
+        .unwrap_or(1);
+    let max_vertical = components
+        .iter()
+        .map(|component| component.vertical)
+        .max()
+        .unwrap_or(1);
+    // A single-component scan is not interleaved: every MCU is one block.
+    if count == 1 {
+        components[0].horizontal = 1;
+        components[0].vertical = 1;
+    }
+    let (unit_width, unit_height) = match count {
+        1 => (8, 8),
+        _ => (8 * max_horizontal, 8 * max_vertical),
+    };
+    let (mcus_wide, mcus_high) = (width.div_ceil(unit_width), height.div_ceil(unit_height));
+    for component in &mut components {
+        component.blocks_wide = mcus_wide * component.horizontal;
+        component.plane = vec![0; component.blocks_wide * mcus_high * component.vertical];
+    }
+
+    let mut bits = BitReader {
+        data: &data[sos + 2 + header_len..],
+        at: 0,
+        byte: 0,
+        left: 0,
+    };
+    let mut detail = 0u64;
+    for mcu in 0..mcus_wide * mcus_high {
+        if restart_interval > 0 && mcu > 0 && mcu % restart_interval == 0 {
+            bits.restart();
+            components
+                .iter_mut()
+                .for_each(|component| component.predictor = 0);
+        }
+        let (mcu_x, mcu_y) = (mcu % mcus_wide, mcu / mcus_wide);
+        for (plane, component) in components.iter_mut().enumerate() {
+            let dc = dc_tables[component.dc_table]
+                .as_ref()
+                .ok_or_else(truncated)?;
+            let ac = ac_tables[component.ac_table]
+                .as_ref()
+                .ok_or_else(truncated)?;
+            for block_y in 0..component.vertical {
+                for block_x in 0..component.horizontal {
+                    let size = dc.decode(&mut bits)?;
+                    if size > 11 {
+                        return Err(truncated());
+                    }
+                    component.predictor += extend(bits.receive(size)?, size);
+                    let mut index = 1;
+                    while index < 64 {
+                        let symbol = ac.decode(&mut bits)?;
+                        let (run, size) = (symbol >> 4, symbol & 0xF);
+                        if size == 0 {
+                            if run != 15 {
+                                break;
+                            }
+                            index += 16;
+                            continue;
+                        }
+                        index += run as usize;
+                        let value = extend(bits.receive(size)?, size);
+                        if plane == 0 {
+                            let step = component.quant.get(index).copied().unwrap_or(0);
+                            detail += (value * step as i32).unsigned_abs() as u64;
+                        }
+                        index += 1;
+                    }
+                    let mean = component.predictor * component.quant[0] as i32 / 8 + 128;
+                    let at = (mcu_y * component.vertical + block_y) * component.blocks_wide
+                        + mcu_x * component.horizontal
+                        + block_x;
+                    component.plane[at] = mean.clamp(0, 255) as u8;
+                }
+            }
+        }
+    }
+
+    let (out_width, out_height) = (width.div_ceil(8), height.div_ceil(8));
+    let mut pixels = Vec::with_capacity(out_width * out_height * 3);
+    for y in 0..out_height {
+        for x in 0..out_width {
+            let sample = |component: &Component| {
+                let row = y * component.vertical / max_vertical;
+                let column = x * component.horizontal / max_horizontal;
+                component.plane[row * component.blocks_wide + column] as f32
+            };
+            if count == 1 {
+                let grey = sample(&components[0]) as u8;
+                pixels.extend([grey; 3]);
+                continue;
+            }
+            let (luma, cb, cr) = (
+                sample(&components[0]),
+                sample(&components[1]) - 128.0,
+                sample(&components[2]) - 128.0,
+            );
+            pixels.extend(
+                [
+                    luma + 1.402 * cr,
+                    luma - 0.344_136 * cb - 0.714_136 * cr,
+                    luma + 1.772 * cb,
+                ]
+                .map(|channel| channel.round().clamp(0.0, 255.0) as u8),
+            );
+        }
+    }
+    let luma_blocks = mcus_wide * mcus_high * components[0].horizontal * components[0].vertical;
+    Ok(Preview {
+        image: Raster {
+            width: out_width,
+            height: out_height,
+            pixels,
+        },
+        detail: detail as f64 / luma_blocks as f64,
+    })
+}
+
+/// The quantization table for `quality` (1–100) with libjpeg's scaling.
+fn scaled_quant(base: &[u16; 64], quality: u8) -> [u16; 64] {
+    let quality = quality.clamp(1, 100) as u32;
+    let scale = if quality < 50 {
+        5000 / quality
+    } else {
+        200 - quality * 2
+    };
+    base.map(|value| ((value as u32 * scale + 50) / 100).clamp(1, 255) as u16)
+}
+
+/// Huffman code lengths from symbol frequencies, limited to 16 bits (spec
+/// K.2). Returns the DHT counts and symbols.
+fn huffman_table(frequencies: &[u32; 256]) -> ([u8; 16], Vec<u8>) {
+    // Symbol 256 reserves the all-ones code, which JPEG forbids.
+    let mut frequency: Vec<u64> = frequencies.iter().map(|&count| count as u64).collect();
+    frequency.push(1);
+    let mut code_size = vec![0usize; 257];
+    let mut others = vec![None; 257];
+    loop {
+        let least = |skip: Option<usize>, frequency: &[u64]| {
+            (0..257)
+                .filter(|&symbol| frequency[symbol] > 0 && Some(symbol) != skip)
+                .min_by_key(|&symbol| (frequency[symbol], std::cmp::Reverse(symbol)))
+        };
+        let Some(mut first) = least(None, &frequency) else {
+            break;
+        };
+        let Some(mut second) = least(Some(first), &frequency) else {
+            break;
+        };
+        frequency[first] += frequency[second];
+        frequency[second] = 0;
+        code_size[first] += 1;
+        while let Some(next) = others[first] {
+            first = next;
+            code_size[first] += 1;
+        }
+        others[first] = Some(second);
+        code_size[second] += 1;
+        while let Some(next) = others[second] {
+            second = next;
+            code_size[second] += 1;
+        }
+    }
+
+    let mut bits = [0usize; 33];
+    for &size in &code_size {
+        if size > 0 {
+            bits[size.min(32)] += 1;
+        }
+    }
+    for length in (17..=32).rev() {
+        while bits[length] > 0 {
+            let mut shorter = length - 2;
+            while bits[shorter] == 0 {
+                shorter -= 1;
+            }
+            bits[length] -= 2;
+            bits[length - 1] += 1;
+            bits[shorter + 1] += 2;
+            bits[shorter] -= 1;
+        }
+    }
+    if let Some(longest) = (1..=16).rev().find(|&length| bits[length] > 0) {
+        bits[longest] -= 1;
+    }
+
+    let mut symbols: Vec<usize> = (0..256).filter(|&symbol| code_size[symbol] > 0).collect();
+    symbols.sort_by_key(|&symbol| (code_size[symbol], symbol));
+    let mut counts = [0u8; 16];
+    for length in 1..=16 {
+        counts[length - 1] = bits[length] as u8;
+    }
+    (
+        counts,
+        symbols.into_iter().map(|symbol| symbol as u8).collect(),
+    )
+}
+
+/// `(code, length)` for each symbol of a DHT table (spec C.2).
+fn huffman_codes(counts: &[u8; 16], symbols: &[u8]) -> [(u16, u8); 256] {
+    let mut codes = [(0, 0); 256];
+    let (mut code, mut index) = (0u16, 0);
+    for length in 1..=16u8 {
+        for _ in 0..counts[length as usize - 1] {
+            codes[symbols[index] as usize] = (code, length);
+            code += 1;
+            index += 1;
+        }
+        code <<= 1;
+    }
+    codes
+}
+
+/// The bits needed for `value`'s magnitude, and the bits sent for it.
+fn category(value: i32) -> (u8, u16) {
+    let size = (32 - value.unsigned_abs().leading_zeros()) as u8;
+    let bits = if value < 0 { value - 1 } else { value };
+    (size, (bits as u32 & ((1 << size) - 1)) as u16)
+}
+
+/// Calls `emit` with each Huffman symbol of a block and the extra bits that follow it.
+fn block_symbols(block: &[i32; 64], predictor: i32, mut emit: impl FnMut(bool, u8, u16, u8)) {
+    let (size, bits) = category(block[0] - predictor);
+    emit(true, size, bits, size);
+    let mut run = 0;
+    for &coefficient in &block[1..] {
+        if coefficient == 0 {
+            run += 1;
+            continue;
+        }
+        while run > 15 {
+            emit(false, 0xF0, 0, 0);
+            run -= 16;
+        }
+        let (size, bits) = category(coefficient);
+        emit(false, (run << 4) | size, bits, size);
+        run = 0;
+    }
+    if run > 0 {
+        emit(false, 0x00, 0, 0);
+    }
+}
+
+struct BitWriter {
+    out: Vec<u8>,
+    buffer: u32,
+    count: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, bits: u16, count: u8) {
+        for shift in (0..count).rev() {
+            self.buffer = (self.buffer << 1) | ((bits >> shift) & 1) as u32;
+            self.count += 1;
+            if self.count == 8 {
+                let byte = self.buffer as u8;
+                self.out.push(byte);
+                if byte == 0xFF {
+                    self.out.push(0);
+                }
+                self.buffer = 0;
+                self.count = 0;
+            }
+        }
+    }
+
+    /// Pads the last byte with one bits.
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.write(0x7F, (8 - self.count) as u8);
+        }
+        self.out
+    }
+}
+
+/// Encodes `raster` as a baseline 4:4:4 JPEG with Huffman tables
+/// optimized for the image. Meant for thumbnails: the DCT is straightforward
+/// rather than fast.
+pub fn encode(raster: &Raster, quality: u8) -> Vec<u8> {
+    let quant = [
+        scaled_quant(&LUMA_QUANT, quality),
+        scaled_quant(&CHROMA_QUANT, quality),
+    ];
+    let cosines: Vec<f32> = (0..64)
+        .map(|index| {
+            let (x, u) = ((index / 8) as f32, (index % 8) as f32);
+            ((2.0 * x + 1.0) * u * std::f32::consts::PI / 16.0).cos()
+        })
+        .collect();
+    let (blocks_wide, blocks_high) = (raster.width.div_ceil(8), raster.height.div_ceil(8));
+
+    // Quantized blocks in zigzag order, Y, Cb and Cr in turn for each position.
+    let mut blocks: Vec<[i32; 64]> = Vec::with_capacity(blocks_wide * blocks_high * 3);
+    for block_y in 0..blocks_high {
+        for block_x in 0..blocks_wide {
+            let samples: [[f32; 3]; 64] = std::array::from_fn(|index| {
+                let x = (block_x * 8 + index % 8).min(raster.width - 1);
+                let y = (block_y * 8 + index / 8).min(raster.height - 1);
+                raster.pixel(x, y)
+            });
+            let planes = [
+                samples.map(|[r, g, b]| 0.299 * r + 0.587 * g + 0.114 * b - 128.0),
+                samples.map(|[r, g, b]| -0.168_736 * r - 0.331_264 * g + 0.5 * b),
+                samples.map(|[r, g, b]| 0.5 * r - 0.418_688 * g - 0.081_312 * b),
+            ];
+            for (plane_index, plane) in planes.iter().enumerate() {
+                let table = &quant[(plane_index > 0) as usize];
+                let mut block = [0i32; 64];
+                for (zigzag, &natural) in ZIGZAG.iter().enumerate() {
+                    let (v, u) = (natural / 8, natural % 8);
+                    let mut sum = 0.0;
+                    for y in 0..8 {
+                        for x in 0..8 {
+                            sum += plane[y * 8 + x] * cosines[x * 8 + u] * cosines[y * 8 + v];
+                        }
+                    }
+                    let scale = |frequency: usize| {
+                        if frequency == 0 {
+                            std::f32::consts::FRAC_1_SQRT_2
+                        } else {
+                            1.0
+                        }
+                    };
+                    let coefficient = 0.25 * scale(u) * scale(v) * sum;
+                    block[zigzag] = (coefficient / table[natural] as f32).round() as i32;
+                }
+                blocks.push(block);
+            }
+        }
+    }
+
+    // Luma uses tables 0, chroma tables 1; DC and AC frequencies are counted apart.
+    let mut frequencies = [[0u32; 256]; 4];
+    let mut predictors = [0i32; 3];
+    for (index, block) in blocks.iter().enumerate() {
+        let plane = index % 3;
+        let table = (plane > 0) as usize;
+        block_symbols(block, predictors[plane], |dc, symbol, _, _| {
+            frequencies[table * 2 + (!dc) as usize][symbol as usize] += 1;
+        });
+        predictors[plane] = block[0];
+    }
+    let tables: Vec<([u8; 16], Vec<u8>)> = frequencies.iter().map(huffman_table).collect();
+    let codes: Vec<[(u16, u8); 256]> = tables
+        .iter()
+        .map(|(counts, symbols)| huffman_codes(counts, symbols))
+        .collect();
+
+    let mut writer = BitWriter {
+        out: Vec::new(),
+        buffer: 0,
+        count: 0,
+    };
+    let mut predictors = [0i32; 3];
+    for (index, block) in blocks.iter().enumerate() {
+        let plane = index % 3;
+        let table = (plane > 0) as usize;
+        block_symbols(block, predictors[plane], |dc, symbol, bits, size| {
+            let (code, length) = codes[table * 2 + (!dc) as usize][symbol as usize];
+            writer.write(code, length);
+            writer.write(bits, size);
+        });
+        predictors[plane] = block[0];
+    }
+    let entropy = writer.finish();
+
+    let mut out = SOI.to_vec();
+    let mut segment = |marker: u8, payload: &[u8]| {
+        out.extend(jpeg::encode_segment(marker, payload).expect("JPEG headers are small"));
+    };
+    let mut dqt = Vec::with_capacity(130);
+    for (id, table) in quant.iter().enumerate() {
+        dqt.push(id as u8);
+        dqt.extend(ZIGZAG.iter().map(|&natural| table[natural] as u8));
+    }
+    segment(DQT, &dqt);
+    let (width, height) = (raster.width as u16, raster.height as u16);
+    let mut sof = vec![8];
+    sof.extend_from_slice(&height.to_be_bytes());
+    sof.extend_from_slice(&width.to_be_bytes());
+    sof.extend_from_slice(&[3, 1, 0x11, 0, 2, 0x11, 1, 3, 0x11, 1]);
+    segment(SOF0, &sof);
+    let mut dht = Vec::new();
+    for (index, (counts, symbols)) in tables.iter().enumerate() {
+        // Table class (0 DC, 1 AC) in the high nibble, table ID in the low.
+        dht.push((((index % 2) << 4) | (index / 2)) as u8);
+        dht.extend_from_slice(counts);
+        dht.extend_from_slice(symbols);
+    }
+    segment(DHT, &dht);
+    segment(SOS, &[3, 1, 0x00, 2, 0x11, 3, 0x11, 0, 63, 0]);
+    out.extend(entropy);
+    out.extend_from_slice(&[0xFF, EOI]);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gradient(width: usize, height: usize) -> Raster {
+        let mut pixels = Vec::with_capacity(width * height * 3);
+        for y in 0..height {
+            for x in 0..width {
+                pixels.extend([(x * 255 / width) as u8, (y * 255 / height) as u8, 96]);
+            }
+        }
+        Raster {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    fn max_difference(a: &Raster, b: &Raster) -> u8 {
+        a.pixels
+            .iter()
+            .zip(&b.pixels)
+            .map(|(a, b)| a.abs_diff(*b))
+            .max()
+            .unwrap_or(0)
+    }
+
+    #[test]
+    fn encoded_images_decode_back_to_their_block_means() {
+        let image = gradient(64, 40);
+        let encoded = encode(&image, 90);
+        let decoded = decode_dc(&encoded).unwrap();
+        assert_eq!((decoded.width, decoded.height), (8, 5));
+        let means = image.fit(8);
+        assert_eq!((means.width, means.height), (8, 5));
+        assert!(max_difference(&decoded, &means) <= 6, "{:?}", decoded);
+    }
+
+    #[test]
+    fn huffman_tables_fit_in_sixteen_bits_without_the_all_ones_code() {
+        // Fibonacci frequencies force code lengths past 16 before limiting.
+        let mut frequencies = [0u32; 256];
+        let (mut a, mut b) = (1u32, 1u32);
+        for frequency in frequencies.iter_mut().take(30) {
+            *frequency = a;
+            (a, b) = (b, a.saturating_add(b));
+        }
+        let (counts, symbols) = huffman_table(&frequencies);
+        assert_eq!(symbols.len(), 30);
+        assert_eq!(
+            counts.iter().map(|&count| count as usize).sum::<usize>(),
+            30
+        );
+        let codes = huffman_codes(&counts, &symbols);
+        let longest = counts.iter().rposition(|&count| count > 0).unwrap() as u8 + 1;
+        assert!(symbols
+            .iter()
+            .all(|&symbol| codes[symbol as usize] != (u16::MAX >> (16 - longest), longest)));
+        assert!(decode_dc(b"\xFF\xD8\xFF\xC2\x00\x02\xFF\xDA\x00\x02").is_err());
+    }
+}
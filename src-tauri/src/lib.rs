@@ -1,64 +1,2331 @@
-use exif::{Error as ExifError, Reader};
+mod apple_photos;
+mod benchmark;
+mod burst;
+mod c2pa;
+mod capabilities;
+mod captions;
+mod capture;
+mod capture_sort;
+mod cbor;
+mod commit;
+mod companions;
+#[cfg(desktop)]
+mod deep_link;
+mod dng;
+mod dof;
+mod events;
+mod exif_write;
+mod field_order;
+mod field_search;
+mod field_table;
+mod film_roll;
+#[cfg(any(test, feature = "fixtures"))]
+pub mod fixtures;
+mod format;
+mod generation;
+mod geo;
+mod geocode;
+mod geotag;
+mod gps;
+mod heif;
+mod icc;
+mod ifd;
+mod index_crypto;
+mod iptc;
+mod iptc_ext;
+mod isolation;
+mod jpeg;
+mod jpeg_codec;
+mod license_audit;
+mod lighting;
+mod lightroom;
+mod logging;
+mod makernote;
+mod memory_budget;
+mod merge;
+mod metadata_index;
+mod mwg;
+mod oplog;
+mod orientation;
+mod orphans;
+mod path_scope;
+mod phone_backup;
+mod pipeline;
+mod placeholder;
+mod png_chunks;
+mod prefilter;
+mod projection;
+#[cfg(desktop)]
+mod quick_inspect;
+mod raw;
+mod relocate;
+mod remote;
+mod report;
+mod retry;
+mod scan_priority;
+mod scan_results;
+mod scan_source;
+mod scan_stream;
+mod selftest;
+mod session_restore;
+mod sessions;
+mod settings;
+mod sftp;
+mod shell_integration;
+mod sidecar;
+#[cfg(test)]
+mod snapshot;
+mod social_export;
+mod storage;
+mod strip;
+mod sun;
+mod tag_edit;
+mod takeout;
+mod text_dump;
+mod thumbnail;
+mod time_shift;
+mod timezone;
+mod track;
+mod tray;
+mod usage_stats;
+mod vendor_trailer;
+mod verify;
+mod video;
+mod virtual_fields;
+mod vocabulary;
+mod weather;
+mod webp;
+mod write_lock;
+mod xmp;
+
+use apple_photos::PhotosLibrary;
+use benchmark::BenchmarkReport;
+use burst::{BurstPick, PickCriteria};
+use c2pa::ContentCredentials;
+use capabilities::Capabilities;
+use captions::CaptionResult;
+use capture::CaptureTime;
+use capture_sort::{DateSource, ScanSort};
+use companions::{Companion, CompanionFinder};
+#[cfg(desktop)]
+use deep_link::{DeepLink, LinkedScan};
+use events::{EventHit, EventTagging, EventTarget};
+use exif::{Error as ExifError, Exif, Reader};
+use field_order::FieldList;
+use field_search::FieldSearchCache;
+use field_table::{MarkdownExport, TableFormat};
 use flate2::read::ZlibDecoder;
-use serde::Serialize;
+use format::FormatStyle;
+use generation::WorkflowExport;
+use geo::{GeoExport, GeoFormat, GeoPaths};
+use geotag::{GeotagTarget, Geotagging};
+use gps::GpsInfo;
+use heif::HeifPreview;
+use icc::IccProfile;
+use index_crypto::IndexSecret;
+use isolation::ParseIsolation;
+use jpeg::SegmentRemoval;
+use license_audit::{LicenseAudit, RightsField};
+use lighting::LightingAudit;
+use lightroom::CatalogReport;
+use logging::{LogLevel, LogRecord};
+use merge::MergePolicy;
+use metadata_index::{Access, IndexCheck, IndexQuery, IndexRefresh, MetadataIndex};
+use mwg::{ReconciledField, SourcePriority};
+use oplog::{OperationLog, OperationRecord};
+use orientation::{OrientationAudit, OrientationRepair};
+use orphans::Orphan;
+use phone_backup::PhoneBackup;
+use pipeline::{Pipeline, PipelineRun, PipelineStore};
+use placeholder::CloudPlaceholders;
+use png_chunks::PngChunkInfo;
+use projection::FieldSelection;
+use relocate::{RelocateMode, Relocation};
+use remote::RemoteLibrary;
+use report::{DiffReport, HtmlReport};
+use retry::ReadError;
+use scan_priority::{Pacer, ScanPriority};
+use scan_results::{ScanPage, ScanResults};
+use scan_source::CaptureKind;
+use scan_stream::{NdjsonWriter, ScanEvent};
+use selftest::SelfTestReport;
+use serde::{Deserialize, Serialize};
+use session_restore::{SavedSession, ScanParameters, SessionJournal};
+use sessions::{WindowSession, WindowSessions};
+use settings::{AppSettings, EditMode, IndexEncryption, SettingsStore};
+use shell_integration::{ContextMenuStatus, LaunchPaths};
+use social_export::SocialImport;
 use std::{
-    cmp::Ordering,
-    fs::{self, File},
+    fs,
     io::{Cursor, ErrorKind, Read},
     path::{Path, PathBuf},
 };
+use storage::StorageReport;
+use strip::{MetadataStrip, StripOptions};
+use sun::LightPhase;
+use tag_edit::{ExifWrite, TagEdit};
+use takeout::TakeoutImport;
+use tauri::{AppHandle, Emitter, Manager, State, Window, WindowEvent};
+use text_dump::{GroupFilter, Verbosity};
+use thumbnail::{ExtractedThumbnail, ThumbnailRebuild};
+use time_shift::TimestampShift;
+use timezone::{TimezoneCheck, TimezoneFix};
+use track::{TrackExport, TrackFormat};
+use tray::{GpsLeakScan, QuickScan};
+use usage_stats::DiagnosticsReport;
+use vendor_trailer::BlockExport;
+use video::VideoMetadata;
+use virtual_fields::VirtualFieldStore;
+use vocabulary::{KeywordAudit, KeywordCheck, Vocabulary};
+use weather::{CsvWeatherProvider, WeatherEnrichment};
+use write_lock::{FileStamp, WriteAssist};
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+const SUPPORTED_IMAGE_EXTENSIONS: &[&str] = &[
+    "jpg", "jpeg", "png", "tif", "tiff", "webp", "heic", "heif", "avif", "bmp", "dng", "cr2",
+    "cr3", "nef", "arw", "raf",
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExifField {
+    tag: String,
+    ifd: String,
+    value: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AestheticMatch {
+    path: String,
+    score: f64,
+    /// darktable or RawTherapee sidecar holding the edit history, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sidecar: Option<String>,
+    /// The fields the scan was asked to return, when it was.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fields: Option<FieldList>,
+    /// When the photo was taken, in ISO 8601.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    captured: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    captured_from: Option<DateSource>,
+    /// The capture day the match is grouped under when sorted by date.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    day: Option<String>,
+    /// The film roll and frame of a negative or slide scan.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    roll: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    frame: Option<String>,
+    /// `City, Country` of the GPS position, when reverse geocoding is on.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    place: Option<String>,
+    /// Sidecars, voice memos, thumbnails and tracks that belong with the
+    /// image, when the scan was asked for them.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    companions: Vec<Companion>,
+    #[serde(skip_serializing_if = "MatchStatus::is_read")]
+    status: MatchStatus,
+    /// Why a pending or placeholder file was not read.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    unavailable: Option<String>,
+    #[serde(skip)]
+    capture: Option<CaptureTime>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MatchStatus {
+    #[default]
+    Read,
+    /// The file stayed locked or undownloaded through every retry, so its
+    /// score (reported as 0) and fields are unknown. Scan it again later.
+    Pending,
+    /// An online-only cloud file left undownloaded, since scans are set to
+    /// skip placeholders.
+    Placeholder,
+    /// A remote object that could not be read for a reason another try will
+    /// not fix, such as refused credentials or an object deleted since the
+    /// listing. The reason says which.
+    Failed,
+}
+
+impl MatchStatus {
+    fn is_read(&self) -> bool {
+        *self == MatchStatus::Read
+    }
+}
+
+impl AestheticMatch {
+    fn unread(path: &Path, status: MatchStatus, reason: String) -> Self {
+        AestheticMatch {
+            path: path.to_string_lossy().into_owned(),
+            score: 0.0,
+            sidecar: None,
+            fields: None,
+            captured: None,
+            captured_from: None,
+            day: None,
+            roll: None,
+            frame: None,
+            place: None,
+            companions: Vec::new(),
+            status,
+            unavailable: Some(reason),
+            capture: None,
+        }
+    }
+}
+
+/// Optional criteria applied on top of the aesthetic score during folder scans.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScanFilters {
+    /// Keep only photos taken in one of these lighting conditions.
+    light_phases: Option<Vec<LightPhase>>,
+    /// Keep only scans, only camera photos, and so on.
+    capture_kinds: Option<Vec<CaptureKind>>,
+}
+
+impl ScanFilters {
+    fn matches(&self, data: &[u8]) -> bool {
+        if self.light_phases.is_none() && self.capture_kinds.is_none() {
+            return true;
+        }
+        let exif = read_exif_container(data);
+        let phase_matches = self.light_phases.as_ref().is_none_or(|phases| {
+            exif.as_ref()
+                .and_then(sun::sun_at_capture)
+                .is_some_and(|sun| phases.contains(&sun.phase()))
+        });
+        let kind_matches = self.capture_kinds.as_ref().is_none_or(|kinds| {
+            let kind = exif
+                .as_ref()
+                .map_or(CaptureKind::Unknown, |exif| scan_source::classify(exif).0);
+            kinds.contains(&kind)
+        });
+        phase_matches && kind_matches
+    }
+}
+
+/// `merge` controls duplicates when EXIF, XMP, IPTC and PNG text carry the
+/// same logical tag; by default every copy is returned. `fields` limits the
+/// result to the listed tags and groups. Fields come in the canonical order of
+/// [`field_order::sort_canonical`], each with a stable `id`. A `remote://`
+/// path reads from a saved remote library, fetching only the header of a
+/// JPEG.
+#[tauri::command]
+fn read_exif(
+    path: String,
+    format: Option<FormatStyle>,
+    merge: Option<MergePolicy>,
+    fields: Option<FieldSelection>,
+) -> Result<FieldList, String> {
+    if !remote::is_remote(&path) {
+        path_scope::check_all([&path])?;
+    }
+    read_file_fields(
+        Path::new(&path),
+        format.unwrap_or_default(),
+        merge.unwrap_or_default(),
+        &fields.unwrap_or_default(),
+    )
+    .map(FieldList)
+}
+
+/// Searches the tags and values `read_exif` returns for `path` with default
+/// options, returning the IDs of the matches (see [`field_order::field_ids`]).
+#[tauri::command]
+fn find_field(
+    cache: State<'_, FieldSearchCache>,
+    path: String,
+    pattern: String,
+) -> Result<Vec<String>, String> {
+    let load = |path: &Path| {
+        read_file_fields(
+            path,
+            FormatStyle::default(),
+            MergePolicy::default(),
+            &FieldSelection::default(),
+        )
+    };
+    // Remote objects have no modification time to validate the cache with.
+    if remote::is_remote(&path) {
+        return Ok(field_search::find(&load(Path::new(&path))?, &pattern));
+    }
+    path_scope::check_all([&path])?;
+    let path = Path::new(&path);
+    let fields = cache.fields(path, || load(path))?;
+    Ok(field_search::find(&fields, &pattern))
+}
+
+/// With `fields`, each match also carries those tags and groups. A
+/// `background` priority trades speed for a responsive machine. `sort`
+/// orders by score (the default) or by capture date. With `companions`, each
+/// match lists the non-image files that belong with it.
+#[tauri::command]
+fn find_aesthetic_images(
+    path: String,
+    min_score: f64,
+    filters: Option<ScanFilters>,
+    fields: Option<FieldSelection>,
+    priority: Option<ScanPriority>,
+    sort: Option<ScanSort>,
+    companions: Option<bool>,
+) -> Result<Vec<AestheticMatch>, String> {
+    if !remote::is_remote(&path) {
+        path_scope::check_all([&path])?;
+    }
+    collect_aesthetic_matches(
+        &path,
+        min_score,
+        &filters.unwrap_or_default(),
+        fields.as_ref(),
+        priority.unwrap_or_default(),
+        sort.unwrap_or_default(),
+        companions.unwrap_or(false),
+    )
+}
+
+/// Runs the same scan as `find_aesthetic_images` but keeps the result on the
+/// backend and returns only its first `page_size` matches in `sort` order.
+/// Fetch the rest with `continue_scan`.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+fn start_aesthetic_scan(
+    window: Window,
+    results: State<'_, ScanResults<AestheticMatch>>,
+    sessions: State<'_, WindowSessions>,
+    journal: State<'_, SessionJournal>,
+    path: String,
+    min_score: f64,
+    filters: Option<ScanFilters>,
+    fields: Option<FieldSelection>,
+    priority: Option<ScanPriority>,
+    sort: Option<ScanSort>,
+    page_size: Option<usize>,
+    companions: Option<bool>,
+) -> Result<ScanPage<AestheticMatch>, String> {
+    if !remote::is_remote(&path) {
+        path_scope::check_all([&path])?;
+    }
+    let scan = ScanParameters {
+        path,
+        min_score,
+        filters: filters.unwrap_or_default(),
+        fields,
+        priority: priority.unwrap_or_default(),
+        sort: sort.unwrap_or_default(),
+        page_size: page_size.unwrap_or(scan_results::DEFAULT_PAGE_SIZE),
+        companions: companions.unwrap_or(false),
+    };
+    let matches = collect_aesthetic_matches(
+        &scan.path,
+        scan.min_score,
+        &scan.filters,
+        scan.fields.as_ref(),
+        scan.priority,
+        scan.sort,
+        scan.companions,
+    )?;
+    sessions.set_scan_folder(window.label(), &scan.path);
+    journal.save_windows(sessions.all());
+    let page_size = scan.page_size;
+    journal.save_scan(scan);
+    Ok(results.start(window.label(), matches, page_size))
+}
+
+/// The page of a stored scan that `cursor` points at. Only the window that
+/// started the scan can page through it.
+#[tauri::command]
+fn continue_scan(
+    window: Window,
+    results: State<'_, ScanResults<AestheticMatch>>,
+    cursor: String,
+) -> Result<ScanPage<AestheticMatch>, String> {
+    results.page(window.label(), &cursor)
+}
+
+/// Forgets a stored scan before its last page was read.
+#[tauri::command]
+fn release_scan(window: Window, results: State<'_, ScanResults<AestheticMatch>>, cursor: String) {
+    results.release(window.label(), &cursor);
+}
+
+/// Opens another app window, optionally showing `path`, and returns its
+/// label. Each window keeps its own open file and scan results.
+// Async so the window is built off the main thread; building it from a
+// synchronous command deadlocks on Windows.
+#[tauri::command]
+async fn open_window(
+    app: AppHandle,
+    sessions: State<'_, WindowSessions>,
+    journal: State<'_, SessionJournal>,
+    path: Option<String>,
+) -> Result<String, String> {
+    if let Some(path) = &path {
+        path_scope::check_all([path])?;
+    }
+    let label = sessions.next_label();
+    sessions.set_open_file(&label, path);
+    journal.save_windows(sessions.all());
+    let built = tauri::WebviewWindowBuilder::new(&app, &label, tauri::WebviewUrl::default())
+        .title("Exif Viewer")
+        .inner_size(800.0, 600.0)
+        .build();
+    if let Err(error) = built {
+        sessions.close(&label);
+        journal.save_windows(sessions.all());
+        return Err(format!("Unable to open a new window: {}", error));
+    }
+    Ok(label)
+}
+
+/// The calling window's label, open file and latest scan folder.
+#[tauri::command]
+fn get_window_session(window: Window, sessions: State<'_, WindowSessions>) -> WindowSession {
+    sessions.get(window.label())
+}
+
+/// What the previous run of the app left behind, once per launch: each
+/// window's open file and scan folder, the latest scan's parameters and the
+/// pipeline runs that never finished, with the files their last step had
+/// done. `None` after a first launch or once restored.
+#[tauri::command]
+fn restore_session(journal: State<'_, SessionJournal>) -> Option<SavedSession> {
+    journal.restore()
+}
+
+/// Records the file the calling window is showing; `None` when it is closed.
+#[tauri::command]
+fn set_window_file(
+    window: Window,
+    sessions: State<'_, WindowSessions>,
+    journal: State<'_, SessionJournal>,
+    path: Option<String>,
+) -> Result<(), String> {
+    if let Some(path) = &path {
+        path_scope::check_all([path])?;
+    }
+    sessions.set_open_file(window.label(), path);
+    journal.save_windows(sessions.all());
+    Ok(())
+}
+
+/// Hides the main window instead of closing it and drops the state of any
+/// window once it is gone.
+fn handle_window_event(window: &Window, event: &WindowEvent) {
+    #[cfg(desktop)]
+    tray::keep_running_on_close(window, event);
+    if let WindowEvent::Destroyed = event {
+        let label = window.label();
+        if let Some(sessions) = window.try_state::<WindowSessions>() {
+            sessions.close(label);
+            if let Some(journal) = window.try_state::<SessionJournal>() {
+                journal.save_windows(sessions.all());
+            }
+        }
+        if let Some(results) = window.try_state::<ScanResults<AestheticMatch>>() {
+            results.release_owner(label);
+        }
+    }
+}
+
+fn collect_aesthetic_matches(
+    path: &str,
+    min_score: f64,
+    filters: &ScanFilters,
+    fields: Option<&FieldSelection>,
+    priority: ScanPriority,
+    sort: ScanSort,
+    companions: bool,
+) -> Result<Vec<AestheticMatch>, String> {
+    let mut matches = Vec::new();
+    let mut finder = companions.then(CompanionFinder::default);
+    scan_aesthetic_images(
+        path,
+        min_score,
+        filters,
+        fields,
+        priority,
+        &mut |mut result| {
+            if let Some(finder) = finder.as_mut() {
+                result.companions = finder.find(Path::new(&result.path), result.capture);
+            }
+            matches.push(result)
+        },
+    )?;
+    capture_sort::sort_matches(&mut matches, sort);
+    Ok(matches)
+}
+
+/// The same scan as `find_aesthetic_images`, but nothing is collected: each
+/// match is emitted as a `scan-event` as soon as it is found and, with
+/// `output`, appended to that file as newline-delimited JSON. A final `done`
+/// event carries the totals.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+fn stream_aesthetic_images(
+    app: AppHandle,
+    settings: State<'_, SettingsStore>,
+    path: String,
+    min_score: f64,
+    filters: Option<ScanFilters>,
+    fields: Option<FieldSelection>,
+    output: Option<String>,
+    priority: Option<ScanPriority>,
+    companions: Option<bool>,
+) -> Result<(), String> {
+    if !remote::is_remote(&path) {
+        path_scope::check_all([&path])?;
+    }
+    if let Some(output) = &output {
+        settings.ensure_writable()?;
+        path_scope::check_write(Path::new(output)).map_err(|violation| violation.message())?;
+    }
+    let mut writer = output
+        .map(|output| NdjsonWriter::create(Path::new(&output)))
+        .transpose()?;
+    let mut write_error = None;
+    let mut publish = |event: ScanEvent| {
+        if let Some(writer) = writer.as_mut() {
+            if let Err(error) = writer.write(&event) {
+                write_error.get_or_insert(error);
+            }
+        }
+        app.emit("scan-event", event).ok();
+    };
+
+    let mut matches = 0;
+    let mut finder = companions.unwrap_or(false).then(CompanionFinder::default);
+    let scanned = scan_aesthetic_images(
+        &path,
+        min_score,
+        &filters.unwrap_or_default(),
+        fields.as_ref(),
+        priority.unwrap_or_default(),
+        &mut |mut result| {
+            if let Some(finder) = finder.as_mut() {
+                result.companions = finder.find(Path::new(&result.path), result.capture);
+            }
+            // Pending files are announced but are not matches yet.
+            if result.status == MatchStatus::Read {
+                matches += 1;
+            }
+            publish(ScanEvent::Match(Box::new(result)));
+        },
+    )?;
+    publish(ScanEvent::Done { scanned, matches });
+    match write_error {
+        Some(error) => Err(format!("Unable to write the scan results: {}", error)),
+        None => Ok(()),
+    }
+}
+
+/// Scans a folder, or a single file, handing each match to `on_match` as it
+/// is found. Returns the number of images examined.
+fn scan_aesthetic_images(
+    path: &str,
+    min_score: f64,
+    filters: &ScanFilters,
+    fields: Option<&FieldSelection>,
+    priority: ScanPriority,
+    on_match: &mut (dyn FnMut(AestheticMatch) + Send),
+) -> Result<usize, String> {
+    if !min_score.is_finite() {
+        return Err("The minimum score must be a valid number.".to_string());
+    }
+    if let Some(location) = remote::Location::parse(path) {
+        return scan_remote_images(&location, min_score, filters, fields, priority, on_match);
+    }
+
+    let root = PathBuf::from(path);
+    if !root.exists() {
+        return Err("The selected folder does not exist.".to_string());
+    }
+
+    if root.is_file() {
+        match analyze_file(&root, min_score, filters, fields) {
+            Ok(Some(result)) => on_match(result),
+            Ok(None) => {}
+            Err(ReadError::Unavailable(reason)) => {
+                on_match(AestheticMatch::unread(&root, MatchStatus::Pending, reason))
+            }
+            Err(ReadError::Placeholder(reason)) => on_match(AestheticMatch::unread(
+                &root,
+                MatchStatus::Placeholder,
+                reason,
+            )),
+            Err(error) => return Err(error.into()),
+        }
+        return Ok(usize::from(is_supported_image(&root)));
+    }
+
+    if !root.is_dir() {
+        return Err("The selected path is not a folder.".to_string());
+    }
+
+    Ok(priority.run(|| {
+        let mut pacer = Pacer::new(priority);
+        let mut scanned = 0;
+        let mut unavailable = Vec::new();
+        walk_files(&root, &mut |path| {
+            if !is_supported_image(path) {
+                return;
+            }
+            scanned += 1;
+            match analyze_file(path, min_score, filters, fields) {
+                Ok(Some(result)) => on_match(result),
+                Err(ReadError::Unavailable(_)) => unavailable.push(path.to_path_buf()),
+                Err(ReadError::Placeholder(reason)) => on_match(AestheticMatch::unread(
+                    path,
+                    MatchStatus::Placeholder,
+                    reason,
+                )),
+                _ => {}
+            }
+            pacer.after_file(path);
+        });
+        // Locked files get one more round once the rest of the folder is
+        // done, which is often long enough for a sync client to finish.
+        for path in unavailable {
+            match analyze_file(&path, min_score, filters, fields) {
+                Ok(Some(result)) => on_match(result),
+                Err(ReadError::Unavailable(reason)) => {
+                    on_match(AestheticMatch::unread(&path, MatchStatus::Pending, reason))
+                }
+                _ => {}
+            }
+            pacer.after_file(&path);
+        }
+        scanned
+    }))
+}
+
+/// [`scan_aesthetic_images`] over the objects of a remote library below
+/// `location`, or the single object it names.
+fn scan_remote_images(
+    location: &remote::Location,
+    min_score: f64,
+    filters: &ScanFilters,
+    fields: Option<&FieldSelection>,
+    priority: ScanPriority,
+    on_match: &mut (dyn FnMut(AestheticMatch) + Send),
+) -> Result<usize, String> {
+    let backend = location.backend()?;
+    let folder = format!("{}/", location.key.trim_end_matches('/'));
+    let objects: Vec<_> = backend
+        .list(&location.key)?
+        .into_iter()
+        .filter(|object| {
+            location.key.is_empty() || object.key == location.key || object.key.starts_with(&folder)
+        })
+        .filter(|object| is_supported_image(Path::new(&location.path_of(&object.key))))
+        .collect();
+
+    Ok(priority.run(|| {
+        let mut pacer = Pacer::new(priority);
+        for object in &objects {
+            let path = PathBuf::from(location.path_of(&object.key));
+            match analyze_remote(backend.as_ref(), &path, object, min_score, filters, fields) {
+                Ok(Some(result)) => on_match(result),
+                Err(ReadError::Unavailable(reason)) => {
+                    on_match(AestheticMatch::unread(&path, MatchStatus::Pending, reason))
+                }
+                Err(error) => on_match(AestheticMatch::unread(
+                    &path,
+                    MatchStatus::Failed,
+                    error.into(),
+                )),
+                Ok(None) => {}
+            }
+            pacer.after_file(&path);
+        }
+        objects.len()
+    }))
+}
+
+/// The GPS position of the file at `path` for map features: signed decimal
+/// degrees, altitude in metres, the UTC time of the fix and the direction
+/// the camera faced. A `remote://` path reads only the object's header.
+#[tauri::command]
+fn read_gps(path: String) -> Result<GpsInfo, String> {
+    if !remote::is_remote(&path) {
+        path_scope::check_all([&path])?;
+    }
+    let data = load_metadata_data(Path::new(&path))?;
+    read_exif_container(&data)
+        .as_ref()
+        .and_then(GpsInfo::from_exif)
+        .ok_or_else(|| "The selected file has no GPS position.".to_string())
+}
+
+#[tauri::command]
+fn export_track(
+    settings: State<'_, SettingsStore>,
+    folder: String,
+    format: TrackFormat,
+    output: Option<String>,
+) -> Result<TrackExport, String> {
+    settings.ensure_writable()?;
+    let root = PathBuf::from(&folder);
+    path_scope::check_all([&root])?;
+    if !root.is_dir() {
+        return Err("The selected path is not a folder.".to_string());
+    }
+
+    let output = output
+        .map(PathBuf::from)
+        .unwrap_or_else(|| default_export_path(&root, format.extension()));
+    path_scope::check_write(&output).map_err(|violation| violation.message())?;
+    track::export_track(&root, format, &output)
+}
+
+#[tauri::command]
+fn export_geo(
+    settings: State<'_, SettingsStore>,
+    folder: String,
+    format: GeoFormat,
+    paths: Option<GeoPaths>,
+    output: Option<String>,
+) -> Result<GeoExport, String> {
+    settings.ensure_writable()?;
+    let root = PathBuf::from(&folder);
+    path_scope::check_all([&root])?;
+    if !root.is_dir() {
+        return Err("The selected path is not a folder.".to_string());
+    }
+
+    let output = output
+        .map(PathBuf::from)
+        .unwrap_or_else(|| default_export_path(&root, format.extension()));
+    path_scope::check_write(&output).map_err(|violation| violation.message())?;
+    geo::export_geo(&root, format, paths.unwrap_or_default(), &output)
+}
+
+/// Writes a standalone HTML metadata report for one image, by default next
+/// to it as `<name>.report.html`.
+#[tauri::command]
+fn export_html_report(
+    store: State<'_, VirtualFieldStore>,
+    settings: State<'_, SettingsStore>,
+    path: String,
+    output: Option<String>,
+) -> Result<HtmlReport, String> {
+    settings.ensure_writable()?;
+    let image = PathBuf::from(&path);
+    path_scope::check_all([&image])?;
+    if !image.is_file() {
+        return Err("The selected file does not exist.".to_string());
+    }
+
+    let output = output.map(PathBuf::from).unwrap_or_else(|| {
+        let mut name = image.file_name().unwrap_or_default().to_os_string();
+        name.push(".report.html");
+        image.with_file_name(name)
+    });
+    let extra_fields = store
+        .fields_for(&image)
+        .iter()
+        .map(|field| field.to_exif_field())
+        .collect();
+    path_scope::check_write(&output).map_err(|violation| violation.message())?;
+    report::export_html_report(&image, extra_fields, &output)
+}
+
+/// Saves the ComfyUI workflow embedded in the PNG at `path` as JSON that
+/// ComfyUI can load again, by default next to it as `<name>.workflow.json`.
+#[tauri::command]
+fn export_comfyui_workflow(
+    settings: State<'_, SettingsStore>,
+    path: String,
+    output: Option<String>,
+) -> Result<WorkflowExport, String> {
+    settings.ensure_writable()?;
+    let image = PathBuf::from(&path);
+    path_scope::check_all([&image])?;
+    if !image.is_file() {
+        return Err("The selected file does not exist.".to_string());
+    }
+
+    let output = output
+        .map(PathBuf::from)
+        .unwrap_or_else(|| image.with_extension("workflow.json"));
+    path_scope::check_write(&output).map_err(|violation| violation.message())?;
+    generation::export_workflow(&load_file_data(&image)?, &output)
+}
+
+/// Compares the metadata of two versions of an image, e.g. before and after
+/// retouching. Without `output` the report goes next to the second file.
+#[tauri::command]
+fn export_diff_report(
+    settings: State<'_, SettingsStore>,
+    path_a: String,
+    path_b: String,
+    output: Option<String>,
+) -> Result<DiffReport, String> {
+    settings.ensure_writable()?;
+    let (before, after) = (PathBuf::from(&path_a), PathBuf::from(&path_b));
+    path_scope::check_all([&before, &after])?;
+    if !before.is_file() || !after.is_file() {
+        return Err("Both files must exist to compare them.".to_string());
+    }
+
+    let output = output.map(PathBuf::from).unwrap_or_else(|| {
+        let mut name = after.file_name().unwrap_or_default().to_os_string();
+        name.push(".diff.html");
+        after.with_file_name(name)
+    });
+    path_scope::check_write(&output).map_err(|violation| violation.message())?;
+    report::export_diff_report(&before, &after, &output)
+}
+
+/// Plain-text rendering of a file's metadata for pasting into mail and tickets.
+#[tauri::command]
+fn dump_text(
+    store: State<'_, VirtualFieldStore>,
+    path: String,
+    verbosity: Option<Verbosity>,
+    groups: Option<GroupFilter>,
+) -> Result<String, String> {
+    let image = PathBuf::from(&path);
+    path_scope::check_all([&image])?;
+    let data = load_file_data(&image)?;
+    let mut fields = collect_fields_with_style(&data, FormatStyle::default())?;
+    fields.extend(
+        store
+            .fields_for(&image)
+            .iter()
+            .map(|field| field.to_exif_field()),
+    );
+    let file_name = image
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or(path);
+    Ok(text_dump::dump_text(
+        &file_name,
+        &fields,
+        verbosity.unwrap_or_default(),
+        &groups.unwrap_or_default(),
+    ))
+}
+
+/// Copies the chosen fields of one file, e.g. the camera settings, and
+/// returns the copied text.
+#[tauri::command]
+fn copy_fields_to_clipboard(
+    app: AppHandle,
+    path: String,
+    tags: Vec<String>,
+    format: Option<TableFormat>,
+) -> Result<String, String> {
+    use tauri_plugin_clipboard_manager::ClipboardExt;
+
+    path_scope::check_all([&path])?;
+    let data = load_file_data(Path::new(&path))?;
+    let fields = collect_fields_with_style(&data, FormatStyle::default())?;
+    let exif = read_exif_container(&data);
+    let selected = field_table::select_fields(&fields, exif.as_ref(), &tags);
+    if selected.is_empty() {
+        return Err("None of the requested fields are present in this file.".to_string());
+    }
+    let text = field_table::render(&selected, format.unwrap_or_default())?;
+    app.clipboard()
+        .write_text(text.clone())
+        .map_err(|error| format!("Unable to copy to the clipboard: {}", error))?;
+    Ok(text)
+}
+
+/// Saves the payload of one Samsung trailer block, named as in the `Vendor`
+/// fields (e.g. `DualShot_Meta_Info`), by default next to the image as
+/// `<name>.<block>.bin`.
+#[tauri::command]
+fn extract_vendor_block(
+    settings: State<'_, SettingsStore>,
+    path: String,
+    block: String,
+    output: Option<String>,
+) -> Result<BlockExport, String> {
+    settings.ensure_writable()?;
+    let image = PathBuf::from(&path);
+    path_scope::check_all([&image])?;
+    let data = load_file_data(&image)?;
+    let payload = vendor_trailer::block_payload(&data, &block)
+        .ok_or_else(|| format!("The file has no trailer block called {}.", block))?;
+
+    let output = output.map(PathBuf::from).unwrap_or_else(|| {
+        let mut name = image.file_name().unwrap_or_default().to_os_string();
+        name.push(format!(".{}.bin", block.replace(['/', '\\'], "_")));
+        image.with_file_name(name)
+    });
+    path_scope::check_write(&output).map_err(|violation| violation.message())?;
+    write_file_atomically(&output, payload)?;
+    Ok(BlockExport {
+        path: output.to_string_lossy().into_owned(),
+        bytes: payload.len(),
+    })
+}
+
+/// Markdown table of camera settings for one image or a whole folder, ready
+/// to paste into a blog post. Without `columns` the usual settings are used.
+#[tauri::command]
+fn export_markdown(
+    settings: State<'_, SettingsStore>,
+    path: String,
+    columns: Option<Vec<String>>,
+    output: Option<String>,
+) -> Result<MarkdownExport, String> {
+    settings.ensure_writable()?;
+    let root = PathBuf::from(&path);
+    path_scope::check_all([&root])?;
+    if !root.exists() {
+        return Err("The selected file or folder does not exist.".to_string());
+    }
+
+    let columns = columns
+        .filter(|columns| !columns.is_empty())
+        .unwrap_or_else(|| {
+            field_table::DEFAULT_MARKDOWN_COLUMNS
+                .iter()
+                .map(|column| column.to_string())
+                .collect()
+        });
+    let output = output.map(PathBuf::from).unwrap_or_else(|| {
+        if root.is_dir() {
+            default_export_path(&root, "md")
+        } else {
+            root.with_extension("md")
+        }
+    });
+    path_scope::check_write(&output).map_err(|violation| violation.message())?;
+    field_table::export_markdown(&root, &columns, &output)
+}
+
+/// The originals inside the Apple Photos library `library`, with the
+/// import names, dates and flags its database holds. Nothing in the library
+/// is written; pass the listed paths to `read_exif` to inspect them.
+#[tauri::command]
+fn list_photos_library(library: String) -> Result<PhotosLibrary, String> {
+    path_scope::check_all([&library])?;
+    apple_photos::list(Path::new(&library))
+}
+
+/// The images in the iPhone or iPad backup folder `backup`, with the paths
+/// they had on the phone. Pass the listed paths, which keep the backup's
+/// hashed names, to `read_exif` to inspect them; nothing is renamed. Folder
+/// scans and pipelines find the same images when given the backup.
+#[tauri::command]
+fn list_phone_backup(backup: String) -> Result<PhoneBackup, String> {
+    path_scope::check_all([&backup])?;
+    phone_backup::list(Path::new(&backup))
+}
+
+/// Rating, labels and collections Lightroom holds for `path`, plus any values
+/// that differ between the catalog and the file. The catalog is never written.
+#[tauri::command]
+fn lightroom_cross_reference(catalog: String, path: String) -> Result<CatalogReport, String> {
+    path_scope::check_all([&catalog, &path])?;
+    let image = Path::new(&path);
+    if !image.is_file() {
+        return Err("The selected file does not exist.".to_string());
+    }
+    lightroom::cross_reference(Path::new(&catalog), image)
+}
+
+#[tauri::command]
+fn check_timezones(paths: Vec<String>) -> Result<Vec<TimezoneCheck>, String> {
+    path_scope::check_all(&paths)?;
+    paths
+        .iter()
+        .map(|path| timezone::check_file(Path::new(path)))
+        .collect()
+}
+
+#[tauri::command]
+fn fix_timezones_from_gps(
+    log: State<'_, OperationLog>,
+    store: State<'_, VirtualFieldStore>,
+    settings: State<'_, SettingsStore>,
+    paths: Vec<String>,
+    dry_run: bool,
+) -> Result<Vec<TimezoneFix>, String> {
+    if !dry_run {
+        settings.ensure_writable()?;
+    }
+    path_scope::check_all(&paths)?;
+    let targets = commit::EditTargets {
+        log: log.inner(),
+        external: store.inner(),
+        mode: settings.get().edit_mode,
+    };
+    store.batch(|| {
+        paths
+            .iter()
+            .map(|path| timezone::fix_file(Path::new(path), dry_run, &targets))
+            .collect()
+    })
+}
+
+/// Moves DateTimeOriginal, CreateDate, ModifyDate and the GPS date and time
+/// of each JPEG or TIFF in `paths` by `delta_seconds`, for cameras whose
+/// clock was set to the wrong time or zone.
+#[tauri::command]
+fn shift_timestamps(
+    log: State<'_, OperationLog>,
+    store: State<'_, VirtualFieldStore>,
+    settings: State<'_, SettingsStore>,
+    paths: Vec<String>,
+    delta_seconds: i64,
+) -> Result<Vec<TimestampShift>, String> {
+    settings.ensure_writable()?;
+    let delta = chrono::Duration::try_seconds(delta_seconds)
+        .filter(|delta| !delta.is_zero())
+        .ok_or_else(|| "Choose a shift other than zero.".to_string())?;
+    path_scope::check_all(&paths)?;
+    let targets = commit::EditTargets {
+        log: log.inner(),
+        external: store.inner(),
+        mode: settings.get().edit_mode,
+    };
+    store.batch(|| {
+        paths
+            .iter()
+            .map(|path| time_shift::shift_file(Path::new(path), delta, &targets))
+            .collect()
+    })
+}
+
+/// Geotags the images under `folder` from the GPX track at `gpx_path`,
+/// interpolating each position from the DateTimeOriginal plus `time_offset`
+/// seconds. Positions go into JPEG files or, with `target: "sidecar"`, into
+/// XMP sidecars; see [`geotag::geotag_file`]. A dry run only reports the
+/// positions found.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+fn geotag_from_gpx(
+    log: State<'_, OperationLog>,
+    store: State<'_, VirtualFieldStore>,
+    settings: State<'_, SettingsStore>,
+    folder: String,
+    gpx_path: String,
+    time_offset: Option<i64>,
+    target: Option<GeotagTarget>,
+    overwrite: Option<bool>,
+    dry_run: Option<bool>,
+) -> Result<Vec<Geotagging>, String> {
+    let dry_run = dry_run.unwrap_or(false);
+    if !dry_run {
+        settings.ensure_writable()?;
+    }
+    path_scope::check_all([&folder, &gpx_path])?;
+    let root = PathBuf::from(&folder);
+    if !root.is_dir() {
+        return Err("The selected path is not a folder.".to_string());
+    }
+    let track = load_file_data(Path::new(&gpx_path))
+        .and_then(|data| {
+            String::from_utf8(data).map_err(|_| "The GPX file is not valid UTF-8.".to_string())
+        })
+        .and_then(|text| geotag::parse_gpx(&text))?;
+    let targets = commit::EditTargets {
+        log: log.inner(),
+        external: store.inner(),
+        mode: settings.get().edit_mode,
+    };
+    let time_offset = chrono::Duration::seconds(time_offset.unwrap_or(0));
+    let mut taggings = Vec::new();
+    store.batch(|| {
+        walk_files(&root, &mut |path| {
+            if is_supported_image(path) {
+                taggings.push(geotag::geotag_file(
+                    path,
+                    &track,
+                    time_offset,
+                    target.unwrap_or_default(),
+                    overwrite.unwrap_or(false),
+                    dry_run,
+                    &targets,
+                ));
+            }
+        });
+    })?;
+    Ok(taggings)
+}
+
+/// Restores what Google Photos Takeout keeps in per-image `.json` files
+/// under `folder`: the description, capture time and position go into the
+/// EXIF of each image and the people into its XMP, where the file has none
+/// of its own unless `overwrite` is set. A dry run only reports what would
+/// be restored.
+#[tauri::command]
+fn import_takeout(
+    log: State<'_, OperationLog>,
+    store: State<'_, VirtualFieldStore>,
+    settings: State<'_, SettingsStore>,
+    folder: String,
+    overwrite: Option<bool>,
+    dry_run: Option<bool>,
+) -> Result<Vec<TakeoutImport>, String> {
+    let dry_run = dry_run.unwrap_or(false);
+    if !dry_run {
+        settings.ensure_writable()?;
+    }
+    path_scope::check_all([&folder])?;
+    let root = PathBuf::from(&folder);
+    if !root.is_dir() {
+        return Err("The selected path is not a folder.".to_string());
+    }
+    let targets = commit::EditTargets {
+        log: log.inner(),
+        external: store.inner(),
+        mode: settings.get().edit_mode,
+    };
+    let mut imports = Vec::new();
+    store.batch(|| {
+        walk_files(&root, &mut |path| {
+            if is_supported_image(path) {
+                imports.extend(takeout::restore_file(
+                    path,
+                    overwrite.unwrap_or(false),
+                    dry_run,
+                    &targets,
+                ));
+            }
+        });
+    })?;
+    Ok(imports)
+}
+
+/// Gives the media of an Instagram or Facebook data export in `folder` back
+/// the captions, times and positions its JSON keeps, as XMP: embedded in
+/// JPEGs and in sidecars for other formats. Nothing is written while
+/// originals are protected. Values the files already have are kept unless
+/// `overwrite` is set; a dry run only reports what would be restored.
+#[tauri::command]
+fn import_social_export(
+    log: State<'_, OperationLog>,
+    store: State<'_, VirtualFieldStore>,
+    settings: State<'_, SettingsStore>,
+    folder: String,
+    overwrite: Option<bool>,
+    dry_run: Option<bool>,
+) -> Result<Vec<SocialImport>, String> {
+    let dry_run = dry_run.unwrap_or(false);
+    if !dry_run {
+        settings.ensure_writable()?;
+    }
+    path_scope::check_all([&folder])?;
+    let root = PathBuf::from(&folder);
+    if !root.is_dir() {
+        return Err("The selected path is not a folder.".to_string());
+    }
+    let targets = commit::EditTargets {
+        log: log.inner(),
+        external: store.inner(),
+        mode: settings.get().edit_mode,
+    };
+    store.batch(|| {
+        social_export::read_export(&root)
+            .iter()
+            .map(|media| {
+                social_export::restore(media, overwrite.unwrap_or(false), dry_run, &targets)
+            })
+            .collect()
+    })
+}
+
+/// Sets or removes Artist, Copyright, DateTimeOriginal, UserComment and
+/// Orientation in a JPEG or TIFF. Only the EXIF block is rewritten; with
+/// `copy_to` the edited image goes to that new file and the original is
+/// left as it was.
+#[tauri::command]
+fn write_exif(
+    log: State<'_, OperationLog>,
+    store: State<'_, VirtualFieldStore>,
+    settings: State<'_, SettingsStore>,
+    path: String,
+    edits: Vec<TagEdit>,
+    copy_to: Option<String>,
+) -> Result<ExifWrite, String> {
+    settings.ensure_writable()?;
+    if edits.is_empty() {
+        return Err("Choose at least one tag to change.".to_string());
+    }
+    path_scope::check_all([&path])?;
+    let copy_to = copy_to.map(PathBuf::from);
+    if let Some(copy) = &copy_to {
+        path_scope::check_write(copy).map_err(|violation| violation.message())?;
+    }
+    let targets = commit::EditTargets {
+        log: log.inner(),
+        external: store.inner(),
+        mode: settings.get().edit_mode,
+    };
+    Ok(tag_edit::write_file(
+        Path::new(&path),
+        &edits,
+        copy_to.as_deref(),
+        &targets,
+    ))
+}
+
+/// Fills the EXIF description of each file from `template`, e.g.
+/// `{event}[ in {place}], {date}`. Files that already have a description are
+/// left alone unless `overwrite` is set; a dry run only reports the captions.
+#[tauri::command]
+fn generate_captions(
+    log: State<'_, OperationLog>,
+    store: State<'_, VirtualFieldStore>,
+    settings: State<'_, SettingsStore>,
+    paths: Vec<String>,
+    template: String,
+    overwrite: Option<bool>,
+    dry_run: Option<bool>,
+) -> Result<Vec<CaptionResult>, String> {
+    let dry_run = dry_run.unwrap_or(false);
+    if !dry_run {
+        settings.ensure_writable()?;
+    }
+    path_scope::check_all(&paths)?;
+    captions::validate(&template)?;
+    let current = settings.get();
+    let targets = commit::EditTargets {
+        log: log.inner(),
+        external: store.inner(),
+        mode: current.edit_mode,
+    };
+    store.batch(|| {
+        paths
+            .iter()
+            .map(|path| {
+                captions::caption_file(
+                    Path::new(path),
+                    &template,
+                    overwrite.unwrap_or(false),
+                    dry_run,
+                    current.captioner.as_deref(),
+                    &targets,
+                )
+            })
+            .collect()
+    })
+}
+
+/// Derives album and event names from the folders below `folder` and
+/// records them in the index, or in the XMP of JPEG files with
+/// `target: "xmp"`. A dry run only reports what was inferred.
+#[tauri::command]
+fn infer_events(
+    log: State<'_, OperationLog>,
+    store: State<'_, VirtualFieldStore>,
+    settings: State<'_, SettingsStore>,
+    folder: String,
+    target: Option<EventTarget>,
+    dry_run: Option<bool>,
+) -> Result<Vec<EventTagging>, String> {
+    let dry_run = dry_run.unwrap_or(false);
+    if !dry_run {
+        settings.ensure_writable()?;
+    }
+    path_scope::check_all([&folder])?;
+    let root = PathBuf::from(&folder);
+    if !root.is_dir() {
+        return Err("The selected path is not a folder.".to_string());
+    }
+    let targets = commit::EditTargets {
+        log: log.inner(),
+        external: store.inner(),
+        mode: settings.get().edit_mode,
+    };
+    let mut taggings = Vec::new();
+    store.batch(|| {
+        walk_files(&root, &mut |path| {
+            if is_supported_image(path) {
+                taggings.push(events::tag_file(
+                    &root,
+                    path,
+                    target.unwrap_or_default(),
+                    dry_run,
+                    &targets,
+                ));
+            }
+        });
+    })?;
+    Ok(taggings)
+}
+
+/// Images below `folder` whose recorded album, event or date matches `query`.
+#[tauri::command]
+fn search_events(
+    store: State<'_, VirtualFieldStore>,
+    folder: String,
+    query: String,
+) -> Result<Vec<EventHit>, String> {
+    path_scope::check_all([&folder])?;
+    let root = PathBuf::from(&folder);
+    if !root.is_dir() {
+        return Err("The selected path is not a folder.".to_string());
+    }
+    Ok(events::search(&root, store.inner(), &query))
+}
+
+/// Copies images into `destination`, or moves them with `mode: "move"`, by
+/// default together with their sidecars, thumbnails, voice memos and
+/// backups. Each copy is checksummed before an original is removed, and
+/// originals are only removed where the app may write.
+#[tauri::command]
+fn relocate_files(
+    log: State<'_, OperationLog>,
+    settings: State<'_, SettingsStore>,
+    paths: Vec<String>,
+    destination: String,
+    with_companions: Option<bool>,
+    mode: Option<RelocateMode>,
+) -> Result<Vec<Relocation>, String> {
+    settings.ensure_writable()?;
+    path_scope::check_all(&paths)?;
+    let destination = PathBuf::from(&destination);
+    path_scope::check_write(&destination).map_err(|violation| violation.message())?;
+    if !destination.is_dir() {
+        return Err("The destination is not a folder.".to_string());
+    }
+    let mode = mode.unwrap_or_default();
+    if mode == RelocateMode::Move && settings.get().edit_mode == EditMode::ExternalOnly {
+        return Err("Moving is disabled while originals are protected.".to_string());
+    }
+    let mut finder = CompanionFinder::default();
+    Ok(paths
+        .iter()
+        .map(|path| {
+            relocate::relocate(
+                Path::new(path),
+                &destination,
+                mode,
+                with_companions.unwrap_or(true),
+                &mut finder,
+                log.inner(),
+            )
+        })
+        .collect())
+}
+
+/// Sidecars and camera thumbnails below `folder` whose image is gone, and
+/// stale `.sh_thumbnails` entries there. With `thumbnailCache` the user's
+/// desktop thumbnail cache is checked for deleted files of `folder` too.
+/// With `remove` they are deleted as well.
+#[tauri::command]
+fn find_orphans(
+    log: State<'_, OperationLog>,
+    settings: State<'_, SettingsStore>,
+    folder: String,
+    remove: Option<bool>,
+    thumbnail_cache: Option<bool>,
+) -> Result<Vec<Orphan>, String> {
+    let remove = remove.unwrap_or(false);
+    if remove {
+        settings.ensure_writable()?;
+        if settings.get().edit_mode == EditMode::ExternalOnly {
+            return Err("Removing files is disabled while originals are protected.".to_string());
+        }
+    }
+    path_scope::check_all([&folder])?;
+    let root = PathBuf::from(&folder);
+    if !root.is_dir() {
+        return Err("The selected path is not a folder.".to_string());
+    }
+    let user_cache = orphans::user_thumbnail_cache().filter(|_| thumbnail_cache.unwrap_or(false));
+    let mut orphans = orphans::find(&root, user_cache.as_deref());
+    if remove {
+        orphans::remove(&mut orphans, user_cache.as_deref(), log.inner());
+    }
+    Ok(orphans)
+}
+
+/// Brings the metadata index up to date with the images below `folder`.
+#[tauri::command]
+fn index_folder(index: State<'_, MetadataIndex>, folder: String) -> Result<IndexRefresh, String> {
+    path_scope::check_all([&folder])?;
+    let root = PathBuf::from(&folder);
+    if !root.is_dir() {
+        return Err("The selected path is not a folder.".to_string());
+    }
+    index.refresh(&root)
+}
+
+/// Indexed images below `folder` that have every one of `tags`, e.g.
+/// `["GPS", "aesthetic score"]`.
+#[tauri::command]
+fn query_index(
+    index: State<'_, MetadataIndex>,
+    folder: String,
+    tags: Vec<String>,
+) -> Result<IndexQuery, String> {
+    path_scope::check_all([&folder])?;
+    index.query(Path::new(&folder), &tags)
+}
+
+/// Checks the metadata index for damage and repairs it, then indexes
+/// `folder` again if one is given so repaired entries are filled back in.
+#[tauri::command]
+fn verify_index(
+    index: State<'_, MetadataIndex>,
+    folder: Option<String>,
+) -> Result<IndexCheck, String> {
+    let root = folder.map(PathBuf::from);
+    if let Some(root) = &root {
+        path_scope::check_all([root])?;
+        if !root.is_dir() {
+            return Err("The selected path is not a folder.".to_string());
+        }
+    }
+    let mut check = index.verify()?;
+    if let Some(root) = root {
+        check.refresh = Some(index.refresh(&root)?);
+    }
+    Ok(check)
+}
+
+/// Disk usage below `folder` by format, camera, year and rating, to help
+/// pick what to move to cold storage.
+#[tauri::command]
+fn storage_report(folder: String) -> Result<StorageReport, String> {
+    path_scope::check_all([&folder])?;
+    let root = PathBuf::from(&folder);
+    if !root.is_dir() {
+        return Err("The selected path is not a folder.".to_string());
+    }
+    storage::storage_report(&root)
+}
+
+/// Chooses the local captioning program used for `{scene}`, or clears it.
+#[tauri::command]
+fn set_captioner(
+    settings: State<'_, SettingsStore>,
+    program: Option<String>,
+) -> Result<AppSettings, String> {
+    settings.update(|settings| settings.captioner = program)
+}
+
+/// Applies the fix offered alongside a failed write, such as clearing a
+/// read-only flag, so the user can retry.
+#[tauri::command]
+fn resolve_write_problem(
+    settings: State<'_, SettingsStore>,
+    path: String,
+    assist: WriteAssist,
+) -> Result<(), String> {
+    settings.ensure_writable()?;
+    write_lock::apply_assist(Path::new(&path), assist)
+}
+
+#[tauri::command]
+fn list_pipelines(pipelines: State<'_, PipelineStore>) -> Vec<Pipeline> {
+    pipelines.list()
+}
+
+/// Saves a recipe, replacing any existing one with the same name.
+#[tauri::command]
+fn save_pipeline(
+    pipelines: State<'_, PipelineStore>,
+    settings: State<'_, SettingsStore>,
+    pipeline: Pipeline,
+) -> Result<Vec<Pipeline>, String> {
+    settings.ensure_writable()?;
+    pipelines.save(pipeline)
+}
+
+#[tauri::command]
+fn delete_pipeline(
+    pipelines: State<'_, PipelineStore>,
+    settings: State<'_, SettingsStore>,
+    name: String,
+) -> Result<Vec<Pipeline>, String> {
+    settings.ensure_writable()?;
+    pipelines.delete(&name)
+}
+
+/// Names of the saved remote libraries, each readable as `remote://<name>/`.
+#[tauri::command]
+fn list_remote_libraries() -> Result<Vec<String>, String> {
+    Ok(remote::libraries()?.names())
+}
+
+/// Saves an S3, WebDAV or SFTP library under `name`, replacing any existing one.
+#[tauri::command]
+fn save_remote_library(
+    settings: State<'_, SettingsStore>,
+    name: String,
+    library: RemoteLibrary,
+) -> Result<Vec<String>, String> {
+    settings.ensure_writable()?;
+    remote::libraries()?.save(&name, library)
+}
+
+#[tauri::command]
+fn delete_remote_library(
+    settings: State<'_, SettingsStore>,
+    name: String,
+) -> Result<Vec<String>, String> {
+    settings.ensure_writable()?;
+    remote::libraries()?.delete(&name)
+}
+
+/// Runs a saved recipe over the selection, emitting `pipeline-progress`
+/// events as each file is handled.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+fn run_pipeline(
+    app: AppHandle,
+    pipelines: State<'_, PipelineStore>,
+    log: State<'_, OperationLog>,
+    store: State<'_, VirtualFieldStore>,
+    settings: State<'_, SettingsStore>,
+    journal: State<'_, SessionJournal>,
+    name: String,
+    paths: Vec<String>,
+    dry_run: Option<bool>,
+) -> Result<PipelineRun, String> {
+    let dry_run = dry_run.unwrap_or(false);
+    if !dry_run {
+        settings.ensure_writable()?;
+    }
+    path_scope::check_all(&paths)?;
+    let pipeline = pipelines
+        .get(&name)
+        .ok_or_else(|| format!("No pipeline named {:?} is saved.", name))?;
+    let targets = commit::EditTargets {
+        log: log.inner(),
+        external: store.inner(),
+        mode: settings.get().edit_mode,
+    };
+    // Previews change nothing, so only real runs are worth resuming.
+    let job = (!dry_run).then(|| journal.start_job(&pipeline.name, &paths, pipeline.steps.len()));
+    let run = store.batch(|| {
+        pipeline::run_pipeline(
+            &pipeline,
+            paths.into_iter().map(PathBuf::from).collect(),
+            dry_run,
+            &targets,
+            &mut |progress| {
+                if let Some(job) = job {
+                    journal.job_progress(job, progress.step, progress.path.as_deref());
+                }
+                app.emit("pipeline-progress", progress).ok();
+            },
+        )
+    });
+    if let Some(job) = job {
+        journal.finish_job(job);
+    }
+    run
+}
+
+/// Returns the files and folders the app was opened with, once.
+#[tauri::command]
+fn take_launch_paths(launch: State<'_, LaunchPaths>) -> Vec<String> {
+    launch
+        .take()
+        .into_iter()
+        .map(|path| path.to_string_lossy().into_owned())
+        .collect()
+}
+
+#[tauri::command]
+fn get_context_menu_status() -> ContextMenuStatus {
+    shell_integration::status()
+}
+
+/// Adds an "Inspect metadata with EXIF Viewer" entry to the file manager's
+/// context menu that launches this executable with the selected path.
+#[tauri::command]
+fn register_context_menu(settings: State<'_, SettingsStore>) -> Result<ContextMenuStatus, String> {
+    settings.ensure_writable()?;
+    let executable = std::env::current_exe().map_err(|error| error.to_string())?;
+    shell_integration::register(&executable)
+}
+
+#[tauri::command]
+fn unregister_context_menu(
+    settings: State<'_, SettingsStore>,
+) -> Result<ContextMenuStatus, String> {
+    settings.ensure_writable()?;
+    shell_integration::unregister()
+}
+
+/// The latest tray scan, for showing it when the window comes back.
+#[tauri::command]
+fn get_last_quick_scan(quick: State<'_, QuickScan>) -> Option<GpsLeakScan> {
+    quick.last()
+}
+
+#[tauri::command]
+fn get_settings(settings: State<'_, SettingsStore>) -> AppSettings {
+    settings.get()
+}
+
+/// Switches between writing edits into files and keeping them in the app only.
+#[tauri::command]
+fn set_edit_mode(
+    settings: State<'_, SettingsStore>,
+    mode: EditMode,
+) -> Result<AppSettings, String> {
+    settings.update(|settings| settings.edit_mode = mode)
+}
+
+/// Caps how much file data scans hold in memory at once; `None` restores the default.
+#[tauri::command]
+fn set_scan_memory_limit(
+    settings: State<'_, SettingsStore>,
+    megabytes: Option<u64>,
+) -> Result<AppSettings, String> {
+    if megabytes.is_some_and(|megabytes| megabytes < memory_budget::MIN_LIMIT_MB) {
+        return Err(format!(
+            "The scan memory limit must be at least {} MB.",
+            memory_budget::MIN_LIMIT_MB
+        ));
+    }
+    let updated = settings.update(|settings| settings.scan_memory_limit_mb = megabytes)?;
+    apply_scan_memory_limit(&updated);
+    Ok(updated)
+}
+
+/// Chooses how parsing is shielded from malformed files; see [`ParseIsolation`].
+#[tauri::command]
+fn set_parse_isolation(
+    settings: State<'_, SettingsStore>,
+    mode: ParseIsolation,
+) -> Result<AppSettings, String> {
+    let updated = settings.update(|settings| settings.parse_isolation = mode)?;
+    isolation::set_mode(mode);
+    Ok(updated)
+}
+
+/// Chooses whether scans skip online-only cloud files or download them;
+/// see [`CloudPlaceholders`].
+#[tauri::command]
+fn set_cloud_placeholders(
+    settings: State<'_, SettingsStore>,
+    policy: CloudPlaceholders,
+) -> Result<AppSettings, String> {
+    let updated = settings.update(|settings| settings.cloud_placeholders = policy)?;
+    placeholder::set_policy(policy);
+    Ok(updated)
+}
+
+/// Turns the offline reverse geocoding of GPS positions on or off. While on,
+/// `read_exif` adds a `Place` group and scan matches carry their `place`.
+#[tauri::command]
+fn set_reverse_geocoding(
+    settings: State<'_, SettingsStore>,
+    enabled: bool,
+) -> Result<AppSettings, String> {
+    let updated = settings.update(|settings| settings.reverse_geocoding = enabled)?;
+    geocode::set_enabled(enabled);
+    Ok(updated)
+}
+
+/// Opts in to or out of local usage statistics. Opting out deletes what was
+/// collected.
+#[tauri::command]
+fn set_usage_stats(
+    app: AppHandle,
+    settings: State<'_, SettingsStore>,
+    enabled: bool,
+) -> Result<AppSettings, String> {
+    let updated = settings.update(|settings| settings.usage_stats = enabled)?;
+    apply_usage_stats(&app, &updated)?;
+    Ok(updated)
+}
+
+fn apply_usage_stats(app: &AppHandle, settings: &AppSettings) -> Result<(), String> {
+    if settings.usage_stats {
+        let data_dir = app
+            .path()
+            .app_data_dir()
+            .map_err(|error| error.to_string())?;
+        usage_stats::global().enable(data_dir.join("usage-stats.json"));
+    } else {
+        usage_stats::global().disable();
+    }
+    Ok(())
+}
+
+/// Formats seen and parse failure rates per format, from the opt-in local
+/// statistics, plus the app version and platform.
+#[tauri::command]
+fn diagnostics_report() -> DiagnosticsReport {
+    usage_stats::global().report()
+}
+
+/// Limits every command to paths inside `roots`; an empty list lifts the limit.
+#[tauri::command]
+fn set_allowed_roots(
+    settings: State<'_, SettingsStore>,
+    roots: Vec<String>,
+) -> Result<AppSettings, String> {
+    path_scope::global().set_roots(&roots)?;
+    settings.update(|settings| settings.allowed_roots = roots)
+}
+
+/// Turns read-only mode on or off. While on, every command that changes files
+/// or the app's stores fails; previews and dry runs still work.
+#[tauri::command]
+fn set_read_only(settings: State<'_, SettingsStore>, enabled: bool) -> Result<AppSettings, String> {
+    settings.update(|settings| settings.read_only = enabled)
+}
+
+/// Chooses the keyword thesaurus keywords are checked against, or clears it.
+/// The file is parsed first so a broken list is refused up front.
+#[tauri::command]
+fn set_keyword_vocabulary(
+    settings: State<'_, SettingsStore>,
+    path: Option<String>,
+) -> Result<AppSettings, String> {
+    if let Some(path) = &path {
+        path_scope::check_all([path])?;
+        Vocabulary::load(Path::new(path))?;
+    }
+    settings.update(|settings| settings.keyword_vocabulary = path)
+}
 
-const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
-const SUPPORTED_IMAGE_EXTENSIONS: &[&str] = &[
-    "jpg", "jpeg", "png", "tif", "tiff", "webp", "heic", "heif", "avif", "bmp",
-];
+fn keyword_vocabulary(settings: &SettingsStore) -> Result<Vocabulary, String> {
+    let path = settings
+        .get()
+        .keyword_vocabulary
+        .ok_or_else(|| "No keyword vocabulary is set; choose one in the settings.".to_string())?;
+    Vocabulary::load(Path::new(&path))
+}
 
-#[derive(Debug, Serialize)]
-pub struct ExifField {
-    tag: String,
-    ifd: String,
-    value: String,
+/// Checks keywords being edited against the vocabulary, suggesting terms
+/// for the ones it does not contain.
+#[tauri::command]
+fn check_keywords(
+    settings: State<'_, SettingsStore>,
+    keywords: Vec<String>,
+) -> Result<Vec<KeywordCheck>, String> {
+    let vocabulary = keyword_vocabulary(&settings)?;
+    Ok(keywords
+        .iter()
+        .map(|keyword| vocabulary.check(keyword))
+        .collect())
 }
 
-#[derive(Debug, Serialize)]
-pub struct AestheticMatch {
+/// Lists the images below `folder` whose keywords fall outside the vocabulary.
+#[tauri::command]
+fn audit_keywords(
+    settings: State<'_, SettingsStore>,
+    folder: String,
+) -> Result<KeywordAudit, String> {
+    path_scope::check_all([&folder])?;
+    let root = PathBuf::from(&folder);
+    if !root.is_dir() {
+        return Err("The selected path is not a folder.".to_string());
+    }
+    Ok(vocabulary::audit(&root, &keyword_vocabulary(&settings)?))
+}
+
+/// How the metadata index opens at start: a keychain-backed index unlocks
+/// itself, a passphrase one waits for `unlock_index`.
+fn index_access(settings: &AppSettings) -> Access {
+    match settings.index_encryption {
+        IndexEncryption::Off => Access::Plain,
+        IndexEncryption::Passphrase => Access::Locked,
+        IndexEncryption::Keychain => index_crypto::keychain_secret(false)
+            .map(Access::Unlocked)
+            .unwrap_or_else(|error| {
+                tracing::warn!(%error, "could not unlock the metadata index");
+                Access::Locked
+            }),
+    }
+}
+
+/// Turns encryption of the metadata index on or off, or changes its
+/// passphrase. Enabling the keychain mode stores a new random key there.
+#[tauri::command]
+fn set_index_encryption(
+    settings: State<'_, SettingsStore>,
+    index: State<'_, MetadataIndex>,
+    mode: IndexEncryption,
+    passphrase: Option<String>,
+) -> Result<AppSettings, String> {
+    settings.ensure_writable()?;
+    let access = match mode {
+        IndexEncryption::Off => Access::Plain,
+        IndexEncryption::Passphrase => {
+            index_crypto::ensure_available()?;
+            match passphrase.filter(|passphrase| !passphrase.is_empty()) {
+                Some(passphrase) => Access::Unlocked(IndexSecret::Passphrase(passphrase)),
+                None => return Err("Enter a passphrase for the metadata index.".to_string()),
+            }
+        }
+        IndexEncryption::Keychain => {
+            index_crypto::ensure_available()?;
+            Access::Unlocked(index_crypto::keychain_secret(true)?)
+        }
+    };
+    index.set_encryption(access)?;
+    if mode != IndexEncryption::Keychain {
+        index_crypto::forget_keychain_secret()?;
+    }
+    settings.update(|settings| settings.index_encryption = mode)
+}
+
+/// Unlocks a passphrase-protected metadata index for this session.
+#[tauri::command]
+fn unlock_index(index: State<'_, MetadataIndex>, passphrase: String) -> Result<(), String> {
+    index_crypto::ensure_available()?;
+    index.unlock(IndexSecret::Passphrase(passphrase))
+}
+
+fn apply_scan_memory_limit(settings: &AppSettings) {
+    let megabytes = settings
+        .scan_memory_limit_mb
+        .unwrap_or(memory_budget::DEFAULT_LIMIT_MB);
+    memory_budget::global().set_limit(megabytes * 1024 * 1024);
+}
+
+/// Sets which of EXIF, IPTC and XMP wins for each logical field.
+#[tauri::command]
+fn set_source_priority(
+    settings: State<'_, SettingsStore>,
+    priority: SourcePriority,
+) -> Result<AppSettings, String> {
+    settings.update(|settings| settings.source_priority = priority)
+}
+
+/// Creator, description, date, copyright and keywords for `path`, each
+/// reconciled across EXIF, IPTC and XMP using the saved source priority.
+#[tauri::command]
+fn reconcile_metadata(
+    settings: State<'_, SettingsStore>,
     path: String,
-    score: f64,
+) -> Result<Vec<ReconciledField>, String> {
+    path_scope::check_all([&path])?;
+    let data = load_file_data(Path::new(&path))?;
+    Ok(mwg::reconcile(&data, &settings.get().source_priority))
 }
 
+/// The embedded ICC profile of `path`, summarized and with its bytes in
+/// base64 so the frontend can offer to save it.
 #[tauri::command]
-fn read_exif(path: String) -> Result<Vec<ExifField>, String> {
-    let path_buf = PathBuf::from(&path);
-    let data = load_file_data(&path_buf)?;
-    collect_fields_from_bytes(&data)
+fn get_icc_profile(path: String) -> Result<IccProfile, String> {
+    path_scope::check_all([&path])?;
+    let data = load_file_data(Path::new(&path))?;
+    icc::profile(&data)
 }
 
+/// Strips the APPn and COM segments `markers` name from the JPEG at `path`
+/// (e.g. `APP2 (MPF)`, `APP13`) without touching the compressed image. With
+/// `duplicates_only`, the first segment of each kind is kept.
 #[tauri::command]
-fn find_aesthetic_images(path: String, min_score: f64) -> Result<Vec<AestheticMatch>, String> {
-    if !min_score.is_finite() {
-        return Err("The minimum score must be a valid number.".to_string());
+fn remove_segments(
+    log: State<'_, OperationLog>,
+    settings: State<'_, SettingsStore>,
+    path: String,
+    markers: Vec<String>,
+    duplicates_only: Option<bool>,
+) -> Result<SegmentRemoval, String> {
+    settings.ensure_writable()?;
+    path_scope::check_all([&path])?;
+    let path = Path::new(&path);
+    let stamp = FileStamp::of(path)?;
+    let data = load_file_data(path)?;
+    let (updated, removal) =
+        jpeg::remove_segments(&data, &markers, duplicates_only.unwrap_or(false))?;
+    if removal.removed.is_empty() {
+        return Ok(removal);
     }
+    let changes = removal
+        .removed
+        .iter()
+        .map(|label| oplog::FieldChange {
+            tag: "JPEG segment".to_string(),
+            before: Some(label.clone()),
+            after: None,
+        })
+        .collect();
+    commit::commit_rewrite(
+        log.inner(),
+        settings.get().edit_mode,
+        "remove_segments",
+        path,
+        stamp,
+        &updated,
+        changes,
+    )
+    .map_err(|error| error.message())?;
+    Ok(removal)
+}
 
-    let root = PathBuf::from(&path);
-    if !root.exists() {
+/// Removes the metadata `options` pick from each of `paths`: EXIF, its GPS
+/// fields alone, XMP, IPTC and PNG text chunks. The files are replaced
+/// unless `options.outputFolder` is set, which receives cleaned copies.
+#[tauri::command]
+fn strip_metadata(
+    log: State<'_, OperationLog>,
+    settings: State<'_, SettingsStore>,
+    paths: Vec<String>,
+    options: StripOptions,
+) -> Result<Vec<MetadataStrip>, String> {
+    settings.ensure_writable()?;
+    if !options.any() {
+        return Err("Choose at least one kind of metadata to remove.".to_string());
+    }
+    path_scope::check_all(&paths)?;
+    if let Some(folder) = &options.output_folder {
+        path_scope::check_write(Path::new(folder)).map_err(|violation| violation.message())?;
+        fs::create_dir_all(folder)
+            .map_err(|error| format!("Unable to create {}: {}", folder, error))?;
+    }
+    let mode = settings.get().edit_mode;
+    Ok(paths
+        .iter()
+        .map(|path| strip::strip_file(Path::new(path), &options, log.inner(), mode))
+        .collect())
+}
+
+/// The preview embedded in the image at `path` (the EXIF thumbnail, or a
+/// HEIC/AVIF thumbnail item) as base64 with its size, so scan results can
+/// show previews without decoding full-resolution images.
+#[tauri::command]
+fn extract_thumbnail(path: String) -> Result<ExtractedThumbnail, String> {
+    path_scope::check_all([&path])?;
+    thumbnail::extract(&memory_budget::load_scan_data(Path::new(&path))?)
+}
+
+/// Replaces the EXIF thumbnail of the JPEG at `path` with one rendered
+/// from the image, fixing thumbnails that are missing, corrupt or stale
+/// after edits. `size` is the long side in pixels (160 by default) and
+/// `quality` the JPEG quality from 1 to 100 (80).
+#[tauri::command]
+fn rebuild_thumbnail(
+    log: State<'_, OperationLog>,
+    settings: State<'_, SettingsStore>,
+    path: String,
+    size: Option<u32>,
+    quality: Option<u8>,
+) -> Result<ThumbnailRebuild, String> {
+    settings.ensure_writable()?;
+    path_scope::check_all([&path])?;
+    let path = Path::new(&path);
+    let stamp = FileStamp::of(path)?;
+    let data = load_file_data(path)?;
+    let (updated, rebuild) = thumbnail::rebuild(
+        &data,
+        size.unwrap_or(thumbnail::DEFAULT_SIZE),
+        quality.unwrap_or(thumbnail::DEFAULT_QUALITY),
+    )?;
+    let change = oplog::FieldChange {
+        tag: "Thumbnail".to_string(),
+        before: rebuild
+            .previous_bytes
+            .map(|bytes| format!("{} bytes", bytes)),
+        after: Some(format!(
+            "{}x{}, {} bytes",
+            rebuild.width, rebuild.height, rebuild.bytes
+        )),
+    };
+    commit::commit_rewrite(
+        log.inner(),
+        settings.get().edit_mode,
+        "rebuild_thumbnail",
+        path,
+        stamp,
+        &updated,
+        vec![change],
+    )
+    .map_err(|error| error.message())?;
+    Ok(rebuild)
+}
+
+/// Container metadata of an MP4 or MOV video: creation time, duration,
+/// tracks with codec and size, `udta` and `keys`/`ilst` tags and the
+/// recorded location. Only the `moov` box is read, however large the video.
+#[tauri::command]
+fn read_video_metadata(path: String) -> Result<VideoMetadata, String> {
+    path_scope::check_all([&path])?;
+    video::read(Path::new(&path))
+}
+
+/// Summarizes the C2PA Content Credentials embedded in the file at `path`:
+/// the active manifest's generator, actions and signer. The structure is
+/// checked, but the signature is not cryptographically verified.
+#[tauri::command]
+fn read_content_credentials(path: String) -> Result<ContentCredentials, String> {
+    path_scope::check_all([&path])?;
+    c2pa::read(&load_file_data(Path::new(&path))?)
+}
+
+/// Lists the chunks of the PNG at `path` with their sizes, CRC status and,
+/// for text chunks, keywords.
+#[tauri::command]
+fn list_png_chunks(path: String) -> Result<Vec<PngChunkInfo>, String> {
+    path_scope::check_all([&path])?;
+    png_chunks::list(&load_file_data(Path::new(&path))?)
+}
+
+/// Rewrites the PNG at `path` with its chunks in `order`, given as indices
+/// from `list_png_chunks`. Ancillary chunks left out are removed; the image
+/// data is copied unchanged.
+#[tauri::command]
+fn rewrite_png_chunks(
+    log: State<'_, OperationLog>,
+    settings: State<'_, SettingsStore>,
+    path: String,
+    order: Vec<usize>,
+) -> Result<Vec<PngChunkInfo>, String> {
+    settings.ensure_writable()?;
+    path_scope::check_all([&path])?;
+    png_chunks::commit(
+        log.inner(),
+        settings.get().edit_mode,
+        "rewrite_png_chunks",
+        Path::new(&path),
+        |data| {
+            Ok((
+                png_chunks::rearrange(data, &order)?,
+                png_chunks::rearrange_changes(data, &order)?,
+            ))
+        },
+    )
+    .map_err(|error| error.message())
+}
+
+/// Recomputes every wrong chunk CRC of the PNG at `path`.
+#[tauri::command]
+fn repair_png_crcs(
+    log: State<'_, OperationLog>,
+    settings: State<'_, SettingsStore>,
+    path: String,
+) -> Result<Vec<PngChunkInfo>, String> {
+    settings.ensure_writable()?;
+    path_scope::check_all([&path])?;
+    png_chunks::commit(
+        log.inner(),
+        settings.get().edit_mode,
+        "repair_png_crcs",
+        Path::new(&path),
+        png_chunks::repair_changes,
+    )
+    .map_err(|error| error.message())
+}
+
+/// Lists every write the app has made to `path`, oldest first.
+#[tauri::command]
+fn get_operation_history(
+    log: State<'_, OperationLog>,
+    path: String,
+) -> Result<Vec<OperationRecord>, String> {
+    path_scope::check_all([&path])?;
+    log.history(Path::new(&path))
+}
+
+/// Times reading, parsing and serializing every image in `folder`, for
+/// comparing releases on a real library.
+#[tauri::command]
+fn benchmark(folder: String) -> Result<BenchmarkReport, String> {
+    path_scope::check_all([&folder])?;
+    let root = PathBuf::from(&folder);
+    if !root.is_dir() {
         return Err("The selected folder does not exist.".to_string());
     }
+    Ok(benchmark::run(&root))
+}
 
-    if root.is_file() {
-        return match analyze_file(&root, min_score)? {
-            Some(result) => Ok(vec![result]),
-            None => Ok(Vec::new()),
-        };
+/// Backend log records at `level` (default `info`) or more severe, written
+/// at or after `since` (Unix milliseconds), oldest first, for attaching to
+/// bug reports, or why logging could not start.
+#[tauri::command]
+fn get_logs(
+    app: AppHandle,
+    level: Option<LogLevel>,
+    since: Option<u64>,
+) -> Result<Vec<LogRecord>, String> {
+    let data_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|error| error.to_string())?;
+    if let Some(error) = logging::startup_error() {
+        return Err(error.to_string());
+    }
+    Ok(logging::read_logs(
+        &logging::directory(&data_dir),
+        level.unwrap_or(LogLevel::Info),
+        since,
+    ))
+}
+
+/// Optional features compiled into this build and what the platform
+/// supports, for the frontend to adapt its UI.
+#[tauri::command]
+fn get_capabilities() -> Capabilities {
+    capabilities::discover()
+}
+
+/// How a HEIC/HEIF file is coded and whether this system can preview it,
+/// with the embedded EXIF thumbnail as a fallback when it cannot. Metadata
+/// never needs the system decoder.
+#[tauri::command]
+fn get_heif_preview(path: String) -> Result<HeifPreview, String> {
+    path_scope::check_all([&path])?;
+    let data = load_file_data(Path::new(&path))?;
+    Ok(heif::preview(&data, heif::hevc_codec()))
+}
+
+/// Parses the fixture corpus bundled with the app and reports pass/fail per
+/// format, to tell a broken build apart from broken images.
+#[tauri::command]
+fn run_selftest() -> SelfTestReport {
+    selftest::run()
+}
+
+#[tauri::command]
+fn audit_lighting(folder: String) -> Result<LightingAudit, String> {
+    path_scope::check_all([&folder])?;
+    let root = PathBuf::from(&folder);
+    if !root.is_dir() {
+        return Err("The selected path is not a folder.".to_string());
+    }
+    lighting::audit_lighting(&root)
+}
+
+/// Groups `frame_paths` into bursts by capture time and ranks each burst's
+/// frames by sharpness, exposure and, if `criteria` weights it, aesthetic
+/// score, nominating a keeper for the UI to confirm. Nothing is changed.
+#[tauri::command]
+fn pick_best(
+    frame_paths: Vec<String>,
+    criteria: Option<PickCriteria>,
+) -> Result<Vec<BurstPick>, String> {
+    path_scope::check_all(&frame_paths)?;
+    let criteria = criteria.unwrap_or_default();
+    criteria.validate()?;
+    if frame_paths.is_empty() {
+        return Err("Select the frames of at least one burst.".to_string());
+    }
+    let paths: Vec<PathBuf> = frame_paths.iter().map(PathBuf::from).collect();
+    Ok(burst::pick_best(&paths, &criteria))
+}
+
+/// Lists the images below `folder` that EXIF rotates, whose pixels look
+/// already rotated (so viewers turn them twice), or whose XMP orientation
+/// disagrees with EXIF, each with the fix `fix_orientation` would apply.
+#[tauri::command]
+fn audit_orientation(folder: String) -> Result<OrientationAudit, String> {
+    path_scope::check_all([&folder])?;
+    let root = PathBuf::from(&folder);
+    if !root.is_dir() {
+        return Err("The selected path is not a folder.".to_string());
+    }
+    orientation::audit_orientation(&root)
+}
+
+/// Applies the audit's metadata fix to each JPEG in `paths`: resetting the
+/// tag of already-rotated pixels or copying EXIF orientation into XMP.
+/// Files whose pixels still need rotating are only reported.
+#[tauri::command]
+fn fix_orientation(
+    log: State<'_, OperationLog>,
+    store: State<'_, VirtualFieldStore>,
+    settings: State<'_, SettingsStore>,
+    paths: Vec<String>,
+    dry_run: Option<bool>,
+) -> Result<Vec<OrientationRepair>, String> {
+    let dry_run = dry_run.unwrap_or(false);
+    if !dry_run {
+        settings.ensure_writable()?;
     }
+    path_scope::check_all(&paths)?;
+    let targets = commit::EditTargets {
+        log: log.inner(),
+        external: store.inner(),
+        mode: settings.get().edit_mode,
+    };
+    store.batch(|| {
+        paths
+            .iter()
+            .map(|path| orientation::fix_file(Path::new(path), dry_run, &targets))
+            .collect()
+    })
+}
 
+/// Lists the images below `folder` that lack rights information a stock
+/// agency requires, or whose copies of it disagree. `required` defaults to
+/// copyright, creator, credit and usage terms.
+#[tauri::command]
+fn license_audit(
+    settings: State<'_, SettingsStore>,
+    folder: String,
+    required: Option<Vec<RightsField>>,
+) -> Result<LicenseAudit, String> {
+    path_scope::check_all([&folder])?;
+    let root = PathBuf::from(&folder);
     if !root.is_dir() {
         return Err("The selected path is not a folder.".to_string());
     }
+    let required = required.unwrap_or_else(|| license_audit::DEFAULT_REQUIRED.to_vec());
+    license_audit::license_audit(&root, &settings.get().source_priority, &required)
+}
 
-    let mut stack = vec![root];
-    let mut matches = Vec::new();
+/// Annotates photos with weather from a local CSV dataset. Results are kept
+/// as virtual fields in the app's store and never written into the files.
+#[tauri::command]
+fn enrich_weather(
+    store: State<'_, VirtualFieldStore>,
+    settings: State<'_, SettingsStore>,
+    paths: Vec<String>,
+    dataset: String,
+) -> Result<Vec<WeatherEnrichment>, String> {
+    settings.ensure_writable()?;
+    path_scope::check_all(paths.iter().chain([&dataset]))?;
+    let provider = CsvWeatherProvider::load(Path::new(&dataset))?;
+    store.batch(|| {
+        paths
+            .iter()
+            .map(|path| weather::enrich_file(Path::new(path), &provider, store.inner(), false))
+            .collect()
+    })
+}
+
+#[tauri::command]
+fn read_virtual_fields(
+    store: State<'_, VirtualFieldStore>,
+    path: String,
+) -> Result<Vec<ExifField>, String> {
+    path_scope::check_all([&path])?;
+    Ok(store
+        .fields_for(Path::new(&path))
+        .iter()
+        .map(|field| field.to_exif_field())
+        .collect())
+}
+
+/// Names an export after its folder and places it inside that folder.
+fn default_export_path(root: &Path, extension: &str) -> PathBuf {
+    let stem = root
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "export".to_string());
+    root.join(format!("{}.{}", stem, extension))
+}
+
+/// Visits every regular file below `root`, skipping unreadable entries.
+/// Only the originals of Photos libraries and the images of phone backups
+/// are visited.
+fn walk_files(root: &Path, visit: &mut dyn FnMut(&Path)) {
+    let mut stack = vec![root.to_path_buf()];
 
     while let Some(dir) = stack.pop() {
+        let dir = apple_photos::originals_folder(&dir).unwrap_or(dir);
+        if phone_backup::is_backup(&dir) {
+            match phone_backup::list(&dir) {
+                Ok(backup) => backup
+                    .photos
+                    .iter()
+                    .for_each(|photo| visit(Path::new(&photo.path))),
+                Err(error) => tracing::warn!(%error, "could not list a phone backup"),
+            }
+            continue;
+        }
         let entries = match fs::read_dir(&dir) {
             Ok(entries) => entries,
             Err(_) => continue,
@@ -79,19 +2346,10 @@ fn find_aesthetic_images(path: String, min_score: f64) -> Result<Vec<AestheticMa
             if file_type.is_dir() {
                 stack.push(path);
             } else if file_type.is_file() {
-                if let Ok(Some(result)) = analyze_file(&path, min_score) {
-                    matches.push(result);
-                }
+                visit(&path);
             }
         }
     }
-
-    matches.sort_by(|a, b| match b.score.partial_cmp(&a.score) {
-        Some(ordering) => ordering,
-        None => Ordering::Equal,
-    });
-
-    Ok(matches)
 }
 
 fn parse_png_text_chunks(data: &[u8]) -> Vec<ExifField> {
@@ -261,24 +2519,175 @@ fn decode_latin1(bytes: &[u8]) -> String {
 }
 
 fn load_file_data(path: &Path) -> Result<Vec<u8>, String> {
-    let mut file = File::open(path).map_err(|error| error.to_string())?;
-    let mut data = Vec::new();
-    file.read_to_end(&mut data)
-        .map_err(|error| error.to_string())?;
-    Ok(data)
+    Ok(retry::read_file(path, &retry::READ_POLICY)?)
+}
+
+/// Canonical path string so stores find a file regardless of how it was named.
+fn path_key(path: &Path) -> String {
+    fs::canonicalize(path)
+        .unwrap_or_else(|_| path.to_path_buf())
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Replaces `path` with `contents` via a sibling temporary file so a failed
+/// write never leaves a half-written image behind.
+fn write_file_atomically(path: &Path, contents: &[u8]) -> Result<(), String> {
+    replace_file(path, contents).map_err(|error| error.to_string())
+}
+
+/// Writes a temporary sibling and renames it over `path`, so readers never
+/// see a half-written file.
+fn replace_file(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let file_name = path.file_name().ok_or_else(|| {
+        std::io::Error::new(ErrorKind::InvalidInput, "The selected path is not a file.")
+    })?;
+    let temporary =
+        path.with_file_name(format!(".{}.exif-viewer.tmp", file_name.to_string_lossy()));
+
+    fs::write(&temporary, contents)?;
+    fs::rename(&temporary, path).inspect_err(|_| {
+        fs::remove_file(&temporary).ok();
+    })
+}
+
+fn read_exif_container(data: &[u8]) -> Option<Exif> {
+    read_container_exif(data).ok()
+}
+
+/// Reads the EXIF of any supported container. WebP goes through our own
+/// chunk walker, which is more forgiving of encoder quirks than the reader's.
+fn read_container_exif(data: &[u8]) -> Result<Exif, ExifError> {
+    if let Some(tiff) = webp::exif(data) {
+        return Reader::new().read_raw(tiff.to_vec());
+    }
+    if let Some(tiff) = heif::exif(data) {
+        return Reader::new().read_raw(tiff);
+    }
+    Reader::new().read_from_container(&mut Cursor::new(raw::container(data)))
+}
+
+/// The bytes of `path` the metadata readers need: the whole local file, or
+/// the header of a `remote://` object.
+fn load_metadata_data(path: &Path) -> Result<Vec<u8>, String> {
+    match remote::Location::parse(&path.to_string_lossy()) {
+        Some(location) => Ok(remote::read_metadata(
+            location.backend()?.as_ref(),
+            &location.key,
+        )?),
+        None => load_file_data(path),
+    }
+}
+
+fn read_file_fields(
+    path: &Path,
+    style: FormatStyle,
+    merge: MergePolicy,
+    selection: &FieldSelection,
+) -> Result<Vec<ExifField>, String> {
+    let data = load_metadata_data(path).inspect_err(|error| {
+        tracing::warn!(file = %logging::file_name(path), %error, "could not read file");
+    })?;
+    let fields = isolation::collect_fields(path, &data, style, selection);
+    usage_stats::global().record(path, fields.is_ok());
+    let fields = fields.inspect_err(|error| {
+        tracing::warn!(file = %logging::file_name(path), %error, "could not parse metadata");
+    })?;
+    let mut fields = merge::apply(fields, merge);
+    // Parsed at most once, for the groups below that compare against EXIF.
+    let exif = std::cell::OnceCell::new();
+    let exif = || exif.get_or_init(|| read_exif_container(&data)).as_ref();
+    if selection.may_want_group(sidecar::COMPUTED_SIDECAR_IFD) {
+        fields.extend(
+            sidecar::computed_fields(path)
+                .into_iter()
+                .chain(sidecar::xmp_fields(path, &data, exif()))
+                .filter(|field| selection.wants(&field.ifd, &field.tag)),
+        );
+    }
+    if selection.may_want_group(phone_backup::COMPUTED_BACKUP_IFD) {
+        fields.extend(
+            phone_backup::computed_fields(phone_backup::listed_photo(path).as_ref())
+                .into_iter()
+                .filter(|field| selection.wants(&field.ifd, &field.tag)),
+        );
+    }
+    if selection.may_want_group(takeout::COMPUTED_TAKEOUT_IFD) {
+        fields.extend(
+            takeout::computed_fields(takeout::read(path).as_ref())
+                .into_iter()
+                .filter(|field| selection.wants(&field.ifd, &field.tag)),
+        );
+    }
+    if selection.may_want_group(film_roll::COMPUTED_FILM_IFD) {
+        fields.extend(
+            film_roll::computed_fields(film_roll::read(path, &data).as_ref())
+                .into_iter()
+                .filter(|field| selection.wants(&field.ifd, &field.tag)),
+        );
+    }
+    if geocode::enabled() && selection.may_want_group(geocode::COMPUTED_PLACE_IFD) {
+        let place = exif().and_then(geocode::place_of);
+        fields.extend(
+            geocode::computed_fields(place.as_ref())
+                .into_iter()
+                .filter(|field| selection.wants(&field.ifd, &field.tag)),
+        );
+    }
+    field_order::sort_canonical(&mut fields);
+    Ok(fields)
 }
 
 fn collect_fields_from_bytes(data: &[u8]) -> Result<Vec<ExifField>, String> {
+    collect_fields_with_style(data, FormatStyle::default())
+}
+
+fn collect_fields_with_style(data: &[u8], style: FormatStyle) -> Result<Vec<ExifField>, String> {
+    collect_selected_fields(data, style, &FieldSelection::default())
+}
+
+/// Like [`collect_fields_with_style`], but fields outside `selection` are
+/// skipped before they are formatted and groups nobody asked for are not
+/// computed at all.
+fn collect_selected_fields(
+    data: &[u8],
+    style: FormatStyle,
+    selection: &FieldSelection,
+) -> Result<Vec<ExifField>, String> {
+    let container = raw::container(data);
+    let data = container.as_ref();
+    let formatter = style.formatter();
     let mut fields: Vec<ExifField> = Vec::new();
     {
-        let mut cursor = Cursor::new(&data[..]);
-        match Reader::new().read_from_container(&mut cursor) {
+        match read_container_exif(data) {
             Ok(exif) => {
-                fields.extend(exif.fields().map(|field| ExifField {
-                    tag: field.tag.to_string(),
-                    ifd: format!("{:?}", field.ifd_num),
-                    value: field.display_value().with_unit(&exif).to_string(),
-                }));
+                fields.extend(
+                    exif.fields()
+                        .filter(|field| selection.wants_exif(field))
+                        .map(|field| ExifField {
+                            tag: field.tag.to_string(),
+                            ifd: format!("{:?}", field.ifd_num),
+                            value: formatter.format(field, &exif),
+                        }),
+                );
+                if selection.may_want_group(gps::COMPUTED_GPS_IFD) {
+                    fields.extend(gps::computed_fields(&gps::GpsReading::from_exif(&exif)));
+                }
+                if selection.may_want_group(sun::COMPUTED_SUN_IFD) {
+                    fields.extend(sun::computed_fields(&exif));
+                }
+                if selection.may_want_group(dof::COMPUTED_DOF_IFD) {
+                    fields.extend(dof::computed_fields(&exif));
+                }
+                if selection.may_want_group(dng::COMPUTED_DNG_IFD) {
+                    fields.extend(dng::computed_fields(&exif));
+                }
+                if selection.may_want_group(dng::APPLE_IFD) {
+                    fields.extend(dng::apple_fields(&exif));
+                }
+                if selection.may_want_group(scan_source::COMPUTED_SCAN_IFD) {
+                    fields.extend(scan_source::computed_fields(&exif, data));
+                }
             }
             Err(ExifError::NotFound(_)) => {}
             Err(ExifError::InvalidFormat(message)) => {
@@ -301,37 +2710,134 @@ fn collect_fields_from_bytes(data: &[u8]) -> Result<Vec<ExifField>, String> {
         }
     }
 
-    fields.extend(parse_png_text_chunks(data));
+    if ["PNG tEXt", "PNG zTXt", "PNG iTXt"]
+        .iter()
+        .any(|group| selection.may_want_group(group))
+    {
+        fields.extend(parse_png_text_chunks(data));
+    }
+    if selection.may_want_group(generation::GENERATION_IFD) {
+        fields.extend(generation::fields(data));
+    }
+    if selection.may_want_group(merge::XMP_IFD) || selection.may_want_group(merge::IPTC_IFD) {
+        fields.extend(merge::embedded_fields(data));
+    }
+    if selection.may_want_group(iptc_ext::IPTC_EXT_IFD) {
+        fields.extend(iptc_ext::fields(data));
+    }
+    if selection.may_want_group(jpeg::JPEG_IFD) {
+        fields.extend(jpeg::fields(data));
+    }
+    if selection.may_want_group(icc::ICC_IFD) {
+        fields.extend(icc::fields(data));
+    }
+    if selection.may_want_group(vendor_trailer::VENDOR_IFD) {
+        fields.extend(vendor_trailer::fields(data));
+    }
+    fields.retain(|field| selection.wants(&field.ifd, &field.tag));
 
-    fields.sort_by(|a, b| match a.ifd.cmp(&b.ifd) {
-        Ordering::Equal => a.tag.cmp(&b.tag),
-        other => other,
-    });
+    field_order::sort_canonical(&mut fields);
 
     Ok(fields)
 }
 
-fn analyze_file(path: &Path, min_score: f64) -> Result<Option<AestheticMatch>, String> {
+fn analyze_file(
+    path: &Path,
+    min_score: f64,
+    filters: &ScanFilters,
+    selection: Option<&FieldSelection>,
+) -> Result<Option<AestheticMatch>, ReadError> {
     if !is_supported_image(path) {
         return Ok(None);
     }
+    // Before the prefilter, whose peek at the header would start a download.
+    placeholder::check(path)?;
+    if !prefilter::may_hold_score(path) {
+        return Ok(None);
+    }
 
-    let data = load_file_data(path)?;
-    let fields = match collect_fields_from_bytes(&data) {
+    let data = memory_budget::load_scan_data(path)?;
+    Ok(analyze_data(path, &data, min_score, filters, selection))
+}
+
+/// [`analyze_file`] for an object in a remote library, read as `path`. A
+/// failed request comes back as the reason to list it as pending.
+fn analyze_remote(
+    backend: &dyn remote::StorageBackend,
+    path: &Path,
+    object: &remote::RemoteObject,
+    min_score: f64,
+    filters: &ScanFilters,
+    selection: Option<&FieldSelection>,
+) -> Result<Option<AestheticMatch>, ReadError> {
+    if !remote::may_hold_score(backend, &object.key) {
+        return Ok(None);
+    }
+    let data = memory_budget::load_remote_scan_data(backend, object)?;
+    Ok(analyze_data(path, &data, min_score, filters, selection))
+}
+
+/// The match for an image already in memory, if it scores high enough.
+fn analyze_data(
+    path: &Path,
+    data: &[u8],
+    min_score: f64,
+    filters: &ScanFilters,
+    selection: Option<&FieldSelection>,
+) -> Option<AestheticMatch> {
+    let fields = isolation::collect_fields(
+        path,
+        data,
+        FormatStyle::default(),
+        &FieldSelection::default(),
+    );
+    usage_stats::global().record(path, fields.is_ok());
+    let fields = match fields {
         Ok(fields) => fields,
-        Err(_) => return Ok(None),
+        Err(error) => {
+            tracing::debug!(file = %logging::file_name(path), %error, "skipping unparsable file in scan");
+            return None;
+        }
     };
 
     if let Some(score) = extract_aesthetic_score(&fields) {
-        if score >= min_score {
-            return Ok(Some(AestheticMatch {
+        if score >= min_score && filters.matches(data) {
+            let exif = read_exif_container(data);
+            let capture = exif.as_ref().and_then(capture::capture_time);
+            let place = exif
+                .as_ref()
+                .filter(|_| geocode::enabled())
+                .and_then(geocode::place_of);
+            let film = film_roll::read(path, data).unwrap_or_default();
+            return Some(AestheticMatch {
                 path: path.to_string_lossy().into_owned(),
                 score,
-            }));
+                sidecar: sidecar::find_sidecars(path)
+                    .first()
+                    .map(|sidecar| sidecar.path.to_string_lossy().into_owned()),
+                fields: selection.map(|selection| {
+                    FieldList(
+                        fields
+                            .into_iter()
+                            .filter(|field| selection.wants(&field.ifd, &field.tag))
+                            .collect(),
+                    )
+                }),
+                captured: capture.map(CaptureTime::to_iso8601),
+                captured_from: capture.map(|_| DateSource::Metadata),
+                day: None,
+                roll: film.roll,
+                frame: film.frame,
+                place: place.as_ref().map(geocode::Place::label),
+                companions: Vec::new(),
+                status: MatchStatus::Read,
+                unavailable: None,
+                capture,
+            });
         }
     }
 
-    Ok(None)
+    None
 }
 
 fn is_supported_image(path: &Path) -> bool {
@@ -343,7 +2849,7 @@ fn is_supported_image(path: &Path) -> bool {
                 .iter()
                 .any(|candidate| *candidate == lower)
         })
-        .unwrap_or(false)
+        .unwrap_or_else(|| phone_backup::listed_photo(path).is_some())
 }
 
 fn extract_aesthetic_score(fields: &[ExifField]) -> Option<f64> {
@@ -367,11 +2873,250 @@ fn parse_score_value(value: &str) -> Option<f64> {
         .find(|score| score.is_finite())
 }
 
+/// Handles a second launch of the app: its paths join the pending launch
+/// paths, the frontend is told to collect them, and the existing window is
+/// brought forward in place of a new instance.
+#[cfg(desktop)]
+fn forward_launch(app: &AppHandle, args: Vec<String>, cwd: String) {
+    let paths = shell_integration::paths_from_args(args, Path::new(&cwd));
+    if !paths.is_empty() {
+        app.state::<LaunchPaths>().push(paths);
+        let _ = app.emit("launch-paths", ());
+    }
+    focus_main_window(app);
+}
+
+#[cfg(desktop)]
+fn focus_main_window(app: &AppHandle) {
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.unminimize();
+        let _ = window.show();
+        let _ = window.set_focus();
+    }
+}
+
+/// Carries out an `exifviewer://` link. Opened paths go through the same
+/// queue as launch arguments; scans run off the main thread and report back
+/// through a `linked-scan` event.
+#[cfg(desktop)]
+fn handle_deep_link(app: &AppHandle, link: &str) {
+    match deep_link::parse(link) {
+        Ok(DeepLink::Open { path }) => {
+            app.state::<LaunchPaths>().push(vec![path]);
+            let _ = app.emit("launch-paths", ());
+        }
+        Ok(DeepLink::Scan { folder, min_score }) => {
+            let app = app.clone();
+            std::thread::spawn(move || {
+                let folder = folder.to_string_lossy().into_owned();
+                let result =
+                    find_aesthetic_images(folder.clone(), min_score, None, None, None, None, None);
+                let (matches, error) = match result {
+                    Ok(matches) => (matches, None),
+                    Err(error) => (Vec::new(), Some(error)),
+                };
+                let _ = app.emit(
+                    "linked-scan",
+                    LinkedScan {
+                        folder,
+                        min_score,
+                        matches,
+                        error,
+                    },
+                );
+            });
+        }
+        Err(error) => {
+            let _ = app.emit("deep-link-error", error);
+        }
+    }
+    focus_main_window(app);
+}
+
+#[cfg_attr(mobile, tauri::mobile_entry_point)]
+pub fn run() {
+    if let Some(code) = isolation::worker_main() {
+        std::process::exit(code);
+    }
+    let mut builder = tauri::Builder::default();
+    // Must be the first plugin so a second instance exits before doing any work.
+    #[cfg(desktop)]
+    {
+        builder = builder
+            .plugin(tauri_plugin_single_instance::init(forward_launch))
+            .plugin(tauri_plugin_deep_link::init());
+    }
+    builder
+        .on_window_event(handle_window_event)
+        .plugin(tauri_plugin_dialog::init())
+        .plugin(tauri_plugin_opener::init())
+        .plugin(tauri_plugin_clipboard_manager::init())
+        .setup(|app| {
+            let data_dir = app.path().app_data_dir()?;
+            if let Err(error) = logging::init(&logging::directory(&data_dir)) {
+                use tauri_plugin_dialog::{DialogExt, MessageDialogKind};
+                app.dialog()
+                    .message(format!("{}. Diagnostics will not be recorded.", error))
+                    .title("Logging unavailable")
+                    .kind(MessageDialogKind::Warning)
+                    .show(|_| {});
+            }
+            tracing::info!(version = env!("CARGO_PKG_VERSION"), "starting");
+            app.manage(VirtualFieldStore::open(
+                data_dir.join("virtual-fields.json"),
+            ));
+            app.manage(OperationLog::open(data_dir.join("operations.jsonl")));
+            let settings = SettingsStore::open(data_dir.join("settings.json"));
+            app.manage(MetadataIndex::open(
+                data_dir.join(metadata_index::FILE_NAME),
+                index_access(&settings.get()),
+            ));
+            apply_scan_memory_limit(&settings.get());
+            isolation::set_mode(settings.get().parse_isolation);
+            placeholder::set_policy(settings.get().cloud_placeholders);
+            geocode::set_enabled(settings.get().reverse_geocoding);
+            path_scope::global().restore(&settings.get().allowed_roots);
+            apply_usage_stats(app.handle(), &settings.get())?;
+            app.manage(settings);
+            app.manage(PipelineStore::open(data_dir.join("pipelines.json")));
+            remote::open_libraries(data_dir.join("remote-libraries.json"));
+            app.manage(QuickScan::default());
+            app.manage(FieldSearchCache::default());
+            app.manage(ScanResults::<AestheticMatch>::default());
+            app.manage(WindowSessions::default());
+            app.manage(SessionJournal::open(data_dir.join("session.json")));
+            let cwd = std::env::current_dir().unwrap_or_default();
+            app.manage(LaunchPaths::new(shell_integration::paths_from_args(
+                std::env::args(),
+                &cwd,
+            )));
+
+            #[cfg(desktop)]
+            {
+                use tauri_plugin_deep_link::DeepLinkExt;
+                // Installed builds register the scheme from the bundle config;
+                // development builds have to do it at runtime.
+                #[cfg(all(debug_assertions, any(windows, target_os = "linux")))]
+                app.deep_link().register_all()?;
+                let handle = app.handle().clone();
+                app.deep_link().on_open_url(move |event| {
+                    for url in event.urls() {
+                        handle_deep_link(&handle, url.as_str());
+                    }
+                });
+                for url in app.deep_link().get_current()?.unwrap_or_default() {
+                    handle_deep_link(app.handle(), url.as_str());
+                }
+                tray::install(app)?;
+                quick_inspect::install(app)?;
+            }
+            Ok(())
+        })
+        .invoke_handler(tauri::generate_handler![
+            read_exif,
+            find_field,
+            find_aesthetic_images,
+            start_aesthetic_scan,
+            continue_scan,
+            release_scan,
+            stream_aesthetic_images,
+            read_gps,
+            export_track,
+            export_geo,
+            export_html_report,
+            export_comfyui_workflow,
+            export_diff_report,
+            dump_text,
+            copy_fields_to_clipboard,
+            export_markdown,
+            extract_vendor_block,
+            lightroom_cross_reference,
+            list_photos_library,
+            list_phone_backup,
+            check_timezones,
+            fix_timezones_from_gps,
+            shift_timestamps,
+            geotag_from_gpx,
+            import_takeout,
+            import_social_export,
+            enrich_weather,
+            read_virtual_fields,
+            audit_lighting,
+            license_audit,
+            audit_orientation,
+            pick_best,
+            fix_orientation,
+            benchmark,
+            get_logs,
+            run_selftest,
+            get_capabilities,
+            get_heif_preview,
+            get_operation_history,
+            get_settings,
+            set_edit_mode,
+            set_source_priority,
+            set_scan_memory_limit,
+            set_parse_isolation,
+            set_cloud_placeholders,
+            set_reverse_geocoding,
+            set_usage_stats,
+            set_allowed_roots,
+            set_read_only,
+            set_keyword_vocabulary,
+            set_captioner,
+            write_exif,
+            generate_captions,
+            infer_events,
+            search_events,
+            relocate_files,
+            find_orphans,
+            storage_report,
+            index_folder,
+            query_index,
+            verify_index,
+            set_index_encryption,
+            unlock_index,
+            check_keywords,
+            audit_keywords,
+            diagnostics_report,
+            reconcile_metadata,
+            get_icc_profile,
+            remove_segments,
+            strip_metadata,
+            extract_thumbnail,
+            rebuild_thumbnail,
+            read_video_metadata,
+            read_content_credentials,
+            list_png_chunks,
+            rewrite_png_chunks,
+            repair_png_crcs,
+            resolve_write_problem,
+            list_pipelines,
+            save_pipeline,
+            delete_pipeline,
+            list_remote_libraries,
+            save_remote_library,
+            delete_remote_library,
+            run_pipeline,
+            take_launch_paths,
+            open_window,
+            get_window_session,
+            restore_session,
+            set_window_file,
+            get_context_menu_status,
+            register_context_menu,
+            unregister_context_menu,
+            get_last_quick_scan
+        ])
+        .run(tauri::generate_context!())
+        .expect("error while running tauri application");
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use flate2::{write::ZlibEncoder, Compression};
-    use std::io::Write;
+    use exif::{In, Tag};
+    use fixtures::{check_property, JpegBuilder, PngBuilder};
 
     fn fixture_path(relative: &str) -> String {
         std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
@@ -382,134 +3127,123 @@ mod tests {
     }
 
     fn build_png_with_text_chunks() -> Vec<u8> {
-        fn png_chunk(kind: &[u8; 4], payload: &[u8]) -> Vec<u8> {
-            let mut chunk = Vec::new();
-            chunk.extend_from_slice(&(payload.len() as u32).to_be_bytes());
-            chunk.extend_from_slice(kind);
-            chunk.extend_from_slice(payload);
-            chunk.extend_from_slice(&[0, 0, 0, 0]);
-            chunk
-        }
-
-        let mut data = Vec::new();
-        data.extend_from_slice(&PNG_SIGNATURE);
-
-        let mut ihdr = Vec::new();
-        ihdr.extend_from_slice(&1u32.to_be_bytes());
-        ihdr.extend_from_slice(&1u32.to_be_bytes());
-        ihdr.push(8);
-        ihdr.push(2);
-        ihdr.push(0);
-        ihdr.push(0);
-        ihdr.push(0);
-        data.extend(png_chunk(b"IHDR", &ihdr));
-
-        data.extend(png_chunk(b"tEXt", b"Software\0Test App"));
-
-        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
-        encoder.write_all(b"Compressed note").unwrap();
-        let compressed = encoder.finish().unwrap();
-        let mut ztxt_payload = Vec::new();
-        ztxt_payload.extend_from_slice(b"Comment");
-        ztxt_payload.push(0);
-        ztxt_payload.push(0);
-        ztxt_payload.extend_from_slice(&compressed);
-        data.extend(png_chunk(b"zTXt", &ztxt_payload));
-
-        let mut itxt_payload = Vec::new();
-        itxt_payload.extend_from_slice(b"Description");
-        itxt_payload.push(0);
-        itxt_payload.push(0);
-        itxt_payload.push(0);
-        itxt_payload.extend_from_slice(b"en");
-        itxt_payload.push(0);
-        itxt_payload.extend_from_slice(b"Beschreibung");
-        itxt_payload.push(0);
-        itxt_payload.extend_from_slice(b"International text");
-        data.extend(png_chunk(b"iTXt", &itxt_payload));
-
-        data.extend(png_chunk(b"IEND", &[]));
-        data
+        PngBuilder::new()
+            .header(1, 1)
+            .text("Software", "Test App")
+            .compressed_text("Comment", "Compressed note")
+            .international_text(
+                "Description",
+                "en",
+                "Beschreibung",
+                "International text",
+                false,
+            )
+            .finish()
     }
 
     fn build_png_without_metadata() -> Vec<u8> {
-        fn png_chunk(kind: &[u8; 4], payload: &[u8]) -> Vec<u8> {
-            let mut chunk = Vec::new();
-            chunk.extend_from_slice(&(payload.len() as u32).to_be_bytes());
-            chunk.extend_from_slice(kind);
-            chunk.extend_from_slice(payload);
-            chunk.extend_from_slice(&[0, 0, 0, 0]);
-            chunk
-        }
-
-        let mut data = Vec::new();
-        data.extend_from_slice(&PNG_SIGNATURE);
+        PngBuilder::new()
+            .header(1, 1)
+            // Minimal single-pixel IDAT payload.
+            .chunk(
+                b"IDAT",
+                &[0x78, 0x9c, 0x63, 0x00, 0x00, 0x00, 0x02, 0x00, 0x01],
+            )
+            .finish()
+    }
 
-        let mut ihdr = Vec::new();
-        ihdr.extend_from_slice(&1u32.to_be_bytes());
-        ihdr.extend_from_slice(&1u32.to_be_bytes());
-        ihdr.push(8);
-        ihdr.push(2);
-        ihdr.push(0);
-        ihdr.push(0);
-        ihdr.push(0);
-        data.extend(png_chunk(b"IHDR", &ihdr));
+    fn build_png_with_aesthetic_score(score: &str) -> Vec<u8> {
+        PngBuilder::new()
+            .header(1, 1)
+            .text("Aesthetic score", score)
+            .finish()
+    }
 
-        // Minimal single-pixel IDAT payload.
-        data.extend(png_chunk(b"IDAT", &[0x78, 0x9c, 0x63, 0x00, 0x00, 0x00, 0x02, 0x00, 0x01]));
+    #[test]
+    fn png_text_chunks_parse_in_any_layout() {
+        check_property(64, |rng| {
+            let mut png = PngBuilder::new().header(1, 1);
+            let mut expected = Vec::new();
+            for _ in 0..rng.below(8) {
+                let (keyword, value) = (rng.text(79), rng.text(200));
+                let ifd = match rng.below(4) {
+                    0 => {
+                        png = png.text(&keyword, &value);
+                        "PNG tEXt"
+                    }
+                    1 => {
+                        png = png.compressed_text(&keyword, &value);
+                        "PNG zTXt"
+                    }
+                    2 => {
+                        let compress = rng.below(2) == 1;
+                        png = png.international_text(&keyword, "", "", &value, compress);
+                        "PNG iTXt"
+                    }
+                    _ => {
+                        let len = rng.below(64);
+                        png = png.chunk(b"prVt", &rng.bytes(len));
+                        continue;
+                    }
+                };
+                expected.push((ifd.to_string(), keyword, value));
+            }
+            let parsed: Vec<(String, String, String)> = parse_png_text_chunks(&png.finish())
+                .into_iter()
+                .map(|field| (field.ifd, field.tag, field.value))
+                .collect();
+            assert_eq!(parsed, expected);
+        });
+    }
 
-        data.extend(png_chunk(b"IEND", &[]));
-        data
+    #[test]
+    fn exif_is_found_among_any_other_segments() {
+        check_property(64, |rng| {
+            let model = rng.text(30);
+            let mut jpeg = JpegBuilder::new();
+            let exif_at = rng.below(6);
+            for position in 0..6 {
+                if position == exif_at {
+                    jpeg = jpeg.exif(&[fixtures::ascii_field(Tag::Model, &model)]);
+                }
+                let len = rng.below(128);
+                jpeg = jpeg.segment(jpeg::APP0 + 2 + rng.below(14) as u8, &rng.bytes(len));
+            }
+            let exif = read_exif_container(&jpeg.finish()).expect("EXIF should be found");
+            let field = exif.get_field(Tag::Model, In::PRIMARY).unwrap();
+            assert!(
+                matches!(&field.value, exif::Value::Ascii(text) if text[0] == model.as_bytes())
+            );
+        });
     }
 
-    fn build_png_with_aesthetic_score(score: &str) -> Vec<u8> {
-        fn png_chunk(kind: &[u8; 4], payload: &[u8]) -> Vec<u8> {
-            let mut chunk = Vec::new();
-            chunk.extend_from_slice(&(payload.len() as u32).to_be_bytes());
-            chunk.extend_from_slice(kind);
-            chunk.extend_from_slice(payload);
-            chunk.extend_from_slice(&[0, 0, 0, 0]);
-            chunk
-        }
-
-        let mut data = Vec::new();
-        data.extend_from_slice(&PNG_SIGNATURE);
-
-        let mut ihdr = Vec::new();
-        ihdr.extend_from_slice(&1u32.to_be_bytes());
-        ihdr.extend_from_slice(&1u32.to_be_bytes());
-        ihdr.push(8);
-        ihdr.push(2);
-        ihdr.push(0);
-        ihdr.push(0);
-        ihdr.push(0);
-        data.extend(png_chunk(b"IHDR", &ihdr));
-
-        let mut payload = Vec::new();
-        payload.extend_from_slice(b"Aesthetic score");
-        payload.push(0);
-        payload.extend_from_slice(score.as_bytes());
-        data.extend(png_chunk(b"tEXt", &payload));
-
-        data.extend(png_chunk(b"IEND", &[]));
-        data
+    #[test]
+    fn truncated_files_never_panic() {
+        check_property(32, |rng| {
+            let fields = [fixtures::ascii_field(Tag::Model, &rng.text(30))];
+            let jpeg = JpegBuilder::new()
+                .exif(&fields)
+                .xmp("<x:xmpmeta/>")
+                .finish();
+            let png = PngBuilder::new()
+                .header(1, 1)
+                .exif(&fields)
+                .compressed_text("Comment", &rng.text(100))
+                .finish();
+            for data in [jpeg, png] {
+                // Any result will do, as long as the parser returns one.
+                let _ = collect_fields_from_bytes(&data[..rng.below(data.len())]);
+            }
+        });
     }
 
     #[test]
     fn png_without_exif_returns_empty_result() {
         let png = build_png_without_metadata();
-        let mut path = std::env::temp_dir();
-        path.push(format!(
-            "exif_viewer_png_empty_{}_{}",
-            std::process::id(),
-            std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_nanos()
-        ));
+        let path = crate::fixtures::temp_path("png_empty");
         std::fs::write(&path, &png).expect("should write PNG fixture without metadata");
 
-        let fields = read_exif(path.to_string_lossy().into_owned())
+        let fields = read_exif(path.to_string_lossy().into_owned(), None, None, None)
             .expect("PNG without metadata should return an empty result");
 
         std::fs::remove_file(&path).ok();
@@ -519,7 +3253,7 @@ mod tests {
 
     #[test]
     fn unsupported_format_returns_friendly_error() {
-        let error = read_exif(fixture_path("README.md"))
+        let error = read_exif(fixture_path("README.md"), None, None, None)
             .expect_err("Non-image files should not produce EXIF data");
         assert_eq!(error, "The selected file format is not supported.");
     }
@@ -527,18 +3261,10 @@ mod tests {
     #[test]
     fn png_text_chunks_are_exposed_as_metadata() {
         let png = build_png_with_text_chunks();
-        let mut path = std::env::temp_dir();
-        path.push(format!(
-            "exif_viewer_png_text_{}_{}.png",
-            std::process::id(),
-            std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_nanos()
-        ));
+        let path = crate::fixtures::temp_path("png_text.png");
         std::fs::write(&path, &png).expect("should write PNG fixture");
 
-        let fields = read_exif(path.to_string_lossy().into_owned())
+        let fields = read_exif(path.to_string_lossy().into_owned(), None, None, None)
             .expect("PNG text chunks should be parsed");
 
         std::fs::remove_file(&path).ok();
@@ -570,15 +3296,7 @@ mod tests {
 
     #[test]
     fn folder_scan_filters_by_aesthetic_score() {
-        let mut dir = std::env::temp_dir();
-        dir.push(format!(
-            "exif_viewer_aesthetic_scan_{}_{}",
-            std::process::id(),
-            std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_nanos()
-        ));
+        let dir = crate::fixtures::temp_path("aesthetic_scan");
         std::fs::create_dir_all(&dir).expect("should create temporary directory");
 
         let high_path = dir.join("high.png");
@@ -589,8 +3307,16 @@ mod tests {
         std::fs::write(&low_path, build_png_with_aesthetic_score("0.25"))
             .expect("should write low score PNG");
 
-        let results = find_aesthetic_images(dir.to_string_lossy().into_owned(), 0.5)
-            .expect("folder scan should succeed");
+        let results = find_aesthetic_images(
+            dir.to_string_lossy().into_owned(),
+            0.5,
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+        .expect("folder scan should succeed");
 
         std::fs::remove_dir_all(&dir).ok();
 
@@ -600,13 +3326,3 @@ mod tests {
         assert!((result.score - 0.82).abs() < f64::EPSILON);
     }
 }
-
-#[cfg_attr(mobile, tauri::mobile_entry_point)]
-pub fn run() {
-    tauri::Builder::default()
-        .plugin(tauri_plugin_dialog::init())
-        .plugin(tauri_plugin_opener::init())
-        .invoke_handler(tauri::generate_handler![read_exif, find_aesthetic_images])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
-}
@@ -0,0 +1,222 @@
+use crate::{
+    iptc,
+    mwg::{self, LogicalField, SourcePriority},
+    xmp,
+};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Rights information a stock agency expects on every delivered file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RightsField {
+    Copyright,
+    Creator,
+    Credit,
+    UsageTerms,
+    /// A PLUS licensor or an `xmpRights:WebStatement` licence link.
+    License,
+}
+
+/// Checked when the audit is not told otherwise; many agencies accept files
+/// without PLUS licence data.
+pub const DEFAULT_REQUIRED: [RightsField; 4] = [
+    RightsField::Copyright,
+    RightsField::Creator,
+    RightsField::Credit,
+    RightsField::UsageTerms,
+];
+
+/// PLUS properties naming the licensor, in the order they are preferred.
+const PLUS_LICENSOR: [&str; 3] = ["plus:LicensorName", "plus:LicensorID", "plus:LicensorURL"];
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RightsEntry {
+    path: String,
+    copyright: Option<String>,
+    creator: Option<String>,
+    credit: Option<String>,
+    usage_terms: Option<String>,
+    license: Option<String>,
+    /// Required fields the file lacks.
+    missing: Vec<RightsField>,
+    /// Fields whose EXIF, IPTC and XMP copies disagree.
+    conflicts: Vec<RightsField>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LicenseAudit {
+    files: Vec<RightsEntry>,
+    /// Files with a missing or conflicting field.
+    incomplete: usize,
+}
+
+fn first_nonempty<'a>(values: impl IntoIterator<Item = Option<&'a str>>) -> Option<String> {
+    values
+        .into_iter()
+        .flatten()
+        .map(str::trim)
+        .find(|value| !value.is_empty())
+        .map(str::to_string)
+}
+
+/// An XMP property stored either as a plain value or as a language
+/// alternative, as `xmpRights:UsageTerms` is.
+fn xmp_text<'a>(packet: &'a str, name: &str) -> Option<&'a str> {
+    xmp::items(packet, name)
+        .first()
+        .copied()
+        .or_else(|| xmp::property(packet, name).filter(|value| !value.contains('<')))
+}
+
+pub fn rights_entry(
+    path: &Path,
+    data: &[u8],
+    priority: &SourcePriority,
+    required: &[RightsField],
+) -> RightsEntry {
+    let datasets = iptc::read(data);
+    let packet = xmp::packet(data);
+    let reconciled = mwg::reconcile(data, priority);
+    let logical = |field: LogicalField| reconciled.iter().find(|found| found.field == field);
+    let (copyright, creator) = (
+        logical(LogicalField::Copyright),
+        logical(LogicalField::Creator),
+    );
+
+    let iptc_credit = iptc::values(&datasets, iptc::CREDIT).first().copied();
+    let xmp_credit = packet.and_then(|packet| xmp::property(packet, "photoshop:Credit"));
+    let credit = first_nonempty([iptc_credit, xmp_credit]);
+    let usage_terms =
+        first_nonempty([packet.and_then(|packet| xmp_text(packet, "xmpRights:UsageTerms"))]);
+    let license = first_nonempty(
+        PLUS_LICENSOR
+            .iter()
+            .chain(&["xmpRights:WebStatement"])
+            .map(|name| packet.and_then(|packet| xmp::property(packet, name))),
+    );
+
+    let mut entry = RightsEntry {
+        path: path.to_string_lossy().into_owned(),
+        copyright: copyright.and_then(|found| found.value.clone()),
+        creator: creator.and_then(|found| found.value.clone()),
+        credit,
+        usage_terms,
+        license,
+        missing: Vec::new(),
+        conflicts: Vec::new(),
+    };
+    entry.missing = required
+        .iter()
+        .copied()
+        .filter(|field| {
+            match field {
+                RightsField::Copyright => &entry.copyright,
+                RightsField::Creator => &entry.creator,
+                RightsField::Credit => &entry.credit,
+                RightsField::UsageTerms => &entry.usage_terms,
+                RightsField::License => &entry.license,
+            }
+            .is_none()
+        })
+        .collect();
+    if copyright.is_some_and(|found| found.conflict) {
+        entry.conflicts.push(RightsField::Copyright);
+    }
+    if creator.is_some_and(|found| found.conflict) {
+        entry.conflicts.push(RightsField::Creator);
+    }
+    if let (Some(iptc_credit), Some(xmp_credit)) = (iptc_credit, xmp_credit) {
+        if iptc_credit.trim() != xmp_credit.trim() {
+            entry.conflicts.push(RightsField::Credit);
+        }
+    }
+    entry
+}
+
+/// Checks the rights fields of every image below `root`.
+pub fn license_audit(
+    root: &Path,
+    priority: &SourcePriority,
+    required: &[RightsField],
+) -> Result<LicenseAudit, String> {
+    let mut files = Vec::new();
+    crate::walk_files(root, &mut |path| {
+        if !crate::is_supported_image(path) {
+            return;
+        }
+        if let Ok(data) = crate::memory_budget::load_scan_data(path) {
+            files.push(rights_entry(path, &data, priority, required));
+        }
+    });
+    if files.is_empty() {
+        return Err("No images were found in the selected folder.".to_string());
+    }
+
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    let incomplete = files
+        .iter()
+        .filter(|entry| !entry.missing.is_empty() || !entry.conflicts.is_empty())
+        .count();
+    Ok(LicenseAudit { files, incomplete })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        fixtures::{ascii_field, jpeg_from_fields},
+        jpeg,
+    };
+    use exif::Tag;
+
+    fn delivered_jpeg(packet: &[u8]) -> Vec<u8> {
+        let jpeg = jpeg_from_fields(&[
+            ascii_field(Tag::Artist, "Ana Ruiz"),
+            ascii_field(Tag::Copyright, "© 2024 Ana Ruiz"),
+        ]);
+        let mut data = jpeg[..2].to_vec();
+        data.extend(iptc::app13_segment(&[
+            (2, iptc::CREDIT, b"Ana Ruiz / Agency"),
+            (2, iptc::COPYRIGHT_NOTICE, b"(c) Ana Ruiz"),
+        ]));
+        data.extend(jpeg::encode_segment(jpeg::APP1, packet).unwrap());
+        data.extend_from_slice(&jpeg[2..]);
+        data
+    }
+
+    #[test]
+    fn missing_and_conflicting_rights_are_reported() {
+        let data = delivered_jpeg(
+            br#"<x:xmpmeta><rdf:Description photoshop:Credit="Ana Ruiz / Agency"/></x:xmpmeta>"#,
+        );
+        let entry = rights_entry(
+            Path::new("a.jpg"),
+            &data,
+            &SourcePriority::default(),
+            &DEFAULT_REQUIRED,
+        );
+        assert_eq!(entry.copyright.as_deref(), Some("© 2024 Ana Ruiz"));
+        assert_eq!(entry.credit.as_deref(), Some("Ana Ruiz / Agency"));
+        assert_eq!(entry.missing, [RightsField::UsageTerms]);
+        assert_eq!(entry.conflicts, [RightsField::Copyright]);
+    }
+
+    #[test]
+    fn usage_terms_and_plus_licensor_come_from_xmp() {
+        let data = delivered_jpeg(
+            br#"<x:xmpmeta><rdf:Description><xmpRights:UsageTerms><rdf:Alt><rdf:li xml:lang="x-default">Editorial use only</rdf:li></rdf:Alt></xmpRights:UsageTerms><plus:Licensor><rdf:Seq><rdf:li rdf:parseType="Resource"><plus:LicensorName>Agency Ltd</plus:LicensorName></rdf:li></rdf:Seq></plus:Licensor></rdf:Description></x:xmpmeta>"#,
+        );
+        let entry = rights_entry(
+            Path::new("a.jpg"),
+            &data,
+            &SourcePriority::default(),
+            &[RightsField::UsageTerms, RightsField::License],
+        );
+        assert_eq!(entry.usage_terms.as_deref(), Some("Editorial use only"));
+        assert_eq!(entry.license.as_deref(), Some("Agency Ltd"));
+        assert!(entry.missing.is_empty());
+    }
+}
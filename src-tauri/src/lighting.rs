@@ -0,0 +1,226 @@
+use crate::{gps::first_rational, xmp};
+use exif::{Exif, In, Tag};
+use serde::Serialize;
+use std::path::Path;
+
+/// Colour temperatures within this many kelvin of the session median match.
+const TEMPERATURE_TOLERANCE_K: f64 = 200.0;
+/// Flash energies within this fraction of the session median match.
+const ENERGY_TOLERANCE: f64 = 0.1;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LightingEntry {
+    path: String,
+    flash_fired: Option<bool>,
+    flash_mode: Option<String>,
+    flash_energy: Option<f64>,
+    white_balance: Option<String>,
+    light_source: Option<String>,
+    color_temperature: Option<f64>,
+    /// Settings that differ from the rest of the session.
+    outliers: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LightingAudit {
+    images: Vec<LightingEntry>,
+    outliers: usize,
+}
+
+fn flash_mode(flash: u32) -> &'static str {
+    match (flash >> 3) & 0b11 {
+        1 => "Forced on",
+        2 => "Forced off",
+        3 => "Auto",
+        _ => "Unknown",
+    }
+}
+
+pub fn lighting_entry(path: &Path, exif: &Exif, data: &[u8]) -> LightingEntry {
+    let flash = exif
+        .get_field(Tag::Flash, In::PRIMARY)
+        .and_then(|field| field.value.get_uint(0));
+    let described = |tag: Tag| {
+        exif.get_field(tag, In::PRIMARY)
+            .map(|field| field.display_value().to_string())
+    };
+    // Colour temperature is not an EXIF tag; raw converters record it in XMP.
+    let color_temperature = xmp::packet(data)
+        .and_then(|packet| xmp::property(packet, "crs:Temperature"))
+        .and_then(|value| value.parse().ok());
+
+    LightingEntry {
+        path: path.to_string_lossy().into_owned(),
+        flash_fired: flash.map(|flash| flash & 1 == 1),
+        flash_mode: flash.map(|flash| flash_mode(flash).to_string()),
+        flash_energy: first_rational(exif, Tag::FlashEnergy),
+        white_balance: described(Tag::WhiteBalance),
+        light_source: described(Tag::LightSource),
+        color_temperature,
+        outliers: Vec::new(),
+    }
+}
+
+/// Most common value, preferring the first seen on ties.
+fn mode<T: PartialEq + Clone>(values: impl Iterator<Item = T>) -> Option<T> {
+    let mut counts: Vec<(T, usize)> = Vec::new();
+    for value in values {
+        match counts.iter_mut().find(|(seen, _)| *seen == value) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((value, 1)),
+        }
+    }
+    counts
+        .into_iter()
+        .rev()
+        .max_by_key(|(_, count)| *count)
+        .map(|(value, _)| value)
+}
+
+fn median(mut values: Vec<f64>) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_by(f64::total_cmp);
+    let middle = values.len() / 2;
+    Some(if values.len().is_multiple_of(2) {
+        (values[middle - 1] + values[middle]) / 2.0
+    } else {
+        values[middle]
+    })
+}
+
+/// Compares every image against the session's typical settings and records
+/// the ones that differ.
+pub fn flag_outliers(entries: &mut [LightingEntry]) {
+    let fired = mode(entries.iter().filter_map(|entry| entry.flash_fired));
+    let flash_mode = mode(entries.iter().filter_map(|entry| entry.flash_mode.clone()));
+    let white_balance = mode(
+        entries
+            .iter()
+            .filter_map(|entry| entry.white_balance.clone()),
+    );
+    let light_source = mode(
+        entries
+            .iter()
+            .filter_map(|entry| entry.light_source.clone()),
+    );
+    let energy = median(
+        entries
+            .iter()
+            .filter_map(|entry| entry.flash_energy)
+            .collect(),
+    );
+    let temperature = median(
+        entries
+            .iter()
+            .filter_map(|entry| entry.color_temperature)
+            .collect(),
+    );
+
+    for entry in entries {
+        let mut outliers = Vec::new();
+        if entry.flash_fired.is_some() && entry.flash_fired != fired {
+            outliers.push("Flash fired");
+        }
+        if entry.flash_mode.is_some() && entry.flash_mode != flash_mode {
+            outliers.push("Flash mode");
+        }
+        if let (Some(value), Some(typical)) = (entry.flash_energy, energy) {
+            if (value - typical).abs() > typical.abs() * ENERGY_TOLERANCE {
+                outliers.push("Flash strength");
+            }
+        }
+        if entry.white_balance.is_some() && entry.white_balance != white_balance {
+            outliers.push("White balance");
+        }
+        if entry.light_source.is_some() && entry.light_source != light_source {
+            outliers.push("Light source");
+        }
+        if let (Some(value), Some(typical)) = (entry.color_temperature, temperature) {
+            if (value - typical).abs() > TEMPERATURE_TOLERANCE_K {
+                outliers.push("Color temperature");
+            }
+        }
+        entry.outliers = outliers.into_iter().map(str::to_string).collect();
+    }
+}
+
+/// Summarizes flash and white balance settings for every image below `root`.
+pub fn audit_lighting(root: &Path) -> Result<LightingAudit, String> {
+    let mut images = Vec::new();
+    crate::walk_files(root, &mut |path| {
+        if !crate::is_supported_image(path) {
+            return;
+        }
+        let Ok(data) = crate::memory_budget::load_scan_data(path) else {
+            return;
+        };
+        if let Some(exif) = crate::read_exif_container(&data) {
+            images.push(lighting_entry(path, &exif, &data));
+        }
+    });
+    if images.is_empty() {
+        return Err("No images with EXIF metadata were found.".to_string());
+    }
+
+    images.sort_by(|a, b| a.path.cmp(&b.path));
+    flag_outliers(&mut images);
+    let outliers = images
+        .iter()
+        .filter(|entry| !entry.outliers.is_empty())
+        .count();
+    Ok(LightingAudit { images, outliers })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::exif_from_fields;
+    use exif::{Field, Value};
+
+    fn studio_shot(name: &str, flash: u16, white_balance: u16) -> LightingEntry {
+        let short = |tag, value| Field {
+            tag,
+            ifd_num: In::PRIMARY,
+            value: Value::Short(vec![value]),
+        };
+        let exif = exif_from_fields(&[
+            short(Tag::Flash, flash),
+            short(Tag::WhiteBalance, white_balance),
+        ]);
+        lighting_entry(Path::new(name), &exif, &[])
+    }
+
+    #[test]
+    fn flash_bits_are_decoded() {
+        let entry = studio_shot("a.jpg", 0x19, 1);
+        assert_eq!(entry.flash_fired, Some(true));
+        assert_eq!(entry.flash_mode.as_deref(), Some("Auto"));
+        assert_eq!(entry.white_balance.as_deref(), Some("manual white balance"));
+    }
+
+    #[test]
+    fn shots_that_break_from_the_session_are_flagged() {
+        let mut entries = vec![
+            studio_shot("a.jpg", 0x09, 1),
+            studio_shot("b.jpg", 0x09, 1),
+            studio_shot("c.jpg", 0x10, 0),
+            studio_shot("d.jpg", 0x09, 1),
+        ];
+        entries[0].color_temperature = Some(5500.0);
+        entries[1].color_temperature = Some(5600.0);
+        entries[3].color_temperature = Some(3200.0);
+
+        flag_outliers(&mut entries);
+        assert!(entries[0].outliers.is_empty());
+        assert!(entries[1].outliers.is_empty());
+        assert_eq!(
+            entries[2].outliers,
+            vec!["Flash fired", "Flash mode", "White balance"]
+        );
+        assert_eq!(entries[3].outliers, vec!["Color temperature"]);
+    }
+}
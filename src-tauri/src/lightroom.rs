@@ -0,0 +1,306 @@
+use crate::{gps::first_rational, xmp};
+use exif::{Exif, In, Tag};
+use serde::Serialize;
+use std::path::Path;
+
+/// What a Lightroom catalog knows about one image.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CatalogImage {
+    pub rating: Option<f64>,
+    pub color_label: Option<String>,
+    /// 1 picked, -1 rejected, 0 unflagged.
+    pub pick: Option<f64>,
+    pub capture_time: Option<String>,
+    pub iso: Option<f64>,
+    pub focal_length: Option<f64>,
+    pub camera_model: Option<String>,
+    pub collections: Vec<String>,
+}
+
+/// A value the catalog and the file on disk disagree about.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetadataDrift {
+    pub field: String,
+    pub catalog: String,
+    pub file: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CatalogReport {
+    pub in_catalog: bool,
+    pub rating: Option<u8>,
+    pub color_label: Option<String>,
+    pub picked: Option<bool>,
+    pub collections: Vec<String>,
+    pub drift: Vec<MetadataDrift>,
+}
+
+/// Looks `image` up in a Lightroom Classic catalog without modifying it.
+pub fn cross_reference(catalog: &Path, image: &Path) -> Result<CatalogReport, String> {
+    if !catalog.is_file() {
+        return Err("The selected Lightroom catalog does not exist.".to_string());
+    }
+    let Some(entry) = catalog_db::lookup(catalog, image)? else {
+        return Ok(CatalogReport::default());
+    };
+
+    let data = crate::load_file_data(image)?;
+    let exif = crate::read_exif_container(&data);
+    let sidecar = std::fs::read(image.with_extension("xmp")).ok();
+    // Lightroom writes ratings to the sidecar for RAW files and into the file otherwise.
+    let packet = sidecar
+        .as_deref()
+        .and_then(xmp::packet)
+        .or_else(|| xmp::packet(&data));
+    let drift = compare(&entry, exif.as_ref(), packet);
+
+    Ok(CatalogReport {
+        in_catalog: true,
+        rating: entry
+            .rating
+            .map(|rating| rating.round().clamp(0.0, 5.0) as u8),
+        color_label: entry.color_label.clone().filter(|label| !label.is_empty()),
+        picked: entry
+            .pick
+            .filter(|pick| *pick != 0.0)
+            .map(|pick| pick > 0.0),
+        collections: entry.collections.clone(),
+        drift,
+    })
+}
+
+/// Compares the catalog's copy of the metadata with what the file carries.
+/// Values missing from the file only count when the catalog has a real value,
+/// so an unrated photo with no rating on disk is not flagged.
+pub fn compare(
+    entry: &CatalogImage,
+    exif: Option<&Exif>,
+    packet: Option<&str>,
+) -> Vec<MetadataDrift> {
+    let mut drift = Vec::new();
+    let mut check = |field: &str, catalog: Option<String>, file: Option<String>| {
+        if let Some(catalog) = catalog {
+            if file.as_deref() != Some(catalog.as_str()) {
+                drift.push(MetadataDrift {
+                    field: field.to_string(),
+                    catalog,
+                    file: file.unwrap_or_else(|| "(missing)".to_string()),
+                });
+            }
+        }
+    };
+
+    let xmp_value = |name: &str| packet.and_then(|packet| xmp::property(packet, name));
+    check(
+        "Rating",
+        entry
+            .rating
+            .filter(|rating| *rating > 0.0)
+            .map(|rating| format!("{}", rating.round())),
+        xmp_value("xmp:Rating").map(str::to_string),
+    );
+    check(
+        "Color label",
+        entry.color_label.clone().filter(|label| !label.is_empty()),
+        xmp_value("xmp:Label").map(str::to_string),
+    );
+
+    let ascii = |tag: Tag| {
+        exif.and_then(|exif| exif.get_field(tag, In::PRIMARY))
+            .and_then(|field| match &field.value {
+                exif::Value::Ascii(strings) => strings
+                    .first()
+                    .map(|bytes| String::from_utf8_lossy(bytes).trim().to_string()),
+                _ => None,
+            })
+    };
+    check(
+        "Capture time",
+        entry.capture_time.as_deref().and_then(normalize_time),
+        ascii(Tag::DateTimeOriginal)
+            .as_deref()
+            .and_then(normalize_time),
+    );
+    check(
+        "Camera model",
+        entry.camera_model.clone(),
+        ascii(Tag::Model),
+    );
+    check(
+        "ISO",
+        entry.iso.map(|iso| format!("{}", iso.round())),
+        exif.and_then(|exif| exif.get_field(Tag::PhotographicSensitivity, In::PRIMARY))
+            .and_then(|field| field.value.get_uint(0))
+            .map(|iso| iso.to_string()),
+    );
+    check(
+        "Focal length",
+        entry.focal_length.map(|focal| format!("{:.1} mm", focal)),
+        exif.and_then(|exif| first_rational(exif, Tag::FocalLength))
+            .map(|focal| format!("{:.1} mm", focal)),
+    );
+    drift
+}
+
+/// Brings `2024-05-01T10:00:00.12` (catalog) and `2024:05:01 10:00:00`
+/// (EXIF) to the same shape, to the second.
+fn normalize_time(text: &str) -> Option<String> {
+    let digits: String = text.chars().filter(char::is_ascii_digit).take(14).collect();
+    (digits.len() == 14).then(|| {
+        format!(
+            "{}-{}-{} {}:{}:{}",
+            &digits[0..4],
+            &digits[4..6],
+            &digits[6..8],
+            &digits[8..10],
+            &digits[10..12],
+            &digits[12..14]
+        )
+    })
+}
+
+/// The SQLite side, compiled only with the `lightroom` feature so default
+/// builds don't carry a bundled SQLite.
+#[cfg(feature = "lightroom")]
+mod catalog_db {
+    use super::CatalogImage;
+    use rusqlite::{ffi::ErrorCode, Connection, OpenFlags, OptionalExtension};
+    use std::path::Path;
+
+    const IMAGE_QUERY: &str = "SELECT i.id_local, i.rating, i.colorLabels, i.pick, i.captureTime,
+               h.isoSpeedRating, h.focalLength, m.value
+        FROM Adobe_images i
+        JOIN AgLibraryFile f ON f.id_local = i.rootFile
+        JOIN AgLibraryFolder d ON d.id_local = f.folder
+        JOIN AgLibraryRootFolder r ON r.id_local = d.rootFolder
+        LEFT JOIN AgHarvestedExifMetadata h ON h.image = i.id_local
+        LEFT JOIN AgInternedExifCameraModel m ON m.id_local = h.cameraModelRef
+        WHERE lower(r.absolutePath || d.pathFromRoot || f.idx_filename) = lower(?1)
+        ORDER BY i.masterImage IS NOT NULL, i.id_local
+        LIMIT 1";
+    const COLLECTIONS_QUERY: &str = "SELECT c.name FROM AgLibraryCollectionImage ci
+        JOIN AgLibraryCollection c ON c.id_local = ci.collection
+        WHERE ci.image = ?1
+        ORDER BY c.name";
+
+    fn describe(error: rusqlite::Error) -> String {
+        match error.sqlite_error_code() {
+            Some(ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked) => {
+                "The catalog is locked; close Lightroom and try again.".to_string()
+            }
+            Some(ErrorCode::NotADatabase) => {
+                "The selected file is not a Lightroom catalog.".to_string()
+            }
+            _ => format!("Unable to read the Lightroom catalog: {}", error),
+        }
+    }
+
+    pub fn lookup(catalog: &Path, image: &Path) -> Result<Option<CatalogImage>, String> {
+        let connection = Connection::open_with_flags(
+            catalog,
+            OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )
+        .map_err(describe)?;
+        // Catalogs store forward slashes on every platform.
+        let key = std::fs::canonicalize(image)
+            .unwrap_or_else(|_| image.to_path_buf())
+            .to_string_lossy()
+            .replace('\\', "/");
+        let key = key.strip_prefix("//?/").unwrap_or(&key).to_string();
+
+        let found = connection
+            .query_row(IMAGE_QUERY, [&key], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    CatalogImage {
+                        rating: row.get(1)?,
+                        color_label: row.get(2)?,
+                        pick: row.get(3)?,
+                        capture_time: row.get(4)?,
+                        iso: row.get(5)?,
+                        focal_length: row.get(6)?,
+                        camera_model: row.get(7)?,
+                        collections: Vec::new(),
+                    },
+                ))
+            })
+            .optional()
+            .map_err(describe)?;
+        let Some((id, mut entry)) = found else {
+            return Ok(None);
+        };
+
+        let mut statement = connection.prepare(COLLECTIONS_QUERY).map_err(describe)?;
+        entry.collections = statement
+            .query_map([id], |row| row.get(0))
+            .map_err(describe)?
+            .collect::<Result<_, _>>()
+            .map_err(describe)?;
+        Ok(Some(entry))
+    }
+}
+
+#[cfg(not(feature = "lightroom"))]
+mod catalog_db {
+    use super::CatalogImage;
+    use std::path::Path;
+
+    pub fn lookup(_catalog: &Path, _image: &Path) -> Result<Option<CatalogImage>, String> {
+        Err(
+            "This build was made without Lightroom catalog support (the `lightroom` feature)."
+                .to_string(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{ascii_field, exif_from_fields, rational_field};
+
+    #[test]
+    fn drift_lists_only_values_that_disagree() {
+        let exif = exif_from_fields(&[
+            ascii_field(Tag::DateTimeOriginal, "2024:05:01 10:00:00"),
+            ascii_field(Tag::Model, "Canon EOS R5"),
+            rational_field(Tag::FocalLength, &[(50, 1)]),
+        ]);
+        let entry = CatalogImage {
+            rating: Some(4.0),
+            color_label: Some(String::new()),
+            capture_time: Some("2024-05-01T10:00:00.12".to_string()),
+            camera_model: Some("Canon EOS R5".to_string()),
+            focal_length: Some(85.0),
+            ..CatalogImage::default()
+        };
+        let packet = r#"<x:xmpmeta><rdf:Description xmp:Rating="2"/></x:xmpmeta>"#;
+
+        let drift = compare(&entry, Some(&exif), Some(packet));
+        assert_eq!(
+            drift,
+            [
+                MetadataDrift {
+                    field: "Rating".to_string(),
+                    catalog: "4".to_string(),
+                    file: "2".to_string(),
+                },
+                MetadataDrift {
+                    field: "Focal length".to_string(),
+                    catalog: "85.0 mm".to_string(),
+                    file: "50.0 mm".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn catalog_and_exif_times_normalize_alike() {
+        assert_eq!(
+            normalize_time("2024-05-01T10:00:00.12"),
+            normalize_time("2024:05:01 10:00:00")
+        );
+        assert_eq!(normalize_time("2024:05"), None);
+    }
+}
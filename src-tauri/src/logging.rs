@@ -0,0 +1,419 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap},
+    fmt::{self, Write as _},
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, OnceLock,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tracing::{
+    field::{Field, Visit},
+    span::{Attributes, Id, Record},
+    Event, Level, Metadata, Subscriber,
+};
+
+const LOG_NAME: &str = "exif-viewer";
+/// A log file is rotated once it grows past this size.
+const MAX_LOG_BYTES: u64 = 2 * 1024 * 1024;
+/// The live file plus this many rotated ones are kept.
+const ROTATED_LOGS: usize = 3;
+/// This crate's events are recorded from this level up; other crates only
+/// from `warn`.
+const DEFAULT_LEVEL: Level = Level::INFO;
+const CRATE_TARGET: &str = "exif_viewer";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl From<Level> for LogLevel {
+    fn from(level: Level) -> Self {
+        match level {
+            Level::ERROR => LogLevel::Error,
+            Level::WARN => LogLevel::Warn,
+            Level::INFO => LogLevel::Info,
+            Level::DEBUG => LogLevel::Debug,
+            _ => LogLevel::Trace,
+        }
+    }
+}
+
+/// One JSON line in the log file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogRecord {
+    /// Milliseconds since the Unix epoch.
+    pub timestamp_ms: u64,
+    pub level: LogLevel,
+    pub target: String,
+    /// The innermost span the event happened in, e.g. `benchmark.parse`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub span: Option<String>,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub fields: BTreeMap<String, String>,
+}
+
+/// `exif-viewer.log`, then `exif-viewer.1.log` (newest rotated) and so on.
+fn log_path(dir: &Path, generation: usize) -> PathBuf {
+    match generation {
+        0 => dir.join(format!("{}.log", LOG_NAME)),
+        n => dir.join(format!("{}.{}.log", LOG_NAME, n)),
+    }
+}
+
+struct RotatingLog {
+    dir: PathBuf,
+    file: File,
+    size: u64,
+}
+
+impl RotatingLog {
+    fn open(dir: &Path) -> std::io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(log_path(dir, 0))?;
+        let size = file.metadata()?.len();
+        Ok(RotatingLog {
+            dir: dir.to_path_buf(),
+            file,
+            size,
+        })
+    }
+
+    fn append(&mut self, line: &[u8]) -> std::io::Result<()> {
+        if self.size > 0 && self.size + line.len() as u64 > MAX_LOG_BYTES {
+            self.rotate()?;
+        }
+        self.file.write_all(line)?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> std::io::Result<()> {
+        fs::remove_file(log_path(&self.dir, ROTATED_LOGS)).ok();
+        for generation in (0..ROTATED_LOGS).rev() {
+            let from = log_path(&self.dir, generation);
+            if from.exists() {
+                fs::rename(&from, log_path(&self.dir, generation + 1))?;
+            }
+        }
+        *self = RotatingLog::open(&self.dir)?;
+        Ok(())
+    }
+}
+
+thread_local! {
+    static ENTERED: RefCell<Vec<u64>> = const { RefCell::new(Vec::new()) };
+}
+
+/// A `tracing` subscriber that appends events as JSON lines to a rotating
+/// log in the app data directory.
+pub struct FileLogger {
+    log: Mutex<RotatingLog>,
+    next_span: AtomicU64,
+    /// Span names with their handle counts, dropped when the last handle closes.
+    spans: Mutex<HashMap<u64, (&'static str, usize)>>,
+}
+
+impl FileLogger {
+    pub fn open(dir: &Path) -> Result<Self, String> {
+        let log = RotatingLog::open(dir)
+            .map_err(|error| format!("Unable to open the log file: {}", error))?;
+        Ok(FileLogger {
+            log: Mutex::new(log),
+            next_span: AtomicU64::new(1),
+            spans: Mutex::new(HashMap::new()),
+        })
+    }
+
+    fn current_span(&self) -> Option<String> {
+        let id = ENTERED.with(|entered| entered.borrow().last().copied())?;
+        let spans = self.spans.lock().expect("log spans poisoned");
+        spans.get(&id).map(|(name, _)| name.to_string())
+    }
+}
+
+/// The last component of `path`, which is all the log keeps of a file so
+/// the folders of the user's library stay out of it.
+pub fn file_name(path: &Path) -> String {
+    path.file_name()
+        .unwrap_or(path.as_os_str())
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Whether `word` reads as an absolute path on any platform: `/a/b`, `~/a`,
+/// `C:\a`, `C:/a`, `\\server\share` or a `file://` URL.
+fn is_absolute_path(word: &str) -> bool {
+    let word = word.strip_prefix("file://").unwrap_or(word);
+    let bytes = word.as_bytes();
+    let drive = bytes.len() > 2
+        && bytes[0].is_ascii_alphabetic()
+        && bytes[1] == b':'
+        && matches!(bytes[2], b'\\' | b'/');
+    drive
+        || word.starts_with("~/")
+        || word.starts_with("\\\\")
+        || (word.starts_with('/') && word.len() > 1 && word[1..].contains('/'))
+}
+
+/// `text` with every absolute path cut down to `.../<file name>`, for the
+/// messages and fields of events (such as error texts) that name a file. The
+/// words after a path that still hold a separator are taken as more of it,
+/// for folders with spaces in their names.
+fn redact_paths(text: &str) -> String {
+    const OPENING: &[char] = &['"', '\'', '(', '[', '='];
+    const CLOSING: &[char] = &['"', '\'', ')', ']', ',', ':', ';', '.'];
+    let mut words: Vec<String> = Vec::new();
+    let mut in_path = false;
+    for word in text.split(' ') {
+        let start = word.len() - word.trim_start_matches(OPENING).len();
+        let end = word.trim_end_matches(CLOSING).len().max(start);
+        let path = &word[start..end];
+        let continued = in_path && start == 0 && path.contains(['/', '\\']);
+        if !continued && !is_absolute_path(path) {
+            in_path = false;
+            words.push(word.to_string());
+            continue;
+        }
+        let mut prefix = word[..start].to_string();
+        if continued {
+            let previous = words.pop().unwrap_or_default();
+            prefix = previous[..previous.find(".../").unwrap_or(0)].to_string();
+        }
+        let name = path.rsplit(['/', '\\']).next().unwrap_or_default();
+        let redacted = format!("{}.../{}{}", prefix, name, &word[end..]);
+        // Only a path that runs to the end of its word can carry on.
+        in_path = end == word.len();
+        words.push(redacted);
+    }
+    words.join(" ")
+}
+
+#[derive(Default)]
+struct FieldVisitor {
+    message: String,
+    fields: BTreeMap<String, String>,
+}
+
+impl Visit for FieldVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        let value = redact_paths(value);
+        if field.name() == "message" {
+            self.message = value;
+        } else {
+            self.fields.insert(field.name().to_string(), value);
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        let mut text = String::new();
+        write!(text, "{:?}", value).ok();
+        let text = redact_paths(&text);
+        if field.name() == "message" {
+            self.message = text;
+        } else {
+            self.fields.insert(field.name().to_string(), text);
+        }
+    }
+}
+
+impl Subscriber for FileLogger {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        let threshold = if metadata.target().starts_with(CRATE_TARGET) {
+            DEFAULT_LEVEL
+        } else {
+            Level::WARN
+        };
+        *metadata.level() <= threshold
+    }
+
+    fn new_span(&self, attributes: &Attributes<'_>) -> Id {
+        let id = self.next_span.fetch_add(1, Ordering::Relaxed);
+        self.spans
+            .lock()
+            .expect("log spans poisoned")
+            .insert(id, (attributes.metadata().name(), 1));
+        Id::from_u64(id)
+    }
+
+    fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut visitor = FieldVisitor::default();
+        event.record(&mut visitor);
+        let metadata = event.metadata();
+        let record = LogRecord {
+            timestamp_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_millis() as u64)
+                .unwrap_or_default(),
+            level: (*metadata.level()).into(),
+            target: metadata.target().to_string(),
+            span: self.current_span(),
+            message: visitor.message,
+            fields: visitor.fields,
+        };
+        let Ok(mut line) = serde_json::to_vec(&record) else {
+            return;
+        };
+        line.push(b'\n');
+        // Logging must never take the app down; a full disk just loses lines.
+        self.log
+            .lock()
+            .expect("log file poisoned")
+            .append(&line)
+            .ok();
+    }
+
+    fn enter(&self, span: &Id) {
+        ENTERED.with(|entered| entered.borrow_mut().push(span.into_u64()));
+    }
+
+    fn exit(&self, span: &Id) {
+        ENTERED.with(|entered| {
+            let mut entered = entered.borrow_mut();
+            if let Some(position) = entered.iter().rposition(|id| *id == span.into_u64()) {
+                entered.remove(position);
+            }
+        });
+    }
+
+    fn clone_span(&self, span: &Id) -> Id {
+        if let Some((_, handles)) = self
+            .spans
+            .lock()
+            .expect("log spans poisoned")
+            .get_mut(&span.into_u64())
+        {
+            *handles += 1;
+        }
+        span.clone()
+    }
+
+    fn try_close(&self, span: Id) -> bool {
+        let mut spans = self.spans.lock().expect("log spans poisoned");
+        let Some((_, handles)) = spans.get_mut(&span.into_u64()) else {
+            return false;
+        };
+        *handles -= 1;
+        if *handles == 0 {
+            spans.remove(&span.into_u64());
+            return true;
+        }
+        false
+    }
+}
+
+/// Where logs live inside the app data directory.
+pub fn directory(data_dir: &Path) -> PathBuf {
+    data_dir.join("logs")
+}
+
+/// Why the file logger could not be installed, if it could not.
+static STARTUP_ERROR: OnceLock<String> = OnceLock::new();
+
+/// Installs the file logger for the whole process. A failure is kept for
+/// [`startup_error`], as the app runs on without a log.
+pub fn init(dir: &Path) -> Result<(), String> {
+    FileLogger::open(dir)
+        .and_then(|logger| {
+            tracing::subscriber::set_global_default(logger)
+                .map_err(|error| format!("Unable to start logging: {}", error))
+        })
+        .inspect_err(|error| {
+            STARTUP_ERROR.set(error.clone()).ok();
+        })
+}
+
+/// The error [`init`] failed with, if it did.
+pub fn startup_error() -> Option<&'static str> {
+    STARTUP_ERROR.get().map(String::as_str)
+}
+
+/// Records at `level` or more severe, written at or after `since_ms`,
+/// oldest first, across the live and rotated files.
+pub fn read_logs(dir: &Path, level: LogLevel, since_ms: Option<u64>) -> Vec<LogRecord> {
+    (0..=ROTATED_LOGS)
+        .rev()
+        .filter_map(|generation| fs::read_to_string(log_path(dir, generation)).ok())
+        .flat_map(|text| {
+            text.lines()
+                .filter_map(|line| serde_json::from_str::<LogRecord>(line).ok())
+                .collect::<Vec<_>>()
+        })
+        .filter(|record| record.level <= level)
+        .filter(|record| since_ms.is_none_or(|since| record.timestamp_ms >= since))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_are_written_with_their_span_and_filtered_on_read() {
+        let dir = crate::fixtures::temp_path("logging");
+        let logger = FileLogger::open(&dir).unwrap();
+        tracing::subscriber::with_default(logger, || {
+            tracing::info_span!("benchmark.parse").in_scope(|| {
+                tracing::warn!(
+                    file = "broken.png",
+                    error = "No such file: \"/Users/ana/My Pictures/broken.png\".",
+                    "could not parse"
+                );
+            });
+            tracing::info!("scan finished");
+            tracing::debug!("not recorded at the default level");
+        });
+
+        let warnings = read_logs(&dir, LogLevel::Warn, None);
+        let all = read_logs(&dir, LogLevel::Trace, None);
+        let later = read_logs(&dir, LogLevel::Trace, Some(u64::MAX));
+        fs::remove_dir_all(&dir).ok();
+
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].message, "could not parse");
+        assert_eq!(warnings[0].span.as_deref(), Some("benchmark.parse"));
+        assert_eq!(warnings[0].fields["file"], "broken.png");
+        assert_eq!(
+            warnings[0].fields["error"],
+            "No such file: \".../broken.png\"."
+        );
+        assert_eq!(all.len(), 2);
+        assert_eq!(all[1].message, "scan finished");
+        assert!(later.is_empty());
+    }
+
+    #[test]
+    fn full_logs_rotate_and_old_ones_are_dropped() {
+        let dir = crate::fixtures::temp_path("log_rotation");
+        let mut log = RotatingLog::open(&dir).unwrap();
+        let line = vec![b'x'; MAX_LOG_BYTES as usize / 2 + 1];
+        for _ in 0..(ROTATED_LOGS + 3) {
+            log.append(&line).unwrap();
+        }
+        let kept: Vec<bool> = (0..=ROTATED_LOGS + 1)
+            .map(|generation| log_path(&dir, generation).exists())
+            .collect();
+        fs::remove_dir_all(&dir).ok();
+        assert_eq!(kept, [true, true, true, true, false]);
+    }
+}
@@ -0,0 +1,215 @@
+use crate::ifd::{self, Tiff};
+use exif::{Exif, In, Tag, Value};
+
+const CANON_SHOT_INFO: u16 = 0x0004;
+const SHORT: u16 = 3;
+
+/// iPhone notes open with this, a version and "MM"; the IFD follows at 14
+/// and its offsets count from the start of the note.
+const APPLE_HEADER: &[u8] = b"Apple iOS\0";
+const APPLE_IFD_START: usize = 14;
+
+/// Apple MakerNote tags worth showing, with their names.
+const APPLE_TAGS: &[(u16, &str)] = &[
+    (0x000A, "HDRImageType"),
+    (0x000B, "BurstUUID"),
+    (0x0011, "ContentIdentifier"),
+    (0x0014, "ImageCaptureType"),
+    (0x0015, "ImageUniqueID"),
+    (0x0017, "LivePhotoVideoIndex"),
+    (0x0021, "HDRHeadroom"),
+    (0x002D, "ColorTemperature"),
+    (0x002E, "CameraType"),
+    (0x0030, "HDRGain"),
+];
+
+/// Focus distance in metres recorded by the lens, from the MakerNote when the
+/// vendor format is understood. `f64::INFINITY` means focused at infinity.
+pub fn focus_distance(exif: &Exif) -> Option<f64> {
+    let make = exif.get_field(Tag::Make, In::PRIMARY)?;
+    let make = make.display_value().to_string();
+    let note = exif.get_field(Tag::MakerNote, In::PRIMARY)?;
+    let Value::Undefined(_, offset) = note.value else {
+        return None;
+    };
+
+    if make.trim_matches('"').starts_with("Canon") {
+        canon_focus_distance(exif.buf(), offset as usize, exif.little_endian())
+    } else {
+        None
+    }
+}
+
+fn apple_value(tag: u16, note: &Tiff, entry: &ifd::Entry) -> Option<String> {
+    if let Some(text) = note.text(entry) {
+        return Some(text);
+    }
+    if let Some(value) = note.srationals(entry).first() {
+        return Some(format!("{:.3}", value));
+    }
+    let code = *note.uints(entry).first()?;
+    let name = match (tag, code) {
+        (0x000A, 3) => "HDR image",
+        (0x000A, 4) => "Original image",
+        (0x0014, 1) => "ProRAW",
+        (0x0014, 2) => "Portrait",
+        (0x0014, 10) => "Photo",
+        (0x0014, 11) => "Manual focus",
+        (0x0014, 12) => "Scene",
+        (0x002E, 0) => "Back wide angle",
+        (0x002E, 1) => "Back normal",
+        (0x002E, 6) => "Front",
+        _ => return Some(code.to_string()),
+    };
+    Some(name.to_string())
+}
+
+/// Named fields from an iPhone MakerNote, which otherwise shows as one
+/// opaque blob. `note` is the MakerNote value itself.
+pub fn apple_fields(note: &[u8]) -> Vec<(&'static str, String)> {
+    if !note.starts_with(APPLE_HEADER) || note.get(12..14) != Some(b"MM") {
+        return Vec::new();
+    }
+    let note = Tiff::new(note, false);
+    let entries = note.entries(APPLE_IFD_START);
+    APPLE_TAGS
+        .iter()
+        .filter_map(|(tag, name)| {
+            let value = apple_value(*tag, &note, ifd::find(&entries, *tag)?)?;
+            Some((*name, value))
+        })
+        .collect()
+}
+
+/// The Apple MakerNote of `exif`, if the file has one.
+pub fn apple_note(exif: &Exif) -> Option<&[u8]> {
+    match &exif.get_field(Tag::MakerNote, In::PRIMARY)?.value {
+        Value::Undefined(note, _) if note.starts_with(APPLE_HEADER) => Some(note),
+        _ => None,
+    }
+}
+
+/// Canon notes are a bare IFD whose value offsets are relative to the TIFF
+/// header, in the byte order of the enclosing file. ShotInfo entries 19 and 20
+/// hold the upper and lower focus distance in centimetres.
+fn canon_focus_distance(tiff: &[u8], start: usize, little_endian: bool) -> Option<f64> {
+    let read_u16 = |at: usize| {
+        let bytes: [u8; 2] = tiff.get(at..at + 2)?.try_into().ok()?;
+        Some(if little_endian {
+            u16::from_le_bytes(bytes)
+        } else {
+            u16::from_be_bytes(bytes)
+        })
+    };
+    let read_u32 = |at: usize| {
+        let bytes: [u8; 4] = tiff.get(at..at + 4)?.try_into().ok()?;
+        Some(if little_endian {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    };
+
+    let count = read_u16(start)? as usize;
+    let entry = (0..count)
+        .map(|index| start + 2 + index * 12)
+        .find(|&entry| read_u16(entry) == Some(CANON_SHOT_INFO))?;
+    if read_u16(entry + 2)? != SHORT || read_u32(entry + 4)? < 21 {
+        return None;
+    }
+    let values = read_u32(entry + 8)? as usize;
+    let upper = read_u16(values + 19 * 2)?;
+    let lower = read_u16(values + 20 * 2)?;
+
+    match (upper, lower) {
+        (0, _) => None,
+        (u16::MAX, _) => Some(f64::INFINITY),
+        (upper, 0) => Some(upper as f64 / 100.0),
+        (upper, lower) => Some((upper as f64 + lower as f64) / 200.0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn canon_note(little_endian: bool, upper: u16, lower: u16) -> Vec<u8> {
+        let u16_bytes = |value: u16| {
+            if little_endian {
+                value.to_le_bytes()
+            } else {
+                value.to_be_bytes()
+            }
+        };
+        let u32_bytes = |value: u32| {
+            if little_endian {
+                value.to_le_bytes()
+            } else {
+                value.to_be_bytes()
+            }
+        };
+
+        // Eight bytes of unrelated TIFF data precede the note at offset 8.
+        let mut tiff = vec![0u8; 8];
+        let values_offset = 8 + 2 + 12 + 4;
+        tiff.extend(u16_bytes(1));
+        tiff.extend(u16_bytes(CANON_SHOT_INFO));
+        tiff.extend(u16_bytes(SHORT));
+        tiff.extend(u32_bytes(34));
+        tiff.extend(u32_bytes(values_offset));
+        tiff.extend(u32_bytes(0));
+        for index in 0..34 {
+            tiff.extend(u16_bytes(match index {
+                19 => upper,
+                20 => lower,
+                _ => 0,
+            }));
+        }
+        tiff
+    }
+
+    #[test]
+    fn apple_notes_get_named_fields() {
+        let mut note = APPLE_HEADER.to_vec();
+        note.extend_from_slice(b"\0\x01MM");
+        note.extend_from_slice(&3u16.to_be_bytes());
+        // ImageCaptureType SLONG 1 = ProRAW, CameraType SLONG 0, and the
+        // HDRHeadroom SRATIONAL stored after the IFD.
+        for (tag, kind, value) in [(0x0014u16, 9u16, 1u32), (0x002E, 9, 0), (0x0021, 10, 56)] {
+            note.extend_from_slice(&tag.to_be_bytes());
+            note.extend_from_slice(&kind.to_be_bytes());
+            note.extend_from_slice(&1u32.to_be_bytes());
+            note.extend_from_slice(&value.to_be_bytes());
+        }
+        note.extend_from_slice(&0u32.to_be_bytes());
+        note.extend_from_slice(&(-3i32).to_be_bytes());
+        note.extend_from_slice(&2i32.to_be_bytes());
+
+        assert_eq!(
+            apple_fields(&note),
+            [
+                ("ImageCaptureType", "ProRAW".to_string()),
+                ("HDRHeadroom", "-1.500".to_string()),
+                ("CameraType", "Back wide angle".to_string()),
+            ]
+        );
+        assert!(apple_fields(b"Nikon\0\x02").is_empty());
+    }
+
+    #[test]
+    fn canon_shot_info_gives_focus_distance() {
+        assert_eq!(
+            canon_focus_distance(&canon_note(true, 310, 290), 8, true),
+            Some(3.0)
+        );
+        assert_eq!(
+            canon_focus_distance(&canon_note(false, 150, 0), 8, false),
+            Some(1.5)
+        );
+        assert_eq!(
+            canon_focus_distance(&canon_note(true, u16::MAX, 0), 8, true),
+            Some(f64::INFINITY)
+        );
+        assert_eq!(canon_focus_distance(&canon_note(true, 0, 0), 8, true), None);
+    }
+}
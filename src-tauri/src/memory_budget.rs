@@ -0,0 +1,168 @@
+use crate::{
+    placeholder,
+    remote::{self, RemoteObject, StorageBackend},
+    retry::{self, ReadError},
+};
+use std::{
+    fs,
+    ops::Deref,
+    path::Path,
+    sync::{Condvar, Mutex, OnceLock},
+    time::Duration,
+};
+
+/// Budget for file buffers held by scans at once when the user has not set one.
+pub const DEFAULT_LIMIT_MB: u64 = 1024;
+/// Smallest budget accepted from settings; below this most RAW files could
+/// only be read one at a time anyway.
+pub const MIN_LIMIT_MB: u64 = 64;
+/// Past this share of the budget, new readers pause briefly even when their
+/// file would fit, so already-loaded buffers get a chance to drain first.
+const PRESSURE_PERCENT: u64 = 75;
+const PRESSURE_PAUSE: Duration = Duration::from_millis(20);
+
+/// Caps the bytes of file data that scans keep in memory together. Readers
+/// reserve a file's size before loading it and wait while the reservation
+/// would overrun the limit; a file larger than the whole budget is admitted
+/// once nothing else is in flight, so it is read alone instead of never.
+pub struct MemoryBudget {
+    state: Mutex<BudgetState>,
+    released: Condvar,
+}
+
+struct BudgetState {
+    limit: u64,
+    in_flight: u64,
+}
+
+/// Bytes held against a [`MemoryBudget`] until dropped.
+pub struct Reservation<'a> {
+    budget: &'a MemoryBudget,
+    bytes: u64,
+}
+
+impl MemoryBudget {
+    pub fn new(limit: u64) -> Self {
+        MemoryBudget {
+            state: Mutex::new(BudgetState {
+                limit,
+                in_flight: 0,
+            }),
+            released: Condvar::new(),
+        }
+    }
+
+    pub fn set_limit(&self, limit: u64) {
+        self.state.lock().expect("memory budget poisoned").limit = limit;
+        self.released.notify_all();
+    }
+
+    pub fn reserve(&self, bytes: u64) -> Reservation<'_> {
+        let mut state = self.state.lock().expect("memory budget poisoned");
+        while state.in_flight > 0 && state.in_flight + bytes > state.limit {
+            state = self.released.wait(state).expect("memory budget poisoned");
+        }
+        let under_pressure = state.in_flight * 100 > state.limit * PRESSURE_PERCENT;
+        state.in_flight += bytes;
+        drop(state);
+        if under_pressure {
+            std::thread::sleep(PRESSURE_PAUSE);
+        }
+        Reservation {
+            budget: self,
+            bytes,
+        }
+    }
+
+    #[cfg(test)]
+    fn in_flight(&self) -> u64 {
+        self.state.lock().expect("memory budget poisoned").in_flight
+    }
+}
+
+impl Drop for Reservation<'_> {
+    fn drop(&mut self) {
+        let mut state = self.budget.state.lock().expect("memory budget poisoned");
+        state.in_flight -= self.bytes;
+        drop(state);
+        self.budget.released.notify_all();
+    }
+}
+
+/// The budget shared by every scan in the process.
+pub fn global() -> &'static MemoryBudget {
+    static BUDGET: OnceLock<MemoryBudget> = OnceLock::new();
+    BUDGET.get_or_init(|| MemoryBudget::new(DEFAULT_LIMIT_MB * 1024 * 1024))
+}
+
+/// A file's bytes, counted against the global budget while alive.
+pub struct ScanBuffer {
+    data: Vec<u8>,
+    _reservation: Reservation<'static>,
+}
+
+impl Deref for ScanBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+/// Reads `path` for a scan, waiting for room in the global budget first.
+/// A file that stays locked through the retries comes back as
+/// [`ReadError::Unavailable`] so the scan can report it as pending, and a
+/// cloud placeholder is refused unless placeholders may be downloaded.
+pub fn load_scan_data(path: &Path) -> Result<ScanBuffer, ReadError> {
+    placeholder::check(path)?;
+    let size = retry::READ_POLICY.run(|| fs::metadata(path))?.len();
+    let reservation = global().reserve(size);
+    let data = retry::read_file(path, &retry::READ_POLICY)?;
+    Ok(ScanBuffer {
+        data,
+        _reservation: reservation,
+    })
+}
+
+/// Like [`load_scan_data`] for an object in a remote library, reserving
+/// its listed size. Only requests the network or server may recover from
+/// come back as [`ReadError::Unavailable`].
+pub fn load_remote_scan_data(
+    backend: &dyn StorageBackend,
+    object: &RemoteObject,
+) -> Result<ScanBuffer, ReadError> {
+    let reservation = global().reserve(object.size);
+    let data = remote::read_metadata(backend, &object.key)?;
+    Ok(ScanBuffer {
+        data,
+        _reservation: reservation,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{sync::Arc, thread};
+
+    #[test]
+    fn reservations_wait_for_room_but_oversized_files_run_alone() {
+        let budget = Arc::new(MemoryBudget::new(100));
+        let first = budget.reserve(60);
+
+        let waiter = {
+            let budget = Arc::clone(&budget);
+            thread::spawn(move || drop(budget.reserve(60)))
+        };
+        thread::sleep(Duration::from_millis(50));
+        // The second reader is still waiting for the first to finish.
+        assert_eq!(budget.in_flight(), 60);
+        drop(first);
+        waiter.join().unwrap();
+
+        // Larger than the whole budget, but nothing else is held.
+        let alone = budget.reserve(500);
+        assert_eq!(budget.in_flight(), 500);
+        drop(alone);
+        assert_eq!(budget.in_flight(), 0);
+    }
+}
@@ -0,0 +1,233 @@
+use crate::{
+    iptc,
+    mwg::{self, LogicalField, LOGICAL_FIELDS},
+    xmp, ExifField,
+};
+use serde::Deserialize;
+
+pub const XMP_IFD: &str = "XMP";
+pub const IPTC_IFD: &str = "IPTC";
+
+/// What to do when the same logical tag arrives from several sources.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MergePolicy {
+    /// Every source's copy is listed.
+    #[default]
+    KeepAll,
+    /// EXIF, then XMP, IPTC and PNG text.
+    PreferExif,
+    /// XMP, then EXIF, IPTC and PNG text.
+    PreferXmp,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Source {
+    Exif,
+    Xmp,
+    Iptc,
+    PngText,
+}
+
+impl MergePolicy {
+    fn rank(self, source: Source) -> usize {
+        let order: &[Source] = match self {
+            MergePolicy::KeepAll | MergePolicy::PreferExif => {
+                &[Source::Exif, Source::Xmp, Source::Iptc, Source::PngText]
+            }
+            MergePolicy::PreferXmp => &[Source::Xmp, Source::Exif, Source::Iptc, Source::PngText],
+        };
+        order
+            .iter()
+            .position(|candidate| *candidate == source)
+            .unwrap_or(order.len())
+    }
+}
+
+/// `(field, EXIF tag, PNG keyword, XMP property, IPTC dataset name)`.
+const TAG_NAMES: [(LogicalField, &str, &str, &str, &str); 5] = [
+    (
+        LogicalField::Creator,
+        "Artist",
+        "Author",
+        "dc:creator",
+        "By-line",
+    ),
+    (
+        LogicalField::Description,
+        "ImageDescription",
+        "Description",
+        "dc:description",
+        "Caption-Abstract",
+    ),
+    (
+        LogicalField::DateCreated,
+        "DateTimeOriginal",
+        "Creation Time",
+        "photoshop:DateCreated",
+        "DateCreated",
+    ),
+    (
+        LogicalField::Copyright,
+        "Copyright",
+        "Copyright",
+        "dc:rights",
+        "CopyrightNotice",
+    ),
+    (
+        LogicalField::Keywords,
+        "",
+        "Keywords",
+        "dc:subject",
+        "Keywords",
+    ),
+];
+
+/// The descriptive XMP and IPTC-IIM properties of `data` as fields, so they
+/// sit beside their EXIF and PNG text equivalents, followed by the rest of
+/// the IIM datasets and XMP properties.
+pub fn embedded_fields(data: &[u8]) -> Vec<ExifField> {
+    let datasets = iptc::read(data);
+    let packet = xmp::container_packet(data);
+    let packet = packet.as_deref();
+    let mut fields = Vec::new();
+    for (field, _, _, xmp_name, iptc_name) in TAG_NAMES {
+        if let Some(value) = packet.and_then(|packet| mwg::from_xmp(packet, field)) {
+            fields.push(ExifField {
+                tag: xmp_name.to_string(),
+                ifd: XMP_IFD.to_string(),
+                value,
+            });
+        }
+        if let Some(value) = mwg::from_iptc(&datasets, field) {
+            fields.push(ExifField {
+                tag: iptc_name.to_string(),
+                ifd: IPTC_IFD.to_string(),
+                value,
+            });
+        }
+    }
+    // The other IIM datasets, then everything else in the packet, so news
+    // and stock files and XMP-only files are not left empty.
+    for (name, value) in iptc::named_values(&datasets) {
+        if !TAG_NAMES
+            .iter()
+            .any(|(_, _, _, _, iptc_name)| *iptc_name == name)
+        {
+            fields.push(ExifField {
+                tag: name.to_string(),
+                ifd: IPTC_IFD.to_string(),
+                value,
+            });
+        }
+    }
+    for (name, value) in packet.map(xmp::properties).unwrap_or_default() {
+        if !TAG_NAMES
+            .iter()
+            .any(|(_, _, _, xmp_name, _)| *xmp_name == name)
+        {
+            fields.push(ExifField {
+                tag: name,
+                ifd: XMP_IFD.to_string(),
+                value,
+            });
+        }
+    }
+    fields
+}
+
+/// Where `field` came from and which logical tag it carries, if any.
+fn classify(field: &ExifField) -> Option<(Source, LogicalField)> {
+    let source = match field.ifd.as_str() {
+        "In(0)" => Source::Exif,
+        XMP_IFD => Source::Xmp,
+        IPTC_IFD => Source::Iptc,
+        ifd if ifd.starts_with("PNG ") => Source::PngText,
+        _ => return None,
+    };
+    TAG_NAMES
+        .iter()
+        .find(|(_, exif, png, xmp, iptc)| {
+            let name = match source {
+                Source::Exif => exif,
+                Source::PngText => png,
+                Source::Xmp => xmp,
+                Source::Iptc => iptc,
+            };
+            !name.is_empty() && field.tag.eq_ignore_ascii_case(name)
+        })
+        .map(|(logical, ..)| (source, *logical))
+}
+
+/// Drops every copy of a logical tag except the one from the best-ranked
+/// source present. Fields with no logical equivalent are always kept.
+pub fn apply(fields: Vec<ExifField>, policy: MergePolicy) -> Vec<ExifField> {
+    if policy == MergePolicy::KeepAll {
+        return fields;
+    }
+    let best: Vec<(LogicalField, usize)> = LOGICAL_FIELDS
+        .iter()
+        .filter_map(|&logical| {
+            fields
+                .iter()
+                .filter_map(classify)
+                .filter(|(_, candidate)| *candidate == logical)
+                .map(|(source, _)| policy.rank(source))
+                .min()
+                .map(|rank| (logical, rank))
+        })
+        .collect();
+    fields
+        .into_iter()
+        .filter(|field| match classify(field) {
+            Some((source, logical)) => best
+                .iter()
+                .any(|(winner, rank)| *winner == logical && *rank == policy.rank(source)),
+            None => true,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(ifd: &str, tag: &str, value: &str) -> ExifField {
+        ExifField {
+            tag: tag.to_string(),
+            ifd: ifd.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    fn merged() -> Vec<ExifField> {
+        vec![
+            field("In(0)", "Artist", "\"Ana\""),
+            field("In(0)", "Make", "\"Canon\""),
+            field("PNG tEXt", "Author", "ana"),
+            field("XMP", "dc:creator", "Ana Ruiz"),
+            field("XMP", "dc:subject", "pier"),
+            field("IPTC", "Keywords", "pier; dusk"),
+        ]
+    }
+
+    fn listed(fields: &[ExifField]) -> Vec<String> {
+        fields
+            .iter()
+            .map(|field| format!("{} {}", field.ifd, field.tag))
+            .collect()
+    }
+
+    #[test]
+    fn preferred_source_wins_per_logical_tag() {
+        assert_eq!(
+            listed(&apply(merged(), MergePolicy::PreferExif)),
+            ["In(0) Artist", "In(0) Make", "XMP dc:subject"]
+        );
+        assert_eq!(
+            listed(&apply(merged(), MergePolicy::PreferXmp)),
+            ["In(0) Make", "XMP dc:creator", "XMP dc:subject"]
+        );
+        assert_eq!(apply(merged(), MergePolicy::KeepAll).len(), merged().len());
+    }
+}
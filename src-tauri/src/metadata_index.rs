@@ -0,0 +1,1074 @@
+use crate::{
+    format::FormatStyle, index_crypto::IndexSecret, merge::MergePolicy, projection::FieldSelection,
+    ExifField,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+/// Bits per file filter. A few hundred fields give a file about a thousand
+/// terms, so with four probes roughly one file in twenty-five that lacks a
+/// queried tag still has its rows read.
+const FILTER_BITS: usize = 8192;
+const PROBES: u64 = 4;
+
+/// A Bloom filter over the names a file's fields can be queried by.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct TagFilter(Vec<u8>);
+
+impl TagFilter {
+    fn new() -> Self {
+        TagFilter(vec![0; FILTER_BITS / 8])
+    }
+
+    /// Bit positions for `term` by double hashing two halves of FNV-1a.
+    fn positions(term: &str) -> impl Iterator<Item = usize> {
+        let hash = term.bytes().fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+        });
+        let (first, second) = (hash & 0xffff_ffff, (hash >> 32) | 1);
+        (0..PROBES).map(move |probe| {
+            (first.wrapping_add(probe.wrapping_mul(second)) % FILTER_BITS as u64) as usize
+        })
+    }
+
+    fn insert(&mut self, term: &str) {
+        for bit in Self::positions(term) {
+            self.0[bit / 8] |= 1 << (bit % 8);
+        }
+    }
+
+    /// `false` means the term was certainly never inserted.
+    fn may_contain(&self, term: &str) -> bool {
+        Self::positions(term).all(|bit| {
+            self.0
+                .get(bit / 8)
+                .is_some_and(|byte| byte >> (bit % 8) & 1 == 1)
+        })
+    }
+}
+
+impl Serialize for TagFilter {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&STANDARD.encode(&self.0))
+    }
+}
+
+impl<'de> Deserialize<'de> for TagFilter {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        STANDARD
+            .decode(text)
+            .map(TagFilter)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Lowercased with `_` and `-` read as spaces, so `aesthetic_score` and
+/// `Aesthetic score` are one term.
+fn normalize(name: &str) -> String {
+    name.trim().to_lowercase().replace(['_', '-'], " ")
+}
+
+/// The terms a field answers to: its tag, its group, the group without a
+/// `(computed)` style suffix (so `GPS` finds `GPS (computed)`) and
+/// `group/tag`.
+fn terms(field: &ExifField) -> Vec<String> {
+    let group = normalize(&field.ifd);
+    let mut terms = vec![
+        normalize(&field.tag),
+        format!("{}/{}", group, normalize(&field.tag)),
+    ];
+    if let Some((short, _)) = group.split_once(" (") {
+        terms.push(short.to_string());
+    }
+    terms.push(group);
+    terms
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct IndexEntry {
+    /// Seconds since the Unix epoch, with the size telling whether the
+    /// file changed since it was indexed.
+    modified: Option<u64>,
+    size: u64,
+    filter: TagFilter,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexRefresh {
+    pub indexed: usize,
+    pub unchanged: usize,
+    pub removed: usize,
+    pub failed: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexQuery {
+    pub matches: Vec<String>,
+    /// Files whose filters passed, so their rows were checked.
+    pub rows_checked: usize,
+    /// Files the filters ruled out without reading their rows.
+    pub skipped: usize,
+}
+
+/// What an index check found and did about it.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexCheck {
+    pub problems: Vec<String>,
+    /// Files dropped from the index so the next refresh reads them again.
+    pub dropped: usize,
+    /// Whether the index was unreadable and has been started afresh; the
+    /// damaged copy is kept beside it with a `.corrupt` extension.
+    pub rebuilt: bool,
+    /// The folder indexed again after the repair, when one was given.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refresh: Option<IndexRefresh>,
+}
+
+/// Problems for entries with a damaged filter and rows with no entry.
+fn entry_problems(damaged: usize, stray: usize) -> Vec<String> {
+    let mut problems = Vec::new();
+    if damaged > 0 {
+        problems.push(format!(
+            "{} indexed files had a damaged tag filter.",
+            damaged
+        ));
+    }
+    if stray > 0 {
+        problems.push(format!("{} field rows belonged to no indexed file.", stray));
+    }
+    problems
+}
+
+/// Moves a damaged index out of the way so a fresh one can take its place.
+fn set_aside(location: &Path) -> Result<(), String> {
+    match fs::rename(location, location.with_extension("corrupt")) {
+        Err(error) if error.kind() != std::io::ErrorKind::NotFound => Err(format!(
+            "Unable to move the damaged index out of the way: {}",
+            error
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn stamp(path: &Path) -> Option<(Option<u64>, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|elapsed| elapsed.as_secs());
+    Some((modified, metadata.len()))
+}
+
+const LOCKED: &str = "The metadata index is encrypted; unlock it with its passphrase first.";
+
+/// Whether the index is kept encrypted and, if so, whether it can be read yet.
+#[derive(Clone)]
+pub enum Access {
+    Plain,
+    Locked,
+    Unlocked(IndexSecret),
+}
+
+/// Every field of the images below a folder, kept in the app data directory
+/// with a per-file tag filter so presence queries skip most files.
+pub struct MetadataIndex {
+    store: store::Store,
+}
+
+pub use store::FILE_NAME;
+
+impl MetadataIndex {
+    /// Opens the index at `location`. An unreadable index starts empty
+    /// until `verify` repairs it, and a locked one refuses every use until
+    /// `unlock` is given its secret.
+    pub fn open(location: PathBuf, access: Access) -> Self {
+        MetadataIndex {
+            store: store::Store::open(location, access),
+        }
+    }
+
+    /// Opens a locked index, failing when `secret` does not fit it.
+    pub fn unlock(&self, secret: IndexSecret) -> Result<(), String> {
+        self.store.unlock(secret)
+    }
+
+    /// Stores the index plain or encrypted with a new secret from now on.
+    /// A JSON index is rewritten; an SQLite one is emptied, since SQLCipher
+    /// cannot encrypt a database in place, and filled again by the next
+    /// refresh.
+    pub fn set_encryption(&self, access: Access) -> Result<(), String> {
+        if matches!(access, Access::Locked) {
+            return Err(LOCKED.to_string());
+        }
+        self.store.set_access(access)
+    }
+
+    /// Indexes new and changed images below `root` and forgets removed ones.
+    /// Files are read without holding the index, so queries keep being
+    /// answered until the changes are written in one go.
+    pub fn refresh(&self, root: &Path) -> Result<IndexRefresh, String> {
+        let mut found = Vec::new();
+        crate::walk_files(root, &mut |path| {
+            if crate::is_supported_image(path) {
+                found.push(path.to_path_buf());
+            }
+        });
+        let prefix = crate::path_key(root);
+        let known = self.store.stamps(&prefix)?;
+        let mut refresh = IndexRefresh::default();
+        let mut seen = HashSet::new();
+        let mut indexed = Vec::new();
+        for path in found {
+            let key = crate::path_key(&path);
+            seen.insert(key.clone());
+            let Some((modified, size)) = stamp(&path) else {
+                refresh.failed += 1;
+                continue;
+            };
+            if known.get(&key) == Some(&(modified, size)) {
+                refresh.unchanged += 1;
+                continue;
+            }
+            let Ok(fields) = crate::read_file_fields(
+                &path,
+                FormatStyle::default(),
+                MergePolicy::default(),
+                &FieldSelection::default(),
+            ) else {
+                refresh.failed += 1;
+                continue;
+            };
+            let mut filter = TagFilter::new();
+            for term in fields.iter().flat_map(terms) {
+                filter.insert(&term);
+            }
+            let entry = IndexEntry {
+                modified,
+                size,
+                filter,
+            };
+            indexed.push((key, entry, fields));
+        }
+        let gone: Vec<String> = known
+            .into_keys()
+            .filter(|key| Path::new(key).starts_with(&prefix) && !seen.contains(key))
+            .collect();
+        refresh.indexed = indexed.len();
+        refresh.removed = gone.len();
+        self.store.apply(indexed, &gone)?;
+        Ok(refresh)
+    }
+
+    /// Indexed files below `root` that have a field answering to every one
+    /// of `tags`, by tag (`GPSLatitude`, `aesthetic score`), group (`GPS`,
+    /// `XMP`) or `group/tag`.
+    pub fn query(&self, root: &Path, tags: &[String]) -> Result<IndexQuery, String> {
+        let prefix = crate::path_key(root);
+        let wanted: Vec<String> = tags.iter().map(|tag| normalize(tag)).collect();
+        let mut query = IndexQuery::default();
+        for (key, filter) in self.store.filters(&prefix)? {
+            if !Path::new(&key).starts_with(&prefix) {
+                continue;
+            }
+            if !wanted.iter().all(|term| filter.may_contain(term)) {
+                query.skipped += 1;
+                continue;
+            }
+            query.rows_checked += 1;
+            let rows = self.store.rows(&key)?;
+            let has = |term: &String| rows.iter().any(|field| terms(field).contains(term));
+            if wanted.iter().all(has) {
+                query.matches.push(key);
+            }
+        }
+        Ok(query)
+    }
+
+    /// Checks the index for damage and repairs what it finds: entries that
+    /// cannot be trusted are dropped and an unreadable index is replaced by
+    /// an empty one.
+    pub fn verify(&self) -> Result<IndexCheck, String> {
+        self.store.verify()
+    }
+}
+
+/// A single JSON file, rewritten whole after each refresh and sealed with
+/// the index secret when the index is encrypted.
+#[cfg(not(feature = "sqlite-index"))]
+mod store {
+    use super::{
+        entry_problems, set_aside, Access, IndexCheck, IndexEntry, TagFilter, FILTER_BITS, LOCKED,
+    };
+    use crate::{
+        index_crypto::{self, IndexSecret},
+        ExifField,
+    };
+    use serde::{Deserialize, Serialize};
+    use std::{
+        collections::BTreeMap,
+        fs,
+        path::{Path, PathBuf},
+        sync::Mutex,
+    };
+
+    pub const FILE_NAME: &str = "metadata-index.json";
+
+    #[derive(Debug, Default, Serialize, Deserialize)]
+    struct IndexData {
+        entries: BTreeMap<String, IndexEntry>,
+        /// Field rows per file, only read when a filter lets a file through.
+        rows: BTreeMap<String, Vec<ExifField>>,
+    }
+
+    /// Why the file on disk was not loaded.
+    enum Unread {
+        /// It cannot be parsed, so `verify` may replace it.
+        Damaged(String),
+        /// It is sealed and the secret is missing or wrong; it is left alone.
+        Refused(String),
+    }
+
+    fn read(location: &Path, secret: Option<&IndexSecret>) -> Result<IndexData, Unread> {
+        let bytes = match fs::read(location) {
+            Ok(bytes) => bytes,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(IndexData::default())
+            }
+            Err(error) => {
+                return Err(Unread::Damaged(format!(
+                    "The index file could not be read: {}",
+                    error
+                )))
+            }
+        };
+        let bytes = match (index_crypto::is_sealed(&bytes), secret) {
+            (true, Some(secret)) => {
+                index_crypto::unseal(secret, &bytes).map_err(Unread::Refused)?
+            }
+            (true, None) => return Err(Unread::Refused(LOCKED.to_string())),
+            // A plain file is sealed the next time it is written.
+            (false, _) => bytes,
+        };
+        serde_json::from_slice(&bytes).map_err(|error| {
+            Unread::Damaged(format!("The index file could not be read: {}", error))
+        })
+    }
+
+    struct State {
+        data: IndexData,
+        /// Why the file on disk could not be read, until it is replaced.
+        damage: Option<String>,
+        access: Access,
+    }
+
+    impl State {
+        fn loaded(location: &Path, access: Access) -> Result<State, String> {
+            let secret = match &access {
+                Access::Plain => None,
+                Access::Locked => return Err(LOCKED.to_string()),
+                Access::Unlocked(secret) => Some(secret),
+            };
+            let (data, damage) = match read(location, secret) {
+                Ok(data) => (data, None),
+                Err(Unread::Damaged(damage)) => (IndexData::default(), Some(damage)),
+                Err(Unread::Refused(reason)) => return Err(reason),
+            };
+            Ok(State {
+                data,
+                damage,
+                access,
+            })
+        }
+
+        fn locked() -> State {
+            State {
+                data: IndexData::default(),
+                damage: None,
+                access: Access::Locked,
+            }
+        }
+
+        fn data(&mut self) -> Result<&mut IndexData, String> {
+            match self.access {
+                Access::Locked => Err(LOCKED.to_string()),
+                _ => Ok(&mut self.data),
+            }
+        }
+    }
+
+    pub struct Store {
+        location: PathBuf,
+        state: Mutex<State>,
+    }
+
+    impl Store {
+        /// Reads the file now unless the index is locked; a file that turns
+        /// out to be sealed leaves it locked.
+        pub fn open(location: PathBuf, access: Access) -> Self {
+            let state = State::loaded(&location, access).unwrap_or_else(|_| State::locked());
+            Store {
+                location,
+                state: Mutex::new(state),
+            }
+        }
+
+        fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+            self.state.lock().expect("metadata index poisoned")
+        }
+
+        pub fn unlock(&self, secret: IndexSecret) -> Result<(), String> {
+            let mut state = self.lock();
+            if !matches!(state.access, Access::Locked) {
+                return Ok(());
+            }
+            *state = State::loaded(&self.location, Access::Unlocked(secret))?;
+            Ok(())
+        }
+
+        /// Rewrites the index plain or sealed with the new secret.
+        pub fn set_access(&self, access: Access) -> Result<(), String> {
+            let mut state = self.lock();
+            state.data()?;
+            let previous = std::mem::replace(&mut state.access, access);
+            self.persist(&mut state)
+                .inspect_err(|_| state.access = previous)
+        }
+
+        pub fn stamps(&self, prefix: &str) -> Result<BTreeMap<String, (Option<u64>, u64)>, String> {
+            Ok(self
+                .lock()
+                .data()?
+                .entries
+                .iter()
+                .filter(|(key, _)| key.starts_with(prefix))
+                .map(|(key, entry)| (key.clone(), (entry.modified, entry.size)))
+                .collect())
+        }
+
+        pub fn apply(
+            &self,
+            indexed: Vec<(String, IndexEntry, Vec<ExifField>)>,
+            removed: &[String],
+        ) -> Result<(), String> {
+            let mut state = self.lock();
+            let data = state.data()?;
+            for key in removed {
+                data.entries.remove(key);
+                data.rows.remove(key);
+            }
+            for (key, entry, fields) in indexed {
+                data.entries.insert(key.clone(), entry);
+                data.rows.insert(key, fields);
+            }
+            self.persist(&mut state)
+        }
+
+        pub fn filters(&self, prefix: &str) -> Result<Vec<(String, TagFilter)>, String> {
+            Ok(self
+                .lock()
+                .data()?
+                .entries
+                .iter()
+                .filter(|(key, _)| key.starts_with(prefix))
+                .map(|(key, entry)| (key.clone(), entry.filter.clone()))
+                .collect())
+        }
+
+        pub fn rows(&self, key: &str) -> Result<Vec<ExifField>, String> {
+            Ok(self
+                .lock()
+                .data()?
+                .rows
+                .get(key)
+                .cloned()
+                .unwrap_or_default())
+        }
+
+        pub fn verify(&self) -> Result<IndexCheck, String> {
+            let mut state = self.lock();
+            state.data()?;
+            let mut check = IndexCheck::default();
+            if let Some(damage) = state.damage.clone() {
+                set_aside(&self.location)?;
+                check.problems.push(damage);
+                check.rebuilt = true;
+            }
+            let data = &mut state.data;
+            let before = data.rows.len();
+            let entries = &data.entries;
+            data.rows.retain(|key, _| entries.contains_key(key));
+            let stray = before - data.rows.len();
+            let damaged: Vec<String> = data
+                .entries
+                .iter()
+                .filter(|(key, entry)| {
+                    entry.filter.0.len() != FILTER_BITS / 8 || !data.rows.contains_key(*key)
+                })
+                .map(|(key, _)| key.clone())
+                .collect();
+            for key in &damaged {
+                data.entries.remove(key);
+                data.rows.remove(key);
+            }
+            check.dropped = damaged.len();
+            check.problems.extend(entry_problems(damaged.len(), stray));
+            if !check.problems.is_empty() {
+                self.persist(&mut state)?;
+            }
+            Ok(check)
+        }
+
+        fn persist(&self, state: &mut State) -> Result<(), String> {
+            if let Some(parent) = self.location.parent() {
+                fs::create_dir_all(parent).map_err(|error| error.to_string())?;
+            }
+            let mut bytes = serde_json::to_vec(&state.data).map_err(|error| error.to_string())?;
+            if let Access::Unlocked(secret) = &state.access {
+                bytes = index_crypto::seal(secret, &bytes)?;
+            }
+            crate::write_file_atomically(&self.location, &bytes)?;
+            state.damage = None;
+            Ok(())
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn unreadable_files_and_damaged_entries_are_repaired() {
+            let location = crate::fixtures::temp_path("index_check.json");
+            fs::write(&location, "{\"entries\": {").unwrap();
+            let store = Store::open(location.clone(), Access::Plain);
+            let unreadable = store.verify().unwrap();
+            let damaged_filter = IndexEntry {
+                modified: None,
+                size: 1,
+                filter: TagFilter(vec![0; 3]),
+            };
+            store
+                .apply(
+                    vec![("/a.jpg".to_string(), damaged_filter, Vec::new())],
+                    &[],
+                )
+                .unwrap();
+            let damaged = store.verify().unwrap();
+            let corrupt = location.with_extension("corrupt");
+            let kept = fs::read_to_string(&corrupt).unwrap_or_default();
+            let left = Store::open(location.clone(), Access::Plain)
+                .stamps("/")
+                .unwrap();
+            fs::remove_file(&location).ok();
+            fs::remove_file(corrupt).ok();
+
+            assert!(unreadable.rebuilt && unreadable.problems.len() == 1);
+            assert_eq!(kept, "{\"entries\": {");
+            assert_eq!((damaged.dropped, damaged.rebuilt), (1, false));
+            assert!(left.is_empty());
+        }
+
+        #[test]
+        fn a_sealed_index_stays_locked_and_untouched() {
+            let location = crate::fixtures::temp_path("index_sealed.json");
+            fs::write(&location, b"EVIX\x01sealed").unwrap();
+            let store = Store::open(location.clone(), Access::Plain);
+            let refused = store.stamps("/").unwrap_err();
+            let checked = store.verify();
+            let kept = fs::read(&location).unwrap();
+            fs::remove_file(&location).ok();
+
+            assert_eq!(refused, LOCKED);
+            assert!(checked.is_err());
+            assert_eq!(kept, b"EVIX\x01sealed");
+        }
+    }
+}
+
+/// A SQLite database in WAL mode, so queries read a consistent snapshot
+/// while a refresh writes. Reads and writes have a connection each.
+#[cfg(feature = "sqlite-index")]
+mod store {
+    use super::{
+        entry_problems, set_aside, Access, IndexCheck, IndexEntry, TagFilter, FILTER_BITS, LOCKED,
+    };
+    use crate::{index_crypto::IndexSecret, ExifField};
+    use rusqlite::{ffi::ErrorCode, params, Connection, TransactionBehavior};
+    use std::{
+        collections::BTreeMap,
+        path::{Path, PathBuf},
+        sync::Mutex,
+        time::Duration,
+    };
+
+    pub const FILE_NAME: &str = "metadata-index.sqlite";
+
+    /// How long a writer waits for another one before giving up.
+    const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Schema changes in order; an index at `user_version` N has had the
+    /// first N applied.
+    const MIGRATIONS: &[&str] = &["CREATE TABLE files (
+            path TEXT PRIMARY KEY,
+            modified INTEGER,
+            size INTEGER NOT NULL,
+            filter BLOB NOT NULL
+        );
+        CREATE TABLE fields (
+            path TEXT NOT NULL,
+            position INTEGER NOT NULL,
+            ifd TEXT NOT NULL,
+            tag TEXT NOT NULL,
+            value TEXT NOT NULL,
+            PRIMARY KEY (path, position)
+        );"];
+    const BELOW: &str = "substr(path, 1, length(?1)) = ?1";
+
+    fn describe(error: rusqlite::Error) -> String {
+        match error.sqlite_error_code() {
+            Some(ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked) => {
+                "The metadata index is busy with another update; try again in a moment.".to_string()
+            }
+            Some(ErrorCode::DatabaseCorrupt | ErrorCode::NotADatabase) => {
+                "The metadata index is damaged; check the index to repair it.".to_string()
+            }
+            _ => format!("Unable to use the metadata index: {}", error),
+        }
+    }
+
+    fn is_damage(error: &rusqlite::Error) -> bool {
+        matches!(
+            error.sqlite_error_code(),
+            Some(ErrorCode::DatabaseCorrupt | ErrorCode::NotADatabase)
+        )
+    }
+
+    /// Brings the schema up to date. Each step runs in an immediate
+    /// transaction that rereads the version, so two connections opening a
+    /// new index at once do not both apply it.
+    fn migrate(connection: &mut Connection) -> Result<(), String> {
+        loop {
+            let transaction = connection
+                .transaction_with_behavior(TransactionBehavior::Immediate)
+                .map_err(describe)?;
+            let version: i64 = transaction
+                .pragma_query_value(None, "user_version", |row| row.get(0))
+                .map_err(describe)?;
+            let version = usize::try_from(version).unwrap_or(0);
+            let Some(migration) = MIGRATIONS.get(version) else {
+                if version > MIGRATIONS.len() {
+                    return Err(
+                        "The metadata index was made by a newer version of the app.".to_string()
+                    );
+                }
+                return Ok(());
+            };
+            transaction.execute_batch(migration).map_err(describe)?;
+            transaction
+                .pragma_update(None, "user_version", version as i64 + 1)
+                .map_err(describe)?;
+            transaction.commit().map_err(describe)?;
+        }
+    }
+
+    /// The value of SQLCipher's `PRAGMA key`: the passphrase, which SQLCipher
+    /// stretches itself, or the raw key in its `x'…'` form.
+    fn key_pragma(secret: &IndexSecret) -> String {
+        match secret {
+            IndexSecret::Passphrase(passphrase) => passphrase.clone(),
+            IndexSecret::Key(key) => {
+                let hex: String = key.iter().map(|byte| format!("{:02X}", byte)).collect();
+                format!("x'{}'", hex)
+            }
+        }
+    }
+
+    /// Opens the database with its key, if it has one. A wrong key only
+    /// shows on the first read, so one is made here.
+    fn open_keyed(location: &Path, access: &Access) -> Result<Connection, String> {
+        let connection = Connection::open(location).map_err(describe)?;
+        connection.busy_timeout(BUSY_TIMEOUT).map_err(describe)?;
+        match access {
+            Access::Plain => {}
+            Access::Locked => return Err(LOCKED.to_string()),
+            Access::Unlocked(secret) => {
+                connection
+                    .pragma_update(None, "key", key_pragma(secret))
+                    .map_err(describe)?;
+                let read: rusqlite::Result<i64> =
+                    connection
+                        .query_row("SELECT count(*) FROM sqlite_master", (), |row| row.get(0));
+                match read {
+                    Err(error) if is_damage(&error) => {
+                        return Err("The passphrase does not unlock the metadata index.".to_string())
+                    }
+                    other => other.map_err(describe)?,
+                };
+            }
+        }
+        Ok(connection)
+    }
+
+    fn connect(location: &Path, access: &Access) -> Result<Connection, String> {
+        if let Some(parent) = location.parent() {
+            std::fs::create_dir_all(parent).map_err(|error| error.to_string())?;
+        }
+        let mut connection = open_keyed(location, access)?;
+        let mode: String = connection
+            .pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get(0))
+            .map_err(describe)?;
+        if !mode.eq_ignore_ascii_case("wal") {
+            // Some network filesystems lack the shared memory WAL needs;
+            // the index still works, with refreshes blocking queries.
+            tracing::warn!(mode = %mode, "metadata index is not in WAL mode");
+        }
+        connection
+            .pragma_update(None, "synchronous", "NORMAL")
+            .map_err(describe)?;
+        migrate(&mut connection)?;
+        Ok(connection)
+    }
+
+    /// Rows `PRAGMA integrity_check` reports, empty for a sound database.
+    fn integrity_problems(location: &Path, access: &Access) -> Result<Vec<String>, String> {
+        let connection = open_keyed(location, access)?;
+        let checked = connection
+            .prepare("PRAGMA integrity_check")
+            .and_then(|mut statement| {
+                let rows = statement
+                    .query_map((), |row| row.get::<_, String>(0))?
+                    .collect::<rusqlite::Result<Vec<String>>>();
+                rows
+            });
+        match checked {
+            Ok(rows) => Ok(rows.into_iter().filter(|row| row != "ok").collect()),
+            Err(error) if is_damage(&error) => Ok(vec![describe(error)]),
+            Err(error) => Err(describe(error)),
+        }
+    }
+
+    /// Drops rows with no file and files whose filter is the wrong size.
+    fn drop_damaged_entries(connection: &mut Connection) -> rusqlite::Result<IndexCheck> {
+        let transaction = connection.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let orphaned = "DELETE FROM fields WHERE path NOT IN (SELECT path FROM files)";
+        let stray = transaction.execute(orphaned, ())?;
+        let damaged = transaction.execute(
+            "DELETE FROM files WHERE length(filter) != ?1",
+            [(FILTER_BITS / 8) as i64],
+        )?;
+        transaction.execute(orphaned, ())?;
+        transaction.commit()?;
+        Ok(IndexCheck {
+            problems: entry_problems(damaged, stray),
+            dropped: damaged,
+            ..IndexCheck::default()
+        })
+    }
+
+    pub struct Store {
+        location: PathBuf,
+        writer: Mutex<Option<Connection>>,
+        reader: Mutex<Option<Connection>>,
+        /// Locked after the connections whenever both are needed.
+        access: Mutex<Access>,
+    }
+
+    impl Store {
+        /// Nothing is opened until the index is first used.
+        pub fn open(location: PathBuf, access: Access) -> Self {
+            Store {
+                location,
+                writer: Mutex::new(None),
+                reader: Mutex::new(None),
+                access: Mutex::new(access),
+            }
+        }
+
+        fn access(&self) -> Access {
+            self.access.lock().expect("metadata index poisoned").clone()
+        }
+
+        pub fn unlock(&self, secret: IndexSecret) -> Result<(), String> {
+            if !matches!(self.access(), Access::Locked) {
+                return Ok(());
+            }
+            let access = Access::Unlocked(secret);
+            let connection = connect(&self.location, &access)?;
+            let mut writer = self.writer.lock().expect("metadata index poisoned");
+            *writer = Some(connection);
+            *self.access.lock().expect("metadata index poisoned") = access;
+            Ok(())
+        }
+
+        /// Closes the connections and removes the database, so the next use
+        /// creates it afresh with the new key.
+        pub fn set_access(&self, access: Access) -> Result<(), String> {
+            let mut writer = self.writer.lock().expect("metadata index poisoned");
+            let mut reader = self.reader.lock().expect("metadata index poisoned");
+            let mut current = self.access.lock().expect("metadata index poisoned");
+            if matches!(*current, Access::Locked) {
+                return Err(LOCKED.to_string());
+            }
+            writer.take();
+            reader.take();
+            self.remove_files()?;
+            *current = access;
+            Ok(())
+        }
+
+        fn remove_files(&self) -> Result<(), String> {
+            for suffix in ["", "-wal", "-shm"] {
+                let mut file = self.location.clone().into_os_string();
+                file.push(suffix);
+                match std::fs::remove_file(file) {
+                    Err(error) if error.kind() != std::io::ErrorKind::NotFound => {
+                        return Err(format!("Unable to remove the metadata index: {}", error))
+                    }
+                    _ => {}
+                }
+            }
+            Ok(())
+        }
+
+        /// Runs `task` on one of the connections, opening it first if needed.
+        fn on<T>(
+            &self,
+            slot: &Mutex<Option<Connection>>,
+            task: impl FnOnce(&mut Connection) -> rusqlite::Result<T>,
+        ) -> Result<T, String> {
+            let mut slot = slot.lock().expect("metadata index poisoned");
+            if slot.is_none() {
+                *slot = Some(connect(&self.location, &self.access())?);
+            }
+            task(slot.as_mut().expect("connection was just opened")).map_err(describe)
+        }
+
+        pub fn stamps(&self, prefix: &str) -> Result<BTreeMap<String, (Option<u64>, u64)>, String> {
+            self.on(&self.reader, |connection| {
+                let mut statement = connection.prepare(&format!(
+                    "SELECT path, modified, size FROM files WHERE {}",
+                    BELOW
+                ))?;
+                let stamps = statement
+                    .query_map([prefix], |row| {
+                        let modified: Option<i64> = row.get(1)?;
+                        let size: i64 = row.get(2)?;
+                        Ok((
+                            row.get::<_, String>(0)?,
+                            (modified.map(|modified| modified as u64), size as u64),
+                        ))
+                    })?
+                    .collect();
+                stamps
+            })
+        }
+
+        /// Writes every change of a refresh in one transaction.
+        pub fn apply(
+            &self,
+            indexed: Vec<(String, IndexEntry, Vec<ExifField>)>,
+            removed: &[String],
+        ) -> Result<(), String> {
+            self.on(&self.writer, |connection| {
+                let transaction =
+                    connection.transaction_with_behavior(TransactionBehavior::Immediate)?;
+                {
+                    let mut forget_file =
+                        transaction.prepare("DELETE FROM files WHERE path = ?1")?;
+                    let mut forget_rows =
+                        transaction.prepare("DELETE FROM fields WHERE path = ?1")?;
+                    let mut insert_file = transaction.prepare(
+                        "INSERT OR REPLACE INTO files (path, modified, size, filter)
+                         VALUES (?1, ?2, ?3, ?4)",
+                    )?;
+                    let mut insert_row = transaction.prepare(
+                        "INSERT INTO fields (path, position, ifd, tag, value)
+                         VALUES (?1, ?2, ?3, ?4, ?5)",
+                    )?;
+                    for key in removed {
+                        forget_file.execute([key])?;
+                        forget_rows.execute([key])?;
+                    }
+                    for (key, entry, fields) in &indexed {
+                        forget_rows.execute([key])?;
+                        insert_file.execute(params![
+                            key,
+                            entry.modified.map(|modified| modified as i64),
+                            entry.size as i64,
+                            entry.filter.0,
+                        ])?;
+                        for (position, field) in fields.iter().enumerate() {
+                            insert_row.execute(params![
+                                key,
+                                position as i64,
+                                field.ifd,
+                                field.tag,
+                                field.value,
+                            ])?;
+                        }
+                    }
+                }
+                transaction.commit()
+            })
+        }
+
+        pub fn filters(&self, prefix: &str) -> Result<Vec<(String, TagFilter)>, String> {
+            self.on(&self.reader, |connection| {
+                let mut statement = connection
+                    .prepare(&format!("SELECT path, filter FROM files WHERE {}", BELOW))?;
+                let filters = statement
+                    .query_map([prefix], |row| Ok((row.get(0)?, TagFilter(row.get(1)?))))?
+                    .collect();
+                filters
+            })
+        }
+
+        pub fn rows(&self, key: &str) -> Result<Vec<ExifField>, String> {
+            self.on(&self.reader, |connection| {
+                let mut statement = connection.prepare(
+                    "SELECT ifd, tag, value FROM fields WHERE path = ?1 ORDER BY position",
+                )?;
+                let rows = statement
+                    .query_map([key], |row| {
+                        Ok(ExifField {
+                            ifd: row.get(0)?,
+                            tag: row.get(1)?,
+                            value: row.get(2)?,
+                        })
+                    })?
+                    .collect();
+                rows
+            })
+        }
+
+        pub fn verify(&self) -> Result<IndexCheck, String> {
+            let problems = integrity_problems(&self.location, &self.access())?;
+            if problems.is_empty() {
+                return self.on(&self.writer, drop_damaged_entries);
+            }
+            // Both connections are closed so nothing uses the damaged file
+            // while it is swapped for a new one on the next use.
+            let mut writer = self.writer.lock().expect("metadata index poisoned");
+            let mut reader = self.reader.lock().expect("metadata index poisoned");
+            writer.take();
+            reader.take();
+            set_aside(&self.location)?;
+            self.remove_files()?;
+            Ok(IndexCheck {
+                problems,
+                rebuilt: true,
+                ..IndexCheck::default()
+            })
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn a_damaged_database_is_set_aside_and_started_afresh() {
+            let location = crate::fixtures::temp_path("index_db.sqlite");
+            let store = Store::open(location.clone(), Access::Plain);
+            store.apply(Vec::new(), &[]).unwrap();
+            let sound = store.verify().unwrap();
+            std::fs::write(&location, vec![0x5a; 4096]).unwrap();
+            let damaged = store.verify().unwrap();
+            let reopened = store.stamps("/").unwrap();
+            let corrupt = location.with_extension("corrupt");
+            let kept = corrupt.exists();
+            std::fs::remove_file(&location).ok();
+            std::fs::remove_file(corrupt).ok();
+
+            assert_eq!(sound, IndexCheck::default());
+            assert!(damaged.rebuilt && !damaged.problems.is_empty());
+            assert!(reopened.is_empty() && kept);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(ifd: &str, tag: &str) -> ExifField {
+        ExifField {
+            tag: tag.to_string(),
+            ifd: ifd.to_string(),
+            value: String::new(),
+        }
+    }
+
+    #[test]
+    fn filters_answer_to_tags_and_groups_and_rule_out_the_rest() {
+        let mut filter = TagFilter::new();
+        for term in [
+            field("GPS (computed)", "Latitude"),
+            field("PNG tEXt", "aesthetic_score"),
+        ]
+        .iter()
+        .flat_map(terms)
+        {
+            filter.insert(&term);
+        }
+        for present in [
+            "gps",
+            "latitude",
+            "aesthetic score",
+            "png text/aesthetic score",
+        ] {
+            assert!(filter.may_contain(present), "{}", present);
+        }
+        assert!(!filter.may_contain("xmp"));
+
+        let encoded = serde_json::to_string(&filter).unwrap();
+        assert_eq!(serde_json::from_str::<TagFilter>(&encoded).unwrap(), filter);
+    }
+
+    #[test]
+    fn queries_read_rows_only_for_files_the_filters_let_through() {
+        use crate::fixtures::{ascii_field, jpeg_from_fields};
+        use exif::Tag;
+
+        let root = crate::fixtures::temp_path("index");
+        fs::create_dir_all(&root).unwrap();
+        fs::write(
+            root.join("credited.jpg"),
+            jpeg_from_fields(&[ascii_field(Tag::Artist, "Ana")]),
+        )
+        .unwrap();
+        fs::write(
+            root.join("plain.jpg"),
+            jpeg_from_fields(&[ascii_field(Tag::Model, "X100V")]),
+        )
+        .unwrap();
+        let index = MetadataIndex::open(root.join("index.json"), Access::Plain);
+
+        let first = index.refresh(&root).unwrap();
+        let second = index.refresh(&root).unwrap();
+        let query = index.query(&root, &["artist".to_string()]).unwrap();
+        fs::remove_file(root.join("plain.jpg")).unwrap();
+        let third = index.refresh(&root).unwrap();
+        let reopened = MetadataIndex::open(root.join("index.json"), Access::Plain)
+            .query(&root, &[])
+            .unwrap();
+        fs::remove_dir_all(&root).ok();
+
+        assert_eq!((first.indexed, second.unchanged, third.removed), (2, 2, 1));
+        assert_eq!(query.matches.len(), 1);
+        assert!(query.matches[0].ends_with("credited.jpg"));
+        assert_eq!((query.rows_checked, query.skipped), (1, 1));
+        assert_eq!(reopened.matches.len(), 1);
+    }
+}
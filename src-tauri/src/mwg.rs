@@ -0,0 +1,304 @@
+use crate::{
+    capture::first_ascii,
+    iptc::{self, Dataset},
+    xmp,
+};
+use exif::{Exif, Tag};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// The Metadata Working Group's reading order for JPEG and TIFF: native EXIF
+/// first, then XMP, with legacy IPTC only as a fallback.
+const MWG_ORDER: [MetadataSource; 3] = [
+    MetadataSource::Exif,
+    MetadataSource::Xmp,
+    MetadataSource::Iptc,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MetadataSource {
+    Exif,
+    Iptc,
+    Xmp,
+}
+
+/// A logical field that several standards store under different names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LogicalField {
+    Creator,
+    Description,
+    DateCreated,
+    Copyright,
+    Keywords,
+}
+
+pub const LOGICAL_FIELDS: [LogicalField; 5] = [
+    LogicalField::Creator,
+    LogicalField::Description,
+    LogicalField::DateCreated,
+    LogicalField::Copyright,
+    LogicalField::Keywords,
+];
+
+/// Which source wins when they disagree. `order` applies to every field
+/// unless `overrides` names one, e.g. IPTC first for captions in a Photo
+/// Mechanic workflow. Sources left out of a list are consulted after the
+/// listed ones, in MWG order.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SourcePriority {
+    pub order: Vec<MetadataSource>,
+    pub overrides: BTreeMap<LogicalField, Vec<MetadataSource>>,
+}
+
+impl Default for SourcePriority {
+    fn default() -> Self {
+        SourcePriority {
+            order: MWG_ORDER.to_vec(),
+            overrides: BTreeMap::new(),
+        }
+    }
+}
+
+impl SourcePriority {
+    fn order_for(&self, field: LogicalField) -> Vec<MetadataSource> {
+        let mut order = self.overrides.get(&field).unwrap_or(&self.order).clone();
+        for source in MWG_ORDER {
+            if !order.contains(&source) {
+                order.push(source);
+            }
+        }
+        order
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Candidate {
+    pub source: MetadataSource,
+    pub value: String,
+}
+
+/// One logical field with the value chosen by the priority rules and every
+/// source's copy, so the UI can show where they drifted apart.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReconciledField {
+    pub field: LogicalField,
+    pub value: Option<String>,
+    pub source: Option<MetadataSource>,
+    pub candidates: Vec<Candidate>,
+    /// True when two sources hold different values.
+    pub conflict: bool,
+}
+
+/// Maps the EXIF, IPTC-IIM and XMP copies of each logical field in `data`
+/// onto one value. Lists (creators, keywords) are joined with `"; "`.
+pub fn reconcile(data: &[u8], priority: &SourcePriority) -> Vec<ReconciledField> {
+    let exif = crate::read_exif_container(data);
+    let datasets = iptc::read(data);
+    let packet = xmp::packet(data);
+    LOGICAL_FIELDS
+        .iter()
+        .map(|&field| {
+            let candidates: Vec<Candidate> = priority
+                .order_for(field)
+                .into_iter()
+                .filter_map(|source| {
+                    let value = match source {
+                        MetadataSource::Exif => {
+                            exif.as_ref().and_then(|exif| from_exif(exif, field))
+                        }
+                        MetadataSource::Iptc => from_iptc(&datasets, field),
+                        MetadataSource::Xmp => packet.and_then(|packet| from_xmp(packet, field)),
+                    }?;
+                    Some(Candidate { source, value })
+                })
+                .collect();
+            let conflict = candidates
+                .iter()
+                .any(|other| !same_value(field, &candidates[0].value, &other.value));
+            ReconciledField {
+                field,
+                value: candidates.first().map(|winner| winner.value.clone()),
+                source: candidates.first().map(|winner| winner.source),
+                candidates,
+                conflict,
+            }
+        })
+        .collect()
+}
+
+fn from_exif(exif: &Exif, field: LogicalField) -> Option<String> {
+    let text = |tag: Tag| {
+        let text = String::from_utf8_lossy(first_ascii(exif, tag)?)
+            .trim()
+            .to_string();
+        (!text.is_empty()).then_some(text)
+    };
+    match field {
+        // MWG: several EXIF artists are separated by semicolons.
+        LogicalField::Creator => text(Tag::Artist).map(|artist| {
+            artist
+                .split(';')
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .collect::<Vec<_>>()
+                .join("; ")
+        }),
+        LogicalField::Description => text(Tag::ImageDescription),
+        LogicalField::DateCreated => {
+            let stamp = text(Tag::DateTimeOriginal)?;
+            let (date, time) = stamp.split_once(' ')?;
+            let mut iso = format!("{}T{}", date.replace(':', "-"), time);
+            if let Some(offset) = text(Tag::OffsetTimeOriginal) {
+                iso.push_str(&offset);
+            }
+            Some(iso)
+        }
+        LogicalField::Copyright => text(Tag::Copyright),
+        LogicalField::Keywords => None,
+    }
+}
+
+pub fn from_iptc(datasets: &[Dataset], field: LogicalField) -> Option<String> {
+    let joined = |number: u8| {
+        let values = iptc::values(datasets, number);
+        (!values.is_empty()).then(|| values.join("; "))
+    };
+    let first = |number: u8| {
+        iptc::values(datasets, number)
+            .first()
+            .map(|value| value.to_string())
+    };
+    match field {
+        LogicalField::Creator => joined(iptc::BY_LINE),
+        LogicalField::Description => first(iptc::CAPTION),
+        LogicalField::DateCreated => {
+            // CCYYMMDD and HHMMSS±HHMM.
+            let date =
+                first(iptc::DATE_CREATED).filter(|date| date.len() == 8 && date.is_ascii())?;
+            let mut iso = format!("{}-{}-{}", &date[0..4], &date[4..6], &date[6..8]);
+            if let Some(time) =
+                first(iptc::TIME_CREATED).filter(|time| time.len() >= 6 && time.is_ascii())
+            {
+                iso.push_str(&format!("T{}:{}:{}", &time[0..2], &time[2..4], &time[4..6]));
+                if time.len() == 11 {
+                    iso.push_str(&format!("{}:{}", &time[6..9], &time[9..11]));
+                }
+            }
+            Some(iso)
+        }
+        LogicalField::Copyright => first(iptc::COPYRIGHT_NOTICE),
+        LogicalField::Keywords => joined(iptc::KEYWORDS),
+    }
+}
+
+pub fn from_xmp(packet: &str, field: LogicalField) -> Option<String> {
+    let joined = |name: &str| {
+        let items = xmp::items(packet, name);
+        (!items.is_empty()).then(|| items.join("; "))
+    };
+    let first = |name: &str| {
+        xmp::items(packet, name)
+            .first()
+            .map(|item| item.to_string())
+    };
+    match field {
+        LogicalField::Creator => joined("dc:creator"),
+        LogicalField::Description => first("dc:description"),
+        LogicalField::DateCreated => {
+            xmp::property(packet, "photoshop:DateCreated").map(str::to_string)
+        }
+        LogicalField::Copyright => first("dc:rights"),
+        LogicalField::Keywords => joined("dc:subject"),
+    }
+}
+
+/// Dates agree when they match to the precision both carry, so an IPTC date
+/// without a time does not conflict with the EXIF timestamp of the same day.
+fn same_value(field: LogicalField, a: &str, b: &str) -> bool {
+    if field != LogicalField::DateCreated {
+        return a == b;
+    }
+    let digits =
+        |text: &str| -> String { text.chars().take(19).filter(char::is_ascii_digit).collect() };
+    let (a, b) = (digits(a), digits(b));
+    let shared = a.len().min(b.len());
+    a[..shared] == b[..shared]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        fixtures::{ascii_field, jpeg_from_fields},
+        jpeg,
+    };
+
+    /// A JPEG with EXIF, an IPTC block and an XMP packet that disagree on the caption.
+    fn tagged_jpeg() -> Vec<u8> {
+        let jpeg = jpeg_from_fields(&[
+            ascii_field(Tag::Artist, "Ana Ruiz;Ben Ode"),
+            ascii_field(Tag::ImageDescription, "Pier at dusk"),
+            ascii_field(Tag::DateTimeOriginal, "2024:05:01 19:45:00"),
+        ]);
+        let mut data = jpeg[..2].to_vec();
+        data.extend(iptc::app13_segment(&[
+            (2, iptc::BY_LINE, b"Ana Ruiz"),
+            (2, iptc::BY_LINE, b"Ben Ode"),
+            (2, iptc::CAPTION, b"Old pier"),
+            (2, iptc::DATE_CREATED, b"20240501"),
+            (2, iptc::KEYWORDS, b"pier"),
+        ]));
+        let packet = br#"<x:xmpmeta><rdf:Description><dc:subject><rdf:Bag><rdf:li>pier</rdf:li><rdf:li>dusk</rdf:li></rdf:Bag></dc:subject></rdf:Description></x:xmpmeta>"#;
+        data.extend(jpeg::encode_segment(jpeg::APP1, packet).unwrap());
+        data.extend_from_slice(&jpeg[2..]);
+        data
+    }
+
+    fn field(fields: &[ReconciledField], field: LogicalField) -> &ReconciledField {
+        fields
+            .iter()
+            .find(|candidate| candidate.field == field)
+            .unwrap()
+    }
+
+    #[test]
+    fn mwg_order_prefers_exif_and_flags_disagreement() {
+        let fields = reconcile(&tagged_jpeg(), &SourcePriority::default());
+
+        let creator = field(&fields, LogicalField::Creator);
+        assert_eq!(creator.value.as_deref(), Some("Ana Ruiz; Ben Ode"));
+        assert!(!creator.conflict);
+
+        let description = field(&fields, LogicalField::Description);
+        assert_eq!(description.source, Some(MetadataSource::Exif));
+        assert_eq!(description.value.as_deref(), Some("Pier at dusk"));
+        assert!(description.conflict);
+
+        let date = field(&fields, LogicalField::DateCreated);
+        assert_eq!(date.value.as_deref(), Some("2024-05-01T19:45:00"));
+        assert!(!date.conflict);
+
+        let keywords = field(&fields, LogicalField::Keywords);
+        assert_eq!(keywords.source, Some(MetadataSource::Xmp));
+        assert_eq!(keywords.value.as_deref(), Some("pier; dusk"));
+    }
+
+    #[test]
+    fn per_field_overrides_change_the_winner() {
+        let priority = SourcePriority {
+            overrides: BTreeMap::from([(LogicalField::Description, vec![MetadataSource::Iptc])]),
+            ..SourcePriority::default()
+        };
+        let fields = reconcile(&tagged_jpeg(), &priority);
+        let description = field(&fields, LogicalField::Description);
+        assert_eq!(description.source, Some(MetadataSource::Iptc));
+        assert_eq!(description.value.as_deref(), Some("Old pier"));
+        // EXIF is still listed as a candidate after the override's sources.
+        assert_eq!(description.candidates[1].source, MetadataSource::Exif);
+    }
+}
@@ -0,0 +1,199 @@
+use crate::exif_write::FieldEdit;
+use chrono::DateTime;
+use exif::{Exif, Field, In};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// One tag changed by an operation, as displayed before and after the write.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FieldChange {
+    pub tag: String,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OperationRecord {
+    /// RFC 3339 UTC timestamp.
+    pub timestamp: String,
+    pub user: String,
+    pub operation: String,
+    pub path: String,
+    pub changes: Vec<FieldChange>,
+}
+
+impl OperationRecord {
+    pub fn new(operation: &str, path: &Path, changes: Vec<FieldChange>) -> Self {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs() as i64)
+            .unwrap_or_default();
+        OperationRecord {
+            timestamp: DateTime::from_timestamp(now, 0)
+                .map(|time| time.to_rfc3339())
+                .unwrap_or_default(),
+            user: current_user(),
+            operation: operation.to_string(),
+            path: crate::path_key(path),
+            changes,
+        }
+    }
+}
+
+/// Login name of whoever is running the app, as reported by the OS.
+pub fn current_user() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+/// Describes what `edits` will change relative to the primary IFD of `exif`.
+pub fn field_changes(exif: Option<&Exif>, edits: &[FieldEdit]) -> Vec<FieldChange> {
+    edits
+        .iter()
+        .filter_map(|edit| {
+            let before = exif
+                .and_then(|exif| exif.get_field(edit.tag, In::PRIMARY))
+                .map(|field| field.display_value().to_string());
+            let after = edit.value.clone().map(|value| {
+                Field {
+                    tag: edit.tag,
+                    ifd_num: In::PRIMARY,
+                    value,
+                }
+                .display_value()
+                .to_string()
+            });
+            (before != after).then(|| FieldChange {
+                tag: edit.tag.to_string(),
+                before,
+                after,
+            })
+        })
+        .collect()
+}
+
+/// Append-only JSON Lines log of every write the app makes to a file.
+pub struct OperationLog {
+    location: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl OperationLog {
+    pub fn open(location: PathBuf) -> Self {
+        OperationLog {
+            location,
+            lock: Mutex::new(()),
+        }
+    }
+
+    pub fn record(&self, record: &OperationRecord) -> Result<(), String> {
+        let _guard = self.lock.lock().expect("operation log poisoned");
+        if let Some(parent) = self.location.parent() {
+            fs::create_dir_all(parent).map_err(|error| error.to_string())?;
+        }
+        let mut line = serde_json::to_string(record).map_err(|error| error.to_string())?;
+        line.push('\n');
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.location)
+            .and_then(|mut file| file.write_all(line.as_bytes()))
+            .map_err(|error| format!("Unable to write the operation log: {}", error))
+    }
+
+    /// Every recorded operation on `path`, oldest first.
+    pub fn history(&self, path: &Path) -> Result<Vec<OperationRecord>, String> {
+        let _guard = self.lock.lock().expect("operation log poisoned");
+        let text = match fs::read_to_string(&self.location) {
+            Ok(text) => text,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(error) => return Err(format!("Unable to read the operation log: {}", error)),
+        };
+        let key = crate::path_key(path);
+        Ok(text
+            .lines()
+            // A line cut short by a crash is skipped rather than failing the query.
+            .filter_map(|line| serde_json::from_str::<OperationRecord>(line).ok())
+            .filter(|record| record.path == key)
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{ascii_field, exif_from_fields};
+    use exif::Tag;
+
+    #[test]
+    fn only_changed_tags_are_described() {
+        let exif = exif_from_fields(&[
+            ascii_field(Tag::Artist, "Ada"),
+            ascii_field(Tag::Copyright, "CC-BY"),
+        ]);
+        let changes = field_changes(
+            Some(&exif),
+            &[
+                FieldEdit::ascii(Tag::Artist, "Grace"),
+                FieldEdit::ascii(Tag::Copyright, "CC-BY"),
+                FieldEdit {
+                    tag: Tag::Software,
+                    value: None,
+                },
+            ],
+        );
+        assert_eq!(
+            changes,
+            vec![FieldChange {
+                tag: "Artist".to_string(),
+                before: Some("\"Ada\"".to_string()),
+                after: Some("\"Grace\"".to_string()),
+            }]
+        );
+    }
+
+    #[test]
+    fn history_is_filtered_by_file_and_survives_torn_lines() {
+        let location = crate::fixtures::temp_path("oplog.jsonl");
+        let log = OperationLog::open(location.clone());
+        let change = FieldChange {
+            tag: "Artist".to_string(),
+            before: None,
+            after: Some("\"Ada\"".to_string()),
+        };
+        log.record(&OperationRecord::new(
+            "edit",
+            Path::new("/photos/a.jpg"),
+            vec![change.clone()],
+        ))
+        .unwrap();
+        log.record(&OperationRecord::new(
+            "edit",
+            Path::new("/photos/b.jpg"),
+            Vec::new(),
+        ))
+        .unwrap();
+        OpenOptions::new()
+            .append(true)
+            .open(&location)
+            .unwrap()
+            .write_all(b"{\"timestamp\":")
+            .unwrap();
+
+        let history = log.history(Path::new("/photos/a.jpg")).unwrap();
+        fs::remove_file(&location).ok();
+
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].changes, vec![change]);
+        assert_eq!(history[0].operation, "edit");
+    }
+}
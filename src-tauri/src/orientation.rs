@@ -0,0 +1,373 @@
+use crate::{
+    commit::{commit_edits, commit_rewrite, Committed, EditTargets},
+    exif_write::FieldEdit,
+    jpeg,
+    oplog::FieldChange,
+    thumbnail::embedded_thumbnail,
+    write_lock::{FileStamp, WriteError, WriteProblemReport},
+    xmp,
+};
+use exif::{Exif, In, Tag, Value};
+use serde::Serialize;
+use std::path::Path;
+
+const XMP_ORIENTATION: &str = "tiff:Orientation";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum OrientationIssue {
+    /// EXIF asks viewers to rotate or mirror the stored pixels.
+    Rotated,
+    /// The pixels already appear turned the way EXIF asks for, so viewers
+    /// that honour the tag turn the image a second time.
+    PixelsRotated,
+    /// XMP `tiff:Orientation` disagrees with EXIF.
+    XmpConflict,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum OrientationFix {
+    /// Set EXIF Orientation (and XMP, if present) to 1; the pixels are
+    /// already upright.
+    ResetTag,
+    /// Copy the EXIF orientation into XMP.
+    SyncXmp,
+    /// The pixels need a lossless rotation, which `fix_orientation` does
+    /// not do; the file is only reported.
+    Rotate,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OrientationEntry {
+    path: String,
+    orientation: Option<u16>,
+    xmp_orientation: Option<u16>,
+    /// Stored pixel size, from the JPEG frame header.
+    width: Option<u32>,
+    height: Option<u32>,
+    issues: Vec<OrientationIssue>,
+    fix: Option<OrientationFix>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OrientationAudit {
+    /// Only the files with an issue.
+    files: Vec<OrientationEntry>,
+    scanned: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OrientationRepair {
+    path: String,
+    fix: Option<OrientationFix>,
+    written: bool,
+    /// The EXIF change was stored in the app because originals are protected.
+    stored_externally: bool,
+    error: Option<String>,
+    problem: Option<WriteProblemReport>,
+}
+
+/// Orientations 5 to 8 swap width and height.
+fn swaps_axes(orientation: u16) -> bool {
+    (5..=8).contains(&orientation)
+}
+
+fn is_landscape((width, height): (u32, u32)) -> Option<bool> {
+    (width != height).then_some(width > height)
+}
+
+fn exif_size(exif: &Exif) -> Option<(u32, u32)> {
+    let value = |tag| exif.get_field(tag, In::PRIMARY)?.value.get_uint(0);
+    Some((value(Tag::PixelXDimension)?, value(Tag::PixelYDimension)?))
+}
+
+/// Whether a JPEG whose EXIF asks for a quarter turn looks already turned:
+/// its EXIF pixel size is the stored size swapped, or its thumbnail lies
+/// the other way from the image, as when an editor rotated the pixels but
+/// left the tag and thumbnail alone.
+fn pixels_rotated(exif: &Exif, size: (u32, u32)) -> bool {
+    let recorded_swapped = exif_size(exif) == Some((size.1, size.0)) && size.0 != size.1;
+    let thumbnail_crossed = embedded_thumbnail(exif)
+        .and_then(jpeg::frame_size)
+        .and_then(is_landscape)
+        .zip(is_landscape(size))
+        .is_some_and(|(thumbnail, image)| thumbnail != image);
+    recorded_swapped || thumbnail_crossed
+}
+
+pub fn orientation_entry(path: &Path, data: &[u8]) -> OrientationEntry {
+    let exif = crate::read_exif_container(data);
+    let orientation = exif.as_ref().and_then(|exif| {
+        let value = exif
+            .get_field(Tag::Orientation, In::PRIMARY)?
+            .value
+            .get_uint(0)?;
+        u16::try_from(value).ok()
+    });
+    let xmp_orientation = xmp::container_packet(data)
+        .and_then(|packet| xmp::property(&packet, XMP_ORIENTATION)?.trim().parse().ok());
+    let size = jpeg::frame_size(data);
+
+    let mut issues = Vec::new();
+    let turned = orientation.is_some_and(|orientation| orientation != 1);
+    if turned {
+        issues.push(OrientationIssue::Rotated);
+    }
+    let already_upright = orientation.is_some_and(swaps_axes)
+        && exif
+            .as_ref()
+            .zip(size)
+            .is_some_and(|(exif, size)| pixels_rotated(exif, size));
+    if already_upright {
+        issues.push(OrientationIssue::PixelsRotated);
+    }
+    if xmp_orientation.is_some_and(|xmp| xmp != orientation.unwrap_or(1)) {
+        issues.push(OrientationIssue::XmpConflict);
+    }
+    let fix = if already_upright {
+        Some(OrientationFix::ResetTag)
+    } else if issues.contains(&OrientationIssue::XmpConflict) {
+        Some(OrientationFix::SyncXmp)
+    } else if turned {
+        Some(OrientationFix::Rotate)
+    } else {
+        None
+    };
+
+    OrientationEntry {
+        path: path.to_string_lossy().into_owned(),
+        orientation,
+        xmp_orientation,
+        width: size.map(|size| size.0),
+        height: size.map(|size| size.1),
+        issues,
+        fix,
+    }
+}
+
+/// Lists the images below `root` that are rotated by EXIF, whose pixels
+/// look already rotated, or whose XMP orientation disagrees.
+pub fn audit_orientation(root: &Path) -> Result<OrientationAudit, String> {
+    let mut files = Vec::new();
+    let mut scanned = 0;
+    crate::walk_files(root, &mut |path| {
+        if !crate::is_supported_image(path) {
+            return;
+        }
+        if let Ok(data) = crate::memory_budget::load_scan_data(path) {
+            scanned += 1;
+            let entry = orientation_entry(path, &data);
+            if !entry.issues.is_empty() {
+                files.push(entry);
+            }
+        }
+    });
+    if scanned == 0 {
+        return Err("No images were found in the selected folder.".to_string());
+    }
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(OrientationAudit { files, scanned })
+}
+
+/// Rewrites the XMP orientation of the JPEG at `path` to `orientation`.
+fn write_xmp_orientation(
+    path: &Path,
+    orientation: u16,
+    targets: &EditTargets,
+) -> Result<(), WriteError> {
+    let stamp = FileStamp::of(path)?;
+    let data = crate::load_file_data(path)?;
+    let packet = xmp::jpeg_packet(&data);
+    let value = orientation.to_string();
+    let change = FieldChange {
+        tag: XMP_ORIENTATION.to_string(),
+        before: packet
+            .and_then(|packet| xmp::property(packet, XMP_ORIENTATION))
+            .map(str::to_string),
+        after: Some(value.clone()),
+    };
+    let packet = xmp::with_properties(packet, &[(XMP_ORIENTATION, &value)])?;
+    let updated = xmp::embed_in_jpeg(&data, &packet)?;
+    commit_rewrite(
+        targets.log,
+        targets.mode,
+        "fix_orientation",
+        path,
+        stamp,
+        &updated,
+        vec![change],
+    )
+}
+
+/// Applies the fix the audit suggests for `path`. Only JPEG metadata is
+/// changed; files that need their pixels rotated are reported with
+/// [`OrientationFix::Rotate`] and left alone.
+pub fn fix_file(path: &Path, dry_run: bool, targets: &EditTargets) -> OrientationRepair {
+    let mut repair = OrientationRepair {
+        path: path.to_string_lossy().into_owned(),
+        fix: None,
+        written: false,
+        stored_externally: false,
+        error: None,
+        problem: None,
+    };
+
+    let result = (|| -> Result<(), WriteError> {
+        let stamp = FileStamp::of(path)?;
+        let data = crate::load_file_data(path)?;
+        let entry = orientation_entry(path, &data);
+        repair.fix = entry.fix;
+        if dry_run
+            || !matches!(
+                entry.fix,
+                Some(OrientationFix::ResetTag | OrientationFix::SyncXmp)
+            )
+        {
+            return Ok(());
+        }
+        if !jpeg::is_jpeg(&data) {
+            return Err("Orientation can only be fixed in JPEG files."
+                .to_string()
+                .into());
+        }
+        if entry.fix == Some(OrientationFix::SyncXmp) {
+            write_xmp_orientation(path, entry.orientation.unwrap_or(1), targets)?;
+            repair.written = true;
+            return Ok(());
+        }
+
+        let exif = crate::read_exif_container(&data);
+        let edit = FieldEdit {
+            tag: Tag::Orientation,
+            value: Some(Value::Short(vec![1])),
+        };
+        match commit_edits(
+            targets,
+            "fix_orientation",
+            path,
+            stamp,
+            &data,
+            exif.as_ref(),
+            &[edit],
+        )? {
+            Committed::InPlace(_) => repair.written = true,
+            Committed::External => repair.stored_externally = true,
+        }
+        if repair.written && entry.xmp_orientation.is_some_and(|xmp| xmp != 1) {
+            write_xmp_orientation(path, 1, targets)?;
+        }
+        Ok(())
+    })();
+
+    if let Err(error) = result {
+        repair.error = Some(error.message());
+        repair.problem = error.report();
+    }
+    repair
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        fixtures::JpegBuilder, oplog::OperationLog, settings::EditMode,
+        virtual_fields::VirtualFieldStore,
+    };
+    use exif::Field;
+    use std::fs;
+
+    fn shot(
+        orientation: u16,
+        stored: (u16, u16),
+        recorded: (u32, u32),
+        xmp: Option<&str>,
+    ) -> Vec<u8> {
+        let field = |tag, value| Field {
+            tag,
+            ifd_num: In::PRIMARY,
+            value,
+        };
+        let mut builder = JpegBuilder::new().exif(&[
+            field(Tag::Orientation, Value::Short(vec![orientation])),
+            field(Tag::PixelXDimension, Value::Long(vec![recorded.0])),
+            field(Tag::PixelYDimension, Value::Long(vec![recorded.1])),
+        ]);
+        if let Some(value) = xmp {
+            builder = builder.xmp(&format!(
+                "<x:xmpmeta xmlns:x=\"adobe:ns:meta/\"><rdf:RDF><rdf:Description tiff:Orientation=\"{}\"/></rdf:RDF></x:xmpmeta>",
+                value
+            ));
+        }
+        let mut frame = vec![8];
+        frame.extend_from_slice(&stored.1.to_be_bytes());
+        frame.extend_from_slice(&stored.0.to_be_bytes());
+        frame.extend_from_slice(&[1, 1, 0x11, 0]);
+        builder.segment(0xC0, &frame).finish()
+    }
+
+    #[test]
+    fn issues_and_fixes_are_told_apart() {
+        let camera = orientation_entry(
+            Path::new("a.jpg"),
+            &shot(6, (4000, 3000), (4000, 3000), None),
+        );
+        assert_eq!(camera.issues, [OrientationIssue::Rotated]);
+        assert_eq!(camera.fix, Some(OrientationFix::Rotate));
+
+        let edited = orientation_entry(
+            Path::new("b.jpg"),
+            &shot(6, (3000, 4000), (4000, 3000), Some("6")),
+        );
+        assert_eq!(
+            edited.issues,
+            [OrientationIssue::Rotated, OrientationIssue::PixelsRotated]
+        );
+        assert_eq!(edited.fix, Some(OrientationFix::ResetTag));
+        assert_eq!((edited.width, edited.height), (Some(3000), Some(4000)));
+
+        let conflict =
+            orientation_entry(Path::new("c.jpg"), &shot(1, (40, 30), (40, 30), Some("8")));
+        assert_eq!(conflict.issues, [OrientationIssue::XmpConflict]);
+        assert_eq!(conflict.fix, Some(OrientationFix::SyncXmp));
+        assert_eq!(conflict.xmp_orientation, Some(8));
+
+        assert!(
+            orientation_entry(Path::new("d.jpg"), &shot(1, (40, 30), (40, 30), Some("1")))
+                .issues
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn already_rotated_pixels_get_their_tags_reset() {
+        let dir = crate::fixtures::temp_path("orientation");
+        fs::create_dir_all(&dir).unwrap();
+        let photo = dir.join("edited.jpg");
+        fs::write(&photo, shot(8, (30, 40), (40, 30), Some("8"))).unwrap();
+        let log = OperationLog::open(dir.join("operations.jsonl"));
+        let external = VirtualFieldStore::open(dir.join("virtual-fields.json"));
+        let targets = EditTargets {
+            log: &log,
+            external: &external,
+            mode: EditMode::InPlace,
+        };
+
+        let repair = fix_file(&photo, false, &targets);
+        let fixed = orientation_entry(&photo, &fs::read(&photo).unwrap());
+        let history = log.history(&photo).unwrap();
+        fs::remove_dir_all(&dir).ok();
+
+        assert_eq!(repair.error, None);
+        assert!(repair.written);
+        assert_eq!(
+            (fixed.orientation, fixed.xmp_orientation),
+            (Some(1), Some(1))
+        );
+        assert!(fixed.issues.is_empty());
+        assert_eq!(history.len(), 2);
+    }
+}
@@ -0,0 +1,253 @@
+use crate::{
+    companions::{self, Relationship},
+    oplog::{FieldChange, OperationLog, OperationRecord},
+};
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Folder names below a freedesktop thumbnail cache, one per size.
+const THUMBNAIL_SIZES: [&str; 4] = ["normal", "large", "x-large", "xx-large"];
+/// The per-folder cache the freedesktop spec allows beside the images.
+const SHARED_THUMBNAILS: &str = ".sh_thumbnails";
+const THUMB_URI: &str = "Thumb::URI";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum OrphanKind {
+    /// An XMP, PP3, DxO or AAE file with no image of its name.
+    Sidecar,
+    /// A camera's THM preview with no video or RAW file of its name.
+    CameraThumbnail,
+    /// A cached desktop thumbnail of a file that has been deleted.
+    CachedThumbnail,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Orphan {
+    pub path: String,
+    pub kind: OrphanKind,
+    /// The file it belonged to, as far as its name or cache entry tells.
+    pub missing: String,
+    pub removed: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// The freedesktop thumbnail cache of the current user.
+pub fn user_thumbnail_cache() -> Option<PathBuf> {
+    std::env::var_os("XDG_CACHE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+        .map(|cache| cache.join("thumbnails"))
+}
+
+/// Sidecars and camera thumbnails in `files` (all of one folder) whose
+/// primary file is gone. `IMG_0001.CR2.xmp` needs `IMG_0001.CR2`, while
+/// `IMG_0001.xmp` is satisfied by any non-companion file called `IMG_0001`.
+fn folder_orphans(files: &[PathBuf]) -> Vec<Orphan> {
+    let lower = |name: Option<&std::ffi::OsStr>| {
+        name.map(|name| name.to_string_lossy().to_lowercase())
+            .unwrap_or_default()
+    };
+    let primaries: Vec<(String, String)> = files
+        .iter()
+        .filter(|path| companions::relationship(path).is_none())
+        .map(|path| (lower(path.file_name()), lower(path.file_stem())))
+        .collect();
+    files
+        .iter()
+        .filter_map(|path| {
+            let kind = match companions::relationship(path)? {
+                Relationship::Sidecar => OrphanKind::Sidecar,
+                Relationship::Thumbnail => OrphanKind::CameraThumbnail,
+                _ => return None,
+            };
+            let base = lower(path.file_stem());
+            let found = primaries
+                .iter()
+                .any(|(name, stem)| *name == base || *stem == base);
+            (!found).then(|| Orphan {
+                path: path.to_string_lossy().into_owned(),
+                kind,
+                missing: path.with_extension("").to_string_lossy().into_owned(),
+                removed: false,
+                error: None,
+            })
+        })
+        .collect()
+}
+
+/// The file a cached thumbnail was made from, from its `Thumb::URI` text.
+fn thumbnail_source(thumbnail: &Path) -> Option<PathBuf> {
+    let data = fs::read(thumbnail).ok()?;
+    let uri = crate::parse_png_text_chunks(&data)
+        .into_iter()
+        .find(|field| field.tag == THUMB_URI)?
+        .value;
+    url::Url::parse(&uri).ok()?.to_file_path().ok()
+}
+
+/// Cached thumbnails in `cache` of files below `root` that no longer exist.
+fn cache_orphans(cache: &Path, root: &Path) -> Vec<Orphan> {
+    THUMBNAIL_SIZES
+        .iter()
+        .filter_map(|size| fs::read_dir(cache.join(size)).ok())
+        .flatten()
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "png"))
+        .filter_map(|path| {
+            let source = thumbnail_source(&path)?;
+            (source.starts_with(root) && !source.exists()).then(|| Orphan {
+                path: path.to_string_lossy().into_owned(),
+                kind: OrphanKind::CachedThumbnail,
+                missing: source.to_string_lossy().into_owned(),
+                removed: false,
+                error: None,
+            })
+        })
+        .collect()
+}
+
+/// Orphaned sidecars and camera thumbnails below `root`, then stale entries
+/// of the `.sh_thumbnails` caches below it and of `user_cache`.
+pub fn find(root: &Path, user_cache: Option<&Path>) -> Vec<Orphan> {
+    let mut folders: BTreeMap<PathBuf, Vec<PathBuf>> = BTreeMap::new();
+    let mut shared_caches = Vec::new();
+    crate::walk_files(root, &mut |path| {
+        let Some(folder) = path.parent() else {
+            return;
+        };
+        if let Some(cache) = folder.parent().filter(|cache| {
+            cache
+                .file_name()
+                .is_some_and(|name| name == SHARED_THUMBNAILS)
+        }) {
+            shared_caches.push(cache.to_path_buf());
+        }
+        folders
+            .entry(folder.to_path_buf())
+            .or_default()
+            .push(path.to_path_buf());
+    });
+    shared_caches.sort();
+    shared_caches.dedup();
+
+    let mut orphans: Vec<Orphan> = folders
+        .values_mut()
+        .flat_map(|files| {
+            files.sort();
+            folder_orphans(files)
+        })
+        .collect();
+    for cache in shared_caches.iter().map(PathBuf::as_path).chain(user_cache) {
+        orphans.extend(cache_orphans(cache, root));
+    }
+    orphans
+}
+
+/// Deletes each orphan, noting on it whether that worked and recording the
+/// removal in `log`. Orphans outside the folders the app may write are left
+/// alone, except entries of `user_cache`, which only holds thumbnails.
+pub fn remove(orphans: &mut [Orphan], user_cache: Option<&Path>, log: &OperationLog) {
+    for orphan in orphans {
+        let path = Path::new(&orphan.path);
+        let in_cache = orphan.kind == OrphanKind::CachedThumbnail
+            && user_cache.is_some_and(|cache| path.starts_with(cache));
+        if !in_cache {
+            if let Err(violation) = crate::path_scope::check_write(path) {
+                orphan.error = Some(violation.message());
+                continue;
+            }
+        }
+        if let Err(error) = fs::remove_file(path) {
+            orphan.error = Some(format!("Unable to remove {}: {}", orphan.path, error));
+            continue;
+        }
+        orphan.removed = true;
+        let change = FieldChange {
+            tag: "File".to_string(),
+            before: Some(orphan.path.clone()),
+            after: None,
+        };
+        if let Err(error) = log.record(&OperationRecord::new("remove", path, vec![change])) {
+            orphan.error = Some(error);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A PNG holding only a `Thumb::URI` text chunk.
+    fn cached_thumbnail(uri: &str) -> Vec<u8> {
+        let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+        let text = format!("{}\0{}", THUMB_URI, uri);
+        png.extend_from_slice(&(text.len() as u32).to_be_bytes());
+        png.extend_from_slice(b"tEXt");
+        png.extend_from_slice(text.as_bytes());
+        png.extend_from_slice(&[0; 4]);
+        png
+    }
+
+    #[test]
+    fn sidecars_and_thumbnails_without_their_image_are_orphans() {
+        let root = crate::fixtures::temp_path("orphans");
+        let cache = root.join(".sh_thumbnails").join("normal");
+        fs::create_dir_all(&cache).unwrap();
+        for name in [
+            "IMG_0001.CR2",
+            "IMG_0001.CR2.xmp",
+            "IMG_0002.xmp",
+            "IMG_0003.CR2.pp3",
+            "MVI_0004.MOV",
+            "MVI_0004.THM",
+            "MVI_0005.THM",
+        ] {
+            fs::write(root.join(name), "").unwrap();
+        }
+        let uri = |name: &str| {
+            url::Url::from_file_path(root.join(name))
+                .unwrap()
+                .to_string()
+        };
+        fs::write(cache.join("a.png"), cached_thumbnail(&uri("IMG_0001.CR2"))).unwrap();
+        fs::write(cache.join("b.png"), cached_thumbnail(&uri("IMG_0009.JPG"))).unwrap();
+
+        let mut orphans = find(&root, None);
+        let names: Vec<(String, OrphanKind)> = orphans
+            .iter()
+            .map(|orphan| {
+                let name = Path::new(&orphan.path).file_name().unwrap();
+                (name.to_string_lossy().into_owned(), orphan.kind)
+            })
+            .collect();
+        remove(
+            &mut orphans,
+            None,
+            &OperationLog::open(root.join("operations.jsonl")),
+        );
+        let cleaned = !root.join("IMG_0002.xmp").exists() && root.join("IMG_0001.CR2.xmp").exists();
+        fs::remove_dir_all(&root).ok();
+
+        assert_eq!(
+            names,
+            [
+                ("IMG_0002.xmp".to_string(), OrphanKind::Sidecar),
+                ("IMG_0003.CR2.pp3".to_string(), OrphanKind::Sidecar),
+                ("MVI_0005.THM".to_string(), OrphanKind::CameraThumbnail),
+                ("b.png".to_string(), OrphanKind::CachedThumbnail),
+            ]
+        );
+        assert!(orphans.iter().all(|orphan| orphan.removed));
+        assert!(cleaned);
+        assert!(orphans[2].missing.ends_with("MVI_0005"));
+    }
+}
@@ -0,0 +1,324 @@
+use serde::Serialize;
+use std::{
+    fs,
+    path::{Component, Path, PathBuf},
+    sync::{OnceLock, RwLock},
+};
+
+/// Why a path was refused, for the UI to explain.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase", tag = "kind")]
+pub enum ScopeViolation {
+    /// The path lies outside the allowed folders.
+    OutsideScope { path: String },
+    /// The path looks inside the allowed folders, but a symlink on the way
+    /// leads out of them.
+    SymlinkEscape { path: String, target: String },
+    /// `..` in a part of the path that does not exist yet, which could only
+    /// come from a template or a crafted request.
+    Traversal { path: String },
+    /// A write into an Apple Photos library, whose files only Photos may change.
+    ManagedLibrary { path: String },
+}
+
+impl ScopeViolation {
+    pub fn message(&self) -> String {
+        match self {
+            ScopeViolation::OutsideScope { path } => {
+                format!("{} is outside the folders the app is allowed to access.", path)
+            }
+            ScopeViolation::SymlinkEscape { path, target } => format!(
+                "{} leads to {} through a symbolic link, outside the folders the app is allowed to access.",
+                path, target
+            ),
+            ScopeViolation::Traversal { path } => {
+                format!("{} climbs out of its folder with \"..\".", path)
+            }
+            ScopeViolation::ManagedLibrary { path } => format!(
+                "{} is inside an Apple Photos library, which only Photos may change; export the photo to edit it.",
+                path
+            ),
+        }
+    }
+}
+
+/// An allowed folder as configured and with its symlinks resolved.
+struct Root {
+    configured: PathBuf,
+    canonical: PathBuf,
+}
+
+impl Root {
+    fn open(root: &str) -> Option<Root> {
+        let canonical = fs::canonicalize(root).ok().filter(|root| root.is_dir())?;
+        Some(Root {
+            configured: lexical_absolute(Path::new(root))?,
+            canonical,
+        })
+    }
+
+    /// Whether `path` is inside the root going by its name alone.
+    fn lexically_holds(&self, path: &Path) -> bool {
+        path.starts_with(&self.configured) || path.starts_with(&self.canonical)
+    }
+}
+
+/// Folders commands may read from and write to. With no roots configured
+/// every path is allowed, which is how the app behaved before scopes existed.
+pub struct PathScope {
+    /// `None` while unrestricted.
+    roots: RwLock<Option<Vec<Root>>>,
+}
+
+impl PathScope {
+    fn new() -> Self {
+        PathScope {
+            roots: RwLock::new(None),
+        }
+    }
+
+    /// Replaces the allowed roots. Each must be an existing folder; an empty
+    /// list lifts the restriction.
+    pub fn set_roots(&self, roots: &[String]) -> Result<(), String> {
+        let resolved = roots
+            .iter()
+            .map(|root| {
+                Root::open(root).ok_or_else(|| format!("{} is not an existing folder.", root))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        *self.roots.write().expect("path scope poisoned") =
+            (!resolved.is_empty()).then_some(resolved);
+        Ok(())
+    }
+
+    /// Reapplies roots saved in the settings. Roots that have gone missing,
+    /// such as an unplugged drive, are left out rather than lifting the
+    /// restriction, so if none are left nothing is allowed.
+    pub fn restore(&self, roots: &[String]) {
+        let resolved: Vec<Root> = roots
+            .iter()
+            .filter_map(|root| {
+                let resolved = Root::open(root);
+                if resolved.is_none() {
+                    tracing::warn!(
+                        folder = %crate::logging::file_name(Path::new(root)),
+                        "an allowed folder is missing"
+                    );
+                }
+                resolved
+            })
+            .collect();
+        *self.roots.write().expect("path scope poisoned") = (!roots.is_empty()).then_some(resolved);
+    }
+
+    /// Fails unless `path`, once symlinks and `..` are resolved, lies inside
+    /// one of the roots.
+    pub fn check(&self, path: &Path) -> Result<(), String> {
+        self.violation(path, false)
+            .map_or(Ok(()), |violation| Err(violation.message()))
+    }
+
+    /// Like [`PathScope::check`] for a file about to be written or created.
+    /// `..` in the part of the path that does not exist yet is refused even
+    /// without roots.
+    pub fn check_write(&self, path: &Path) -> Result<(), ScopeViolation> {
+        if crate::apple_photos::library_of(path).is_some() {
+            return Err(ScopeViolation::ManagedLibrary {
+                path: path.display().to_string(),
+            });
+        }
+        self.violation(path, true).map_or(Ok(()), Err)
+    }
+
+    fn violation(&self, path: &Path, write: bool) -> Option<ScopeViolation> {
+        let roots = self.roots.read().expect("path scope poisoned");
+        let shown = path.display().to_string();
+        let resolved = match resolve(path) {
+            Ok(resolved) => Some(resolved),
+            Err(violation @ ScopeViolation::Traversal { .. }) if write || roots.is_some() => {
+                return Some(violation);
+            }
+            Err(_) => None,
+        };
+        let roots = roots.as_ref()?;
+        let violation = match resolved {
+            Some(resolved)
+                if roots
+                    .iter()
+                    .any(|root| resolved.starts_with(&root.canonical)) =>
+            {
+                return None;
+            }
+            Some(resolved)
+                if lexical_absolute(path).is_some_and(|lexical| {
+                    roots.iter().any(|root| root.lexically_holds(&lexical))
+                }) =>
+            {
+                ScopeViolation::SymlinkEscape {
+                    path: shown,
+                    target: resolved.display().to_string(),
+                }
+            }
+            _ => ScopeViolation::OutsideScope { path: shown },
+        };
+        tracing::warn!(
+            file = %crate::logging::file_name(path),
+            ?violation,
+            write,
+            "refused a path outside the allowed folders"
+        );
+        Some(violation)
+    }
+}
+
+fn absolute(path: &Path) -> Option<PathBuf> {
+    if path.is_absolute() {
+        Some(path.to_path_buf())
+    } else {
+        Some(std::env::current_dir().ok()?.join(path))
+    }
+}
+
+/// `path` made absolute with `.` and `..` folded away, without looking at
+/// the file system.
+fn lexical_absolute(path: &Path) -> Option<PathBuf> {
+    let mut folded = PathBuf::new();
+    for component in absolute(path)?.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                folded.pop();
+            }
+            other => folded.push(other),
+        }
+    }
+    Some(folded)
+}
+
+/// The real location of `path`. Paths that do not exist yet, such as export
+/// targets, resolve through their nearest existing ancestor; `..` is not
+/// allowed in the part that does not exist.
+fn resolve(path: &Path) -> Result<PathBuf, ScopeViolation> {
+    let outside = || ScopeViolation::OutsideScope {
+        path: path.display().to_string(),
+    };
+    let absolute = absolute(path).ok_or_else(outside)?;
+    let existing = absolute
+        .ancestors()
+        .find(|ancestor| ancestor.exists())
+        .ok_or_else(outside)?;
+    let rest = absolute.strip_prefix(existing).map_err(|_| outside())?;
+    if rest
+        .components()
+        .any(|component| !matches!(component, Component::Normal(_)))
+    {
+        return Err(ScopeViolation::Traversal {
+            path: path.display().to_string(),
+        });
+    }
+    Ok(fs::canonicalize(existing)
+        .map_err(|_| outside())?
+        .join(rest))
+}
+
+/// The scope every command is checked against.
+pub fn global() -> &'static PathScope {
+    static SCOPE: OnceLock<PathScope> = OnceLock::new();
+    SCOPE.get_or_init(PathScope::new)
+}
+
+/// Checks each of `paths` against the global scope.
+pub fn check_all<P: AsRef<Path>>(paths: impl IntoIterator<Item = P>) -> Result<(), String> {
+    paths
+        .into_iter()
+        .try_for_each(|path| global().check(path.as_ref()))
+}
+
+/// Checks a file about to be written against the global scope.
+pub fn check_write(path: &Path) -> Result<(), ScopeViolation> {
+    global().check_write(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_paths_inside_a_root_pass() {
+        let base = crate::fixtures::temp_path("scope");
+        let photos = base.join("photos");
+        let private = base.join("private");
+        fs::create_dir_all(&photos).unwrap();
+        fs::create_dir_all(&private).unwrap();
+        fs::write(photos.join("a.jpg"), b"").unwrap();
+        fs::write(private.join("secret.txt"), b"").unwrap();
+
+        let scope = PathScope::new();
+        assert!(scope.check(&private.join("secret.txt")).is_ok());
+        scope
+            .set_roots(&[photos.to_string_lossy().into_owned()])
+            .unwrap();
+
+        let inside = [photos.join("a.jpg"), photos.join("exports/new.gpx")];
+        let outside = [
+            private.join("secret.txt"),
+            photos.join("../private/secret.txt"),
+            photos.join("missing/../../private/secret.txt"),
+        ];
+        let inside_ok: Vec<bool> = inside
+            .iter()
+            .map(|path| scope.check(path).is_ok())
+            .collect();
+        let outside_ok: Vec<bool> = outside
+            .iter()
+            .map(|path| scope.check(path).is_ok())
+            .collect();
+        // A link inside a root does not lead out of it.
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(&private, photos.join("link")).unwrap();
+            assert!(scope.check(&photos.join("link/secret.txt")).is_err());
+        }
+        let missing = base.join("nope").to_string_lossy().into_owned();
+        let bad_root = scope.set_roots(std::slice::from_ref(&missing));
+        scope.restore(&[missing]);
+        let after_restore = scope.check(&photos.join("a.jpg"));
+        fs::remove_dir_all(&base).ok();
+
+        assert_eq!(inside_ok, [true, true]);
+        assert_eq!(outside_ok, [false, false, false]);
+        assert!(bad_root.is_err());
+        // Only a missing root was saved: nothing is allowed rather than everything.
+        assert!(after_restore.is_err());
+    }
+
+    #[test]
+    fn refused_writes_say_why() {
+        let base = crate::fixtures::temp_path("scope_write");
+        let photos = base.join("photos");
+        let private = base.join("private");
+        fs::create_dir_all(&photos).unwrap();
+        fs::create_dir_all(&private).unwrap();
+
+        let scope = PathScope::new();
+        let traversal = scope.check_write(&photos.join("new/../../escape.jpg"));
+        scope
+            .set_roots(&[photos.to_string_lossy().into_owned()])
+            .unwrap();
+        let outside = scope.check_write(&private.join("out.gpx"));
+        #[cfg(unix)]
+        let escape = {
+            std::os::unix::fs::symlink(&private, photos.join("link")).unwrap();
+            scope.check_write(&photos.join("link/out.gpx"))
+        };
+        let inside = scope.check_write(&photos.join("out.gpx"));
+        fs::remove_dir_all(&base).ok();
+
+        // `..` through a folder that does not exist is refused even unscoped.
+        assert!(matches!(traversal, Err(ScopeViolation::Traversal { .. })));
+        assert!(matches!(outside, Err(ScopeViolation::OutsideScope { .. })));
+        #[cfg(unix)]
+        assert!(matches!(escape, Err(ScopeViolation::SymlinkEscape { .. })));
+        assert!(inside.is_ok());
+    }
+}
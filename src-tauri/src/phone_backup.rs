@@ -0,0 +1,270 @@
+use crate::ExifField;
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+    sync::{Mutex, OnceLock},
+};
+
+pub const COMPUTED_BACKUP_IFD: &str = "Backup";
+
+const MANIFEST: &str = "Manifest.db";
+/// The manifest of backups made before iOS 10, a binary format of its own.
+const LEGACY_MANIFEST: &str = "Manifest.mbdb";
+/// Encrypted backups keep an encrypted manifest, which lacks this header.
+const SQLITE_HEADER: &[u8] = b"SQLite format 3\0";
+
+/// One file row of the backup manifest.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ManifestEntry {
+    /// The SHA-1 the file is stored under.
+    pub file_id: String,
+    /// Such as `CameraRollDomain` or `AppDomainGroup-group.net.whatsapp.WhatsApp.shared`.
+    pub domain: String,
+    pub relative_path: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupPhoto {
+    /// The hashed file in the backup, for `read_exif` and the other readers.
+    pub path: String,
+    pub domain: String,
+    /// Where the file was on the phone, such as `Media/DCIM/100APPLE/IMG_0042.HEIC`.
+    pub original_path: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PhoneBackup {
+    pub photos: Vec<BackupPhoto>,
+    /// Images the manifest lists that are not in the backup folder, as after
+    /// an interrupted backup.
+    pub missing: usize,
+}
+
+/// The images of every backup listed so far, by their hashed path. The
+/// hashed names have no extension, so scans tell images apart by these.
+static LISTED: OnceLock<Mutex<HashMap<PathBuf, BackupPhoto>>> = OnceLock::new();
+
+fn listed() -> &'static Mutex<HashMap<PathBuf, BackupPhoto>> {
+    LISTED.get_or_init(Default::default)
+}
+
+/// What the manifest says about `file`, if it is an image of a backup that
+/// was listed or walked before.
+pub fn listed_photo(file: &Path) -> Option<BackupPhoto> {
+    let listed = listed().lock().expect("backup listing poisoned");
+    listed.get(file).cloned()
+}
+
+/// "Path on phone" and "Domain" for an image of a backup.
+pub fn computed_fields(photo: Option<&BackupPhoto>) -> Vec<ExifField> {
+    let Some(photo) = photo else {
+        return Vec::new();
+    };
+    [
+        ("Path on phone", &photo.original_path),
+        ("Domain", &photo.domain),
+    ]
+    .into_iter()
+    .map(|(tag, value)| ExifField {
+        tag: tag.to_string(),
+        ifd: COMPUTED_BACKUP_IFD.to_string(),
+        value: value.clone(),
+    })
+    .collect()
+}
+
+/// Whether `path` is an iTunes or Finder backup of an iPhone or iPad.
+pub fn is_backup(path: &Path) -> bool {
+    path.join(MANIFEST).is_file() || path.join(LEGACY_MANIFEST).is_file()
+}
+
+/// Where the backup keeps `file_id`: in a folder named after its first two
+/// characters since iOS 10, at the top before.
+fn stored_file(backup: &Path, file_id: &str) -> Option<PathBuf> {
+    let folder = file_id.get(..2)?;
+    [backup.join(folder).join(file_id), backup.join(file_id)]
+        .into_iter()
+        .find(|candidate| candidate.is_file())
+}
+
+/// The images among `entries`, by the extension of their path on the phone,
+/// and how many of them the backup lacks.
+fn photos(backup: &Path, entries: Vec<ManifestEntry>) -> (Vec<BackupPhoto>, usize) {
+    let mut photos = Vec::new();
+    let mut missing = 0;
+    for entry in entries {
+        if !crate::is_supported_image(Path::new(&entry.relative_path)) {
+            continue;
+        }
+        match stored_file(backup, &entry.file_id) {
+            Some(path) => photos.push(BackupPhoto {
+                path: path.to_string_lossy().into_owned(),
+                domain: entry.domain,
+                original_path: entry.relative_path,
+            }),
+            None => missing += 1,
+        }
+    }
+    photos.sort_by(|a, b| (&a.domain, &a.original_path).cmp(&(&b.domain, &b.original_path)));
+    let mut listed = listed().lock().expect("backup listing poisoned");
+    listed.extend(
+        photos
+            .iter()
+            .map(|photo| (PathBuf::from(&photo.path), photo.clone())),
+    );
+    (photos, missing)
+}
+
+/// Lists the images of the backup at `backup` under the names they had on
+/// the phone, so the hashed files can be read and scanned without renaming
+/// them. The backup is only read.
+pub fn list(backup: &Path) -> Result<PhoneBackup, String> {
+    if !is_backup(backup) {
+        return Err("The selected folder is not an iPhone or iPad backup.".to_string());
+    }
+    let manifest = backup.join(MANIFEST);
+    if !manifest.is_file() {
+        return Err("Backups made before iOS 10 are not supported.".to_string());
+    }
+    let mut header = [0; 16];
+    File::open(&manifest)
+        .and_then(|mut file| file.read_exact(&mut header))
+        .map_err(|error| format!("Unable to read the backup manifest: {}", error))?;
+    if header != SQLITE_HEADER {
+        return Err(
+            "The backup is encrypted. Decrypt it, or make an unencrypted backup, and try again."
+                .to_string(),
+        );
+    }
+    let (photos, missing) = photos(backup, manifest_db::files(&manifest)?);
+    Ok(PhoneBackup { photos, missing })
+}
+
+/// The SQLite side, compiled only with the `phone-backup` feature.
+#[cfg(feature = "phone-backup")]
+mod manifest_db {
+    use super::ManifestEntry;
+    use rusqlite::{Connection, OpenFlags};
+    use std::path::Path;
+
+    /// `flags` is 1 for files, 2 for folders and 4 for symbolic links.
+    const FILE_FLAG: i64 = 1;
+
+    fn describe(error: rusqlite::Error) -> String {
+        format!("Unable to read the backup manifest: {}", error)
+    }
+
+    pub fn files(manifest: &Path) -> Result<Vec<ManifestEntry>, String> {
+        // `immutable` keeps SQLite from creating journal files, so the
+        // backup folder is left exactly as it was.
+        let uri = format!(
+            "file:{}?immutable=1",
+            manifest
+                .to_string_lossy()
+                .replace('?', "%3f")
+                .replace('#', "%23")
+        );
+        let connection = Connection::open_with_flags(
+            uri,
+            OpenFlags::SQLITE_OPEN_READ_ONLY
+                | OpenFlags::SQLITE_OPEN_URI
+                | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )
+        .map_err(describe)?;
+        let mut statement = connection
+            .prepare(
+                "SELECT fileID, domain, relativePath FROM Files
+                 WHERE flags = ?1 AND relativePath IS NOT NULL",
+            )
+            .map_err(describe)?;
+        let rows = statement
+            .query_map([FILE_FLAG], |row| {
+                Ok(ManifestEntry {
+                    file_id: row.get(0)?,
+                    domain: row.get::<_, Option<String>>(1)?.unwrap_or_default(),
+                    relative_path: row.get(2)?,
+                })
+            })
+            .map_err(describe)?
+            .collect::<Result<_, _>>()
+            .map_err(describe)?;
+        Ok(rows)
+    }
+}
+
+#[cfg(not(feature = "phone-backup"))]
+mod manifest_db {
+    use super::ManifestEntry;
+    use std::path::Path;
+
+    pub fn files(_manifest: &Path) -> Result<Vec<ManifestEntry>, String> {
+        Err(
+            "This build was made without iPhone backup support (the `phone-backup` feature)."
+                .to_string(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn hashed_files_are_listed_under_their_phone_paths() {
+        let backup = crate::fixtures::temp_path("backup");
+        fs::create_dir_all(backup.join("3d")).unwrap();
+        fs::write(
+            backup.join("3d/3d0d7e5fb2ce288813306e4d4636395e047a3d28"),
+            b"",
+        )
+        .unwrap();
+        fs::write(backup.join(MANIFEST), b"encrypted bytes here").unwrap();
+
+        let entry = |file_id: &str, relative_path: &str| ManifestEntry {
+            file_id: file_id.to_string(),
+            domain: "CameraRollDomain".to_string(),
+            relative_path: relative_path.to_string(),
+        };
+        let (listed, missing) = photos(
+            &backup,
+            vec![
+                entry(
+                    "3d0d7e5fb2ce288813306e4d4636395e047a3d28",
+                    "Media/DCIM/100APPLE/IMG_0042.HEIC",
+                ),
+                entry(
+                    "240b1770ee1ad3e2ae4e0ed3ee5fb9c486a1f7f2",
+                    "Media/DCIM/100APPLE/IMG_0043.JPG",
+                ),
+                entry(
+                    "12b144c0bd44f2b3dffd9186d3f9c05b917cee25",
+                    "Media/PhotoData/Photos.sqlite",
+                ),
+            ],
+        );
+        let encrypted = list(&backup);
+        let detected = is_backup(&backup);
+        fs::remove_dir_all(&backup).ok();
+
+        assert!(detected);
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].original_path, "Media/DCIM/100APPLE/IMG_0042.HEIC");
+        assert!(listed[0]
+            .path
+            .ends_with("3d0d7e5fb2ce288813306e4d4636395e047a3d28"));
+        assert_eq!(missing, 1);
+        assert!(encrypted.unwrap_err().contains("encrypted"));
+        let hashed = Path::new(&listed[0].path);
+        assert!(crate::is_supported_image(hashed));
+        assert_eq!(
+            computed_fields(listed_photo(hashed).as_ref())[0].value,
+            "Media/DCIM/100APPLE/IMG_0042.HEIC"
+        );
+    }
+}
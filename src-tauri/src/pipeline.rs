@@ -0,0 +1,590 @@
+use crate::{
+    capture::capture_time,
+    commit::{commit_edits, EditTargets},
+    geo::{self, GeoFormat, GeoPaths},
+    gps,
+    oplog::{FieldChange, OperationRecord},
+    path_scope::{self, ScopeViolation},
+    settings::EditMode,
+    strip, timezone,
+    track::{self, TrackFormat},
+    weather::{self, CsvWeatherProvider},
+    write_lock::{FileStamp, WriteError},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+/// One operation in a recipe. Each step sees the paths left by the previous one.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "op")]
+pub enum PipelineOp {
+    /// Removes every GPS tag.
+    StripGps,
+    /// Renames files from a template using `{name}`, `{ext}`, `{index}` and `{date}`.
+    Rename {
+        template: String,
+    },
+    FixTimezones,
+    EnrichWeather {
+        dataset: String,
+    },
+    ExportTrack {
+        format: TrackFormat,
+        output: Option<String>,
+    },
+    ExportGeo {
+        format: GeoFormat,
+        /// What each photo's `path` property holds; none by default.
+        #[serde(default)]
+        paths: GeoPaths,
+        output: Option<String>,
+    },
+}
+
+impl PipelineOp {
+    pub fn label(&self) -> &'static str {
+        match self {
+            PipelineOp::StripGps => "Strip GPS",
+            PipelineOp::Rename { .. } => "Rename",
+            PipelineOp::FixTimezones => "Fix timezones",
+            PipelineOp::EnrichWeather { .. } => "Enrich weather",
+            PipelineOp::ExportTrack { .. } => "Export track",
+            PipelineOp::ExportGeo { .. } => "Export map",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PipelineStep {
+    #[serde(flatten)]
+    pub op: PipelineOp,
+    /// Report what the step would do without changing anything.
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// A named, saved chain of operations.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Pipeline {
+    pub name: String,
+    pub steps: Vec<PipelineStep>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PipelineProgress {
+    pub pipeline: String,
+    pub step: usize,
+    pub step_count: usize,
+    pub label: String,
+    pub completed: usize,
+    pub total: usize,
+    /// The file the step just finished, for steps that go file by file.
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StepReport {
+    label: String,
+    dry_run: bool,
+    succeeded: usize,
+    errors: Vec<String>,
+    /// Files the step created, such as exports or renamed images.
+    outputs: Vec<String>,
+    /// Writes refused for leaving the allowed folders; also listed in `errors`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    refused: Vec<ScopeViolation>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PipelineRun {
+    pipeline: String,
+    steps: Vec<StepReport>,
+}
+
+/// Saved recipes, persisted as JSON in the app data directory.
+pub struct PipelineStore {
+    location: PathBuf,
+    pipelines: Mutex<BTreeMap<String, Pipeline>>,
+}
+
+impl PipelineStore {
+    pub fn open(location: PathBuf) -> Self {
+        let pipelines = fs::read(&location)
+            .ok()
+            .and_then(|data| serde_json::from_slice(&data).ok())
+            .unwrap_or_default();
+        PipelineStore {
+            location,
+            pipelines: Mutex::new(pipelines),
+        }
+    }
+
+    pub fn list(&self) -> Vec<Pipeline> {
+        let pipelines = self.pipelines.lock().expect("pipeline store poisoned");
+        pipelines.values().cloned().collect()
+    }
+
+    pub fn get(&self, name: &str) -> Option<Pipeline> {
+        let pipelines = self.pipelines.lock().expect("pipeline store poisoned");
+        pipelines.get(name).cloned()
+    }
+
+    pub fn save(&self, pipeline: Pipeline) -> Result<Vec<Pipeline>, String> {
+        if pipeline.name.trim().is_empty() {
+            return Err("The pipeline needs a name.".to_string());
+        }
+        if pipeline.steps.is_empty() {
+            return Err("The pipeline needs at least one step.".to_string());
+        }
+        self.modify(|pipelines| {
+            pipelines.insert(pipeline.name.clone(), pipeline);
+        })
+    }
+
+    pub fn delete(&self, name: &str) -> Result<Vec<Pipeline>, String> {
+        self.modify(|pipelines| {
+            pipelines.remove(name);
+        })
+    }
+
+    fn modify(
+        &self,
+        change: impl FnOnce(&mut BTreeMap<String, Pipeline>),
+    ) -> Result<Vec<Pipeline>, String> {
+        let mut pipelines = self.pipelines.lock().expect("pipeline store poisoned");
+        change(&mut pipelines);
+        if let Some(parent) = self.location.parent() {
+            fs::create_dir_all(parent).map_err(|error| error.to_string())?;
+        }
+        let data = serde_json::to_vec_pretty(&*pipelines).map_err(|error| error.to_string())?;
+        crate::write_file_atomically(&self.location, &data)?;
+        Ok(pipelines.values().cloned().collect())
+    }
+}
+
+fn strip_gps(path: &Path, dry_run: bool, targets: &EditTargets) -> Result<(), WriteError> {
+    let stamp = FileStamp::of(path)?;
+    let data = crate::load_file_data(path)?;
+    let Some(exif) = crate::read_exif_container(&data) else {
+        return Ok(());
+    };
+    let edits = strip::gps_edits(&exif);
+    if edits.is_empty() || dry_run {
+        return Ok(());
+    }
+    commit_edits(
+        targets,
+        "strip_gps",
+        path,
+        stamp,
+        &data,
+        Some(&exif),
+        &edits,
+    )?;
+    Ok(())
+}
+
+/// Expands a rename template for the `index`th of `total` files.
+pub fn rename_target(
+    path: &Path,
+    template: &str,
+    index: usize,
+    total: usize,
+) -> Result<PathBuf, String> {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let date = crate::load_file_data(path)
+        .ok()
+        .and_then(|data| crate::read_exif_container(&data))
+        .and_then(|exif| capture_time(&exif))
+        .map(|time| time.local.format("%Y-%m-%d").to_string())
+        .unwrap_or_else(|| "undated".to_string());
+    let width = total.to_string().len();
+
+    let name = template
+        .replace("{name}", &stem)
+        .replace("{ext}", &extension)
+        .replace("{index}", &format!("{:0width$}", index + 1, width = width))
+        .replace("{date}", &date);
+    if name.is_empty() || name.contains(['/', '\\']) || name == "." || name == ".." {
+        return Err(format!(
+            "The template produced an invalid file name: {:?}",
+            name
+        ));
+    }
+    Ok(path.with_file_name(name))
+}
+
+/// Moves `path` to `target` without ever replacing a file that appears there
+/// in the meantime. A hard link only succeeds while `target` is free; where
+/// hard links are unsupported, `target` is claimed as an empty file first.
+fn rename_no_clobber(path: &Path, target: &Path) -> io::Result<()> {
+    match fs::hard_link(path, target) {
+        Ok(()) => fs::remove_file(path).inspect_err(|_| {
+            fs::remove_file(target).ok();
+        }),
+        Err(error) if error.kind() == io::ErrorKind::AlreadyExists => Err(error),
+        Err(_) => {
+            fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(target)?;
+            fs::rename(path, target).inspect_err(|_| {
+                fs::remove_file(target).ok();
+            })
+        }
+    }
+}
+
+fn rename_file(
+    path: &Path,
+    target: &Path,
+    dry_run: bool,
+    targets: &EditTargets,
+) -> Result<(), String> {
+    if targets.mode == EditMode::ExternalOnly {
+        return Err("Renaming is disabled while originals are protected.".to_string());
+    }
+    if target.exists() && target != path {
+        return Err(format!("{} already exists.", target.display()));
+    }
+    if dry_run {
+        return Ok(());
+    }
+    if target != path {
+        rename_no_clobber(path, target).map_err(|error| match error.kind() {
+            io::ErrorKind::AlreadyExists => format!("{} already exists.", target.display()),
+            _ => format!("Unable to rename the file: {}", error),
+        })?;
+    }
+    let change = FieldChange {
+        tag: "File name".to_string(),
+        before: Some(path.to_string_lossy().into_owned()),
+        after: Some(target.to_string_lossy().into_owned()),
+    };
+    targets
+        .log
+        .record(&OperationRecord::new("rename", target, vec![change]))
+}
+
+fn export_output(output: &Option<String>, paths: &[PathBuf], extension: &str) -> PathBuf {
+    match output {
+        Some(output) => PathBuf::from(output),
+        None => {
+            let folder = paths
+                .first()
+                .and_then(|path| path.parent())
+                .unwrap_or_else(|| Path::new("."));
+            crate::default_export_path(folder, extension)
+        }
+    }
+}
+
+/// Runs `step` over `paths`, returning its report and the paths for the next step.
+fn run_step(
+    step: &PipelineStep,
+    paths: Vec<PathBuf>,
+    targets: &EditTargets,
+    progress: &mut dyn FnMut(usize, usize, Option<&Path>),
+) -> (StepReport, Vec<PathBuf>) {
+    let mut report = StepReport {
+        label: step.op.label().to_string(),
+        dry_run: step.dry_run,
+        succeeded: 0,
+        errors: Vec::new(),
+        outputs: Vec::new(),
+        refused: Vec::new(),
+    };
+    let total = paths.len();
+
+    let next = match &step.op {
+        PipelineOp::StripGps => {
+            for (index, path) in paths.iter().enumerate() {
+                let result =
+                    strip_gps(path, step.dry_run, targets).map_err(|error| error.message());
+                record(&mut report, path, result);
+                progress(index + 1, total, Some(path));
+            }
+            paths
+        }
+        PipelineOp::Rename { template } => {
+            let mut renamed = Vec::with_capacity(total);
+            for (index, path) in paths.iter().enumerate() {
+                let result = rename_target(path, template, index, total).and_then(|target| {
+                    check_write(&mut report, &target)?;
+                    rename_file(path, &target, step.dry_run, targets)?;
+                    report.outputs.push(target.to_string_lossy().into_owned());
+                    Ok(target)
+                });
+                // A dry run leaves files where they are for the following steps.
+                renamed.push(match &result {
+                    Ok(target) if !step.dry_run => target.clone(),
+                    _ => path.clone(),
+                });
+                record(&mut report, path, result.map(|_| ()));
+                progress(index + 1, total, renamed.last().map(PathBuf::as_path));
+            }
+            renamed
+        }
+        PipelineOp::FixTimezones => {
+            for (index, path) in paths.iter().enumerate() {
+                let fix = timezone::fix_file(path, step.dry_run, targets);
+                let result = fix.error().map_or(Ok(()), |error| Err(error.to_string()));
+                record(&mut report, path, result);
+                progress(index + 1, total, Some(path));
+            }
+            paths
+        }
+        PipelineOp::EnrichWeather { dataset } => {
+            let dataset = Path::new(dataset);
+            match path_scope::global()
+                .check(dataset)
+                .and_then(|_| CsvWeatherProvider::load(dataset))
+            {
+                Ok(provider) => {
+                    for (index, path) in paths.iter().enumerate() {
+                        let enrichment =
+                            weather::enrich_file(path, &provider, targets.external, step.dry_run);
+                        let result = enrichment
+                            .error()
+                            .map_or(Ok(()), |error| Err(error.to_string()));
+                        record(&mut report, path, result);
+                        progress(index + 1, total, Some(path));
+                    }
+                }
+                Err(error) => report.errors.push(error),
+            }
+            paths
+        }
+        PipelineOp::ExportTrack { format, output } => {
+            let output = export_output(output, &paths, format.extension());
+            let points = paths
+                .iter()
+                .filter_map(|path| gps::gps_point(path, *format == TrackFormat::Kml))
+                .collect();
+            let result = check_write(&mut report, &output).and_then(|_| {
+                if step.dry_run {
+                    Ok(())
+                } else {
+                    track::write_track(points, *format, &output).map(|_| ())
+                }
+            });
+            finish_export(&mut report, &output, result);
+            progress(total, total, None);
+            paths
+        }
+        PipelineOp::ExportGeo {
+            format,
+            paths: path_style,
+            output,
+        } => {
+            let output = export_output(output, &paths, format.extension());
+            let points: Vec<_> = paths
+                .iter()
+                .filter_map(|path| gps::gps_point(path, false))
+                .collect();
+            let result = if points.is_empty() {
+                Err("No geotagged photos were selected.".to_string())
+            } else {
+                check_write(&mut report, &output).and_then(|_| {
+                    if step.dry_run {
+                        Ok(())
+                    } else {
+                        geo::write_geo(&points, *format, *path_style, &output).map(|_| ())
+                    }
+                })
+            };
+            finish_export(&mut report, &output, result);
+            progress(total, total, None);
+            paths
+        }
+    };
+    (report, next)
+}
+
+/// Refuses a write outside the allowed folders, noting why in the report.
+fn check_write(report: &mut StepReport, target: &Path) -> Result<(), String> {
+    path_scope::check_write(target).map_err(|violation| {
+        let message = violation.message();
+        report.refused.push(violation);
+        message
+    })
+}
+
+fn record(report: &mut StepReport, path: &Path, result: Result<(), String>) {
+    match result {
+        Ok(()) => report.succeeded += 1,
+        Err(error) => report.errors.push(format!("{}: {}", path.display(), error)),
+    }
+}
+
+fn finish_export(report: &mut StepReport, output: &Path, result: Result<(), String>) {
+    match result {
+        Ok(()) => {
+            report.succeeded += 1;
+            report.outputs.push(output.to_string_lossy().into_owned());
+        }
+        Err(error) => report.errors.push(error),
+    }
+}
+
+/// Executes every step of `pipeline` over `paths` in order. `force_dry_run`
+/// turns the whole run into a preview regardless of the per-step flags.
+pub fn run_pipeline(
+    pipeline: &Pipeline,
+    paths: Vec<PathBuf>,
+    force_dry_run: bool,
+    targets: &EditTargets,
+    progress: &mut dyn FnMut(PipelineProgress),
+) -> PipelineRun {
+    let step_count = pipeline.steps.len();
+    let mut current = paths;
+    let mut steps = Vec::with_capacity(step_count);
+    for (index, step) in pipeline.steps.iter().enumerate() {
+        let mut step = step.clone();
+        step.dry_run |= force_dry_run;
+        let label = step.op.label().to_string();
+        let (report, next) = run_step(&step, current, targets, &mut |completed, total, path| {
+            progress(PipelineProgress {
+                pipeline: pipeline.name.clone(),
+                step: index + 1,
+                step_count,
+                label: label.clone(),
+                completed,
+                total,
+                path: path.map(|path| path.to_string_lossy().into_owned()),
+            })
+        });
+        steps.push(report);
+        current = next;
+    }
+    PipelineRun {
+        pipeline: pipeline.name.clone(),
+        steps,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{oplog::OperationLog, virtual_fields::VirtualFieldStore};
+
+    fn temporary_dir() -> PathBuf {
+        let path = crate::fixtures::temp_path("pipeline");
+        fs::create_dir_all(&path).unwrap();
+        path
+    }
+
+    #[test]
+    fn recipes_round_trip_as_tagged_json() {
+        let pipeline: Pipeline = serde_json::from_str(
+            r#"{"name":"Client delivery","steps":[
+                {"op":"stripGps"},
+                {"op":"rename","template":"{date}_{index}.{ext}","dryRun":true},
+                {"op":"exportGeo","format":"geojson","output":null}
+            ]}"#,
+        )
+        .expect("recipe should parse");
+        assert_eq!(pipeline.steps[0].op, PipelineOp::StripGps);
+        assert!(pipeline.steps[1].dry_run);
+        assert_eq!(
+            pipeline.steps[2].op,
+            PipelineOp::ExportGeo {
+                format: GeoFormat::GeoJson,
+                paths: GeoPaths::Omitted,
+                output: None
+            }
+        );
+    }
+
+    #[test]
+    fn rename_feeds_new_paths_to_later_steps_and_dry_runs_change_nothing() {
+        let folder = temporary_dir();
+        for name in ["b.jpg", "a.jpg"] {
+            fs::write(folder.join(name), b"not really a jpeg").unwrap();
+        }
+        let log = OperationLog::open(folder.join("operations.jsonl"));
+        let external = VirtualFieldStore::open(folder.join("virtual-fields.json"));
+        let targets = EditTargets {
+            log: &log,
+            external: &external,
+            mode: EditMode::InPlace,
+        };
+        let pipeline = Pipeline {
+            name: "Tidy".to_string(),
+            steps: vec![
+                PipelineStep {
+                    op: PipelineOp::Rename {
+                        template: "shoot-{index}-{name}.{ext}".to_string(),
+                    },
+                    dry_run: false,
+                },
+                PipelineStep {
+                    op: PipelineOp::Rename {
+                        template: "{date}.{ext}".to_string(),
+                    },
+                    dry_run: true,
+                },
+            ],
+        };
+
+        let mut events = Vec::new();
+        let run = run_pipeline(
+            &pipeline,
+            vec![folder.join("a.jpg"), folder.join("b.jpg")],
+            false,
+            &targets,
+            &mut |progress| events.push((progress.step, progress.completed)),
+        );
+
+        let renamed = folder.join("shoot-1-a.jpg").exists()
+            && folder.join("shoot-2-b.jpg").exists()
+            && !folder.join("undated.jpg").exists();
+        let history = log.history(&folder.join("shoot-1-a.jpg")).unwrap();
+        fs::remove_dir_all(&folder).ok();
+
+        assert!(renamed);
+        assert_eq!(run.steps[0].succeeded, 2);
+        // The preview reports planned names without touching the files.
+        assert_eq!(run.steps[1].outputs.len(), 2);
+        assert!(run.steps[1].dry_run);
+        assert_eq!(events, vec![(1, 1), (1, 2), (2, 1), (2, 2)]);
+        assert_eq!(history.len(), 1);
+    }
+
+    #[test]
+    fn renames_never_replace_a_file_that_appeared_at_the_target() {
+        let folder = temporary_dir();
+        let (from, to) = (folder.join("a.jpg"), folder.join("b.jpg"));
+        fs::write(&from, b"mine").unwrap();
+        fs::write(&to, b"theirs").unwrap();
+
+        let clash = rename_no_clobber(&from, &to);
+        let kept = (fs::read(&from).unwrap(), fs::read(&to).unwrap());
+        fs::remove_file(&to).unwrap();
+        let moved = rename_no_clobber(&from, &to);
+        let renamed = !from.exists() && fs::read(&to).unwrap() == b"mine";
+        fs::remove_dir_all(&folder).ok();
+
+        assert_eq!(clash.unwrap_err().kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(kept, (b"mine".to_vec(), b"theirs".to_vec()));
+        assert!(moved.is_ok());
+        assert!(renamed);
+    }
+}
@@ -0,0 +1,104 @@
+use crate::retry::ReadError;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::Path,
+    sync::atomic::{AtomicU8, Ordering},
+};
+
+/// What scans do with online-only files whose contents a sync client
+/// (OneDrive, iCloud Drive, Dropbox) has not downloaded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CloudPlaceholders {
+    /// Leave them in the cloud and list them as placeholders.
+    #[default]
+    Skip,
+    /// Read them anyway, which has the sync client download each one first.
+    Hydrate,
+}
+
+static POLICY: AtomicU8 = AtomicU8::new(0);
+
+pub fn set_policy(policy: CloudPlaceholders) {
+    POLICY.store(policy as u8, Ordering::Relaxed);
+}
+
+pub fn policy() -> CloudPlaceholders {
+    match POLICY.load(Ordering::Relaxed) {
+        1 => CloudPlaceholders::Hydrate,
+        _ => CloudPlaceholders::Skip,
+    }
+}
+
+/// Offline, recall-on-open and recall-on-data-access: the Cloud Files API
+/// marks dehydrated files with these.
+#[cfg(windows)]
+const RECALL_ATTRIBUTES: u32 = 0x1000 | 0x4_0000 | 0x40_0000;
+/// `st_flags` bit of an APFS file whose data lives with a file provider.
+#[cfg(target_os = "macos")]
+const SF_DATALESS: u32 = 0x4000_0000;
+/// Larger files with no blocks on disk are stubs; smaller ones may be
+/// stored inline in the inode.
+#[cfg(unix)]
+const INLINE_LIMIT: u64 = 4096;
+
+#[cfg(windows)]
+fn dehydrated(metadata: &fs::Metadata) -> bool {
+    use std::os::windows::fs::MetadataExt;
+    metadata.file_attributes() & RECALL_ATTRIBUTES != 0
+}
+
+#[cfg(unix)]
+fn dehydrated(metadata: &fs::Metadata) -> bool {
+    #[cfg(target_os = "macos")]
+    if std::os::macos::fs::MetadataExt::st_flags(metadata) & SF_DATALESS != 0 {
+        return true;
+    }
+    metadata.len() > INLINE_LIMIT && std::os::unix::fs::MetadataExt::blocks(metadata) == 0
+}
+
+#[cfg(not(any(windows, unix)))]
+fn dehydrated(_metadata: &fs::Metadata) -> bool {
+    false
+}
+
+/// Whether `path` is a cloud placeholder, judged from its metadata alone so
+/// the check itself never starts a download.
+pub fn is_placeholder(path: &Path) -> bool {
+    // Not following links keeps Windows from opening the file's reparse point.
+    fs::symlink_metadata(path).is_ok_and(|metadata| dehydrated(&metadata))
+}
+
+/// Refuses a placeholder while scans are set to skip them.
+pub fn check(path: &Path) -> Result<(), ReadError> {
+    if policy() == CloudPlaceholders::Skip && is_placeholder(path) {
+        return Err(ReadError::Placeholder(
+            "The file is online-only and has not been downloaded; allow cloud placeholders to be downloaded to read it."
+                .to_string(),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(unix)]
+    fn files_without_data_on_disk_are_placeholders() {
+        let dir = crate::fixtures::temp_path("stub");
+        fs::create_dir_all(&dir).unwrap();
+        let stub = dir.join("stub.jpg");
+        fs::File::create(&stub).unwrap().set_len(1 << 20).unwrap();
+        let local = dir.join("local.jpg");
+        fs::write(&local, vec![0xAB; 64 * 1024]).unwrap();
+
+        assert!(is_placeholder(&stub));
+        assert!(matches!(check(&stub), Err(ReadError::Placeholder(_))));
+        assert!(!is_placeholder(&local));
+        assert_eq!(check(&local), Ok(()));
+        fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,404 @@
+use crate::{
+    commit::commit_rewrite,
+    oplog::{FieldChange, OperationLog},
+    settings::EditMode,
+    write_lock::{FileStamp, WriteError},
+    PNG_SIGNATURE,
+};
+use flate2::Crc;
+use serde::Serialize;
+use std::path::Path;
+
+/// Ancillary chunks the PNG spec places before `PLTE`, and so before `IDAT`.
+const BEFORE_PLTE: [&[u8; 4]; 6] = [b"cHRM", b"cICP", b"gAMA", b"iCCP", b"sBIT", b"sRGB"];
+/// Ancillary chunks that follow `PLTE`, when there is one, but precede `IDAT`.
+const AFTER_PLTE: [&[u8; 4]; 3] = [b"bKGD", b"hIST", b"tRNS"];
+const BEFORE_IDAT: [&[u8; 4]; 2] = [b"pHYs", b"sPLT"];
+const TEXT_CHUNKS: [&[u8; 4]; 3] = [b"tEXt", b"zTXt", b"iTXt"];
+
+/// One chunk of a PNG file, from its length field up to and including its CRC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Chunk {
+    pub kind: [u8; 4],
+    /// Offset of the length field.
+    pub start: usize,
+    /// Offset one past the CRC.
+    pub end: usize,
+}
+
+impl Chunk {
+    pub fn payload<'a>(&self, data: &'a [u8]) -> &'a [u8] {
+        &data[self.start + 8..self.end - 4]
+    }
+
+    fn stored_crc(&self, data: &[u8]) -> u32 {
+        u32::from_be_bytes(data[self.end - 4..self.end].try_into().expect("4 bytes"))
+    }
+
+    /// The CRC the chunk should have, computed over its type and payload.
+    fn computed_crc(&self, data: &[u8]) -> u32 {
+        let mut crc = Crc::new();
+        crc.update(&data[self.start + 4..self.end - 4]);
+        crc.sum()
+    }
+
+    /// Critical chunks have an uppercase first letter; a decoder needs all of them.
+    fn is_critical(&self) -> bool {
+        self.kind[0].is_ascii_uppercase()
+    }
+
+    fn name(&self) -> String {
+        String::from_utf8_lossy(&self.kind).into_owned()
+    }
+}
+
+/// A chunk as the chunk editor lists it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PngChunkInfo {
+    /// Position in the file, as `rewrite_png_chunks` expects it in `order`.
+    pub index: usize,
+    pub kind: String,
+    pub offset: usize,
+    /// Payload size, without the length, type and CRC fields.
+    pub length: usize,
+    pub critical: bool,
+    pub crc_valid: bool,
+    /// The keyword of a text chunk.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keyword: Option<String>,
+}
+
+/// Lists the chunks of a PNG up to and including `IEND`.
+///
+/// Returns the chunks and the offset just past `IEND`; anything after it is
+/// copied verbatim by every rewrite.
+pub fn chunks(data: &[u8]) -> Result<(Vec<Chunk>, usize), String> {
+    if !data.starts_with(&PNG_SIGNATURE) {
+        return Err("The selected file is not a PNG image.".to_string());
+    }
+    let truncated = || "The selected file appears to be truncated or corrupted.".to_string();
+    let mut chunks = Vec::new();
+    let mut offset = PNG_SIGNATURE.len();
+    loop {
+        let header = data.get(offset..offset + 8).ok_or_else(truncated)?;
+        let length = u32::from_be_bytes(header[..4].try_into().expect("4 bytes")) as usize;
+        let end = offset
+            .checked_add(12 + length)
+            .filter(|&end| end <= data.len())
+            .ok_or_else(truncated)?;
+        let chunk = Chunk {
+            kind: header[4..8].try_into().expect("4 bytes"),
+            start: offset,
+            end,
+        };
+        chunks.push(chunk);
+        offset = end;
+        if &chunk.kind == b"IEND" {
+            return Ok((chunks, offset));
+        }
+    }
+}
+
+pub fn list(data: &[u8]) -> Result<Vec<PngChunkInfo>, String> {
+    let (chunks, _) = chunks(data)?;
+    Ok(chunks
+        .iter()
+        .enumerate()
+        .map(|(index, chunk)| PngChunkInfo {
+            index,
+            kind: chunk.name(),
+            offset: chunk.start,
+            length: chunk.end - chunk.start - 12,
+            critical: chunk.is_critical(),
+            crc_valid: chunk.stored_crc(data) == chunk.computed_crc(data),
+            keyword: TEXT_CHUNKS.contains(&&chunk.kind).then(|| {
+                let payload = chunk.payload(data);
+                let keyword = payload.split(|&byte| byte == 0).next().unwrap_or_default();
+                String::from_utf8_lossy(keyword).into_owned()
+            }),
+        })
+        .collect())
+}
+
+/// Checks that chunks in `order` still make a valid PNG: `IHDR` first, `IEND`
+/// last, the critical chunks in their original order with the `IDAT`s
+/// together, and the ancillary chunks the spec pins on the right side of
+/// `PLTE` and `IDAT`.
+fn check_layout(chunks: &[Chunk], order: &[usize]) -> Result<(), String> {
+    let kinds: Vec<&[u8; 4]> = order.iter().map(|&index| &chunks[index].kind).collect();
+    if kinds.first() != Some(&b"IHDR") || kinds.last() != Some(&b"IEND") {
+        return Err("A PNG must start with its IHDR chunk and end with IEND.".to_string());
+    }
+    let critical: Vec<usize> = order
+        .iter()
+        .copied()
+        .filter(|&index| chunks[index].is_critical())
+        .collect();
+    if critical.windows(2).any(|pair| pair[0] > pair[1]) {
+        return Err("The IHDR, PLTE, IDAT and IEND chunks must keep their order.".to_string());
+    }
+    let is_idat = |kind: &&[u8; 4]| *kind == b"IDAT";
+    let first_idat = kinds.iter().position(is_idat);
+    let last_idat = kinds.iter().rposition(is_idat);
+    if let (Some(first), Some(last)) = (first_idat, last_idat) {
+        if kinds[first..=last].iter().any(|kind| !is_idat(kind)) {
+            return Err(
+                "The IDAT chunks must follow each other without other chunks between them."
+                    .to_string(),
+            );
+        }
+    }
+    let plte = kinds.iter().position(|kind| *kind == b"PLTE");
+    for (position, kind) in kinds.iter().enumerate() {
+        let name = String::from_utf8_lossy(*kind);
+        let before = |limit: Option<usize>, what: &str| match limit {
+            Some(limit) if position > limit => {
+                Err(format!("The {} chunk must come before {}.", name, what))
+            }
+            _ => Ok(()),
+        };
+        if BEFORE_PLTE.contains(kind) {
+            before(plte, "PLTE")?;
+        }
+        if BEFORE_PLTE.contains(kind) || AFTER_PLTE.contains(kind) || BEFORE_IDAT.contains(kind) {
+            before(first_idat, "IDAT")?;
+        }
+        if AFTER_PLTE.contains(kind) && plte.is_some_and(|plte| position < plte) {
+            return Err(format!("The {} chunk must come after PLTE.", name));
+        }
+    }
+    Ok(())
+}
+
+/// The PNG with its chunks in `order`, given as indices into [`chunks`].
+/// Chunks left out are removed; only ancillary chunks may be. Every kept
+/// chunk, CRC included, is copied byte for byte.
+pub fn rearrange(data: &[u8], order: &[usize]) -> Result<Vec<u8>, String> {
+    let (chunks, end) = chunks(data)?;
+    let mut kept = vec![false; chunks.len()];
+    for &index in order {
+        match kept.get_mut(index) {
+            Some(seen) if !*seen => *seen = true,
+            Some(_) => return Err(format!("Chunk {} is listed more than once.", index)),
+            None => return Err(format!("The PNG has no chunk {}.", index)),
+        }
+    }
+    if let Some(chunk) = chunks
+        .iter()
+        .zip(&kept)
+        .find_map(|(chunk, kept)| (!kept && chunk.is_critical()).then_some(chunk))
+    {
+        return Err(format!(
+            "The {} chunk is part of the image and cannot be removed.",
+            chunk.name()
+        ));
+    }
+    check_layout(&chunks, order)?;
+
+    let mut out = PNG_SIGNATURE.to_vec();
+    for &index in order {
+        out.extend_from_slice(&data[chunks[index].start..chunks[index].end]);
+    }
+    out.extend_from_slice(&data[end..]);
+    Ok(out)
+}
+
+/// The PNG with the payload of chunk `index` replaced and its CRC
+/// recomputed; every other chunk is copied byte for byte.
+pub fn replace_payload(data: &[u8], index: usize, payload: &[u8]) -> Result<Vec<u8>, String> {
+    let (chunks, _) = chunks(data)?;
+    let chunk = chunks
+        .get(index)
+        .ok_or_else(|| format!("The PNG has no chunk {}.", index))?;
+    let length = u32::try_from(payload.len())
+        .map_err(|_| "The new chunk is too large for a PNG.".to_string())?;
+    let mut crc = Crc::new();
+    crc.update(&chunk.kind);
+    crc.update(payload);
+
+    let mut out = data[..chunk.start].to_vec();
+    out.extend_from_slice(&length.to_be_bytes());
+    out.extend_from_slice(&chunk.kind);
+    out.extend_from_slice(payload);
+    out.extend_from_slice(&crc.sum().to_be_bytes());
+    out.extend_from_slice(&data[chunk.end..]);
+    Ok(out)
+}
+
+/// The PNG with every wrong CRC replaced by the right one, and the indices
+/// of the chunks that were repaired.
+pub fn repair_crcs(data: &[u8]) -> Result<(Vec<u8>, Vec<usize>), String> {
+    let (chunks, _) = chunks(data)?;
+    let mut out = data.to_vec();
+    let mut repaired = Vec::new();
+    for (index, chunk) in chunks.iter().enumerate() {
+        let crc = chunk.computed_crc(data);
+        if chunk.stored_crc(data) != crc {
+            out[chunk.end - 4..chunk.end].copy_from_slice(&crc.to_be_bytes());
+            repaired.push(index);
+        }
+    }
+    Ok((out, repaired))
+}
+
+/// [`repair_crcs`] with one change-log entry per repaired chunk.
+pub fn repair_changes(data: &[u8]) -> Result<(Vec<u8>, Vec<FieldChange>), String> {
+    let (repaired, indices) = repair_crcs(data)?;
+    let (chunks, _) = chunks(data)?;
+    let changes = indices
+        .into_iter()
+        .map(|index| {
+            let chunk = chunks[index];
+            FieldChange {
+                tag: format!("PNG {} CRC", chunk.name()),
+                before: Some(format!("{:08X}", chunk.stored_crc(data))),
+                after: Some(format!("{:08X}", chunk.computed_crc(data))),
+            }
+        })
+        .collect();
+    Ok((repaired, changes))
+}
+
+/// The concatenated `IDAT` payloads, which is all the pixel data there is.
+fn image_data(data: &[u8]) -> Result<Vec<u8>, String> {
+    let (chunks, _) = chunks(data)?;
+    Ok(chunks
+        .iter()
+        .filter(|chunk| &chunk.kind == b"IDAT")
+        .flat_map(|chunk| chunk.payload(data).iter().copied())
+        .collect())
+}
+
+/// Change-log entries for a chunk rewrite: one per removed chunk, and the
+/// chunk order when it changed.
+pub fn rearrange_changes(data: &[u8], order: &[usize]) -> Result<Vec<FieldChange>, String> {
+    let (chunks, _) = chunks(data)?;
+    let mut changes: Vec<FieldChange> = chunks
+        .iter()
+        .enumerate()
+        .filter(|(index, _)| !order.contains(index))
+        .map(|(_, chunk)| FieldChange {
+            tag: format!("PNG {}", chunk.name()),
+            before: Some(format!("{} bytes", chunk.end - chunk.start - 12)),
+            after: None,
+        })
+        .collect();
+    let names = |indices: &mut dyn Iterator<Item = usize>| {
+        indices
+            .map(|index| chunks[index].name())
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    let before = names(&mut (0..chunks.len()).filter(|index| order.contains(index)));
+    let after = names(&mut order.iter().copied());
+    if before != after {
+        changes.push(FieldChange {
+            tag: "PNG chunk order".to_string(),
+            before: Some(before),
+            after: Some(after),
+        });
+    }
+    Ok(changes)
+}
+
+/// Replaces the PNG at `path` with what `edit` makes of it through
+/// [`commit_rewrite`], after checking that the pixel data is unchanged.
+pub fn commit(
+    log: &OperationLog,
+    mode: EditMode,
+    operation: &str,
+    path: &Path,
+    edit: impl FnOnce(&[u8]) -> Result<(Vec<u8>, Vec<FieldChange>), String>,
+) -> Result<Vec<PngChunkInfo>, WriteError> {
+    let stamp = FileStamp::of(path)?;
+    let data = crate::load_file_data(path)?;
+    let (updated, changes) = edit(&data)?;
+    if image_data(&updated)? != image_data(&data)? {
+        return Err(
+            "The rewrite would change the image data, so the file was left alone."
+                .to_string()
+                .into(),
+        );
+    }
+    if changes.is_empty() {
+        return Ok(list(&data)?);
+    }
+    commit_rewrite(log, mode, operation, path, stamp, &updated, changes)?;
+    Ok(list(&crate::load_file_data(path)?)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::PngBuilder;
+
+    fn kinds(data: &[u8]) -> Vec<String> {
+        list(data)
+            .unwrap()
+            .into_iter()
+            .map(|chunk| chunk.kind)
+            .collect()
+    }
+
+    fn png() -> Vec<u8> {
+        PngBuilder::new()
+            .header(1, 1)
+            .chunk(b"IDAT", b"pixels-1")
+            .chunk(b"IDAT", b"pixels-2")
+            .text("Comment", "junk")
+            .chunk(b"pHYs", &[0, 0, 0x0B, 0x13, 0, 0, 0x0B, 0x13, 1])
+            .finish()
+    }
+
+    #[test]
+    fn chunks_are_moved_and_removed_verbatim() {
+        let data = png();
+        assert_eq!(
+            kinds(&data),
+            ["IHDR", "IDAT", "IDAT", "tEXt", "pHYs", "IEND"]
+        );
+
+        let mut trailing = data.clone();
+        trailing.extend_from_slice(b"trailer");
+        let moved = rearrange(&trailing, &[0, 3, 1, 2, 5]).unwrap();
+        assert_eq!(kinds(&moved), ["IHDR", "tEXt", "IDAT", "IDAT", "IEND"]);
+        assert!(moved.ends_with(b"IEND\xAE\x42\x60\x82trailer"));
+        assert_eq!(image_data(&moved).unwrap(), b"pixels-1pixels-2");
+        let changes = rearrange_changes(&trailing, &[0, 3, 1, 2, 5]).unwrap();
+        assert_eq!(changes[0].tag, "PNG pHYs");
+        assert_eq!(
+            changes[1].after.as_deref(),
+            Some("IHDR tEXt IDAT IDAT IEND")
+        );
+
+        let refused = |order: &[usize]| rearrange(&data, order).unwrap_err();
+        assert!(refused(&[0, 1, 3, 4, 5]).contains("IDAT chunk is part of the image"));
+        assert!(refused(&[0, 1, 3, 2, 4, 5]).contains("follow each other"));
+        assert!(refused(&[0, 1, 2, 4, 3, 5]).contains("pHYs chunk must come before IDAT"));
+        assert!(refused(&[3, 0, 1, 2, 5]).contains("start with its IHDR"));
+        assert!(refused(&[0, 1, 2, 5, 9]).contains("no chunk 9"));
+    }
+
+    #[test]
+    fn bad_crcs_are_found_and_repaired() {
+        let mut data = png();
+        let text = chunks(&data).unwrap().0[3];
+        data[text.end - 1] ^= 0xFF;
+        let listed = list(&data).unwrap();
+        assert_eq!(
+            listed
+                .iter()
+                .filter(|chunk| !chunk.crc_valid)
+                .map(|chunk| chunk.index)
+                .collect::<Vec<_>>(),
+            [3]
+        );
+        assert_eq!(listed[3].keyword.as_deref(), Some("Comment"));
+
+        let (repaired, indices) = repair_crcs(&data).unwrap();
+        assert_eq!(indices, [3]);
+        assert_eq!(repaired, png());
+        assert!(chunks(&data[..data.len() - 1]).is_err());
+    }
+}
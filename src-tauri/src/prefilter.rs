@@ -0,0 +1,125 @@
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::Path,
+};
+
+/// How much of each file is read up front; later PNG chunk headers are
+/// reached by seeking past the image data.
+const PREFIX_BYTES: usize = 64 * 1024;
+/// PNG keywords are at most 79 bytes, followed by a NUL.
+const KEYWORD_BYTES: usize = 80;
+const TEXT_CHUNKS: [&[u8]; 3] = [b"tEXt", b"zTXt", b"iTXt"];
+
+/// `len` bytes at `offset`, from `prefix` when it covers them and through
+/// `read_at` otherwise. Shorter at the end of the file.
+fn bytes_at(
+    read_at: &mut impl FnMut(u64, usize) -> Option<Vec<u8>>,
+    prefix: &[u8],
+    offset: u64,
+    len: usize,
+) -> Option<Vec<u8>> {
+    let start = usize::try_from(offset).ok()?;
+    if start + len <= prefix.len() {
+        return Some(prefix[start..start + len].to_vec());
+    }
+    read_at(offset, len)
+}
+
+/// Whether a scan has to read `path` in full to look for an aesthetic score.
+/// Scores are only ever found in PNG text chunks, so other files are ruled
+/// out by their signature and PNG files by the keywords of their text
+/// chunks, which are reached by reading chunk headers alone. Anything that
+/// cannot be read here is left to the full read to report.
+pub fn may_hold_score(path: &Path) -> bool {
+    let Ok(mut file) = File::open(path) else {
+        return true;
+    };
+    may_hold_score_in(|offset, len| {
+        file.seek(SeekFrom::Start(offset)).ok()?;
+        let mut bytes = Vec::with_capacity(len);
+        (&mut file).take(len as u64).read_to_end(&mut bytes).ok()?;
+        Some(bytes)
+    })
+}
+
+/// [`may_hold_score`] for a file reached through `read_at(offset, len)`,
+/// such as ranged requests to a remote library.
+pub fn may_hold_score_in(mut read_at: impl FnMut(u64, usize) -> Option<Vec<u8>>) -> bool {
+    let Some(prefix) = read_at(0, PREFIX_BYTES) else {
+        return true;
+    };
+    if !prefix.starts_with(&crate::PNG_SIGNATURE) {
+        return false;
+    }
+
+    let mut offset = crate::PNG_SIGNATURE.len() as u64;
+    loop {
+        let Some(header) = bytes_at(&mut read_at, &prefix, offset, 8 + KEYWORD_BYTES) else {
+            return true;
+        };
+        let Some(length) = header.get(..4) else {
+            return false;
+        };
+        let length = u32::from_be_bytes(length.try_into().expect("slice has 4 bytes"));
+        let chunk_type = header.get(4..8).unwrap_or_default();
+        if TEXT_CHUNKS.contains(&chunk_type) {
+            let keyword = &header[8..];
+            let keyword = &keyword[..keyword
+                .iter()
+                .position(|&byte| byte == 0)
+                .unwrap_or(keyword.len())];
+            if crate::is_aesthetic_tag(&crate::decode_latin1(keyword)) {
+                return true;
+            }
+        }
+        if chunk_type == b"IEND" || header.len() < 8 {
+            return false;
+        }
+        offset += 12 + u64::from(length);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(kind: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut chunk = (data.len() as u32).to_be_bytes().to_vec();
+        chunk.extend_from_slice(kind);
+        chunk.extend_from_slice(data);
+        chunk.extend_from_slice(&[0; 4]);
+        chunk
+    }
+
+    #[test]
+    fn only_png_files_with_a_score_chunk_need_a_full_read() {
+        let folder = crate::fixtures::temp_path("prefilter");
+        std::fs::create_dir_all(&folder).unwrap();
+        let png = |text: &[u8]| {
+            let mut png = crate::PNG_SIGNATURE.to_vec();
+            png.extend(chunk(b"IHDR", &[0; 13]));
+            // Image data well past the prefix, with the text after it.
+            png.extend(chunk(b"IDAT", &vec![0; PREFIX_BYTES * 2]));
+            png.extend(chunk(b"tEXt", text));
+            png.extend(chunk(b"IEND", &[]));
+            png
+        };
+        let files = [
+            ("scored.png", png(b"aesthetic_score\x006.5")),
+            ("titled.png", png(b"Title\0Beach")),
+            ("photo.jpg", b"\xFF\xD8\xFF\xE1aesthetic_score 9".to_vec()),
+        ];
+        let verdicts: Vec<bool> = files
+            .iter()
+            .map(|(name, contents)| {
+                let path = folder.join(name);
+                std::fs::write(&path, contents).unwrap();
+                may_hold_score(&path)
+            })
+            .collect();
+        std::fs::remove_dir_all(&folder).ok();
+
+        assert_eq!(verdicts, [true, false, false]);
+    }
+}
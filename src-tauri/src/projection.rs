@@ -0,0 +1,65 @@
+use crate::report::group_title;
+use exif::{Context, Field};
+use serde::{Deserialize, Serialize};
+
+/// Group name that selects the EXIF GPS IFD tags (`GPSLatitude`, ...), which
+/// the reader otherwise lists under the image they belong to.
+const GPS_GROUP: &str = "GPS";
+
+/// The fields a caller wants back. A field is kept when its tag or its group
+/// (`Image`, `GPS (computed)`, `PNG tEXt`, ...) is listed, matched
+/// case-insensitively; leaving both lists empty keeps everything.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct FieldSelection {
+    pub tags: Vec<String>,
+    pub groups: Vec<String>,
+}
+
+fn listed(names: &[String], name: &str) -> bool {
+    names.iter().any(|listed| listed.eq_ignore_ascii_case(name))
+}
+
+impl FieldSelection {
+    pub fn is_everything(&self) -> bool {
+        self.tags.is_empty() && self.groups.is_empty()
+    }
+
+    pub fn wants(&self, ifd: &str, tag: &str) -> bool {
+        self.is_everything() || listed(&self.tags, tag) || listed(&self.groups, group_title(ifd))
+    }
+
+    /// Checked before a raw EXIF field is formatted, so unwanted MakerNotes
+    /// are never decoded.
+    pub fn wants_exif(&self, field: &Field) -> bool {
+        self.wants(&format!("{:?}", field.ifd_num), &field.tag.to_string())
+            || (field.tag.context() == Context::Gps && listed(&self.groups, GPS_GROUP))
+    }
+
+    /// Whether producing `group` at all could be worthwhile: anything listed
+    /// by tag might come from any group.
+    pub fn may_want_group(&self, group: &str) -> bool {
+        self.is_everything() || !self.tags.is_empty() || listed(&self.groups, group)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{ascii_field, rational_field};
+    use exif::Tag;
+
+    #[test]
+    fn tags_and_groups_select_fields() {
+        let selection = FieldSelection {
+            tags: vec!["datetimeoriginal".to_string()],
+            groups: vec!["gps".to_string(), "GPS (computed)".to_string()],
+        };
+        assert!(selection.wants_exif(&ascii_field(Tag::DateTimeOriginal, "2024:05:01 10:00:00")));
+        assert!(selection.wants_exif(&rational_field(Tag::GPSLatitude, &[(48, 1)])));
+        assert!(!selection.wants_exif(&ascii_field(Tag::Model, "EOS R5")));
+        assert!(selection.wants("GPS (computed)", "Position"));
+        assert!(!selection.wants("PNG tEXt", "Software"));
+        assert!(FieldSelection::default().wants("PNG tEXt", "Software"));
+    }
+}
@@ -0,0 +1,124 @@
+use crate::shell_integration::LaunchPaths;
+use std::{path::PathBuf, process::Command};
+use tauri::{App, AppHandle, Emitter, Manager};
+use tauri_plugin_global_shortcut::{Builder, GlobalShortcutExt, ShortcutState};
+
+/// Ctrl+Shift+I is taken by the webview inspector, so Alt is used instead.
+pub const INSPECT_SHORTCUT: &str = "CommandOrControl+Alt+I";
+/// Whether the file manager selection can be read on this platform.
+pub const SELECTION_SUPPORTED: bool = cfg!(any(target_os = "macos", target_os = "windows"));
+
+/// Registers the global "inspect the selected file" shortcut. A shortcut
+/// already claimed by another app is not fatal; the rest of the app still works.
+pub fn install(app: &App) -> Result<(), Box<dyn std::error::Error>> {
+    let plugin = Builder::new()
+        .with_handler(|app, _shortcut, event| {
+            if event.state() == ShortcutState::Pressed {
+                inspect_selection(app);
+            }
+        })
+        .build();
+    app.handle().plugin(plugin)?;
+    if let Err(error) = app.global_shortcut().register(INSPECT_SHORTCUT) {
+        tracing::warn!(%error, shortcut = INSPECT_SHORTCUT, "could not register the inspect shortcut");
+    }
+    Ok(())
+}
+
+/// Looks up the file manager's selection off the main thread, then hands it
+/// to the frontend the same way launch arguments are.
+fn inspect_selection(app: &AppHandle) {
+    let app = app.clone();
+    std::thread::spawn(move || {
+        match selected_files() {
+            Ok(paths) if !paths.is_empty() => {
+                app.state::<LaunchPaths>().push(paths);
+                let _ = app.emit("launch-paths", ());
+            }
+            Ok(_) => {
+                let _ = app.emit(
+                    "inspect-selection-error",
+                    "Nothing is selected in the file manager.",
+                );
+            }
+            Err(error) => {
+                let _ = app.emit("inspect-selection-error", error);
+            }
+        }
+        crate::focus_main_window(&app);
+    });
+}
+
+/// Files selected in the frontmost Finder or Explorer window.
+pub fn selected_files() -> Result<Vec<PathBuf>, String> {
+    let output = selection_command()?.output().map_err(|error| {
+        format!(
+            "Unable to ask the file manager for its selection: {}",
+            error
+        )
+    })?;
+    if !output.status.success() {
+        return Err(format!(
+            "The file manager did not report a selection ({}). Check that EXIF Viewer may control it.",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(parse_selection(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// One path per line; anything that no longer exists is dropped.
+fn parse_selection(output: &str) -> Vec<PathBuf> {
+    output
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(PathBuf::from)
+        .filter(|path| path.exists())
+        .collect()
+}
+
+#[cfg(target_os = "macos")]
+fn selection_command() -> Result<Command, String> {
+    const SCRIPT: &str = r#"tell application "Finder"
+    set out to ""
+    repeat with entry in (get selection)
+        set out to out & POSIX path of (entry as alias) & linefeed
+    end repeat
+    return out
+end tell"#;
+    let mut command = Command::new("osascript");
+    command.arg("-e").arg(SCRIPT);
+    Ok(command)
+}
+
+#[cfg(target_os = "windows")]
+fn selection_command() -> Result<Command, String> {
+    // Only the Explorer window that was in front when the shortcut fired counts.
+    const SCRIPT: &str = r#"$native = Add-Type -PassThru -Name Foreground -Namespace ExifViewer -MemberDefinition '[DllImport("user32.dll")] public static extern IntPtr GetForegroundWindow();'
+$front = $native::GetForegroundWindow().ToInt64()
+foreach ($window in (New-Object -ComObject Shell.Application).Windows()) {
+    if ($window.HWND -eq $front) {
+        foreach ($item in $window.Document.SelectedItems()) { $item.Path }
+    }
+}"#;
+    let mut command = Command::new("powershell");
+    command.args(["-NoProfile", "-NonInteractive", "-Command", SCRIPT]);
+    Ok(command)
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn selection_command() -> Result<Command, String> {
+    Err("Reading the file manager selection is not supported on this platform; open the file from the app or its context menu instead.".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn selection_output_keeps_existing_paths_only() {
+        let readme = env!("CARGO_MANIFEST_DIR").to_string() + "/../README.md";
+        let output = format!("{}\r\n\n  /definitely/not/here.jpg\n", readme);
+        assert_eq!(parse_selection(&output), vec![PathBuf::from(readme)]);
+    }
+}
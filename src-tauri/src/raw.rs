@@ -0,0 +1,159 @@
+use crate::heif::{boxes, child, u32_at};
+use exif::{experimental::Writer, Context, Field, In, Reader, Tag, Value};
+use std::{borrow::Cow, io::Cursor};
+
+/// `moov/uuid` box of a CR3 that holds the CMT1–CMT4 metadata boxes.
+const CANON_UUID: [u8; 16] = [
+    0x85, 0xc0, 0xb6, 0x87, 0x82, 0x0f, 0x11, 0xe0, 0x81, 0x11, 0xf4, 0xce, 0x46, 0x2b, 0x6a, 0x48,
+];
+/// Top-level `uuid` box of a CR3 that holds its XMP packet.
+const XMP_UUID: [u8; 16] = [
+    0xbe, 0x7a, 0xcf, 0xcb, 0x97, 0xa9, 0x42, 0xe8, 0x9c, 0x71, 0x99, 0x94, 0x91, 0xe3, 0xaf, 0xac,
+];
+/// CMT1 is IFD0, CMT2 the EXIF IFD and CMT4 the GPS IFD, each stored as a
+/// TIFF of its own. CMT3 is the Canon MakerNote.
+const CR3_IFDS: [(&[u8], Context); 3] = [
+    (b"CMT1", Context::Tiff),
+    (b"CMT2", Context::Exif),
+    (b"CMT4", Context::Gps),
+];
+const RAF_MAGIC: &[u8] = b"FUJIFILMCCD-RAW ";
+/// Where a RAF header stores the offset and length of its JPEG preview.
+const RAF_JPEG_AT: usize = 84;
+const TIFF_XMP: u16 = 700;
+
+/// The bytes the EXIF reader should be given for `data`. CR2, NEF, ARW and
+/// DNG files are TIFF already and come back as they are; a RAF gives its
+/// JPEG preview, whose EXIF describes the raw, and a CR3 a TIFF rebuilt
+/// from its metadata boxes.
+pub fn container(data: &[u8]) -> Cow<'_, [u8]> {
+    if let Some(jpeg) = raf_jpeg(data) {
+        return Cow::Borrowed(jpeg);
+    }
+    match cr3_tiff(data) {
+        Some(tiff) => Cow::Owned(tiff),
+        None => Cow::Borrowed(data),
+    }
+}
+
+fn raf_jpeg(data: &[u8]) -> Option<&[u8]> {
+    if !data.starts_with(RAF_MAGIC) {
+        return None;
+    }
+    let offset = u32_at(data, RAF_JPEG_AT)? as usize;
+    let length = u32_at(data, RAF_JPEG_AT + 4)? as usize;
+    data.get(offset..offset.checked_add(length)?)
+        .filter(|jpeg| jpeg.starts_with(&[0xFF, 0xD8]))
+}
+
+/// The body of the `uuid` box with `id` among `data`'s boxes, after the ID.
+fn uuid_box<'a>(data: &'a [u8], id: &[u8; 16]) -> Option<&'a [u8]> {
+    boxes(data)
+        .filter(|(kind, _)| *kind == b"uuid")
+        .find_map(|(_, body)| body.strip_prefix(id.as_slice()))
+}
+
+/// One TIFF with the IFD0, EXIF and GPS fields of a CR3 and its XMP packet
+/// as tag 700, so every reader sees a CR3 like any other TIFF-based raw.
+fn cr3_tiff(data: &[u8]) -> Option<Vec<u8>> {
+    if data.get(4..12) != Some(b"ftypcrx ".as_slice()) {
+        return None;
+    }
+    let canon = uuid_box(child(data, b"moov")?, &CANON_UUID)?;
+    let mut fields = Vec::new();
+    for (kind, context) in CR3_IFDS {
+        let Some(exif) =
+            child(canon, kind).and_then(|tiff| Reader::new().read_raw(tiff.to_vec()).ok())
+        else {
+            continue;
+        };
+        // Each box is read as IFD0, so the tags are put back in their IFD.
+        fields.extend(
+            exif.fields()
+                .filter(|field| field.ifd_num == In::PRIMARY)
+                .filter(|field| !matches!(field.value, Value::Unknown(..)))
+                .map(|field| Field {
+                    tag: Tag(context, field.tag.number()),
+                    ifd_num: In::PRIMARY,
+                    value: field.value.clone(),
+                }),
+        );
+    }
+    if let Some(packet) = uuid_box(data, &XMP_UUID) {
+        fields.push(Field {
+            tag: Tag(Context::Tiff, TIFF_XMP),
+            ifd_num: In::PRIMARY,
+            value: Value::Byte(packet.to_vec()),
+        });
+    }
+    if fields.is_empty() {
+        return None;
+    }
+    let mut writer = Writer::new();
+    for field in &fields {
+        writer.push_field(field);
+    }
+    let mut tiff = Cursor::new(Vec::new());
+    writer.write(&mut tiff, false).ok()?;
+    Some(tiff.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{ascii_field, jpeg_from_fields, rational_field, tiff_from_fields};
+
+    fn isobmff_box(kind: &[u8], body: &[u8]) -> Vec<u8> {
+        let mut out = ((body.len() + 8) as u32).to_be_bytes().to_vec();
+        out.extend_from_slice(kind);
+        out.extend_from_slice(body);
+        out
+    }
+
+    fn field<'a>(fields: &'a [crate::ExifField], tag: &str) -> Option<&'a str> {
+        fields
+            .iter()
+            .find(|field| field.tag == tag)
+            .map(|field| field.value.as_str())
+    }
+
+    #[test]
+    fn cr3_metadata_boxes_read_as_one_exif() {
+        // CMT2 keeps EXIF tags in its IFD0.
+        let exposure = rational_field(Tag(Context::Tiff, Tag::ExposureTime.number()), &[(1, 250)]);
+        let mut canon = CANON_UUID.to_vec();
+        canon.extend(isobmff_box(
+            b"CMT1",
+            &tiff_from_fields(&[ascii_field(Tag::Model, "Canon EOS R5")]),
+        ));
+        canon.extend(isobmff_box(b"CMT2", &tiff_from_fields(&[exposure])));
+        let mut xmp = XMP_UUID.to_vec();
+        xmp.extend_from_slice(
+            b"<x:xmpmeta xmlns:x=\"adobe:ns:meta/\"><rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\"><rdf:Description xmlns:xmp=\"http://ns.adobe.com/xap/1.0/\" xmp:Rating=\"4\"/></rdf:RDF></x:xmpmeta>",
+        );
+        let mut cr3 = isobmff_box(b"ftyp", b"crx \0\0\0\x01crx isom");
+        cr3.extend(isobmff_box(b"moov", &isobmff_box(b"uuid", &canon)));
+        cr3.extend(isobmff_box(b"uuid", &xmp));
+
+        let fields = crate::collect_fields_from_bytes(&cr3).unwrap();
+        assert_eq!(field(&fields, "Model"), Some("\"Canon EOS R5\""));
+        assert_eq!(field(&fields, "ExposureTime"), Some("1/250 s"));
+        assert_eq!(field(&fields, "xmp:Rating"), Some("4"));
+    }
+
+    #[test]
+    fn raf_exif_comes_from_the_jpeg_preview() {
+        let jpeg = jpeg_from_fields(&[ascii_field(Tag::Model, "X-T5")]);
+        let mut raf = RAF_MAGIC.to_vec();
+        raf.resize(RAF_JPEG_AT, 0);
+        raf.extend_from_slice(&100u32.to_be_bytes());
+        raf.extend_from_slice(&(jpeg.len() as u32).to_be_bytes());
+        raf.resize(100, 0);
+        raf.extend(&jpeg);
+
+        assert_eq!(container(&raf), jpeg.as_slice());
+        let fields = crate::collect_fields_from_bytes(&raf).unwrap();
+        assert_eq!(field(&fields, "Model"), Some("\"X-T5\""));
+        assert_eq!(container(b"II*\0rest"), b"II*\0rest".as_slice());
+    }
+}
@@ -0,0 +1,300 @@
+use crate::{
+    companions::CompanionFinder,
+    oplog::{FieldChange, OperationLog, OperationRecord},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RelocateMode {
+    Move,
+    #[default]
+    Copy,
+}
+
+impl RelocateMode {
+    fn operation(self) -> &'static str {
+        match self {
+            RelocateMode::Move => "move",
+            RelocateMode::Copy => "copy",
+        }
+    }
+}
+
+/// What happened to one image and the files that travel with it.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Relocation {
+    pub path: String,
+    pub destination: String,
+    /// Sidecars, thumbnails, memos and backups, at their new paths.
+    pub companions: Vec<String>,
+    /// CRC-32 of the image, matched by its copy.
+    pub checksum: Option<String>,
+    pub error: Option<String>,
+}
+
+fn checksum(path: &Path) -> Result<u32, String> {
+    let mut file = fs::File::open(path)
+        .map_err(|error| format!("Unable to read {}: {}", path.display(), error))?;
+    let mut crc = flate2::Crc::new();
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let read = file
+            .read(&mut buffer)
+            .map_err(|error| format!("Unable to read {}: {}", path.display(), error))?;
+        if read == 0 {
+            return Ok(crc.sum());
+        }
+        crc.update(&buffer[..read]);
+    }
+}
+
+/// Copies `source` beside `target` under a temporary name and only renames
+/// it into place once its checksum matches.
+fn copy_verified(source: &Path, target: &Path) -> Result<u32, String> {
+    let name = target.file_name().unwrap_or_default().to_string_lossy();
+    let partial = target.with_file_name(format!(".{}.relocating", name));
+    let copied = fs::copy(source, &partial)
+        .map_err(|error| format!("Unable to copy {}: {}", source.display(), error))
+        .and_then(|_| Ok((checksum(source)?, checksum(&partial)?)))
+        .and_then(|(expected, actual)| {
+            if expected != actual {
+                return Err(format!(
+                    "The copy of {} does not match the original.",
+                    source.display()
+                ));
+            }
+            fs::rename(&partial, target)
+                .map_err(|error| format!("Unable to copy {}: {}", source.display(), error))?;
+            Ok(expected)
+        });
+    if copied.is_err() {
+        fs::remove_file(&partial).ok();
+    }
+    copied
+}
+
+/// The image followed by its companions, leaving out GPX tracks that only
+/// cover its capture time since other photos share them.
+fn family(path: &Path, with_companions: bool, finder: &mut CompanionFinder) -> Vec<PathBuf> {
+    let mut files = vec![path.to_path_buf()];
+    if with_companions {
+        files.extend(
+            finder
+                .find(path, None)
+                .into_iter()
+                .map(|companion| PathBuf::from(companion.path)),
+        );
+    }
+    files
+}
+
+/// Moves or copies `path` and, with `with_companions`, its companion files
+/// into `destination`. Every copy is verified before anything is removed,
+/// and a failure part way removes the copies again, so the image and its
+/// sidecars always stay together.
+pub fn relocate(
+    path: &Path,
+    destination: &Path,
+    mode: RelocateMode,
+    with_companions: bool,
+    finder: &mut CompanionFinder,
+    log: &OperationLog,
+) -> Relocation {
+    let mut relocation = Relocation {
+        path: path.to_string_lossy().into_owned(),
+        ..Relocation::default()
+    };
+    let files = family(path, with_companions, finder);
+    let moves: Vec<(PathBuf, PathBuf)> = files
+        .into_iter()
+        .map(|source| {
+            let target = destination.join(source.file_name().unwrap_or_default());
+            (source, target)
+        })
+        .collect();
+
+    let result = (|| -> Result<u32, String> {
+        if !path.is_file() {
+            return Err(format!("{} is not a file.", path.display()));
+        }
+        for (source, target) in &moves {
+            if source.parent() == Some(destination) {
+                return Err(format!("{} is already in that folder.", source.display()));
+            }
+            if target.exists() {
+                return Err(format!("{} already exists.", target.display()));
+            }
+            // A move deletes the source, so it must be writable like any edit.
+            if mode == RelocateMode::Move {
+                crate::path_scope::check_write(source).map_err(|violation| violation.message())?;
+            }
+        }
+        let mut copied: Vec<&Path> = Vec::new();
+        let mut image_checksum = 0;
+        for (source, target) in &moves {
+            match copy_verified(source, target) {
+                Ok(sum) => {
+                    if copied.is_empty() {
+                        image_checksum = sum;
+                    }
+                    copied.push(target);
+                }
+                Err(error) => {
+                    for target in copied {
+                        fs::remove_file(target).ok();
+                    }
+                    return Err(error);
+                }
+            }
+        }
+        Ok(image_checksum)
+    })();
+
+    match result {
+        Ok(sum) => relocation.checksum = Some(format!("{:08x}", sum)),
+        Err(error) => {
+            relocation.error = Some(error);
+            return relocation;
+        }
+    }
+
+    let mut problems = Vec::new();
+    for (source, target) in &moves {
+        if mode == RelocateMode::Move {
+            if let Err(error) = fs::remove_file(source) {
+                problems.push(format!(
+                    "{} was copied but could not be removed: {}",
+                    source.display(),
+                    error
+                ));
+            }
+        }
+        let change = FieldChange {
+            tag: "Location".to_string(),
+            before: Some(source.to_string_lossy().into_owned()),
+            after: Some(target.to_string_lossy().into_owned()),
+        };
+        if let Err(error) = log.record(&OperationRecord::new(
+            mode.operation(),
+            target,
+            vec![change],
+        )) {
+            problems.push(error);
+        }
+    }
+    let mut targets = moves
+        .into_iter()
+        .map(|(_, target)| target.to_string_lossy().into_owned());
+    relocation.destination = targets.next().unwrap_or_default();
+    relocation.companions = targets.collect();
+    if !problems.is_empty() {
+        relocation.error = Some(problems.join(" "));
+    }
+    relocation
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn images_move_with_their_sidecars_and_clashes_touch_nothing() {
+        let root = crate::fixtures::temp_path("relocate");
+        let (from, to) = (root.join("card"), root.join("archive"));
+        fs::create_dir_all(&from).unwrap();
+        fs::create_dir_all(&to).unwrap();
+        for (name, contents) in [
+            ("IMG_0001.CR2", "raw"),
+            ("IMG_0001.CR2.xmp", "edits"),
+            ("IMG_0001.CR2_original", "backup"),
+            ("IMG_0002.CR2", "raw"),
+            ("IMG_0002.xmp", "edits"),
+        ] {
+            fs::write(from.join(name), contents).unwrap();
+        }
+        fs::write(to.join("IMG_0002.xmp"), "other edits").unwrap();
+        let log = OperationLog::open(root.join("operations.jsonl"));
+        let mut finder = CompanionFinder::default();
+
+        let moved = relocate(
+            &from.join("IMG_0001.CR2"),
+            &to,
+            RelocateMode::Move,
+            true,
+            &mut finder,
+            &log,
+        );
+        let clash = relocate(
+            &from.join("IMG_0002.CR2"),
+            &to,
+            RelocateMode::Move,
+            true,
+            &mut finder,
+            &log,
+        );
+        let mut moved_names: Vec<String> = fs::read_dir(&to)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        moved_names.sort();
+        let left_behind = from.join("IMG_0002.CR2").exists() && !from.join("IMG_0001.CR2").exists();
+        let history: Vec<String> = log
+            .history(&to.join("IMG_0001.CR2.xmp"))
+            .unwrap()
+            .into_iter()
+            .map(|record| record.operation)
+            .collect();
+        fs::remove_dir_all(&root).ok();
+
+        assert_eq!(moved.error, None);
+        assert_eq!(moved.companions.len(), 2);
+        assert_eq!(moved.checksum.as_deref(), Some("1ab3db55"));
+        assert_eq!(
+            moved_names,
+            [
+                "IMG_0001.CR2",
+                "IMG_0001.CR2.xmp",
+                "IMG_0001.CR2_original",
+                "IMG_0002.xmp"
+            ]
+        );
+        assert!(clash
+            .error
+            .unwrap()
+            .ends_with("IMG_0002.xmp already exists."));
+        assert!(left_behind);
+        assert_eq!(history, ["move"]);
+    }
+
+    #[test]
+    fn moves_out_of_a_photos_library_are_refused() {
+        let root = crate::fixtures::temp_path("relocate_library");
+        let originals = root.join("Photos.photoslibrary/originals/A");
+        let to = root.join("archive");
+        fs::create_dir_all(&originals).unwrap();
+        fs::create_dir_all(&to).unwrap();
+        fs::write(originals.join("ABCD.jpeg"), "jpeg").unwrap();
+        let log = OperationLog::open(root.join("operations.jsonl"));
+
+        let moved = relocate(
+            &originals.join("ABCD.jpeg"),
+            &to,
+            RelocateMode::Move,
+            false,
+            &mut CompanionFinder::default(),
+            &log,
+        );
+        let kept = originals.join("ABCD.jpeg").exists() && !to.join("ABCD.jpeg").exists();
+        fs::remove_dir_all(&root).ok();
+
+        assert!(moved.error.is_some());
+        assert!(kept);
+    }
+}
@@ -0,0 +1,355 @@
+use crate::{
+    format::FormatStyle, gps::GpsReading, thumbnail::embedded_thumbnail, track::escape_xml,
+    ExifField,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::Serialize;
+use std::path::Path;
+
+const STYLE: &str = "body{font-family:system-ui,sans-serif;margin:2rem auto;max-width:60rem;color:#222}\
+h1{font-size:1.4rem;word-break:break-all}h2{font-size:1.1rem;margin-top:2rem}\
+table{border-collapse:collapse;width:100%}td{border-top:1px solid #ddd;padding:.3rem .5rem;vertical-align:top}\
+td:first-child{width:30%;color:#555}img.thumbnail{max-width:20rem;border:1px solid #ccc}\
+iframe{width:100%;height:18rem;border:1px solid #ccc}footer{margin-top:2rem;color:#888;font-size:.8rem}";
+
+const DIFF_STYLE: &str = "th{text-align:left;padding:.3rem .5rem}td:first-child{width:auto}\
+tr.changed{background:#fff4cc}tr.added{background:#dcf5dc}tr.removed{background:#fbdcdc}tr.unchanged{color:#777}";
+
+#[derive(Debug, Serialize)]
+pub struct HtmlReport {
+    path: String,
+    fields: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DiffStatus {
+    Unchanged,
+    Changed,
+    Added,
+    Removed,
+}
+
+impl DiffStatus {
+    fn label(self) -> &'static str {
+        match self {
+            DiffStatus::Unchanged => "unchanged",
+            DiffStatus::Changed => "changed",
+            DiffStatus::Added => "added",
+            DiffStatus::Removed => "removed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FieldDiff {
+    pub ifd: String,
+    pub tag: String,
+    pub before: Option<String>,
+    pub after: Option<String>,
+    pub status: DiffStatus,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffReport {
+    path: String,
+    changed: usize,
+    added: usize,
+    removed: usize,
+    unchanged: usize,
+}
+
+/// What a report shows for one image.
+pub struct ReportContents<'a> {
+    pub file_name: &'a str,
+    pub fields: &'a [ExifField],
+    pub thumbnail: Option<&'a [u8]>,
+    pub position: Option<(f64, f64)>,
+}
+
+/// Writes a single HTML page describing `image` that opens anywhere without
+/// the app: styles and thumbnail are inlined, only the map tile needs a network.
+pub fn export_html_report(
+    image: &Path,
+    extra_fields: Vec<ExifField>,
+    output: &Path,
+) -> Result<HtmlReport, String> {
+    let data = crate::load_file_data(image)?;
+    let mut fields = crate::collect_fields_with_style(&data, FormatStyle::default())?;
+    fields.extend(extra_fields);
+    let exif = crate::read_exif_container(&data);
+    let file_name = image
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| image.to_string_lossy().into_owned());
+
+    let html = render_html(&ReportContents {
+        file_name: &file_name,
+        fields: &fields,
+        thumbnail: exif.as_ref().and_then(embedded_thumbnail),
+        position: exif
+            .as_ref()
+            .and_then(|exif| GpsReading::from_exif(exif).position()),
+    });
+    crate::write_file_atomically(output, html.as_bytes())?;
+    Ok(HtmlReport {
+        path: output.to_string_lossy().into_owned(),
+        fields: fields.len(),
+    })
+}
+
+pub fn render_html(report: &ReportContents) -> String {
+    let title = escape_xml(report.file_name);
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>Metadata: {title}</title>\n<style>{STYLE}</style>\n</head>\n<body>\n\
+         <h1>{title}</h1>\n"
+    );
+
+    if let Some(thumbnail) = report.thumbnail {
+        html.push_str(&format!(
+            "<img class=\"thumbnail\" alt=\"Embedded thumbnail\" src=\"data:image/jpeg;base64,{}\">\n",
+            STANDARD.encode(thumbnail)
+        ));
+    }
+
+    if let Some((latitude, longitude)) = report.position {
+        let bbox = format!(
+            "{:.5},{:.5},{:.5},{:.5}",
+            longitude - 0.01,
+            latitude - 0.01,
+            longitude + 0.01,
+            latitude + 0.01
+        );
+        html.push_str(&format!(
+            "<h2>Location</h2>\n<p>{latitude:.6}, {longitude:.6} \
+             (<a href=\"https://www.openstreetmap.org/?mlat={latitude:.6}&amp;mlon={longitude:.6}#map=16/{latitude:.6}/{longitude:.6}\">open map</a>)</p>\n\
+             <iframe title=\"Map\" loading=\"lazy\" src=\"https://www.openstreetmap.org/export/embed.html?bbox={bbox}&amp;layer=mapnik&amp;marker={latitude:.6},{longitude:.6}\"></iframe>\n"
+        ));
+    }
+
+    // Groups keep the order in which the reader produced them.
+    let mut groups: Vec<(&str, Vec<&ExifField>)> = Vec::new();
+    for field in report.fields {
+        match groups.iter_mut().find(|(ifd, _)| *ifd == field.ifd) {
+            Some((_, members)) => members.push(field),
+            None => groups.push((&field.ifd, vec![field])),
+        }
+    }
+    if groups.is_empty() {
+        html.push_str("<p>This file carries no metadata.</p>\n");
+    }
+    for (ifd, members) in groups {
+        html.push_str(&format!(
+            "<h2>{}</h2>\n<table>\n",
+            escape_xml(group_title(ifd))
+        ));
+        for field in members {
+            html.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td></tr>\n",
+                escape_xml(&field.tag),
+                escape_xml(&field.value)
+            ));
+        }
+        html.push_str("</table>\n");
+    }
+
+    html.push_str("<footer>Generated by EXIF Viewer</footer>\n</body>\n</html>\n");
+    html
+}
+
+/// Pairs fields by IFD and tag, in the order of `before` followed by fields
+/// that only `after` has.
+pub fn diff_fields(before: &[ExifField], after: &[ExifField]) -> Vec<FieldDiff> {
+    let find = |fields: &[ExifField], field: &ExifField| {
+        fields
+            .iter()
+            .find(|other| other.ifd == field.ifd && other.tag == field.tag)
+            .map(|other| other.value.clone())
+    };
+    let mut diffs: Vec<FieldDiff> = before
+        .iter()
+        .map(|field| {
+            let after = find(after, field);
+            let status = match &after {
+                None => DiffStatus::Removed,
+                Some(value) if *value == field.value => DiffStatus::Unchanged,
+                Some(_) => DiffStatus::Changed,
+            };
+            FieldDiff {
+                ifd: field.ifd.clone(),
+                tag: field.tag.clone(),
+                before: Some(field.value.clone()),
+                after,
+                status,
+            }
+        })
+        .collect();
+    diffs.extend(
+        after
+            .iter()
+            .filter(|field| find(before, field).is_none())
+            .map(|field| FieldDiff {
+                ifd: field.ifd.clone(),
+                tag: field.tag.clone(),
+                before: None,
+                after: Some(field.value.clone()),
+                status: DiffStatus::Added,
+            }),
+    );
+    diffs
+}
+
+/// Writes an HTML page comparing the metadata of two files, with changed,
+/// added and removed fields highlighted. Browsers print it to PDF for
+/// archives that want a fixed document.
+pub fn export_diff_report(
+    before: &Path,
+    after: &Path,
+    output: &Path,
+) -> Result<DiffReport, String> {
+    let read = |path: &Path| -> Result<Vec<ExifField>, String> {
+        let data = crate::load_file_data(path)?;
+        crate::collect_fields_with_style(&data, FormatStyle::default())
+    };
+    let diffs = diff_fields(&read(before)?, &read(after)?);
+    let html = render_diff_html(before, after, &diffs);
+    crate::write_file_atomically(output, html.as_bytes())?;
+
+    let count = |status| diffs.iter().filter(|diff| diff.status == status).count();
+    Ok(DiffReport {
+        path: output.to_string_lossy().into_owned(),
+        changed: count(DiffStatus::Changed),
+        added: count(DiffStatus::Added),
+        removed: count(DiffStatus::Removed),
+        unchanged: count(DiffStatus::Unchanged),
+    })
+}
+
+fn describe_file(path: &Path) -> String {
+    let size = std::fs::metadata(path)
+        .map(|metadata| format!("{} bytes", metadata.len()))
+        .unwrap_or_else(|_| "size unknown".to_string());
+    format!("{} ({})", escape_xml(&path.to_string_lossy()), size)
+}
+
+pub fn render_diff_html(before: &Path, after: &Path, diffs: &[FieldDiff]) -> String {
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>Metadata comparison</title>\n<style>{STYLE}{DIFF_STYLE}</style>\n</head>\n<body>\n\
+         <h1>Metadata comparison</h1>\n<p>Before: {}<br>After: {}</p>\n",
+        describe_file(before),
+        describe_file(after)
+    );
+
+    let changes = diffs
+        .iter()
+        .filter(|diff| diff.status != DiffStatus::Unchanged)
+        .count();
+    html.push_str(&format!(
+        "<p>{} of {} fields differ.</p>\n<table>\n\
+         <tr><th>Group</th><th>Field</th><th>Before</th><th>After</th><th>Status</th></tr>\n",
+        changes,
+        diffs.len()
+    ));
+    for diff in diffs {
+        let cell = |value: &Option<String>| {
+            value
+                .as_deref()
+                .map(escape_xml)
+                .unwrap_or_else(|| "&mdash;".to_string())
+        };
+        html.push_str(&format!(
+            "<tr class=\"{status}\"><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{status}</td></tr>\n",
+            escape_xml(group_title(&diff.ifd)),
+            escape_xml(&diff.tag),
+            cell(&diff.before),
+            cell(&diff.after),
+            status = diff.status.label()
+        ));
+    }
+    html.push_str("</table>\n<footer>Generated by EXIF Viewer</footer>\n</body>\n</html>\n");
+    html
+}
+
+/// Readers label the EXIF IFDs `In(0)` and `In(1)`; clients get plain words.
+pub fn group_title(ifd: &str) -> &str {
+    match ifd {
+        "In(0)" => "Image",
+        "In(1)" => "Thumbnail",
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(ifd: &str, tag: &str, value: &str) -> ExifField {
+        ExifField {
+            tag: tag.to_string(),
+            ifd: ifd.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    #[test]
+    fn report_groups_fields_inlines_the_thumbnail_and_escapes_values() {
+        let fields = [
+            field("In(0)", "Make", "Canon"),
+            field("GPS", "Latitude", "48.8584"),
+            field("In(0)", "Artist", "<script>alert(1)</script>"),
+        ];
+        let html = render_html(&ReportContents {
+            file_name: "IMG_0001.jpg",
+            fields: &fields,
+            thumbnail: Some(&[0xFF, 0xD8, 0xFF, 0xD9]),
+            position: Some((48.8584, 2.2945)),
+        });
+
+        assert!(html.contains("<title>Metadata: IMG_0001.jpg</title>"));
+        assert!(html.contains("data:image/jpeg;base64,/9j/2Q=="));
+        assert!(html.contains("marker=48.858400,2.294500"));
+        assert!(html.contains("&lt;script&gt;"));
+        assert!(!html.contains("<script>"));
+        // Both primary fields land in one table ahead of the GPS group.
+        let primary = html.find("<h2>Image</h2>").unwrap();
+        let gps = html.find("<h2>GPS</h2>").unwrap();
+        assert!(primary < gps);
+        assert!(html[primary..gps].contains("Artist"));
+    }
+
+    #[test]
+    fn diff_pairs_fields_by_group_and_tag() {
+        let before = [
+            field("In(0)", "Artist", "Ada"),
+            field("In(0)", "Make", "Canon"),
+            field("In(1)", "Make", "Canon"),
+        ];
+        let after = [
+            field("In(0)", "Make", "Canon"),
+            field("In(0)", "Artist", "Studio"),
+            field("In(0)", "Software", "Retouch 2"),
+        ];
+        let diffs = diff_fields(&before, &after);
+        let statuses: Vec<DiffStatus> = diffs.iter().map(|diff| diff.status).collect();
+        assert_eq!(
+            statuses,
+            [
+                DiffStatus::Changed,
+                DiffStatus::Unchanged,
+                DiffStatus::Removed,
+                DiffStatus::Added
+            ]
+        );
+        assert_eq!(diffs[0].after.as_deref(), Some("Studio"));
+
+        let html = render_diff_html(Path::new("a.jpg"), Path::new("b.jpg"), &diffs);
+        assert!(html.contains("3 of 4 fields differ."));
+        assert!(html.contains(
+            "<tr class=\"removed\"><td>Thumbnail</td><td>Make</td><td>Canon</td><td>&mdash;</td>"
+        ));
+    }
+}
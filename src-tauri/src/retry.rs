@@ -0,0 +1,172 @@
+use std::{
+    fs,
+    io::{self, ErrorKind},
+    path::Path,
+    thread,
+    time::Duration,
+};
+
+/// How to try again when a file is momentarily unavailable: open in another
+/// program, held by a virus scanner or still being downloaded by a cloud
+/// sync client such as OneDrive or Dropbox.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Tries in all, the first included.
+    pub attempts: u32,
+    pub first_delay: Duration,
+    /// The delay doubles after every try, up to this.
+    pub max_delay: Duration,
+}
+
+/// About 0.7 s of waiting per file, so a folder of locked files holds a scan
+/// up for seconds rather than minutes.
+pub const READ_POLICY: RetryPolicy = RetryPolicy {
+    attempts: 4,
+    first_delay: Duration::from_millis(100),
+    max_delay: Duration::from_millis(800),
+};
+
+/// Sharing and lock violations, and a cloud files provider that has not
+/// started yet.
+#[cfg(windows)]
+const TRANSIENT_OS_ERRORS: &[i32] = &[32, 33, 362];
+#[cfg(not(windows))]
+const TRANSIENT_OS_ERRORS: &[i32] = &[];
+
+/// Whether `error` may go away by itself if the operation is tried again.
+pub fn is_transient(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        ErrorKind::WouldBlock
+            | ErrorKind::Interrupted
+            | ErrorKind::TimedOut
+            | ErrorKind::ResourceBusy
+    ) || error
+        .raw_os_error()
+        .is_some_and(|code| TRANSIENT_OS_ERRORS.contains(&code))
+}
+
+/// Like [`is_transient`] for a connection to a server, which may also drop
+/// or refuse to connect for a while.
+#[cfg_attr(
+    not(any(feature = "remote-storage", feature = "sftp")),
+    allow(dead_code)
+)]
+pub fn is_transient_network(error: &io::Error) -> bool {
+    is_transient(error)
+        || matches!(
+            error.kind(),
+            ErrorKind::ConnectionRefused
+                | ErrorKind::ConnectionReset
+                | ErrorKind::ConnectionAborted
+                | ErrorKind::NotConnected
+                | ErrorKind::BrokenPipe
+                | ErrorKind::UnexpectedEof
+        )
+}
+
+impl RetryPolicy {
+    /// Runs `attempt` until it succeeds, fails for good or runs out of tries.
+    pub fn run<T>(&self, mut attempt: impl FnMut() -> io::Result<T>) -> io::Result<T> {
+        let mut delay = self.first_delay;
+        let mut tries = 1;
+        loop {
+            match attempt() {
+                Err(error) if is_transient(&error) && tries < self.attempts => {
+                    thread::sleep(delay);
+                    delay = (delay * 2).min(self.max_delay);
+                    tries += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// Why a file could not be read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReadError {
+    /// Still unavailable after every retry; worth trying again later.
+    Unavailable(String),
+    /// An online-only file that scans were told not to download.
+    Placeholder(String),
+    Failed(String),
+}
+
+impl From<io::Error> for ReadError {
+    fn from(error: io::Error) -> Self {
+        if is_transient(&error) {
+            ReadError::Unavailable(format!(
+                "The file is in use by another program or still downloading ({}).",
+                error
+            ))
+        } else {
+            ReadError::Failed(error.to_string())
+        }
+    }
+}
+
+impl From<String> for ReadError {
+    fn from(message: String) -> Self {
+        ReadError::Failed(message)
+    }
+}
+
+impl From<ReadError> for String {
+    fn from(error: ReadError) -> Self {
+        match error {
+            ReadError::Unavailable(message)
+            | ReadError::Placeholder(message)
+            | ReadError::Failed(message) => message,
+        }
+    }
+}
+
+/// Reads all of `path`, retrying while it is momentarily unavailable.
+pub fn read_file(path: &Path, policy: &RetryPolicy) -> Result<Vec<u8>, ReadError> {
+    Ok(policy.run(|| fs::read(path))?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_transient_errors_are_retried() {
+        let policy = RetryPolicy {
+            attempts: 3,
+            first_delay: Duration::ZERO,
+            max_delay: Duration::ZERO,
+        };
+        let busy = || io::Error::from(ErrorKind::ResourceBusy);
+
+        let mut tries = 0;
+        let result = policy.run(|| {
+            tries += 1;
+            if tries < 3 {
+                Err(busy())
+            } else {
+                Ok(tries)
+            }
+        });
+        assert_eq!(result.unwrap(), 3);
+
+        tries = 0;
+        let result: io::Result<()> = policy.run(|| {
+            tries += 1;
+            Err(io::Error::from(ErrorKind::NotFound))
+        });
+        assert_eq!((result.is_err(), tries), (true, 1));
+
+        tries = 0;
+        let still_busy = policy.run(|| -> io::Result<()> {
+            tries += 1;
+            Err(busy())
+        });
+        assert_eq!(tries, 3);
+        assert!(matches!(
+            ReadError::from(still_busy.unwrap_err()),
+            ReadError::Unavailable(_)
+        ));
+    }
+}
@@ -0,0 +1,146 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::Path,
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Read rate a background scan is held to.
+const BACKGROUND_BYTES_PER_SEC: f64 = 16.0 * 1024.0 * 1024.0;
+/// Pause after every file in a background scan, even when under the rate.
+const BACKGROUND_YIELD: Duration = Duration::from_millis(2);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ScanPriority {
+    /// As fast as the disk allows.
+    #[default]
+    Normal,
+    /// Throttled reads on a thread the OS schedules last for CPU and disk,
+    /// so the machine stays usable during long scans.
+    Background,
+}
+
+impl ScanPriority {
+    /// Runs `work` at this priority. Background work gets its own thread so
+    /// the lowered priority never sticks to a thread that is reused later.
+    pub fn run<T: Send>(self, work: impl FnOnce() -> T + Send) -> T {
+        match self {
+            ScanPriority::Normal => work(),
+            ScanPriority::Background => thread::scope(|scope| {
+                scope
+                    .spawn(|| {
+                        lower_current_thread();
+                        work()
+                    })
+                    .join()
+                    .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+            }),
+        }
+    }
+}
+
+/// Spaces out reads so a background scan averages at most
+/// [`BACKGROUND_BYTES_PER_SEC`]; does nothing at normal priority.
+pub struct Pacer {
+    priority: ScanPriority,
+    started: Instant,
+    bytes: u64,
+}
+
+impl Pacer {
+    pub fn new(priority: ScanPriority) -> Self {
+        Pacer {
+            priority,
+            started: Instant::now(),
+            bytes: 0,
+        }
+    }
+
+    /// Call after each file; sleeps for as long as the scan is ahead of the
+    /// rate, judged by the size of the file at `path`.
+    pub fn after_file(&mut self, path: &Path) {
+        if self.priority == ScanPriority::Normal {
+            return;
+        }
+        self.bytes += fs::metadata(path)
+            .map(|metadata| metadata.len())
+            .unwrap_or_default();
+        thread::sleep(self.delay(self.started.elapsed()));
+    }
+
+    fn delay(&self, elapsed: Duration) -> Duration {
+        let due = Duration::from_secs_f64(self.bytes as f64 / BACKGROUND_BYTES_PER_SEC);
+        due.saturating_sub(elapsed).max(BACKGROUND_YIELD)
+    }
+}
+
+/// Asks the OS to schedule the calling thread's CPU time and disk I/O
+/// behind interactive work. Best effort: failures are ignored.
+#[cfg(target_os = "linux")]
+pub fn lower_current_thread() {
+    const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+    const IOPRIO_CLASS_IDLE: libc::c_int = 3;
+    const IOPRIO_CLASS_SHIFT: libc::c_int = 13;
+    // SAFETY: plain syscalls on the calling thread's own id.
+    unsafe {
+        let tid = libc::syscall(libc::SYS_gettid) as libc::id_t;
+        libc::setpriority(libc::PRIO_PROCESS, tid, 19);
+        libc::syscall(
+            libc::SYS_ioprio_set,
+            IOPRIO_WHO_PROCESS,
+            tid,
+            IOPRIO_CLASS_IDLE << IOPRIO_CLASS_SHIFT,
+        );
+    }
+}
+
+#[cfg(target_os = "macos")]
+pub fn lower_current_thread() {
+    // SAFETY: PRIO_DARWIN_THREAD with id 0 only affects the calling thread.
+    unsafe {
+        libc::setpriority(libc::PRIO_DARWIN_THREAD, 0, libc::PRIO_DARWIN_BG);
+    }
+}
+
+#[cfg(windows)]
+pub fn lower_current_thread() {
+    use std::ffi::c_void;
+    const THREAD_MODE_BACKGROUND_BEGIN: i32 = 0x0001_0000;
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetCurrentThread() -> *mut c_void;
+        fn SetThreadPriority(thread: *mut c_void, priority: i32) -> i32;
+    }
+    // SAFETY: GetCurrentThread returns a pseudo-handle that needs no closing.
+    unsafe {
+        SetThreadPriority(GetCurrentThread(), THREAD_MODE_BACKGROUND_BEGIN);
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+pub fn lower_current_thread() {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn background_pacing_keeps_to_the_read_rate() {
+        let mut pacer = Pacer::new(ScanPriority::Background);
+        pacer.bytes = 32 * 1024 * 1024;
+        assert_eq!(
+            pacer.delay(Duration::from_millis(500)),
+            Duration::from_millis(1500)
+        );
+        // Behind schedule: only the yield remains.
+        assert_eq!(pacer.delay(Duration::from_secs(5)), BACKGROUND_YIELD);
+    }
+
+    #[test]
+    fn background_work_runs_and_returns_its_result() {
+        assert_eq!(ScanPriority::Background.run(|| 6 * 7), 42);
+        assert_eq!(ScanPriority::Normal.run(|| "done"), "done");
+    }
+}
@@ -0,0 +1,193 @@
+use serde::Serialize;
+use std::{collections::VecDeque, sync::Mutex};
+
+/// Matches sent per page when the caller does not ask for a size.
+pub const DEFAULT_PAGE_SIZE: usize = 500;
+/// Finished scans kept for paging per window; starting another drops that
+/// window's oldest.
+const HELD_SCANS: usize = 4;
+
+/// One page of a stored scan result.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScanPage<T> {
+    /// Pass to `continue_scan` for the next page; `None` on the last one.
+    pub cursor: Option<String>,
+    /// Matches in the whole result.
+    pub total: usize,
+    pub matches: Vec<T>,
+}
+
+struct HeldScan<T> {
+    id: u64,
+    /// Label of the window that ran the scan; only it can page through it.
+    owner: String,
+    page_size: usize,
+    matches: Vec<T>,
+}
+
+struct Held<T> {
+    next_id: u64,
+    scans: VecDeque<HeldScan<T>>,
+}
+
+/// Keeps large scan results on the backend so the UI can fetch them a page
+/// at a time instead of in one huge IPC payload.
+pub struct ScanResults<T> {
+    held: Mutex<Held<T>>,
+}
+
+impl<T> Default for ScanResults<T> {
+    fn default() -> Self {
+        ScanResults {
+            held: Mutex::new(Held {
+                next_id: 1,
+                scans: VecDeque::new(),
+            }),
+        }
+    }
+}
+
+fn cursor(id: u64, offset: usize) -> String {
+    format!("{}:{}", id, offset)
+}
+
+fn parse_cursor(cursor: &str) -> Option<(u64, usize)> {
+    let (id, offset) = cursor.split_once(':')?;
+    Some((id.parse().ok()?, offset.parse().ok()?))
+}
+
+fn expired() -> String {
+    "These scan results are no longer available; run the scan again.".to_string()
+}
+
+impl<T: Clone> ScanResults<T> {
+    /// Stores `matches` for the `owner` window and returns their first page.
+    /// Results that fit in one page are not stored at all.
+    pub fn start(&self, owner: &str, matches: Vec<T>, page_size: usize) -> ScanPage<T> {
+        let page_size = page_size.max(1);
+        let total = matches.len();
+        if total <= page_size {
+            return ScanPage {
+                cursor: None,
+                total,
+                matches,
+            };
+        }
+        let mut held = self.held.lock().expect("scan results poisoned");
+        let id = held.next_id;
+        held.next_id += 1;
+        let first = matches[..page_size].to_vec();
+        if held.scans.iter().filter(|scan| scan.owner == owner).count() >= HELD_SCANS {
+            if let Some(oldest) = held.scans.iter().position(|scan| scan.owner == owner) {
+                held.scans.remove(oldest);
+            }
+        }
+        held.scans.push_back(HeldScan {
+            id,
+            owner: owner.to_string(),
+            page_size,
+            matches,
+        });
+        ScanPage {
+            cursor: Some(cursor(id, page_size)),
+            total,
+            matches: first,
+        }
+    }
+
+    /// The page `cursor` points at. The stored result is dropped once its
+    /// last page has been read.
+    pub fn page(&self, owner: &str, cursor_text: &str) -> Result<ScanPage<T>, String> {
+        let (id, offset) = parse_cursor(cursor_text).ok_or_else(expired)?;
+        let mut held = self.held.lock().expect("scan results poisoned");
+        let position = held
+            .scans
+            .iter()
+            .position(|scan| scan.id == id && scan.owner == owner)
+            .ok_or_else(expired)?;
+        let scan = &held.scans[position];
+        let total = scan.matches.len();
+        let end = total.min(offset.saturating_add(scan.page_size));
+        let matches = scan.matches.get(offset..end).ok_or_else(expired)?.to_vec();
+        let cursor = (end < total).then(|| cursor(id, end));
+        if cursor.is_none() {
+            held.scans.remove(position);
+        }
+        Ok(ScanPage {
+            cursor,
+            total,
+            matches,
+        })
+    }
+
+    /// Drops a stored result the UI no longer wants to page through.
+    pub fn release(&self, owner: &str, cursor_text: &str) {
+        if let Some((id, _)) = parse_cursor(cursor_text) {
+            self.held
+                .lock()
+                .expect("scan results poisoned")
+                .scans
+                .retain(|scan| scan.id != id || scan.owner != owner);
+        }
+    }
+
+    /// Drops every result held for a window that has closed.
+    pub fn release_owner(&self, owner: &str) {
+        self.held
+            .lock()
+            .expect("scan results poisoned")
+            .scans
+            .retain(|scan| scan.owner != owner);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pages_cover_the_result_once_and_then_expire() {
+        let results = ScanResults::default();
+        let mut page = results.start("main", (0..7).collect(), 3);
+        let mut seen = page.matches.clone();
+        while let Some(cursor) = page.cursor.clone() {
+            page = results.page("main", &cursor).unwrap();
+            assert_eq!(page.total, 7);
+            seen.extend(&page.matches);
+        }
+        assert_eq!(seen, (0..7).collect::<Vec<_>>());
+        assert!(results.page("main", "1:6").is_err());
+
+        let small = results.start("main", vec![1, 2], 3);
+        assert_eq!((small.cursor, small.matches), (None, vec![1, 2]));
+    }
+
+    #[test]
+    fn old_scans_are_dropped_when_new_ones_start() {
+        let results = ScanResults::default();
+        let cursors: Vec<String> = (0..=HELD_SCANS)
+            .map(|_| results.start("main", vec![0; 4], 2).cursor.unwrap())
+            .collect();
+        assert!(results.page("main", &cursors[0]).is_err());
+        assert_eq!(results.page("main", &cursors[1]).unwrap().matches, [0, 0]);
+        results.release("main", &cursors[2]);
+        assert!(results.page("main", &cursors[2]).is_err());
+        assert!(results.page("main", "nonsense").is_err());
+    }
+
+    #[test]
+    fn each_window_pages_only_its_own_scans() {
+        let results = ScanResults::default();
+        let main = results.start("main", vec![1, 2, 3], 1).cursor.unwrap();
+        let viewer: Vec<String> = (0..=HELD_SCANS)
+            .map(|_| results.start("viewer-1", vec![4, 5], 1).cursor.unwrap())
+            .collect();
+
+        assert!(results.page("viewer-1", &main).is_err());
+        results.release("viewer-1", &main);
+        results.release_owner("viewer-1");
+        assert!(results.page("viewer-1", &viewer[HELD_SCANS]).is_err());
+        assert_eq!(results.page("main", &main).unwrap().matches, [2]);
+    }
+}
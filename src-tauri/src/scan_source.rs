@@ -0,0 +1,268 @@
+use crate::{capture::first_ascii, gps::first_rational, icc, ExifField};
+use exif::{Context, Exif, In, Tag, Value};
+use serde::{Deserialize, Serialize};
+
+pub const COMPUTED_SCAN_IFD: &str = "Scan";
+
+const DOCUMENT_NAME: Tag = Tag(Context::Tiff, 269);
+const PAGE_NAME: Tag = Tag(Context::Tiff, 285);
+/// Scans are rarely below this and camera files rarely claim more.
+const SCAN_MIN_DPI: f64 = 150.0;
+
+/// Scanner drivers and scanning applications that name themselves in the
+/// Software tag, matched case-insensitively.
+const SCANNER_SOFTWARE: &[&str] = &[
+    "vuescan",
+    "epson scan",
+    "silverfast",
+    "naps2",
+    "scangear",
+    "canoscan",
+    "hp scan",
+    "scansnap",
+    "paperport",
+    "nikon scan",
+    "xsane",
+    "simple scan",
+    "twain",
+    "wia",
+];
+
+/// Where an image came from, as far as its metadata tells.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CaptureKind {
+    ScannedDocument,
+    FilmScan,
+    CameraPhoto,
+    Unknown,
+}
+
+impl CaptureKind {
+    fn label(self) -> &'static str {
+        match self {
+            CaptureKind::ScannedDocument => "Scanned document",
+            CaptureKind::FilmScan => "Film scan",
+            CaptureKind::CameraPhoto => "Camera photo",
+            CaptureKind::Unknown => "Unknown",
+        }
+    }
+}
+
+fn text(exif: &Exif, tag: Tag) -> Option<String> {
+    let text = String::from_utf8_lossy(first_ascii(exif, tag)?)
+        .trim_matches(['\0', ' '])
+        .to_string();
+    (!text.is_empty()).then_some(text)
+}
+
+/// Horizontal and vertical resolution in dots per inch.
+fn resolution(exif: &Exif) -> Option<(f64, f64)> {
+    let x = first_rational(exif, Tag::XResolution)?;
+    let y = first_rational(exif, Tag::YResolution).unwrap_or(x);
+    let per_inch = match exif
+        .get_field(Tag::ResolutionUnit, In::PRIMARY)
+        .and_then(|field| field.value.get_uint(0))
+    {
+        Some(3) => 2.54,
+        _ => 1.0,
+    };
+    Some((x * per_inch, y * per_inch))
+}
+
+fn has_exposure(exif: &Exif) -> bool {
+    [
+        Tag::ExposureTime,
+        Tag::FNumber,
+        Tag::FocalLength,
+        Tag::PhotographicSensitivity,
+    ]
+    .iter()
+    .any(|tag| exif.get_field(*tag, In::PRIMARY).is_some())
+}
+
+/// Decides whether the image is a scan or a camera photo, with the reason.
+pub fn classify(exif: &Exif) -> (CaptureKind, String) {
+    let file_source = exif
+        .get_field(Tag::FileSource, In::PRIMARY)
+        .and_then(|field| match &field.value {
+            Value::Undefined(bytes, _) => bytes.first().copied(),
+            _ => None,
+        });
+    match file_source {
+        Some(1) => {
+            return (
+                CaptureKind::FilmScan,
+                "FileSource is a film scanner".to_string(),
+            )
+        }
+        Some(2) => {
+            return (
+                CaptureKind::ScannedDocument,
+                "FileSource is a reflection print scanner".to_string(),
+            )
+        }
+        Some(3) => {
+            return (
+                CaptureKind::CameraPhoto,
+                "FileSource is a digital camera".to_string(),
+            )
+        }
+        _ => {}
+    }
+    if let Some(software) = text(exif, Tag::Software) {
+        let lower = software.to_lowercase();
+        if SCANNER_SOFTWARE.iter().any(|name| lower.contains(name)) {
+            return (
+                CaptureKind::ScannedDocument,
+                format!("written by scanning software ({})", software),
+            );
+        }
+    }
+    if has_exposure(exif) {
+        return (
+            CaptureKind::CameraPhoto,
+            "records exposure settings".to_string(),
+        );
+    }
+    match resolution(exif) {
+        Some((x, y)) if x.min(y) >= SCAN_MIN_DPI => (
+            CaptureKind::ScannedDocument,
+            format!("{:.0} dpi without exposure settings", x.min(y)),
+        ),
+        _ => (
+            CaptureKind::Unknown,
+            "no scanner or camera details".to_string(),
+        ),
+    }
+}
+
+fn field(tag: &str, value: String) -> ExifField {
+    ExifField {
+        tag: tag.to_string(),
+        ifd: COMPUTED_SCAN_IFD.to_string(),
+        value,
+    }
+}
+
+/// The classification with its reason and, for scans, the scan resolution,
+/// scanner, software, source ICC profile and document and page names.
+pub fn computed_fields(exif: &Exif, data: &[u8]) -> Vec<ExifField> {
+    let (kind, reason) = classify(exif);
+    let mut fields = vec![
+        field("Classification", kind.label().to_string()),
+        field("ClassificationReason", reason),
+    ];
+    if !matches!(kind, CaptureKind::ScannedDocument | CaptureKind::FilmScan) {
+        return fields;
+    }
+    if let Some((x, y)) = resolution(exif) {
+        fields.push(field("ScanResolution", format!("{:.0} × {:.0} dpi", x, y)));
+    }
+    let scanner = [text(exif, Tag::Make), text(exif, Tag::Model)]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join(" ");
+    if !scanner.is_empty() {
+        fields.push(field("Scanner", scanner));
+    }
+    for (tag, name) in [
+        (Tag::Software, "ScanSoftware"),
+        (DOCUMENT_NAME, "DocumentName"),
+        (PAGE_NAME, "PageName"),
+    ] {
+        if let Some(value) = text(exif, tag) {
+            fields.push(field(name, value));
+        }
+    }
+    if let Some(profile) = icc::extract(data, Some(exif))
+        .as_deref()
+        .and_then(icc::description)
+    {
+        fields.push(field("SourceProfile", profile));
+    }
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{ascii_field, exif_from_fields, rational_field};
+    use exif::Field;
+
+    fn undefined_field(tag: Tag, bytes: &[u8]) -> Field {
+        Field {
+            tag,
+            ifd_num: In::PRIMARY,
+            value: Value::Undefined(bytes.to_vec(), 0),
+        }
+    }
+
+    fn values(fields: Vec<ExifField>) -> Vec<(String, String)> {
+        fields
+            .into_iter()
+            .map(|field| (field.tag, field.value))
+            .collect()
+    }
+
+    /// A v2 profile whose only tag is `desc`.
+    fn icc_with_description(description: &str) -> Vec<u8> {
+        let mut profile = vec![0; 128];
+        profile.extend_from_slice(&1u32.to_be_bytes());
+        profile.extend_from_slice(b"desc");
+        profile.extend_from_slice(&144u32.to_be_bytes());
+        profile.extend_from_slice(&(12 + description.len() as u32 + 1).to_be_bytes());
+        profile.extend_from_slice(b"desc\0\0\0\0");
+        profile.extend_from_slice(&(description.len() as u32 + 1).to_be_bytes());
+        profile.extend_from_slice(description.as_bytes());
+        profile.push(0);
+        profile
+    }
+
+    #[test]
+    fn scanner_software_and_profile_mark_a_scanned_document() {
+        let exif = exif_from_fields(&[
+            ascii_field(Tag::Make, "EPSON"),
+            ascii_field(Tag::Model, "Perfection V600"),
+            ascii_field(Tag::Software, "EPSON Scan 3.9"),
+            rational_field(Tag::XResolution, &[(600, 1)]),
+            rational_field(Tag::YResolution, &[(600, 1)]),
+            undefined_field(
+                icc::ICC_PROFILE,
+                &icc_with_description("EPSON Standard RGB"),
+            ),
+        ]);
+        let expected = [
+            ("Classification", "Scanned document"),
+            (
+                "ClassificationReason",
+                "written by scanning software (EPSON Scan 3.9)",
+            ),
+            ("ScanResolution", "600 × 600 dpi"),
+            ("Scanner", "EPSON Perfection V600"),
+            ("ScanSoftware", "EPSON Scan 3.9"),
+            ("SourceProfile", "EPSON Standard RGB"),
+        ]
+        .map(|(tag, value)| (tag.to_string(), value.to_string()));
+        assert_eq!(values(computed_fields(&exif, &[])), expected);
+    }
+
+    #[test]
+    fn file_source_and_exposure_settings_tell_scans_from_photos() {
+        let film = exif_from_fields(&[undefined_field(Tag::FileSource, &[1])]);
+        assert_eq!(classify(&film).0, CaptureKind::FilmScan);
+
+        let photo = exif_from_fields(&[
+            rational_field(Tag::XResolution, &[(300, 1)]),
+            rational_field(Tag::ExposureTime, &[(1, 250)]),
+        ]);
+        assert_eq!(classify(&photo).0, CaptureKind::CameraPhoto);
+        assert_eq!(values(computed_fields(&photo, &[])).len(), 2);
+
+        let flatbed = exif_from_fields(&[rational_field(Tag::XResolution, &[(300, 1)])]);
+        assert_eq!(classify(&flatbed).0, CaptureKind::ScannedDocument);
+        let screenshot = exif_from_fields(&[rational_field(Tag::XResolution, &[(72, 1)])]);
+        assert_eq!(classify(&screenshot).0, CaptureKind::Unknown);
+    }
+}
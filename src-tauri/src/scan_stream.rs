@@ -0,0 +1,86 @@
+use crate::AestheticMatch;
+use serde::Serialize;
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+/// One line of a streamed scan. Matches arrive in the order files are
+/// visited, not sorted by score, and a `done` event closes the stream.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum ScanEvent {
+    Match(Box<AestheticMatch>),
+    Done { scanned: usize, matches: usize },
+}
+
+/// Writes scan events as newline-delimited JSON, one event per line,
+/// flushing after each so a reader tailing the file sees matches at once.
+pub struct NdjsonWriter<W: Write> {
+    out: W,
+}
+
+impl NdjsonWriter<BufWriter<File>> {
+    pub fn create(path: &Path) -> Result<Self, String> {
+        let file = File::create(path)
+            .map_err(|error| format!("Unable to create {}: {}", path.display(), error))?;
+        Ok(NdjsonWriter::new(BufWriter::new(file)))
+    }
+}
+
+impl<W: Write> NdjsonWriter<W> {
+    pub fn new(out: W) -> Self {
+        NdjsonWriter { out }
+    }
+
+    pub fn write(&mut self, event: &ScanEvent) -> Result<(), String> {
+        serde_json::to_writer(&mut self.out, event).map_err(|error| error.to_string())?;
+        self.out
+            .write_all(b"\n")
+            .and_then(|_| self.out.flush())
+            .map_err(|error| error.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MatchStatus;
+
+    #[test]
+    fn each_event_is_one_json_line() {
+        let mut writer = NdjsonWriter::new(Vec::new());
+        writer
+            .write(&ScanEvent::Match(Box::new(AestheticMatch {
+                path: "a.png".to_string(),
+                score: 0.9,
+                sidecar: None,
+                fields: None,
+                captured: None,
+                captured_from: None,
+                day: None,
+                roll: None,
+                frame: None,
+                place: None,
+                companions: Vec::new(),
+                status: MatchStatus::Read,
+                unavailable: None,
+                capture: None,
+            })))
+            .unwrap();
+        writer
+            .write(&ScanEvent::Done {
+                scanned: 3,
+                matches: 1,
+            })
+            .unwrap();
+
+        let text = String::from_utf8(writer.out).unwrap();
+        assert_eq!(
+            text,
+            "{\"type\":\"match\",\"path\":\"a.png\",\"score\":0.9}\n\
+             {\"type\":\"done\",\"scanned\":3,\"matches\":1}\n"
+        );
+    }
+}
@@ -0,0 +1,266 @@
+use crate::ExifField;
+use serde::Serialize;
+use std::path::Path;
+
+/// What a corpus file must produce.
+enum Expect {
+    /// A field in `group` named `tag` whose value contains `value`.
+    Field {
+        group: &'static str,
+        tag: &'static str,
+        value: &'static str,
+    },
+    /// Parses cleanly but carries no metadata.
+    Nothing,
+    /// Fails with an error containing this text.
+    Error(&'static str),
+}
+
+struct Case {
+    file: &'static str,
+    format: &'static str,
+    data: &'static [u8],
+    expect: &'static [Expect],
+}
+
+macro_rules! corpus_file {
+    ($name:literal) => {
+        (
+            $name,
+            include_bytes!(concat!("../selftest/", $name)).as_slice(),
+        )
+    };
+}
+
+const fn case(
+    (file, data): (&'static str, &'static [u8]),
+    format: &'static str,
+    expect: &'static [Expect],
+) -> Case {
+    Case {
+        file,
+        format,
+        data,
+        expect,
+    }
+}
+
+const fn model(value: &'static str) -> Expect {
+    Expect::Field {
+        group: "In(0)",
+        tag: "Model",
+        value,
+    }
+}
+
+/// Tiny fixtures in `selftest/`, one or more per supported container.
+const CORPUS: &[Case] = &[
+    case(
+        corpus_file!("jpeg-exif.jpg"),
+        "jpeg",
+        &[model("JPEG big-endian")],
+    ),
+    case(
+        corpus_file!("jpeg-gps-little-endian.jpg"),
+        "jpeg",
+        &[
+            model("JPEG little-endian"),
+            Expect::Field {
+                group: "GPS (computed)",
+                tag: "Position",
+                value: "48.858333, 2.294444",
+            },
+        ],
+    ),
+    case(
+        corpus_file!("jpeg-xmp-iptc.jpg"),
+        "jpeg",
+        &[
+            Expect::Field {
+                group: "XMP",
+                tag: "dc:creator",
+                value: "Self-test",
+            },
+            Expect::Field {
+                group: "IPTC",
+                tag: "By-line",
+                value: "Self-test",
+            },
+        ],
+    ),
+    case(
+        corpus_file!("jpeg-no-metadata.jpg"),
+        "jpeg",
+        &[Expect::Nothing],
+    ),
+    case(
+        corpus_file!("jpeg-truncated.jpg"),
+        "jpeg",
+        &[Expect::Error("Broken JPEG file")],
+    ),
+    case(
+        corpus_file!("tiff-little-endian.tif"),
+        "tiff",
+        &[model("TIFF little-endian")],
+    ),
+    case(
+        corpus_file!("tiff-big-endian.tif"),
+        "tiff",
+        &[model("TIFF big-endian")],
+    ),
+    case(corpus_file!("png-exif.png"), "png", &[model("PNG eXIf")]),
+    case(
+        corpus_file!("png-text.png"),
+        "png",
+        &[
+            Expect::Field {
+                group: "PNG tEXt",
+                tag: "Comment",
+                value: "Self-test tEXt",
+            },
+            Expect::Field {
+                group: "PNG zTXt",
+                tag: "Description",
+                value: "Self-test zTXt",
+            },
+            Expect::Field {
+                group: "PNG iTXt",
+                tag: "Title",
+                value: "Self-test iTXt",
+            },
+        ],
+    ),
+    case(
+        corpus_file!("webp-exif.webp"),
+        "webp",
+        &[model("WebP EXIF")],
+    ),
+    case(
+        corpus_file!("heic-exif.heic"),
+        "heic",
+        &[model("HEIC EXIF")],
+    ),
+    case(
+        corpus_file!("avif-exif.avif"),
+        "avif",
+        &[model("AVIF EXIF")],
+    ),
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CaseResult {
+    pub file: String,
+    pub passed: bool,
+    /// Why the case failed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub problem: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FormatResult {
+    pub format: String,
+    pub passed: bool,
+    pub cases: Vec<CaseResult>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SelfTestReport {
+    pub passed: bool,
+    pub formats: Vec<FormatResult>,
+}
+
+/// Parses every file of the bundled corpus and checks it against its
+/// expected fields, grouped by format.
+pub fn run() -> SelfTestReport {
+    let mut formats: Vec<FormatResult> = Vec::new();
+    for case in CORPUS {
+        let result = CaseResult {
+            file: case.file.to_string(),
+            ..check(case)
+        };
+        match formats
+            .iter_mut()
+            .find(|format| format.format == case.format)
+        {
+            Some(format) => {
+                format.passed &= result.passed;
+                format.cases.push(result);
+            }
+            None => formats.push(FormatResult {
+                format: case.format.to_string(),
+                passed: result.passed,
+                cases: vec![result],
+            }),
+        }
+    }
+    SelfTestReport {
+        passed: formats.iter().all(|format| format.passed),
+        formats,
+    }
+}
+
+fn check(case: &Case) -> CaseResult {
+    let parsed = crate::isolation::guarded(Path::new(case.file), || {
+        crate::collect_fields_from_bytes(case.data)
+    });
+    let problem = case.expect.iter().find_map(|expect| unmet(expect, &parsed));
+    CaseResult {
+        file: String::new(),
+        passed: problem.is_none(),
+        problem,
+    }
+}
+
+/// Describes how `parsed` misses `expect`, or `None` when it meets it.
+fn unmet(expect: &Expect, parsed: &Result<Vec<ExifField>, String>) -> Option<String> {
+    match (expect, parsed) {
+        (Expect::Error(text), Err(error)) => (!error.contains(text))
+            .then(|| format!("Expected an error about \"{}\", got: {}", text, error)),
+        (Expect::Error(text), Ok(_)) => Some(format!(
+            "Expected an error about \"{}\", but the file parsed.",
+            text
+        )),
+        (_, Err(error)) => Some(error.clone()),
+        (Expect::Nothing, Ok(fields)) => (!fields.is_empty())
+            .then(|| format!("Expected no metadata, found {} fields.", fields.len())),
+        (Expect::Field { group, tag, value }, Ok(fields)) => {
+            match fields
+                .iter()
+                .find(|field| field.ifd == *group && field.tag == *tag)
+            {
+                None => Some(format!("{} / {} is missing.", group, tag)),
+                Some(field) if !field.value.contains(value) => Some(format!(
+                    "{} / {} is {}, expected {}.",
+                    group, tag, field.value, value
+                )),
+                Some(_) => None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_bundled_corpus_passes() {
+        let report = run();
+        let failures: Vec<&CaseResult> = report
+            .formats
+            .iter()
+            .flat_map(|format| &format.cases)
+            .filter(|case| !case.passed)
+            .collect();
+        assert!(failures.is_empty(), "{:?}", failures);
+        let formats: Vec<&str> = report
+            .formats
+            .iter()
+            .map(|format| format.format.as_str())
+            .collect();
+        assert_eq!(formats, ["jpeg", "tiff", "png", "webp", "heic", "avif"]);
+    }
+}
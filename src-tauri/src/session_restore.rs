@@ -0,0 +1,266 @@
+use crate::{
+    capture_sort::ScanSort, projection::FieldSelection, scan_priority::ScanPriority,
+    sessions::WindowSession, ScanFilters,
+};
+use chrono::DateTime;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+/// Progress through a step is written to disk after this many files or this
+/// long since the last save, whichever comes first. Every other change, and
+/// the end of a step or job, is written at once.
+const SAVE_EVERY: usize = 20;
+const SAVE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// What `start_aesthetic_scan` was last called with, enough to run it again.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScanParameters {
+    pub path: String,
+    pub min_score: f64,
+    pub filters: ScanFilters,
+    pub fields: Option<FieldSelection>,
+    pub priority: ScanPriority,
+    pub sort: ScanSort,
+    pub page_size: usize,
+    pub companions: bool,
+}
+
+/// A pipeline run that had not finished when the session was last saved.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingJob {
+    pub id: u64,
+    pub pipeline: String,
+    pub paths: Vec<String>,
+    /// The step that was running, counting from 1.
+    pub step: usize,
+    pub step_count: usize,
+    /// Files the running step had finished, under their current names.
+    pub processed: Vec<String>,
+    /// RFC 3339 UTC timestamp.
+    pub started: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SavedSession {
+    pub windows: Vec<WindowSession>,
+    pub last_scan: Option<ScanParameters>,
+    pub jobs: Vec<PendingJob>,
+}
+
+struct JournalState {
+    session: SavedSession,
+    /// Files finished since the session was last written.
+    unsaved: usize,
+    saved_at: Instant,
+}
+
+/// Keeps the backend session on disk as it changes, so the next launch can
+/// pick up after a crash.
+pub struct SessionJournal {
+    location: PathBuf,
+    current: Mutex<JournalState>,
+    /// What the previous run left behind, until it is restored.
+    previous: Mutex<Option<SavedSession>>,
+    next_job: AtomicU64,
+}
+
+impl SessionJournal {
+    pub fn open(location: PathBuf) -> Self {
+        let previous: Option<SavedSession> = fs::read(&location)
+            .ok()
+            .and_then(|data| serde_json::from_slice(&data).ok());
+        // Unfinished jobs stay on record until they are restored, in case
+        // this run ends badly too.
+        let jobs = previous
+            .as_ref()
+            .map(|previous| previous.jobs.clone())
+            .unwrap_or_default();
+        let next_job = jobs.iter().map(|job| job.id).max().unwrap_or(0) + 1;
+        SessionJournal {
+            location,
+            current: Mutex::new(JournalState {
+                session: SavedSession {
+                    jobs,
+                    ..SavedSession::default()
+                },
+                unsaved: 0,
+                saved_at: Instant::now(),
+            }),
+            previous: Mutex::new(previous),
+            next_job: AtomicU64::new(next_job),
+        }
+    }
+
+    /// The previous run's session, handed out once. Its unfinished jobs are
+    /// then dropped from the record; resuming one starts a new job.
+    pub fn restore(&self) -> Option<SavedSession> {
+        let previous = self
+            .previous
+            .lock()
+            .expect("session journal poisoned")
+            .take()?;
+        self.modify(|current| {
+            current
+                .jobs
+                .retain(|job| !previous.jobs.iter().any(|old| old.id == job.id))
+        });
+        Some(previous)
+    }
+
+    pub fn save_windows(&self, windows: Vec<WindowSession>) {
+        self.modify(|current| current.windows = windows);
+    }
+
+    pub fn save_scan(&self, scan: ScanParameters) {
+        self.modify(|current| current.last_scan = Some(scan));
+    }
+
+    /// Records a pipeline run over `paths` and returns its id.
+    pub fn start_job(&self, pipeline: &str, paths: &[String], step_count: usize) -> u64 {
+        let id = self.next_job.fetch_add(1, Ordering::SeqCst);
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs() as i64)
+            .unwrap_or_default();
+        let job = PendingJob {
+            id,
+            pipeline: pipeline.to_string(),
+            paths: paths.to_vec(),
+            step: 1,
+            step_count,
+            processed: Vec::new(),
+            started: DateTime::from_timestamp(now, 0)
+                .map(|time| time.to_rfc3339())
+                .unwrap_or_default(),
+        };
+        self.modify(|current| current.jobs.push(job));
+        id
+    }
+
+    /// Notes that `step` of job `id` finished `path`. Moving on to another
+    /// step starts its list of finished files afresh. Files within a step
+    /// are saved in batches, so a crash may redo the last few.
+    pub fn job_progress(&self, id: u64, step: usize, path: Option<&str>) {
+        let mut state = self.current.lock().expect("session journal poisoned");
+        let Some(job) = state.session.jobs.iter_mut().find(|job| job.id == id) else {
+            return;
+        };
+        let new_step = job.step != step;
+        if new_step {
+            job.step = step;
+            job.processed.clear();
+        }
+        job.processed.extend(path.map(str::to_string));
+        state.unsaved += 1;
+        if new_step || state.unsaved >= SAVE_EVERY || state.saved_at.elapsed() >= SAVE_INTERVAL {
+            self.save(&mut state);
+        }
+    }
+
+    pub fn finish_job(&self, id: u64) {
+        self.modify(|current| current.jobs.retain(|job| job.id != id));
+    }
+
+    fn modify(&self, change: impl FnOnce(&mut SavedSession)) {
+        let mut state = self.current.lock().expect("session journal poisoned");
+        change(&mut state.session);
+        self.save(&mut state);
+    }
+
+    fn save(&self, state: &mut JournalState) {
+        // Without the file only a later restore is lost, so the work goes on.
+        let written = self
+            .location
+            .parent()
+            .map_or(Ok(()), |parent| {
+                fs::create_dir_all(parent).map_err(|error| error.to_string())
+            })
+            .and_then(|_| serde_json::to_vec(&state.session).map_err(|error| error.to_string()))
+            .and_then(|data| crate::write_file_atomically(&self.location, &data));
+        if let Err(error) = written {
+            tracing::warn!(%error, "could not save the session");
+        }
+        state.unsaved = 0;
+        state.saved_at = Instant::now();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn an_interrupted_session_is_restored_once() {
+        let location = crate::fixtures::temp_path("session").join("session.json");
+        let journal = SessionJournal::open(location.clone());
+        journal.save_windows(vec![WindowSession {
+            label: "main".to_string(),
+            open_file: Some("/photos/a.jpg".to_string()),
+            scan_folder: Some("/photos".to_string()),
+        }]);
+        let paths = ["/photos/a.jpg".to_string(), "/photos/b.jpg".to_string()];
+        let finished = journal.start_job("Publish", &paths, 2);
+        journal.finish_job(finished);
+        let interrupted = journal.start_job("Publish", &paths, 2);
+        journal.job_progress(interrupted, 1, Some("/photos/a.jpg"));
+        journal.job_progress(interrupted, 1, Some("/photos/b.jpg"));
+        journal.job_progress(interrupted, 2, Some("/photos/a.jpg"));
+        drop(journal);
+
+        let reopened = SessionJournal::open(location.clone());
+        let restored = reopened.restore().unwrap();
+        assert_eq!(
+            restored.windows[0].open_file.as_deref(),
+            Some("/photos/a.jpg")
+        );
+        assert_eq!(restored.jobs.len(), 1);
+        let job = &restored.jobs[0];
+        assert_eq!((job.id, job.step), (interrupted, 2));
+        assert_eq!(job.processed, ["/photos/a.jpg"]);
+        assert!(reopened.restore().is_none());
+        assert!(reopened.start_job("Publish", &paths, 1) > interrupted);
+        drop(reopened);
+
+        // Once handed out, the old job is not offered again.
+        let restored = SessionJournal::open(location.clone()).restore().unwrap();
+        assert_eq!(restored.jobs.len(), 1);
+        assert_ne!(restored.jobs[0].id, interrupted);
+        fs::remove_dir_all(location.parent().unwrap()).ok();
+    }
+
+    #[test]
+    fn progress_within_a_step_is_saved_in_batches() {
+        let location = crate::fixtures::temp_path("session_batches").join("session.json");
+        let saved_processed = || {
+            let saved: SavedSession =
+                serde_json::from_slice(&fs::read(&location).unwrap()).unwrap();
+            saved.jobs[0].processed.len()
+        };
+        let journal = SessionJournal::open(location.clone());
+        let paths: Vec<String> = (0..SAVE_EVERY)
+            .map(|n| format!("/photos/{}.jpg", n))
+            .collect();
+        let job = journal.start_job("Publish", &paths, 1);
+        for path in &paths[..SAVE_EVERY - 1] {
+            journal.job_progress(job, 1, Some(path));
+        }
+        let before_batch = saved_processed();
+        journal.job_progress(job, 1, Some(&paths[SAVE_EVERY - 1]));
+        let after_batch = saved_processed();
+        fs::remove_dir_all(location.parent().unwrap()).ok();
+
+        assert_eq!(before_batch, 0);
+        assert_eq!(after_batch, SAVE_EVERY);
+    }
+}
@@ -0,0 +1,133 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+/// Label of the window Tauri opens from `tauri.conf.json`.
+pub const MAIN_WINDOW: &str = "main";
+/// Windows opened with `open_window` are labelled `viewer-1`, `viewer-2`, ...
+const VIEWER_PREFIX: &str = "viewer-";
+
+/// What one app window is showing.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WindowSession {
+    pub label: String,
+    /// The file open in the window, if any.
+    pub open_file: Option<String>,
+    /// The folder of the window's latest paged scan.
+    pub scan_folder: Option<String>,
+}
+
+/// Per-window state, keyed by window label. A window's entry is dropped
+/// when the window is destroyed.
+pub struct WindowSessions {
+    sessions: Mutex<HashMap<String, WindowSession>>,
+    next_viewer: AtomicU64,
+}
+
+impl Default for WindowSessions {
+    fn default() -> Self {
+        WindowSessions {
+            sessions: Mutex::new(HashMap::new()),
+            next_viewer: AtomicU64::new(1),
+        }
+    }
+}
+
+impl WindowSessions {
+    /// A label no window has used yet.
+    pub fn next_label(&self) -> String {
+        format!(
+            "{}{}",
+            VIEWER_PREFIX,
+            self.next_viewer.fetch_add(1, Ordering::SeqCst)
+        )
+    }
+
+    /// The session of `label`; empty for a window that has not set anything.
+    pub fn get(&self, label: &str) -> WindowSession {
+        self.sessions
+            .lock()
+            .expect("window sessions poisoned")
+            .get(label)
+            .cloned()
+            .unwrap_or_else(|| WindowSession {
+                label: label.to_string(),
+                ..WindowSession::default()
+            })
+    }
+
+    /// Every window's session, ordered by label.
+    pub fn all(&self) -> Vec<WindowSession> {
+        let sessions = self.sessions.lock().expect("window sessions poisoned");
+        let mut all: Vec<WindowSession> = sessions.values().cloned().collect();
+        all.sort_by(|a, b| a.label.cmp(&b.label));
+        all
+    }
+
+    fn update(&self, label: &str, change: impl FnOnce(&mut WindowSession)) {
+        let mut sessions = self.sessions.lock().expect("window sessions poisoned");
+        let session = sessions
+            .entry(label.to_string())
+            .or_insert_with(|| WindowSession {
+                label: label.to_string(),
+                ..WindowSession::default()
+            });
+        change(session);
+    }
+
+    pub fn set_open_file(&self, label: &str, path: Option<String>) {
+        self.update(label, |session| session.open_file = path);
+    }
+
+    pub fn set_scan_folder(&self, label: &str, folder: &str) {
+        self.update(label, |session| {
+            session.scan_folder = Some(folder.to_string())
+        });
+    }
+
+    /// Forgets a closed window and returns what it was showing.
+    pub fn close(&self, label: &str) -> Option<WindowSession> {
+        self.sessions
+            .lock()
+            .expect("window sessions poisoned")
+            .remove(label)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn windows_keep_separate_sessions_until_closed() {
+        let sessions = WindowSessions::default();
+        let viewer = sessions.next_label();
+        assert_eq!(viewer, "viewer-1");
+        assert_ne!(sessions.next_label(), viewer);
+
+        sessions.set_open_file(MAIN_WINDOW, Some("/photos/a.jpg".to_string()));
+        sessions.set_open_file(&viewer, Some("/photos/b.jpg".to_string()));
+        sessions.set_scan_folder(&viewer, "/photos");
+        assert_eq!(
+            sessions.get(MAIN_WINDOW).open_file.as_deref(),
+            Some("/photos/a.jpg")
+        );
+        assert_eq!(sessions.get(MAIN_WINDOW).scan_folder, None);
+
+        let closed = sessions.close(&viewer).unwrap();
+        assert_eq!(closed.open_file.as_deref(), Some("/photos/b.jpg"));
+        assert_eq!(
+            sessions.get(&viewer),
+            WindowSession {
+                label: viewer.clone(),
+                ..WindowSession::default()
+            }
+        );
+    }
+}
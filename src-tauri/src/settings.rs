@@ -0,0 +1,104 @@
+use crate::{isolation::ParseIsolation, mwg::SourcePriority, placeholder::CloudPlaceholders};
+use serde::{Deserialize, Serialize};
+use std::{fs, path::PathBuf, sync::Mutex};
+
+/// Where metadata edits are written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum EditMode {
+    /// Edits are written into the image files.
+    #[default]
+    InPlace,
+    /// Originals are never opened for writing; edits are kept in the app's
+    /// store so archive fixity checks keep passing.
+    ExternalOnly,
+}
+
+/// How the metadata index is protected at rest.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum IndexEncryption {
+    #[default]
+    Off,
+    /// Locked at each start until the passphrase is entered.
+    Passphrase,
+    /// A random key kept in the OS keychain, unlocked at start.
+    Keychain,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AppSettings {
+    pub edit_mode: EditMode,
+    /// How EXIF, IPTC and XMP copies of the same logical field are reconciled.
+    pub source_priority: SourcePriority,
+    /// Cap on file data held in memory by scans, in MB; `None` uses the default.
+    pub scan_memory_limit_mb: Option<u64>,
+    /// How parsing is protected from files that crash the parser.
+    pub parse_isolation: ParseIsolation,
+    /// Whether scans skip OneDrive, iCloud and Dropbox files that are not
+    /// downloaded, or download them to read them.
+    pub cloud_placeholders: CloudPlaceholders,
+    /// Names the city and country of GPS positions from a bundled table.
+    pub reverse_geocoding: bool,
+    /// Whether formats seen and parse failures are counted locally.
+    pub usage_stats: bool,
+    /// Folders commands are limited to; empty allows any path.
+    pub allowed_roots: Vec<String>,
+    /// Refuses every command that would change files or the app's stores.
+    pub read_only: bool,
+    /// Keyword thesaurus that keywords are checked against, if any.
+    pub keyword_vocabulary: Option<String>,
+    /// Local captioning program behind `{scene}` in caption templates, run
+    /// as `<program> <image>`; only builds with `ml-captions` use it.
+    pub captioner: Option<String>,
+    /// Only builds with `index-encryption` can turn this on.
+    pub index_encryption: IndexEncryption,
+}
+
+/// User preferences persisted as JSON in the app data directory.
+pub struct SettingsStore {
+    location: PathBuf,
+    settings: Mutex<AppSettings>,
+}
+
+impl SettingsStore {
+    pub fn open(location: PathBuf) -> Self {
+        let settings = fs::read(&location)
+            .ok()
+            .and_then(|data| serde_json::from_slice(&data).ok())
+            .unwrap_or_default();
+        SettingsStore {
+            location,
+            settings: Mutex::new(settings),
+        }
+    }
+
+    pub fn get(&self) -> AppSettings {
+        self.settings.lock().expect("settings poisoned").clone()
+    }
+
+    /// Fails while read-only mode is on; called first by every mutating command.
+    pub fn ensure_writable(&self) -> Result<(), String> {
+        if self.get().read_only {
+            return Err(
+                "The app is in read-only mode; turn it off in the settings to make changes."
+                    .to_string(),
+            );
+        }
+        Ok(())
+    }
+
+    pub fn update(&self, change: impl FnOnce(&mut AppSettings)) -> Result<AppSettings, String> {
+        let mut settings = self.settings.lock().expect("settings poisoned");
+        let mut updated = settings.clone();
+        change(&mut updated);
+        if let Some(parent) = self.location.parent() {
+            fs::create_dir_all(parent).map_err(|error| error.to_string())?;
+        }
+        let data = serde_json::to_vec_pretty(&updated).map_err(|error| error.to_string())?;
+        crate::write_file_atomically(&self.location, &data)?;
+        *settings = updated.clone();
+        Ok(updated)
+    }
+}
@@ -0,0 +1,347 @@
+use crate::{
+    remote::{RemoteObject, StorageBackend},
+    retry::ReadError,
+};
+use std::sync::Mutex;
+
+/// Where to sign in. Without a key file or password the SSH agent is used.
+#[derive(Debug, Clone)]
+#[cfg_attr(not(feature = "sftp"), allow(dead_code))]
+pub struct SftpConfig {
+    pub host: String,
+    pub port: Option<u16>,
+    pub username: String,
+    pub password: Option<String>,
+    /// An OpenSSH private key; `password` then unlocks it, if it is encrypted.
+    pub private_key: Option<String>,
+    /// The folder on the server that keys are relative to.
+    pub root: String,
+}
+
+/// One entry of a remote folder.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirEntry {
+    pub name: String,
+    pub is_dir: bool,
+    pub size: u64,
+}
+
+/// Every file below `folder` (a key, empty for the root), listing one folder
+/// at a time with `read_dir`, which takes a key too.
+fn walk(
+    folder: &str,
+    read_dir: &mut dyn FnMut(&str) -> Result<Vec<DirEntry>, ReadError>,
+) -> Result<Vec<RemoteObject>, ReadError> {
+    let mut objects = Vec::new();
+    let mut folders = vec![folder.trim_matches('/').to_string()];
+    while let Some(folder) = folders.pop() {
+        for entry in read_dir(&folder)? {
+            if entry.name == "." || entry.name == ".." {
+                continue;
+            }
+            let key = match folder.is_empty() {
+                true => entry.name,
+                false => format!("{}/{}", folder, entry.name),
+            };
+            if entry.is_dir {
+                folders.push(key);
+            } else {
+                objects.push(RemoteObject {
+                    key,
+                    size: entry.size,
+                });
+            }
+        }
+    }
+    objects.sort_by(|a, b| a.key.cmp(&b.key));
+    Ok(objects)
+}
+
+/// The path on the server of `key`.
+fn server_path(root: &str, key: &str) -> String {
+    let root = root.trim_end_matches('/');
+    match key.trim_matches('/') {
+        "" if root.is_empty() => "/".to_string(),
+        "" => root.to_string(),
+        key => format!("{}/{}", root, key),
+    }
+}
+
+#[cfg(feature = "sftp")]
+mod session {
+    use super::{DirEntry, SftpConfig};
+    use crate::retry::{self, ReadError};
+    use ssh2::{CheckResult, KnownHostFileKind, Session, Sftp};
+    use std::{
+        io::{Read, Seek, SeekFrom},
+        net::TcpStream,
+        path::{Path, PathBuf},
+    };
+
+    const DEFAULT_PORT: u16 = 22;
+    /// Gives up on a server that stops answering rather than stalling a scan.
+    const TIMEOUT_MS: u32 = 30_000;
+
+    pub struct Connection {
+        // Kept alive for as long as the SFTP channel is in use.
+        _session: Session,
+        sftp: Sftp,
+    }
+
+    fn known_hosts_file() -> Option<PathBuf> {
+        let home = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"))?;
+        Some(PathBuf::from(home).join(".ssh").join("known_hosts"))
+    }
+
+    /// Refuses servers whose key is not in the user's `known_hosts`, as
+    /// `ssh` itself would.
+    fn check_host_key(session: &Session, host: &str, port: u16) -> Result<(), String> {
+        let (key, _) = session
+            .host_key()
+            .ok_or_else(|| "The server sent no host key.".to_string())?;
+        let mut known_hosts = session.known_hosts().map_err(|error| error.to_string())?;
+        if let Some(file) = known_hosts_file().filter(|file| file.is_file()) {
+            known_hosts
+                .read_file(&file, KnownHostFileKind::OpenSSH)
+                .map_err(|error| format!("Unable to read known_hosts: {}", error))?;
+        }
+        match known_hosts.check_port(host, port, key) {
+            CheckResult::Match => Ok(()),
+            CheckResult::Mismatch => Err(format!(
+                "The host key of {} has changed since it was last trusted; the connection was refused.",
+                host
+            )),
+            _ => Err(format!(
+                "{} is not a known host; connect once with `ssh` to trust its key.",
+                host
+            )),
+        }
+    }
+
+    /// A server that cannot be reached may be back later; one that refuses
+    /// its key or the sign-in will refuse them again.
+    pub fn connect(config: &SftpConfig) -> Result<Connection, ReadError> {
+        let port = config.port.unwrap_or(DEFAULT_PORT);
+        let unreachable = |error: &dyn std::fmt::Display| {
+            ReadError::Unavailable(format!("Unable to reach {}: {}", config.host, error))
+        };
+        let stream = TcpStream::connect((config.host.as_str(), port))
+            .map_err(|error| unreachable(&error))?;
+        let mut session = Session::new().map_err(|error| unreachable(&error))?;
+        session.set_timeout(TIMEOUT_MS);
+        session.set_tcp_stream(stream);
+        session.handshake().map_err(|error| unreachable(&error))?;
+        check_host_key(&session, &config.host, port)?;
+
+        let username = config.username.as_str();
+        let signed_in = match (&config.private_key, &config.password) {
+            (Some(key), passphrase) => {
+                session.userauth_pubkey_file(username, None, Path::new(key), passphrase.as_deref())
+            }
+            (None, Some(password)) => session.userauth_password(username, password),
+            (None, None) => session.userauth_agent(username),
+        };
+        if signed_in.is_err() || !session.authenticated() {
+            return Err(ReadError::Failed(format!(
+                "{} refused the sign-in; check the user name and key or password.",
+                config.host
+            )));
+        }
+        let sftp = session
+            .sftp()
+            .map_err(|error| format!("{} does not offer SFTP: {}", config.host, error))?;
+        Ok(Connection {
+            _session: session,
+            sftp,
+        })
+    }
+
+    impl Connection {
+        pub fn read_dir(&self, path: &str) -> Result<Vec<DirEntry>, ReadError> {
+            let entries = self
+                .sftp
+                .readdir(Path::new(path))
+                .map_err(|error| format!("Unable to list {}: {}", path, error))?;
+            Ok(entries
+                .into_iter()
+                .map(|(path, stat)| DirEntry {
+                    name: path
+                        .file_name()
+                        .map(|name| name.to_string_lossy().into_owned())
+                        .unwrap_or_default(),
+                    is_dir: stat.is_dir(),
+                    size: stat.size.unwrap_or_default(),
+                })
+                .collect())
+        }
+
+        /// Up to `length` bytes from `offset`, so only headers cross the network.
+        pub fn read_range(
+            &self,
+            path: &str,
+            offset: u64,
+            length: u64,
+        ) -> Result<Vec<u8>, ReadError> {
+            let failed =
+                |error: &dyn std::fmt::Display| format!("Unable to read {}: {}", path, error);
+            // A dropped connection mid-read may pass; a missing file will not.
+            let interrupted = |error: std::io::Error| match retry::is_transient_network(&error) {
+                true => ReadError::Unavailable(failed(&error)),
+                false => ReadError::Failed(failed(&error)),
+            };
+            let mut file = self
+                .sftp
+                .open(Path::new(path))
+                .map_err(|error| failed(&error))?;
+            file.seek(SeekFrom::Start(offset)).map_err(interrupted)?;
+            let mut data = Vec::new();
+            file.take(length)
+                .read_to_end(&mut data)
+                .map_err(interrupted)?;
+            Ok(data)
+        }
+    }
+}
+
+#[cfg(not(feature = "sftp"))]
+mod session {
+    use super::{DirEntry, SftpConfig};
+    use crate::retry::ReadError;
+
+    pub enum Connection {}
+
+    pub fn connect(_config: &SftpConfig) -> Result<Connection, ReadError> {
+        Err(ReadError::Failed(
+            "This build was made without SFTP support (the `sftp` feature).".to_string(),
+        ))
+    }
+
+    impl Connection {
+        pub fn read_dir(&self, _path: &str) -> Result<Vec<DirEntry>, ReadError> {
+            match *self {}
+        }
+
+        pub fn read_range(
+            &self,
+            _path: &str,
+            _offset: u64,
+            _length: u64,
+        ) -> Result<Vec<u8>, ReadError> {
+            match *self {}
+        }
+    }
+}
+
+/// Connects on first use and keeps the connection for the rest of the scan.
+pub struct SftpBackend {
+    config: SftpConfig,
+    connection: Mutex<Option<session::Connection>>,
+}
+
+impl SftpBackend {
+    pub fn new(config: SftpConfig) -> Self {
+        SftpBackend {
+            config,
+            connection: Mutex::new(None),
+        }
+    }
+
+    fn with_connection<T>(
+        &self,
+        action: impl FnOnce(&session::Connection) -> Result<T, ReadError>,
+    ) -> Result<T, ReadError> {
+        let mut connection = self.connection.lock().expect("SFTP connection poisoned");
+        if connection.is_none() {
+            *connection = Some(session::connect(&self.config)?);
+        }
+        let result = action(connection.as_ref().expect("connected above"));
+        if result.is_err() {
+            // A dropped connection is opened afresh on the next request.
+            *connection = None;
+        }
+        result
+    }
+}
+
+impl StorageBackend for SftpBackend {
+    fn list(&self, prefix: &str) -> Result<Vec<RemoteObject>, ReadError> {
+        self.with_connection(|connection| {
+            // The prefix may name a file or a partial name; list its folder.
+            let folder = match prefix.rsplit_once('/') {
+                Some((folder, _)) if !prefix.ends_with('/') => folder,
+                _ if !prefix.ends_with('/') => "",
+                _ => prefix,
+            };
+            let objects = walk(folder, &mut |key| {
+                connection.read_dir(&server_path(&self.config.root, key))
+            })?;
+            Ok(objects
+                .into_iter()
+                .filter(|object| object.key.starts_with(prefix))
+                .collect())
+        })
+    }
+
+    fn read_range(&self, key: &str, offset: u64, length: u64) -> Result<Vec<u8>, ReadError> {
+        self.with_connection(|connection| {
+            connection.read_range(&server_path(&self.config.root, key), offset, length)
+        })
+    }
+
+    fn read(&self, key: &str) -> Result<Vec<u8>, ReadError> {
+        self.read_range(key, 0, u64::MAX)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn folders_are_walked_into_keys_below_the_root() {
+        let entry = |name: &str, is_dir, size| DirEntry {
+            name: name.to_string(),
+            is_dir,
+            size,
+        };
+        let tree = BTreeMap::from([
+            (
+                "",
+                vec![
+                    entry(".", true, 0),
+                    entry("2024", true, 0),
+                    entry("a.jpg", false, 10),
+                ],
+            ),
+            (
+                "2024",
+                vec![entry("..", true, 0), entry("b.jpg", false, 20)],
+            ),
+        ]);
+        let mut listed = Vec::new();
+        let objects = walk("", &mut |key| {
+            listed.push(server_path("/volume1/photos/", key));
+            tree.get(key)
+                .cloned()
+                .ok_or_else(|| ReadError::Failed("missing".to_string()))
+        })
+        .unwrap();
+
+        assert_eq!(
+            objects,
+            [
+                RemoteObject {
+                    key: "2024/b.jpg".to_string(),
+                    size: 20
+                },
+                RemoteObject {
+                    key: "a.jpg".to_string(),
+                    size: 10
+                },
+            ]
+        );
+        assert_eq!(listed, ["/volume1/photos", "/volume1/photos/2024"]);
+        assert_eq!(server_path("", ""), "/");
+    }
+}
@@ -0,0 +1,338 @@
+use serde::Serialize;
+use std::{
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+pub const MENU_LABEL: &str = "Inspect metadata with EXIF Viewer";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContextMenuStatus {
+    pub supported: bool,
+    pub registered: bool,
+    /// Where the entry lives: registry keys, service bundles or menu files.
+    pub locations: Vec<String>,
+}
+
+/// Paths the app was launched with, held until the frontend asks for them.
+#[derive(Default)]
+pub struct LaunchPaths(Mutex<Vec<PathBuf>>);
+
+impl LaunchPaths {
+    pub fn new(paths: Vec<PathBuf>) -> Self {
+        LaunchPaths(Mutex::new(paths))
+    }
+
+    /// Drains the pending paths so each launch is handled once.
+    pub fn take(&self) -> Vec<PathBuf> {
+        std::mem::take(&mut *self.0.lock().expect("launch paths poisoned"))
+    }
+
+    /// Queues paths forwarded by a later launch of the app.
+    pub fn push(&self, paths: Vec<PathBuf>) {
+        self.0.lock().expect("launch paths poisoned").extend(paths);
+    }
+}
+
+/// Picks the file and folder arguments out of a command line, skipping the
+/// executable itself and any flags added by the OS or the dev tooling.
+///
+/// Relative arguments are resolved against `cwd`, which for a forwarded launch
+/// is the second process's working directory rather than ours.
+pub fn paths_from_args(args: impl IntoIterator<Item = String>, cwd: &Path) -> Vec<PathBuf> {
+    args.into_iter()
+        .skip(1)
+        .filter(|arg| !arg.starts_with('-'))
+        .map(|arg| cwd.join(arg))
+        .filter(|path| path.exists())
+        .collect()
+}
+
+pub fn status() -> ContextMenuStatus {
+    let locations = platform::locations();
+    ContextMenuStatus {
+        supported: platform::SUPPORTED,
+        registered: !locations.is_empty()
+            && locations.iter().all(|location| platform::exists(location)),
+        locations: locations
+            .iter()
+            .map(|location| location.to_string_lossy().into_owned())
+            .collect(),
+    }
+}
+
+pub fn register(executable: &Path) -> Result<ContextMenuStatus, String> {
+    if !platform::SUPPORTED {
+        return Err("Context menu registration is not supported on this platform.".to_string());
+    }
+    platform::register(executable)?;
+    Ok(status())
+}
+
+pub fn unregister() -> Result<ContextMenuStatus, String> {
+    if !platform::SUPPORTED {
+        return Err("Context menu registration is not supported on this platform.".to_string());
+    }
+    platform::unregister()?;
+    Ok(status())
+}
+
+/// Registry verbs under HKCU, so no elevation is needed.
+#[cfg(target_os = "windows")]
+mod platform {
+    use std::{
+        path::{Path, PathBuf},
+        process::Command,
+    };
+
+    pub const SUPPORTED: bool = true;
+    const KEYS: [&str; 2] = [
+        r"HKCU\Software\Classes\*\shell\ExifViewer",
+        r"HKCU\Software\Classes\Directory\shell\ExifViewer",
+    ];
+
+    pub fn locations() -> Vec<PathBuf> {
+        KEYS.iter().map(PathBuf::from).collect()
+    }
+
+    pub fn exists(location: &Path) -> bool {
+        Command::new("reg")
+            .arg("query")
+            .arg(location)
+            .output()
+            .is_ok_and(|output| output.status.success())
+    }
+
+    fn reg(args: &[&str]) -> Result<(), String> {
+        let output = Command::new("reg")
+            .args(args)
+            .output()
+            .map_err(|error| format!("Unable to run reg.exe: {}", error))?;
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+        }
+    }
+
+    pub fn register(executable: &Path) -> Result<(), String> {
+        let executable = executable.to_string_lossy();
+        let command = format!("\"{}\" \"%1\"", executable);
+        for key in KEYS {
+            reg(&["add", key, "/ve", "/d", super::MENU_LABEL, "/f"])?;
+            reg(&["add", key, "/v", "Icon", "/d", &executable, "/f"])?;
+            reg(&[
+                "add",
+                &format!(r"{}\command", key),
+                "/ve",
+                "/d",
+                &command,
+                "/f",
+            ])?;
+        }
+        Ok(())
+    }
+
+    pub fn unregister() -> Result<(), String> {
+        for key in KEYS {
+            if exists(Path::new(key)) {
+                reg(&["delete", key, "/f"])?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A Finder Quick Action installed in the user's Services folder.
+#[cfg(target_os = "macos")]
+mod platform {
+    use std::{
+        fs,
+        path::{Path, PathBuf},
+    };
+
+    pub const SUPPORTED: bool = true;
+
+    fn workflow() -> Option<PathBuf> {
+        home_dir().map(|home| {
+            home.join("Library/Services")
+                .join(format!("{}.workflow", super::MENU_LABEL))
+        })
+    }
+
+    fn home_dir() -> Option<PathBuf> {
+        std::env::var_os("HOME").map(PathBuf::from)
+    }
+
+    pub fn locations() -> Vec<PathBuf> {
+        workflow().into_iter().collect()
+    }
+
+    pub fn exists(location: &Path) -> bool {
+        location.join("Contents/document.wflow").is_file()
+    }
+
+    pub fn register(executable: &Path) -> Result<(), String> {
+        let workflow = workflow().ok_or_else(|| "The home folder is unknown.".to_string())?;
+        let contents = workflow.join("Contents");
+        fs::create_dir_all(&contents).map_err(|error| error.to_string())?;
+        let script = crate::track::escape_xml(&format!(
+            "for f in \"$@\"; do \"{}\" \"$f\" & done",
+            executable.to_string_lossy()
+        ));
+        let info = format!(
+            include_str!("../data/quick-action-info.plist"),
+            label = super::MENU_LABEL
+        );
+        let document = format!(include_str!("../data/quick-action.wflow"), script = script);
+        fs::write(contents.join("Info.plist"), info).map_err(|error| error.to_string())?;
+        fs::write(contents.join("document.wflow"), document).map_err(|error| error.to_string())
+    }
+
+    pub fn unregister() -> Result<(), String> {
+        match workflow() {
+            Some(workflow) if workflow.exists() => {
+                fs::remove_dir_all(workflow).map_err(|error| error.to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Nautilus scripts and a KDE service menu, the two cover most desktops.
+#[cfg(all(unix, not(target_os = "macos")))]
+mod platform {
+    use std::{
+        fs,
+        path::{Path, PathBuf},
+    };
+
+    pub const SUPPORTED: bool = true;
+
+    fn data_home() -> Option<PathBuf> {
+        std::env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| {
+                std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share"))
+            })
+    }
+
+    pub fn locations() -> Vec<PathBuf> {
+        data_home()
+            .map(|data| {
+                vec![
+                    data.join("nautilus/scripts").join(super::MENU_LABEL),
+                    data.join("kio/servicemenus/exif-viewer.desktop"),
+                ]
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn exists(location: &Path) -> bool {
+        location.is_file()
+    }
+
+    fn write_executable(path: &Path, contents: &str) -> Result<(), String> {
+        use std::os::unix::fs::PermissionsExt;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|error| error.to_string())?;
+        }
+        fs::write(path, contents).map_err(|error| error.to_string())?;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o755))
+            .map_err(|error| error.to_string())
+    }
+
+    pub fn register(executable: &Path) -> Result<(), String> {
+        let [script, service_menu] = <[PathBuf; 2]>::try_from(locations())
+            .map_err(|_| "The user data folder is unknown.".to_string())?;
+        let executable = executable.to_string_lossy();
+        let quoted = format!("'{}'", executable.replace('\'', r"'\''"));
+
+        write_executable(
+            &script,
+            &format!(
+                "#!/bin/sh\n# Installed by EXIF Viewer.\nexec {} \"$@\"\n",
+                quoted
+            ),
+        )?;
+        write_executable(
+            &service_menu,
+            &format!(
+                "[Desktop Entry]\nType=Service\nMimeType=image/*;inode/directory;\nActions=inspect\nX-KDE-ServiceTypes=KonqPopupMenu/Plugin\n\n[Desktop Action inspect]\nName={}\nIcon=image-x-generic\nExec={} %F\n",
+                super::MENU_LABEL,
+                quoted
+            ),
+        )
+    }
+
+    pub fn unregister() -> Result<(), String> {
+        for location in locations() {
+            if location.exists() {
+                fs::remove_file(&location).map_err(|error| error.to_string())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(any(unix, target_os = "windows")))]
+mod platform {
+    use std::path::{Path, PathBuf};
+
+    pub const SUPPORTED: bool = false;
+
+    pub fn locations() -> Vec<PathBuf> {
+        Vec::new()
+    }
+
+    pub fn exists(_location: &Path) -> bool {
+        false
+    }
+
+    pub fn register(_executable: &Path) -> Result<(), String> {
+        Ok(())
+    }
+
+    pub fn unregister() -> Result<(), String> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_existing_non_flag_arguments_are_launch_paths() {
+        let readme = env!("CARGO_MANIFEST_DIR").to_string() + "/../README.md";
+        let paths = paths_from_args(
+            [
+                "/Applications/EXIF Viewer".to_string(),
+                "--no-sandbox".to_string(),
+                readme.clone(),
+                "/definitely/not/here.jpg".to_string(),
+            ],
+            Path::new("/"),
+        );
+        assert_eq!(paths, vec![PathBuf::from(readme)]);
+    }
+
+    #[test]
+    fn forwarded_relative_arguments_resolve_against_the_callers_directory() {
+        let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let paths = paths_from_args(
+            ["exif-viewer".to_string(), "../README.md".to_string()],
+            manifest_dir,
+        );
+        assert_eq!(paths, vec![manifest_dir.join("../README.md")]);
+    }
+
+    #[test]
+    fn launch_paths_are_handed_out_once() {
+        let pending = LaunchPaths::new(vec![PathBuf::from("a.jpg")]);
+        pending.push(vec![PathBuf::from("b.jpg")]);
+        assert_eq!(pending.take().len(), 2);
+        assert!(pending.take().is_empty());
+    }
+}
@@ -0,0 +1,421 @@
+use crate::{
+    oplog::{FieldChange, OperationLog, OperationRecord},
+    tag_edit,
+    write_lock::{write_checked, FileStamp, WriteError},
+    xmp, ExifField,
+};
+use chrono::DateTime;
+use exif::{Exif, In, Value};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+pub const COMPUTED_SIDECAR_IFD: &str = "Sidecar";
+/// darktable stores `change_timestamp` as microseconds since 0001-01-01 (GLib's
+/// `GDateTime` epoch); this many seconds separate it from the Unix epoch.
+const GLIB_EPOCH_OFFSET_SECS: i64 = 62_135_596_800;
+
+/// Edit state a RAW developer left next to an image.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SidecarSummary {
+    pub path: PathBuf,
+    pub developed_with: String,
+    pub edit_count: Option<usize>,
+    /// Seconds since the Unix epoch.
+    pub last_edit: Option<i64>,
+}
+
+/// Sidecars for `image`: darktable writes `IMG_0001.CR2.xmp` and RawTherapee
+/// `IMG_0001.CR2.pp3`; `IMG_0001.xmp` is checked too for tools configured to
+/// drop the extension.
+pub fn find_sidecars(image: &Path) -> Vec<SidecarSummary> {
+    let Some(name) = image.file_name() else {
+        return Vec::new();
+    };
+    let with_suffix = |suffix: &str| {
+        let mut sidecar = name.to_os_string();
+        sidecar.push(suffix);
+        image.with_file_name(sidecar)
+    };
+
+    let mut xmp_candidates = vec![with_suffix(".xmp")];
+    if image.extension().is_some() {
+        xmp_candidates.push(image.with_extension("xmp"));
+    }
+    let darktable = xmp_candidates.into_iter().find_map(|path| {
+        let text = fs::read_to_string(&path).ok()?;
+        let summary = parse_darktable(&text, modified_secs(&path))?;
+        Some(SidecarSummary { path, ..summary })
+    });
+
+    let pp3 = with_suffix(".pp3");
+    let rawtherapee = fs::read_to_string(&pp3).ok().map(|text| SidecarSummary {
+        path: pp3.clone(),
+        ..parse_pp3(&text, modified_secs(&pp3))
+    });
+
+    darktable.into_iter().chain(rawtherapee).collect()
+}
+
+/// Reads darktable's history stack summary; `None` for XMP written by other tools.
+fn parse_darktable(text: &str, modified: Option<i64>) -> Option<SidecarSummary> {
+    let packet = xmp::packet(text.as_bytes()).unwrap_or(text);
+    if !packet.contains("darktable:") {
+        return None;
+    }
+    let developed_with = xmp::property(packet, "xmp:CreatorTool")
+        .filter(|tool| tool.to_ascii_lowercase().contains("darktable"))
+        .unwrap_or("darktable")
+        .to_string();
+    // `history_end` is where the user left the stack; entries above it are undone.
+    let edit_count = xmp::property(packet, "darktable:history_end")
+        .and_then(|end| end.parse().ok())
+        .or_else(|| Some(packet.matches("darktable:operation=").count()));
+    let last_edit = xmp::property(packet, "darktable:change_timestamp")
+        .and_then(|stamp| stamp.parse::<i64>().ok())
+        .filter(|&stamp| stamp > 0)
+        .map(|stamp| stamp / 1_000_000 - GLIB_EPOCH_OFFSET_SECS)
+        .or(modified);
+    Some(SidecarSummary {
+        path: PathBuf::new(),
+        developed_with,
+        edit_count,
+        last_edit,
+    })
+}
+
+/// RawTherapee profiles are INI files; every tool section switched on with
+/// `Enabled=true` counts as an edit. They carry no timestamp of their own.
+fn parse_pp3(text: &str, modified: Option<i64>) -> SidecarSummary {
+    let mut section = "";
+    let mut version = None;
+    let mut enabled = 0;
+    for line in text.lines().map(str::trim) {
+        if let Some(name) = line
+            .strip_prefix('[')
+            .and_then(|rest| rest.strip_suffix(']'))
+        {
+            section = name;
+        } else if let Some((key, value)) = line.split_once('=') {
+            match (section, key.trim()) {
+                ("Version", "AppVersion") => version = Some(value.trim().to_string()),
+                (_, "Enabled") if value.trim().eq_ignore_ascii_case("true") => enabled += 1,
+                _ => {}
+            }
+        }
+    }
+    SidecarSummary {
+        path: PathBuf::new(),
+        developed_with: match version {
+            Some(version) => format!("RawTherapee {}", version),
+            None => "RawTherapee".to_string(),
+        },
+        edit_count: Some(enabled),
+        last_edit: modified,
+    }
+}
+
+fn modified_secs(path: &Path) -> Option<i64> {
+    let modified = fs::metadata(path).ok()?.modified().ok()?;
+    let secs = modified.duration_since(UNIX_EPOCH).ok()?.as_secs();
+    i64::try_from(secs).ok()
+}
+
+/// "Developed with", "Edit count", "Last edit" and the sidecar path for each
+/// sidecar found next to `image`.
+pub fn computed_fields(image: &Path) -> Vec<ExifField> {
+    let mut fields = Vec::new();
+    for sidecar in find_sidecars(image) {
+        let mut push = |tag: &str, value: String| {
+            fields.push(ExifField {
+                tag: tag.to_string(),
+                ifd: COMPUTED_SIDECAR_IFD.to_string(),
+                value,
+            });
+        };
+        push("Developed with", sidecar.developed_with);
+        if let Some(count) = sidecar.edit_count {
+            push("Edit count", count.to_string());
+        }
+        if let Some(time) = sidecar
+            .last_edit
+            .and_then(|secs| DateTime::from_timestamp(secs, 0))
+        {
+            push(
+                "Last edit",
+                time.format("%Y-%m-%d %H:%M:%S UTC").to_string(),
+            );
+        }
+        push("Sidecar file", sidecar.path.to_string_lossy().into_owned());
+    }
+    fields
+}
+
+/// XMP properties with an EXIF copy under another name; `tiff:` and `exif:`
+/// properties are otherwise named after their tag.
+const EXIF_EQUIVALENTS: [(&str, &str); 5] = [
+    ("xmp:CreateDate", "DateTimeDigitized"),
+    ("xmp:ModifyDate", "DateTime"),
+    ("dc:description", "ImageDescription"),
+    ("dc:creator", "Artist"),
+    ("dc:rights", "Copyright"),
+];
+
+/// Whether the EXIF `field` holds what the XMP text `value` says, allowing
+/// for XMP's ISO dates, decimal or `n/d` rationals and plain numbers.
+fn same_value(field: &exif::Field, value: &str) -> bool {
+    let value = value.trim();
+    let rational = |parts: Option<f64>| {
+        let parsed = match value.split_once('/') {
+            Some((num, denom)) => num
+                .parse::<f64>()
+                .ok()
+                .zip(denom.parse::<f64>().ok())
+                .filter(|(_, denom)| *denom != 0.0)
+                .map(|(num, denom)| num / denom),
+            None => value.parse().ok(),
+        };
+        parsed
+            .zip(parts)
+            .is_some_and(|(parsed, own)| (parsed - own).abs() <= 1e-6 * own.abs().max(1.0))
+    };
+    match &field.value {
+        Value::Ascii(texts) => {
+            let text = texts
+                .first()
+                .map(|text| String::from_utf8_lossy(text).trim().to_string())
+                .unwrap_or_default();
+            // XMP dates may carry a fraction and a zone EXIF keeps elsewhere.
+            text == value
+                || value
+                    .get(..19)
+                    .and_then(|time| tag_edit::exif_date(time).ok())
+                    .is_some_and(|time| time == text)
+        }
+        Value::Rational(parts) => rational(parts.first().map(|part| part.to_f64())),
+        Value::SRational(parts) => rational(parts.first().map(|part| part.to_f64())),
+        _ => match field.value.get_uint(0) {
+            Some(own) => value.parse::<u32>() == Ok(own),
+            None => field.display_value().to_string() == value,
+        },
+    }
+}
+
+/// Whether the file's own metadata has `name` with another value than
+/// `value`: the same property in its XMP, or the matching EXIF tag.
+fn overrides_embedded(
+    name: &str,
+    value: &str,
+    embedded: &HashMap<String, String>,
+    exif: Option<&Exif>,
+) -> bool {
+    if let Some(own) = embedded.get(name) {
+        return own != value;
+    }
+    let tag = EXIF_EQUIVALENTS
+        .iter()
+        .find(|(property, _)| *property == name)
+        .map(|(_, tag)| *tag)
+        .or_else(|| name.strip_prefix("tiff:"))
+        .or_else(|| name.strip_prefix("exif:"))
+        .or_else(|| name.strip_prefix("exifEX:"));
+    tag.zip(exif).is_some_and(|(tag, exif)| {
+        exif.fields().any(|field| {
+            field.ifd_num == In::PRIMARY
+                && field.tag.to_string() == tag
+                && !same_value(field, value)
+        })
+    })
+}
+
+/// The properties of the sidecar packet `sidecar` as fields, followed by an
+/// "Overrides" field naming those that replace a different value in the
+/// file itself, as Lightroom and darktable read the sidecar's copy first.
+fn merged_fields(sidecar: &str, embedded: Option<&str>, exif: Option<&Exif>) -> Vec<ExifField> {
+    let embedded: HashMap<String, String> = embedded
+        .map(xmp::properties)
+        .unwrap_or_default()
+        .into_iter()
+        .collect();
+    let mut fields = Vec::new();
+    let mut overrides = Vec::new();
+    for (name, value) in xmp::properties(sidecar) {
+        if overrides_embedded(&name, &value, &embedded, exif) {
+            overrides.push(name.clone());
+        }
+        fields.push(ExifField {
+            tag: name,
+            ifd: COMPUTED_SIDECAR_IFD.to_string(),
+            value,
+        });
+    }
+    if !overrides.is_empty() {
+        fields.push(ExifField {
+            tag: "Overrides".to_string(),
+            ifd: COMPUTED_SIDECAR_IFD.to_string(),
+            value: overrides.join(", "),
+        });
+    }
+    fields
+}
+
+/// The properties of the XMP sidecar next to `image` (`photo.nef.xmp` or
+/// `photo.xmp`), if it has one, checked against the file's own `data` and
+/// its already parsed `exif`.
+pub fn xmp_fields(image: &Path, data: &[u8], exif: Option<&Exif>) -> Vec<ExifField> {
+    let sidecar = xmp_path(image);
+    if sidecar == image {
+        return Vec::new();
+    }
+    let Ok(text) = fs::read_to_string(&sidecar) else {
+        return Vec::new();
+    };
+    let Some(packet) = xmp::packet(text.as_bytes()) else {
+        return Vec::new();
+    };
+    let embedded = xmp::container_packet(data);
+    merged_fields(packet, embedded.as_deref(), exif)
+}
+
+/// An existing XMP sidecar of `image`, or where a new one goes.
+pub fn xmp_path(image: &Path) -> PathBuf {
+    let mut name = image.file_name().unwrap_or_default().to_os_string();
+    name.push(".xmp");
+    [image.with_file_name(name), image.with_extension("xmp")]
+        .into_iter()
+        .find(|candidate| candidate.is_file())
+        .unwrap_or_else(|| image.with_extension("xmp"))
+}
+
+/// Sets `properties` in the XMP sidecar of `image`, creating it if needed,
+/// and logs the change under `operation`. Returns the sidecar's path.
+pub fn write_xmp(
+    image: &Path,
+    properties: &[(&str, String)],
+    operation: &str,
+    log: &OperationLog,
+) -> Result<PathBuf, WriteError> {
+    let sidecar = xmp_path(image);
+    let existing = if sidecar.is_file() {
+        let stamp = FileStamp::of(&sidecar)?;
+        let text = String::from_utf8(crate::load_file_data(&sidecar)?)
+            .map_err(|_| "The XMP sidecar is not valid UTF-8.".to_string())?;
+        Some((stamp, text))
+    } else {
+        None
+    };
+    let packet = existing.as_ref().map(|(_, text)| text.as_str());
+    let changes = properties
+        .iter()
+        .map(|(name, value)| FieldChange {
+            tag: name.to_string(),
+            before: packet
+                .and_then(|packet| xmp::property(packet, name))
+                .map(str::to_string),
+            after: Some(value.clone()),
+        })
+        .collect();
+    let properties: Vec<(&str, &str)> = properties
+        .iter()
+        .map(|(name, value)| (*name, value.as_str()))
+        .collect();
+    let updated = xmp::with_properties(packet, &properties)?;
+    let stamp = existing
+        .map(|(stamp, _)| stamp)
+        .unwrap_or_else(FileStamp::absent);
+    write_checked(&sidecar, stamp, updated.as_bytes())?;
+    log.record(&OperationRecord::new(operation, image, changes))?;
+    Ok(sidecar)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn darktable_history_end_and_timestamp_are_read() {
+        let text = r#"<x:xmpmeta xmlns:x="adobe:ns:meta/"><rdf:RDF><rdf:Description
+            xmp:CreatorTool="darktable 4.6.1"
+            darktable:change_timestamp="63839664000000000"
+            darktable:history_end="2">
+            <darktable:history><rdf:Seq>
+              <rdf:li darktable:operation="exposure"/>
+              <rdf:li darktable:operation="filmicrgb"/>
+              <rdf:li darktable:operation="sharpen"/>
+            </rdf:Seq></darktable:history>
+            </rdf:Description></rdf:RDF></x:xmpmeta>"#;
+        let summary = parse_darktable(text, Some(1)).unwrap();
+        assert_eq!(summary.developed_with, "darktable 4.6.1");
+        assert_eq!(summary.edit_count, Some(2));
+        // 2024-01-01T00:00:00Z
+        assert_eq!(summary.last_edit, Some(1_704_067_200));
+
+        let lightroom = r#"<x:xmpmeta><rdf:Description crs:Exposure2012="+0.5"/></x:xmpmeta>"#;
+        assert!(parse_darktable(lightroom, None).is_none());
+    }
+
+    #[test]
+    fn rawtherapee_profiles_count_enabled_tools() {
+        let text = "[Version]\nAppVersion=5.10\nVersion=351\n\n[Exposure]\nAuto=false\n\n\
+                    [Sharpening]\nEnabled=true\n\n[Vignetting Correction]\nEnabled=false\n\n\
+                    [Color Toning]\nEnabled=true\n";
+        let summary = parse_pp3(text, Some(1_704_067_200));
+        assert_eq!(summary.developed_with, "RawTherapee 5.10");
+        assert_eq!(summary.edit_count, Some(2));
+        assert_eq!(summary.last_edit, Some(1_704_067_200));
+    }
+
+    #[test]
+    fn sidecar_properties_flag_the_embedded_values_they_replace() {
+        let sidecar = r#"<x:xmpmeta><rdf:RDF><rdf:Description
+            xmp:Rating="4" tiff:Orientation="6" crs:Exposure2012="+0.50">
+            <dc:description><rdf:Alt><rdf:li xml:lang="x-default">Pier at dusk</rdf:li></rdf:Alt></dc:description>
+            </rdf:Description></rdf:RDF></x:xmpmeta>"#;
+        let embedded =
+            r#"<x:xmpmeta><rdf:RDF><rdf:Description xmp:Rating="4"/></rdf:RDF></x:xmpmeta>"#;
+        let exif = crate::fixtures::exif_from_fields(&[crate::fixtures::ascii_field(
+            exif::Tag::ImageDescription,
+            "OLYMPUS DIGITAL CAMERA",
+        )]);
+        let fields = merged_fields(sidecar, Some(embedded), Some(&exif));
+
+        assert!(fields.iter().all(|field| field.ifd == COMPUTED_SIDECAR_IFD));
+        assert!(fields
+            .iter()
+            .any(|field| field.tag == "crs:Exposure2012" && field.value == "+0.50"));
+        let overrides = fields
+            .iter()
+            .find(|field| field.tag == "Overrides")
+            .unwrap();
+        assert_eq!(overrides.value, "dc:description");
+    }
+
+    #[test]
+    fn sidecar_values_equal_to_the_exif_ones_are_not_overrides() {
+        let sidecar = r#"<x:xmpmeta><rdf:RDF><rdf:Description
+            tiff:Orientation="6" exif:FNumber="28/10" exif:ExposureTime="0.004"
+            exif:DateTimeOriginal="2024-06-01T18:30:00.25+02:00"
+            tiff:Make="Canon"/></rdf:RDF></x:xmpmeta>"#;
+        let exif = crate::fixtures::exif_from_fields(&[
+            exif::Field {
+                tag: exif::Tag::Orientation,
+                ifd_num: In::PRIMARY,
+                value: Value::Short(vec![6]),
+            },
+            crate::fixtures::rational_field(exif::Tag::FNumber, &[(28, 10)]),
+            crate::fixtures::rational_field(exif::Tag::ExposureTime, &[(1, 250)]),
+            crate::fixtures::ascii_field(exif::Tag::DateTimeOriginal, "2024:06:01 18:30:00"),
+            crate::fixtures::ascii_field(exif::Tag::Make, "NIKON CORPORATION"),
+        ]);
+        let fields = merged_fields(sidecar, None, Some(&exif));
+
+        let overrides = fields
+            .iter()
+            .find(|field| field.tag == "Overrides")
+            .unwrap();
+        assert_eq!(overrides.value, "tiff:Make");
+    }
+}
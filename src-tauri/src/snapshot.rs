@@ -0,0 +1,155 @@
+use crate::{
+    field_order::{field_ids, FieldList},
+    merge::MergePolicy,
+    FieldSelection, FormatStyle,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Set to rewrite the golden files from the current output instead of
+/// comparing against them.
+const UPDATE_VAR: &str = "UPDATE_SNAPSHOTS";
+
+/// What `read_exif` gives for one fixture, IDs included, as stored in its
+/// golden file.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum Snapshot {
+    Fields(FieldList),
+    Error(String),
+}
+
+fn golden_path(golden: &Path, fixture: &Path) -> PathBuf {
+    let mut name = fixture.file_name().unwrap_or_default().to_os_string();
+    name.push(".json");
+    golden.join(name)
+}
+
+/// Runs every file in `fixtures` through the same pipeline as `read_exif`
+/// with default options and the golden file of the same name plus `.json`
+/// in `golden`. Returns one message per fixture whose output differs or
+/// has no golden file; with `update` the golden files are rewritten instead.
+pub fn check_dir(fixtures: &Path, golden: &Path, update: bool) -> Vec<String> {
+    let mut files: Vec<PathBuf> = fs::read_dir(fixtures)
+        .expect("fixtures folder should be readable")
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file())
+        .collect();
+    files.sort();
+    let mut problems = Vec::new();
+    for fixture in files {
+        let snapshot = match crate::read_file_fields(
+            &fixture,
+            FormatStyle::default(),
+            MergePolicy::default(),
+            &FieldSelection::default(),
+        ) {
+            Ok(fields) => Snapshot::Fields(FieldList(fields)),
+            Err(error) => Snapshot::Error(error),
+        };
+        let actual =
+            serde_json::to_string_pretty(&snapshot).expect("snapshot should serialize") + "\n";
+        let golden_file = golden_path(golden, &fixture);
+        if update {
+            fs::create_dir_all(golden).expect("golden folder should be writable");
+            fs::write(&golden_file, &actual).expect("golden file should be writable");
+            continue;
+        }
+        match fs::read_to_string(&golden_file) {
+            Ok(expected) if expected == actual => {}
+            Ok(expected) => problems.push(format!(
+                "{} differs from {}:\n{}",
+                fixture.display(),
+                golden_file.display(),
+                snapshot_diff(&expected, &snapshot)
+            )),
+            Err(_) => problems.push(format!(
+                "{} has no golden file at {}",
+                fixture.display(),
+                golden_file.display()
+            )),
+        }
+    }
+    problems
+}
+
+/// One line per field, keyed by its ID, so a renamed tag or reformatted
+/// value shows up as a single changed line.
+fn describe(snapshot: &Snapshot) -> Vec<String> {
+    match snapshot {
+        Snapshot::Fields(fields) => field_ids(fields)
+            .into_iter()
+            .zip(fields.iter())
+            .map(|(id, field)| format!("{} = {}", id, field.value))
+            .collect(),
+        Snapshot::Error(error) => vec![format!("error: {}", error)],
+    }
+}
+
+/// The fields only in the golden file (`-`) and only in the new output (`+`).
+fn snapshot_diff(expected: &str, actual: &Snapshot) -> String {
+    let Ok(expected) = serde_json::from_str::<Snapshot>(expected) else {
+        return "  the golden file is not a snapshot".to_string();
+    };
+    let (expected, actual) = (describe(&expected), describe(actual));
+    let removed = expected
+        .iter()
+        .filter(|line| !actual.contains(line))
+        .map(|line| format!("- {}", line));
+    let added = actual
+        .iter()
+        .filter(|line| !expected.contains(line))
+        .map(|line| format!("+ {}", line));
+    let diff: Vec<String> = removed.chain(added).collect();
+    if diff.is_empty() {
+        "  the same fields in a different order or layout".to_string()
+    } else {
+        diff.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_selftest_corpus_matches_its_golden_files() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let problems = check_dir(
+            &root.join("selftest"),
+            &root.join("snapshots"),
+            std::env::var_os(UPDATE_VAR).is_some(),
+        );
+        assert!(
+            problems.is_empty(),
+            "{}\n\nRun with {}=1 to accept the new output.",
+            problems.join("\n\n"),
+            UPDATE_VAR
+        );
+    }
+
+    #[test]
+    fn changed_output_is_reported_until_updated() {
+        let root = crate::fixtures::temp_path("snapshot");
+        let (fixtures, golden) = (root.join("fixtures"), root.join("golden"));
+        fs::create_dir_all(&fixtures).unwrap();
+        fs::write(fixtures.join("empty.jpg"), [0xFF, 0xD8, 0xFF, 0xD9]).unwrap();
+
+        assert_eq!(check_dir(&fixtures, &golden, false).len(), 1);
+        assert!(check_dir(&fixtures, &golden, true).is_empty());
+        assert!(check_dir(&fixtures, &golden, false).is_empty());
+
+        fs::write(
+            golden.join("empty.jpg.json"),
+            "{\n  \"error\": \"stale\"\n}\n",
+        )
+        .unwrap();
+        let problems = check_dir(&fixtures, &golden, false);
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].contains("- error: stale"), "{}", problems[0]);
+        fs::remove_dir_all(root).unwrap();
+    }
+}
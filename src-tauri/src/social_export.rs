@@ -0,0 +1,460 @@
+use crate::{
+    commit::{commit_xmp, EditTargets},
+    geotag::{self, TrackPoint},
+    gps::GpsReading,
+    jpeg,
+    oplog::FieldChange,
+    settings::EditMode,
+    sidecar,
+    verify::WriteVerification,
+    write_lock::{FileStamp, WriteError, WriteProblemReport},
+    xmp,
+};
+use chrono::DateTime;
+use exif::{In, Tag};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+const CAPTION_PROPERTY: &str = "dc:description";
+const DATE_PROPERTY: &str = "photoshop:DateCreated";
+const LATITUDE_PROPERTY: &str = "exif:GPSLatitude";
+
+/// What an export's JSON says about one of its media files.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExportedMedia {
+    pub path: PathBuf,
+    /// The JSON file it was first found in.
+    pub json: PathBuf,
+    pub caption: Option<String>,
+    /// Seconds since the epoch: the capture time when the export kept the
+    /// photo's EXIF, the upload time otherwise.
+    pub time: Option<i64>,
+    pub position: Option<(f64, f64)>,
+}
+
+/// What the posts around a media entry say, for entries that say nothing
+/// themselves.
+#[derive(Debug, Clone, Default)]
+struct Post {
+    caption: Option<String>,
+    time: Option<i64>,
+    position: Option<(f64, f64)>,
+}
+
+/// Facebook writes UTF-8 text as if each byte were a Latin-1 character, so
+/// "é" arrives as "Ã©". Text that only decodes that way is repaired.
+fn repair_text(text: &str) -> String {
+    if text.is_ascii() || text.chars().any(|c| c as u32 > 0xFF) {
+        return text.to_string();
+    }
+    let bytes: Vec<u8> = text.chars().map(|c| c as u32 as u8).collect();
+    String::from_utf8(bytes).unwrap_or_else(|_| text.to_string())
+}
+
+fn text(value: Option<&Value>) -> Option<String> {
+    let text = repair_text(value?.as_str()?);
+    let text = text.trim();
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+fn coordinate(value: &Value) -> Option<(f64, f64)> {
+    let latitude = value.get("latitude")?.as_f64()?;
+    let longitude = value.get("longitude")?.as_f64()?;
+    // Exports fill in 0, 0 for photos without a position.
+    (latitude != 0.0 || longitude != 0.0).then_some((latitude, longitude))
+}
+
+/// The first value `pick` finds in `value` or anywhere inside it.
+fn find<T>(value: &Value, pick: &dyn Fn(&Value) -> Option<T>) -> Option<T> {
+    pick(value).or_else(|| match value {
+        Value::Array(items) => items.iter().find_map(|item| find(item, pick)),
+        Value::Object(map) => map.values().find_map(|item| find(item, pick)),
+        _ => None,
+    })
+}
+
+/// `inherited` with what the post `map` adds. Facebook titles its posts
+/// with a sentence of its own ("Ana added a new photo."), so there only the
+/// text under `data` counts as the caption; Instagram's `title` is the
+/// caption itself. Facebook keeps a check-in's place beside the media.
+fn post(map: &Map<String, Value>, inherited: &Post) -> Post {
+    let facebook = map.contains_key("attachments");
+    let data_text = map
+        .get("data")
+        .and_then(Value::as_array)
+        .and_then(|items| items.iter().find_map(|item| text(item.get("post"))));
+    let caption = match facebook {
+        true => data_text,
+        false => data_text.or_else(|| text(map.get("title"))),
+    };
+    let place = |place: Option<&Value>| place?.get("coordinate").and_then(coordinate);
+    Post {
+        caption: caption.or_else(|| inherited.caption.clone()),
+        time: ["timestamp", "creation_timestamp"]
+            .iter()
+            .find_map(|key| map.get(*key)?.as_i64())
+            .or(inherited.time),
+        position: map
+            .get("attachments")
+            .and_then(|attachments| find(attachments, &|value| place(value.get("place"))))
+            .or_else(|| place(map.get("place")))
+            .or(inherited.position),
+    }
+}
+
+/// The media file `uri` names inside the export at `root`, if it is there.
+fn media_path(root: &Path, uri: &str) -> Option<PathBuf> {
+    let relative = Path::new(uri);
+    let inside = !uri.contains("://")
+        && relative
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)));
+    let path = root.join(relative);
+    (inside && crate::is_supported_image(&path) && path.is_file()).then_some(path)
+}
+
+/// Collects every media entry (an object with a `uri`) below `value`.
+fn collect(value: &Value, inherited: &Post, collected: &mut Vec<(String, Post)>) {
+    match value {
+        Value::Array(items) => {
+            for item in items {
+                collect(item, inherited, collected);
+            }
+        }
+        Value::Object(map) => {
+            let Some(uri) = map.get("uri").and_then(Value::as_str) else {
+                let context = post(map, inherited);
+                for item in map.values() {
+                    collect(item, &context, collected);
+                }
+                return;
+            };
+            let metadata = map.get("media_metadata");
+            // Facebook titles media with the album name; Instagram, whose
+            // media live under `media/`, with the caption.
+            let title = uri
+                .starts_with("media/")
+                .then(|| text(map.get("title")))
+                .flatten();
+            collected.push((
+                uri.to_string(),
+                Post {
+                    caption: text(map.get("description"))
+                        .or_else(|| inherited.caption.clone())
+                        .or(title),
+                    time: metadata
+                        .and_then(|metadata| {
+                            find(metadata, &|value| value.get("taken_timestamp")?.as_i64())
+                        })
+                        .or_else(|| map.get("creation_timestamp")?.as_i64())
+                        .or(inherited.time),
+                    position: metadata
+                        .and_then(|metadata| find(metadata, &coordinate))
+                        .or(inherited.position),
+                },
+            ));
+        }
+        _ => {}
+    }
+}
+
+/// Every media file of the Instagram or Facebook export at `root` that its
+/// JSON files describe, with what they say about it. A file listed more
+/// than once takes each value from the first listing that has one.
+pub fn read_export(root: &Path) -> Vec<ExportedMedia> {
+    let mut json_files = Vec::new();
+    crate::walk_files(root, &mut |path| {
+        if path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("json"))
+        {
+            json_files.push(path.to_path_buf());
+        }
+    });
+    json_files.sort();
+
+    let mut media: BTreeMap<PathBuf, ExportedMedia> = BTreeMap::new();
+    for json in json_files {
+        let Ok(value) = crate::load_file_data(&json).and_then(|data| {
+            serde_json::from_slice::<Value>(&data).map_err(|error| error.to_string())
+        }) else {
+            continue;
+        };
+        let mut collected = Vec::new();
+        collect(&value, &Post::default(), &mut collected);
+        for (uri, found) in collected {
+            let Some(path) = media_path(root, &uri) else {
+                continue;
+            };
+            let entry = media.entry(path.clone()).or_insert_with(|| ExportedMedia {
+                path,
+                json: json.clone(),
+                ..ExportedMedia::default()
+            });
+            entry.caption = entry.caption.take().or(found.caption);
+            entry.time = entry.time.or(found.time);
+            entry.position = entry.position.or(found.position);
+        }
+    }
+    media.into_values().collect()
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SocialImport {
+    path: String,
+    json: String,
+    caption: Option<String>,
+    time: Option<String>,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+    /// The XMP properties the file gains (or, on a dry run, would gain).
+    restored: Vec<String>,
+    /// Values the file already had and kept, since `overwrite` was not set.
+    kept: Vec<String>,
+    written: bool,
+    /// Where the XMP went when it was not embedded in the file.
+    sidecar: Option<String>,
+    error: Option<String>,
+    problem: Option<WriteProblemReport>,
+    verification: Option<WriteVerification>,
+}
+
+/// The XMP properties restoring `media`, leaving out the ones the file
+/// already has unless `overwrite` is set, and the names of those kept. A
+/// recorded DateTimeOriginal or EXIF position counts as having one, since
+/// the camera's value beats an upload time.
+fn properties(
+    media: &ExportedMedia,
+    packet: Option<&str>,
+    exif: Option<&exif::Exif>,
+    overwrite: bool,
+) -> (Vec<(&'static str, String)>, Vec<String>) {
+    let existing: Vec<String> = packet
+        .map(|packet| {
+            xmp::properties(packet)
+                .into_iter()
+                .map(|(name, _)| name)
+                .collect()
+        })
+        .unwrap_or_default();
+    let has = |name: &str| existing.iter().any(|existing| existing == name);
+    let mut properties = Vec::new();
+    let mut kept = Vec::new();
+
+    if let Some(caption) = &media.caption {
+        match overwrite || !has(CAPTION_PROPERTY) {
+            true => properties.push((CAPTION_PROPERTY, caption.clone())),
+            false => kept.push(CAPTION_PROPERTY.to_string()),
+        }
+    }
+    if let Some(time) = media
+        .time
+        .and_then(|seconds| DateTime::from_timestamp(seconds, 0))
+    {
+        let dated = has(DATE_PROPERTY)
+            || exif
+                .is_some_and(|exif| exif.get_field(Tag::DateTimeOriginal, In::PRIMARY).is_some());
+        match overwrite || !dated {
+            true => properties.push((DATE_PROPERTY, time.format("%Y-%m-%dT%H:%M:%SZ").to_string())),
+            false => kept.push(DATE_PROPERTY.to_string()),
+        }
+    }
+    if let Some((latitude, longitude)) = media.position {
+        let located = has(LATITUDE_PROPERTY)
+            || exif.is_some_and(|exif| GpsReading::from_exif(exif).position().is_some());
+        match overwrite || !located {
+            true => properties.extend(geotag::xmp_properties(&TrackPoint {
+                time: Default::default(),
+                latitude,
+                longitude,
+                elevation: None,
+            })),
+            false => kept.push(LATITUDE_PROPERTY.to_string()),
+        }
+    }
+    (properties, kept)
+}
+
+/// Writes the caption, time and position the export kept for `media` as
+/// XMP: into a JPEG itself, or into an XMP sidecar for other formats.
+pub fn restore(
+    media: &ExportedMedia,
+    overwrite: bool,
+    dry_run: bool,
+    targets: &EditTargets,
+) -> SocialImport {
+    let path = media.path.as_path();
+    let (latitude, longitude) = media.position.unzip();
+    let mut import = SocialImport {
+        path: path.to_string_lossy().into_owned(),
+        json: media.json.to_string_lossy().into_owned(),
+        caption: media.caption.clone(),
+        time: media
+            .time
+            .and_then(|seconds| DateTime::from_timestamp(seconds, 0))
+            .map(|time| time.format("%Y-%m-%d %H:%M:%S UTC").to_string()),
+        latitude,
+        longitude,
+        restored: Vec::new(),
+        kept: Vec::new(),
+        written: false,
+        sidecar: None,
+        error: None,
+        problem: None,
+        verification: None,
+    };
+
+    let outcome = (|| -> Result<(), WriteError> {
+        let stamp = FileStamp::of(path)?;
+        let data = crate::load_file_data(path)?;
+        let exif = crate::read_exif_container(&data);
+        let embed = jpeg::is_jpeg(&data);
+        let sidecar_packet = match embed {
+            true => None,
+            false => fs::read_to_string(sidecar::xmp_path(path)).ok(),
+        };
+        let packet = match embed {
+            true => xmp::jpeg_packet(&data),
+            false => sidecar_packet.as_deref(),
+        };
+        let (properties, kept) = properties(media, packet, exif.as_ref(), overwrite);
+        import.kept = kept;
+        import.restored = properties
+            .iter()
+            .map(|(name, _)| name.to_string())
+            .collect();
+        if dry_run || properties.is_empty() {
+            return Ok(());
+        }
+        // Like the people of a Takeout import, the values stay readable from
+        // the export's JSON while nothing may be written beside the originals.
+        if targets.mode == EditMode::ExternalOnly {
+            return Err("Importing is disabled while originals are protected."
+                .to_string()
+                .into());
+        }
+
+        if !embed {
+            let written =
+                sidecar::write_xmp(path, &properties, "import_social_export", targets.log)?;
+            import.sidecar = Some(written.to_string_lossy().into_owned());
+            import.written = true;
+            return Ok(());
+        }
+        let changes = properties
+            .iter()
+            .map(|(name, value)| FieldChange {
+                tag: name.to_string(),
+                before: packet
+                    .and_then(|packet| xmp::property(packet, name))
+                    .map(str::to_string),
+                after: Some(value.clone()),
+            })
+            .collect();
+        let pairs: Vec<(&str, &str)> = properties
+            .iter()
+            .map(|(name, value)| (*name, value.as_str()))
+            .collect();
+        let packet = xmp::with_properties(packet, &pairs)?;
+        import.verification = Some(commit_xmp(
+            targets,
+            "import_social_export",
+            path,
+            stamp,
+            &data,
+            &packet,
+            changes,
+        )?);
+        import.written = true;
+        Ok(())
+    })();
+
+    if let Err(error) = outcome {
+        import.error = Some(error.message());
+        import.problem = error.report();
+    }
+    import
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::JpegBuilder;
+
+    const INSTAGRAM: &str = r#"[
+        {
+            "media": [
+                { "uri": "media/posts/202406/a.jpg", "creation_timestamp": 1717266600, "title": "",
+                  "media_metadata": { "photo_metadata": { "exif_data": [{ "latitude": 38.7139, "longitude": -9.1334 }] } } },
+                { "uri": "media/posts/202406/b.jpg", "creation_timestamp": 1717266601, "title": "" }
+            ],
+            "title": "Lisbon trams",
+            "creation_timestamp": 1717266700
+        },
+        { "media": [{ "uri": "https://example.com/x.jpg", "title": "elsewhere" }] }
+    ]"#;
+
+    const FACEBOOK: &str = r#"[{
+        "timestamp": 1717000000,
+        "title": "Ana Ruiz added a new photo.",
+        "data": [{ "post": "CafÃ© with friends" }],
+        "attachments": [
+            { "data": [{ "media": { "uri": "your_facebook_activity/posts/media/c.jpg", "title": "Timeline photos",
+                "media_metadata": { "photo_metadata": { "exif_data": [{ "taken_timestamp": 1716990000 }] } } } }] },
+            { "data": [{ "place": { "name": "Porto", "coordinate": { "latitude": 41.15, "longitude": -8.61 } } }] }
+        ]
+    }]"#;
+
+    #[test]
+    fn media_entries_take_what_their_posts_say() {
+        let root = crate::fixtures::temp_path("social");
+        for name in [
+            "media/posts/202406/a.jpg",
+            "media/posts/202406/b.jpg",
+            "your_facebook_activity/posts/media/c.jpg",
+        ] {
+            let path = root.join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, JpegBuilder::new().finish()).unwrap();
+        }
+        fs::write(root.join("posts_1.json"), INSTAGRAM).unwrap();
+        fs::write(root.join("your_posts_1.json"), FACEBOOK).unwrap();
+        let media = read_export(&root);
+        fs::remove_dir_all(&root).ok();
+
+        assert_eq!(media.len(), 3);
+        assert_eq!(media[0].caption.as_deref(), Some("Lisbon trams"));
+        assert_eq!(media[0].time, Some(1717266600));
+        assert_eq!(media[0].position, Some((38.7139, -9.1334)));
+        assert_eq!(media[1].position, None);
+        assert_eq!(media[2].caption.as_deref(), Some("Café with friends"));
+        assert_eq!(media[2].time, Some(1716990000));
+        assert_eq!(media[2].position, Some((41.15, -8.61)));
+    }
+
+    #[test]
+    fn existing_values_are_kept_unless_overwritten() {
+        let media = ExportedMedia {
+            caption: Some("Lisbon trams".to_string()),
+            time: Some(1717266600),
+            position: Some((38.7139, -9.1334)),
+            ..ExportedMedia::default()
+        };
+        let packet = xmp::with_properties(None, &[(CAPTION_PROPERTY, "Mine")]).unwrap();
+
+        let (added, kept) = properties(&media, Some(&packet), None, false);
+        assert_eq!(kept, [CAPTION_PROPERTY]);
+        assert!(added.contains(&(DATE_PROPERTY, "2024-06-01T18:30:00Z".to_string())));
+        assert!(added.iter().any(|(name, _)| *name == LATITUDE_PROPERTY));
+
+        let (added, kept) = properties(&media, Some(&packet), None, true);
+        assert!(kept.is_empty());
+        assert!(added.contains(&(CAPTION_PROPERTY, "Lisbon trams".to_string())));
+    }
+}
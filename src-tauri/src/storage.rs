@@ -0,0 +1,221 @@
+use crate::{captions, capture::capture_time, xmp};
+use chrono::Datelike;
+use exif::{Context, Exif, In, Tag};
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// The Microsoft rating tag Windows and many cameras write to IFD0.
+const RATING: Tag = Tag(Context::Tiff, 0x4746);
+const UNKNOWN: &str = "Unknown";
+/// Camera RAW formats, counted alongside the images the viewer opens since
+/// they usually take most of the space.
+const RAW_EXTENSIONS: &[&str] = &[
+    "cr2", "cr3", "crw", "nef", "nrw", "arw", "srf", "sr2", "raf", "orf", "rw2", "pef", "srw",
+    "x3f", "3fr", "iiq", "erf", "mos", "rwl",
+];
+
+/// Files and bytes sharing one value of an attribute.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageBucket {
+    pub label: String,
+    pub files: usize,
+    pub bytes: u64,
+}
+
+/// Disk usage below a folder, largest buckets first.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageReport {
+    pub files: usize,
+    pub bytes: u64,
+    pub by_format: Vec<StorageBucket>,
+    pub by_camera: Vec<StorageBucket>,
+    pub by_year: Vec<StorageBucket>,
+    pub by_rating: Vec<StorageBucket>,
+    /// Rating, format and year together, e.g. `Rejected CR2 from 2019`.
+    pub combinations: Vec<StorageBucket>,
+}
+
+#[derive(Default)]
+struct Tally(BTreeMap<String, (usize, u64)>);
+
+impl Tally {
+    fn add(&mut self, label: String, bytes: u64) {
+        let entry = self.0.entry(label).or_default();
+        entry.0 += 1;
+        entry.1 += bytes;
+    }
+
+    fn buckets(self) -> Vec<StorageBucket> {
+        let mut buckets: Vec<StorageBucket> = self
+            .0
+            .into_iter()
+            .map(|(label, (files, bytes))| StorageBucket {
+                label,
+                files,
+                bytes,
+            })
+            .collect();
+        buckets.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.label.cmp(&b.label)));
+        buckets
+    }
+}
+
+/// `xmp:Rating` from the file's own XMP, then from a sidecar (where
+/// Lightroom keeps it for RAW files), then the EXIF rating. `-1` marks a
+/// rejected photo.
+fn rating(path: &Path, data: &[u8], exif: Option<&Exif>) -> Option<i32> {
+    let from_packet = |packet: &str| xmp::property(packet, "xmp:Rating")?.trim().parse().ok();
+    if let Some(rating) = xmp::packet(data).and_then(from_packet) {
+        return Some(rating);
+    }
+    let mut sidecar = path.as_os_str().to_os_string();
+    sidecar.push(".xmp");
+    let sidecar_rating = [PathBuf::from(sidecar), path.with_extension("xmp")]
+        .iter()
+        .filter_map(|sidecar| fs::read(sidecar).ok())
+        .find_map(|text| xmp::packet(&text).and_then(from_packet));
+    sidecar_rating.or_else(|| {
+        exif?
+            .get_field(RATING, In::PRIMARY)?
+            .value
+            .get_uint(0)
+            .map(|rating| rating as i32)
+    })
+}
+
+fn rating_label(rating: Option<i32>) -> String {
+    match rating {
+        Some(rating) if rating < 0 => "Rejected".to_string(),
+        Some(1) => "1 star".to_string(),
+        Some(rating) if rating > 1 => format!("{} stars", rating.min(5)),
+        _ => "Unrated".to_string(),
+    }
+}
+
+fn is_counted(path: &Path) -> bool {
+    crate::is_supported_image(path)
+        || path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .is_some_and(|extension| {
+                RAW_EXTENSIONS.contains(&extension.to_ascii_lowercase().as_str())
+            })
+}
+
+/// Sums the size of every image and RAW file below `root` by format, camera, capture
+/// year and rating.
+pub fn storage_report(root: &Path) -> Result<StorageReport, String> {
+    let (mut files, mut bytes) = (0, 0);
+    let (mut formats, mut cameras, mut years, mut ratings, mut combinations) = (
+        Tally::default(),
+        Tally::default(),
+        Tally::default(),
+        Tally::default(),
+        Tally::default(),
+    );
+    crate::walk_files(root, &mut |path| {
+        if !is_counted(path) {
+            return;
+        }
+        let (Ok(metadata), Ok(data)) = (
+            fs::metadata(path),
+            crate::memory_budget::load_scan_data(path),
+        ) else {
+            return;
+        };
+        let size = metadata.len();
+        let exif = crate::read_exif_container(&data);
+        let format = path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_uppercase())
+            .unwrap_or_else(|| UNKNOWN.to_string());
+        let camera = exif
+            .as_ref()
+            .and_then(captions::camera)
+            .unwrap_or_else(|| UNKNOWN.to_string());
+        let year = exif
+            .as_ref()
+            .and_then(capture_time)
+            .map(|captured| captured.local.year().to_string());
+        let rating = rating_label(rating(path, &data, exif.as_ref()));
+
+        combinations.add(
+            format!(
+                "{} {} from {}",
+                rating,
+                format,
+                year.as_deref().unwrap_or("an unknown year")
+            ),
+            size,
+        );
+        formats.add(format, size);
+        cameras.add(camera, size);
+        years.add(year.unwrap_or_else(|| UNKNOWN.to_string()), size);
+        ratings.add(rating, size);
+        files += 1;
+        bytes += size;
+    });
+    if files == 0 {
+        return Err("No images were found in the selected folder.".to_string());
+    }
+    Ok(StorageReport {
+        files,
+        bytes,
+        by_format: formats.buckets(),
+        by_camera: cameras.buckets(),
+        by_year: years.buckets(),
+        by_rating: ratings.buckets(),
+        combinations: combinations.buckets(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn usage_is_grouped_by_rating_format_and_year() {
+        let root = crate::fixtures::temp_path("storage");
+        fs::create_dir_all(&root).unwrap();
+        let rated = |rating: &str| {
+            format!(
+                r#"<x:xmpmeta><rdf:Description xmp:Rating="{}"/></x:xmpmeta>"#,
+                rating
+            )
+        };
+        fs::write(root.join("a.cr2"), vec![0; 600]).unwrap();
+        fs::write(root.join("a.cr2.xmp"), rated("-1")).unwrap();
+        fs::write(root.join("b.cr2"), vec![0; 400]).unwrap();
+        fs::write(root.join("b.xmp"), rated("-1")).unwrap();
+        fs::write(root.join("c.jpg"), rated("4")).unwrap();
+
+        let report = storage_report(&root);
+        fs::remove_dir_all(&root).ok();
+        let report = report.unwrap();
+
+        assert_eq!(
+            (report.files, report.bytes),
+            (3, 1000 + rated("4").len() as u64)
+        );
+        assert_eq!(
+            report.combinations[0],
+            StorageBucket {
+                label: "Rejected CR2 from an unknown year".to_string(),
+                files: 2,
+                bytes: 1000,
+            }
+        );
+        let ratings: Vec<&str> = report
+            .by_rating
+            .iter()
+            .map(|bucket| bucket.label.as_str())
+            .collect();
+        assert_eq!(ratings, ["Rejected", "4 stars"]);
+    }
+}
@@ -0,0 +1,482 @@
+use crate::{
+    commit::commit_rewrite,
+    exif_write::{self, FieldEdit},
+    jpeg,
+    oplog::{FieldChange, OperationLog, OperationRecord},
+    png_chunks,
+    settings::EditMode,
+    write_lock::{write_new, FileStamp, WriteError, WriteProblemReport},
+    xmp, PNG_SIGNATURE,
+};
+use exif::{Context, Exif, In, Tag, Value};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+const EXIF_SEGMENT: &str = "APP1 (Exif)";
+const XMP_SEGMENTS: [&str; 2] = [
+    "APP1 (http://ns.adobe.com/xap/1.0/)",
+    "APP1 (http://ns.adobe.com/xmp/extension/)",
+];
+const IPTC_SEGMENT: &str = "APP13 (Photoshop 3.0)";
+const PNG_XMP_KEYWORD: &str = "XML:com.adobe.xmp";
+/// ImageMagick's text-chunk copies of EXIF and IPTC blocks.
+const PNG_EXIF_PROFILE: &str = "Raw profile type exif";
+const PNG_IPTC_PROFILE: &str = "Raw profile type iptc";
+const TIFF_XMP: Tag = Tag(Context::Tiff, 700);
+const TIFF_IPTC: Tag = Tag(Context::Tiff, 33723);
+/// Baseline TIFF tags that describe the shot or the people behind it rather
+/// than the pixels, removed with the rest of a TIFF's EXIF. 0x013C is
+/// HostComputer, which the reader has no name for.
+const TIFF_DESCRIPTIVE: [Tag; 8] = [
+    Tag::ImageDescription,
+    Tag::Make,
+    Tag::Model,
+    Tag::Software,
+    Tag::DateTime,
+    Tag::Artist,
+    Tag(Context::Tiff, 0x013C),
+    Tag::Copyright,
+];
+
+/// What `strip_metadata` removes. Each kind is independent: `gps` alone
+/// leaves the rest of EXIF, and `pngText` leaves the XMP and IPTC chunks
+/// unless `xmp` and `iptc` are set too.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct StripOptions {
+    pub exif: bool,
+    /// The GPS fields of EXIF and XMP.
+    pub gps: bool,
+    pub xmp: bool,
+    pub iptc: bool,
+    /// PNG `tEXt`, `zTXt` and `iTXt` chunks.
+    pub png_text: bool,
+    /// Writes cleaned copies with the same names into this folder instead of
+    /// replacing the originals.
+    pub output_folder: Option<String>,
+}
+
+impl StripOptions {
+    pub fn any(&self) -> bool {
+        self.exif || self.gps || self.xmp || self.iptc || self.png_text
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetadataStrip {
+    path: String,
+    /// The file that was written: `path` itself or the copy.
+    output: String,
+    /// What was removed, e.g. `GPS` or `APP13 (Photoshop 3.0)`.
+    removed: Vec<String>,
+    bytes_saved: usize,
+    written: bool,
+    error: Option<String>,
+    problem: Option<WriteProblemReport>,
+}
+
+/// Removes every GPS tag of the primary IFD.
+pub fn gps_edits(exif: &Exif) -> Vec<FieldEdit> {
+    exif.fields()
+        .filter(|field| field.ifd_num == In::PRIMARY && field.tag.context() == Context::Gps)
+        .map(|field| FieldEdit {
+            tag: field.tag,
+            value: None,
+        })
+        .collect()
+}
+
+/// `packet` without its `exif:GPS*` properties, or `None` when it has none.
+fn without_gps(packet: &str) -> Option<String> {
+    let names: Vec<String> = xmp::properties(packet)
+        .into_iter()
+        .map(|(name, _)| name)
+        .filter(|name| name.starts_with("exif:GPS"))
+        .collect();
+    if names.is_empty() {
+        return None;
+    }
+    let names: Vec<&str> = names.iter().map(String::as_str).collect();
+    Some(xmp::without_properties(packet, &names))
+}
+
+/// `data` without the metadata `options` pick, and what was removed.
+/// JPEG segments and PNG chunks are dropped whole and everything else is
+/// copied byte for byte; only EXIF that loses just its GPS is re-encoded.
+pub fn strip(data: &[u8], options: &StripOptions) -> Result<(Vec<u8>, Vec<String>), String> {
+    if jpeg::is_jpeg(data) {
+        strip_jpeg(data, options)
+    } else if data.starts_with(&PNG_SIGNATURE) {
+        strip_png(data, options)
+    } else if exif_write::is_tiff(data) {
+        strip_tiff(data, options)
+    } else {
+        Err("Metadata can only be stripped from JPEG, PNG and TIFF files.".to_string())
+    }
+}
+
+fn strip_jpeg(data: &[u8], options: &StripOptions) -> Result<(Vec<u8>, Vec<String>), String> {
+    let exif = crate::read_exif_container(data);
+    let mut data = data.to_vec();
+    let mut removed = Vec::new();
+
+    if options.gps && !options.exif {
+        let edits = exif.as_ref().map(gps_edits).unwrap_or_default();
+        if !edits.is_empty() {
+            data = exif_write::apply_jpeg_edits(&data, &edits)?;
+            removed.push("GPS".to_string());
+        }
+    }
+    if options.gps && !options.xmp {
+        if let Some(packet) = xmp::jpeg_packet(&data).and_then(without_gps) {
+            data = xmp::embed_in_jpeg(&data, &packet)?;
+            removed.push("XMP GPS".to_string());
+        }
+    }
+
+    let mut selectors = Vec::new();
+    if options.exif {
+        selectors.push(EXIF_SEGMENT.to_string());
+    }
+    if options.xmp {
+        selectors.extend(XMP_SEGMENTS.map(String::from));
+    }
+    if options.iptc {
+        selectors.push(IPTC_SEGMENT.to_string());
+    }
+    if !selectors.is_empty() {
+        let (updated, removal) = jpeg::remove_segments(&data, &selectors, false)?;
+        data = updated;
+        removed.extend(removal.removed);
+    }
+
+    // Without its Orientation a rotated photo would display sideways, and the
+    // tag says nothing about where or by whom it was taken.
+    let orientation = exif
+        .as_ref()
+        .and_then(|exif| exif.get_field(Tag::Orientation, In::PRIMARY))
+        .and_then(|field| field.value.get_uint(0))
+        .filter(|&orientation| orientation != 1);
+    if let (true, Some(orientation)) = (options.exif, orientation) {
+        if removed.iter().any(|label| label == EXIF_SEGMENT) {
+            data = exif_write::apply_jpeg_edits(
+                &data,
+                &[FieldEdit {
+                    tag: Tag::Orientation,
+                    value: Some(Value::Short(vec![orientation as u16])),
+                }],
+            )?;
+        }
+    }
+    Ok((data, removed))
+}
+
+fn strip_png(data: &[u8], options: &StripOptions) -> Result<(Vec<u8>, Vec<String>), String> {
+    let keyword_of = |data: &[u8], chunk: &png_chunks::Chunk| {
+        [b"tEXt", b"zTXt", b"iTXt"].contains(&&chunk.kind).then(|| {
+            let payload = chunk.payload(data);
+            let keyword = payload.split(|&byte| byte == 0).next().unwrap_or_default();
+            String::from_utf8_lossy(keyword).into_owned()
+        })
+    };
+    let (chunks, _) = png_chunks::chunks(data)?;
+    let mut data = data.to_vec();
+    let mut removed = Vec::new();
+
+    if options.gps && !options.exif {
+        // Both hold a whole EXIF block, which is not re-encoded here.
+        let exif_block = chunks.iter().any(|chunk| {
+            &chunk.kind == b"eXIf" || keyword_of(&data, chunk).as_deref() == Some(PNG_EXIF_PROFILE)
+        });
+        if exif_block {
+            return Err(
+                "GPS alone cannot be removed from a PNG's EXIF; remove all of its EXIF instead."
+                    .to_string(),
+            );
+        }
+    }
+    if options.gps && !options.xmp {
+        let xmp_chunk = chunks.iter().position(|chunk| {
+            &chunk.kind == b"iTXt" && keyword_of(&data, chunk).as_deref() == Some(PNG_XMP_KEYWORD)
+        });
+        let packet = xmp::container_packet(&data).and_then(|packet| without_gps(&packet));
+        if let (Some(index), Some(packet)) = (xmp_chunk, packet) {
+            // Keyword, no compression, and empty language and translated keyword.
+            let mut payload = PNG_XMP_KEYWORD.as_bytes().to_vec();
+            payload.extend_from_slice(&[0, 0, 0, 0, 0]);
+            payload.extend_from_slice(packet.as_bytes());
+            data = png_chunks::replace_payload(&data, index, &payload)?;
+            removed.push("XMP GPS".to_string());
+        }
+    }
+
+    // The XMP chunk may have changed size, so the offsets are read afresh.
+    let (chunks, _) = png_chunks::chunks(&data)?;
+    let mut order = Vec::new();
+    for (index, chunk) in chunks.iter().enumerate() {
+        let keyword = keyword_of(&data, chunk);
+        let remove = match keyword.as_deref() {
+            None => &chunk.kind == b"eXIf" && options.exif,
+            Some(PNG_EXIF_PROFILE) => options.exif,
+            Some(PNG_XMP_KEYWORD) => options.xmp,
+            Some(PNG_IPTC_PROFILE) => options.iptc,
+            Some(_) => options.png_text,
+        };
+        if remove {
+            let kind = String::from_utf8_lossy(&chunk.kind).into_owned();
+            removed.push(match keyword {
+                Some(keyword) => format!("PNG {} ({})", kind, keyword),
+                None => format!("PNG {}", kind),
+            });
+        } else {
+            order.push(index);
+        }
+    }
+    Ok((png_chunks::rearrange(&data, &order)?, removed))
+}
+
+/// A TIFF keeps its image layout in the same IFD as its metadata, so tags
+/// are removed one by one and the pixels are carried over unchanged.
+fn strip_tiff(data: &[u8], options: &StripOptions) -> Result<(Vec<u8>, Vec<String>), String> {
+    let Some(exif) = crate::read_exif_container(data) else {
+        return Err("The TIFF's tags are unreadable.".to_string());
+    };
+    let has = |tag: Tag| exif.get_field(tag, In::PRIMARY).is_some();
+    let remove = |tag: Tag| FieldEdit { tag, value: None };
+    let mut edits = Vec::new();
+    let mut removed = Vec::new();
+
+    if options.exif {
+        let exif_edits: Vec<FieldEdit> = exif
+            .fields()
+            .filter(|field| field.ifd_num == In::PRIMARY)
+            .filter(|field| {
+                matches!(field.tag.context(), Context::Exif | Context::Gps)
+                    || TIFF_DESCRIPTIVE.contains(&field.tag)
+            })
+            .map(|field| remove(field.tag))
+            .collect();
+        if !exif_edits.is_empty() {
+            edits.extend(exif_edits);
+            removed.push("EXIF".to_string());
+        }
+    } else if options.gps {
+        let gps = gps_edits(&exif);
+        if !gps.is_empty() {
+            edits.extend(gps);
+            removed.push("GPS".to_string());
+        }
+    }
+    if options.gps && !options.xmp {
+        let packet = exif
+            .get_field(TIFF_XMP, In::PRIMARY)
+            .and_then(|field| match &field.value {
+                Value::Byte(bytes) | Value::Undefined(bytes, _) => std::str::from_utf8(bytes).ok(),
+                _ => None,
+            })
+            .and_then(without_gps);
+        if let Some(packet) = packet {
+            edits.push(FieldEdit {
+                tag: TIFF_XMP,
+                value: Some(Value::Byte(packet.into_bytes())),
+            });
+            removed.push("XMP GPS".to_string());
+        }
+    }
+    if options.xmp && has(TIFF_XMP) {
+        edits.push(remove(TIFF_XMP));
+        removed.push("XMP".to_string());
+    }
+    if options.iptc && has(TIFF_IPTC) {
+        edits.push(remove(TIFF_IPTC));
+        removed.push("IPTC".to_string());
+    }
+    if edits.is_empty() {
+        return Ok((data.to_vec(), removed));
+    }
+    Ok((exif_write::apply_tiff_edits(data, &edits)?, removed))
+}
+
+/// Strips `path` in place, or writes the cleaned image into
+/// `output_folder` under the same name and leaves the original alone.
+pub fn strip_file(
+    path: &Path,
+    options: &StripOptions,
+    log: &OperationLog,
+    mode: EditMode,
+) -> MetadataStrip {
+    let output = match &options.output_folder {
+        Some(folder) => PathBuf::from(folder).join(path.file_name().unwrap_or_default()),
+        None => path.to_path_buf(),
+    };
+    let mut result = MetadataStrip {
+        path: path.to_string_lossy().into_owned(),
+        output: output.to_string_lossy().into_owned(),
+        removed: Vec::new(),
+        bytes_saved: 0,
+        written: false,
+        error: None,
+        problem: None,
+    };
+
+    let outcome = (|| -> Result<(), WriteError> {
+        let stamp = FileStamp::of(path)?;
+        let data = crate::load_file_data(path)?;
+        let (updated, removed) = strip(&data, options)?;
+        let changes: Vec<FieldChange> = removed
+            .iter()
+            .map(|label| FieldChange {
+                tag: "Metadata".to_string(),
+                before: Some(label.clone()),
+                after: None,
+            })
+            .collect();
+        result.bytes_saved = data.len().saturating_sub(updated.len());
+        result.removed = removed;
+
+        if options.output_folder.is_some() {
+            // A copy is written even when nothing had to go, since it is
+            // the file the user means to share.
+            write_new(&output, &updated)?;
+            log.record(&OperationRecord::new(
+                "strip_metadata (copy)",
+                &output,
+                changes,
+            ))?;
+            result.written = true;
+        } else if !changes.is_empty() {
+            commit_rewrite(log, mode, "strip_metadata", path, stamp, &updated, changes)?;
+            result.written = true;
+        }
+        Ok(())
+    })();
+
+    if let Err(error) = outcome {
+        result.error = Some(error.message());
+        result.problem = error.report();
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{ascii_field, rational_field, JpegBuilder, PngBuilder};
+    use exif::Field;
+
+    fn options(change: impl FnOnce(&mut StripOptions)) -> StripOptions {
+        let mut options = StripOptions::default();
+        change(&mut options);
+        options
+    }
+
+    fn jpeg() -> Vec<u8> {
+        JpegBuilder::new()
+            .exif(&[
+                ascii_field(Tag::Artist, "Ada"),
+                Field {
+                    tag: Tag::Orientation,
+                    ifd_num: In::PRIMARY,
+                    value: Value::Short(vec![6]),
+                },
+                rational_field(Tag::GPSLatitude, &[(51, 1), (30, 1), (0, 1)]),
+            ])
+            .xmp(r#"<x:xmpmeta><rdf:RDF><rdf:Description exif:GPSLatitude="51,30.0N" dc:format="image/jpeg"/></rdf:RDF></x:xmpmeta>"#)
+            .segment(crate::iptc::APP13, b"Photoshop 3.0\0")
+            .finish()
+    }
+
+    #[test]
+    fn gps_only_keeps_the_rest_of_the_metadata() {
+        let original = jpeg();
+        let (data, removed) = strip(&original, &options(|o| o.gps = true)).unwrap();
+        let exif = crate::read_exif_container(&data).unwrap();
+        let packet = xmp::jpeg_packet(&data).unwrap();
+
+        assert_eq!(removed, ["GPS", "XMP GPS"]);
+        assert!(exif.get_field(Tag::GPSLatitude, In::PRIMARY).is_none());
+        assert!(exif.get_field(Tag::Artist, In::PRIMARY).is_some());
+        assert!(!packet.contains("GPSLatitude") && packet.contains("dc:format"));
+        assert!(data.ends_with(&original[original.len() - 8..]));
+    }
+
+    #[test]
+    fn whole_blocks_go_but_the_orientation_stays() {
+        let (data, removed) = strip(
+            &jpeg(),
+            &options(|o| {
+                o.exif = true;
+                o.xmp = true;
+                o.iptc = true;
+            }),
+        )
+        .unwrap();
+        let exif = crate::read_exif_container(&data).unwrap();
+
+        assert_eq!(
+            removed,
+            [EXIF_SEGMENT, XMP_SEGMENTS[0], IPTC_SEGMENT].map(String::from)
+        );
+        assert_eq!(exif.fields().count(), 1);
+        assert_eq!(
+            exif.get_field(Tag::Orientation, In::PRIMARY)
+                .and_then(|field| field.value.get_uint(0)),
+            Some(6)
+        );
+        assert!(xmp::jpeg_packet(&data).is_none());
+
+        let png = PngBuilder::new()
+            .header(1, 1)
+            .text("Comment", "at home")
+            .international_text(PNG_XMP_KEYWORD, "", "", "<x:xmpmeta></x:xmpmeta>", false)
+            .chunk(b"IDAT", &[0])
+            .finish();
+        let (data, removed) = strip(&png, &options(|o| o.png_text = true)).unwrap();
+        assert_eq!(removed, ["PNG tEXt (Comment)"]);
+        assert!(xmp::container_packet(&data).is_some());
+    }
+
+    #[test]
+    fn gps_only_reaches_the_xmp_of_pngs_and_tiffs() {
+        let packet = r#"<x:xmpmeta><rdf:RDF><rdf:Description exif:GPSLatitude="51,30.0N" dc:format="image/png"/></rdf:RDF></x:xmpmeta>"#;
+        let png = PngBuilder::new()
+            .header(1, 1)
+            .international_text(PNG_XMP_KEYWORD, "", "", packet, true)
+            .chunk(b"IDAT", &[0])
+            .finish();
+        let (data, removed) = strip(&png, &options(|o| o.gps = true)).unwrap();
+        let cleaned = xmp::container_packet(&data).unwrap();
+        assert_eq!(removed, ["XMP GPS"]);
+        assert!(!cleaned.contains("GPSLatitude") && cleaned.contains("dc:format"));
+        assert!(png_chunks::list(&data)
+            .unwrap()
+            .iter()
+            .all(|chunk| chunk.crc_valid));
+
+        let profile = PngBuilder::new()
+            .header(1, 1)
+            .compressed_text(PNG_EXIF_PROFILE, "\nexif\n       8\n457869660000")
+            .chunk(b"IDAT", &[0])
+            .finish();
+        assert!(strip(&profile, &options(|o| o.gps = true)).is_err());
+
+        let tiff = crate::fixtures::tiff_from_fields(&[
+            ascii_field(Tag::Artist, "Ada"),
+            rational_field(Tag::GPSLatitude, &[(51, 1), (30, 1), (0, 1)]),
+            Field {
+                tag: TIFF_XMP,
+                ifd_num: In::PRIMARY,
+                value: Value::Byte(packet.as_bytes().to_vec()),
+            },
+        ]);
+        let (data, removed) = strip(&tiff, &options(|o| o.gps = true)).unwrap();
+        let cleaned = xmp::container_packet(&data).unwrap();
+        assert_eq!(removed, ["GPS", "XMP GPS"]);
+        assert!(!cleaned.contains("GPSLatitude") && cleaned.contains("dc:format"));
+        assert!(crate::read_exif_container(&data)
+            .unwrap()
+            .get_field(Tag::Artist, In::PRIMARY)
+            .is_some());
+    }
+}
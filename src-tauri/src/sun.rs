@@ -0,0 +1,150 @@
+use crate::{gps::cardinal_direction, timezone::located_capture, ExifField};
+use chrono::NaiveDateTime;
+use exif::Exif;
+use serde::{Deserialize, Serialize};
+
+pub const COMPUTED_SUN_IFD: &str = "Sun (computed)";
+
+/// Lighting conditions classified by the sun's altitude above the horizon.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LightPhase {
+    Day,
+    GoldenHour,
+    BlueHour,
+    Night,
+}
+
+impl LightPhase {
+    pub fn from_altitude(altitude: f64) -> Self {
+        if altitude >= 6.0 {
+            LightPhase::Day
+        } else if altitude >= -4.0 {
+            LightPhase::GoldenHour
+        } else if altitude >= -6.0 {
+            LightPhase::BlueHour
+        } else {
+            LightPhase::Night
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            LightPhase::Day => "Daylight",
+            LightPhase::GoldenHour => "Golden hour",
+            LightPhase::BlueHour => "Blue hour",
+            LightPhase::Night => "Night",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SunPosition {
+    /// Degrees above the horizon, negative below it.
+    pub altitude: f64,
+    /// Degrees clockwise from true north.
+    pub azimuth: f64,
+}
+
+impl SunPosition {
+    pub fn phase(&self) -> LightPhase {
+        LightPhase::from_altitude(self.altitude)
+    }
+}
+
+/// Low-precision solar ephemeris (about 0.01° until 2050), plenty for
+/// classifying light at the moment of capture.
+pub fn sun_position(latitude: f64, longitude: f64, utc: NaiveDateTime) -> SunPosition {
+    let days = utc.and_utc().timestamp_millis() as f64 / 86_400_000.0;
+    let n = days + 2_440_587.5 - 2_451_545.0;
+
+    let mean_longitude = (280.460 + 0.985_647_4 * n).rem_euclid(360.0);
+    let mean_anomaly = (357.528 + 0.985_600_3 * n).rem_euclid(360.0).to_radians();
+    let ecliptic_longitude =
+        (mean_longitude + 1.915 * mean_anomaly.sin() + 0.020 * (2.0 * mean_anomaly).sin())
+            .to_radians();
+    let obliquity = (23.439 - 0.000_000_4 * n).to_radians();
+
+    let right_ascension = (obliquity.cos() * ecliptic_longitude.sin())
+        .atan2(ecliptic_longitude.cos())
+        .to_degrees();
+    let declination = (obliquity.sin() * ecliptic_longitude.sin()).asin();
+
+    let sidereal_hours = (18.697_374_558 + 24.065_709_824_419_08 * n).rem_euclid(24.0);
+    let hour_angle = (sidereal_hours * 15.0 + longitude - right_ascension).to_radians();
+    let latitude = latitude.to_radians();
+
+    let altitude = (latitude.sin() * declination.sin()
+        + latitude.cos() * declination.cos() * hour_angle.cos())
+    .asin();
+    let azimuth = hour_angle
+        .sin()
+        .atan2(hour_angle.cos() * latitude.sin() - declination.tan() * latitude.cos())
+        .to_degrees()
+        + 180.0;
+
+    SunPosition {
+        altitude: altitude.to_degrees(),
+        azimuth: azimuth.rem_euclid(360.0),
+    }
+}
+
+pub fn sun_at_capture(exif: &Exif) -> Option<SunPosition> {
+    let ((latitude, longitude), utc) = located_capture(exif)?;
+    Some(sun_position(latitude, longitude, utc))
+}
+
+pub fn computed_fields(exif: &Exif) -> Vec<ExifField> {
+    let Some(sun) = sun_at_capture(exif) else {
+        return Vec::new();
+    };
+    [
+        ("Sun altitude", format!("{:.1}°", sun.altitude)),
+        (
+            "Sun azimuth",
+            format!("{:.1}° {}", sun.azimuth, cardinal_direction(sun.azimuth)),
+        ),
+        ("Light", sun.phase().label().to_string()),
+    ]
+    .into_iter()
+    .map(|(tag, value)| ExifField {
+        tag: tag.to_string(),
+        ifd: COMPUTED_SUN_IFD.to_string(),
+        value,
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn utc(month: u32, day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, month, day)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn equinox_noon_on_the_equator_is_overhead() {
+        let sun = sun_position(0.0, 0.0, utc(3, 20, 12, 7));
+        assert!(sun.altitude > 88.0, "altitude was {}", sun.altitude);
+        assert_eq!(sun.phase(), LightPhase::Day);
+    }
+
+    #[test]
+    fn midsummer_evening_in_paris_is_golden_hour_in_the_north_west() {
+        let sun = sun_position(48.85, 2.35, utc(6, 21, 19, 30));
+        assert_eq!(sun.phase(), LightPhase::GoldenHour);
+        assert!(
+            (290.0..315.0).contains(&sun.azimuth),
+            "azimuth was {}",
+            sun.azimuth
+        );
+
+        let night = sun_position(48.85, 2.35, utc(6, 21, 23, 30));
+        assert_eq!(night.phase(), LightPhase::Night);
+    }
+}
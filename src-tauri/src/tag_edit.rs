@@ -0,0 +1,263 @@
+use crate::{
+    commit::{commit_edits, Committed, EditTargets},
+    exif_write::{self, FieldEdit},
+    oplog::{field_changes, OperationRecord},
+    verify::{verify_write, WriteVerification},
+    write_lock::{write_new, FileStamp, WriteError, WriteProblemReport},
+};
+use chrono::NaiveDateTime;
+use exif::{Tag, Value};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+const EXIF_DATE_FORMAT: &str = "%Y:%m:%d %H:%M:%S";
+/// Also accepted for DateTimeOriginal, as date pickers produce them.
+const ISO_DATE_FORMATS: [&str; 2] = ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M:%S"];
+
+/// The tags `write_exif` can set, named as EXIF names them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum EditableTag {
+    Artist,
+    Copyright,
+    DateTimeOriginal,
+    UserComment,
+    Orientation,
+}
+
+/// Sets `tag` to `value`, or removes it when `value` is missing.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TagEdit {
+    pub tag: EditableTag,
+    pub value: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExifWrite {
+    path: String,
+    /// The file that received the edits: `path` itself or the copy.
+    output: String,
+    written: bool,
+    /// The edits were stored in the app because originals are protected.
+    stored_externally: bool,
+    error: Option<String>,
+    problem: Option<WriteProblemReport>,
+    verification: Option<WriteVerification>,
+}
+
+pub fn exif_date(text: &str) -> Result<String, String> {
+    std::iter::once(EXIF_DATE_FORMAT)
+        .chain(ISO_DATE_FORMATS)
+        .find_map(|format| NaiveDateTime::parse_from_str(text.trim(), format).ok())
+        .map(|time| time.format(EXIF_DATE_FORMAT).to_string())
+        .ok_or_else(|| {
+            format!(
+                "\"{}\" is not a date and time such as 2024:06:01 18:30:00.",
+                text
+            )
+        })
+}
+
+fn orientation(text: &str) -> Result<u16, String> {
+    text.trim()
+        .parse()
+        .ok()
+        .filter(|value| (1..=8).contains(value))
+        .ok_or_else(|| format!("\"{}\" is not an orientation from 1 to 8.", text))
+}
+
+/// An 8-byte character code and the comment: ASCII when it fits, UTF-16 in
+/// the file's byte order otherwise.
+fn user_comment(text: &str, little_endian: bool) -> Value {
+    let mut bytes = Vec::new();
+    if text.is_ascii() {
+        bytes.extend_from_slice(b"ASCII\0\0\0");
+        bytes.extend_from_slice(text.as_bytes());
+    } else {
+        bytes.extend_from_slice(b"UNICODE\0");
+        for unit in text.encode_utf16() {
+            bytes.extend(match little_endian {
+                true => unit.to_le_bytes(),
+                false => unit.to_be_bytes(),
+            });
+        }
+    }
+    Value::Undefined(bytes, 0)
+}
+
+impl TagEdit {
+    /// The edit as the encoder takes it, for a file in the given byte order.
+    pub fn field_edit(&self, little_endian: bool) -> Result<FieldEdit, String> {
+        let tag = match self.tag {
+            EditableTag::Artist => Tag::Artist,
+            EditableTag::Copyright => Tag::Copyright,
+            EditableTag::DateTimeOriginal => Tag::DateTimeOriginal,
+            EditableTag::UserComment => Tag::UserComment,
+            EditableTag::Orientation => Tag::Orientation,
+        };
+        let Some(text) = self.value.as_deref() else {
+            return Ok(FieldEdit { tag, value: None });
+        };
+        let value = match self.tag {
+            EditableTag::Artist | EditableTag::Copyright => {
+                Value::Ascii(vec![text.as_bytes().to_vec()])
+            }
+            EditableTag::DateTimeOriginal => Value::Ascii(vec![exif_date(text)?.into_bytes()]),
+            EditableTag::UserComment => user_comment(text, little_endian),
+            EditableTag::Orientation => Value::Short(vec![orientation(text)?]),
+        };
+        Ok(FieldEdit {
+            tag,
+            value: Some(value),
+        })
+    }
+}
+
+/// Applies `edits` to the JPEG or TIFF at `path`, in place or, with
+/// `copy_to`, into a new file beside an untouched original.
+pub fn write_file(
+    path: &Path,
+    edits: &[TagEdit],
+    copy_to: Option<&Path>,
+    targets: &EditTargets,
+) -> ExifWrite {
+    let mut result = ExifWrite {
+        path: path.to_string_lossy().into_owned(),
+        output: copy_to.unwrap_or(path).to_string_lossy().into_owned(),
+        written: false,
+        stored_externally: false,
+        error: None,
+        problem: None,
+        verification: None,
+    };
+
+    let outcome = (|| -> Result<(), WriteError> {
+        let stamp = FileStamp::of(path)?;
+        let data = crate::load_file_data(path)?;
+        let exif = crate::read_exif_container(&data);
+        let little_endian = exif.as_ref().is_some_and(|exif| exif.little_endian());
+        let field_edits = edits
+            .iter()
+            .map(|edit| edit.field_edit(little_endian))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let Some(copy) = copy_to else {
+            match commit_edits(
+                targets,
+                "write_exif",
+                path,
+                stamp,
+                &data,
+                exif.as_ref(),
+                &field_edits,
+            )? {
+                Committed::InPlace(verification) => {
+                    result.written = true;
+                    result.verification = Some(verification);
+                }
+                Committed::External => result.stored_externally = true,
+            }
+            return Ok(());
+        };
+
+        // A new file leaves the original alone, so this works in every edit mode.
+        let updated = exif_write::apply_edits(&data, &field_edits)?;
+        write_new(copy, &updated)?;
+        let reread = crate::load_file_data(copy)?;
+        result.written = true;
+        result.verification = Some(verify_write(&data, &reread, &field_edits));
+        targets.log.record(&OperationRecord::new(
+            "write_exif (copy)",
+            copy,
+            field_changes(exif.as_ref(), &field_edits),
+        ))?;
+        Ok(())
+    })();
+
+    if let Err(error) = outcome {
+        result.error = Some(error.message());
+        result.problem = error.report();
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        fixtures::{ascii_field, jpeg_from_fields},
+        oplog::OperationLog,
+        settings::EditMode,
+        virtual_fields::VirtualFieldStore,
+    };
+    use exif::In;
+    use std::fs;
+
+    fn edit(tag: EditableTag, value: &str) -> TagEdit {
+        TagEdit {
+            tag,
+            value: Some(value.to_string()),
+        }
+    }
+
+    #[test]
+    fn values_are_checked_and_encoded_for_exif() {
+        let date = edit(EditableTag::DateTimeOriginal, "2024-06-01T18:30:00")
+            .field_edit(false)
+            .unwrap();
+        assert!(
+            matches!(date.value, Some(Value::Ascii(ref text)) if text[0] == b"2024:06:01 18:30:00")
+        );
+        assert!(edit(EditableTag::DateTimeOriginal, "yesterday")
+            .field_edit(false)
+            .is_err());
+        assert!(edit(EditableTag::Orientation, "9")
+            .field_edit(false)
+            .is_err());
+
+        let comment = edit(EditableTag::UserComment, "Café")
+            .field_edit(true)
+            .unwrap();
+        let Some(Value::Undefined(bytes, _)) = comment.value else {
+            panic!("UserComment should be undefined bytes");
+        };
+        assert_eq!(&bytes[..8], b"UNICODE\0");
+        assert_eq!(&bytes[8..10], &[b'C', 0]);
+    }
+
+    #[test]
+    fn copies_take_the_edits_and_leave_the_original() {
+        let dir = crate::fixtures::temp_path("tag_edit");
+        fs::create_dir_all(&dir).unwrap();
+        let photo = dir.join("photo.jpg");
+        let copy = dir.join("photo-edited.jpg");
+        let original = jpeg_from_fields(&[ascii_field(Tag::Artist, "Ada")]);
+        fs::write(&photo, &original).unwrap();
+        let log = OperationLog::open(dir.join("operations.jsonl"));
+        let external = VirtualFieldStore::open(dir.join("virtual-fields.json"));
+        let targets = EditTargets {
+            log: &log,
+            external: &external,
+            mode: EditMode::ExternalOnly,
+        };
+
+        let edits = [
+            edit(EditableTag::Copyright, "CC-BY"),
+            TagEdit {
+                tag: EditableTag::Artist,
+                value: None,
+            },
+        ];
+        let result = write_file(&photo, &edits, Some(&copy), &targets);
+        let written = crate::read_exif_container(&fs::read(&copy).unwrap()).unwrap();
+        let untouched = fs::read(&photo).unwrap();
+        let again = write_file(&photo, &edits, Some(&copy), &targets);
+        fs::remove_dir_all(&dir).ok();
+
+        assert!(result.written && result.verification.unwrap().verified);
+        assert!(written.get_field(Tag::Artist, In::PRIMARY).is_none());
+        assert!(written.get_field(Tag::Copyright, In::PRIMARY).is_some());
+        assert_eq!(untouched, original);
+        assert!(again.error.unwrap().contains("already exists"));
+    }
+}
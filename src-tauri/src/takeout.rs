@@ -0,0 +1,434 @@
+use crate::{
+    commit::{commit_edits, commit_xmp, Committed, EditTargets},
+    exif_write::FieldEdit,
+    geotag::{self, TrackPoint},
+    gps::GpsReading,
+    jpeg,
+    oplog::FieldChange,
+    settings::EditMode,
+    verify::WriteVerification,
+    write_lock::{FileStamp, WriteError, WriteProblemReport},
+    xmp, ExifField,
+};
+use chrono::DateTime;
+use exif::{Exif, In, Tag};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+pub const COMPUTED_TAKEOUT_IFD: &str = "Takeout";
+const PEOPLE_PROPERTY: &str = "Iptc4xmpExt:PersonInImage";
+/// Takeout cuts the name of a metadata file before `.json` to this many
+/// characters.
+const NAME_LIMIT: usize = 46;
+const SUPPLEMENTAL: &str = ".supplemental-metadata";
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+struct Timestamp {
+    /// Seconds since the epoch, as a string.
+    timestamp: String,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(default)]
+struct GeoData {
+    latitude: f64,
+    longitude: f64,
+    altitude: f64,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+struct Person {
+    name: String,
+}
+
+/// What Google Photos kept about an image in its Takeout `.json` file.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TakeoutMetadata {
+    title: Option<String>,
+    description: Option<String>,
+    photo_taken_time: Option<Timestamp>,
+    /// The position shown in Google Photos, which may have been edited there.
+    geo_data: Option<GeoData>,
+    /// The position Google read from the file on upload.
+    geo_data_exif: Option<GeoData>,
+    people: Vec<Person>,
+    favorited: Option<bool>,
+}
+
+impl TakeoutMetadata {
+    pub fn description(&self) -> Option<&str> {
+        self.description
+            .as_deref()
+            .map(str::trim)
+            .filter(|text| !text.is_empty())
+    }
+
+    /// The capture time in seconds since the epoch (UTC).
+    pub fn taken(&self) -> Option<i64> {
+        self.photo_taken_time.as_ref()?.timestamp.parse().ok()
+    }
+
+    /// Takeout writes zeros when there is no position.
+    pub fn position(&self) -> Option<(f64, f64, Option<f64>)> {
+        [self.geo_data, self.geo_data_exif]
+            .into_iter()
+            .flatten()
+            .find(|geo| geo.latitude != 0.0 || geo.longitude != 0.0)
+            .map(|geo| {
+                let altitude = (geo.altitude != 0.0).then_some(geo.altitude);
+                (geo.latitude, geo.longitude, altitude)
+            })
+    }
+
+    pub fn people(&self) -> Vec<&str> {
+        self.people
+            .iter()
+            .map(|person| person.name.trim())
+            .filter(|name| !name.is_empty())
+            .collect()
+    }
+}
+
+fn takeout_time(seconds: i64, format: &str) -> Option<String> {
+    Some(
+        DateTime::from_timestamp(seconds, 0)?
+            .format(format)
+            .to_string(),
+    )
+}
+
+fn metadata_names(name: &str) -> Vec<String> {
+    let truncated = |stem: &str| {
+        let mut end = stem.len().min(NAME_LIMIT);
+        while !stem.is_char_boundary(end) {
+            end -= 1;
+        }
+        format!("{}.json", &stem[..end])
+    };
+    let mut names = Vec::new();
+    let mut add = |base: &str, copy: &str| {
+        for stem in [base.to_string(), format!("{}{}", base, SUPPLEMENTAL)] {
+            let stem = format!("{}{}", stem, copy);
+            for name in [format!("{}.json", stem), truncated(&stem)] {
+                if !names.contains(&name) {
+                    names.push(name);
+                }
+            }
+        }
+    };
+    add(name, "");
+    let (stem, extension) = name.rsplit_once('.').unwrap_or((name, ""));
+    // `IMG_1(1).jpg` is described by `IMG_1.jpg(1).json`.
+    if let Some(open) = stem.rfind('(').filter(|_| stem.ends_with(')')) {
+        let copy = &stem[open..];
+        if copy[1..copy.len() - 1].chars().all(|c| c.is_ascii_digit()) {
+            add(&format!("{}.{}", &stem[..open], extension), copy);
+        }
+    }
+    // Edited copies share the metadata of the original.
+    if let Some(original) = stem.strip_suffix("-edited") {
+        add(&format!("{}.{}", original, extension), "");
+    }
+    names
+}
+
+/// The Takeout metadata file for `image`, from the names Takeout gives it:
+/// `<name>.json` or `<name>.supplemental-metadata.json`, cut short for long
+/// names, with a copy number moved to the end.
+pub fn find_json(image: &Path) -> Option<PathBuf> {
+    let name = image.file_name()?.to_str()?;
+    metadata_names(name)
+        .into_iter()
+        .map(|candidate| image.with_file_name(candidate))
+        .find(|candidate| candidate.is_file())
+}
+
+pub fn read(image: &Path) -> Option<TakeoutMetadata> {
+    let text = fs::read_to_string(find_json(image)?).ok()?;
+    serde_json::from_str(&text).ok()
+}
+
+pub fn computed_fields(metadata: Option<&TakeoutMetadata>) -> Vec<ExifField> {
+    let Some(metadata) = metadata else {
+        return Vec::new();
+    };
+    let mut fields = Vec::new();
+    let mut push = |tag: &str, value: String| {
+        fields.push(ExifField {
+            tag: tag.to_string(),
+            ifd: COMPUTED_TAKEOUT_IFD.to_string(),
+            value,
+        });
+    };
+    if let Some(title) = metadata.title.as_deref().filter(|title| !title.is_empty()) {
+        push("Title", title.to_string());
+    }
+    if let Some(description) = metadata.description() {
+        push("Description", description.to_string());
+    }
+    if let Some(taken) = metadata
+        .taken()
+        .and_then(|seconds| takeout_time(seconds, "%Y-%m-%d %H:%M:%S UTC"))
+    {
+        push("PhotoTakenTime", taken);
+    }
+    if let Some((latitude, longitude, altitude)) = metadata.position() {
+        push("Position", format!("{:.6}, {:.6}", latitude, longitude));
+        if let Some(altitude) = altitude {
+            push("Altitude", format!("{:.1} m", altitude));
+        }
+    }
+    let people = metadata.people();
+    if !people.is_empty() {
+        push("People", people.join(", "));
+    }
+    if metadata.favorited == Some(true) {
+        push("Favorited", "Yes".to_string());
+    }
+    fields
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TakeoutImport {
+    path: String,
+    json: String,
+    /// What the file gains (or, on a dry run, would gain): EXIF tag names
+    /// and the XMP people property.
+    restored: Vec<String>,
+    /// Values the file already had and kept, since `overwrite` was not set.
+    kept: Vec<String>,
+    written: bool,
+    /// The EXIF change was stored in the app because originals are protected.
+    stored_externally: bool,
+    error: Option<String>,
+    problem: Option<WriteProblemReport>,
+    /// The re-read of the last write, or of the first one that failed it.
+    verification: Option<WriteVerification>,
+}
+
+fn has_text(exif: Option<&Exif>, tag: Tag) -> bool {
+    exif.and_then(|exif| exif.get_field(tag, In::PRIMARY))
+        .is_some_and(|field| {
+            !field
+                .display_value()
+                .to_string()
+                .trim_matches('"')
+                .trim()
+                .is_empty()
+        })
+}
+
+/// The EXIF edits restoring `metadata`, each group only where the file has
+/// no value of its own unless `overwrite` is set, and the names of the
+/// groups that were kept.
+fn exif_edits(
+    metadata: &TakeoutMetadata,
+    exif: Option<&Exif>,
+    overwrite: bool,
+) -> (Vec<FieldEdit>, Vec<String>) {
+    let mut edits = Vec::new();
+    let mut kept = Vec::new();
+    if let Some(description) = metadata.description() {
+        if overwrite || !has_text(exif, Tag::ImageDescription) {
+            edits.push(FieldEdit::ascii(Tag::ImageDescription, description));
+        } else {
+            kept.push(Tag::ImageDescription.to_string());
+        }
+    }
+    if let Some(taken) = metadata
+        .taken()
+        .and_then(|seconds| takeout_time(seconds, "%Y:%m:%d %H:%M:%S"))
+    {
+        if overwrite || !has_text(exif, Tag::DateTimeOriginal) {
+            // Takeout only has UTC, so the offset says so.
+            edits.push(FieldEdit::ascii(Tag::DateTimeOriginal, &taken));
+            edits.push(FieldEdit::ascii(Tag::OffsetTimeOriginal, "+00:00"));
+        } else {
+            kept.push(Tag::DateTimeOriginal.to_string());
+        }
+    }
+    if let Some((latitude, longitude, elevation)) = metadata.position() {
+        let located = exif.is_some_and(|exif| GpsReading::from_exif(exif).position().is_some());
+        if overwrite || !located {
+            edits.extend(geotag::exif_edits(&TrackPoint {
+                time: Default::default(),
+                latitude,
+                longitude,
+                elevation,
+            }));
+        } else {
+            kept.push(Tag::GPSLatitude.to_string());
+        }
+    }
+    (edits, kept)
+}
+
+/// Adds the people to the XMP of a JPEG, keeping any already listed.
+fn write_people(
+    path: &Path,
+    people: &[&str],
+    targets: &EditTargets,
+) -> Result<WriteVerification, WriteError> {
+    let stamp = FileStamp::of(path)?;
+    let data = crate::load_file_data(path)?;
+    let packet = xmp::jpeg_packet(&data);
+    let existing: Vec<&str> = packet
+        .map(|packet| xmp::items(packet, PEOPLE_PROPERTY))
+        .unwrap_or_default();
+    let mut merged = existing.clone();
+    merged.extend(people.iter().filter(|person| !existing.contains(person)));
+    let change = FieldChange {
+        tag: PEOPLE_PROPERTY.to_string(),
+        before: (!existing.is_empty()).then(|| existing.join(", ")),
+        after: Some(merged.join(", ")),
+    };
+    let packet = xmp::with_bag(packet, PEOPLE_PROPERTY, &merged)?;
+    commit_xmp(
+        targets,
+        "import_takeout",
+        path,
+        stamp,
+        &data,
+        &packet,
+        vec![change],
+    )
+}
+
+/// Writes the description, capture time and position from the Takeout
+/// metadata of `path` back into its EXIF, and the people into its XMP.
+/// People are only written into JPEG files, and not in
+/// [`EditMode::ExternalOnly`], where they stay readable from the `.json`.
+pub fn restore_file(
+    path: &Path,
+    overwrite: bool,
+    dry_run: bool,
+    targets: &EditTargets,
+) -> Option<TakeoutImport> {
+    let json = find_json(path)?;
+    let mut import = TakeoutImport {
+        path: path.to_string_lossy().into_owned(),
+        json: json.to_string_lossy().into_owned(),
+        restored: Vec::new(),
+        kept: Vec::new(),
+        written: false,
+        stored_externally: false,
+        error: None,
+        problem: None,
+        verification: None,
+    };
+
+    let outcome = (|| -> Result<(), WriteError> {
+        let text = crate::load_file_data(&json)?;
+        let metadata: TakeoutMetadata = serde_json::from_slice(&text)
+            .map_err(|error| format!("The Takeout metadata is not valid JSON: {}", error))?;
+        let stamp = FileStamp::of(path)?;
+        let data = crate::load_file_data(path)?;
+        let exif = crate::read_exif_container(&data);
+        let (edits, kept) = exif_edits(&metadata, exif.as_ref(), overwrite);
+        let people = metadata.people();
+        let people_go_in =
+            !people.is_empty() && jpeg::is_jpeg(&data) && targets.mode == EditMode::InPlace;
+        import.kept = kept;
+        import.restored = edits.iter().map(|edit| edit.tag.to_string()).collect();
+        if people_go_in {
+            import.restored.push(PEOPLE_PROPERTY.to_string());
+        }
+        if dry_run || import.restored.is_empty() {
+            return Ok(());
+        }
+        if !edits.is_empty() {
+            match commit_edits(
+                targets,
+                "import_takeout",
+                path,
+                stamp,
+                &data,
+                exif.as_ref(),
+                &edits,
+            )? {
+                Committed::InPlace(verification) => {
+                    import.written = true;
+                    import.verification = Some(verification);
+                }
+                Committed::External => import.stored_externally = true,
+            }
+        }
+        if people_go_in {
+            let people = write_people(path, &people, targets)?;
+            // A failed check of the EXIF write is not hidden by the XMP one.
+            if import
+                .verification
+                .as_ref()
+                .is_none_or(|exif| exif.verified)
+            {
+                import.verification = Some(people);
+            }
+            import.written = true;
+        }
+        Ok(())
+    })();
+
+    if let Err(error) = outcome {
+        import.error = Some(error.message());
+        import.problem = error.report();
+    }
+    Some(import)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{ascii_field, exif_from_fields};
+
+    const TAKEOUT: &str = r#"{
+        "title": "IMG_0042.jpg",
+        "description": "Lisbon trams ",
+        "photoTakenTime": { "timestamp": "1717266600", "formatted": "1 Jun 2024, 18:30:00 UTC" },
+        "geoData": { "latitude": 0.0, "longitude": 0.0, "altitude": 0.0 },
+        "geoDataExif": { "latitude": 38.7139, "longitude": -9.1334, "altitude": 12.0 },
+        "people": [{ "name": "Ana Ruiz" }],
+        "url": "https://photos.google.com/photo/abc"
+    }"#;
+
+    #[test]
+    fn metadata_files_are_found_under_takeout_names() {
+        let names = metadata_names("IMG_0042(1).jpg");
+        assert!(names.contains(&"IMG_0042(1).jpg.json".to_string()));
+        assert!(names.contains(&"IMG_0042.jpg(1).json".to_string()));
+        assert!(names.contains(&"IMG_0042.jpg.supplemental-metadata(1).json".to_string()));
+
+        let long = metadata_names("PXL_20240601_183000123.jpg");
+        assert!(long.contains(&"PXL_20240601_183000123.jpg.supplemental-metada.json".to_string()));
+        assert!(metadata_names("IMG_7-edited.jpg").contains(&"IMG_7.jpg.json".to_string()));
+    }
+
+    #[test]
+    fn takeout_values_fill_only_what_the_file_lacks() {
+        let metadata: TakeoutMetadata = serde_json::from_str(TAKEOUT).unwrap();
+        let fields = computed_fields(Some(&metadata));
+        let value = |tag: &str| {
+            fields
+                .iter()
+                .find(|field| field.tag == tag)
+                .map(|field| field.value.as_str())
+        };
+        assert_eq!(value("Description"), Some("Lisbon trams"));
+        assert_eq!(value("PhotoTakenTime"), Some("2024-06-01 18:30:00 UTC"));
+        assert_eq!(value("Position"), Some("38.713900, -9.133400"));
+        assert_eq!(value("People"), Some("Ana Ruiz"));
+
+        let exif = exif_from_fields(&[ascii_field(Tag::DateTimeOriginal, "2024:06:01 19:30:00")]);
+        let (edits, kept) = exif_edits(&metadata, Some(&exif), false);
+        assert_eq!(kept, ["DateTimeOriginal"]);
+        assert!(edits.iter().any(|edit| edit.tag == Tag::ImageDescription));
+        assert!(edits.iter().any(|edit| edit.tag == Tag::GPSLatitude));
+        assert!(!edits.iter().any(|edit| edit.tag == Tag::DateTimeOriginal));
+    }
+}
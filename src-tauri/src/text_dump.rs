@@ -0,0 +1,197 @@
+use crate::{field_table::plain_value, report::group_title, ExifField};
+use serde::Deserialize;
+
+const WRAP_WIDTH: usize = 72;
+const STANDARD_VALUE_LIMIT: usize = 200;
+/// `(tag, label)` pairs shown first, so the gist is heard before the detail.
+const SUMMARY_TAGS: &[(&str, &str)] = &[
+    ("Make", "Camera make"),
+    ("Model", "Camera model"),
+    ("LensModel", "Lens"),
+    ("DateTimeOriginal", "Taken"),
+    ("ExposureTime", "Exposure time"),
+    ("FNumber", "Aperture"),
+    ("PhotographicSensitivity", "ISO"),
+    ("FocalLength", "Focal length"),
+    ("Position", "Location"),
+    ("Artist", "Artist"),
+    ("Copyright", "Copyright"),
+];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Verbosity {
+    /// Only the summary lines.
+    Summary,
+    /// Summary plus every group except the thumbnail, with long values cut short.
+    #[default]
+    Standard,
+    /// Everything, untruncated.
+    Full,
+}
+
+/// Group titles as shown in the dump (`Image`, `GPS (computed)`, ...),
+/// matched case-insensitively. An empty `include` means all groups.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct GroupFilter {
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+impl GroupFilter {
+    fn allows(&self, group: &str) -> bool {
+        let listed = |names: &[String]| names.iter().any(|name| name.eq_ignore_ascii_case(group));
+        (self.include.is_empty() || listed(&self.include)) && !listed(&self.exclude)
+    }
+}
+
+/// Renders metadata as plain lines of `Label: value`, one group after another
+/// with a blank line between them: no tables or box drawing, so it pastes
+/// cleanly into mail and reads linearly in a screen reader.
+pub fn dump_text(
+    file_name: &str,
+    fields: &[ExifField],
+    verbosity: Verbosity,
+    filter: &GroupFilter,
+) -> String {
+    let mut out = String::new();
+    push_line(&mut out, "", &format!("File: {}", file_name));
+
+    let summary: Vec<(&str, String)> = SUMMARY_TAGS
+        .iter()
+        .filter_map(|(tag, label)| {
+            let field = fields.iter().find(|field| field.tag == *tag)?;
+            Some((*label, plain_value(&field.value)))
+        })
+        .collect();
+    if !summary.is_empty() && filter.allows("Summary") {
+        out.push_str("\nSummary\n");
+        for (label, value) in summary {
+            push_line(&mut out, "  ", &format!("{}: {}", label, value));
+        }
+    }
+    if verbosity == Verbosity::Summary {
+        return out;
+    }
+
+    let mut groups: Vec<(&str, Vec<&ExifField>)> = Vec::new();
+    for field in fields {
+        let group = group_title(&field.ifd);
+        if verbosity == Verbosity::Standard && group == "Thumbnail" {
+            continue;
+        }
+        if !filter.allows(group) {
+            continue;
+        }
+        match groups.iter_mut().find(|(title, _)| *title == group) {
+            Some((_, members)) => members.push(field),
+            None => groups.push((group, vec![field])),
+        }
+    }
+    for (group, members) in groups {
+        out.push_str(&format!("\n{}\n", group));
+        for field in members {
+            let mut value = plain_value(&field.value);
+            if verbosity == Verbosity::Standard && value.chars().count() > STANDARD_VALUE_LIMIT {
+                value = value.chars().take(STANDARD_VALUE_LIMIT).collect();
+                value.push_str(" (truncated)");
+            }
+            push_line(&mut out, "  ", &format!("{}: {}", field.tag, value));
+        }
+    }
+    out
+}
+
+/// Appends `text` word-wrapped at [`WRAP_WIDTH`]; continuation lines are
+/// indented two further spaces. Words longer than a line are kept whole.
+fn push_line(out: &mut String, indent: &str, text: &str) {
+    let continuation = format!("{}  ", indent);
+    let mut line = indent.to_string();
+    let mut line_has_words = false;
+    for word in text.split_whitespace() {
+        if line_has_words && line.chars().count() + 1 + word.chars().count() > WRAP_WIDTH {
+            out.push_str(&line);
+            out.push('\n');
+            line = continuation.clone();
+            line_has_words = false;
+        }
+        if line_has_words {
+            line.push(' ');
+        }
+        line.push_str(word);
+        line_has_words = true;
+    }
+    out.push_str(&line);
+    out.push('\n');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(ifd: &str, tag: &str, value: &str) -> ExifField {
+        ExifField {
+            tag: tag.to_string(),
+            ifd: ifd.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    fn fixture() -> Vec<ExifField> {
+        vec![
+            field("In(0)", "Make", "\"Canon\""),
+            field("In(0)", "ImageDescription", &"word ".repeat(60)),
+            field("In(1)", "Compression", "JPEG"),
+            field("GPS (computed)", "Position", "48.858400, 2.294500"),
+        ]
+    }
+
+    #[test]
+    fn summary_comes_first_and_values_lose_their_quotes() {
+        let text = dump_text(
+            "IMG_0001.jpg",
+            &fixture(),
+            Verbosity::Summary,
+            &GroupFilter::default(),
+        );
+        assert_eq!(
+            text,
+            "File: IMG_0001.jpg\n\nSummary\n  Camera make: Canon\n  Location: 48.858400, 2.294500\n"
+        );
+    }
+
+    #[test]
+    fn standard_wraps_long_values_and_skips_the_thumbnail_group() {
+        let text = dump_text(
+            "IMG_0001.jpg",
+            &fixture(),
+            Verbosity::Standard,
+            &GroupFilter {
+                include: Vec::new(),
+                exclude: vec!["gps (computed)".to_string()],
+            },
+        );
+        assert!(text.contains("\nImage\n  Make: Canon\n  ImageDescription: word"));
+        assert!(!text.contains("Thumbnail"));
+        assert!(!text.contains("GPS (computed)"));
+        assert!(text.lines().all(|line| line.chars().count() <= WRAP_WIDTH));
+        assert!(text.lines().any(|line| line.starts_with("    word")));
+    }
+
+    #[test]
+    fn full_keeps_every_group() {
+        let text = dump_text(
+            "IMG_0001.jpg",
+            &fixture(),
+            Verbosity::Full,
+            &GroupFilter {
+                include: vec!["thumbnail".to_string()],
+                exclude: Vec::new(),
+            },
+        );
+        assert!(text.contains("\nThumbnail\n  Compression: JPEG\n"));
+        assert!(!text.contains("\nImage\n"));
+        assert!(!text.contains("Summary"));
+    }
+}
@@ -0,0 +1,168 @@
+use crate::{
+    exif_write,
+    heif::{self, HeifCodec},
+    jpeg, jpeg_codec, read_exif_container,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use exif::{Exif, In, Tag};
+use serde::Serialize;
+
+/// Long side of a rebuilt thumbnail when none is asked for; 160×120 is what
+/// the EXIF spec recommends for 4:3 images.
+pub const DEFAULT_SIZE: u32 = 160;
+pub const DEFAULT_QUALITY: u8 = 80;
+/// Keeps a thumbnail comfortably inside the 64 KiB APP1 segment.
+const MAX_SIZE: u32 = 512;
+
+/// Outcome of [`rebuild`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ThumbnailRebuild {
+    pub width: usize,
+    pub height: usize,
+    pub bytes: usize,
+    /// Size of the thumbnail that was replaced, if there was a readable one.
+    pub previous_bytes: Option<usize>,
+}
+
+/// Where an extracted thumbnail came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ThumbnailSource {
+    /// The JPEG in EXIF IFD1.
+    Exif,
+    /// The thumbnail item of a HEIC or AVIF primary image.
+    HeifItem,
+}
+
+/// An embedded preview, for showing scan results without decoding the
+/// full image.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExtractedThumbnail {
+    /// Base64 of the image file.
+    pub data: String,
+    pub mime_type: String,
+    pub width: u32,
+    pub height: u32,
+    pub source: ThumbnailSource,
+}
+
+/// Returns the JPEG thumbnail referenced by IFD1, if it lies inside the EXIF blob.
+pub fn embedded_thumbnail(exif: &Exif) -> Option<&[u8]> {
+    let offset = exif
+        .get_field(Tag::JPEGInterchangeFormat, In::THUMBNAIL)?
+        .value
+        .get_uint(0)? as usize;
+    let length = exif
+        .get_field(Tag::JPEGInterchangeFormatLength, In::THUMBNAIL)?
+        .value
+        .get_uint(0)? as usize;
+    let end = offset.checked_add(length)?;
+    let thumbnail = exif.buf().get(offset..end)?;
+    thumbnail.starts_with(&[0xFF, 0xD8]).then_some(thumbnail)
+}
+
+/// The thumbnail embedded in `data`: the EXIF one, or for HEIF files
+/// without one the primary image's thumbnail item.
+pub fn extract(data: &[u8]) -> Result<ExtractedThumbnail, String> {
+    let exif = read_exif_container(data);
+    let from_exif = exif.as_ref().and_then(|exif| {
+        let thumbnail = embedded_thumbnail(exif)?;
+        Some((thumbnail, jpeg::frame_size(thumbnail)?))
+    });
+    if let Some((thumbnail, (width, height))) = from_exif {
+        return Ok(ExtractedThumbnail {
+            data: STANDARD.encode(thumbnail),
+            mime_type: "image/jpeg".to_string(),
+            width,
+            height,
+            source: ThumbnailSource::Exif,
+        });
+    }
+    let item = heif::thumbnail(data)
+        .ok_or_else(|| "The selected file has no embedded thumbnail.".to_string())?;
+    let mime_type = match item.codec {
+        HeifCodec::Hevc => "image/heic",
+        HeifCodec::Av1 => "image/avif",
+        _ => "image/jpeg",
+    };
+    Ok(ExtractedThumbnail {
+        data: STANDARD.encode(&item.file),
+        mime_type: mime_type.to_string(),
+        width: item.width,
+        height: item.height,
+        source: ThumbnailSource::HeifItem,
+    })
+}
+
+/// Renders a fresh thumbnail of the JPEG `data`, at most `size` pixels on
+/// its long side, and writes it into IFD1 in place of the old one.
+pub fn rebuild(data: &[u8], size: u32, quality: u8) -> Result<(Vec<u8>, ThumbnailRebuild), String> {
+    if !(16..=MAX_SIZE).contains(&size) {
+        return Err(format!(
+            "Thumbnails can be between 16 and {} pixels on their long side.",
+            MAX_SIZE
+        ));
+    }
+    let previous_bytes =
+        read_exif_container(data).and_then(|exif| embedded_thumbnail(&exif).map(<[u8]>::len));
+    let image = jpeg_codec::decode_dc(data)?.fit(size as usize);
+    let thumbnail = jpeg_codec::encode(&image, quality);
+    let updated = exif_write::replace_jpeg_thumbnail(data, &thumbnail)?;
+    Ok((
+        updated,
+        ThumbnailRebuild {
+            width: image.width,
+            height: image.height,
+            bytes: thumbnail.len(),
+            previous_bytes,
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jpeg_codec::Raster;
+
+    #[test]
+    fn rebuilt_thumbnails_replace_the_old_one_in_ifd1() {
+        let photo = Raster {
+            width: 320,
+            height: 240,
+            pixels: (0..320 * 240)
+                .flat_map(|at| [(at % 320 * 255 / 320) as u8, 40, 200])
+                .collect(),
+        };
+        let original = exif_write::apply_jpeg_edits(
+            &jpeg_codec::encode(&photo, 75),
+            &[exif_write::FieldEdit::ascii(Tag::Artist, "Ada")],
+        )
+        .unwrap();
+
+        let (first, report) = rebuild(&original, 40, 80).unwrap();
+        assert_eq!(
+            (report.width, report.height, report.previous_bytes),
+            (40, 30, None)
+        );
+        let exif = read_exif_container(&first).unwrap();
+        let thumbnail = embedded_thumbnail(&exif).expect("thumbnail should be embedded");
+        assert_eq!(thumbnail.len(), report.bytes);
+        assert_eq!(jpeg_codec::decode_dc(thumbnail).unwrap().width, 5);
+        assert!(exif.get_field(Tag::Artist, In::PRIMARY).is_some());
+        assert!(first.ends_with(&original[original.len() - 64..]));
+
+        let (_, again) = rebuild(&first, 64, 60).unwrap();
+        assert_eq!(again.previous_bytes, Some(report.bytes));
+        assert!(rebuild(&first, 4096, 80).is_err());
+
+        let extracted = extract(&first).unwrap();
+        assert_eq!(
+            (extracted.width, extracted.height, extracted.source),
+            (40, 30, ThumbnailSource::Exif)
+        );
+        assert_eq!(STANDARD.decode(extracted.data).unwrap(), thumbnail);
+        assert!(extract(&original).is_err());
+    }
+}
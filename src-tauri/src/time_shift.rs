@@ -0,0 +1,218 @@
+use crate::{
+    capture::{first_ascii, gps_time},
+    commit::{commit_edits, Committed, EditTargets},
+    exif_write::FieldEdit,
+    oplog::{field_changes, FieldChange},
+    verify::WriteVerification,
+    write_lock::{FileStamp, WriteError, WriteProblemReport},
+};
+use chrono::{Duration, NaiveDateTime, Timelike};
+use exif::{Exif, In, Rational, Tag, Value};
+use serde::Serialize;
+use std::path::Path;
+
+const EXIF_DATE_FORMAT: &str = "%Y:%m:%d %H:%M:%S";
+/// DateTimeOriginal, CreateDate and ModifyDate, as `exiftool -AllDates` has them.
+const CAMERA_TIMES: [Tag; 3] = [Tag::DateTimeOriginal, Tag::DateTimeDigitized, Tag::DateTime];
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimestampShift {
+    path: String,
+    /// Each time tag before and after the shift.
+    shifted: Vec<FieldChange>,
+    written: bool,
+    /// The shifted times were stored in the app because originals are protected.
+    stored_externally: bool,
+    error: Option<String>,
+    problem: Option<WriteProblemReport>,
+    verification: Option<WriteVerification>,
+}
+
+/// `second` plus the fraction of `original`, in its denominator when the
+/// total fits a rational and in a coarser one where a fine denominator (such
+/// as nanoseconds) would overflow it.
+fn seconds_with_fraction(second: u32, original: Rational) -> Rational {
+    let mut denom = u64::from(original.denom);
+    let mut fraction = u64::from(original.num) % denom;
+    let total = |denom: u64, fraction: u64| u64::from(second) * denom + fraction;
+    while total(denom, fraction) > u64::from(u32::MAX) {
+        let coarser = denom / 10;
+        fraction = fraction * coarser / denom;
+        denom = coarser;
+    }
+    Rational {
+        num: total(denom, fraction) as u32,
+        denom: denom as u32,
+    }
+}
+
+/// The edits moving every recorded time of `exif` by `delta`. Times left
+/// blank by the camera are skipped; the OffsetTime tags stay as they are,
+/// since the shift corrects the clock and not the zone.
+fn shift_edits(exif: &Exif, delta: Duration) -> Result<Vec<FieldEdit>, String> {
+    let out_of_range = || "The shift moves a date out of the range EXIF can store.".to_string();
+    let mut edits = Vec::new();
+    for tag in CAMERA_TIMES {
+        let Some(text) = first_ascii(exif, tag).map(String::from_utf8_lossy) else {
+            continue;
+        };
+        let Ok(time) = NaiveDateTime::parse_from_str(text.trim(), EXIF_DATE_FORMAT) else {
+            continue;
+        };
+        let shifted = time.checked_add_signed(delta).ok_or_else(out_of_range)?;
+        edits.push(FieldEdit::ascii(
+            tag,
+            &shifted.format(EXIF_DATE_FORMAT).to_string(),
+        ));
+    }
+
+    if let Some(gps) = gps_time(exif) {
+        let shifted = gps
+            .local
+            .checked_add_signed(delta)
+            .ok_or_else(out_of_range)?;
+        // Fractions of a second, which the fix may record, are carried over.
+        let seconds = match exif
+            .get_field(Tag::GPSTimeStamp, In::PRIMARY)
+            .map(|field| &field.value)
+        {
+            Some(Value::Rational(parts)) if parts[2].denom > 1 => {
+                seconds_with_fraction(shifted.second(), parts[2])
+            }
+            _ => Rational::from((shifted.second(), 1)),
+        };
+        edits.push(FieldEdit::ascii(
+            Tag::GPSDateStamp,
+            &shifted.format("%Y:%m:%d").to_string(),
+        ));
+        edits.push(FieldEdit {
+            tag: Tag::GPSTimeStamp,
+            value: Some(Value::Rational(vec![
+                Rational::from((shifted.hour(), 1)),
+                Rational::from((shifted.minute(), 1)),
+                seconds,
+            ])),
+        });
+    }
+    Ok(edits)
+}
+
+/// Moves the capture, digitized, modified and GPS times of the JPEG or TIFF
+/// at `path` by `delta`, for a camera whose clock was set wrong.
+pub fn shift_file(path: &Path, delta: Duration, targets: &EditTargets) -> TimestampShift {
+    let mut shift = TimestampShift {
+        path: path.to_string_lossy().into_owned(),
+        shifted: Vec::new(),
+        written: false,
+        stored_externally: false,
+        error: None,
+        problem: None,
+        verification: None,
+    };
+
+    let result = (|| -> Result<(), WriteError> {
+        let stamp = FileStamp::of(path)?;
+        let data = crate::load_file_data(path)?;
+        let exif = crate::read_exif_container(&data)
+            .ok_or_else(|| "The selected file has no EXIF metadata.".to_string())?;
+        let edits = shift_edits(&exif, delta)?;
+        if edits.is_empty() {
+            return Err("The file records no times to shift.".to_string().into());
+        }
+        shift.shifted = field_changes(Some(&exif), &edits);
+        match commit_edits(
+            targets,
+            "shift_timestamps",
+            path,
+            stamp,
+            &data,
+            Some(&exif),
+            &edits,
+        )? {
+            Committed::InPlace(verification) => {
+                shift.written = true;
+                shift.verification = Some(verification);
+            }
+            Committed::External => shift.stored_externally = true,
+        }
+        Ok(())
+    })();
+
+    if let Err(error) = result {
+        shift.error = Some(error.message());
+        shift.problem = error.report();
+    }
+    shift
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{ascii_field, exif_from_fields, rational_field};
+
+    fn text(edits: &[FieldEdit], tag: Tag) -> Option<String> {
+        match &edits.iter().find(|edit| edit.tag == tag)?.value {
+            Some(Value::Ascii(values)) => Some(String::from_utf8_lossy(&values[0]).into_owned()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn every_time_moves_across_midnight() {
+        let exif = exif_from_fields(&[
+            ascii_field(Tag::DateTimeOriginal, "2024:06:01 23:30:00"),
+            ascii_field(Tag::DateTimeDigitized, "    :  :     :  :  "),
+            ascii_field(Tag::GPSDateStamp, "2024:06:01"),
+            rational_field(Tag::GPSTimeStamp, &[(21, 1), (30, 1), (1550, 100)]),
+        ]);
+        let edits = shift_edits(&exif, Duration::hours(2)).unwrap();
+
+        assert_eq!(
+            text(&edits, Tag::DateTimeOriginal).as_deref(),
+            Some("2024:06:02 01:30:00")
+        );
+        assert_eq!(text(&edits, Tag::DateTimeDigitized), None);
+        assert_eq!(
+            text(&edits, Tag::GPSDateStamp).as_deref(),
+            Some("2024:06:01")
+        );
+        let Some(Value::Rational(parts)) = &edits
+            .iter()
+            .find(|edit| edit.tag == Tag::GPSTimeStamp)
+            .and_then(|edit| edit.value.clone())
+        else {
+            panic!("GPSTimeStamp should be rationals");
+        };
+        assert_eq!(
+            (parts[0].num, parts[2].num, parts[2].denom),
+            (23, 1550, 100)
+        );
+
+        let back = shift_edits(&exif, Duration::hours(-22)).unwrap();
+        assert_eq!(
+            text(&back, Tag::GPSDateStamp).as_deref(),
+            Some("2024:05:31")
+        );
+    }
+    #[test]
+    fn nanosecond_fractions_do_not_overflow() {
+        let exif = exif_from_fields(&[
+            ascii_field(Tag::GPSDateStamp, "2024:06:01"),
+            rational_field(
+                Tag::GPSTimeStamp,
+                &[(21, 1), (30, 1), (1_250_000_000, 1_000_000_000)],
+            ),
+        ]);
+        let edits = shift_edits(&exif, Duration::seconds(58)).unwrap();
+
+        let Some(Value::Rational(parts)) = &edits
+            .iter()
+            .find(|edit| edit.tag == Tag::GPSTimeStamp)
+            .and_then(|edit| edit.value.clone())
+        else {
+            panic!("GPSTimeStamp should be rationals");
+        };
+        assert_eq!((parts[2].num, parts[2].denom), (592_500_000, 10_000_000));
+    }
+}
@@ -0,0 +1,478 @@
+use crate::{
+    capture::{capture_time, gps_time, original_time},
+    commit::{commit_edits, Committed, EditTargets},
+    exif_write::FieldEdit,
+    gps::GpsReading,
+    verify::WriteVerification,
+    write_lock::{FileStamp, WriteError, WriteProblemReport},
+};
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, Weekday};
+use exif::{Exif, Tag};
+use serde::{Deserialize, Serialize};
+use std::{path::Path, sync::OnceLock};
+
+/// Coarse zone boxes, checked in order so more specific regions come first.
+/// Positions outside every box fall back to the nautical zone for the longitude.
+/// These are not the real zone boundaries (no timezone-boundary-builder
+/// polygons are bundled), and neighbouring boxes overlap, so a box alone can
+/// give the wrong offset. Each region also lists `core` boxes drawn well
+/// inside its borders and the date its current rules took effect; only a
+/// capture in a core box after that date is an exact match, and fixes write
+/// nothing else.
+const TIMEZONE_DATA: &str = include_str!("../data/timezones.json");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum DstRule {
+    None,
+    Eu,
+    Us,
+    Au,
+    Nz,
+}
+
+#[derive(Debug, Deserialize)]
+struct ZoneRegion {
+    name: String,
+    offset: i32,
+    dst: DstRule,
+    boxes: Vec<[f64; 4]>,
+    #[serde(default)]
+    core: Vec<[f64; 4]>,
+    /// First day of the offset and daylight-saving rules bundled here.
+    #[serde(default)]
+    since: Option<String>,
+}
+
+impl ZoneRegion {
+    fn rules_apply(&self, utc: NaiveDateTime) -> bool {
+        self.since.as_deref().is_none_or(|since| {
+            NaiveDate::parse_from_str(since, "%Y-%m-%d").is_ok_and(|since| utc.date() >= since)
+        })
+    }
+}
+
+fn inside(boxes: &[[f64; 4]], latitude: f64, longitude: f64) -> bool {
+    boxes.iter().any(|[min_lat, min_lon, max_lat, max_lon]| {
+        (*min_lat..=*max_lat).contains(&latitude) && (*min_lon..=*max_lon).contains(&longitude)
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ZoneMatch {
+    pub name: String,
+    pub offset_minutes: i32,
+    /// True unless the position lies in a region's core boxes and the
+    /// capture falls under the bundled rules: box-only matches near a border
+    /// and the nautical fallback are guesses.
+    pub approximate: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TimezoneStatus {
+    /// The recorded offset matches the one expected at the GPS position.
+    Consistent,
+    /// An offset is recorded but differs from the expected one.
+    OffsetMismatch,
+    /// No offset is recorded; the expected one can be written.
+    OffsetMissing,
+    /// The camera clock itself disagrees with the GPS fix, so writing an
+    /// offset alone would not fix the capture time.
+    ClockMismatch,
+    /// The file lacks a GPS position or capture time.
+    Unknown,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimezoneCheck {
+    path: String,
+    zone: Option<String>,
+    approximate: bool,
+    expected_offset: Option<String>,
+    recorded_offset: Option<String>,
+    clock_offset: Option<String>,
+    status: TimezoneStatus,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimezoneFix {
+    path: String,
+    offset: Option<String>,
+    written: bool,
+    /// The offset was stored in the app because originals are protected.
+    stored_externally: bool,
+    error: Option<String>,
+    problem: Option<WriteProblemReport>,
+    verification: Option<WriteVerification>,
+}
+
+impl TimezoneFix {
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+}
+
+fn regions() -> &'static [ZoneRegion] {
+    static REGIONS: OnceLock<Vec<ZoneRegion>> = OnceLock::new();
+    REGIONS.get_or_init(|| {
+        serde_json::from_str(TIMEZONE_DATA).expect("bundled timezone data is valid JSON")
+    })
+}
+
+/// Resolves the UTC offset in effect at a position and UTC instant.
+pub fn zone_for(latitude: f64, longitude: f64, utc: NaiveDateTime) -> ZoneMatch {
+    let core = regions()
+        .iter()
+        .find(|region| inside(&region.core, latitude, longitude));
+    let region = core.or_else(|| {
+        regions()
+            .iter()
+            .find(|region| inside(&region.boxes, latitude, longitude))
+    });
+
+    match region {
+        Some(region) => {
+            let daylight = dst_active(region.dst, region.offset, utc);
+            ZoneMatch {
+                name: region.name.clone(),
+                offset_minutes: region.offset + if daylight { 60 } else { 0 },
+                approximate: core.is_none() || !region.rules_apply(utc),
+            }
+        }
+        None => {
+            let hours = (longitude / 15.0).round() as i32;
+            ZoneMatch {
+                name: format!("UTC{}", format_offset(hours * 60)),
+                offset_minutes: hours * 60,
+                approximate: true,
+            }
+        }
+    }
+}
+
+/// GPS position and UTC capture instant, using the recorded UTC time or,
+/// failing that, the camera clock corrected by the zone at the position.
+pub fn located_capture(exif: &Exif) -> Option<((f64, f64), NaiveDateTime)> {
+    let (latitude, longitude) = GpsReading::from_exif(exif).position()?;
+    let captured = capture_time(exif)?;
+    let utc = captured.utc().unwrap_or_else(|| {
+        let zone = zone_for(latitude, longitude, captured.local);
+        captured.local - Duration::minutes(zone.offset_minutes as i64)
+    });
+    Some(((latitude, longitude), utc))
+}
+
+/// Formats minutes east of UTC the way OffsetTime tags store them, e.g. `+05:30`.
+pub fn format_offset(minutes: i32) -> String {
+    let sign = if minutes < 0 { '-' } else { '+' };
+    let minutes = minutes.abs();
+    format!("{}{:02}:{:02}", sign, minutes / 60, minutes % 60)
+}
+
+fn dst_active(rule: DstRule, standard_offset: i32, utc: NaiveDateTime) -> bool {
+    let year = utc.year();
+    let at = |date: Option<NaiveDate>, local_hour: u32, offset: i32| {
+        date.and_then(|date| date.and_hms_opt(local_hour, 0, 0))
+            .map(|local| local - Duration::minutes(offset as i64))
+    };
+    let window = match rule {
+        DstRule::None => return false,
+        DstRule::Eu => (
+            at(last_sunday(year, 3), 1, 0),
+            at(last_sunday(year, 10), 1, 0),
+        ),
+        DstRule::Us => (
+            at(nth_sunday(year, 3, 2), 2, standard_offset),
+            at(nth_sunday(year, 11, 1), 2, standard_offset + 60),
+        ),
+        DstRule::Au => (
+            at(nth_sunday(year, 10, 1), 2, standard_offset),
+            at(nth_sunday(year, 4, 1), 3, standard_offset + 60),
+        ),
+        DstRule::Nz => (
+            at(last_sunday(year, 9), 2, standard_offset),
+            at(nth_sunday(year, 4, 1), 3, standard_offset + 60),
+        ),
+    };
+    let (Some(start), Some(end)) = window else {
+        return false;
+    };
+    if start < end {
+        utc >= start && utc < end
+    } else {
+        // Southern hemisphere: daylight time spans the new year.
+        utc >= start || utc < end
+    }
+}
+
+fn nth_sunday(year: i32, month: u32, n: u8) -> Option<NaiveDate> {
+    NaiveDate::from_weekday_of_month_opt(year, month, Weekday::Sun, n)
+}
+
+fn last_sunday(year: i32, month: u32) -> Option<NaiveDate> {
+    nth_sunday(year, month, 5).or_else(|| nth_sunday(year, month, 4))
+}
+
+/// Compares the camera's recorded offset with the one implied by its GPS position.
+pub fn check_exif(path: &Path, exif: &Exif) -> TimezoneCheck {
+    let mut check = TimezoneCheck {
+        path: path.to_string_lossy().into_owned(),
+        zone: None,
+        approximate: false,
+        expected_offset: None,
+        recorded_offset: None,
+        clock_offset: None,
+        status: TimezoneStatus::Unknown,
+    };
+
+    let camera = original_time(exif);
+    let gps = gps_time(exif);
+    check.recorded_offset = camera
+        .and_then(|time| time.offset_minutes)
+        .map(format_offset);
+
+    let Some((latitude, longitude)) = GpsReading::from_exif(exif).position() else {
+        return check;
+    };
+    let utc = match (gps.and_then(|time| time.utc()), camera) {
+        (Some(utc), _) => utc,
+        (None, Some(camera)) => camera.sort_key(),
+        (None, None) => return check,
+    };
+
+    let zone = zone_for(latitude, longitude, utc);
+    let expected = zone.offset_minutes;
+    check.zone = Some(zone.name);
+    check.approximate = zone.approximate;
+    check.expected_offset = Some(format_offset(expected));
+
+    // The camera clock minus the GPS fix, rounded to the nearest quarter hour.
+    let clock = match (camera, gps.and_then(|time| time.utc())) {
+        (Some(camera), Some(utc)) => {
+            let minutes = (camera.local - utc).num_minutes() as f64;
+            Some(((minutes / 15.0).round() * 15.0) as i32)
+        }
+        _ => None,
+    };
+    check.clock_offset = clock.map(format_offset);
+
+    let recorded = camera.and_then(|time| time.offset_minutes);
+    check.status = match (recorded, clock) {
+        (_, Some(clock)) if clock != expected => TimezoneStatus::ClockMismatch,
+        (Some(recorded), _) if recorded == expected => TimezoneStatus::Consistent,
+        (Some(_), _) => TimezoneStatus::OffsetMismatch,
+        (None, _) if camera.is_some() => TimezoneStatus::OffsetMissing,
+        (None, _) => TimezoneStatus::Unknown,
+    };
+    check
+}
+
+pub fn check_file(path: &Path) -> Result<TimezoneCheck, String> {
+    let data = crate::load_file_data(path)?;
+    let exif = crate::read_exif_container(&data)
+        .ok_or_else(|| "The selected file has no EXIF metadata.".to_string())?;
+    Ok(check_exif(path, &exif))
+}
+
+/// Writes the GPS-derived offset into the OffsetTime tags of a JPEG when the
+/// camera clock is right but the recorded offset is missing or wrong. Only
+/// exact zone matches are fixed.
+pub fn fix_file(path: &Path, dry_run: bool, targets: &EditTargets) -> TimezoneFix {
+    let mut fix = TimezoneFix {
+        path: path.to_string_lossy().into_owned(),
+        offset: None,
+        written: false,
+        stored_externally: false,
+        error: None,
+        problem: None,
+        verification: None,
+    };
+
+    let result = (|| -> Result<(), WriteError> {
+        let stamp = FileStamp::of(path)?;
+        let data = crate::load_file_data(path)?;
+        let exif = crate::read_exif_container(&data)
+            .ok_or_else(|| "The selected file has no EXIF metadata.".to_string())?;
+        let check = check_exif(path, &exif);
+        if check.approximate {
+            return Err(
+                "The bundled time zones cannot place the GPS position exactly, so the \
+                        offset was left for you to set by hand."
+                    .to_string()
+                    .into(),
+            );
+        }
+        if !matches!(
+            check.status,
+            TimezoneStatus::OffsetMissing | TimezoneStatus::OffsetMismatch
+        ) {
+            return Ok(());
+        }
+        let offset = check
+            .expected_offset
+            .expect("fixable checks always carry an expected offset");
+        fix.offset = Some(offset.clone());
+        if dry_run {
+            return Ok(());
+        }
+
+        let edits = [
+            Tag::OffsetTime,
+            Tag::OffsetTimeOriginal,
+            Tag::OffsetTimeDigitized,
+        ]
+        .map(|tag| FieldEdit::ascii(tag, &offset));
+        match commit_edits(
+            targets,
+            "fix_timezones_from_gps",
+            path,
+            stamp,
+            &data,
+            Some(&exif),
+            &edits,
+        )? {
+            Committed::InPlace(verification) => {
+                fix.written = true;
+                fix.verification = Some(verification);
+            }
+            Committed::External => fix.stored_externally = true,
+        }
+        Ok(())
+    })();
+
+    if let Err(error) = result {
+        fix.error = Some(error.message());
+        fix.problem = error.report();
+    }
+    fix
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{ascii_field, exif_from_fields, rational_field};
+
+    fn utc(year: i32, month: u32, day: u32, hour: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(year, month, day)
+            .unwrap()
+            .and_hms_opt(hour, 0, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn zones_follow_daylight_saving_rules() {
+        assert_eq!(
+            zone_for(48.85, 2.35, utc(2024, 7, 1, 12)).offset_minutes,
+            120
+        );
+        assert_eq!(
+            zone_for(48.85, 2.35, utc(2024, 1, 1, 12)).offset_minutes,
+            60
+        );
+        assert_eq!(
+            zone_for(40.71, -74.0, utc(2024, 7, 1, 12)).offset_minutes,
+            -240
+        );
+        assert_eq!(
+            zone_for(-33.87, 151.2, utc(2024, 1, 1, 12)).offset_minutes,
+            660
+        );
+        assert_eq!(
+            zone_for(-33.87, 151.2, utc(2024, 7, 1, 12)).offset_minutes,
+            600
+        );
+
+        let ocean = zone_for(0.0, -150.0, utc(2024, 7, 1, 12));
+        assert!(ocean.approximate);
+        assert_eq!(ocean.name, "UTC-10:00");
+    }
+
+    #[test]
+    fn only_positions_well_inside_a_zone_are_exact() {
+        let july = utc(2024, 7, 1, 12);
+        // Boulogne sits inside both the London and Paris boxes.
+        let boulogne = zone_for(50.72, 1.61, july);
+        assert_eq!(boulogne.name, "Europe/Paris");
+        assert_eq!(boulogne.offset_minutes, 120);
+        assert!(!boulogne.approximate);
+        assert!(!zone_for(39.77, -86.16, july).approximate);
+        assert_eq!(zone_for(39.77, -86.16, july).offset_minutes, -240);
+
+        // Minsk, Algiers, Tunis, Lahore, Dhaka and Almaty only fall in a
+        // neighbour's box.
+        for (latitude, longitude) in [
+            (53.9, 27.56),
+            (36.75, 3.06),
+            (36.8, 10.18),
+            (31.55, 74.34),
+            (23.81, 90.41),
+            (43.24, 76.89),
+        ] {
+            assert!(zone_for(latitude, longitude, july).approximate);
+        }
+        // The bundled Moscow rules only hold from late 2014.
+        assert!(zone_for(55.75, 37.62, utc(2013, 7, 1, 12)).approximate);
+    }
+
+    #[test]
+    fn fixes_leave_guessed_offsets_alone() {
+        let photo = crate::fixtures::temp_path("minsk.jpg");
+        std::fs::write(
+            &photo,
+            crate::fixtures::jpeg_from_fields(&[
+                ascii_field(Tag::DateTimeOriginal, "2024:07:01 14:00:00"),
+                rational_field(Tag::GPSLatitude, &[(53, 1), (54, 1), (0, 1)]),
+                ascii_field(Tag::GPSLatitudeRef, "N"),
+                rational_field(Tag::GPSLongitude, &[(27, 1), (34, 1), (0, 1)]),
+                ascii_field(Tag::GPSLongitudeRef, "E"),
+                ascii_field(Tag::GPSDateStamp, "2024:07:01"),
+                rational_field(Tag::GPSTimeStamp, &[(11, 1), (0, 1), (0, 1)]),
+            ]),
+        )
+        .unwrap();
+        let log_path = crate::fixtures::temp_path("operations.jsonl");
+        let store_path = crate::fixtures::temp_path("virtual-fields.json");
+        let log = crate::oplog::OperationLog::open(log_path.clone());
+        let external = crate::virtual_fields::VirtualFieldStore::open(store_path.clone());
+        let targets = EditTargets {
+            log: &log,
+            external: &external,
+            mode: crate::settings::EditMode::InPlace,
+        };
+
+        let fix = fix_file(&photo, false, &targets);
+        let unchanged = crate::read_exif_container(&std::fs::read(&photo).unwrap())
+            .unwrap()
+            .get_field(Tag::OffsetTimeOriginal, exif::In::PRIMARY)
+            .is_none();
+        for path in [&photo, &log_path, &store_path] {
+            std::fs::remove_file(path).ok();
+        }
+
+        assert!(!fix.written);
+        assert!(fix.error().is_some());
+        assert!(unchanged);
+    }
+
+    #[test]
+    fn wrong_offset_with_correct_clock_is_a_mismatch() {
+        let exif = exif_from_fields(&[
+            ascii_field(Tag::DateTimeOriginal, "2024:07:01 14:00:00"),
+            ascii_field(Tag::OffsetTimeOriginal, "+00:00"),
+            rational_field(Tag::GPSLatitude, &[(48, 1), (51, 1), (0, 1)]),
+            ascii_field(Tag::GPSLatitudeRef, "N"),
+            rational_field(Tag::GPSLongitude, &[(2, 1), (21, 1), (0, 1)]),
+            ascii_field(Tag::GPSLongitudeRef, "E"),
+            ascii_field(Tag::GPSDateStamp, "2024:07:01"),
+            rational_field(Tag::GPSTimeStamp, &[(12, 1), (0, 1), (0, 1)]),
+        ]);
+
+        let check = check_exif(Path::new("paris.jpg"), &exif);
+        assert_eq!(check.status, TimezoneStatus::OffsetMismatch);
+        assert_eq!(check.expected_offset.as_deref(), Some("+02:00"));
+        assert_eq!(check.clock_offset.as_deref(), Some("+02:00"));
+    }
+}
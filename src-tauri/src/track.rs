@@ -0,0 +1,250 @@
+use crate::gps::{collect_gps_points, GpsPoint};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+use std::{fmt::Write, fs, path::Path};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TrackFormat {
+    Gpx,
+    Kml,
+}
+
+impl TrackFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            TrackFormat::Gpx => "gpx",
+            TrackFormat::Kml => "kml",
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrackExport {
+    path: String,
+    points: usize,
+    skipped: usize,
+}
+
+/// Writes the geotagged photos under `root`, ordered by capture time, as a track.
+///
+/// Photos without a capture time cannot be placed on the track and are
+/// reported as skipped.
+pub fn export_track(
+    root: &Path,
+    format: TrackFormat,
+    output: &Path,
+) -> Result<TrackExport, String> {
+    write_track(
+        collect_gps_points(root, format == TrackFormat::Kml),
+        format,
+        output,
+    )
+}
+
+/// Writes the timed points of `collected` as a track, skipping untimed ones.
+pub fn write_track(
+    collected: Vec<GpsPoint>,
+    format: TrackFormat,
+    output: &Path,
+) -> Result<TrackExport, String> {
+    let total = collected.len();
+    let mut points: Vec<GpsPoint> = collected
+        .into_iter()
+        .filter(|point| point.captured.is_some())
+        .collect();
+    if points.is_empty() {
+        return Err("No geotagged photos with a capture time were found.".to_string());
+    }
+    points.sort_by_key(|point| point.captured.map(|time| time.sort_key()));
+
+    let document = match format {
+        TrackFormat::Gpx => render_gpx(&points),
+        TrackFormat::Kml => render_kml(&points),
+    };
+    fs::write(output, document)
+        .map_err(|error| format!("Unable to write the track file: {}", error))?;
+
+    Ok(TrackExport {
+        path: output.to_string_lossy().into_owned(),
+        points: points.len(),
+        skipped: total - points.len(),
+    })
+}
+
+fn render_gpx(points: &[GpsPoint]) -> String {
+    let mut waypoints = String::new();
+    let mut segment = String::new();
+    for point in points {
+        let (latitude, longitude) = point.position();
+        let mut body = String::new();
+        if let Some(altitude) = point.reading.altitude {
+            let _ = write!(body, "<ele>{:.1}</ele>", altitude);
+        }
+        if let Some(time) = point.captured {
+            let _ = write!(body, "<time>{}</time>", time.to_iso8601());
+        }
+        let _ = writeln!(
+            waypoints,
+            "  <wpt lat=\"{:.7}\" lon=\"{:.7}\">{}<name>{}</name></wpt>",
+            latitude,
+            longitude,
+            body,
+            escape_xml(&file_name(point))
+        );
+        let _ = writeln!(
+            segment,
+            "      <trkpt lat=\"{:.7}\" lon=\"{:.7}\">{}</trkpt>",
+            latitude, longitude, body
+        );
+    }
+
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <gpx version=\"1.1\" creator=\"Exif Viewer\" xmlns=\"http://www.topografix.com/GPX/1/1\">\n\
+         {}  <trk>\n    <name>Photo track</name>\n    <trkseg>\n{}    </trkseg>\n  </trk>\n</gpx>\n",
+        waypoints, segment
+    )
+}
+
+/// `lon,lat[,alt]` and the altitude mode that goes with it: a position
+/// without a recorded altitude is clamped to the ground rather than placed
+/// at sea level.
+fn kml_coordinates(longitude: f64, latitude: f64, altitude: Option<f64>) -> (String, &'static str) {
+    match altitude {
+        Some(altitude) => (
+            format!("{:.7},{:.7},{:.1}", longitude, latitude, altitude),
+            "absolute",
+        ),
+        None => (format!("{:.7},{:.7}", longitude, latitude), "clampToGround"),
+    }
+}
+
+fn render_kml(points: &[GpsPoint]) -> String {
+    // One altitude mode covers the whole line, so it only climbs when every
+    // photo has an altitude.
+    let line_altitudes = points.iter().all(|point| point.reading.altitude.is_some());
+    let line_mode = if line_altitudes {
+        "absolute"
+    } else {
+        "clampToGround"
+    };
+    let mut placemarks = String::new();
+    let mut coordinates = Vec::with_capacity(points.len());
+    for point in points {
+        let (latitude, longitude) = point.position();
+        let altitude = point.reading.altitude;
+        let line_altitude = altitude.filter(|_| line_altitudes);
+        coordinates.push(kml_coordinates(longitude, latitude, line_altitude).0);
+        let (position, mode) = kml_coordinates(longitude, latitude, altitude);
+
+        let mut description = String::new();
+        if let Some(time) = point.captured {
+            let _ = write!(description, "<p>{}</p>", time.to_iso8601());
+        }
+        if let Some(thumbnail) = &point.thumbnail {
+            let _ = write!(
+                description,
+                "<img src=\"data:image/jpeg;base64,{}\" />",
+                STANDARD.encode(thumbnail)
+            );
+        }
+        let _ = writeln!(
+            placemarks,
+            "    <Placemark><name>{}</name><description><![CDATA[{}]]></description>\
+             <Point><altitudeMode>{}</altitudeMode><coordinates>{}</coordinates></Point></Placemark>",
+            escape_xml(&file_name(point)),
+            description,
+            mode,
+            position
+        );
+    }
+
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <kml xmlns=\"http://www.opengis.net/kml/2.2\">\n  <Document>\n    <name>Photo track</name>\n\
+         {}    <Placemark><name>Track</name><LineString><altitudeMode>{}</altitudeMode>\
+         <coordinates>{}</coordinates></LineString></Placemark>\n  </Document>\n</kml>\n",
+        placemarks,
+        line_mode,
+        coordinates.join(" ")
+    )
+}
+
+fn file_name(point: &GpsPoint) -> String {
+    point
+        .path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+pub fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for character in text.chars() {
+        match character {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            other => escaped.push(other),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{capture::CaptureTime, gps::GpsReading};
+    use chrono::NaiveDate;
+
+    fn point(name: &str, hour: u32, latitude: f64) -> GpsPoint {
+        GpsPoint {
+            path: name.into(),
+            reading: GpsReading {
+                latitude: Some(latitude),
+                longitude: Some(10.0),
+                altitude: Some(120.0),
+                ..GpsReading::default()
+            },
+            captured: Some(CaptureTime {
+                local: NaiveDate::from_ymd_opt(2024, 5, 4)
+                    .unwrap()
+                    .and_hms_opt(hour, 0, 0)
+                    .unwrap(),
+                offset_minutes: Some(0),
+            }),
+            thumbnail: Some(vec![0xFF, 0xD8, 0xFF, 0xD9]),
+        }
+    }
+
+    #[test]
+    fn gpx_lists_track_points_with_elevation() {
+        let gpx = render_gpx(&[point("a&b.jpg", 9, 45.0), point("c.jpg", 10, 45.5)]);
+        assert!(gpx.contains("<name>a&amp;b.jpg</name>"));
+        assert_eq!(gpx.matches("<trkpt").count(), 2);
+        assert!(gpx.contains("<ele>120.0</ele><time>2024-05-04T09:00:00Z</time>"));
+    }
+
+    #[test]
+    fn kml_embeds_thumbnails_and_line_string() {
+        let kml = render_kml(&[point("a.jpg", 9, 45.0), point("b.jpg", 10, 46.0)]);
+        assert!(kml.contains("data:image/jpeg;base64,/9j/2Q=="));
+        assert!(kml.contains(
+            "<coordinates>10.0000000,45.0000000,120.0 10.0000000,46.0000000,120.0</coordinates>"
+        ));
+
+        let mut flat = point("c.jpg", 11, 47.0);
+        flat.reading.altitude = None;
+        let kml = render_kml(&[point("a.jpg", 9, 45.0), flat]);
+        assert!(kml.contains(
+            "<Point><altitudeMode>clampToGround</altitudeMode><coordinates>10.0000000,47.0000000</coordinates>"
+        ));
+        assert!(kml.contains(
+            "<LineString><altitudeMode>clampToGround</altitudeMode><coordinates>10.0000000,45.0000000 10.0000000,47.0000000</coordinates>"
+        ));
+    }
+}
@@ -0,0 +1,244 @@
+use crate::gps::gps_point;
+use crate::scan_priority::{Pacer, ScanPriority};
+use serde::Serialize;
+#[cfg(desktop)]
+use std::path::PathBuf;
+use std::{
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+#[cfg(desktop)]
+use tauri::{
+    menu::{Menu, MenuEvent, MenuItem},
+    tray::TrayIconBuilder,
+    App, AppHandle, Emitter, Manager, Window, WindowEvent,
+};
+
+#[cfg(desktop)]
+const TRAY_ID: &str = "quick-scan";
+#[cfg(desktop)]
+const SCAN_DOWNLOADS: &str = "scan-downloads";
+#[cfg(desktop)]
+const OPEN_LAST: &str = "open-last";
+#[cfg(desktop)]
+const SHOW_WINDOW: &str = "show-window";
+#[cfg(desktop)]
+const QUIT: &str = "quit";
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GpsLeak {
+    pub path: String,
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+/// Result of a background scan for images that would reveal where they were taken.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GpsLeakScan {
+    pub folder: String,
+    pub scanned: usize,
+    pub leaks: Vec<GpsLeak>,
+    /// Seconds since the Unix epoch.
+    pub finished_at: u64,
+}
+
+/// The most recent tray scan, kept while the window is hidden.
+#[derive(Default)]
+pub struct QuickScan {
+    last: Mutex<Option<GpsLeakScan>>,
+    running: AtomicBool,
+}
+
+impl QuickScan {
+    pub fn last(&self) -> Option<GpsLeakScan> {
+        self.last.lock().expect("quick scan poisoned").clone()
+    }
+
+    /// Claims the scanner; `false` while another scan is still going.
+    #[cfg_attr(not(desktop), allow(dead_code))]
+    fn begin(&self) -> bool {
+        !self.running.swap(true, Ordering::SeqCst)
+    }
+
+    #[cfg_attr(not(desktop), allow(dead_code))]
+    fn finish(&self, scan: GpsLeakScan) {
+        *self.last.lock().expect("quick scan poisoned") = Some(scan);
+        self.running.store(false, Ordering::SeqCst);
+    }
+}
+
+#[cfg_attr(not(desktop), allow(dead_code))]
+pub fn scan_for_gps_leaks(folder: &Path, priority: ScanPriority) -> GpsLeakScan {
+    let mut pacer = Pacer::new(priority);
+    let mut scanned = 0;
+    let mut leaks = Vec::new();
+    crate::walk_files(folder, &mut |path| {
+        if !crate::is_supported_image(path) {
+            return;
+        }
+        scanned += 1;
+        if let Some(point) = gps_point(path, false) {
+            let (latitude, longitude) = point.position();
+            leaks.push(GpsLeak {
+                path: path.to_string_lossy().into_owned(),
+                latitude,
+                longitude,
+            });
+        }
+        pacer.after_file(path);
+    });
+    leaks.sort_by(|a, b| a.path.cmp(&b.path));
+    GpsLeakScan {
+        folder: folder.to_string_lossy().into_owned(),
+        scanned,
+        leaks,
+        finished_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or_default(),
+    }
+}
+
+/// Adds the tray icon and its menu.
+#[cfg(desktop)]
+pub fn install(app: &App) -> tauri::Result<()> {
+    let menu = Menu::with_items(
+        app,
+        &[
+            &MenuItem::with_id(
+                app,
+                SCAN_DOWNLOADS,
+                "Scan Downloads for GPS leaks",
+                true,
+                None::<&str>,
+            )?,
+            &MenuItem::with_id(app, OPEN_LAST, "Open last result", true, None::<&str>)?,
+            &MenuItem::with_id(app, SHOW_WINDOW, "Show EXIF Viewer", true, None::<&str>)?,
+            &MenuItem::with_id(app, QUIT, "Quit", true, None::<&str>)?,
+        ],
+    )?;
+
+    let mut tray = TrayIconBuilder::with_id(TRAY_ID)
+        .tooltip("EXIF Viewer")
+        .menu(&menu)
+        .on_menu_event(handle_menu_event);
+    if let Some(icon) = app.default_window_icon() {
+        tray = tray.icon(icon.clone());
+    }
+    tray.build(app)?;
+    Ok(())
+}
+
+/// Closing the main window only hides it, so the scanner stays loaded and
+/// tray scans keep running in the background until Quit is chosen. Other
+/// windows close for real.
+#[cfg(desktop)]
+pub fn keep_running_on_close(window: &Window, event: &WindowEvent) {
+    if window.label() != crate::sessions::MAIN_WINDOW {
+        return;
+    }
+    if let WindowEvent::CloseRequested { api, .. } = event {
+        if window.hide().is_ok() {
+            api.prevent_close();
+        }
+    }
+}
+
+#[cfg(desktop)]
+fn handle_menu_event(app: &AppHandle, event: MenuEvent) {
+    match event.id().as_ref() {
+        SCAN_DOWNLOADS => match app.path().download_dir() {
+            Ok(downloads) => start_scan(app, downloads),
+            Err(_) => {
+                let _ = app.emit(
+                    "quick-scan-error",
+                    "The Downloads folder could not be found.",
+                );
+            }
+        },
+        OPEN_LAST => {
+            crate::focus_main_window(app);
+            if let Some(scan) = app.state::<QuickScan>().last() {
+                let _ = app.emit("quick-scan-result", scan);
+            }
+        }
+        SHOW_WINDOW => crate::focus_main_window(app),
+        QUIT => app.exit(0),
+        _ => {}
+    }
+}
+
+#[cfg(desktop)]
+fn start_scan(app: &AppHandle, folder: PathBuf) {
+    if !app.state::<QuickScan>().begin() {
+        return;
+    }
+    let app = app.clone();
+    std::thread::spawn(move || {
+        // Nobody is waiting on a tray scan, so it never competes with the user.
+        crate::scan_priority::lower_current_thread();
+        let scan = scan_for_gps_leaks(&folder, ScanPriority::Background);
+        if let Some(tray) = app.tray_by_id(TRAY_ID) {
+            let _ = tray.set_tooltip(Some(format!(
+                "EXIF Viewer: {} of {} images in {} carry GPS",
+                scan.leaks.len(),
+                scan.scanned,
+                folder.display()
+            )));
+        }
+        app.state::<QuickScan>().finish(scan.clone());
+        let _ = app.emit("quick-scan-result", scan);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{ascii_field, jpeg_from_fields, rational_field};
+    use exif::Tag;
+    use std::fs;
+
+    #[test]
+    fn only_images_with_a_position_are_reported_as_leaks() {
+        let folder = crate::fixtures::temp_path("quick_scan");
+        fs::create_dir_all(&folder).unwrap();
+        let tagged = jpeg_from_fields(&[
+            ascii_field(Tag::GPSLatitudeRef, "N"),
+            rational_field(Tag::GPSLatitude, &[(48, 1), (51, 1), (3024, 100)]),
+            ascii_field(Tag::GPSLongitudeRef, "E"),
+            rational_field(Tag::GPSLongitude, &[(2, 1), (17, 1), (4020, 100)]),
+        ]);
+        fs::write(folder.join("tagged.jpg"), tagged).unwrap();
+        let plain = jpeg_from_fields(&[ascii_field(Tag::Artist, "Ada")]);
+        fs::write(folder.join("plain.jpg"), plain).unwrap();
+        fs::write(folder.join("notes.txt"), b"not an image").unwrap();
+
+        let scan = scan_for_gps_leaks(&folder, ScanPriority::Normal);
+        fs::remove_dir_all(&folder).ok();
+
+        assert_eq!(scan.scanned, 2);
+        assert_eq!(scan.leaks.len(), 1);
+        assert!(scan.leaks[0].path.ends_with("tagged.jpg"));
+        assert!((scan.leaks[0].latitude - 48.8584).abs() < 1e-3);
+        assert!((scan.leaks[0].longitude - 2.2945).abs() < 1e-3);
+    }
+
+    #[test]
+    fn a_second_scan_waits_for_the_first() {
+        let quick = QuickScan::default();
+        assert!(quick.begin());
+        assert!(!quick.begin());
+        quick.finish(scan_for_gps_leaks(
+            Path::new("/definitely/not/here"),
+            ScanPriority::Normal,
+        ));
+        assert!(quick.begin());
+        assert_eq!(quick.last().unwrap().scanned, 0);
+    }
+}
@@ -0,0 +1,234 @@
+use chrono::DateTime;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Mutex, OnceLock},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Counts are written to disk after this many new records, and whenever a
+/// report is made.
+const SAVE_EVERY: usize = 25;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FormatCounts {
+    parsed: u64,
+    failed: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct StoredStats {
+    /// RFC 3339 time collection was switched on.
+    since: String,
+    formats: BTreeMap<String, FormatCounts>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FormatStats {
+    pub format: String,
+    pub files: u64,
+    pub failed: u64,
+    /// Share of `files` that failed to parse, 0 to 1.
+    pub failure_rate: f64,
+}
+
+/// What the user can paste into a bug report. Built from local counts only;
+/// nothing here is ever sent anywhere by the app.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiagnosticsReport {
+    pub enabled: bool,
+    pub since: Option<String>,
+    pub app_version: String,
+    pub os: String,
+    pub arch: String,
+    pub files: u64,
+    pub failed: u64,
+    /// Worst failure rate first.
+    pub formats: Vec<FormatStats>,
+}
+
+/// Opt-in tally of the formats the app has parsed and how often parsing
+/// failed. Records are dropped while collection is off.
+pub struct UsageStats {
+    state: Mutex<StatsState>,
+}
+
+#[derive(Default)]
+struct StatsState {
+    /// `None` while collection is off.
+    location: Option<PathBuf>,
+    stats: StoredStats,
+    unsaved: usize,
+}
+
+impl StatsState {
+    fn save(&mut self) {
+        let Some(location) = &self.location else {
+            return;
+        };
+        if let Some(parent) = location.parent() {
+            fs::create_dir_all(parent).ok();
+        }
+        // Losing a few counts to a failed write is not worth an error.
+        if let Ok(data) = serde_json::to_vec_pretty(&self.stats) {
+            crate::write_file_atomically(location, &data).ok();
+        }
+        self.unsaved = 0;
+    }
+}
+
+impl UsageStats {
+    fn new() -> Self {
+        UsageStats {
+            state: Mutex::new(StatsState::default()),
+        }
+    }
+
+    /// Starts collecting into `location`, continuing any counts already there.
+    pub fn enable(&self, location: PathBuf) {
+        let stats = fs::read(&location)
+            .ok()
+            .and_then(|data| serde_json::from_slice(&data).ok())
+            .unwrap_or_else(|| StoredStats {
+                since: now_rfc3339(),
+                ..StoredStats::default()
+            });
+        let mut state = self.state.lock().expect("usage stats poisoned");
+        *state = StatsState {
+            location: Some(location),
+            stats,
+            unsaved: 0,
+        };
+        state.save();
+    }
+
+    /// Stops collecting and deletes what was collected.
+    pub fn disable(&self) {
+        let mut state = self.state.lock().expect("usage stats poisoned");
+        if let Some(location) = state.location.take() {
+            fs::remove_file(location).ok();
+        }
+        *state = StatsState::default();
+    }
+
+    /// Counts one parse attempt of the file at `path`.
+    pub fn record(&self, path: &Path, parsed: bool) {
+        let mut state = self.state.lock().expect("usage stats poisoned");
+        if state.location.is_none() {
+            return;
+        }
+        let counts = state
+            .stats
+            .formats
+            .entry(crate::benchmark::format_name(path))
+            .or_default();
+        if parsed {
+            counts.parsed += 1;
+        } else {
+            counts.failed += 1;
+        }
+        state.unsaved += 1;
+        if state.unsaved >= SAVE_EVERY {
+            state.save();
+        }
+    }
+
+    pub fn report(&self) -> DiagnosticsReport {
+        let mut state = self.state.lock().expect("usage stats poisoned");
+        if state.unsaved > 0 {
+            state.save();
+        }
+        let mut formats: Vec<FormatStats> = state
+            .stats
+            .formats
+            .iter()
+            .map(|(format, counts)| {
+                let files = counts.parsed + counts.failed;
+                FormatStats {
+                    format: format.clone(),
+                    files,
+                    failed: counts.failed,
+                    failure_rate: counts.failed as f64 / files.max(1) as f64,
+                }
+            })
+            .collect();
+        formats.sort_by(|a, b| {
+            b.failure_rate
+                .total_cmp(&a.failure_rate)
+                .then_with(|| b.files.cmp(&a.files))
+        });
+        DiagnosticsReport {
+            enabled: state.location.is_some(),
+            since: state.location.is_some().then(|| state.stats.since.clone()),
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+            files: formats.iter().map(|format| format.files).sum(),
+            failed: formats.iter().map(|format| format.failed).sum(),
+            formats,
+        }
+    }
+}
+
+fn now_rfc3339() -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() as i64)
+        .unwrap_or_default();
+    DateTime::from_timestamp(now, 0)
+        .map(|time| time.to_rfc3339())
+        .unwrap_or_default()
+}
+
+/// The tally shared by every parse in the process; off until enabled.
+pub fn global() -> &'static UsageStats {
+    static STATS: OnceLock<UsageStats> = OnceLock::new();
+    STATS.get_or_init(UsageStats::new)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_only_while_enabled_and_forgets_on_disable() {
+        let location = crate::fixtures::temp_path("usage_stats.json");
+        let stats = UsageStats::new();
+        stats.record(Path::new("ignored.jpg"), true);
+        stats.enable(location.clone());
+        stats.record(Path::new("a.jpg"), true);
+        stats.record(Path::new("b.JPEG"), false);
+        stats.record(Path::new("c.png"), true);
+        stats.record(Path::new("d.heif"), false);
+
+        let report = stats.report();
+        assert!(report.enabled);
+        assert_eq!((report.files, report.failed), (4, 2));
+        let formats: Vec<(&str, u64, f64)> = report
+            .formats
+            .iter()
+            .map(|format| (format.format.as_str(), format.files, format.failure_rate))
+            .collect();
+        assert_eq!(
+            formats,
+            [("heic", 1, 1.0), ("jpeg", 2, 0.5), ("png", 1, 0.0)]
+        );
+
+        // A restart picks the counts back up from disk.
+        let reopened = UsageStats::new();
+        reopened.enable(location.clone());
+        assert_eq!(reopened.report().files, 4);
+
+        reopened.disable();
+        assert!(!location.exists());
+        let report = reopened.report();
+        assert!(!report.enabled);
+        assert!(report.formats.is_empty());
+    }
+}
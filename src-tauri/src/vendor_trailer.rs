@@ -0,0 +1,266 @@
+use crate::{xmp, ExifField};
+use serde::Serialize;
+
+pub const VENDOR_IFD: &str = "Vendor";
+
+/// Samsung phones append data blocks after the JPEG end marker, indexed by
+/// an `SEFH` directory and closed by its length and `SEFT`.
+const SAMSUNG_FOOTER: &[u8] = b"SEFT";
+const SAMSUNG_DIRECTORY: &[u8] = b"SEFH";
+/// XMP prefixes of Xiaomi camera properties and of the depth data Google's
+/// format stores for portrait shots, which Xiaomi phones also write.
+const XIAOMI_PREFIX: &str = "MiCamera:";
+const DEPTH_PREFIX: &str = "GDepth:";
+
+/// One block of a Samsung trailer.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrailerBlock {
+    /// The block's own name, e.g. `Watermark_Info` or `DualShot_Meta_Info`.
+    pub name: String,
+    pub kind: u16,
+    /// Where the payload starts in the file.
+    pub offset: usize,
+    pub length: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockExport {
+    pub path: String,
+    pub bytes: usize,
+}
+
+fn u16_le(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        data.get(at..at.checked_add(2)?)?.try_into().ok()?,
+    ))
+}
+
+fn u32_le(data: &[u8], at: usize) -> Option<usize> {
+    Some(u32::from_le_bytes(data.get(at..at.checked_add(4)?)?.try_into().ok()?) as usize)
+}
+
+/// The blocks of a Samsung `SEFT` trailer; empty when the file has none.
+pub fn samsung_blocks(data: &[u8]) -> Vec<TrailerBlock> {
+    let directory = data
+        .ends_with(SAMSUNG_FOOTER)
+        .then(|| u32_le(data, data.len().checked_sub(8)?))
+        .flatten()
+        .and_then(|length| data.len().checked_sub(8)?.checked_sub(length))
+        .filter(|start| data.get(*start..start + 4) == Some(SAMSUNG_DIRECTORY));
+    let Some(directory) = directory else {
+        return Vec::new();
+    };
+    let count = u32_le(data, directory + 8).unwrap_or_default();
+    (0..count)
+        .map_while(|index| {
+            let entry = directory + 12 + index * 12;
+            let kind = u16_le(data, entry + 2)?;
+            let start = directory.checked_sub(u32_le(data, entry + 4)?)?;
+            let length = u32_le(data, entry + 8)?;
+            // Each block opens with its kind again and a length-prefixed name.
+            let name_length = u32_le(data, start + 4)?;
+            let name = data.get(start + 8..start + 8 + name_length)?;
+            let payload = start + 8 + name_length;
+            Some(TrailerBlock {
+                name: String::from_utf8_lossy(name).into_owned(),
+                kind,
+                offset: payload,
+                length: (start + length).checked_sub(payload)?,
+            })
+        })
+        .filter(|block| {
+            block
+                .offset
+                .checked_add(block.length)
+                .is_some_and(|end| end <= directory)
+        })
+        .collect()
+}
+
+/// What a block is for, going by its name.
+fn purpose(name: &str) -> Option<&'static str> {
+    let name = name.to_ascii_lowercase();
+    [
+        ("watermark", "watermark"),
+        ("dualshot", "dual-camera depth"),
+        ("dual_shot", "dual-camera depth"),
+        ("depth", "dual-camera depth"),
+        ("bokeh", "bokeh"),
+        ("blur", "bokeh"),
+        ("motionphoto", "motion photo"),
+    ]
+    .iter()
+    .find(|(needle, _)| name.contains(needle))
+    .map(|(_, purpose)| *purpose)
+}
+
+/// `prefix` properties written as XMP attributes, in packet order.
+fn prefixed_properties<'a>(packet: &'a str, prefix: &str) -> Vec<(&'a str, &'a str)> {
+    let mut found = Vec::new();
+    let mut rest = packet;
+    while let Some(at) = rest.find(prefix) {
+        let after = &rest[at..];
+        let name_end = after
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == ':' || c == '_'))
+            .unwrap_or(after.len());
+        let (name, tail) = after.split_at(name_end);
+        match tail
+            .strip_prefix("=\"")
+            .and_then(|value| value.split_once('"'))
+        {
+            Some((value, remaining)) => {
+                if !found.iter().any(|(seen, _)| *seen == name) {
+                    found.push((name, value));
+                }
+                rest = remaining;
+            }
+            None => rest = tail,
+        }
+    }
+    found
+}
+
+fn field(tag: &str, value: String) -> ExifField {
+    ExifField {
+        tag: tag.to_string(),
+        ifd: VENDOR_IFD.to_string(),
+        value,
+    }
+}
+
+/// Summary fields for Samsung trailer blocks and Xiaomi camera properties,
+/// plus a flag for each of watermark, bokeh and dual-camera depth data.
+pub fn fields(data: &[u8]) -> Vec<ExifField> {
+    let mut fields = Vec::new();
+    let mut purposes: Vec<&str> = Vec::new();
+
+    for block in samsung_blocks(data) {
+        let purpose = purpose(&block.name);
+        purposes.extend(purpose);
+        fields.push(field(
+            &format!("Samsung {}", block.name),
+            match purpose {
+                Some(purpose) => format!("{} bytes ({})", block.length, purpose),
+                None => format!("{} bytes", block.length),
+            },
+        ));
+    }
+
+    if let Some(packet) = xmp::packet(data) {
+        for (name, value) in prefixed_properties(packet, XIAOMI_PREFIX) {
+            purposes.extend(purpose(name));
+            fields.push(field(name, value.to_string()));
+        }
+        if let Some(depth) = prefixed_properties(packet, DEPTH_PREFIX)
+            .into_iter()
+            .find(|(name, _)| *name == "GDepth:Mime")
+        {
+            purposes.push("dual-camera depth");
+            fields.push(field("DepthMap", format!("present ({})", depth.1)));
+        }
+    }
+
+    for (purpose, tag) in [
+        ("watermark", "Watermark"),
+        ("bokeh", "BokehData"),
+        ("dual-camera depth", "DualCameraDepth"),
+    ] {
+        if purposes.contains(&purpose) {
+            fields.push(field(tag, "present".to_string()));
+        }
+    }
+    fields
+}
+
+/// The payload of the Samsung trailer block called `name`.
+pub fn block_payload<'a>(data: &'a [u8], name: &str) -> Option<&'a [u8]> {
+    samsung_blocks(data)
+        .into_iter()
+        .find(|block| block.name == name)
+        .and_then(|block| data.get(block.offset..block.offset + block.length))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A JPEG with a Samsung trailer of two blocks.
+    fn samsung_jpeg() -> Vec<u8> {
+        let mut file = b"\xFF\xD8\xFF\xD9".to_vec();
+        let mut entries = Vec::new();
+        let blocks: [(u16, &str, &[u8]); 2] = [
+            (0x0A01, "Image_UTC_Data", b"1717243200000"),
+            (0x0BD0, "DualShot_Meta_Info", b"\x01\x02\x03\x04\x05"),
+        ];
+        let mut starts = Vec::new();
+        for (kind, name, payload) in blocks {
+            starts.push(file.len());
+            file.extend_from_slice(&[0, 0]);
+            file.extend_from_slice(&kind.to_le_bytes());
+            file.extend_from_slice(&(name.len() as u32).to_le_bytes());
+            file.extend_from_slice(name.as_bytes());
+            file.extend_from_slice(payload);
+            entries.push((kind, 8 + name.len() + payload.len()));
+        }
+        let directory = file.len();
+        file.extend_from_slice(SAMSUNG_DIRECTORY);
+        file.extend_from_slice(&101u32.to_le_bytes());
+        file.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+        for ((kind, length), start) in entries.iter().zip(&starts) {
+            file.extend_from_slice(&[0, 0]);
+            file.extend_from_slice(&kind.to_le_bytes());
+            file.extend_from_slice(&((directory - start) as u32).to_le_bytes());
+            file.extend_from_slice(&(*length as u32).to_le_bytes());
+        }
+        let length = file.len() - directory;
+        file.extend_from_slice(&(length as u32).to_le_bytes());
+        file.extend_from_slice(SAMSUNG_FOOTER);
+        file
+    }
+
+    #[test]
+    fn samsung_trailer_blocks_are_listed_and_extracted() {
+        let file = samsung_jpeg();
+        let names: Vec<String> = samsung_blocks(&file)
+            .into_iter()
+            .map(|block| block.name)
+            .collect();
+        assert_eq!(names, ["Image_UTC_Data", "DualShot_Meta_Info"]);
+        assert_eq!(
+            block_payload(&file, "Image_UTC_Data"),
+            Some(b"1717243200000".as_slice())
+        );
+
+        let fields: Vec<(String, String)> = fields(&file)
+            .into_iter()
+            .map(|field| (field.tag, field.value))
+            .collect();
+        assert!(fields.contains(&(
+            "Samsung DualShot_Meta_Info".to_string(),
+            "5 bytes (dual-camera depth)".to_string()
+        )));
+        assert!(fields.contains(&("DualCameraDepth".to_string(), "present".to_string())));
+        assert!(samsung_blocks(b"\xFF\xD8\xFF\xD9").is_empty());
+    }
+
+    #[test]
+    fn xiaomi_properties_and_depth_maps_come_from_xmp() {
+        let file = br#"<x:xmpmeta><rdf:Description MiCamera:Watermark="1"
+            MiCamera:Model="2211133G" GDepth:Mime="image/jpeg" GDepth:Data="..."/></x:xmpmeta>"#;
+        let fields: Vec<(String, String)> = fields(file)
+            .into_iter()
+            .map(|field| (field.tag, field.value))
+            .collect();
+        let expected = [
+            ("MiCamera:Watermark", "1"),
+            ("MiCamera:Model", "2211133G"),
+            ("DepthMap", "present (image/jpeg)"),
+            ("Watermark", "present"),
+            ("DualCameraDepth", "present"),
+        ]
+        .map(|(tag, value)| (tag.to_string(), value.to_string()));
+        assert_eq!(fields, expected);
+    }
+}
@@ -0,0 +1,237 @@
+use crate::{
+    exif_write::{self, FieldEdit, ImageData},
+    jpeg::{self, Segment},
+};
+use exif::{Exif, Field, In, Tag};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Tags the encoder rewrites on every save, so their values are expected to move.
+const LAYOUT_TAGS: [Tag; 7] = [
+    Tag::ExifIFDPointer,
+    Tag::GPSInfoIFDPointer,
+    Tag::InteropIFDPointer,
+    Tag::JPEGInterchangeFormat,
+    Tag::JPEGInterchangeFormatLength,
+    Tag::StripOffsets,
+    Tag::TileOffsets,
+];
+
+/// Outcome of re-reading a file right after it was written.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WriteVerification {
+    pub verified: bool,
+    /// Edits that did not land as intended.
+    pub missing_edits: Vec<String>,
+    /// Tags the write changed even though no edit asked for it.
+    pub unexpected_changes: Vec<String>,
+    /// Whether every byte outside the EXIF segment is identical; for a TIFF,
+    /// whether the image data is.
+    pub other_segments_intact: bool,
+}
+
+fn displayed_fields(exif: &Exif) -> BTreeMap<(String, String), String> {
+    exif.fields()
+        .filter(|field| !LAYOUT_TAGS.contains(&field.tag))
+        .map(|field| {
+            (
+                (field.ifd_num.to_string(), field.tag.to_string()),
+                field.display_value().to_string(),
+            )
+        })
+        .collect()
+}
+
+/// Everything outside the EXIF APP1 segment, in file order, or the pixels
+/// of a TIFF, whose metadata and image data share one structure.
+fn non_exif_bytes<'a>(data: &'a [u8], exif: Option<&Exif>) -> Option<Vec<&'a [u8]>> {
+    if exif_write::is_tiff(data) {
+        let images = exif_write::tiff_image_data(data, exif?).ok()?;
+        return Some(
+            images
+                .into_iter()
+                .flat_map(|(_, image)| match image {
+                    ImageData::Strips(chunks) | ImageData::Tiles(chunks) => chunks,
+                    ImageData::Jpeg(jpeg) => vec![jpeg],
+                })
+                .collect(),
+        );
+    }
+    jpeg_bytes_outside(data, |segment| segment.is_exif(data))
+}
+
+/// The segments of a JPEG `skip` leaves, then the scan data.
+fn jpeg_bytes_outside(data: &[u8], skip: impl Fn(&Segment) -> bool) -> Option<Vec<&[u8]>> {
+    let (segments, scan_start) = jpeg::segments(data).ok()?;
+    let mut parts: Vec<&[u8]> = segments
+        .iter()
+        .filter(|segment| !skip(segment))
+        .map(|segment| &data[segment.start..segment.end])
+        .collect();
+    parts.push(&data[scan_start..]);
+    Some(parts)
+}
+
+/// EXIF tags whose displayed value differs between `before` and `after`,
+/// leaving out those `edited` expects to change.
+fn changed_tags(
+    before: Option<&Exif>,
+    after: Option<&Exif>,
+    edited: impl Fn(&(String, String)) -> bool,
+) -> Vec<String> {
+    let before_fields = before.map(displayed_fields).unwrap_or_default();
+    let after_fields = after.map(displayed_fields).unwrap_or_default();
+    before_fields
+        .keys()
+        .chain(after_fields.keys())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .filter(|key| !edited(key) && before_fields.get(*key) != after_fields.get(*key))
+        .map(|(ifd, tag)| format!("{} ({})", tag, ifd))
+        .collect()
+}
+
+/// Checks that `written` carries `edits` and differs from `original` nowhere else.
+pub fn verify_write(original: &[u8], written: &[u8], edits: &[FieldEdit]) -> WriteVerification {
+    let before = crate::read_exif_container(original);
+    let after = crate::read_exif_container(written);
+
+    let mut missing_edits = Vec::new();
+    for edit in edits {
+        let landed = after
+            .as_ref()
+            .and_then(|exif| exif.get_field(edit.tag, In::PRIMARY))
+            .map(|field| field.display_value().to_string());
+        let intended = edit.value.clone().map(|value| {
+            Field {
+                tag: edit.tag,
+                ifd_num: In::PRIMARY,
+                value,
+            }
+            .display_value()
+            .to_string()
+        });
+        if landed != intended {
+            missing_edits.push(edit.tag.to_string());
+        }
+    }
+
+    let edited = |(ifd, tag): &(String, String)| {
+        *ifd == In::PRIMARY.to_string() && edits.iter().any(|edit| &edit.tag.to_string() == tag)
+    };
+    let unexpected_changes = changed_tags(before.as_ref(), after.as_ref(), edited);
+
+    let other_segments_intact = match (
+        non_exif_bytes(original, before.as_ref()),
+        non_exif_bytes(written, after.as_ref()),
+    ) {
+        (Some(before), Some(after)) => before == after,
+        _ => false,
+    };
+
+    WriteVerification {
+        verified: after.is_some()
+            && missing_edits.is_empty()
+            && unexpected_changes.is_empty()
+            && other_segments_intact,
+        missing_edits,
+        unexpected_changes,
+        other_segments_intact,
+    }
+}
+
+/// Checks that the JPEG `written` carries the XMP `packet` and differs from
+/// `original` nowhere outside its XMP segment; `properties` are the ones
+/// reported missing when the packet did not land.
+pub fn verify_xmp_write(
+    original: &[u8],
+    written: &[u8],
+    packet: &str,
+    properties: &[String],
+) -> WriteVerification {
+    let missing_edits = match crate::xmp::jpeg_packet(written) {
+        Some(landed) if landed == packet => Vec::new(),
+        _ => properties.to_vec(),
+    };
+    let unexpected_changes = changed_tags(
+        crate::read_exif_container(original).as_ref(),
+        crate::read_exif_container(written).as_ref(),
+        |_| false,
+    );
+    let is_xmp = |data: &[u8], segment: &Segment| {
+        segment.marker == jpeg::APP1 && segment.payload(data).starts_with(crate::xmp::JPEG_HEADER)
+    };
+    let other_segments_intact = match (
+        jpeg_bytes_outside(original, |segment| is_xmp(original, segment)),
+        jpeg_bytes_outside(written, |segment| is_xmp(written, segment)),
+    ) {
+        (Some(before), Some(after)) => before == after,
+        _ => false,
+    };
+
+    WriteVerification {
+        verified: missing_edits.is_empty()
+            && unexpected_changes.is_empty()
+            && other_segments_intact,
+        missing_edits,
+        unexpected_changes,
+        other_segments_intact,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        exif_write::apply_jpeg_edits,
+        jpeg::{APP0, SOI, SOS},
+    };
+
+    fn minimal_jpeg() -> Vec<u8> {
+        let mut data = SOI.to_vec();
+        data.extend(jpeg::encode_segment(APP0, b"JFIF\0\x01\x02\0\0\x01\0\x01\0\0").unwrap());
+        data.extend_from_slice(&[0xFF, SOS, 0x00, 0x02, 0xAB, 0xCD, 0xFF, 0xD9]);
+        data
+    }
+
+    #[test]
+    fn clean_edit_is_verified() {
+        let original = apply_jpeg_edits(
+            &minimal_jpeg(),
+            &[FieldEdit::ascii(Tag::Copyright, "CC-BY")],
+        )
+        .unwrap();
+        let edits = [FieldEdit::ascii(Tag::Artist, "Ada")];
+        let written = apply_jpeg_edits(&original, &edits).unwrap();
+
+        let verification = verify_write(&original, &written, &edits);
+        assert!(verification.verified, "{:?}", verification);
+    }
+
+    #[test]
+    fn stray_changes_and_damaged_scan_data_are_reported() {
+        let original = apply_jpeg_edits(
+            &minimal_jpeg(),
+            &[FieldEdit::ascii(Tag::Copyright, "CC-BY")],
+        )
+        .unwrap();
+        let edits = [FieldEdit::ascii(Tag::Artist, "Ada")];
+        let mut written = apply_jpeg_edits(
+            &original,
+            &[
+                FieldEdit::ascii(Tag::Artist, "Grace"),
+                FieldEdit::ascii(Tag::Copyright, "All rights reserved"),
+            ],
+        )
+        .unwrap();
+        let last = written.len() - 3;
+        written[last] ^= 0xFF;
+
+        let verification = verify_write(&original, &written, &edits);
+        assert!(!verification.verified);
+        assert_eq!(verification.missing_edits, vec!["Artist"]);
+        assert_eq!(verification.unexpected_changes, vec!["Copyright (primary)"]);
+        assert!(!verification.other_segments_intact);
+    }
+}
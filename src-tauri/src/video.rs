@@ -0,0 +1,488 @@
+use crate::heif::{boxes, child, u16_at, u32_at};
+use chrono::DateTime;
+use serde::Serialize;
+use std::{
+    fs::File,
+    io::{ErrorKind, Read, Seek, SeekFrom},
+    path::Path,
+};
+
+/// Seconds from the QuickTime epoch, 1904-01-01 UTC, to the Unix epoch.
+const QUICKTIME_EPOCH_OFFSET: i64 = 2_082_844_800;
+/// A phone video's `moov` is a few megabytes at most; a larger one is not
+/// loaded.
+const MAX_MOOV_BYTES: u64 = 64 * 1024 * 1024;
+const APPLE_LOCATION_KEY: &str = "com.apple.quicktime.location.ISO6709";
+/// `udta` and `ilst` entries named by a fourcc starting with `©`.
+const COPYRIGHT_SIGN: u8 = 0xA9;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VideoTrack {
+    /// `video`, `audio`, or the handler type of other tracks (`meta`, `tmcd`...).
+    pub kind: String,
+    /// The sample entry fourcc, such as `hvc1`, `avc1` or `mp4a`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub codec: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_seconds: Option<f64>,
+}
+
+/// A user data or metadata item: a `©` atom of `udta` or an `ilst` entry,
+/// named by its `keys` entry when the file has one.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VideoTag {
+    pub key: String,
+    pub value: String,
+}
+
+/// What the `moov` box of an MP4 or MOV says about the video.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VideoMetadata {
+    /// The major brand from `ftyp`, e.g. `qt  ` for QuickTime or `isom`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub brand: Option<String>,
+    /// RFC 3339 UTC time from `mvhd`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub creation_time: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_seconds: Option<f64>,
+    pub tracks: Vec<VideoTrack>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latitude: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub longitude: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub altitude: Option<f64>,
+    pub tags: Vec<VideoTag>,
+}
+
+fn u64_at(data: &[u8], at: usize) -> Option<u64> {
+    Some(u64::from_be_bytes(data.get(at..at + 8)?.try_into().ok()?))
+}
+
+/// A fourcc as text; `©` is byte 0xA9 as in Latin-1.
+fn fourcc(kind: &[u8]) -> String {
+    let text: String = kind.iter().map(|&byte| byte as char).collect();
+    text.trim_end().to_string()
+}
+
+/// Reads the top-level `ftyp` and `moov` boxes of `path`, skipping over
+/// `mdat` and everything else without reading it, so multi-gigabyte videos
+/// cost a few reads. The boxes are returned back to back, as in a file.
+pub fn read_header_boxes(path: &Path) -> Result<Vec<u8>, String> {
+    let mut file = File::open(path).map_err(|error| error.to_string())?;
+    let mut boxes = Vec::new();
+    loop {
+        let start = file.stream_position().map_err(|error| error.to_string())?;
+        let mut header = [0u8; 8];
+        match file.read_exact(&mut header) {
+            Ok(()) => {}
+            Err(error) if error.kind() == ErrorKind::UnexpectedEof => break,
+            Err(error) => return Err(error.to_string()),
+        }
+        let kind = [header[4], header[5], header[6], header[7]];
+        let (header_len, size) = match u32::from_be_bytes(header[..4].try_into().expect("4 bytes"))
+        {
+            // The last box runs to the end of the file.
+            0 => {
+                let position = file.stream_position().map_err(|error| error.to_string())?;
+                let len = file.metadata().map_err(|error| error.to_string())?.len();
+                (8, len - position + 8)
+            }
+            1 => {
+                let mut large = [0u8; 8];
+                file.read_exact(&mut large).map_err(|_| {
+                    "The selected file appears to be truncated or corrupted.".to_string()
+                })?;
+                (16, u64::from_be_bytes(large))
+            }
+            size => (8, size as u64),
+        };
+        let Some(body_len) = size.checked_sub(header_len) else {
+            return Err("The selected file appears to be truncated or corrupted.".to_string());
+        };
+        if &kind == b"ftyp" || &kind == b"moov" {
+            if body_len > MAX_MOOV_BYTES {
+                return Err("The video's metadata is too large to read.".to_string());
+            }
+            let mut body = vec![0u8; body_len as usize];
+            file.read_exact(&mut body).map_err(|_| {
+                "The selected file appears to be truncated or corrupted.".to_string()
+            })?;
+            boxes.extend_from_slice(&((body_len + 8) as u32).to_be_bytes());
+            boxes.extend_from_slice(&kind);
+            boxes.extend(body);
+            if &kind == b"moov" {
+                break;
+            }
+        } else {
+            let skip = i64::try_from(body_len).map_err(|_| {
+                "The selected file appears to be truncated or corrupted.".to_string()
+            })?;
+            file.seek(SeekFrom::Current(skip))
+                .map_err(|error| error.to_string())?;
+        }
+        // Every box must move the reader on, or a crafted size could loop forever.
+        if file.stream_position().map_err(|error| error.to_string())? <= start {
+            return Err("The selected file appears to be truncated or corrupted.".to_string());
+        }
+    }
+    Ok(boxes)
+}
+
+/// Creation time, timescale and duration from an `mvhd` or `mdhd` box.
+fn media_header(body: &[u8]) -> Option<(u64, u32, u64)> {
+    match body.first()? {
+        1 => Some((u64_at(body, 4)?, u32_at(body, 20)?, u64_at(body, 24)?)),
+        _ => Some((
+            u32_at(body, 4)? as u64,
+            u32_at(body, 12)?,
+            u32_at(body, 16)? as u64,
+        )),
+    }
+}
+
+fn seconds(timescale: u32, duration: u64) -> Option<f64> {
+    (timescale > 0 && duration > 0).then(|| duration as f64 / timescale as f64)
+}
+
+fn track(trak: &[u8]) -> Option<VideoTrack> {
+    let mdia = child(trak, b"mdia")?;
+    let handler = child(mdia, b"hdlr").and_then(|hdlr| hdlr.get(8..12));
+    let kind = match handler {
+        Some(b"vide") => "video".to_string(),
+        Some(b"soun") => "audio".to_string(),
+        Some(other) => fourcc(other),
+        None => "unknown".to_string(),
+    };
+    let codec = child(mdia, b"minf")
+        .and_then(|minf| child(minf, b"stbl"))
+        .and_then(|stbl| child(stbl, b"stsd"))
+        .and_then(|stsd| stsd.get(12..16))
+        .map(fourcc);
+    // Track sizes are 16.16 fixed point at the end of `tkhd`.
+    let size = child(trak, b"tkhd").and_then(|tkhd| {
+        let at = if tkhd.first()? == &1 { 88 } else { 76 };
+        Some((u32_at(tkhd, at)? >> 16, u32_at(tkhd, at + 4)? >> 16))
+    });
+    let (width, height) = match size {
+        Some((width, height)) if width > 0 && height > 0 => (Some(width), Some(height)),
+        _ => (None, None),
+    };
+    Some(VideoTrack {
+        kind,
+        codec,
+        width,
+        height,
+        duration_seconds: child(mdia, b"mdhd")
+            .and_then(media_header)
+            .and_then(|(_, timescale, duration)| seconds(timescale, duration)),
+    })
+}
+
+/// The value of an `ilst` item's `data` box, by its well-known type.
+fn data_value(data: &[u8]) -> Option<String> {
+    let kind = u32_at(data, 0)? & 0x00FF_FFFF;
+    let value = data.get(8..)?;
+    Some(match (kind, value.len()) {
+        (1, _) => String::from_utf8_lossy(value).into_owned(),
+        (21, 1) => (value[0] as i8).to_string(),
+        (21, 2) => i16::from_be_bytes(value.try_into().ok()?).to_string(),
+        (21, 4) => i32::from_be_bytes(value.try_into().ok()?).to_string(),
+        (21, 8) => i64::from_be_bytes(value.try_into().ok()?).to_string(),
+        (22, 1) => value[0].to_string(),
+        (22, 2) => u16::from_be_bytes(value.try_into().ok()?).to_string(),
+        (22, 4) => u32::from_be_bytes(value.try_into().ok()?).to_string(),
+        (22, 8) => u64::from_be_bytes(value.try_into().ok()?).to_string(),
+        (23, 4) => f32::from_be_bytes(value.try_into().ok()?).to_string(),
+        (24, 8) => f64::from_be_bytes(value.try_into().ok()?).to_string(),
+        (_, len) => format!("({} bytes)", len),
+    })
+}
+
+/// The items of a `meta` box: a QuickTime one with `keys` naming its `ilst`
+/// entries by index, or an iTunes-style one whose entries are named by fourcc.
+fn meta_tags(meta: &[u8], tags: &mut Vec<VideoTag>) {
+    // QuickTime `meta` boxes have no version and flags, ISO ones do.
+    let meta = if meta.get(4..8) == Some(b"hdlr".as_slice()) {
+        meta
+    } else {
+        meta.get(4..).unwrap_or_default()
+    };
+    let keys: Vec<String> = child(meta, b"keys")
+        .and_then(|keys| keys.get(8..))
+        .map(|entries| {
+            boxes(entries)
+                .map(|(_namespace, name)| String::from_utf8_lossy(name).into_owned())
+                .collect()
+        })
+        .unwrap_or_default();
+    let Some(ilst) = child(meta, b"ilst") else {
+        return;
+    };
+    for (kind, item) in boxes(ilst) {
+        let key = match u32_at(kind, 0) {
+            Some(index) if !keys.is_empty() => match keys.get((index as usize).wrapping_sub(1)) {
+                Some(key) => key.clone(),
+                None => continue,
+            },
+            _ => fourcc(kind),
+        };
+        if let Some(value) = child(item, b"data").and_then(data_value) {
+            tags.push(VideoTag { key, value });
+        }
+    }
+}
+
+/// The `©` atoms of `udta`: a 16-bit length, a language code, then text.
+fn user_data_tags(udta: &[u8], tags: &mut Vec<VideoTag>) {
+    for (kind, body) in boxes(udta) {
+        if kind.first() == Some(&COPYRIGHT_SIGN) {
+            let Some(len) = u16_at(body, 0) else {
+                continue;
+            };
+            if let Some(text) = body.get(4..4 + len as usize) {
+                tags.push(VideoTag {
+                    key: fourcc(kind),
+                    value: String::from_utf8_lossy(text).into_owned(),
+                });
+            }
+        } else if kind == b"meta" {
+            meta_tags(body, tags);
+        }
+    }
+}
+
+/// Latitude, longitude and altitude from an ISO 6709 string in decimal
+/// degrees, as phones write it: `+37.3318-122.0312+012.345/`.
+pub fn iso6709(text: &str) -> Option<(f64, f64, Option<f64>)> {
+    let text = text.trim();
+    let end = text
+        .find(|c: char| !(c.is_ascii_digit() || c == '+' || c == '-' || c == '.'))
+        .unwrap_or(text.len());
+    let text = &text[..end];
+    let mut starts: Vec<usize> = text
+        .char_indices()
+        .filter(|(_, c)| *c == '+' || *c == '-')
+        .map(|(index, _)| index)
+        .collect();
+    if starts.first() != Some(&0) {
+        return None;
+    }
+    starts.push(text.len());
+    let parts: Vec<f64> = starts
+        .windows(2)
+        .map(|pair| text[pair[0]..pair[1]].parse::<f64>())
+        .collect::<Result<_, _>>()
+        .ok()?;
+    let (latitude, longitude, altitude) = match parts[..] {
+        [latitude, longitude] => (latitude, longitude, None),
+        [latitude, longitude, altitude] => (latitude, longitude, Some(altitude)),
+        _ => return None,
+    };
+    ((-90.0..=90.0).contains(&latitude) && (-180.0..=180.0).contains(&longitude))
+        .then_some((latitude, longitude, altitude))
+}
+
+/// Parses the `ftyp` and `moov` boxes among `data`'s top-level boxes.
+pub fn metadata(data: &[u8]) -> Result<VideoMetadata, String> {
+    let moov = child(data, b"moov")
+        .ok_or_else(|| "The selected file is not an MP4 or MOV video.".to_string())?;
+    let header = child(moov, b"mvhd").and_then(media_header);
+    let mut metadata = VideoMetadata {
+        brand: child(data, b"ftyp")
+            .and_then(|ftyp| ftyp.get(..4))
+            .map(|brand| String::from_utf8_lossy(brand).into_owned()),
+        creation_time: header
+            .map(|(created, _, _)| created)
+            .filter(|&created| created > 0)
+            .and_then(|created| {
+                DateTime::from_timestamp(created as i64 - QUICKTIME_EPOCH_OFFSET, 0)
+            })
+            .map(|time| time.to_rfc3339()),
+        duration_seconds: header.and_then(|(_, timescale, duration)| seconds(timescale, duration)),
+        tracks: boxes(moov)
+            .filter(|(kind, _)| *kind == b"trak")
+            .filter_map(|(_, trak)| track(trak))
+            .collect(),
+        ..VideoMetadata::default()
+    };
+    if let Some(udta) = child(moov, b"udta") {
+        user_data_tags(udta, &mut metadata.tags);
+    }
+    if let Some(meta) = child(moov, b"meta") {
+        meta_tags(meta, &mut metadata.tags);
+    }
+    let location = metadata
+        .tags
+        .iter()
+        .filter(|tag| tag.key == APPLE_LOCATION_KEY || tag.key == "©xyz")
+        .find_map(|tag| iso6709(&tag.value));
+    if let Some((latitude, longitude, altitude)) = location {
+        metadata.latitude = Some(latitude);
+        metadata.longitude = Some(longitude);
+        metadata.altitude = altitude;
+    }
+    Ok(metadata)
+}
+
+pub fn read(path: &Path) -> Result<VideoMetadata, String> {
+    metadata(&read_header_boxes(path)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn atom(kind: &[u8], body: &[u8]) -> Vec<u8> {
+        let mut out = ((body.len() + 8) as u32).to_be_bytes().to_vec();
+        out.extend_from_slice(kind);
+        out.extend_from_slice(body);
+        out
+    }
+
+    fn full_box(kind: &[u8], version: u8, body: &[u8]) -> Vec<u8> {
+        let mut full = vec![version, 0, 0, 0];
+        full.extend_from_slice(body);
+        atom(kind, &full)
+    }
+
+    /// A 12-second 1920x1080 HEVC iPhone video with QuickTime keys.
+    fn iphone_mov() -> Vec<u8> {
+        // 2024-06-01T10:00:00Z in QuickTime seconds, timescale 600, 12 s.
+        let created = (1_717_236_000 + QUICKTIME_EPOCH_OFFSET) as u32;
+        let mut mvhd = created.to_be_bytes().to_vec();
+        mvhd.extend_from_slice(&[0; 4]);
+        mvhd.extend_from_slice(&600u32.to_be_bytes());
+        mvhd.extend_from_slice(&7200u32.to_be_bytes());
+        let mut tkhd = vec![0; 72];
+        tkhd.extend_from_slice(&(1920u32 << 16).to_be_bytes());
+        tkhd.extend_from_slice(&(1080u32 << 16).to_be_bytes());
+        let mut hdlr = vec![0; 4];
+        hdlr.extend_from_slice(b"vide");
+        let mut stsd = 1u32.to_be_bytes().to_vec();
+        stsd.extend(atom(b"hvc1", &[0; 8]));
+        let mdia = [
+            full_box(b"mdhd", 0, &mvhd),
+            full_box(b"hdlr", 0, &hdlr),
+            atom(b"minf", &atom(b"stbl", &full_box(b"stsd", 0, &stsd))),
+        ]
+        .concat();
+        let trak = [full_box(b"tkhd", 0, &tkhd), atom(b"mdia", &mdia)].concat();
+
+        let mut keys = 2u32.to_be_bytes().to_vec();
+        keys.extend(atom(b"mdta", b"com.apple.quicktime.make"));
+        keys.extend(atom(b"mdta", APPLE_LOCATION_KEY.as_bytes()));
+        let data = |text: &str| {
+            let mut data = 1u32.to_be_bytes().to_vec();
+            data.extend_from_slice(&[0; 4]);
+            data.extend_from_slice(text.as_bytes());
+            atom(b"data", &data)
+        };
+        let ilst = [
+            atom(&1u32.to_be_bytes(), &data("Apple")),
+            atom(&2u32.to_be_bytes(), &data("+38.7139-009.1394+045.000/")),
+        ]
+        .concat();
+        let mut mdta = vec![0; 8];
+        mdta.extend_from_slice(b"mdta");
+        mdta.extend_from_slice(&[0; 12]);
+        let meta = [
+            atom(b"hdlr", &mdta),
+            full_box(b"keys", 0, &keys),
+            atom(b"ilst", &ilst),
+        ]
+        .concat();
+        let mut software = 5u16.to_be_bytes().to_vec();
+        software.extend_from_slice(&[0x15, 0xC7]);
+        software.extend_from_slice(b"17.5");
+        software.push(b'1');
+
+        let moov = [
+            full_box(b"mvhd", 0, &mvhd),
+            atom(b"trak", &trak),
+            atom(b"udta", &atom(b"\xA9swr", &software)),
+            atom(b"meta", &meta),
+        ]
+        .concat();
+        [atom(b"ftyp", b"qt  \0\0\0\0qt  "), atom(b"moov", &moov)].concat()
+    }
+
+    #[test]
+    fn reads_tracks_times_tags_and_location() {
+        let metadata = metadata(&iphone_mov()).unwrap();
+        assert_eq!(metadata.brand.as_deref(), Some("qt  "));
+        assert_eq!(
+            metadata.creation_time.as_deref(),
+            Some("2024-06-01T10:00:00+00:00")
+        );
+        assert_eq!(metadata.duration_seconds, Some(12.0));
+        assert_eq!(
+            metadata.tracks,
+            [VideoTrack {
+                kind: "video".to_string(),
+                codec: Some("hvc1".to_string()),
+                width: Some(1920),
+                height: Some(1080),
+                duration_seconds: Some(12.0),
+            }]
+        );
+        let tags: Vec<(&str, &str)> = metadata
+            .tags
+            .iter()
+            .map(|tag| (tag.key.as_str(), tag.value.as_str()))
+            .collect();
+        assert_eq!(
+            tags,
+            [
+                ("©swr", "17.51"),
+                ("com.apple.quicktime.make", "Apple"),
+                (APPLE_LOCATION_KEY, "+38.7139-009.1394+045.000/"),
+            ]
+        );
+        assert_eq!(
+            (metadata.latitude, metadata.longitude, metadata.altitude),
+            (Some(38.7139), Some(-9.1394), Some(45.0))
+        );
+        assert!(super::metadata(b"\xFF\xD8\xFF\xD9").is_err());
+    }
+
+    #[test]
+    fn mdat_is_skipped_when_reading_from_disk() {
+        let path = crate::fixtures::temp_path("video.mov");
+        let mut file = atom(b"ftyp", b"qt  \0\0\0\0qt  ");
+        file.extend(atom(b"mdat", &vec![0x55; 100_000]));
+        file.extend(&iphone_mov()[20..]);
+        std::fs::write(&path, &file).unwrap();
+        let read = read(&path);
+        std::fs::remove_file(&path).ok();
+        assert_eq!(read.unwrap(), metadata(&iphone_mov()).unwrap());
+
+        assert_eq!(
+            iso6709("+40.7128-074.0060CRSWGS_84/"),
+            Some((40.7128, -74.006, None))
+        );
+        assert_eq!(iso6709("40.7128-074.0060/"), None);
+        assert_eq!(iso6709("+95.0+010.0/"), None);
+    }
+
+    #[test]
+    fn box_sizes_beyond_a_seek_are_rejected() {
+        let path = crate::fixtures::temp_path("huge.mov");
+        let mut file = atom(b"ftyp", b"qt  \0\0\0\0qt  ");
+        file.extend(1u32.to_be_bytes());
+        file.extend(b"free");
+        file.extend(u64::MAX.to_be_bytes());
+        std::fs::write(&path, &file).unwrap();
+        let boxes = read_header_boxes(&path);
+        std::fs::remove_file(&path).ok();
+        assert!(boxes.is_err());
+    }
+}
@@ -0,0 +1,175 @@
+use crate::ExifField;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+
+/// A field attached to a file by the app; it is never written into the file itself.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VirtualField {
+    pub source: String,
+    pub tag: String,
+    pub value: String,
+}
+
+impl VirtualField {
+    pub fn to_exif_field(&self) -> ExifField {
+        ExifField {
+            tag: self.tag.clone(),
+            ifd: format!("{} (virtual)", self.source),
+            value: self.value.clone(),
+        }
+    }
+}
+
+/// Persists virtual fields per file as JSON in the app data directory.
+pub struct VirtualFieldStore {
+    location: PathBuf,
+    entries: Mutex<BTreeMap<String, Vec<VirtualField>>>,
+    /// Batches running; while any is, changes are only saved at its end.
+    batches: AtomicUsize,
+    unsaved: AtomicBool,
+}
+
+/// Saves what a batch changed when it ends, even by panicking.
+struct BatchEnd<'a>(&'a VirtualFieldStore);
+
+impl BatchEnd<'_> {
+    fn finish(self) -> Result<(), String> {
+        let result = self.0.end_batch();
+        std::mem::forget(self);
+        result
+    }
+}
+
+impl Drop for BatchEnd<'_> {
+    fn drop(&mut self) {
+        self.0.end_batch().ok();
+    }
+}
+
+impl VirtualFieldStore {
+    /// Opens the store at `location`, starting empty if the file is missing or unreadable.
+    pub fn open(location: PathBuf) -> Self {
+        let entries = fs::read(&location)
+            .ok()
+            .and_then(|data| serde_json::from_slice(&data).ok())
+            .unwrap_or_default();
+        VirtualFieldStore {
+            location,
+            entries: Mutex::new(entries),
+            batches: AtomicUsize::new(0),
+            unsaved: AtomicBool::new(false),
+        }
+    }
+
+    pub fn fields_for(&self, path: &Path) -> Vec<VirtualField> {
+        let entries = self.entries.lock().expect("virtual field store poisoned");
+        entries
+            .get(&crate::path_key(path))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Replaces every field `source` previously attached to `path`.
+    pub fn replace_source(
+        &self,
+        path: &Path,
+        source: &str,
+        fields: Vec<VirtualField>,
+    ) -> Result<(), String> {
+        let mut entries = self.entries.lock().expect("virtual field store poisoned");
+        let key = crate::path_key(path);
+        let existing = entries.entry(key.clone()).or_default();
+        existing.retain(|field| field.source != source);
+        existing.extend(fields);
+        if existing.is_empty() {
+            entries.remove(&key);
+        }
+        if self.batches.load(Ordering::SeqCst) > 0 {
+            self.unsaved.store(true, Ordering::SeqCst);
+            return Ok(());
+        }
+        self.persist(&entries)
+    }
+
+    /// Runs `work`, which may replace the fields of many files, and saves
+    /// the store once at the end instead of after every file.
+    pub fn batch<T>(&self, work: impl FnOnce() -> T) -> Result<T, String> {
+        self.batches.fetch_add(1, Ordering::SeqCst);
+        let end = BatchEnd(self);
+        let result = work();
+        end.finish()?;
+        Ok(result)
+    }
+
+    fn end_batch(&self) -> Result<(), String> {
+        if self.batches.fetch_sub(1, Ordering::SeqCst) > 1
+            || !self.unsaved.swap(false, Ordering::SeqCst)
+        {
+            return Ok(());
+        }
+        let entries = self.entries.lock().expect("virtual field store poisoned");
+        self.persist(&entries)
+    }
+
+    fn persist(&self, entries: &BTreeMap<String, Vec<VirtualField>>) -> Result<(), String> {
+        if let Some(parent) = self.location.parent() {
+            fs::create_dir_all(parent).map_err(|error| error.to_string())?;
+        }
+        let data = serde_json::to_vec_pretty(entries).map_err(|error| error.to_string())?;
+        crate::write_file_atomically(&self.location, &data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fields_survive_reopening_and_sources_are_replaced_independently() {
+        let location = crate::fixtures::temp_path("virtual_fields.json");
+        let photo = Path::new("/photos/a.jpg");
+        let field = |source: &str, value: &str| VirtualField {
+            source: source.to_string(),
+            tag: "Note".to_string(),
+            value: value.to_string(),
+        };
+
+        let store = VirtualFieldStore::open(location.clone());
+        store
+            .replace_source(photo, "Weather", vec![field("Weather", "Sunny")])
+            .unwrap();
+        store
+            .replace_source(photo, "Album", vec![field("Album", "Trip")])
+            .unwrap();
+        store
+            .replace_source(photo, "Weather", vec![field("Weather", "Rain")])
+            .unwrap();
+
+        std::fs::remove_file(&location).ok();
+        let saved_during_batch = store
+            .batch(|| {
+                store
+                    .replace_source(photo, "Weather", vec![field("Weather", "Rain")])
+                    .unwrap();
+                location.exists()
+            })
+            .unwrap();
+
+        let reopened = VirtualFieldStore::open(location.clone());
+        std::fs::remove_file(&location).ok();
+
+        assert!(!saved_during_batch);
+        let fields = reopened.fields_for(photo);
+        assert_eq!(fields.len(), 2);
+        assert!(fields.contains(&field("Weather", "Rain")));
+        assert_eq!(fields[0].to_exif_field().ifd, "Album (virtual)");
+    }
+}
@@ -0,0 +1,275 @@
+use crate::{iptc, xmp};
+use serde::Serialize;
+use std::{fs, path::Path};
+
+/// Suggestions offered for a keyword outside the vocabulary.
+const MAX_SUGGESTIONS: usize = 5;
+/// Typos within this many edits of a term are suggested.
+const MAX_EDIT_DISTANCE: usize = 2;
+
+/// One term of a keyword thesaurus.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Term {
+    name: String,
+    /// Ancestors from the top of the hierarchy; empty in a flat list.
+    parents: Vec<String>,
+    synonyms: Vec<String>,
+}
+
+impl Term {
+    /// `Places|Europe|Portugal`, the form Lightroom writes to
+    /// `lr:hierarchicalSubject`.
+    fn hierarchy(&self) -> String {
+        self.parents
+            .iter()
+            .chain([&self.name])
+            .cloned()
+            .collect::<Vec<_>>()
+            .join("|")
+    }
+}
+
+/// A controlled vocabulary of keywords.
+///
+/// Read from a flat list (one keyword per line) or Lightroom's keyword
+/// export, where tabs nest a term under the one above it, `{braces}` mark a
+/// synonym of the enclosing term and `[brackets]` a category used only to
+/// organise the list.
+#[derive(Debug, Clone, Default)]
+pub struct Vocabulary {
+    terms: Vec<Term>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum KeywordStatus {
+    Known,
+    /// A synonym; `preferred` holds the term it stands for.
+    Synonym,
+    Unknown,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeywordCheck {
+    keyword: String,
+    status: KeywordStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    preferred: Option<String>,
+    /// Close terms for an unknown keyword, best first.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    suggestions: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeywordAuditEntry {
+    path: String,
+    /// The file's keywords that are synonyms or outside the vocabulary.
+    flagged: Vec<KeywordCheck>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeywordAudit {
+    /// Files with at least one flagged keyword.
+    files: Vec<KeywordAuditEntry>,
+    checked: usize,
+}
+
+/// Levenshtein distance, compared case-insensitively.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.to_lowercase().chars().collect();
+    let b: Vec<char> = b.to_lowercase().chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.iter().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != b_char);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+impl Vocabulary {
+    pub fn parse(text: &str) -> Vocabulary {
+        let mut terms: Vec<Term> = Vec::new();
+        // The term or category at each depth above the current line.
+        let mut ancestry: Vec<(String, Option<usize>)> = Vec::new();
+        for line in text.lines() {
+            let depth = line.chars().take_while(|c| *c == '\t').count();
+            let entry = line.trim();
+            if entry.is_empty() {
+                continue;
+            }
+            ancestry.truncate(depth);
+            if let Some(synonym) = entry.strip_prefix('{').and_then(|s| s.strip_suffix('}')) {
+                if let Some(term) = ancestry.last().and_then(|(_, term)| *term) {
+                    terms[term].synonyms.push(synonym.trim().to_string());
+                }
+                continue;
+            }
+            let parents = ancestry.iter().map(|(name, _)| name.clone()).collect();
+            match entry.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+                Some(category) => ancestry.push((category.trim().to_string(), None)),
+                None => {
+                    terms.push(Term {
+                        name: entry.to_string(),
+                        parents,
+                        synonyms: Vec::new(),
+                    });
+                    ancestry.push((entry.to_string(), Some(terms.len() - 1)));
+                }
+            }
+        }
+        Vocabulary { terms }
+    }
+
+    pub fn load(path: &Path) -> Result<Vocabulary, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|_| "The keyword vocabulary could not be read.".to_string())?;
+        let vocabulary = Vocabulary::parse(&text);
+        if vocabulary.terms.is_empty() {
+            return Err("The keyword vocabulary has no terms.".to_string());
+        }
+        Ok(vocabulary)
+    }
+
+    fn suggestions(&self, keyword: &str) -> Vec<String> {
+        let lower = keyword.to_lowercase();
+        let mut scored: Vec<(usize, &str)> = self
+            .terms
+            .iter()
+            .flat_map(|term| {
+                std::iter::once(&term.name)
+                    .chain(&term.synonyms)
+                    .map(move |name| (term, name))
+            })
+            .filter_map(|(term, name)| {
+                let distance = edit_distance(keyword, name);
+                let related =
+                    name.to_lowercase().contains(&lower) || lower.contains(&name.to_lowercase());
+                (distance <= MAX_EDIT_DISTANCE || related).then_some((distance, term.name.as_str()))
+            })
+            .collect();
+        scored.sort();
+        let mut suggestions: Vec<String> = Vec::new();
+        for (_, name) in scored {
+            if !suggestions.iter().any(|seen| seen == name) {
+                suggestions.push(name.to_string());
+            }
+        }
+        suggestions.truncate(MAX_SUGGESTIONS);
+        suggestions
+    }
+
+    /// Checks one keyword, given as a bare term or a `|`-separated hierarchy.
+    pub fn check(&self, keyword: &str) -> KeywordCheck {
+        let keyword = keyword.trim();
+        let matches = |name: &str| name.eq_ignore_ascii_case(keyword);
+        let known = self
+            .terms
+            .iter()
+            .any(|term| matches(&term.name) || matches(&term.hierarchy()));
+        let preferred = self
+            .terms
+            .iter()
+            .find(|term| term.synonyms.iter().any(|synonym| matches(synonym)));
+        let (status, preferred, suggestions) = if known {
+            (KeywordStatus::Known, None, Vec::new())
+        } else if let Some(term) = preferred {
+            (KeywordStatus::Synonym, Some(term.name.clone()), Vec::new())
+        } else {
+            (KeywordStatus::Unknown, None, self.suggestions(keyword))
+        };
+        KeywordCheck {
+            keyword: keyword.to_string(),
+            status,
+            preferred,
+            suggestions,
+        }
+    }
+}
+
+/// The keywords of a file from XMP `dc:subject` and IPTC, without repeats.
+fn file_keywords(data: &[u8]) -> Vec<String> {
+    let mut keywords: Vec<String> = Vec::new();
+    let xmp_keywords = xmp::packet(data)
+        .map(|packet| xmp::items(packet, "dc:subject"))
+        .unwrap_or_default();
+    let datasets = iptc::read(data);
+    for keyword in xmp_keywords
+        .into_iter()
+        .chain(iptc::values(&datasets, iptc::KEYWORDS))
+    {
+        let keyword = keyword.trim();
+        if !keyword.is_empty()
+            && !keywords
+                .iter()
+                .any(|seen| seen.eq_ignore_ascii_case(keyword))
+        {
+            keywords.push(keyword.to_string());
+        }
+    }
+    keywords
+}
+
+/// Checks the keywords of every image below `root` against `vocabulary`.
+pub fn audit(root: &Path, vocabulary: &Vocabulary) -> KeywordAudit {
+    let mut files = Vec::new();
+    let mut checked = 0;
+    crate::walk_files(root, &mut |path| {
+        if !crate::is_supported_image(path) {
+            return;
+        }
+        let Ok(data) = crate::memory_budget::load_scan_data(path) else {
+            return;
+        };
+        checked += 1;
+        let flagged: Vec<KeywordCheck> = file_keywords(&data)
+            .iter()
+            .map(|keyword| vocabulary.check(keyword))
+            .filter(|check| check.status != KeywordStatus::Known)
+            .collect();
+        if !flagged.is_empty() {
+            files.push(KeywordAuditEntry {
+                path: path.to_string_lossy().into_owned(),
+                flagged,
+            });
+        }
+    });
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    KeywordAudit { files, checked }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIGHTROOM_LIST: &str =
+        "[Places]\n\tEurope\n\t\tPortugal\n\t\t\tPorto\n\t\t\t\t{Oporto}\n\
+        Animals\n\tDog\n\t\t{Canine}\n";
+
+    #[test]
+    fn lightroom_hierarchies_synonyms_and_categories_are_read() {
+        let vocabulary = Vocabulary::parse(LIGHTROOM_LIST);
+        let status = |keyword: &str| vocabulary.check(keyword).status;
+        assert_eq!(status("porto"), KeywordStatus::Known);
+        assert_eq!(status("Places|Europe|Portugal|Porto"), KeywordStatus::Known);
+        assert_eq!(status("Places"), KeywordStatus::Unknown);
+        let synonym = vocabulary.check("Oporto");
+        assert_eq!(synonym.status, KeywordStatus::Synonym);
+        assert_eq!(synonym.preferred.as_deref(), Some("Porto"));
+    }
+
+    #[test]
+    fn unknown_keywords_get_close_suggestions() {
+        let vocabulary = Vocabulary::parse("Dog\nDogs at play\nCat\n");
+        let check = vocabulary.check("Dgo");
+        assert_eq!(check.status, KeywordStatus::Unknown);
+        assert_eq!(check.suggestions, ["Dog"]);
+        assert_eq!(vocabulary.check("dog").suggestions, Vec::<String>::new());
+    }
+}
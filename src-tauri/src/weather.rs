@@ -0,0 +1,253 @@
+use crate::{
+    gps::distance_km,
+    timezone::located_capture,
+    virtual_fields::{VirtualField, VirtualFieldStore},
+};
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use std::path::Path;
+
+pub const WEATHER_SOURCE: &str = "Weather";
+
+/// Observations further away than this are not attributed to a photo.
+const MAX_DISTANCE_KM: f64 = 50.0;
+const MAX_TIME_GAP_MINUTES: i64 = 180;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WeatherObservation {
+    pub time: NaiveDateTime,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub temperature_c: Option<f64>,
+    pub conditions: Option<String>,
+}
+
+/// Looks up historical weather near a position at a UTC instant.
+///
+/// The bundled provider reads a local CSV file so enrichment works offline;
+/// online services can implement the same trait.
+pub trait WeatherProvider {
+    fn lookup(
+        &self,
+        latitude: f64,
+        longitude: f64,
+        utc: NaiveDateTime,
+    ) -> Option<WeatherObservation>;
+}
+
+/// Observations loaded from a CSV file with the header
+/// `time,latitude,longitude,temperature_c,conditions`, where `time` is UTC in
+/// RFC 3339 or `YYYY-MM-DD HH:MM:SS` form and the last two columns may be empty.
+pub struct CsvWeatherProvider {
+    observations: Vec<WeatherObservation>,
+}
+
+impl CsvWeatherProvider {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|error| format!("Unable to read the weather dataset: {}", error))?;
+        Self::parse(&text)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut lines = text.lines().filter(|line| !line.trim().is_empty());
+        match lines.next() {
+            Some(header) if header.trim().starts_with("time,latitude,longitude") => {}
+            _ => {
+                return Err(
+                    "The weather dataset must start with a time,latitude,longitude header."
+                        .to_string(),
+                )
+            }
+        }
+
+        let observations = lines
+            .enumerate()
+            .map(|(index, line)| {
+                parse_row(line)
+                    .ok_or_else(|| format!("Weather dataset row {} is malformed.", index + 2))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(CsvWeatherProvider { observations })
+    }
+}
+
+fn parse_row(line: &str) -> Option<WeatherObservation> {
+    let mut columns = line.splitn(5, ',').map(str::trim);
+    let time = columns.next()?;
+    let time = chrono::DateTime::parse_from_rfc3339(time)
+        .map(|time| time.naive_utc())
+        .or_else(|_| NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M:%S"))
+        .ok()?;
+    let latitude = columns.next()?.parse().ok()?;
+    let longitude = columns.next()?.parse().ok()?;
+    let temperature_c = match columns.next().unwrap_or("") {
+        "" => None,
+        value => Some(value.parse().ok()?),
+    };
+    let conditions = columns
+        .next()
+        .map(|value| value.trim_matches('"'))
+        .filter(|value| !value.is_empty())
+        .map(str::to_string);
+    Some(WeatherObservation {
+        time,
+        latitude,
+        longitude,
+        temperature_c,
+        conditions,
+    })
+}
+
+impl WeatherProvider for CsvWeatherProvider {
+    fn lookup(
+        &self,
+        latitude: f64,
+        longitude: f64,
+        utc: NaiveDateTime,
+    ) -> Option<WeatherObservation> {
+        // Trade distance against time: one hour counts as much as 25 km.
+        self.observations
+            .iter()
+            .filter_map(|observation| {
+                let distance = distance_km(
+                    (latitude, longitude),
+                    (observation.latitude, observation.longitude),
+                );
+                let gap = (observation.time - utc).num_minutes().abs();
+                (distance <= MAX_DISTANCE_KM && gap <= MAX_TIME_GAP_MINUTES)
+                    .then_some((distance + gap as f64 * 25.0 / 60.0, observation))
+            })
+            .min_by(|(a, _), (b, _)| a.total_cmp(b))
+            .map(|(_, observation)| observation.clone())
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WeatherEnrichment {
+    path: String,
+    temperature_c: Option<f64>,
+    conditions: Option<String>,
+    matched: bool,
+    error: Option<String>,
+}
+
+impl WeatherEnrichment {
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+}
+
+pub fn observation_fields(observation: &WeatherObservation) -> Vec<VirtualField> {
+    let mut fields = Vec::new();
+    let mut push = |tag: &str, value: String| {
+        fields.push(VirtualField {
+            source: WEATHER_SOURCE.to_string(),
+            tag: tag.to_string(),
+            value,
+        })
+    };
+    if let Some(temperature) = observation.temperature_c {
+        push("Temperature", format!("{:.1} °C", temperature));
+    }
+    if let Some(conditions) = &observation.conditions {
+        push("Conditions", conditions.clone());
+    }
+    push(
+        "Observed at",
+        observation.time.format("%Y-%m-%d %H:%M UTC").to_string(),
+    );
+    fields
+}
+
+/// Annotates a photo with the closest observation, replacing any earlier
+/// weather fields. The image file itself is never modified.
+pub fn enrich_file(
+    path: &Path,
+    provider: &dyn WeatherProvider,
+    store: &VirtualFieldStore,
+    dry_run: bool,
+) -> WeatherEnrichment {
+    let mut enrichment = WeatherEnrichment {
+        path: path.to_string_lossy().into_owned(),
+        temperature_c: None,
+        conditions: None,
+        matched: false,
+        error: None,
+    };
+
+    let result = (|| {
+        let data = crate::load_file_data(path)?;
+        let exif = crate::read_exif_container(&data)
+            .ok_or_else(|| "The selected file has no EXIF metadata.".to_string())?;
+        let ((latitude, longitude), utc) = located_capture(&exif)
+            .ok_or_else(|| "The selected file has no GPS position or capture time.".to_string())?;
+        let fields = match provider.lookup(latitude, longitude, utc) {
+            Some(observation) => {
+                enrichment.matched = true;
+                enrichment.temperature_c = observation.temperature_c;
+                enrichment.conditions = observation.conditions.clone();
+                observation_fields(&observation)
+            }
+            None => Vec::new(),
+        };
+        if dry_run {
+            return Ok(());
+        }
+        store.replace_source(path, WEATHER_SOURCE, fields)
+    })();
+
+    if let Err(error) = result {
+        enrichment.error = Some(error);
+    }
+    enrichment
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    const DATASET: &str = "time,latitude,longitude,temperature_c,conditions
+2024-07-01T12:00:00Z,48.85,2.35,24.5,Sunny
+2024-07-01 15:00:00,48.85,2.35,27.0,\"Partly cloudy, light wind\"
+2024-07-01T12:00:00Z,51.5,-0.12,18.0,
+";
+
+    fn utc(hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 7, 1)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn closest_observation_in_space_and_time_wins() {
+        let provider = CsvWeatherProvider::parse(DATASET).expect("dataset should parse");
+
+        let noon = provider.lookup(48.86, 2.34, utc(12, 20)).unwrap();
+        assert_eq!(noon.conditions.as_deref(), Some("Sunny"));
+
+        let afternoon = provider.lookup(48.86, 2.34, utc(14, 10)).unwrap();
+        assert_eq!(
+            afternoon.conditions.as_deref(),
+            Some("Partly cloudy, light wind")
+        );
+
+        let london = provider.lookup(51.5, -0.1, utc(12, 0)).unwrap();
+        assert_eq!(london.temperature_c, Some(18.0));
+        assert_eq!(london.conditions, None);
+
+        assert!(provider.lookup(40.71, -74.0, utc(12, 0)).is_none());
+        assert!(provider.lookup(48.85, 2.35, utc(20, 0)).is_none());
+    }
+
+    #[test]
+    fn malformed_rows_are_reported_with_their_line() {
+        let error = CsvWeatherProvider::parse("time,latitude,longitude\nyesterday,1,2\n")
+            .err()
+            .unwrap();
+        assert_eq!(error, "Weather dataset row 2 is malformed.");
+    }
+}
@@ -0,0 +1,93 @@
+/// Some encoders start the `EXIF` chunk with the JPEG APP1 header, which
+/// the WebP container spec does not have.
+const EXIF_PREFIX: &[u8] = b"Exif\0\0";
+
+pub fn is_webp(data: &[u8]) -> bool {
+    data.len() >= 12 && data.starts_with(b"RIFF") && &data[8..12] == b"WEBP"
+}
+
+/// The fourcc and payload of each chunk in a WebP file. A RIFF size larger
+/// than the file is tolerated; walking stops at the first truncated chunk.
+fn chunks(data: &[u8]) -> impl Iterator<Item = (&[u8], &[u8])> {
+    let mut rest = if is_webp(data) { &data[12..] } else { &[][..] };
+    std::iter::from_fn(move || {
+        let kind = rest.get(..4)?;
+        let size = u32::from_le_bytes(rest.get(4..8)?.try_into().ok()?) as usize;
+        let payload = rest.get(8..8usize.checked_add(size)?)?;
+        // Payloads of odd size are followed by a padding byte.
+        rest = rest.get(8 + size + size % 2..).unwrap_or_default();
+        Some((kind, payload))
+    })
+}
+
+fn chunk<'a>(data: &'a [u8], kind: &[u8; 4]) -> Option<&'a [u8]> {
+    chunks(data)
+        .find(|(found, _)| found == kind)
+        .map(|(_, payload)| payload)
+}
+
+/// The TIFF-encoded EXIF of a WebP file, from its `EXIF` chunk.
+pub fn exif(data: &[u8]) -> Option<&[u8]> {
+    let payload = chunk(data, b"EXIF")?;
+    Some(payload.strip_prefix(EXIF_PREFIX).unwrap_or(payload))
+}
+
+/// The XMP packet of a WebP file, from its `XMP ` chunk.
+pub fn xmp(data: &[u8]) -> Option<&[u8]> {
+    chunk(data, b"XMP ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{ascii_field, tiff_from_fields};
+    use exif::Tag;
+
+    fn webp(chunks: &[(&[u8; 4], &[u8])]) -> Vec<u8> {
+        let mut body = b"WEBP".to_vec();
+        for (kind, payload) in chunks {
+            body.extend_from_slice(*kind);
+            body.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+            body.extend_from_slice(payload);
+            if payload.len() % 2 == 1 {
+                body.push(0);
+            }
+        }
+        let mut data = b"RIFF".to_vec();
+        data.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        data.extend(body);
+        data
+    }
+
+    #[test]
+    fn exif_and_xmp_chunks_feed_the_field_list() {
+        let mut exif = EXIF_PREFIX.to_vec();
+        exif.extend(tiff_from_fields(&[ascii_field(Tag::Model, "Pixel 8")]));
+        let packet = br#"<x:xmpmeta xmlns:x="adobe:ns:meta/"><rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#"><rdf:Description xmlns:xmp="http://ns.adobe.com/xap/1.0/" xmp:Rating="3"/></rdf:RDF></x:xmpmeta>"#;
+        let data = webp(&[
+            (b"VP8X", &[0x0C, 0, 0, 0, 0, 0, 0, 0, 0, 0]),
+            (b"VP8 ", b"odd"),
+            (b"EXIF", &exif),
+            (b"XMP ", packet),
+        ]);
+
+        assert_eq!(xmp(&data), Some(packet.as_slice()));
+        let fields = crate::collect_fields_from_bytes(&data).unwrap();
+        let value = |tag: &str| {
+            fields
+                .iter()
+                .find(|field| field.tag == tag)
+                .map(|field| field.value.clone())
+        };
+        assert_eq!(value("Model").as_deref(), Some("\"Pixel 8\""));
+        assert_eq!(value("xmp:Rating").as_deref(), Some("3"));
+    }
+
+    #[test]
+    fn truncated_files_stop_the_walk() {
+        let data = webp(&[(b"VP8 ", b"abcd"), (b"EXIF", b"II*\0")]);
+        assert_eq!(exif(&data), Some(b"II*\0".as_slice()));
+        assert_eq!(exif(&data[..data.len() - 1]), None);
+        assert_eq!(chunks(b"RIFF\0\0\0\0WEBPVP8").count(), 0);
+    }
+}
@@ -0,0 +1,427 @@
+use crate::path_scope::{self, ScopeViolation};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, ErrorKind, Write},
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, SystemTime},
+};
+
+const LOCK_ATTEMPTS: u32 = 5;
+const LOCK_RETRY_DELAY: Duration = Duration::from_millis(200);
+/// Locks older than this were left behind by a crashed writer and are broken.
+const STALE_LOCK_AGE: Duration = Duration::from_secs(10 * 60);
+
+/// A one-click fix offered for a [`WriteProblem`] when applying it is safe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum WriteAssist {
+    /// Give the owner write permission on the file.
+    ClearReadOnly,
+    /// Remove a lock file left behind by one of the user's own sessions.
+    RemoveLock,
+}
+
+/// Why a write was refused, specific enough for the UI to explain and fix.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase", tag = "kind")]
+pub enum WriteProblem {
+    /// Another writer holds the lock file next to the image.
+    Locked { holder: String },
+    /// The file changed on disk after it was read.
+    Modified,
+    /// The file is marked read-only.
+    ReadOnlyFile,
+    /// The OS refused access to the file or its folder.
+    PermissionDenied,
+    /// The whole volume is mounted read-only.
+    ReadOnlyVolume,
+    /// Another program has the file open exclusively.
+    InUse,
+    /// The path leaves the folders the app may access.
+    Security { violation: ScopeViolation },
+}
+
+impl WriteProblem {
+    pub fn message(&self) -> String {
+        match self {
+            WriteProblem::Locked { holder } => {
+                format!("The file is being edited by {}; try again later.", holder)
+            }
+            WriteProblem::Modified => {
+                "The file was changed by someone else after it was read; reload it and try again."
+                    .to_string()
+            }
+            WriteProblem::ReadOnlyFile => "The file is read-only.".to_string(),
+            WriteProblem::PermissionDenied => {
+                "You do not have permission to change this file.".to_string()
+            }
+            WriteProblem::ReadOnlyVolume => "The file is on a read-only volume.".to_string(),
+            WriteProblem::InUse => "The file is open in another program.".to_string(),
+            WriteProblem::Security { violation } => violation.message(),
+        }
+    }
+
+    pub fn hint(&self) -> &'static str {
+        match self {
+            WriteProblem::Locked { .. } => {
+                "Wait for the other session to finish. If it crashed, remove the lock file next to the image."
+            }
+            WriteProblem::Modified => "Reload the file to pick up the other changes, then reapply your edit.",
+            WriteProblem::ReadOnlyFile => "Clear the read-only flag (chmod u+w on macOS/Linux) and retry.",
+            WriteProblem::PermissionDenied => {
+                "Ask the owner of the file or folder for write access, or copy the file somewhere you can write to."
+            }
+            WriteProblem::ReadOnlyVolume => {
+                "Remount the volume read-write or copy the files to a writable location."
+            }
+            WriteProblem::InUse => "Close the file in the other program and retry.",
+            WriteProblem::Security { .. } => {
+                "Move the file into one of the allowed folders, or add its folder in the settings."
+            }
+        }
+    }
+
+    pub fn assist(&self) -> Option<WriteAssist> {
+        match self {
+            WriteProblem::ReadOnlyFile => Some(WriteAssist::ClearReadOnly),
+            WriteProblem::Locked { holder } if is_own_orphaned_lock(holder) => {
+                Some(WriteAssist::RemoveLock)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// A [`WriteProblem`] with the remediation the UI should show alongside it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WriteProblemReport {
+    #[serde(flatten)]
+    pub problem: WriteProblem,
+    pub hint: String,
+    pub assist: Option<WriteAssist>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WriteError {
+    Problem(WriteProblem),
+    Failed(String),
+}
+
+impl WriteError {
+    pub fn problem(&self) -> Option<&WriteProblem> {
+        match self {
+            WriteError::Problem(problem) => Some(problem),
+            WriteError::Failed(_) => None,
+        }
+    }
+
+    pub fn report(&self) -> Option<WriteProblemReport> {
+        self.problem().map(|problem| WriteProblemReport {
+            problem: problem.clone(),
+            hint: problem.hint().to_string(),
+            assist: problem.assist(),
+        })
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            WriteError::Problem(problem) => problem.message(),
+            WriteError::Failed(message) => message.clone(),
+        }
+    }
+}
+
+impl From<String> for WriteError {
+    fn from(message: String) -> Self {
+        WriteError::Failed(message)
+    }
+}
+
+/// Maps OS errors onto the problems users can act on.
+fn classify(path: &Path, error: io::Error) -> WriteError {
+    let problem = match error.kind() {
+        ErrorKind::PermissionDenied if is_read_only(path) => WriteProblem::ReadOnlyFile,
+        ErrorKind::PermissionDenied => WriteProblem::PermissionDenied,
+        ErrorKind::ReadOnlyFilesystem => WriteProblem::ReadOnlyVolume,
+        ErrorKind::ResourceBusy => WriteProblem::InUse,
+        // ERROR_SHARING_VIOLATION and ERROR_LOCK_VIOLATION on Windows.
+        _ if cfg!(windows) && matches!(error.raw_os_error(), Some(32 | 33)) => WriteProblem::InUse,
+        _ => return WriteError::Failed(error.to_string()),
+    };
+    WriteError::Problem(problem)
+}
+
+fn is_read_only(path: &Path) -> bool {
+    fs::metadata(path)
+        .map(|metadata| metadata.permissions().readonly())
+        .unwrap_or(false)
+}
+
+/// Size and modification time captured when a file is read, compared again
+/// right before it is replaced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileStamp {
+    exists: bool,
+    len: u64,
+    modified: Option<SystemTime>,
+}
+
+impl FileStamp {
+    pub fn of(path: &Path) -> Result<Self, String> {
+        let metadata = fs::metadata(path).map_err(|error| error.to_string())?;
+        Ok(FileStamp {
+            exists: true,
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+        })
+    }
+
+    /// The stamp of a file that is about to be created; writing with it
+    /// fails if the file has appeared in the meantime.
+    pub fn absent() -> Self {
+        FileStamp {
+            exists: false,
+            len: 0,
+            modified: None,
+        }
+    }
+
+    fn current(path: &Path) -> Result<Self, String> {
+        match path.try_exists() {
+            Ok(false) => Ok(FileStamp::absent()),
+            _ => FileStamp::of(path),
+        }
+    }
+}
+
+fn lock_path(target: &Path) -> Result<PathBuf, String> {
+    let file_name = target
+        .file_name()
+        .ok_or_else(|| "The selected path is not a file.".to_string())?;
+    Ok(target.with_file_name(format!(".{}.exif-viewer.lock", file_name.to_string_lossy())))
+}
+
+fn lock_holder() -> String {
+    format!(
+        "{} (pid {})",
+        crate::oplog::current_user(),
+        std::process::id()
+    )
+}
+
+/// A lock written by the same user from a process other than this one, which
+/// can only be a session that ended without cleaning up.
+fn is_own_orphaned_lock(holder: &str) -> bool {
+    let user_prefix = format!("{} (pid ", crate::oplog::current_user());
+    holder.starts_with(&user_prefix) && holder != lock_holder()
+}
+
+/// A `.name.exif-viewer.lock` file beside the target, removed on drop.
+struct LockGuard {
+    path: PathBuf,
+}
+
+impl LockGuard {
+    fn acquire(target: &Path, attempts: u32) -> Result<Self, WriteError> {
+        let path = lock_path(target)?;
+        for attempt in 0..attempts {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut file) => {
+                    writeln!(file, "{}", lock_holder()).ok();
+                    return Ok(LockGuard { path });
+                }
+                Err(error) if error.kind() == ErrorKind::AlreadyExists => {
+                    if is_stale(&path) {
+                        fs::remove_file(&path).ok();
+                        continue;
+                    }
+                    if attempt + 1 < attempts {
+                        thread::sleep(LOCK_RETRY_DELAY);
+                    }
+                }
+                Err(error) => return Err(classify(target, error)),
+            }
+        }
+
+        let holder = read_holder(&path).unwrap_or_else(|| "another user".to_string());
+        Err(WriteError::Problem(WriteProblem::Locked { holder }))
+    }
+}
+
+impl Drop for LockGuard {
+    fn drop(&mut self) {
+        fs::remove_file(&self.path).ok();
+    }
+}
+
+fn read_holder(lock: &Path) -> Option<String> {
+    fs::read_to_string(lock)
+        .ok()
+        .map(|text| text.trim().to_string())
+        .filter(|text| !text.is_empty())
+}
+
+fn is_stale(lock: &Path) -> bool {
+    fs::metadata(lock)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| modified.elapsed().ok())
+        .is_some_and(|age| age > STALE_LOCK_AGE)
+}
+
+/// Replaces `path` with `contents` while holding its lock file, refusing when
+/// the file no longer matches `expected`; [`FileStamp::absent`] creates it. Safe on shared network folders as
+/// long as every writer honours the lock file.
+pub fn write_checked(path: &Path, expected: FileStamp, contents: &[u8]) -> Result<(), WriteError> {
+    path_scope::check_write(path)
+        .map_err(|violation| WriteError::Problem(WriteProblem::Security { violation }))?;
+    // Replacing via rename would silently succeed on a read-only file, so the
+    // flag is honoured explicitly.
+    if is_read_only(path) {
+        return Err(WriteError::Problem(WriteProblem::ReadOnlyFile));
+    }
+    let _lock = LockGuard::acquire(path, LOCK_ATTEMPTS)?;
+    if FileStamp::current(path)? != expected {
+        return Err(WriteError::Problem(WriteProblem::Modified));
+    }
+    crate::replace_file(path, contents).map_err(|error| classify(path, error))
+}
+
+/// Writes `contents` to `path`, which must not exist yet, under the same
+/// lock and checks as [`write_checked`].
+pub fn write_new(path: &Path, contents: &[u8]) -> Result<(), WriteError> {
+    match write_checked(path, FileStamp::absent(), contents) {
+        Err(WriteError::Problem(WriteProblem::Modified)) => {
+            Err(format!("{} already exists.", path.display()).into())
+        }
+        result => result,
+    }
+}
+
+/// Applies a [`WriteAssist`] offered for `path`, re-checking that it is still safe.
+pub fn apply_assist(path: &Path, assist: WriteAssist) -> Result<(), String> {
+    path_scope::check_write(path).map_err(|violation| violation.message())?;
+    match assist {
+        WriteAssist::ClearReadOnly => {
+            let metadata = fs::metadata(path).map_err(|error| error.to_string())?;
+            let mut permissions = metadata.permissions();
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                permissions.set_mode(permissions.mode() | 0o200);
+            }
+            #[cfg(not(unix))]
+            #[allow(clippy::permissions_set_readonly_false)]
+            permissions.set_readonly(false);
+            fs::set_permissions(path, permissions)
+                .map_err(|error| format!("Unable to make the file writable: {}", error))
+        }
+        WriteAssist::RemoveLock => {
+            let lock = lock_path(path)?;
+            match read_holder(&lock) {
+                Some(holder) if is_own_orphaned_lock(&holder) => fs::remove_file(&lock)
+                    .map_err(|error| format!("Unable to remove the lock file: {}", error)),
+                Some(holder) => Err(format!(
+                    "The lock belongs to {} and cannot be removed from here.",
+                    holder
+                )),
+                None => Ok(()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temporary_file(name: &str) -> PathBuf {
+        let path = crate::fixtures::temp_path(name);
+        fs::write(&path, b"original").unwrap();
+        path
+    }
+
+    #[test]
+    fn held_lock_is_reported_with_its_holder() {
+        let path = temporary_file("held.jpg");
+        let held = LockGuard::acquire(&path, 1).expect("first lock should succeed");
+        let error = LockGuard::acquire(&path, 1).err().unwrap();
+        drop(held);
+        let again = LockGuard::acquire(&path, 1);
+        fs::remove_file(&path).ok();
+
+        match error.problem() {
+            Some(WriteProblem::Locked { holder }) => assert!(holder.contains("pid")),
+            other => panic!("expected a lock conflict, got {:?}", other),
+        }
+        // Our own live session's lock must never be offered for removal.
+        assert_eq!(error.report().unwrap().assist, None);
+        assert!(again.is_ok());
+    }
+
+    #[test]
+    fn changes_since_read_abort_the_write() {
+        let path = temporary_file("changed.jpg");
+        let stamp = FileStamp::of(&path).unwrap();
+        fs::write(&path, b"edited elsewhere").unwrap();
+
+        let error = write_checked(&path, stamp, b"ours").err().unwrap();
+        // A file expected to be new must not have been created meanwhile.
+        let appeared = write_checked(&path, FileStamp::absent(), b"ours")
+            .err()
+            .unwrap();
+        let contents = fs::read(&path).unwrap();
+        let fresh = path.with_extension("xmp");
+        let created = write_checked(&fresh, FileStamp::absent(), b"new");
+        let created_contents = fs::read(&fresh).ok();
+        fs::remove_file(&path).ok();
+        fs::remove_file(&fresh).ok();
+
+        assert_eq!(error.problem(), Some(&WriteProblem::Modified));
+        assert_eq!(appeared.problem(), Some(&WriteProblem::Modified));
+        assert_eq!(contents, b"edited elsewhere");
+        assert!(created.is_ok());
+        assert_eq!(created_contents.as_deref(), Some(&b"new"[..]));
+    }
+
+    #[test]
+    fn read_only_files_are_refused_until_the_assist_is_applied() {
+        let path = temporary_file("readonly.jpg");
+        let mut permissions = fs::metadata(&path).unwrap().permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(&path, permissions).unwrap();
+
+        let stamp = FileStamp::of(&path).unwrap();
+        let error = write_checked(&path, stamp, b"ours").err().unwrap();
+        let report = error.report().unwrap();
+        assert_eq!(report.problem, WriteProblem::ReadOnlyFile);
+        assert_eq!(report.assist, Some(WriteAssist::ClearReadOnly));
+
+        apply_assist(&path, WriteAssist::ClearReadOnly).unwrap();
+        let stamp = FileStamp::of(&path).unwrap();
+        let written = write_checked(&path, stamp, b"ours");
+        let contents = fs::read(&path).unwrap();
+        fs::remove_file(&path).ok();
+
+        assert!(written.is_ok());
+        assert_eq!(contents, b"ours");
+    }
+
+    #[test]
+    fn orphaned_locks_from_the_same_user_can_be_removed() {
+        let path = temporary_file("orphaned.jpg");
+        let lock = lock_path(&path).unwrap();
+        let orphan = format!("{} (pid 0)", crate::oplog::current_user());
+        fs::write(&lock, &orphan).unwrap();
+
+        let problem = WriteProblem::Locked { holder: orphan };
+        assert_eq!(problem.assist(), Some(WriteAssist::RemoveLock));
+        apply_assist(&path, WriteAssist::RemoveLock).unwrap();
+        let removed = !lock.exists();
+        fs::remove_file(&path).ok();
+        assert!(removed);
+    }
+}
@@ -0,0 +1,750 @@
+use crate::jpeg;
+use exif::{Context, In, Tag, Value};
+use std::borrow::Cow;
+
+/// TIFF's XMP tag.
+const TIFF_XMP: Tag = Tag(Context::Tiff, 700);
+const PNG_XMP_KEYWORD: &str = "XML:com.adobe.xmp";
+
+/// Locates the first XMP packet embedded anywhere in a file.
+///
+/// XMP is plain UTF-8 text wrapped in `x:xmpmeta`, so a byte search finds it
+/// in JPEG APP1 segments, TIFF tag 700, PNG iTXt chunks and sidecar files alike.
+pub fn packet(data: &[u8]) -> Option<&str> {
+    const START: &[u8] = b"<x:xmpmeta";
+    const END: &[u8] = b"</x:xmpmeta>";
+    let start = find(data, START)?;
+    let end = start + find(&data[start..], END)? + END.len();
+    std::str::from_utf8(&data[start..end]).ok()
+}
+
+/// The XMP packet a file's container declares: the JPEG XMP segment, the
+/// PNG `XML:com.adobe.xmp` iTXt chunk (which may be compressed), the WebP
+/// `XMP ` chunk, the HEIF XMP item or TIFF tag 700, falling back to
+/// [`packet`] for other files.
+pub fn container_packet(data: &[u8]) -> Option<Cow<'_, str>> {
+    if jpeg::is_jpeg(data) {
+        if let Some(packet) = jpeg_packet(data).and_then(|payload| packet(payload.as_bytes())) {
+            return Some(Cow::Borrowed(packet));
+        }
+    } else if data.starts_with(&crate::PNG_SIGNATURE) {
+        let chunk = crate::parse_png_text_chunks(data)
+            .into_iter()
+            .find(|field| field.tag == PNG_XMP_KEYWORD);
+        if let Some(packet) =
+            chunk.and_then(|field| Some(packet(field.value.as_bytes())?.to_string()))
+        {
+            return Some(Cow::Owned(packet));
+        }
+    } else if crate::webp::is_webp(data) {
+        if let Some(packet) = crate::webp::xmp(data).and_then(packet) {
+            return Some(Cow::Borrowed(packet));
+        }
+    } else if let Some(packet) = crate::heif::xmp(data)
+        .as_deref()
+        .and_then(packet)
+        .map(str::to_string)
+    {
+        return Some(Cow::Owned(packet));
+    } else if let Some(exif) = crate::read_exif_container(data) {
+        let bytes = exif
+            .get_field(TIFF_XMP, In::PRIMARY)
+            .and_then(|field| match &field.value {
+                Value::Byte(bytes) | Value::Undefined(bytes, _) => Some(bytes.as_slice()),
+                _ => None,
+            });
+        if let Some(packet) = bytes.and_then(packet) {
+            return Some(Cow::Owned(packet.to_string()));
+        }
+    }
+    packet(data).map(Cow::Borrowed)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Token<'a> {
+    /// An element's name and the whole start tag, which is `empty` when it
+    /// closes itself.
+    Start {
+        name: &'a str,
+        tag: &'a str,
+        empty: bool,
+    },
+    End(&'a str),
+    Text(&'a str),
+}
+
+fn tokens(xml: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut rest = xml;
+    while let Some(open) = rest.find('<') {
+        let text = rest[..open].trim();
+        if !text.is_empty() {
+            tokens.push(Token::Text(text));
+        }
+        let after = &rest[open + 1..];
+        let end_marker = if after.starts_with("!--") {
+            "-->"
+        } else if after.starts_with('?') {
+            "?>"
+        } else {
+            ">"
+        };
+        let Some(end) = after.find(end_marker) else {
+            break;
+        };
+        let tag = &after[..end];
+        rest = &after[end + end_marker.len()..];
+        if tag.starts_with(['!', '?']) {
+            continue;
+        }
+        if let Some(name) = tag.strip_prefix('/') {
+            tokens.push(Token::End(name.trim()));
+            continue;
+        }
+        let name = &tag[..tag
+            .find(|c: char| c.is_whitespace() || c == '/')
+            .unwrap_or(tag.len())];
+        tokens.push(Token::Start {
+            name,
+            tag,
+            empty: tag.ends_with('/'),
+        });
+    }
+    tokens
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Walks the RDF of a packet, naming nested values by their path.
+struct RdfWalker<'a> {
+    tokens: Vec<Token<'a>>,
+    at: usize,
+    found: Vec<(String, String)>,
+}
+
+impl<'a> RdfWalker<'a> {
+    fn next(&mut self) -> Option<Token<'a>> {
+        let token = self.tokens.get(self.at).copied();
+        self.at += 1;
+        token
+    }
+
+    fn push_attributes(&mut self, path: Option<&str>, tag: &str) {
+        for (name, value) in attributes(tag) {
+            let name = match path {
+                Some(path) => format!("{}/{}", path, name),
+                None => name.to_string(),
+            };
+            self.found.push((name, unescape(value)));
+        }
+    }
+
+    /// Reads up to the end of `element`, recording structure fields and
+    /// array entries under `path`. Returns the text of a simple value, or
+    /// the joined items of an array of simple values.
+    fn body(&mut self, path: &str, element: &str) -> Option<String> {
+        let mut text = None;
+        while let Some(token) = self.next() {
+            match token {
+                Token::End(name) if name == element => break,
+                Token::End(_) => {}
+                Token::Text(value) => text = Some(unescape(value)),
+                Token::Start { name, tag, empty } => match name {
+                    "rdf:Bag" | "rdf:Seq" | "rdf:Alt" if !empty => {
+                        text = self.array(path, name);
+                    }
+                    "rdf:Description" => {
+                        self.push_attributes(Some(path), tag);
+                        if !empty {
+                            self.body(path, name);
+                        }
+                    }
+                    _ => self.property(&format!("{}/{}", path, name), name, tag, empty),
+                },
+            }
+        }
+        text
+    }
+
+    fn array(&mut self, path: &str, kind: &str) -> Option<String> {
+        let mut items: Vec<(bool, String)> = Vec::new();
+        let mut index = 0;
+        while let Some(token) = self.next() {
+            match token {
+                Token::End(name) if name == kind => break,
+                Token::Start {
+                    name: "rdf:li",
+                    tag,
+                    empty,
+                } => {
+                    index += 1;
+                    let item = format!("{}[{}]", path, index);
+                    self.push_attributes(Some(&item), tag);
+                    if !empty {
+                        if let Some(value) = self.body(&item, "rdf:li") {
+                            items.push((tag.contains("xml:lang=\"x-default\""), value));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        if kind == "rdf:Alt" {
+            let default = items.iter().position(|(default, _)| *default).unwrap_or(0);
+            return (!items.is_empty()).then(|| items.swap_remove(default).1);
+        }
+        (!items.is_empty()).then(|| {
+            items
+                .into_iter()
+                .map(|(_, value)| value)
+                .collect::<Vec<_>>()
+                .join("; ")
+        })
+    }
+
+    fn property(&mut self, path: &str, element: &str, tag: &str, empty: bool) {
+        if let Some(resource) = attribute(tag, "rdf:resource") {
+            self.found.push((path.to_string(), unescape(resource)));
+        }
+        self.push_attributes(Some(path), tag);
+        if !empty {
+            if let Some(value) = self.body(path, element) {
+                self.found.push((path.to_string(), value));
+            }
+        }
+    }
+}
+
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let start = tag.find(&format!("{}=\"", name))? + name.len() + 2;
+    tag[start..].find('"').map(|end| &tag[start..start + end])
+}
+
+/// Every property of `packet` with its value. Structure fields are named
+/// by path (`exif:Flash/exif:Fired`), array items of structures by index
+/// (`Iptc4xmpExt:LocationShown[1]/Iptc4xmpExt:City`), arrays of simple
+/// values are joined with `; ` and language alternatives give `x-default`.
+pub fn properties(packet: &str) -> Vec<(String, String)> {
+    let mut walker = RdfWalker {
+        tokens: tokens(packet),
+        at: 0,
+        found: Vec::new(),
+    };
+    while let Some(token) = walker.next() {
+        if let Token::Start {
+            name: "rdf:Description",
+            tag,
+            empty,
+        } = token
+        {
+            walker.push_attributes(None, tag);
+            if empty {
+                continue;
+            }
+            while let Some(token) = walker.next() {
+                match token {
+                    Token::End("rdf:Description") => break,
+                    Token::Start { name, tag, empty } => walker.property(name, name, tag, empty),
+                    _ => {}
+                }
+            }
+        }
+    }
+    walker.found
+}
+
+/// Reads a simple property written either as an attribute (`crs:Temperature="5600"`)
+/// or as an element (`<crs:Temperature>5600</crs:Temperature>`).
+pub fn property<'a>(packet: &'a str, name: &str) -> Option<&'a str> {
+    let attribute = format!("{}=\"", name);
+    if let Some(start) = packet.find(&attribute) {
+        let value = &packet[start + attribute.len()..];
+        return value.find('"').map(|end| &value[..end]);
+    }
+
+    let open = format!("<{}>", name);
+    let close = format!("</{}>", name);
+    let start = packet.find(&open)? + open.len();
+    let end = start + packet[start..].find(&close)?;
+    Some(packet[start..end].trim())
+}
+
+/// Reads the `rdf:li` entries of an array property such as `dc:creator`
+/// (`rdf:Seq`), `dc:subject` (`rdf:Bag`) or `dc:description` (`rdf:Alt`).
+/// Alternatives keep their file order, so `x-default` normally comes first.
+pub fn items<'a>(packet: &'a str, name: &str) -> Vec<&'a str> {
+    let open = format!("<{}>", name);
+    let close = format!("</{}>", name);
+    let Some(start) = packet.find(&open).map(|start| start + open.len()) else {
+        return Vec::new();
+    };
+    let Some(end) = packet[start..].find(&close).map(|end| start + end) else {
+        return Vec::new();
+    };
+
+    let mut items = Vec::new();
+    let mut rest = &packet[start..end];
+    while let Some(tag) = rest.find("<rdf:li") {
+        let Some(body) = rest[tag..].find('>').map(|body| tag + body + 1) else {
+            break;
+        };
+        let Some(stop) = rest[body..].find("</rdf:li>").map(|stop| body + stop) else {
+            break;
+        };
+        let value = rest[body..stop].trim();
+        if !value.is_empty() {
+            items.push(value);
+        }
+        rest = &rest[stop..];
+    }
+    items
+}
+
+/// Reads the structures of an array property such as
+/// `Iptc4xmpExt:LocationShown`, each as its `(field, value)` pairs. Fields
+/// may be attributes of the `rdf:li` or child elements, and a language
+/// alternative gives its first entry. A property holding a single
+/// structure instead of an array yields that one structure.
+pub fn structs<'a>(packet: &'a str, name: &str) -> Vec<Vec<(&'a str, &'a str)>> {
+    let open = format!("<{}", name);
+    let close = format!("</{}>", name);
+    let Some(start) = packet.match_indices(&open).find_map(|(at, _)| {
+        let rest = &packet[at + open.len()..];
+        rest.starts_with(['>', ' ', '\n', '\t', '\r'])
+            .then(|| Some(at + open.len() + rest.find('>')? + 1))
+            .flatten()
+    }) else {
+        return Vec::new();
+    };
+    let Some(end) = packet[start..].find(&close).map(|end| start + end) else {
+        return Vec::new();
+    };
+    let body = &packet[start..end];
+    let items = top_level_items(body);
+    if items.is_empty() {
+        let fields = struct_fields(body);
+        return if fields.is_empty() {
+            Vec::new()
+        } else {
+            vec![fields]
+        };
+    }
+    items
+        .into_iter()
+        .map(|(tag, inner)| {
+            let mut fields = attributes(tag);
+            fields.extend(struct_fields(inner));
+            fields
+        })
+        .filter(|fields| !fields.is_empty())
+        .collect()
+}
+
+/// The `rdf:li` start tags and contents directly inside `body`, skipping
+/// the items of nested arrays.
+fn top_level_items(body: &str) -> Vec<(&str, &str)> {
+    let mut items = Vec::new();
+    let mut depth = 0usize;
+    let mut open: Option<(usize, usize)> = None;
+    let mut at = 0;
+    while let Some(offset) = body[at..].find("rdf:li") {
+        let position = at + offset;
+        let Some(tag_end) = body[position..].find('>').map(|end| position + end) else {
+            break;
+        };
+        at = tag_end + 1;
+        if body[..position].ends_with("</") {
+            depth = depth.saturating_sub(1);
+            if depth == 0 {
+                if let Some((tag, inner)) = open.take() {
+                    items.push((&body[tag..inner - 1], &body[inner..position - 2]));
+                }
+            }
+        } else if body[..position].ends_with('<') {
+            let self_closing = body[..tag_end].ends_with('/');
+            if depth == 0 && self_closing {
+                items.push((&body[position..tag_end], ""));
+            } else if depth == 0 {
+                open = Some((position, tag_end + 1));
+            }
+            if !self_closing {
+                depth += 1;
+            }
+        }
+    }
+    items
+}
+
+/// `name="value"` pairs of a start tag, leaving out RDF and XML plumbing.
+fn attributes(tag: &str) -> Vec<(&str, &str)> {
+    let mut found = Vec::new();
+    let mut rest = tag;
+    while let Some(equals) = rest.find("=\"") {
+        let name_start = rest[..equals]
+            .trim_end_matches(|c: char| c.is_ascii_alphanumeric() || matches!(c, ':' | '_' | '-'))
+            .len();
+        let name = &rest[name_start..equals];
+        let value = &rest[equals + 2..];
+        let Some(value_end) = value.find('"') else {
+            break;
+        };
+        if name.contains(':') && !name.starts_with("rdf:") && !name.starts_with("xml") {
+            found.push((name, &value[..value_end]));
+        }
+        rest = &value[value_end + 1..];
+    }
+    found
+}
+
+/// Child elements of a structure, plus the attributes of an `rdf:Description`
+/// wrapping them.
+fn struct_fields(inner: &str) -> Vec<(&str, &str)> {
+    let mut fields = Vec::new();
+    let mut rest = inner;
+    while let Some(open) = rest.find('<') {
+        let after = &rest[open + 1..];
+        let Some(tag_end) = after.find('>') else {
+            break;
+        };
+        let tag = &after[..tag_end];
+        let name = &tag[..tag
+            .find(|c: char| c.is_whitespace() || c == '/')
+            .unwrap_or(tag.len())];
+        if tag.starts_with(['/', '?', '!']) || name.starts_with("rdf:") || tag.ends_with('/') {
+            fields.extend(attributes(tag));
+            rest = &after[tag_end + 1..];
+            continue;
+        }
+        let body = &after[tag_end + 1..];
+        let close = format!("</{}>", name);
+        let Some(end) = body.find(&close) else {
+            break;
+        };
+        let value = match top_level_items(&body[..end]).first() {
+            Some((_, first)) => first.trim(),
+            None => body[..end].trim(),
+        };
+        if !value.is_empty() && !value.contains('<') {
+            fields.push((name, value));
+        }
+        rest = &body[end + close.len()..];
+    }
+    fields
+}
+
+/// The APP1 header that marks a JPEG segment as holding XMP.
+pub const JPEG_HEADER: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+const EMPTY_PACKET: &str = r#"<x:xmpmeta xmlns:x="adobe:ns:meta/"><rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#"></rdf:RDF></x:xmpmeta>"#;
+/// Namespaces of the properties the app writes.
+const NAMESPACES: [(&str, &str); 6] = [
+    ("dc", "http://purl.org/dc/elements/1.1/"),
+    ("exif", "http://ns.adobe.com/exif/1.0/"),
+    ("Iptc4xmpExt", "http://iptc.org/std/Iptc4xmpExt/2008-02-29/"),
+    ("xmpDM", "http://ns.adobe.com/xmp/1.0/DynamicMedia/"),
+    ("photoshop", "http://ns.adobe.com/photoshop/1.0/"),
+    ("tiff", "http://ns.adobe.com/tiff/1.0/"),
+];
+/// Written properties whose type is a language alternative.
+const LANG_ALT: [&str; 2] = ["dc:description", "Iptc4xmpExt:Event"];
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// `packet` without any copy of `name`, whether attribute or element.
+fn remove_property(packet: &str, name: &str) -> String {
+    let mut out = packet.to_string();
+    let attribute = format!("{}=\"", name);
+    let mut from = 0;
+    while let Some(start) = out[from..].find(&attribute).map(|at| from + at) {
+        let value = start + attribute.len();
+        let Some(end) = out[value..].find('"').map(|end| value + end + 1) else {
+            break;
+        };
+        if out[..start].ends_with(char::is_whitespace) {
+            out.replace_range(out[..start].trim_end().len()..end, "");
+            from = 0;
+        } else {
+            from = end;
+        }
+    }
+    let close = format!("</{}>", name);
+    let mut from = 0;
+    while let Some(start) = out[from..].find(&format!("<{}", name)).map(|at| from + at) {
+        let after = &out[start + 1 + name.len()..];
+        let Some(tag_end) = after.find('>') else {
+            break;
+        };
+        if !after.starts_with(['>', '/', ' ', '\n', '\t', '\r']) {
+            from = start + 1;
+            continue;
+        }
+        let end = if after[..tag_end].ends_with('/') {
+            start + 1 + name.len() + tag_end + 1
+        } else {
+            match out[start..].find(&close) {
+                Some(close_at) => start + close_at + close.len(),
+                None => break,
+            }
+        };
+        out.replace_range(out[..start].trim_end().len()..end, "");
+        from = 0;
+    }
+    out
+}
+
+/// `packet` without any of the `names` properties.
+pub fn without_properties(packet: &str, names: &[&str]) -> String {
+    names.iter().fold(packet.to_string(), |packet, name| {
+        remove_property(&packet, name)
+    })
+}
+
+/// `packet`, or a new one, with each `(name, value)` property set. Earlier
+/// copies are removed and the new values go in their own `rdf:Description`.
+pub fn with_properties(
+    packet: Option<&str>,
+    properties: &[(&str, &str)],
+) -> Result<String, String> {
+    let elements: Vec<(&str, String)> = properties
+        .iter()
+        .map(|(name, value)| {
+            let value = escape(value);
+            let content = match LANG_ALT.contains(name) {
+                true => format!(
+                    "<rdf:Alt><rdf:li xml:lang=\"x-default\">{}</rdf:li></rdf:Alt>",
+                    value
+                ),
+                false => value,
+            };
+            (*name, content)
+        })
+        .collect();
+    with_elements(packet, &elements)
+}
+
+/// Like [`with_properties`] for an unordered list, such as the people shown.
+pub fn with_bag(packet: Option<&str>, name: &str, items: &[&str]) -> Result<String, String> {
+    let items: String = items
+        .iter()
+        .map(|item| format!("<rdf:li>{}</rdf:li>", escape(item)))
+        .collect();
+    with_elements(packet, &[(name, format!("<rdf:Bag>{}</rdf:Bag>", items))])
+}
+
+/// Sets each `(name, content)` element, `content` being XML already.
+fn with_elements(packet: Option<&str>, elements: &[(&str, String)]) -> Result<String, String> {
+    let mut packet = packet.unwrap_or(EMPTY_PACKET).to_string();
+    for (name, _) in elements {
+        packet = remove_property(&packet, name);
+    }
+    let mut description = String::from("<rdf:Description rdf:about=\"\"");
+    for (prefix, uri) in NAMESPACES {
+        if elements
+            .iter()
+            .any(|(name, _)| name.split(':').next() == Some(prefix))
+        {
+            description.push_str(&format!(" xmlns:{}=\"{}\"", prefix, uri));
+        }
+    }
+    description.push('>');
+    for (name, content) in elements {
+        description.push_str(&format!("<{0}>{1}</{0}>", name, content));
+    }
+    description.push_str("</rdf:Description>");
+    let end = packet
+        .rfind("</rdf:RDF>")
+        .ok_or_else(|| "The file's XMP packet is malformed.".to_string())?;
+    packet.insert_str(end, &description);
+    Ok(packet)
+}
+
+/// The XMP packet of a JPEG's own XMP segment, wrapper included.
+pub fn jpeg_packet(data: &[u8]) -> Option<&str> {
+    let (segments, _) = jpeg::segments(data).ok()?;
+    segments.iter().find_map(|segment| {
+        let payload = segment.payload(data);
+        (segment.marker == jpeg::APP1 && payload.starts_with(JPEG_HEADER))
+            .then(|| std::str::from_utf8(&payload[JPEG_HEADER.len()..]).ok())?
+    })
+}
+
+/// `data` with its XMP segment replaced by `packet`, or a new one added
+/// after the JFIF and EXIF segments.
+pub fn embed_in_jpeg(data: &[u8], packet: &str) -> Result<Vec<u8>, String> {
+    let (segments, _) = jpeg::segments(data)?;
+    let mut payload = JPEG_HEADER.to_vec();
+    payload.extend_from_slice(packet.as_bytes());
+    let segment = jpeg::encode_segment(jpeg::APP1, &payload)?;
+    let existing = segments.iter().find(|segment| {
+        segment.marker == jpeg::APP1 && segment.payload(data).starts_with(JPEG_HEADER)
+    });
+    let (start, end) = match existing {
+        Some(existing) => (existing.start, existing.end),
+        None => {
+            let after = segments
+                .iter()
+                .take_while(|segment| segment.marker == jpeg::APP0 || segment.is_exif(data))
+                .last()
+                .map_or(jpeg::SOI.len(), |segment| segment.end);
+            (after, after)
+        }
+    };
+    let mut updated = data[..start].to_vec();
+    updated.extend(segment);
+    updated.extend_from_slice(&data[end..]);
+    Ok(updated)
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn properties_are_read_from_attributes_and_elements() {
+        let mut data = b"\xFF\xD8\xFF\xE1junk".to_vec();
+        data.extend_from_slice(
+            br#"<x:xmpmeta xmlns:x="adobe:ns:meta/"><rdf:Description crs:Temperature="5600">
+<crs:Tint> +4 </crs:Tint></rdf:Description></x:xmpmeta>"#,
+        );
+        data.extend_from_slice(b"\xFF\xD9");
+
+        let packet = packet(&data).expect("packet should be found");
+        assert!(packet.ends_with("</x:xmpmeta>"));
+        assert_eq!(property(packet, "crs:Temperature"), Some("5600"));
+        assert_eq!(property(packet, "crs:Tint"), Some("+4"));
+        assert_eq!(property(packet, "crs:Exposure2012"), None);
+    }
+
+    #[test]
+    fn every_property_is_named_by_its_path() {
+        let packet = r#"<?xpacket begin=""?><x:xmpmeta x:xmptk="XMP Core"><rdf:RDF>
+<rdf:Description rdf:about="" xmp:Rating="4" lr:Label="Red &amp; Blue"/>
+<rdf:Description rdf:about="">
+<dc:subject><rdf:Bag><rdf:li>surf</rdf:li><rdf:li>beach</rdf:li></rdf:Bag></dc:subject>
+<dc:title><rdf:Alt><rdf:li xml:lang="pt">Praia</rdf:li><rdf:li xml:lang="x-default">Beach</rdf:li></rdf:Alt></dc:title>
+<exif:Flash rdf:parseType="Resource"><exif:Fired>False</exif:Fired></exif:Flash>
+<Iptc4xmpExt:LocationShown><rdf:Bag><rdf:li Iptc4xmpExt:City="Lisbon"/></rdf:Bag></Iptc4xmpExt:LocationShown>
+<xmpMM:DerivedFrom stRef:documentID="abc"/>
+</rdf:Description></rdf:RDF></x:xmpmeta>"#;
+        let properties = properties(packet);
+        let found: Vec<(&str, &str)> = properties
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect();
+        let expected = [
+            ("xmp:Rating", "4"),
+            ("lr:Label", "Red & Blue"),
+            ("dc:subject", "surf; beach"),
+            ("dc:title", "Beach"),
+            ("exif:Flash/exif:Fired", "False"),
+            ("Iptc4xmpExt:LocationShown[1]/Iptc4xmpExt:City", "Lisbon"),
+            ("xmpMM:DerivedFrom/stRef:documentID", "abc"),
+        ];
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn compressed_png_packets_are_found() {
+        use flate2::{write::ZlibEncoder, Compression};
+        use std::io::Write;
+
+        let xmp = r#"<x:xmpmeta><rdf:RDF><rdf:Description xmp:Rating="5"/></rdf:RDF></x:xmpmeta>"#;
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(xmp.as_bytes()).unwrap();
+        let mut chunk = format!("{}\0\x01\0\0\0", PNG_XMP_KEYWORD).into_bytes();
+        chunk.extend(encoder.finish().unwrap());
+
+        let mut png = crate::PNG_SIGNATURE.to_vec();
+        png.extend_from_slice(&(chunk.len() as u32).to_be_bytes());
+        png.extend_from_slice(b"iTXt");
+        png.extend_from_slice(&chunk);
+        png.extend_from_slice(&[0; 4]);
+        assert_eq!(container_packet(&png).as_deref(), Some(xmp));
+
+        let fields = crate::collect_fields_from_bytes(&png).unwrap();
+        assert!(fields
+            .iter()
+            .any(|field| field.ifd == "XMP" && field.tag == "xmp:Rating" && field.value == "5"));
+    }
+
+    #[test]
+    fn array_items_are_listed_in_order() {
+        let packet = r#"<x:xmpmeta><rdf:Description>
+<dc:creator><rdf:Seq><rdf:li>Ana</rdf:li><rdf:li> Ben </rdf:li></rdf:Seq></dc:creator>
+<dc:description><rdf:Alt><rdf:li xml:lang="x-default">Pier</rdf:li></rdf:Alt></dc:description>
+</rdf:Description></x:xmpmeta>"#;
+        assert_eq!(items(packet, "dc:creator"), ["Ana", "Ben"]);
+        assert_eq!(items(packet, "dc:description"), ["Pier"]);
+        assert!(items(packet, "dc:subject").is_empty());
+    }
+
+    #[test]
+    fn structures_are_read_from_attributes_and_elements() {
+        let packet = r#"<x:xmpmeta><rdf:Description>
+<Iptc4xmpExt:LocationShown><rdf:Bag>
+<rdf:li Iptc4xmpExt:City="Lisbon" Iptc4xmpExt:CountryCode="PT"/>
+<rdf:li rdf:parseType="Resource"><Iptc4xmpExt:City>Porto</Iptc4xmpExt:City>
+<Iptc4xmpExt:LocationName><rdf:Alt><rdf:li xml:lang="x-default">Ribeira</rdf:li></rdf:Alt></Iptc4xmpExt:LocationName></rdf:li>
+</rdf:Bag></Iptc4xmpExt:LocationShown>
+</rdf:Description></x:xmpmeta>"#;
+        assert_eq!(
+            structs(packet, "Iptc4xmpExt:LocationShown"),
+            [
+                vec![
+                    ("Iptc4xmpExt:City", "Lisbon"),
+                    ("Iptc4xmpExt:CountryCode", "PT")
+                ],
+                vec![
+                    ("Iptc4xmpExt:City", "Porto"),
+                    ("Iptc4xmpExt:LocationName", "Ribeira")
+                ],
+            ]
+        );
+        assert!(structs(packet, "Iptc4xmpExt:Location").is_empty());
+    }
+
+    #[test]
+    fn written_properties_replace_earlier_copies() {
+        let jpeg = crate::fixtures::jpeg_from_fields(&[crate::fixtures::ascii_field(
+            exif::Tag::Model,
+            "EOS R5",
+        )]);
+        let packet = with_properties(None, &[("xmpDM:album", "Trips")]).unwrap();
+        let data = embed_in_jpeg(&jpeg, &packet).unwrap();
+        assert_eq!(
+            property(jpeg_packet(&data).unwrap(), "xmpDM:album"),
+            Some("Trips")
+        );
+
+        let packet = with_properties(
+            jpeg_packet(&data),
+            &[
+                ("xmpDM:album", "Travel"),
+                ("Iptc4xmpExt:Event", "Porto & Gaia"),
+            ],
+        )
+        .unwrap();
+        let data = embed_in_jpeg(&data, &packet).unwrap();
+        let packet = jpeg_packet(&data).unwrap();
+        assert_eq!(packet.matches("xmpDM:album>").count(), 2);
+        assert_eq!(property(packet, "xmpDM:album"), Some("Travel"));
+        assert_eq!(items(packet, "Iptc4xmpExt:Event"), ["Porto &amp; Gaia"]);
+        assert!(crate::read_exif_container(&data).is_some());
+    }
+}